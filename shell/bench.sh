@@ -0,0 +1,8 @@
+#!/bin/sh
+
+# Runs the hot path benchmarks on a synthetic repo of the given size.
+# Usage: shell/bench.sh [files] [commits] [contributors]
+# Compare the output with stackmuncher_lib/benches/BASELINE.md before and after a change.
+
+STM_BENCH_FILES=${1:-500} STM_BENCH_COMMITS=${2:-1000} STM_BENCH_CONTRIBUTORS=${3:-10} \
+  cargo bench -p stackmuncher_lib --bench hot_paths
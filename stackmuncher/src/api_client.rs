@@ -0,0 +1,311 @@
+use crate::http_client::new_https_client;
+use crate::signing::ReportSignature;
+use hyper::body::Bytes;
+use hyper::{Method, Request, StatusCode};
+use ring::signature::Ed25519KeyPair;
+use serde::de::DeserializeOwned;
+use std::fmt;
+use std::time::Duration;
+use tracing::{debug, info, warn};
+
+/// The header with the base58 public key of the user for signed requests.
+const HEADER_USER_PUB_KEY: &str = "stackmuncher_key";
+/// The header with the base58 signature of the request body for signed requests.
+const HEADER_USER_SIGNATURE: &str = "stackmuncher_sig";
+/// Some APIs, e.g. GitHub, reject requests without a user agent.
+const USER_AGENT: &str = "StackMuncher App";
+/// The header with a key that lets the server drop repeats of the same request, e.g. a retried submission.
+const HEADER_IDEMPOTENCY_KEY: &str = "Idempotency-Key";
+/// Network errors and 5xx responses are retried up to this many attempts in total.
+const MAX_ATTEMPTS: u32 = 3;
+/// The delay before the first retry. It doubles with every attempt.
+const RETRY_DELAY_MS: u64 = 1000;
+/// Longer bodies are not logged or added to error messages.
+const MAX_LOGGED_BODY_BYTES: usize = 3000;
+
+/// Why a request to a web service did not produce the expected response.
+#[derive(Debug)]
+pub(crate) enum ApiError {
+    /// The connection could not be established, so the server never got the request, e.g. no network.
+    Connect(String),
+    /// The request failed after it may have reached the server, e.g. a timeout, or the response body could not be read.
+    Network(String),
+    /// The server responded with a status other than 2xx.
+    Status(ApiResponse),
+    /// A 2xx response with a body that is not the expected JSON.
+    Decode(String),
+}
+
+impl fmt::Display for ApiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Connect(e) | Self::Network(e) => write!(f, "{}", e),
+            Self::Status(response) => write!(f, "{}", response.summary()),
+            Self::Decode(e) => write!(f, "invalid response: {}", e),
+        }
+    }
+}
+
+impl ApiError {
+    /// Network errors, throttling and server errors may go away if the request is repeated.
+    pub(crate) fn is_retryable(&self) -> bool {
+        match self {
+            Self::Connect(_) | Self::Network(_) => true,
+            Self::Status(response) => {
+                response.status.is_server_error() || response.status == StatusCode::TOO_MANY_REQUESTS
+            }
+            Self::Decode(_) => false,
+        }
+    }
+
+    /// Returns true if the server did not act on the request, so that it can be repeated even if it is not idempotent.
+    /// A timeout or a 5xx response may come after the server has already acted on it.
+    pub(crate) fn is_not_processed(&self) -> bool {
+        match self {
+            Self::Connect(_) => true,
+            Self::Status(response) => response.status == StatusCode::TOO_MANY_REQUESTS,
+            Self::Network(_) | Self::Decode(_) => false,
+        }
+    }
+}
+
+/// The status and the entire body of a response.
+#[derive(Debug)]
+pub(crate) struct ApiResponse {
+    pub status: StatusCode,
+    pub body: Bytes,
+}
+
+impl ApiResponse {
+    /// Returns the response as-is if the status is 2xx or an error otherwise.
+    pub(crate) fn error_for_status(self) -> Result<Self, ApiError> {
+        if self.status.is_success() {
+            Ok(self)
+        } else {
+            Err(ApiError::Status(self))
+        }
+    }
+
+    /// Decodes the JSON body into the expected response type.
+    pub(crate) fn json<T: DeserializeOwned>(&self) -> Result<T, ApiError> {
+        serde_json::from_slice::<T>(&self.body).map_err(|e| ApiError::Decode(e.to_string()))
+    }
+
+    /// Returns the body as text if it is short enough to be logged or shown to the user.
+    pub(crate) fn body_text(&self) -> Option<String> {
+        if self.body.is_empty() || self.body.len() > MAX_LOGGED_BODY_BYTES {
+            None
+        } else {
+            Some(String::from_utf8_lossy(&self.body).trim().to_owned())
+        }
+    }
+
+    /// Returns the status with the body, if any, for the audit log, e.g. `400 Bad Request: invalid signature`.
+    pub(crate) fn summary(&self) -> String {
+        match self.body_text() {
+            Some(body) => format!("{}: {}", self.status, body),
+            None if self.body.is_empty() => self.status.to_string(),
+            None => format!("{}, {} bytes", self.status, self.body.len()),
+        }
+    }
+
+    /// Logs the body as info!(), if possible.
+    pub(crate) fn log_body(&self) {
+        match self.body_text() {
+            Some(body) => info!("Response body: {}", body),
+            None if self.body.is_empty() => info!("Empty response body."),
+            None => info!("Response is too long to log: {}B", self.body.len()),
+        }
+    }
+}
+
+/// A request to STM Inbox, GitHub API or any other web service with retries for transient failures.
+/// POST requests are only retried if the server did not get them, unless they have an idempotency key.
+/// ```ignore
+/// let gist = ApiRequest::get(&uri).header("Accept", "application/json").send().await?.json::<RawGist>()?;
+/// ```
+pub(crate) struct ApiRequest {
+    method: Method,
+    uri: String,
+    headers: Vec<(&'static str, String)>,
+    body: Vec<u8>,
+    max_attempts: u32,
+    /// The request can be repeated after any transient failure without doing the same thing twice.
+    idempotent: bool,
+}
+
+impl ApiRequest {
+    pub(crate) fn get(uri: &str) -> Self {
+        Self::new(Method::GET, uri, Vec::new())
+    }
+
+    pub(crate) fn post(uri: &str, body: Vec<u8>) -> Self {
+        Self::new(Method::POST, uri, body)
+    }
+
+    pub(crate) fn put(uri: &str, body: Vec<u8>) -> Self {
+        Self::new(Method::PUT, uri, body)
+    }
+
+    fn new(method: Method, uri: &str, body: Vec<u8>) -> Self {
+        Self {
+            idempotent: method != Method::POST,
+            method,
+            uri: uri.to_owned(),
+            headers: vec![("User-Agent", USER_AGENT.to_owned())],
+            body,
+            max_attempts: MAX_ATTEMPTS,
+        }
+    }
+
+    pub(crate) fn header(mut self, name: &'static str, value: &str) -> Self {
+        self.headers.push((name, value.to_owned()));
+        self
+    }
+
+    /// Adds a key that is the same for all repeats of the request, e.g. a hash of the body, for the server to drop
+    /// the repeats. Such a request is retried after any transient failure.
+    pub(crate) fn idempotency_key(mut self, key: &str) -> Self {
+        self.idempotent = true;
+        self.header(HEADER_IDEMPOTENCY_KEY, key)
+    }
+
+    /// Signs the body with the user's key and adds the public key and the signature as headers.
+    pub(crate) fn signed(self, user_key_pair: &Ed25519KeyPair) -> Self {
+        let sig = ReportSignature::sign(&self.body, user_key_pair);
+        self.header(HEADER_USER_PUB_KEY, &sig.public_key)
+            .header(HEADER_USER_SIGNATURE, &sig.signature)
+    }
+
+    /// Sends the request and reads the entire response. Only 2xx responses are returned as Ok.
+    /// Network errors and 5xx responses are retried with an increasing delay.
+    pub(crate) async fn send(&self) -> Result<ApiResponse, ApiError> {
+        let mut attempt = 1;
+        loop {
+            let result = self.send_once().await.and_then(|response| response.error_for_status());
+            match result {
+                Err(e) if self.can_retry(&e) && attempt < self.max_attempts => {
+                    let delay = RETRY_DELAY_MS * 2u64.pow(attempt - 1);
+                    warn!("{} {} failed with {}. Retrying in {}ms.", self.method, self.uri, e, delay);
+                    tokio::time::sleep(Duration::from_millis(delay)).await;
+                    attempt += 1;
+                }
+                _ => return result,
+            }
+        }
+    }
+
+    /// Returns true if the request can be repeated after failing with `e`.
+    fn can_retry(&self, e: &ApiError) -> bool {
+        e.is_retryable() && (self.idempotent || e.is_not_processed())
+    }
+
+    /// A single attempt with no status check.
+    async fn send_once(&self) -> Result<ApiResponse, ApiError> {
+        let mut req = Request::builder().method(self.method.clone()).uri(&self.uri);
+        for (name, value) in &self.headers {
+            req = req.header(*name, value);
+        }
+        let req = req
+            .body(hyper::Body::from(self.body.clone()))
+            .map_err(|e| ApiError::Network(format!("Invalid request: {}", e)))?;
+        debug!("Http rq: {:?}", req);
+
+        let res = new_https_client().request(req).await.map_err(|e| {
+            if e.is_connect() {
+                ApiError::Connect(format!("Request failed: {}", e))
+            } else {
+                ApiError::Network(format!("Request failed: {}", e))
+            }
+        })?;
+
+        let status = res.status();
+        debug!("Response status: {}", status);
+
+        let body = hyper::body::to_bytes(res)
+            .await
+            .map_err(|e| ApiError::Network(format!("{}, the body cannot be read: {}", status, e)))?;
+
+        Ok(ApiResponse { status, body })
+    }
+}
+
+#[cfg(test)]
+mod test_api_client {
+    use super::{ApiError, ApiRequest, ApiResponse};
+    use hyper::body::Bytes;
+    use hyper::StatusCode;
+    use serde::Deserialize;
+
+    #[derive(Deserialize, Debug, PartialEq)]
+    struct Owner {
+        login: Option<String>,
+    }
+
+    fn response(status: u16, body: &'static str) -> ApiResponse {
+        ApiResponse {
+            status: StatusCode::from_u16(status).unwrap(),
+            body: Bytes::from(body),
+        }
+    }
+
+    #[test]
+    fn test_json() {
+        let owner = response(200, r#"{"login":"rimutaka","id":1}"#).json::<Owner>().unwrap();
+        assert_eq!(owner.login, Some("rimutaka".to_owned()));
+
+        assert!(matches!(response(200, "<html>").json::<Owner>(), Err(ApiError::Decode(_))));
+        assert!(matches!(response(200, "").json::<Owner>(), Err(ApiError::Decode(_))));
+    }
+
+    #[test]
+    fn test_error_for_status() {
+        assert!(response(200, "").error_for_status().is_ok());
+
+        let e = response(400, "invalid signature\n").error_for_status().unwrap_err();
+        assert_eq!(e.to_string(), "400 Bad Request: invalid signature");
+        assert!(!e.is_retryable());
+
+        assert!(response(503, "").error_for_status().unwrap_err().is_retryable());
+        assert!(response(429, "").error_for_status().unwrap_err().is_retryable());
+        assert!(ApiError::Network("Request failed: timeout".to_owned()).is_retryable());
+        assert!(!ApiError::Decode("EOF".to_owned()).is_retryable());
+    }
+
+    #[test]
+    fn test_can_retry() {
+        let timeout = ApiError::Network("Request failed: timeout".to_owned());
+        let connect = ApiError::Connect("Request failed: connection refused".to_owned());
+        let server_error = response(500, "").error_for_status().unwrap_err();
+        let throttled = response(429, "").error_for_status().unwrap_err();
+
+        // the server may have accepted a POST that timed out or failed with 5xx
+        let post = ApiRequest::post("https://inbox.example.com", Vec::new());
+        assert!(!post.can_retry(&timeout));
+        assert!(!post.can_retry(&server_error));
+        assert!(post.can_retry(&connect));
+        assert!(post.can_retry(&throttled));
+
+        // unless the server can drop the repeats
+        let post = post.idempotency_key("a1b2");
+        assert!(post.can_retry(&timeout));
+        assert!(post.can_retry(&server_error));
+
+        let get = ApiRequest::get("https://api.github.com");
+        assert!(get.can_retry(&timeout));
+        assert!(!get.can_retry(&ApiError::Decode("EOF".to_owned())));
+    }
+
+    #[test]
+    fn test_summary() {
+        assert_eq!(response(200, "").summary(), "200 OK");
+        assert_eq!(response(404, " Not here ").summary(), "404 Not Found: Not here");
+
+        let long_body = ApiResponse {
+            status: StatusCode::BAD_GATEWAY,
+            body: Bytes::from(vec![b'x'; 5000]),
+        };
+        assert_eq!(long_body.summary(), "502 Bad Gateway, 5000 bytes");
+        assert!(long_body.body_text().is_none());
+    }
+}
@@ -1,6 +1,10 @@
-use crate::help;
+use crate::{help, report_sink};
+use chrono::{NaiveDate, Utc};
 use pico_args;
 use regex::Regex;
+use stackmuncher_lib::bench::{SyntheticRepoSpec, DEFAULT_MAX_REGRESSION_PCT};
+use stackmuncher_lib::report::WorkdayOverlap;
+use std::collections::BTreeMap;
 use std::env::consts::EXE_SUFFIX;
 use std::str::FromStr;
 use std::{path::PathBuf, process::exit};
@@ -9,7 +13,7 @@ use tracing::debug;
 pub(crate) const GIST_ID_REGEX: &str = "[a-f0-9]{32}";
 
 /// List of valid app commands
-#[derive(PartialEq)]
+#[derive(PartialEq, Clone, Copy, Debug)]
 pub(crate) enum AppArgCommands {
     /// The default value
     Munch,
@@ -23,27 +27,454 @@ pub(crate) enum AppArgCommands {
     DeleteProfile,
     /// Configure Github validation page
     GitGHubConfig,
+    /// Combine local project reports into summaries, e.g. per org
+    Rollup,
+    /// Benchmark the hot paths on a synthetic repo. Hidden from the general help.
+    Bench,
+    /// Stay resident and re-munch the watched projects when new commits appear
+    Watch,
+    /// Export the dependency graph of the project files from the project report
+    Graph,
+    /// Export or import the key-pair and cached identities to submit under one profile from several machines
+    Identity,
+    /// Print the local log of report submissions
+    Audit,
+    /// Print the last accepted submission per project and the profile URLs
+    Status,
+    /// Validate file-type and muncher rules
+    Rules,
+    /// Print sections of the project report from the last analysis, e.g. hotspots
+    Show,
+    /// Save the config, the key and all local reports in a single archive for moving them to another machine
+    ExportData,
+    /// Restore the config, the key and the reports from an archive made by `export-data`
+    ImportData,
+    /// Merge the local reports of all projects into a single cross-project profile or update the profile details
+    Profile,
+    /// Check the timestamp token of a report against the report
+    Verify,
+    /// Analyze a sample project in a temp folder and explain the results
+    Demo,
+    /// Analyze the project as it was at regular intervals in the past to show how the stack changed
+    Timeline,
+    /// Export the libraries used by the project from the project report as a CycloneDX or SPDX SBOM
+    Sbom,
+    /// Check the config, the key and the cached reports for damage and optionally remove the damaged files
+    Doctor,
+    /// Print a summary of the combined contributor report for pasting into a resume or a profile README
+    Export,
 }
 
+/// What `rules` command does
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub(crate) enum RulesAction {
+    /// Compile all rules and print errors, muncher hashes and file extension mappings
+    Check,
+    /// Run a muncher over sample files and compare the counts with the expected ones from the sidecar files
+    Test,
+}
+
+impl FromStr for RulesAction {
+    type Err = AppArgsError;
+    /// Returns a parsed value or an error with the list of valid options.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "check" => Ok(Self::Check),
+            "test" => Ok(Self::Test),
+            _ => Err(AppArgsError::usage(format!(
+                "STACKMUNCHER CONFIG ERROR: invalid action `{}` for `rules` command. Valid actions: check, test.",
+                s
+            ))),
+        }
+    }
+}
+
+/// What `identity` command does with the identity bundle
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub(crate) enum IdentityAction {
+    /// Save the key-pair and identities of this machine in an encrypted file
+    Export,
+    /// Replace the key-pair of this machine with the one from the file and merge the identities
+    Import,
+}
+
+impl FromStr for IdentityAction {
+    type Err = AppArgsError;
+    /// Returns a parsed value or an error with the list of valid options.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "export" => Ok(Self::Export),
+            "import" => Ok(Self::Import),
+            _ => Err(AppArgsError::usage(format!(
+                "STACKMUNCHER CONFIG ERROR: invalid action `{}` for `identity` command. Valid actions: export, import.",
+                s
+            ))),
+        }
+    }
+}
+
+/// What `profile` command does instead of merging the local reports
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub(crate) enum ProfileAction {
+    /// Save the public details of the Directory Profile in the config and print them
+    Set,
+}
+
+impl FromStr for ProfileAction {
+    type Err = AppArgsError;
+    /// Returns a parsed value or an error with the list of valid options.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "set" => Ok(Self::Set),
+            _ => Err(AppArgsError::usage(format!(
+                "STACKMUNCHER CONFIG ERROR: invalid action `{}` for `profile` command. Valid actions: set.",
+                s
+            ))),
+        }
+    }
+}
+
+/// How `rollup` command groups the local project reports
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub(crate) enum RollupGroupBy {
+    /// All projects are combined into a single summary
+    None,
+    /// Projects are grouped by the org or namespace of their git remote, e.g. `github.com/rust-lang`
+    Org,
+}
+
+impl FromStr for RollupGroupBy {
+    type Err = AppArgsError;
+    /// Returns a parsed value or an error with the list of valid options.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "" | "none" => Ok(Self::None),
+            "org" => Ok(Self::Org),
+            _ => Err(AppArgsError::usage(format!(
+                "STACKMUNCHER CONFIG ERROR: invalid value `{}` for `--group-by`. Valid values: org, none.",
+                s
+            ))),
+        }
+    }
+}
+
+/// The output format of `graph` command
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub(crate) enum GraphFormat {
+    /// Graphviz DOT
+    Dot,
+    /// Nodes and edges as JSON
+    Json,
+}
+
+impl FromStr for GraphFormat {
+    type Err = AppArgsError;
+    /// Returns a parsed value or an error with the list of valid options.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "" | "dot" | "gv" => Ok(Self::Dot),
+            "json" => Ok(Self::Json),
+            _ => Err(AppArgsError::usage(format!(
+                "STACKMUNCHER CONFIG ERROR: invalid value `{}` for `--format`. Valid values: dot, json.",
+                s
+            ))),
+        }
+    }
+}
+
+/// The output format of `sbom` command
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub(crate) enum SbomFormat {
+    /// CycloneDX 1.5 JSON
+    CycloneDx,
+    /// SPDX 2.3 JSON
+    Spdx,
+}
+
+impl FromStr for SbomFormat {
+    type Err = AppArgsError;
+    /// Returns a parsed value or an error with the list of valid options.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "" | "cyclonedx" | "cdx" => Ok(Self::CycloneDx),
+            "spdx" => Ok(Self::Spdx),
+            _ => Err(AppArgsError::usage(format!(
+                "STACKMUNCHER CONFIG ERROR: invalid value `{}` for `--format`. Valid values: cyclonedx, spdx.",
+                s
+            ))),
+        }
+    }
+}
+
+/// The output format of `export` command
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub(crate) enum ExportFormat {
+    /// A compact Markdown summary
+    Markdown,
+}
+
+impl FromStr for ExportFormat {
+    type Err = AppArgsError;
+    /// Returns a parsed value or an error with the list of valid options.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "" | "md" | "markdown" => Ok(Self::Markdown),
+            _ => Err(AppArgsError::usage(format!(
+                "STACKMUNCHER CONFIG ERROR: invalid value `{}` for `--format`. Valid values: md.",
+                s
+            ))),
+        }
+    }
+}
+
+/// The format of the log output
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub(crate) enum LogFormat {
+    /// Human-readable lines
+    Text,
+    /// One JSON object per line with span fields for ingestion into ELK and similar
+    Json,
+}
+
+impl FromStr for LogFormat {
+    type Err = AppArgsError;
+    /// Returns a parsed value or an error with the list of valid options.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "" | "text" => Ok(Self::Text),
+            "json" => Ok(Self::Json),
+            _ => Err(AppArgsError::usage(format!(
+                "STACKMUNCHER CONFIG ERROR: invalid value `{}` for `--log-format`. Valid values: text, json.",
+                s
+            ))),
+        }
+    }
+}
+
+/// Canonical names of all CLI params. Alternative spellings are normalized to these names before validation.
+const PARAM_DRYRUN: &str = "--dryrun";
+const PARAM_FULL_HISTORY: &str = "--full-history";
+const PARAM_SHOW_WHAT_IS_SENT: &str = "--show-what-is-sent";
+const PARAM_PRIMARY_EMAIL: &str = "--primary_email";
+const PARAM_PUBLIC_NAME: &str = "--public_name";
+const PARAM_HEADLINE: &str = "--headline";
+const PARAM_PUBLIC_CONTACT: &str = "--public_contact";
+const PARAM_AVAILABLE: &str = "--available";
+const PARAM_LOCATION: &str = "--location";
+/// Params `profile set` command can change. They are only valid with `set` action.
+const PROFILE_DETAIL_PARAMS: [&str; 5] = [
+    PARAM_PUBLIC_NAME,
+    PARAM_HEADLINE,
+    PARAM_PUBLIC_CONTACT,
+    PARAM_AVAILABLE,
+    PARAM_LOCATION,
+];
+const PARAM_EMAILS: &str = "--emails";
+const PARAM_ALIAS: &str = "--alias";
+const PARAM_EXCLUDE_EMAILS: &str = "--exclude-emails";
+const PARAM_EXCLUDE_PATHS: &str = "--exclude-paths";
+const PARAM_GIST: &str = "--gist";
+const PARAM_PROJECT: &str = "--project";
+const PARAM_GITDIR: &str = "--gitdir";
+const PARAM_REPORTS: &str = "--reports";
+const PARAM_CONFIG: &str = "--config";
+const PARAM_LOG: &str = "--log";
+const PARAM_LOG_FORMAT: &str = "--log-format";
+const PARAM_LOG_RAW: &str = "--log-raw";
+const PARAM_LOG_FILE: &str = "--log-file";
+const PARAM_GROUP_BY: &str = "--group-by";
+const PARAM_FILES: &str = "--files";
+const PARAM_COMMITS: &str = "--commits";
+const PARAM_CONTRIBUTORS: &str = "--contributors";
+const PARAM_BASELINE: &str = "--baseline";
+const PARAM_MAX_REGRESSION: &str = "--max-regression";
+const PARAM_INTERVAL: &str = "--interval";
+const PARAM_FORMAT: &str = "--format";
+const PARAM_OUT: &str = "--out";
+const PARAM_EXTERNAL: &str = "--external";
+const PARAM_INBOX_URL: &str = "--inbox-url";
+const PARAM_FILE: &str = "--file";
+const PARAM_LAST: &str = "--last";
+const PARAM_RULES: &str = "--rules";
+const PARAM_HOTSPOTS: &str = "--hotspots";
+const PARAM_MODULES: &str = "--modules";
+const PARAM_TRACE_FILE: &str = "--trace-file";
+const PARAM_WORKDAY: &str = "--workday";
+const PARAM_TIMEZONES: &str = "--timezones";
+const PARAM_ENCRYPT_KEY: &str = "--encrypt-key";
+const PARAM_TIMESTAMP_URL: &str = "--timestamp-url";
+const PARAM_REPORT_SINK: &str = "--report-sink";
+const PARAM_AS_OF: &str = "--as-of";
+const PARAM_REF: &str = "--ref";
+const PARAM_WAIT: &str = "--wait";
+const PARAM_FIX: &str = "--fix";
+
+/// The max length of `--public_name` value in characters.
+const MAX_PUBLIC_NAME_LENGTH: usize = 100;
+/// The max length of `--headline` value in characters.
+const MAX_HEADLINE_LENGTH: usize = 200;
+/// The max length of `--public_contact` value in characters.
+const MAX_PUBLIC_CONTACT_LENGTH: usize = 200;
+/// The max length of `--location` value in characters.
+const MAX_LOCATION_LENGTH: usize = 100;
+
+/// How often `watch` command checks the projects for new commits, in minutes.
+const DEFAULT_WATCH_INTERVAL_MINUTES: usize = 5;
+
+/// Months between the snapshots of `timeline` command.
+const DEFAULT_TIMELINE_INTERVAL_MONTHS: usize = 12;
+
 /// A container for user-provided CLI commands and params. The names of the members correspond
 /// to the names of CLI args. E.g. --emails -> emails
 pub(crate) struct AppArgs {
     pub command: AppArgCommands,
+    /// The command the user asked help for, e.g. `stackmuncher config --help` or `stackmuncher help config`.
+    /// Only set if `command` is `Help`. None means the general help message.
+    pub help_topic: Option<AppArgCommands>,
     pub dryrun: bool,
+    /// Fetch the missing history of a shallow clone before munching
+    pub full_history: bool,
+    /// Wait for another instance of the app to finish with the project instead of exiting with an error
+    pub wait: bool,
+    /// Remove or set aside the damaged files found by `doctor` command
+    pub fix: bool,
+    /// Save the exact submission payload for review
+    pub show_what_is_sent: bool,
     pub primary_email: Option<String>,
+    /// The name shown in the Directory Profile. An empty string removes it.
+    pub public_name: Option<String>,
+    /// A one-line summary shown in the Directory Profile, e.g. `Backend engineer - Rust/Go`. An empty string removes it.
+    pub headline: Option<String>,
+    /// A URL or an email shown in the Directory Profile. An empty string removes it. Only used by `profile set` command.
+    pub public_contact: Option<String>,
+    /// Open to job offers. Only used by `profile set` command.
+    pub available: Option<bool>,
+    /// A free-form location, e.g. `Berlin, Germany`. An empty string removes it. Only used by `profile set` command.
+    pub location: Option<String>,
     pub emails: Option<Vec<String>>,
+    /// Commit emails to merge into other emails, e.g. `old@x.com=new@y.com` -> (`old@x.com`, `new@y.com`).
+    /// An empty new email removes the alias. An empty map removes all aliases.
+    pub aliases: Option<BTreeMap<String, String>>,
+    /// Commit emails left out of the combined report, e.g. a shared identity on a pairing workstation.
+    /// Replaces the cached list. An empty list removes all of them.
+    pub exclude_emails: Option<Vec<String>>,
+    /// Regex strings for file paths left out of the contributor reports. Replaces the cached list. An empty list removes all of them.
+    pub exclude_paths: Option<Vec<String>>,
+    /// The URL of a self-hosted submission endpoint. An empty string resets it to the default.
+    pub inbox_url: Option<String>,
+    /// The URL of an RFC3161 timestamping authority for report hashes. An empty string turns timestamping off.
+    pub timestamp_url: Option<String>,
+    /// Where to save copies of the project reports, e.g. `s3://bucket/prefix`. Only used by `munch` and `watch` commands.
+    pub report_sink: Option<String>,
     /// A 32-byte long hex string of the Gist ID with the validation string for the user's GH account
     /// E.g. `fb8fc0f87ee78231f064131022c8154a`
     pub gh_validation_id: Option<String>,
     pub project: Option<PathBuf>,
+    /// The git folder of the project if its `.git` file points at a folder that was moved, e.g. the main repo of a worktree.
+    pub git_dir: Option<PathBuf>,
     pub reports: Option<PathBuf>,
     pub config: Option<PathBuf>,
     pub log: Option<tracing::Level>,
+    /// Text or JSON log output. Applies to all commands.
+    pub log_format: LogFormat,
+    /// Turns off the redaction of emails, absolute paths and secrets in the log output.
+    pub log_raw: bool,
+    /// A local file for an unredacted copy of the log output.
+    pub log_file: Option<PathBuf>,
+    /// Only used by `rollup` command.
+    pub group_by: RollupGroupBy,
+    /// The size of the synthetic repo. Only used by `bench` command.
+    pub bench_spec: SyntheticRepoSpec,
+    /// Timings recorded with `bench --out` to check the new timings against. The size of the synthetic repo
+    /// is taken from the baseline. Only used by `bench` command.
+    pub bench_baseline: Option<PathBuf>,
+    /// How many percent slower than the baseline a hot path may get. Only used by `bench` command.
+    pub bench_max_regression: usize,
+    /// Minutes between checks for new commits. Only used by `watch` command.
+    pub watch_interval: usize,
+    /// Analyze the project as it was on this date instead of HEAD, e.g. `2023-06-01`. Only used by `munch` command.
+    pub as_of: Option<NaiveDate>,
+    /// A branch, tag or commit to analyze instead of HEAD, e.g. `release/1.2`. Only used by `munch` command.
+    pub git_ref: Option<String>,
+    /// Months between the snapshots. Only used by `timeline` command, which reads it from `--interval`.
+    pub timeline_interval: usize,
+    /// Only used by `graph` command.
+    pub graph_format: GraphFormat,
+    /// The file to save the graph, the SBOM, the summary or the benchmark baseline into.
+    /// Only used by `graph`, `sbom`, `export` and `bench` commands.
+    /// Prints to stdout if None.
+    /// `export-data` command saves `--out` in `data_file` instead.
+    pub graph_out: Option<PathBuf>,
+    /// Add external packages to the graph. Only used by `graph` command.
+    pub graph_external: bool,
+    /// Only used by `sbom` command, which reads it from `--format`.
+    pub sbom_format: SbomFormat,
+    /// Only used by `export` command, which reads it from `--format`.
+    pub export_format: ExportFormat,
+    /// Print the hotspots section. Only used by `show` command, which requires at least one section.
+    pub show_hotspots: bool,
+    /// Print the most central files from the import graph. Only used by `show` command.
+    pub show_modules: bool,
+    /// A file or a folder relative to the project root to print the evaluation stages for, e.g. `src/main.rs`.
+    pub trace_file: Option<String>,
+    /// Only used by `identity` command, where it is required.
+    pub identity_action: Option<IdentityAction>,
+    /// The identity bundle file. Only used by `identity` command. Defaults to a file in the current folder if None.
+    pub identity_file: Option<PathBuf>,
+    /// The data archive set with `--out` for `export-data` or `--file` for `import-data`.
+    /// Defaults to a file in the current folder if None.
+    pub data_file: Option<PathBuf>,
+    /// Protect the key in the data archive with a passphrase. Only used by `export-data` command.
+    pub encrypt_key: bool,
+    /// The report to check the timestamp of. Only used by `verify` command. Defaults to the combined project report if None.
+    pub verify_file: Option<PathBuf>,
+    /// The number of the most recent entries to print. Only used by `audit` command. Prints all entries if None.
+    pub audit_last: Option<usize>,
+    /// Only used by `rules` command, where it is required.
+    pub rules_action: Option<RulesAction>,
+    /// The name of the muncher to test, e.g. `rust.rs`. Only used by `rules test`, where it is required.
+    pub rules_muncher: Option<String>,
+    /// The folder with the sample files and their `.expected.json` sidecars. Only used by `rules test`, where it is required.
+    pub rules_sample_dir: Option<PathBuf>,
+    /// Only used by `profile` command. The local reports are merged into a profile if None.
+    pub profile_action: Option<ProfileAction>,
+    /// A folder with `file_types` and `munchers` sub-folders. Only used by `rules` command. The embedded rules are used if None.
+    pub rules_dir: Option<PathBuf>,
+    /// The working day as (start hour, end hour) for calculating the timezone overlap, e.g. `9-17` -> (9, 17).
+    pub workday: Option<(u32, u32)>,
+    /// IANA timezone names for calculating the working hours overlap. An empty list resets it to all UTC offsets.
+    pub timezones: Option<Vec<String>>,
+}
+
+/// A CLI parsing error with a message for the user and the help section that should be printed after it.
+pub(crate) struct AppArgsError {
+    /// The error message printed to stderr as-is.
+    pub msg: String,
+    /// Prints the follow up help, e.g. `help::emit_usage_msg`.
+    pub help: fn(),
+}
+
+impl AppArgsError {
+    /// An error followed by the standard usage message.
+    fn usage(msg: String) -> Self {
+        Self {
+            msg,
+            help: help::emit_usage_msg,
+        }
+    }
+
+    /// An error followed by the explanation of where the reports are stored.
+    fn report_dir(msg: String) -> Self {
+        Self {
+            msg,
+            help: help::emit_report_dir_msg,
+        }
+    }
 }
 
 impl FromStr for AppArgCommands {
-    type Err = ();
-    /// Returns a parsed value or prints an error message and exits.
+    type Err = AppArgsError;
+    /// Returns a parsed value or an error with the invalid command name.
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         let command = s.trim().to_lowercase();
         let command = match command.as_str() {
@@ -53,10 +484,26 @@ impl FromStr for AppArgCommands {
             "makeanon" | "make-anon" | "make_anon" => Self::MakeAnon,
             "deleteprofile" | "delete-profile" | "delete_profile" | "delete" => Self::DeleteProfile,
             "github" => Self::GitGHubConfig,
+            "rollup" => Self::Rollup,
+            "bench" => Self::Bench,
+            "watch" => Self::Watch,
+            "graph" => Self::Graph,
+            "identity" => Self::Identity,
+            "audit" => Self::Audit,
+            "status" => Self::Status,
+            "rules" => Self::Rules,
+            "show" => Self::Show,
+            "exportdata" | "export-data" | "export_data" => Self::ExportData,
+            "importdata" | "import-data" | "import_data" => Self::ImportData,
+            "profile" => Self::Profile,
+            "verify" => Self::Verify,
+            "demo" => Self::Demo,
+            "timeline" => Self::Timeline,
+            "sbom" => Self::Sbom,
+            "doctor" => Self::Doctor,
+            "export" => Self::Export,
             _ => {
-                eprintln!("STACKMUNCHER CONFIG ERROR: invalid command `{}`", command);
-                help::emit_usage_msg();
-                exit(1);
+                return Err(AppArgsError::usage(format!("STACKMUNCHER CONFIG ERROR: invalid command `{}`", command)));
             }
         };
 
@@ -64,72 +511,506 @@ impl FromStr for AppArgCommands {
     }
 }
 
+impl AppArgCommands {
+    /// Returns the name of the command as it is typed in the CLI.
+    pub(crate) fn cli_name(&self) -> &'static str {
+        match self {
+            Self::Munch => "munch",
+            Self::Help => "help",
+            Self::ViewConfig => "config",
+            Self::MakeAnon => "makeanon",
+            Self::DeleteProfile => "delete",
+            Self::GitGHubConfig => "github",
+            Self::Rollup => "rollup",
+            Self::Bench => "bench",
+            Self::Watch => "watch",
+            Self::Graph => "graph",
+            Self::Identity => "identity",
+            Self::Audit => "audit",
+            Self::Status => "status",
+            Self::Rules => "rules",
+            Self::Show => "show",
+            Self::ExportData => "export-data",
+            Self::ImportData => "import-data",
+            Self::Profile => "profile",
+            Self::Verify => "verify",
+            Self::Demo => "demo",
+            Self::Timeline => "timeline",
+            Self::Sbom => "sbom",
+            Self::Doctor => "doctor",
+            Self::Export => "export",
+        }
+    }
+
+    /// Returns the list of params accepted by the command. Any other params are rejected with an error.
+    /// `--log`, `--config` and `--reports` are accepted by all commands because they affect the app config.
+    fn valid_params(&self) -> &'static [&'static str] {
+        match self {
+            Self::Munch => &[
+                PARAM_DRYRUN,
+                PARAM_FULL_HISTORY,
+                PARAM_WAIT,
+                PARAM_SHOW_WHAT_IS_SENT,
+                PARAM_PRIMARY_EMAIL,
+                PARAM_PUBLIC_NAME,
+                PARAM_HEADLINE,
+                PARAM_EMAILS,
+                PARAM_ALIAS,
+                PARAM_EXCLUDE_EMAILS,
+                PARAM_EXCLUDE_PATHS,
+                PARAM_GIST,
+                PARAM_INBOX_URL,
+                PARAM_TIMESTAMP_URL,
+                PARAM_REPORT_SINK,
+                PARAM_WORKDAY,
+                PARAM_TIMEZONES,
+                PARAM_PROJECT,
+                PARAM_GITDIR,
+                PARAM_TRACE_FILE,
+                PARAM_AS_OF,
+                PARAM_REF,
+                PARAM_REPORTS,
+                PARAM_CONFIG,
+                PARAM_LOG,
+                PARAM_LOG_FORMAT,
+                PARAM_LOG_RAW,
+                PARAM_LOG_FILE,
+            ],
+            Self::ViewConfig => &[
+                PARAM_PRIMARY_EMAIL,
+                PARAM_PUBLIC_NAME,
+                PARAM_HEADLINE,
+                PARAM_EMAILS,
+                PARAM_ALIAS,
+                PARAM_EXCLUDE_EMAILS,
+                PARAM_EXCLUDE_PATHS,
+                PARAM_GIST,
+                PARAM_INBOX_URL,
+                PARAM_TIMESTAMP_URL,
+                PARAM_WORKDAY,
+                PARAM_TIMEZONES,
+                PARAM_REPORTS,
+                PARAM_CONFIG,
+                PARAM_LOG,
+                PARAM_LOG_FORMAT,
+                PARAM_LOG_RAW,
+                PARAM_LOG_FILE,
+            ],
+            Self::GitGHubConfig => &[
+                PARAM_GIST,
+                PARAM_REPORTS,
+                PARAM_CONFIG,
+                PARAM_LOG,
+                PARAM_LOG_FORMAT,
+                PARAM_LOG_RAW,
+                PARAM_LOG_FILE,
+            ],
+            Self::Rollup => &[
+                PARAM_GROUP_BY,
+                PARAM_WORKDAY,
+                PARAM_TIMEZONES,
+                PARAM_REPORTS,
+                PARAM_CONFIG,
+                PARAM_LOG,
+                PARAM_LOG_FORMAT,
+                PARAM_LOG_RAW,
+                PARAM_LOG_FILE,
+            ],
+            Self::Bench => &[
+                PARAM_FILES,
+                PARAM_COMMITS,
+                PARAM_CONTRIBUTORS,
+                PARAM_BASELINE,
+                PARAM_MAX_REGRESSION,
+                PARAM_OUT,
+                PARAM_REPORTS,
+                PARAM_CONFIG,
+                PARAM_LOG,
+                PARAM_LOG_FORMAT,
+                PARAM_LOG_RAW,
+                PARAM_LOG_FILE,
+            ],
+            Self::Watch => &[
+                PARAM_DRYRUN,
+                PARAM_FULL_HISTORY,
+                PARAM_WAIT,
+                PARAM_INBOX_URL,
+                PARAM_TIMESTAMP_URL,
+                PARAM_REPORT_SINK,
+                PARAM_PROJECT,
+                PARAM_INTERVAL,
+                PARAM_REPORTS,
+                PARAM_CONFIG,
+                PARAM_LOG,
+                PARAM_LOG_FORMAT,
+                PARAM_LOG_RAW,
+                PARAM_LOG_FILE,
+            ],
+            Self::Graph => &[
+                PARAM_FORMAT,
+                PARAM_OUT,
+                PARAM_EXTERNAL,
+                PARAM_PROJECT,
+                PARAM_GITDIR,
+                PARAM_REPORTS,
+                PARAM_CONFIG,
+                PARAM_LOG,
+                PARAM_LOG_FORMAT,
+                PARAM_LOG_RAW,
+                PARAM_LOG_FILE,
+            ],
+            Self::Sbom => &[
+                PARAM_FORMAT,
+                PARAM_OUT,
+                PARAM_PROJECT,
+                PARAM_GITDIR,
+                PARAM_REPORTS,
+                PARAM_CONFIG,
+                PARAM_LOG,
+                PARAM_LOG_FORMAT,
+                PARAM_LOG_RAW,
+                PARAM_LOG_FILE,
+            ],
+            Self::Export => &[
+                PARAM_FORMAT,
+                PARAM_OUT,
+                PARAM_REF,
+                PARAM_PROJECT,
+                PARAM_GITDIR,
+                PARAM_REPORTS,
+                PARAM_CONFIG,
+                PARAM_LOG,
+                PARAM_LOG_FORMAT,
+                PARAM_LOG_RAW,
+                PARAM_LOG_FILE,
+            ],
+            Self::Identity => &[
+                PARAM_FILE,
+                PARAM_REPORTS,
+                PARAM_CONFIG,
+                PARAM_LOG,
+                PARAM_LOG_FORMAT,
+                PARAM_LOG_RAW,
+                PARAM_LOG_FILE,
+            ],
+            Self::Audit => &[
+                PARAM_LAST,
+                PARAM_REPORTS,
+                PARAM_CONFIG,
+                PARAM_LOG,
+                PARAM_LOG_FORMAT,
+                PARAM_LOG_RAW,
+                PARAM_LOG_FILE,
+            ],
+            Self::ExportData => &[
+                PARAM_OUT,
+                PARAM_ENCRYPT_KEY,
+                PARAM_REPORTS,
+                PARAM_CONFIG,
+                PARAM_LOG,
+                PARAM_LOG_FORMAT,
+                PARAM_LOG_RAW,
+                PARAM_LOG_FILE,
+            ],
+            Self::ImportData => &[
+                PARAM_FILE,
+                PARAM_REPORTS,
+                PARAM_CONFIG,
+                PARAM_LOG,
+                PARAM_LOG_FORMAT,
+                PARAM_LOG_RAW,
+                PARAM_LOG_FILE,
+            ],
+            Self::Verify => &[
+                PARAM_FILE,
+                PARAM_PROJECT,
+                PARAM_GITDIR,
+                PARAM_REPORTS,
+                PARAM_CONFIG,
+                PARAM_LOG,
+                PARAM_LOG_FORMAT,
+                PARAM_LOG_RAW,
+                PARAM_LOG_FILE,
+            ],
+            Self::Rules => &[
+                PARAM_RULES,
+                PARAM_REPORTS,
+                PARAM_CONFIG,
+                PARAM_LOG,
+                PARAM_LOG_FORMAT,
+                PARAM_LOG_RAW,
+                PARAM_LOG_FILE,
+            ],
+            Self::Show => &[
+                PARAM_HOTSPOTS,
+                PARAM_MODULES,
+                PARAM_PROJECT,
+                PARAM_GITDIR,
+                PARAM_REPORTS,
+                PARAM_CONFIG,
+                PARAM_LOG,
+                PARAM_LOG_FORMAT,
+                PARAM_LOG_RAW,
+                PARAM_LOG_FILE,
+            ],
+            Self::Timeline => &[
+                PARAM_INTERVAL,
+                PARAM_WAIT,
+                PARAM_EMAILS,
+                PARAM_ALIAS,
+                PARAM_EXCLUDE_EMAILS,
+                PARAM_EXCLUDE_PATHS,
+                PARAM_PROJECT,
+                PARAM_REPORTS,
+                PARAM_CONFIG,
+                PARAM_LOG,
+                PARAM_LOG_FORMAT,
+                PARAM_LOG_RAW,
+                PARAM_LOG_FILE,
+            ],
+            Self::Doctor => &[
+                PARAM_FIX,
+                PARAM_PROJECT,
+                PARAM_REPORTS,
+                PARAM_CONFIG,
+                PARAM_LOG,
+                PARAM_LOG_FORMAT,
+                PARAM_LOG_RAW,
+                PARAM_LOG_FILE,
+            ],
+            Self::Profile => &[
+                PARAM_PUBLIC_NAME,
+                PARAM_HEADLINE,
+                PARAM_PUBLIC_CONTACT,
+                PARAM_AVAILABLE,
+                PARAM_LOCATION,
+                PARAM_REPORTS,
+                PARAM_CONFIG,
+                PARAM_LOG,
+                PARAM_LOG_FORMAT,
+                PARAM_LOG_RAW,
+                PARAM_LOG_FILE,
+            ],
+            Self::Help | Self::MakeAnon | Self::DeleteProfile | Self::Demo | Self::Status => &[
+                PARAM_REPORTS,
+                PARAM_CONFIG,
+                PARAM_LOG,
+                PARAM_LOG_FORMAT,
+                PARAM_LOG_RAW,
+                PARAM_LOG_FILE,
+            ],
+        }
+    }
+}
+
 impl AppArgs {
     /// Read the CLI params from the environment and place them in `self`.
-    /// Uses None for omitted params.
+    /// Uses None for omitted params. Prints an error message and exits if the params are invalid.
     pub(crate) fn read_params() -> Self {
+        match Self::parse(pico_args::Arguments::from_env()) {
+            Ok(v) => v,
+            Err(e) => {
+                if !e.msg.is_empty() {
+                    eprintln!("{}", e.msg);
+                }
+                (e.help)();
+                exit(1);
+            }
+        }
+    }
+
+    /// Parses the CLI params from `pargs` and validates them against the subcommand.
+    /// Returns an error with a user-friendly message on the first invalid param.
+    pub(crate) fn parse(mut pargs: pico_args::Arguments) -> Result<Self, AppArgsError> {
         let mut app_args = AppArgs {
             command: AppArgCommands::Munch,
+            help_topic: None,
             dryrun: false,
+            full_history: false,
+            wait: false,
+            fix: false,
+            show_what_is_sent: false,
             primary_email: None,
+            public_name: None,
+            headline: None,
+            public_contact: None,
+            available: None,
+            location: None,
             emails: None,
+            aliases: None,
+            exclude_emails: None,
+            exclude_paths: None,
+            inbox_url: None,
+            timestamp_url: None,
+            report_sink: None,
             gh_validation_id: None,
             project: None,
+            git_dir: None,
             reports: None,
             config: None,
             log: None,
+            log_format: LogFormat::Text,
+            log_raw: false,
+            log_file: None,
+            group_by: RollupGroupBy::None,
+            bench_spec: SyntheticRepoSpec::default(),
+            bench_baseline: None,
+            bench_max_regression: DEFAULT_MAX_REGRESSION_PCT,
+            watch_interval: DEFAULT_WATCH_INTERVAL_MINUTES,
+            as_of: None,
+            git_ref: None,
+            timeline_interval: DEFAULT_TIMELINE_INTERVAL_MONTHS,
+            graph_format: GraphFormat::Dot,
+            graph_out: None,
+            graph_external: false,
+            sbom_format: SbomFormat::CycloneDx,
+            export_format: ExportFormat::Markdown,
+            show_hotspots: false,
+            show_modules: false,
+            trace_file: None,
+            identity_action: None,
+            identity_file: None,
+            data_file: None,
+            encrypt_key: false,
+            verify_file: None,
+            audit_last: None,
+            rules_action: None,
+            rules_muncher: None,
+            rules_sample_dir: None,
+            profile_action: None,
+            rules_dir: None,
+            workday: None,
+            timezones: None,
         };
 
-        // read the params into a parser
-        let mut pargs = pico_args::Arguments::from_env();
+        // canonical names of params found in the CLI for validating them against the command
+        let mut params_used: Vec<&'static str> = Vec::new();
 
         // process sub-command
+        let mut explicit_command = false;
         match pargs.subcommand() {
             Ok(v) => {
                 if let Some(command) = v {
-                    app_args.command =
-                        AppArgCommands::from_str(&command).expect("Failed to parse subcommand. It's a bug.");
+                    app_args.command = AppArgCommands::from_str(&command)?;
+                    explicit_command = true;
                 };
             }
             Err(_) => {
-                help::emit_cli_err_msg();
-                exit(1);
+                return Err(AppArgsError {
+                    msg: String::new(),
+                    help: help::emit_cli_err_msg,
+                });
             }
         };
 
         // help has a higher priority and should be handled separately
         if pargs.contains(["-h", "--help"]) {
+            // `stackmuncher --help` is the general help, `stackmuncher munch --help` is specific to munching
+            if explicit_command && app_args.command != AppArgCommands::Help {
+                app_args.help_topic = Some(app_args.command);
+            }
             app_args.command = AppArgCommands::Help;
         }
 
-        // --noupdate param with different misspellings
+        // --dryrun param with different misspellings
         app_args.dryrun = pargs.contains("--dryrun") || pargs.contains("--dry-run") || pargs.contains("--dry_run");
+        if app_args.dryrun {
+            params_used.push(PARAM_DRYRUN);
+        }
+
+        // --full-history
+        app_args.full_history = pargs.contains("--full-history") || pargs.contains("--full_history");
+        if app_args.full_history {
+            params_used.push(PARAM_FULL_HISTORY);
+        }
+
+        // --wait
+        app_args.wait = pargs.contains("--wait");
+        if app_args.wait {
+            params_used.push(PARAM_WAIT);
+        }
+
+        // --fix
+        app_args.fix = pargs.contains("--fix");
+        if app_args.fix {
+            params_used.push(PARAM_FIX);
+        }
+
+        // --show-what-is-sent
+        app_args.show_what_is_sent = pargs.contains("--show-what-is-sent") || pargs.contains("--show_what_is_sent");
+        if app_args.show_what_is_sent {
+            params_used.push(PARAM_SHOW_WHAT_IS_SENT);
+        }
 
         // --primary_email
         if let Some(primary_email) =
-            find_arg_value(&mut pargs, vec!["--primary_email", "--primary-email", "--primaryemail"])
+            find_arg_value(&mut pargs, vec!["--primary_email", "--primary-email", "--primaryemail"])?
         {
+            params_used.push(PARAM_PRIMARY_EMAIL);
             app_args.primary_email = Some(primary_email);
         };
 
+        // --public_name
+        if let Some(public_name) = find_arg_value(&mut pargs, vec!["--public_name", "--public-name", "--publicname"])? {
+            params_used.push(PARAM_PUBLIC_NAME);
+            app_args.public_name = Some(validate_max_length(public_name, PARAM_PUBLIC_NAME, MAX_PUBLIC_NAME_LENGTH)?);
+        };
+
+        // --headline
+        if let Some(headline) = find_arg_value(&mut pargs, vec!["--headline"])? {
+            params_used.push(PARAM_HEADLINE);
+            app_args.headline = Some(validate_max_length(headline, PARAM_HEADLINE, MAX_HEADLINE_LENGTH)?);
+        };
+
+        // --public_contact
+        if let Some(public_contact) =
+            find_arg_value(&mut pargs, vec!["--public_contact", "--public-contact", "--publiccontact"])?
+        {
+            params_used.push(PARAM_PUBLIC_CONTACT);
+            app_args.public_contact =
+                Some(validate_max_length(public_contact, PARAM_PUBLIC_CONTACT, MAX_PUBLIC_CONTACT_LENGTH)?);
+        };
+
+        // --available yes|no
+        if let Some(available) = find_arg_value(&mut pargs, vec!["--available"])? {
+            params_used.push(PARAM_AVAILABLE);
+            app_args.available = Some(parse_yes_no(&available, PARAM_AVAILABLE)?);
+        };
+
+        // --location
+        if let Some(location) = find_arg_value(&mut pargs, vec!["--location"])? {
+            params_used.push(PARAM_LOCATION);
+            app_args.location = Some(validate_max_length(location, PARAM_LOCATION, MAX_LOCATION_LENGTH)?);
+        };
+
         // emails are a comma-separated list and should be cleaned up from various forms like
         // a@example.com,,d@example.com,
         // "a@example.com d@example.com"
         // can be empty if the user wants the project report only and no contributor reports
-        if let Some(emails) = find_arg_value(&mut pargs, vec!["--emails"]) {
-            let emails = emails
-                .trim()
-                .to_lowercase()
-                .replace(" ", ",")
-                .split(",")
-                .filter_map(|v| if v.is_empty() { None } else { Some(v.to_owned()) })
-                .collect::<Vec<String>>();
+        if let Some(emails) = find_arg_value(&mut pargs, vec!["--emails"])? {
+            params_used.push(PARAM_EMAILS);
+            app_args.emails = Some(parse_email_list(&emails));
+        };
+
+        // aliases are a comma-separated list of old=new email pairs, e.g. old@x.com=new@y.com
+        if let Some(aliases) = find_arg_value(&mut pargs, vec!["--alias", "--aliases"])? {
+            params_used.push(PARAM_ALIAS);
+            app_args.aliases = Some(parse_aliases(&aliases)?);
+        };
 
-            app_args.emails = Some(emails);
+        // shared identities and paths to leave out of the combined report, in the same format as --emails
+        if let Some(emails) = find_arg_value(&mut pargs, vec!["--exclude-emails", "--exclude_emails"])? {
+            params_used.push(PARAM_EXCLUDE_EMAILS);
+            app_args.exclude_emails = Some(parse_email_list(&emails));
+        };
+        if let Some(paths) = find_arg_value(&mut pargs, vec!["--exclude-paths", "--exclude_paths"])? {
+            params_used.push(PARAM_EXCLUDE_PATHS);
+            app_args.exclude_paths = Some(parse_exclude_paths(&paths)?);
         };
 
         // --gist
-        if let Some(gist_url) = find_arg_value(&mut pargs, vec!["--gist"]) {
+        if let Some(gist_url) = find_arg_value(&mut pargs, vec!["--gist"])? {
+            params_used.push(PARAM_GIST);
             // extract the gist id from the input, which can be the full URL, just the ID or the raw URL which is even longer
             // e.g. fb8fc0f87ee78231f064131022c8154a
             // or https://gist.github.com/rimutaka/fb8fc0f87ee78231f064131022c8154a
@@ -147,65 +1028,129 @@ impl AppArgs {
                 app_args.gh_validation_id = Some(matches.as_str().to_string());
             } else {
                 // some other value was provided
-                eprintln!("STACKMUNCHER CONFIG ERROR: param `--gist` has an invalid value.",);
-                eprintln!();
-                eprintln!("    It accepts either the Gist URL or the Gist ID found in the Gist URL:",);
-                eprintln!("    * https://gist.github.com/rimutaka/fb8fc0f87ee78231f064131022c8154a");
-                eprintln!("    * fb8fc0f87ee78231f064131022c8154a");
-                eprintln!();
-                eprintln!("    To unlink from GitHub and remove private projects from your public profile use `stackmuncher{} --gist \"\"`", EXE_SUFFIX);
-                help::emit_usage_msg();
-                exit(1);
+                return Err(AppArgsError::usage([
+                    "STACKMUNCHER CONFIG ERROR: param `--gist` has an invalid value.\n\n",
+                    "    It accepts either the Gist URL or the Gist ID found in the Gist URL:\n",
+                    "    * https://gist.github.com/rimutaka/fb8fc0f87ee78231f064131022c8154a\n",
+                    "    * fb8fc0f87ee78231f064131022c8154a\n\n",
+                    "    To unlink from GitHub and remove private projects from your public profile use `stackmuncher",
+                    EXE_SUFFIX,
+                    " --gist \"\"`",
+                ]
+                .concat()));
             }
         };
 
+        // a self-hosted inbox, if any
+        if let Some(inbox_url) = find_arg_value(&mut pargs, vec!["--inbox-url", "--inbox_url", "--inboxurl"])? {
+            params_used.push(PARAM_INBOX_URL);
+            app_args.inbox_url = Some(validate_inbox_url(inbox_url)?);
+        };
+
+        // a timestamping authority for report hashes, if any
+        if let Some(timestamp_url) = find_arg_value(&mut pargs, vec!["--timestamp-url", "--timestamp_url"])? {
+            params_used.push(PARAM_TIMESTAMP_URL);
+            app_args.timestamp_url = Some(validate_timestamp_url(timestamp_url)?);
+        };
+
+        // a bucket, an HTTP endpoint or a folder for copies of the reports, e.g. s3://bucket/prefix
+        if let Some(report_sink) = find_arg_value(&mut pargs, vec!["--report-sink", "--report_sink", "--reportsink"])? {
+            params_used.push(PARAM_REPORT_SINK);
+            if let Err(e) = report_sink::validate_location(&report_sink) {
+                return Err(AppArgsError::usage(format!(
+                    "STACKMUNCHER CONFIG ERROR: `{}` is not a valid value for `--report-sink`: {}. Use `s3://bucket/prefix`, `https://example.com/reports` or a folder.",
+                    report_sink, e
+                )));
+            }
+            app_args.report_sink = Some(report_sink);
+        };
+
+        // the working day for the timezone overlap, e.g. 9-17
+        if let Some(workday) = find_arg_value(&mut pargs, vec!["--workday"])? {
+            params_used.push(PARAM_WORKDAY);
+            app_args.workday = Some(parse_workday(&workday)?);
+        };
+
+        // the timezones for the working hours overlap, e.g. Europe/Berlin,US/Pacific
+        if let Some(timezones) = find_arg_value(&mut pargs, vec!["--timezones", "--timezone", "--tz"])? {
+            params_used.push(PARAM_TIMEZONES);
+            app_args.timezones = Some(parse_timezones(&timezones)?);
+        };
+
+        // a file or folder in the project to explain what happens to, e.g. src/main.rs
+        if let Some(trace_file) = find_arg_value(&mut pargs, vec!["--trace-file", "--trace_file", "--tracefile"])? {
+            params_used.push(PARAM_TRACE_FILE);
+            // paths in the tree always use / as the separator
+            let trace_file = trace_file.trim().replace('\\', "/");
+            if trace_file.is_empty() {
+                return Err(AppArgsError::usage(
+                    "STACKMUNCHER CONFIG ERROR: param `--trace-file` has no value. It must be a file or a folder relative to the project root, e.g. `src/main.rs`, or `.` for all files.".to_owned(),
+                ));
+            }
+            app_args.trace_file = Some(trace_file);
+        };
+
         // project folder
-        if let Some(project) = find_arg_value(&mut pargs, vec!["--project", "-p"]) {
+        if let Some(project) = find_arg_value(&mut pargs, vec!["--project", "-p"])? {
+            params_used.push(PARAM_PROJECT);
             // en empty value doesn't make sense in this context
             if project.trim().is_empty() {
-                eprintln!(
-                    "STACKMUNCHER CONFIG ERROR: param `--project` has no value. Omit it to use the current folder or provide a valid path to where the project is located (absolute or relative).",
-                );
-                help::emit_usage_msg();
-                exit(1);
+                return Err(AppArgsError::usage(
+                    "STACKMUNCHER CONFIG ERROR: param `--project` has no value. Omit it to use the current folder or provide a valid path to where the project is located (absolute or relative).".to_owned(),
+                ));
             }
 
             // try to covert to path and expand ~/somepath on Linux to /home/user/...
             match PathBuf::from_str(&project) {
-                Ok(v) => app_args.project = Some(tilde_expand(v)),
+                Ok(v) => app_args.project = Some(tilde_expand(v)?),
                 Err(_) => {
-                    eprintln!(
+                    return Err(AppArgsError::usage(format!(
                         "STACKMUNCHER CONFIG ERROR: `{}` is not a valid path for `--project`. Omit that param to use the current folder or provide a valid path to where the project is located (absolute or relative).",
                         project
-                    );
-                    help::emit_usage_msg();
-                    exit(1);
+                    )));
+                }
+            }
+        };
+
+        // git folder of a worktree or a submodule that was moved
+        if let Some(git_dir) = find_arg_value(&mut pargs, vec!["--gitdir", "--git-dir"])? {
+            params_used.push(PARAM_GITDIR);
+            if git_dir.trim().is_empty() {
+                return Err(AppArgsError::usage(
+                    "STACKMUNCHER CONFIG ERROR: param `--gitdir` has no value. It must be the path to the git folder of the project, e.g. `~/repos/main/.git/worktrees/feature`.".to_owned(),
+                ));
+            }
+
+            match PathBuf::from_str(&git_dir) {
+                Ok(v) => app_args.git_dir = Some(tilde_expand(v)?),
+                Err(_) => {
+                    return Err(AppArgsError::usage(format!(
+                        "STACKMUNCHER CONFIG ERROR: `{}` is not a valid path for `--gitdir`.",
+                        git_dir
+                    )));
                 }
             }
         };
 
         // report folder
-        if let Some(reports) = find_arg_value(&mut pargs, vec!["--reports"]) {
+        if let Some(reports) = find_arg_value(&mut pargs, vec!["--reports"])? {
+            params_used.push(PARAM_REPORTS);
             // en empty value doesn't make sense in this context
             if reports.trim().is_empty() {
-                eprintln!(
-                    "STACKMUNCHER CONFIG ERROR: param `--reports` has no value. Omit it to use the default location or provide a valid path to where report files should be placed (absolute or relative).",
-                );
-                help::emit_report_dir_msg();
-                exit(1);
+                return Err(AppArgsError::report_dir(
+                    "STACKMUNCHER CONFIG ERROR: param `--reports` has no value. Omit it to use the default location or provide a valid path to where report files should be placed (absolute or relative).".to_owned(),
+                ));
             }
 
             // try to convert to path
             let reports_dir = match PathBuf::from_str(&reports) {
                 // expand ~/somepath on Linux to /home/user/...
-                Ok(v) => tilde_expand(v),
+                Ok(v) => tilde_expand(v)?,
                 Err(_) => {
-                    eprintln!(
+                    return Err(AppArgsError::report_dir(format!(
                         "STACKMUNCHER CONFIG ERROR: `{}` is not a valid path for `--reports`. Omit it to use the default location or provide a valid path to where report files should be placed (absolute or relative).",
                         reports
-                    );
-                    help::emit_report_dir_msg();
-                    exit(1);
+                    )));
                 }
             };
 
@@ -214,110 +1159,576 @@ impl AppArgs {
         };
 
         // config folder
-        if let Some(config_folder) = find_arg_value(&mut pargs, vec!["--config"]) {
+        if let Some(config_folder) = find_arg_value(&mut pargs, vec!["--config"])? {
+            params_used.push(PARAM_CONFIG);
             // en empty value doesn't make sense in this context
             if config_folder.trim().is_empty() {
-                eprintln!(
-                    "STACKMUNCHER CONFIG ERROR: param `--config` has no value. Omit it to use the default location or provide a valid path to where your encryption keys and config details should be stored (absolute or relative).",
-                );
-                help::emit_usage_msg();
-                exit(1);
+                return Err(AppArgsError::usage(
+                    "STACKMUNCHER CONFIG ERROR: param `--config` has no value. Omit it to use the default location or provide a valid path to where your encryption keys and config details should be stored (absolute or relative).".to_owned(),
+                ));
             }
 
             match PathBuf::from_str(&config_folder) {
-                Ok(v) => app_args.config = Some(tilde_expand(v)),
+                Ok(v) => app_args.config = Some(tilde_expand(v)?),
                 Err(_) => {
-                    eprintln!(
+                    return Err(AppArgsError::usage(format!(
                         "STACKMUNCHER CONFIG ERROR: `{}` is not a valid path for `--config`. Omit it to use the default location or provide a valid path to where your encryption keys and config details should be stored (absolute or relative)",
                         config_folder
-                    );
-                    help::emit_usage_msg();
-                    exit(1);
+                    )));
                 }
             }
         };
 
         // logging level
-        if let Some(log) = find_arg_value(&mut pargs, vec!["--log", "-l"]) {
-            app_args.log = Some(string_to_log_level(log));
+        if let Some(log) = find_arg_value(&mut pargs, vec!["--log", "-l"])? {
+            params_used.push(PARAM_LOG);
+            app_args.log = Some(string_to_log_level(log)?);
+        };
+        if let Some(log_format) = find_arg_value(&mut pargs, vec!["--log-format", "--log_format", "--logformat"])? {
+            params_used.push(PARAM_LOG_FORMAT);
+            app_args.log_format = LogFormat::from_str(&log_format)?;
+        };
+        app_args.log_raw = pargs.contains("--log-raw") || pargs.contains("--log_raw") || pargs.contains("--lograw");
+        if app_args.log_raw {
+            params_used.push(PARAM_LOG_RAW);
+        }
+        if let Some(log_file) = find_arg_value(&mut pargs, vec!["--log-file", "--log_file", "--logfile"])? {
+            params_used.push(PARAM_LOG_FILE);
+            if log_file.is_empty() {
+                return Err(AppArgsError::usage(
+                    "STACKMUNCHER CONFIG ERROR: `--log-file` requires the path of a file for the unredacted log."
+                        .to_owned(),
+                ));
+            }
+            app_args.log_file = Some(PathBuf::from(log_file));
+        };
+
+        // rollup grouping
+        if let Some(group_by) = find_arg_value(&mut pargs, vec!["--group-by", "--group_by", "--groupby"])? {
+            params_used.push(PARAM_GROUP_BY);
+            app_args.group_by = RollupGroupBy::from_str(&group_by)?;
+        };
+
+        // synthetic repo size for benchmarking
+        if let Some(files) = find_arg_value(&mut pargs, vec!["--files"])? {
+            params_used.push(PARAM_FILES);
+            app_args.bench_spec.files = parse_positive_number(&files, PARAM_FILES)?;
+        };
+        if let Some(commits) = find_arg_value(&mut pargs, vec!["--commits"])? {
+            params_used.push(PARAM_COMMITS);
+            app_args.bench_spec.commits = parse_positive_number(&commits, PARAM_COMMITS)?;
+        };
+        if let Some(contributors) = find_arg_value(&mut pargs, vec!["--contributors"])? {
+            params_used.push(PARAM_CONTRIBUTORS);
+            app_args.bench_spec.contributors = parse_positive_number(&contributors, PARAM_CONTRIBUTORS)?;
+        };
+        if let Some(baseline) = find_arg_value(&mut pargs, vec!["--baseline"])? {
+            // timings taken on a repo of a different size are not comparable with the baseline
+            if params_used.contains(&PARAM_FILES)
+                || params_used.contains(&PARAM_COMMITS)
+                || params_used.contains(&PARAM_CONTRIBUTORS)
+            {
+                return Err(AppArgsError::usage(
+                    "STACKMUNCHER CONFIG ERROR: `--baseline` cannot be combined with `--files`, `--commits` or `--contributors`. The size of the synthetic repo is taken from the baseline.".to_owned(),
+                ));
+            }
+            params_used.push(PARAM_BASELINE);
+            match PathBuf::from_str(&baseline) {
+                Ok(v) if !baseline.is_empty() => app_args.bench_baseline = Some(tilde_expand(v)?),
+                _ => {
+                    return Err(AppArgsError::usage(format!(
+                        "STACKMUNCHER CONFIG ERROR: `{}` is not a valid file name for `--baseline`.",
+                        baseline
+                    )));
+                }
+            }
+        };
+        if let Some(max_regression) = find_arg_value(&mut pargs, vec!["--max-regression", "--max_regression"])? {
+            params_used.push(PARAM_MAX_REGRESSION);
+            app_args.bench_max_regression = parse_positive_number(&max_regression, PARAM_MAX_REGRESSION)?;
+        };
+
+        // minutes between checks for new commits or months between timeline snapshots
+        if let Some(interval) = find_arg_value(&mut pargs, vec!["--interval"])? {
+            params_used.push(PARAM_INTERVAL);
+            let interval = parse_positive_number(&interval, PARAM_INTERVAL)?;
+            if app_args.command == AppArgCommands::Timeline {
+                app_args.timeline_interval = interval;
+            } else {
+                app_args.watch_interval = interval;
+            }
+        };
+
+        // a past date to analyze the project at
+        if let Some(as_of) = find_arg_value(&mut pargs, vec!["--as-of", "--as_of"])? {
+            params_used.push(PARAM_AS_OF);
+            app_args.as_of = Some(parse_date(&as_of, PARAM_AS_OF)?);
+        };
+
+        // a branch, tag or commit to analyze instead of HEAD
+        if let Some(git_ref) = find_arg_value(&mut pargs, vec!["--ref"])? {
+            params_used.push(PARAM_REF);
+            // the value is passed to git as-is, so it must not look like an option
+            if git_ref.trim().is_empty() || git_ref.starts_with('-') || git_ref.contains(char::is_whitespace) {
+                return Err(AppArgsError::usage(format!(
+                    "STACKMUNCHER CONFIG ERROR: invalid `--ref` value `{}`. Expected a branch, a tag or a commit SHA1, e.g. `--ref release/1.2`.",
+                    git_ref
+                )));
+            }
+            app_args.git_ref = Some(git_ref);
+        };
+
+        // dependency graph and SBOM output
+        if let Some(format) = find_arg_value(&mut pargs, vec!["--format"])? {
+            params_used.push(PARAM_FORMAT);
+            match app_args.command {
+                AppArgCommands::Sbom => app_args.sbom_format = SbomFormat::from_str(&format)?,
+                AppArgCommands::Export => app_args.export_format = ExportFormat::from_str(&format)?,
+                _ => app_args.graph_format = GraphFormat::from_str(&format)?,
+            }
+        };
+        if let Some(out) = find_arg_value(&mut pargs, vec!["--out", "-o"])? {
+            params_used.push(PARAM_OUT);
+            if out.is_empty() {
+                return Err(AppArgsError::usage(
+                    "STACKMUNCHER CONFIG ERROR: param `--out` has no value. Omit it to use the default output or provide a file name (absolute or relative).".to_owned(),
+                ));
+            }
+            match PathBuf::from_str(&out) {
+                Ok(v) if app_args.command == AppArgCommands::ExportData => app_args.data_file = Some(tilde_expand(v)?),
+                Ok(v) => app_args.graph_out = Some(tilde_expand(v)?),
+                Err(_) => {
+                    return Err(AppArgsError::usage(format!(
+                        "STACKMUNCHER CONFIG ERROR: `{}` is not a valid file name for `--out`.",
+                        out
+                    )));
+                }
+            }
+        };
+        app_args.graph_external = pargs.contains("--external");
+        if app_args.graph_external {
+            params_used.push(PARAM_EXTERNAL);
+        }
+
+        // sections of `show` command
+        app_args.show_hotspots = pargs.contains("--hotspots");
+        if app_args.show_hotspots {
+            params_used.push(PARAM_HOTSPOTS);
+        }
+        app_args.show_modules = pargs.contains("--modules");
+        if app_args.show_modules {
+            params_used.push(PARAM_MODULES);
+        }
+
+        // identity bundle, data archive or report file
+        if let Some(file) = find_arg_value(&mut pargs, vec!["--file", "-f"])? {
+            params_used.push(PARAM_FILE);
+            if file.is_empty() {
+                return Err(AppArgsError::usage(
+                    "STACKMUNCHER CONFIG ERROR: param `--file` has no value. Omit it to use the default file name or provide a file name (absolute or relative).".to_owned(),
+                ));
+            }
+            match PathBuf::from_str(&file) {
+                Ok(v) if app_args.command == AppArgCommands::ImportData => app_args.data_file = Some(tilde_expand(v)?),
+                Ok(v) if app_args.command == AppArgCommands::Verify => app_args.verify_file = Some(tilde_expand(v)?),
+                Ok(v) => app_args.identity_file = Some(tilde_expand(v)?),
+                Err(_) => {
+                    return Err(AppArgsError::usage(format!(
+                        "STACKMUNCHER CONFIG ERROR: `{}` is not a valid file name for `--file`.",
+                        file
+                    )));
+                }
+            }
+        };
+
+        app_args.encrypt_key = pargs.contains("--encrypt-key");
+        if app_args.encrypt_key {
+            params_used.push(PARAM_ENCRYPT_KEY);
+        }
+
+        // the number of audit log entries to print
+        if let Some(last) = find_arg_value(&mut pargs, vec!["--last", "-n"])? {
+            params_used.push(PARAM_LAST);
+            app_args.audit_last = Some(parse_positive_number(&last, PARAM_LAST)?);
+        };
+
+        // a folder with the rules to check instead of the embedded ones
+        if let Some(rules) = find_arg_value(&mut pargs, vec!["--rules"])? {
+            params_used.push(PARAM_RULES);
+            if rules.is_empty() {
+                return Err(AppArgsError::usage(
+                    "STACKMUNCHER CONFIG ERROR: param `--rules` has no value. Omit it to check the built-in rules or provide the path to a folder with `file_types` and `munchers` sub-folders.".to_owned(),
+                ));
+            }
+            match PathBuf::from_str(&rules) {
+                Ok(v) => app_args.rules_dir = Some(tilde_expand(v)?),
+                Err(_) => {
+                    return Err(AppArgsError::usage(format!(
+                        "STACKMUNCHER CONFIG ERROR: `{}` is not a valid path for `--rules`.",
+                        rules
+                    )));
+                }
+            }
         };
 
+        // `stackmuncher help config` is the same as `stackmuncher config --help`
+        // free-standing args must be taken after all the named params were consumed
+        if app_args.command == AppArgCommands::Help {
+            if let Ok(Some(topic)) = pargs.opt_free_from_str::<String>() {
+                let topic = AppArgCommands::from_str(&topic)?;
+                if topic != AppArgCommands::Help {
+                    app_args.help_topic = Some(topic);
+                }
+            }
+        }
+
+        // `stackmuncher identity export|import` must have the action
+        if app_args.command == AppArgCommands::Identity {
+            match pargs.opt_free_from_str::<String>() {
+                Ok(Some(action)) => app_args.identity_action = Some(IdentityAction::from_str(&action)?),
+                _ => {
+                    return Err(AppArgsError::usage(
+                        "STACKMUNCHER CONFIG ERROR: `identity` command requires an action: `stackmuncher identity export` or `stackmuncher identity import`.".to_owned(),
+                    ));
+                }
+            }
+        }
+
+        // `stackmuncher show` has nothing to print without a section
+        if app_args.command == AppArgCommands::Show && !app_args.show_hotspots && !app_args.show_modules {
+            return Err(AppArgsError::usage(
+                "STACKMUNCHER CONFIG ERROR: `show` command requires a section to print: `stackmuncher show --hotspots` or `stackmuncher show --modules`."
+                    .to_owned(),
+            ));
+        }
+
+        // `stackmuncher rules check` must have the action
+        if app_args.command == AppArgCommands::Rules {
+            match pargs.opt_free_from_str::<String>() {
+                Ok(Some(action)) => app_args.rules_action = Some(RulesAction::from_str(&action)?),
+                _ => {
+                    return Err(AppArgsError::usage(
+                        "STACKMUNCHER CONFIG ERROR: `rules` command requires an action: `stackmuncher rules check` or `stackmuncher rules test <muncher> <sample_dir>`."
+                            .to_owned(),
+                    ));
+                }
+            }
+        }
+
+        // `stackmuncher rules test rust.rs ./samples` must have the muncher and the samples folder
+        if app_args.rules_action == Some(RulesAction::Test) {
+            match (pargs.opt_free_from_str::<String>(), pargs.opt_free_from_str::<String>()) {
+                (Ok(Some(muncher)), Ok(Some(sample_dir))) => {
+                    app_args.rules_muncher = Some(muncher);
+                    app_args.rules_sample_dir = Some(tilde_expand(PathBuf::from(sample_dir))?);
+                }
+                _ => {
+                    return Err(AppArgsError::usage(
+                        "STACKMUNCHER CONFIG ERROR: `rules test` requires a muncher name and a folder with samples, e.g. `stackmuncher rules test rust.rs ./samples`."
+                            .to_owned(),
+                    ));
+                }
+            }
+        }
+
+        // `stackmuncher profile set` needs at least one detail to set and the details cannot be used without `set`
+        if app_args.command == AppArgCommands::Profile {
+            if let Ok(Some(action)) = pargs.opt_free_from_str::<String>() {
+                app_args.profile_action = Some(ProfileAction::from_str(&action)?);
+            }
+            let details_used = params_used.iter().any(|p| PROFILE_DETAIL_PARAMS.contains(p));
+            if app_args.profile_action.is_none() && details_used {
+                return Err(AppArgsError::usage(
+                    "STACKMUNCHER CONFIG ERROR: profile details can only be changed with `stackmuncher profile set`, e.g. `stackmuncher profile set --available yes`.".to_owned(),
+                ));
+            }
+            if app_args.profile_action == Some(ProfileAction::Set) && !details_used {
+                return Err(AppArgsError::usage(
+                    "STACKMUNCHER CONFIG ERROR: `profile set` requires at least one of `--public_name`, `--headline`, `--public_contact`, `--available` or `--location`.".to_owned(),
+                ));
+            }
+        }
+
         // check for any leftovers or unrecognized params
         let leftovers = pargs.finish();
         if !leftovers.is_empty() {
-            eprintln!("STACKMUNCHER CONFIG ERROR: {:?} params are not recognized.", leftovers);
-            help::emit_usage_msg();
-            exit(1);
+            return Err(AppArgsError::usage(format!(
+                "STACKMUNCHER CONFIG ERROR: {:?} params are not recognized.",
+                leftovers
+            )));
+        }
+
+        // help ignores all other params, but the other commands should only get the params they can act upon
+        if app_args.command != AppArgCommands::Help {
+            let valid_params = app_args.command.valid_params();
+            if let Some(invalid_param) = params_used.iter().find(|p| !valid_params.contains(p)) {
+                let command = app_args.command.cli_name();
+                return Err(AppArgsError {
+                    msg: format!(
+                        "STACKMUNCHER CONFIG ERROR: param `{}` cannot be used with `{}` command. Run `stackmuncher{} {} --help` for the list of valid params.",
+                        invalid_param, command, EXE_SUFFIX, command
+                    ),
+                    help: help::emit_support_msg,
+                });
+            }
+        }
+
+        // the snapshot is a separate clone, which cannot share a moved git folder
+        if params_used.contains(&PARAM_AS_OF) && params_used.contains(&PARAM_GITDIR) {
+            return Err(AppArgsError::usage(
+                "STACKMUNCHER CONFIG ERROR: `--as-of` cannot be used with `--gitdir`. Run it from the main repo of the worktree instead.".to_owned(),
+            ));
+        }
+
+        // `--as-of` picks the commit on its own
+        if params_used.contains(&PARAM_AS_OF) && params_used.contains(&PARAM_REF) {
+            return Err(AppArgsError::usage(
+                "STACKMUNCHER CONFIG ERROR: `--as-of` cannot be used with `--ref`.".to_owned(),
+            ));
         }
 
-        app_args
+        Ok(app_args)
     }
 }
 
-/// Returns the value for the first matching param name. Prints an error and exists if the parser fails.
-fn find_arg_value(pargs: &mut pico_args::Arguments, arg_names: Vec<&'static str>) -> Option<String> {
-    //
-
+/// Returns the value for the first matching param name or an error if the parser fails.
+fn find_arg_value(
+    pargs: &mut pico_args::Arguments,
+    arg_names: Vec<&'static str>,
+) -> Result<Option<String>, AppArgsError> {
     for arg_name in arg_names {
         // try to read the setting and inform the user if there is an error
         let value: Option<String> = match pargs.opt_value_from_str(arg_name) {
             Ok(v) => v,
             Err(_) => {
-                eprintln!(
+                return Err(AppArgsError::usage(format!(
                     "STACKMUNCHER CONFIG ERROR: invalid or missing value for `{}`. Add \"\" to reset this setting.",
                     arg_name
-                );
-                help::emit_usage_msg();
-                exit(1);
+                )));
             }
         };
 
         // return the first value encountered
         if let Some(v) = value {
-            return Some(v.trim().to_owned());
+            return Ok(Some(v.trim().to_owned()));
         }
     }
 
     // no value was found
-    None
+    Ok(None)
 }
 
-/// Converts case insensitive level as String into Enum, defaults to INFO
-fn string_to_log_level(s: String) -> tracing::Level {
+/// Returns the value as-is if it is no longer than `max_length` characters or an error naming the param.
+fn validate_max_length(s: String, param_name: &str, max_length: usize) -> Result<String, AppArgsError> {
+    if s.chars().count() > max_length {
+        return Err(AppArgsError::usage(format!(
+            "STACKMUNCHER CONFIG ERROR: the value of `{}` is too long. It must be no longer than {} characters.",
+            param_name, max_length
+        )));
+    }
+
+    Ok(s)
+}
+
+/// Returns the URL as-is if it is empty or a valid HTTPS URL, otherwise an error.
+fn validate_inbox_url(url: String) -> Result<String, AppArgsError> {
+    if url.is_empty() || is_https_url(&url) {
+        Ok(url)
+    } else {
+        Err(AppArgsError::usage(format!(
+            "STACKMUNCHER CONFIG ERROR: `{}` is not a valid value for `--inbox-url`. It must be an HTTPS URL, e.g. https://inbox.example.com, or \"\" to use the default.",
+            url
+        )))
+    }
+}
+
+/// Returns the URL as-is if it is empty or a valid HTTPS URL, otherwise an error.
+fn validate_timestamp_url(url: String) -> Result<String, AppArgsError> {
+    if url.is_empty() || is_https_url(&url) {
+        Ok(url)
+    } else {
+        Err(AppArgsError::usage(format!(
+            "STACKMUNCHER CONFIG ERROR: `{}` is not a valid value for `--timestamp-url`. It must be the HTTPS URL of an RFC3161 timestamping authority, e.g. https://freetsa.org/tsr, or \"\" to turn timestamping off.",
+            url
+        )))
+    }
+}
+
+/// Returns true if the URL has https scheme and a host, e.g. `https://user@inbox.example.com:8443/submit`.
+/// The URL is not parsed any further because builds without `cloud` feature have no HTTP client to parse it with.
+pub(crate) fn is_https_url(url: &str) -> bool {
+    let authority = match url.strip_prefix("https://") {
+        Some(v) => v.split(['/', '?', '#']).next().unwrap_or_default(),
+        None => return false,
+    };
+    let host = authority.rsplit('@').next().unwrap_or_default();
+    let host = match host.rsplit_once(':') {
+        // an IPv6 address has colons inside the brackets
+        Some((host, port)) if !port.contains(']') => host,
+        _ => host,
+    };
+
+    !host.is_empty()
+        && !url.chars().any(|c| c.is_whitespace() || c.is_control())
+        && host
+            .chars()
+            .all(|c| c.is_alphanumeric() || c == '.' || c == '-' || c == '_' || c == '[' || c == ']' || c == ':')
+}
+
+/// Splits a comma or space separated list of emails into a list of lower case values, e.g.
+/// `a@example.com,,d@example.com,` or `"a@example.com d@example.com"`. An empty value returns an empty list.
+fn parse_email_list(s: &str) -> Vec<String> {
+    s.trim()
+        .to_lowercase()
+        .replace(' ', ",")
+        .split(',')
+        .filter_map(|v| if v.is_empty() { None } else { Some(v.to_owned()) })
+        .collect::<Vec<String>>()
+}
+
+/// Splits a comma-separated list of file path regex, e.g. `^pairing/,\.generated\.rs$`.
+/// An empty value returns an empty list.
+fn parse_exclude_paths(s: &str) -> Result<Vec<String>, AppArgsError> {
+    let mut exclude_paths: Vec<String> = Vec::new();
+    for exclude_path in s.split(',').map(|v| v.trim()).filter(|v| !v.is_empty()) {
+        if let Err(e) = Regex::new(exclude_path) {
+            return Err(AppArgsError::usage(format!(
+                "STACKMUNCHER CONFIG ERROR: `{}` is an invalid value for `--exclude-paths`. It must be a comma-separated list of regex, e.g. `^pairing/,\\.lock$`.\n\n    {}",
+                exclude_path, e
+            )));
+        }
+        exclude_paths.push(exclude_path.to_owned());
+    }
+
+    Ok(exclude_paths)
+}
+
+/// Splits a comma-separated list of `old=new` email pairs into a map with lower case values.
+/// `old=` has an empty new email to remove the alias. An empty value returns an empty map.
+fn parse_aliases(s: &str) -> Result<BTreeMap<String, String>, AppArgsError> {
+    let mut aliases: BTreeMap<String, String> = BTreeMap::new();
+    for alias in s.split(',').map(|v| v.trim()).filter(|v| !v.is_empty()) {
+        match alias.split_once('=') {
+            Some((old, new)) if !old.trim().is_empty() && !old.trim().eq_ignore_ascii_case(new.trim()) => {
+                aliases.insert(old.trim().to_lowercase(), new.trim().to_lowercase());
+            }
+            _ => {
+                return Err(AppArgsError::usage(format!(
+                    "STACKMUNCHER CONFIG ERROR: `{}` is an invalid value for `--alias`. It must be the old and the new commit emails, e.g. `old@example.com=new@example.com`.",
+                    alias
+                )));
+            }
+        }
+    }
+
+    Ok(aliases)
+}
+
+/// Parses `start-end` hours of the working day, e.g. `9-17`. The end can be less than the start for night shifts.
+fn parse_workday(s: &str) -> Result<(u32, u32), AppArgsError> {
+    let hours = s
+        .split('-')
+        .map(|v| v.trim().parse::<u32>().ok().filter(|v| *v < 24))
+        .collect::<Vec<Option<u32>>>();
+
+    match hours.as_slice() {
+        [Some(start), Some(end)] if start != end => Ok((*start, *end)),
+        _ => Err(AppArgsError::usage(format!(
+            "STACKMUNCHER CONFIG ERROR: `{}` is an invalid value for `--workday`. It must be the start and end hours of the working day in 24hr format, e.g. `9-17`.",
+            s
+        ))),
+    }
+}
+
+/// Splits a comma-separated list of timezones and validates them against the IANA database.
+/// An empty value returns an empty list.
+fn parse_timezones(s: &str) -> Result<Vec<String>, AppArgsError> {
+    let timezones = s
+        .split(',')
+        .map(|v| v.trim())
+        .filter(|v| !v.is_empty())
+        .map(|v| v.to_owned())
+        .collect::<Vec<String>>();
+
+    if let Some(invalid) = timezones.iter().find(|v| !WorkdayOverlap::is_valid_timezone(v)) {
+        return Err(AppArgsError::usage(format!(
+            "STACKMUNCHER CONFIG ERROR: `{}` is not a known timezone for `--timezones`. Use IANA timezone names, e.g. `Europe/Berlin,US/Pacific`, or \"\" to use all UTC offsets.",
+            invalid
+        )));
+    }
+
+    Ok(timezones)
+}
+
+/// Parses a number greater than zero or returns an error naming the param.
+fn parse_positive_number(s: &str, param_name: &str) -> Result<usize, AppArgsError> {
+    match s.parse::<usize>() {
+        Ok(v) if v > 0 => Ok(v),
+        _ => Err(AppArgsError::usage(format!(
+            "STACKMUNCHER CONFIG ERROR: `{}` is an invalid value for `{}`. It must be a whole number greater than zero.",
+            s, param_name
+        ))),
+    }
+}
+
+/// Returns true for `yes`, false for `no` and their common synonyms, otherwise an error.
+/// Returns the date from a `YYYY-MM-DD` string or an error with the param name. Future dates are rejected.
+fn parse_date(s: &str, param_name: &str) -> Result<NaiveDate, AppArgsError> {
+    match NaiveDate::parse_from_str(s.trim(), "%Y-%m-%d") {
+        Ok(v) if v <= Utc::now().date_naive() => Ok(v),
+        Ok(_) => Err(AppArgsError::usage(format!(
+            "STACKMUNCHER CONFIG ERROR: `{}` is in the future. Use a past date for `{}`.",
+            s, param_name
+        ))),
+        Err(_) => Err(AppArgsError::usage(format!(
+            "STACKMUNCHER CONFIG ERROR: `{}` is not a valid date for `{}`. Use YYYY-MM-DD format, e.g. `2023-06-01`.",
+            s, param_name
+        ))),
+    }
+}
+
+fn parse_yes_no(s: &str, param_name: &str) -> Result<bool, AppArgsError> {
     match s.trim().to_lowercase().as_str() {
-        "trace" => tracing::Level::TRACE,
-        "debug" => tracing::Level::DEBUG,
-        "error" => tracing::Level::ERROR,
-        "warn" => tracing::Level::WARN,
-        "info" => tracing::Level::INFO,
+        "yes" | "y" | "true" | "on" => Ok(true),
+        "no" | "n" | "false" | "off" => Ok(false),
+        _ => Err(AppArgsError::usage(format!(
+            "STACKMUNCHER CONFIG ERROR: `{}` is an invalid value for `{}`. Valid values: yes, no.",
+            s, param_name
+        ))),
+    }
+}
+
+/// Converts case insensitive level as String into Enum. Returns an error for unknown values.
+fn string_to_log_level(s: String) -> Result<tracing::Level, AppArgsError> {
+    match s.trim().to_lowercase().as_str() {
+        "trace" => Ok(tracing::Level::TRACE),
+        "debug" => Ok(tracing::Level::DEBUG),
+        "error" => Ok(tracing::Level::ERROR),
+        "warn" => Ok(tracing::Level::WARN),
+        "info" => Ok(tracing::Level::INFO),
         _ => {
             // the user specified something, but is it not a valid value
-            // it may still be better off to complete the job with some extended logging, so defaulting to INFO
-            eprintln!("STACKMUNCHER CONFIG ERROR. `{}` is an invalid logging output level for --log option. Omit that param to get error messages only or use any of `info | warn | debug | trace` for more verbose output.", s);
-            help::emit_usage_msg();
-            exit(1);
+            Err(AppArgsError::usage(format!("STACKMUNCHER CONFIG ERROR. `{}` is an invalid logging output level for --log option. Omit that param to get error messages only or use any of `info | warn | debug | trace` for more verbose output.", s)))
         }
     }
 }
 
 /// Replaces `~` in Linux paths with the full path to the home directory.
 /// E.g. `~/rust/stm_app` -> `/home/ubuntu/rust/stm_app`
-fn tilde_expand(path: PathBuf) -> PathBuf {
+fn tilde_expand(path: PathBuf) -> Result<PathBuf, AppArgsError> {
     // check if there is a ~ at all
     if !path.starts_with("~") {
-        return path;
+        return Ok(path);
     }
 
     // is there a home directory?
     let home_dir = match std::env::var("HOME") {
         Ok(v) => v,
         Err(e) => {
-            eprintln!("STACKMUNCHER CONFIG ERROR: Cannot get the name of HOME directory due to {}", e);
-            eprintln!();
-            eprintln!("     Try replacing ~ with an absolute path.");
-            eprintln!();
-            help::emit_usage_msg();
-            exit(1);
+            return Err(AppArgsError::usage(format!(
+                "STACKMUNCHER CONFIG ERROR: Cannot get the name of HOME directory due to {}\n\n     Try replacing ~ with an absolute path.\n",
+                e
+            )));
         }
     };
 
@@ -325,27 +1736,603 @@ fn tilde_expand(path: PathBuf) -> PathBuf {
 
     let home_dir = match PathBuf::from_str(&home_dir) {
         Err(_) => {
-            eprintln!("STACKMUNCHER CONFIG ERROR: $HOME has invalid home directory path: {}", home_dir);
-            eprintln!();
-            eprintln!("     Try replacing ~ with an absolute path.");
-            eprintln!();
-            help::emit_usage_msg();
-            exit(1);
+            return Err(AppArgsError::usage(format!(
+                "STACKMUNCHER CONFIG ERROR: $HOME has invalid home directory path: {}\n\n     Try replacing ~ with an absolute path.\n",
+                home_dir
+            )));
         }
         Ok(v) => {
             if path.starts_with("~/") {
                 v.join(&path.to_string_lossy()[2..])
             } else {
-                eprintln!("STACKMUNCHER CONFIG ERROR: cannot expand ~ shortcut");
-                eprintln!();
-                eprintln!("     Try replacing ~ with an absolute path.");
-                eprintln!();
-                help::emit_usage_msg();
-                exit(1);
+                return Err(AppArgsError::usage(
+                    "STACKMUNCHER CONFIG ERROR: cannot expand ~ shortcut\n\n     Try replacing ~ with an absolute path.\n"
+                        .to_owned(),
+                ));
             }
         }
     };
 
     debug!("Expanded {} -> {}", path.to_string_lossy(), home_dir.to_string_lossy());
-    home_dir
+    Ok(home_dir)
+}
+
+#[cfg(test)]
+mod test_app_args {
+    use super::{
+        is_https_url, AppArgCommands, AppArgs, ExportFormat, GraphFormat, IdentityAction, LogFormat, ProfileAction,
+        RollupGroupBy, RulesAction, SbomFormat, MAX_HEADLINE_LENGTH, MAX_LOCATION_LENGTH,
+    };
+    use chrono::NaiveDate;
+    use stackmuncher_lib::bench::DEFAULT_MAX_REGRESSION_PCT;
+    use std::ffi::OsString;
+    use std::path::PathBuf;
+
+    /// Parses a space-separated command line without the name of the executable.
+    fn parse(args: &[&str]) -> Result<AppArgs, String> {
+        let args = args.iter().map(OsString::from).collect::<Vec<OsString>>();
+        AppArgs::parse(pico_args::Arguments::from_vec(args)).map_err(|e| e.msg)
+    }
+
+    #[test]
+    fn test_no_params() {
+        let args = parse(&[]).unwrap();
+        assert!(args.command == AppArgCommands::Munch);
+        assert!(!args.dryrun);
+        assert!(args.emails.is_none());
+        assert!(args.log.is_none());
+    }
+
+    #[test]
+    fn test_commands() {
+        assert!(parse(&["munch"]).unwrap().command == AppArgCommands::Munch);
+        assert!(parse(&["config"]).unwrap().command == AppArgCommands::ViewConfig);
+        assert!(parse(&["github"]).unwrap().command == AppArgCommands::GitGHubConfig);
+        assert!(parse(&["make-anon"]).unwrap().command == AppArgCommands::MakeAnon);
+        assert!(parse(&["delete_profile"]).unwrap().command == AppArgCommands::DeleteProfile);
+        assert!(parse(&["help"]).unwrap().command == AppArgCommands::Help);
+        assert!(parse(&["profile"]).unwrap().command == AppArgCommands::Profile);
+        assert!(parse(&["demo"]).unwrap().command == AppArgCommands::Demo);
+        assert!(parse(&["munchh"]).is_err());
+    }
+
+    #[test]
+    fn test_munch_params() {
+        let args = parse(&[
+            "--dry-run",
+            "--emails",
+            "A@example.com, b@example.com,,",
+            "--primary_email",
+            "a@example.com",
+            "--project",
+            "/tmp/project",
+            "--reports",
+            "/tmp/reports",
+            "--config",
+            "/tmp/config",
+            "--log",
+            "DEBUG",
+        ])
+        .unwrap();
+
+        assert!(args.command == AppArgCommands::Munch);
+        assert!(args.dryrun);
+        assert_eq!(args.emails.unwrap(), vec!["a@example.com".to_owned(), "b@example.com".to_owned()]);
+        assert_eq!(args.primary_email.unwrap(), "a@example.com");
+        assert_eq!(args.project.unwrap(), PathBuf::from("/tmp/project"));
+        assert_eq!(args.reports.unwrap(), PathBuf::from("/tmp/reports"));
+        assert_eq!(args.config.unwrap(), PathBuf::from("/tmp/config"));
+        assert_eq!(args.log.unwrap(), tracing::Level::DEBUG);
+    }
+
+    #[test]
+    fn test_gitdir() {
+        let args = parse(&["--gitdir", "/mnt/repos/main/.git/worktrees/feature"]).unwrap();
+        assert_eq!(args.git_dir.unwrap(), PathBuf::from("/mnt/repos/main/.git/worktrees/feature"));
+        assert!(parse(&["graph", "--git-dir", "../main/.git"])
+            .unwrap()
+            .git_dir
+            .is_some());
+        assert!(parse(&["--gitdir", ""]).is_err());
+        assert!(parse(&["rollup", "--gitdir", "../main/.git"]).is_err());
+    }
+
+    #[test]
+    fn test_show_what_is_sent() {
+        assert!(parse(&["--show-what-is-sent", "--dryrun"]).unwrap().show_what_is_sent);
+        assert!(!parse(&["--dryrun"]).unwrap().show_what_is_sent);
+        assert!(parse(&["config", "--show-what-is-sent"]).is_err());
+    }
+
+    #[test]
+    fn test_full_history() {
+        assert!(parse(&["--full-history"]).unwrap().full_history);
+        assert!(parse(&["watch", "--full_history"]).unwrap().full_history);
+        assert!(!parse(&["--dryrun"]).unwrap().full_history);
+        assert!(parse(&["rollup", "--full-history"]).is_err());
+    }
+
+    #[test]
+    fn test_wait() {
+        assert!(parse(&["--wait"]).unwrap().wait);
+        assert!(parse(&["watch", "--wait"]).unwrap().wait);
+        assert!(!parse(&["--dryrun"]).unwrap().wait);
+        assert!(parse(&["show", "--hotspots", "--wait"]).is_err());
+    }
+
+    #[test]
+    fn test_alt_spellings() {
+        let args = parse(&[
+            "--dry_run",
+            "--primary-email",
+            "a@example.com",
+            "-p",
+            "/tmp",
+            "-l",
+            "warn",
+        ])
+        .unwrap();
+        assert!(args.dryrun);
+        assert_eq!(args.primary_email.unwrap(), "a@example.com");
+        assert_eq!(args.project.unwrap(), PathBuf::from("/tmp"));
+        assert_eq!(args.log.unwrap(), tracing::Level::WARN);
+    }
+
+    #[test]
+    fn test_public_name_headline() {
+        let args = parse(&[
+            "config",
+            "--public-name",
+            " Jane Doe ",
+            "--headline",
+            "Backend engineer — Rust/Go",
+        ])
+        .unwrap();
+        assert_eq!(args.public_name.unwrap(), "Jane Doe");
+        assert_eq!(args.headline.unwrap(), "Backend engineer — Rust/Go");
+        // an empty value removes the setting
+        assert_eq!(parse(&["--public_name", ""]).unwrap().public_name.unwrap(), "");
+        assert!(parse(&["--headline", &"x".repeat(MAX_HEADLINE_LENGTH + 1)]).is_err());
+        assert!(parse(&["rollup", "--headline", "x"]).is_err());
+    }
+
+    #[test]
+    fn test_profile_set() {
+        let args = parse(&[
+            "profile",
+            "set",
+            "--public-contact",
+            "https://example.com/jane",
+            "--available",
+            "Yes",
+            "--location",
+            "Berlin, Germany",
+        ])
+        .unwrap();
+        assert_eq!(args.command, AppArgCommands::Profile);
+        assert_eq!(args.profile_action, Some(ProfileAction::Set));
+        assert_eq!(args.public_contact.unwrap(), "https://example.com/jane");
+        assert_eq!(args.available, Some(true));
+        assert_eq!(args.location.unwrap(), "Berlin, Germany");
+
+        assert_eq!(parse(&["profile", "set", "--available", "no"]).unwrap().available, Some(false));
+        assert_eq!(parse(&["profile", "set", "--location", ""]).unwrap().location.unwrap(), "");
+        assert!(parse(&["profile"]).unwrap().profile_action.is_none());
+
+        assert!(parse(&["profile", "set"]).is_err());
+        assert!(parse(&["profile", "--available", "yes"]).is_err());
+        assert!(parse(&["profile", "set", "--available", "maybe"]).is_err());
+        assert!(parse(&["profile", "get", "--available", "yes"]).is_err());
+        assert!(parse(&["profile", "set", "--location", &"x".repeat(MAX_LOCATION_LENGTH + 1)]).is_err());
+        assert!(parse(&["config", "--available", "yes"]).is_err());
+    }
+
+    #[test]
+    fn test_identity() {
+        let args = parse(&["identity", "export", "--file", "id.txt"]).unwrap();
+        assert_eq!(args.command, AppArgCommands::Identity);
+        assert_eq!(args.identity_action, Some(IdentityAction::Export));
+        assert_eq!(args.identity_file.unwrap(), PathBuf::from("id.txt"));
+        assert_eq!(parse(&["identity", "import"]).unwrap().identity_action, Some(IdentityAction::Import));
+        assert!(parse(&["identity"]).is_err());
+        assert!(parse(&["identity", "delete"]).is_err());
+        assert!(parse(&["config", "--file", "id.txt"]).is_err());
+    }
+
+    #[test]
+    fn test_data() {
+        let args = parse(&["export-data", "--out", "backup.tar.gz", "--encrypt-key"]).unwrap();
+        assert_eq!(args.command, AppArgCommands::ExportData);
+        assert_eq!(args.data_file.unwrap(), PathBuf::from("backup.tar.gz"));
+        assert!(args.encrypt_key);
+        assert!(args.graph_out.is_none());
+        let args = parse(&["import-data", "--file", "backup.tar.gz"]).unwrap();
+        assert_eq!(args.command, AppArgCommands::ImportData);
+        assert_eq!(args.data_file.unwrap(), PathBuf::from("backup.tar.gz"));
+        assert!(args.identity_file.is_none());
+        assert!(parse(&["import-data", "--encrypt-key"]).is_err());
+        assert!(parse(&["export-data", "--file", "backup.tar.gz"]).is_err());
+    }
+
+    #[test]
+    fn test_timestamp() {
+        let args = parse(&["--timestamp-url", "https://freetsa.org/tsr"]).unwrap();
+        assert_eq!(args.timestamp_url.unwrap(), "https://freetsa.org/tsr");
+        assert_eq!(
+            parse(&["config", "--timestamp-url", ""])
+                .unwrap()
+                .timestamp_url
+                .unwrap(),
+            ""
+        );
+        assert!(parse(&["--timestamp-url", "http://timestamp.digicert.com"]).is_err());
+        assert!(parse(&["rollup", "--timestamp-url", "https://freetsa.org/tsr"]).is_err());
+
+        let args = parse(&["verify", "--file", "report.json"]).unwrap();
+        assert_eq!(args.command, AppArgCommands::Verify);
+        assert_eq!(args.verify_file.unwrap(), PathBuf::from("report.json"));
+        assert!(args.identity_file.is_none());
+        assert!(parse(&["verify", "--dryrun"]).is_err());
+    }
+
+    #[test]
+    fn test_inbox_url() {
+        let args = parse(&["--inbox-url", "https://inbox.example.com/submit"]).unwrap();
+        assert_eq!(args.inbox_url.unwrap(), "https://inbox.example.com/submit");
+        assert_eq!(parse(&["config", "--inbox_url", ""]).unwrap().inbox_url.unwrap(), "");
+        assert!(parse(&["--inbox-url", "http://inbox.example.com"]).is_err());
+        assert!(parse(&["--inbox-url", "inbox.example.com"]).is_err());
+        assert!(parse(&["rollup", "--inbox-url", "https://inbox.example.com"]).is_err());
+
+        assert!(is_https_url("https://user@inbox.example.com:8443/submit?v=1"));
+        assert!(is_https_url("https://[::1]:8443"));
+        assert!(!is_https_url("https://"));
+        assert!(!is_https_url("https:///submit"));
+        assert!(!is_https_url("https://inbox example.com"));
+    }
+
+    #[test]
+    fn test_report_sink() {
+        let args = parse(&["watch", "--report-sink", "/mnt/reports"]).unwrap();
+        assert_eq!(args.report_sink.unwrap(), "/mnt/reports");
+        #[cfg(feature = "cloud")]
+        assert!(parse(&["--report_sink", "https://artifacts.example.com/stm"]).is_ok());
+        #[cfg(not(feature = "cloud"))]
+        assert!(parse(&["--report_sink", "https://artifacts.example.com/stm"]).is_err());
+        assert!(parse(&["--report-sink", "ftp://example.com"]).is_err());
+        assert!(parse(&["--report-sink", ""]).is_err());
+        assert!(parse(&["config", "--report-sink", "/mnt/reports"]).is_err());
+    }
+
+    #[test]
+    fn test_trace_file() {
+        let args = parse(&["--trace-file", "src\\main.rs"]).unwrap();
+        assert_eq!(args.trace_file.unwrap(), "src/main.rs");
+        assert_eq!(parse(&["munch", "--trace_file", "src/"]).unwrap().trace_file.unwrap(), "src/");
+        assert!(parse(&["--trace-file", " "]).is_err());
+        assert!(parse(&["watch", "--trace-file", "src/main.rs"]).is_err());
+    }
+
+    #[test]
+    fn test_aliases() {
+        let aliases = parse(&[
+            "--alias",
+            "Old@x.com=new@y.com, 1+me@users.noreply.github.com=new@y.com,",
+        ])
+        .unwrap()
+        .aliases
+        .unwrap();
+        assert_eq!(aliases.len(), 2);
+        assert_eq!(aliases.get("old@x.com").unwrap(), "new@y.com");
+        // an empty new email removes the alias and an empty value removes all of them
+        assert_eq!(parse(&["config", "--alias", "old@x.com="]).unwrap().aliases.unwrap()["old@x.com"], "");
+        assert!(parse(&["config", "--aliases", ""]).unwrap().aliases.unwrap().is_empty());
+        assert!(parse(&["--alias", "old@x.com"]).is_err());
+        assert!(parse(&["--alias", "=new@y.com"]).is_err());
+        assert!(parse(&["--alias", "a@x.com=A@x.com"]).is_err());
+        assert!(parse(&["watch", "--alias", "old@x.com=new@y.com"]).is_err());
+    }
+
+    #[test]
+    fn test_exclusions() {
+        let args = parse(&[
+            "--exclude-emails",
+            "Pair@office.com, bot@x.com",
+            "--exclude-paths",
+            "^pairing/, \\.lock$",
+        ])
+        .unwrap();
+        assert_eq!(args.exclude_emails.unwrap(), vec!["pair@office.com", "bot@x.com"]);
+        assert_eq!(args.exclude_paths.unwrap(), vec!["^pairing/", "\\.lock$"]);
+        // an empty value removes all of them
+        assert!(parse(&["config", "--exclude-emails", ""])
+            .unwrap()
+            .exclude_emails
+            .unwrap()
+            .is_empty());
+        assert!(parse(&["config", "--exclude_paths", ""])
+            .unwrap()
+            .exclude_paths
+            .unwrap()
+            .is_empty());
+        assert!(parse(&["--exclude-paths", "(unclosed"]).is_err());
+        assert!(parse(&["watch", "--exclude-emails", "pair@office.com"]).is_err());
+    }
+
+    #[test]
+    fn test_workday_timezones() {
+        let args = parse(&["rollup", "--workday", "9-17", "--tz", "Europe/Berlin, US/Pacific"]).unwrap();
+        assert_eq!(args.workday, Some((9, 17)));
+        assert_eq!(args.timezones.unwrap(), vec!["Europe/Berlin", "US/Pacific"]);
+        assert_eq!(parse(&["config", "--workday", "22-6"]).unwrap().workday, Some((22, 6)));
+        let args = parse(&["config", "--timezones", ""]).unwrap();
+        assert!(args.timezones.unwrap().is_empty());
+        assert!(parse(&["--workday", "9"]).is_err());
+        assert!(parse(&["--workday", "9-24"]).is_err());
+        assert!(parse(&["--workday", "9-9"]).is_err());
+        assert!(parse(&["--timezones", "Europe/Berlin,UTC+2"]).is_err());
+        assert!(parse(&["watch", "--workday", "9-17"]).is_err());
+    }
+
+    #[test]
+    fn test_gist() {
+        let args = parse(&[
+            "config",
+            "--gist",
+            "https://gist.github.com/rimutaka/fb8fc0f87ee78231f064131022c8154a",
+        ])
+        .unwrap();
+        assert_eq!(args.gh_validation_id.unwrap(), "fb8fc0f87ee78231f064131022c8154a");
+        // an empty value unlinks the GitHub account
+        assert_eq!(parse(&["config", "--gist", ""]).unwrap().gh_validation_id.unwrap(), "");
+        assert!(parse(&["config", "--gist", "not-a-gist"]).is_err());
+    }
+
+    #[test]
+    fn test_invalid_params() {
+        // typos and unknown params
+        assert!(parse(&["--projcet", "/tmp"]).is_err());
+        assert!(parse(&["--dryrun", "extra"]).is_err());
+        // missing or invalid values
+        assert!(parse(&["--project"]).is_err());
+        assert!(parse(&["--project", ""]).is_err());
+        assert!(parse(&["--reports", " "]).is_err());
+        assert!(parse(&["--log", "verbose"]).is_err());
+    }
+
+    #[test]
+    fn test_params_per_command() {
+        // project and dryrun only make sense for munching
+        assert!(parse(&["config", "--project", "/tmp"]).is_err());
+        assert!(parse(&["config", "--dryrun"]).is_err());
+        assert!(parse(&["github", "--emails", "a@example.com"]).is_err());
+        assert!(parse(&["delete", "--gist", ""]).is_err());
+        // config-related params are valid for config command
+        assert!(parse(&["config", "--emails", "a@example.com", "--primary_email", ""]).is_ok());
+        // logging and folders are valid for any command
+        assert!(parse(&["makeanon", "--log", "info", "--config", "/tmp"]).is_ok());
+        assert!(parse(&["rollup", "--log-format", "json"]).unwrap().log_format == LogFormat::Json);
+        assert!(parse(&["--log-format", "xml"]).is_err());
+        let args = parse(&["demo", "--log", "debug", "--log-raw", "--log-file", "stm.log"]).unwrap();
+        assert!(args.log_raw);
+        assert_eq!(args.log_file, Some(PathBuf::from("stm.log")));
+        assert!(parse(&["--log-file", ""]).is_err());
+    }
+
+    #[test]
+    fn test_rollup() {
+        assert!(parse(&["rollup"]).unwrap().group_by == RollupGroupBy::None);
+        assert!(parse(&["rollup", "--group-by", "ORG"]).unwrap().group_by == RollupGroupBy::Org);
+        assert!(parse(&["rollup", "--group-by", "repo"]).is_err());
+        // grouping is meaningless for munching
+        assert!(parse(&["--group-by", "org"]).is_err());
+    }
+
+    #[test]
+    fn test_bench() {
+        let args = parse(&["bench", "--files", "20", "--commits", "30"]).unwrap();
+        assert!(args.command == AppArgCommands::Bench);
+        assert_eq!(args.bench_spec.files, 20);
+        assert_eq!(args.bench_spec.commits, 30);
+        assert!(parse(&["bench", "--files", "0"]).is_err());
+        assert!(parse(&["bench", "--contributors", "many"]).is_err());
+        assert!(args.bench_baseline.is_none());
+        assert_eq!(args.bench_max_regression, DEFAULT_MAX_REGRESSION_PCT);
+
+        let args = parse(&["bench", "--baseline", "/tmp/baseline.json", "--max-regression", "10"]).unwrap();
+        assert_eq!(args.bench_baseline.unwrap(), PathBuf::from("/tmp/baseline.json"));
+        assert_eq!(args.bench_max_regression, 10);
+        let args = parse(&["bench", "--out", "/tmp/baseline.json"]).unwrap();
+        assert_eq!(args.graph_out.unwrap(), PathBuf::from("/tmp/baseline.json"));
+        // the baseline sets the size of the synthetic repo
+        assert!(parse(&["bench", "--files", "20", "--baseline", "/tmp/baseline.json"]).is_err());
+        assert!(parse(&["bench", "--baseline", ""]).is_err());
+        assert!(parse(&["bench", "--max-regression", "0"]).is_err());
+        // the size of the synthetic repo is meaningless for munching
+        assert!(parse(&["--files", "20"]).is_err());
+    }
+
+    #[test]
+    fn test_watch() {
+        let args = parse(&["watch"]).unwrap();
+        assert!(args.command == AppArgCommands::Watch);
+        assert_eq!(args.watch_interval, super::DEFAULT_WATCH_INTERVAL_MINUTES);
+
+        let args = parse(&["watch", "--interval", "15", "--project", "/tmp", "--dryrun"]).unwrap();
+        assert_eq!(args.watch_interval, 15);
+        assert_eq!(args.project.unwrap(), PathBuf::from("/tmp"));
+        assert!(args.dryrun);
+
+        assert!(parse(&["watch", "--interval", "0"]).is_err());
+        // the interval is meaningless for a single run
+        assert!(parse(&["--interval", "15"]).is_err());
+    }
+
+    #[test]
+    fn test_timeline() {
+        let args = parse(&["timeline"]).unwrap();
+        assert!(args.command == AppArgCommands::Timeline);
+        assert_eq!(args.timeline_interval, super::DEFAULT_TIMELINE_INTERVAL_MONTHS);
+
+        // the interval is in months for timeline and does not change the watch interval
+        let args = parse(&["timeline", "--interval", "6"]).unwrap();
+        assert_eq!(args.timeline_interval, 6);
+        assert_eq!(args.watch_interval, super::DEFAULT_WATCH_INTERVAL_MINUTES);
+
+        let args = parse(&["--as-of", "2023-06-01", "--dryrun"]).unwrap();
+        assert_eq!(args.as_of, NaiveDate::from_ymd_opt(2023, 6, 1));
+        assert!(parse(&["--as-of", "2023-06"]).is_err());
+        assert!(parse(&["--as-of", "2999-01-01"]).is_err());
+        assert!(parse(&["timeline", "--as-of", "2023-06-01"]).is_err());
+        assert!(parse(&["--as-of", "2023-06-01", "--gitdir", "/tmp"]).is_err());
+    }
+
+    #[test]
+    fn test_ref() {
+        let args = parse(&["--ref", "release/1.2", "--dryrun"]).unwrap();
+        assert_eq!(args.git_ref.as_deref(), Some("release/1.2"));
+        assert!(parse(&[]).unwrap().git_ref.is_none());
+        assert!(parse(&["--ref", "--all"]).is_err());
+        assert!(parse(&["--ref", "a b"]).is_err());
+        assert!(parse(&["--ref", "v1.0", "--as-of", "2023-06-01"]).is_err());
+        assert!(parse(&["timeline", "--ref", "v1.0"]).is_err());
+        assert_eq!(parse(&["export", "--ref", "v1.0"]).unwrap().git_ref.as_deref(), Some("v1.0"));
+    }
+
+    #[test]
+    fn test_graph() {
+        let args = parse(&["graph"]).unwrap();
+        assert!(args.command == AppArgCommands::Graph);
+        assert!(args.graph_format == GraphFormat::Dot);
+        assert!(args.graph_out.is_none());
+        assert!(!args.graph_external);
+
+        let args = parse(&["graph", "--format", "JSON", "--out", "/tmp/deps.json", "--external"]).unwrap();
+        assert!(args.graph_format == GraphFormat::Json);
+        assert_eq!(args.graph_out.unwrap(), PathBuf::from("/tmp/deps.json"));
+        assert!(args.graph_external);
+
+        assert!(parse(&["graph", "--format", "svg"]).is_err());
+        assert!(parse(&["graph", "--out", ""]).is_err());
+        // graph options are meaningless for munching
+        assert!(parse(&["--external"]).is_err());
+    }
+
+    #[test]
+    fn test_sbom() {
+        let args = parse(&["sbom"]).unwrap();
+        assert!(args.command == AppArgCommands::Sbom);
+        assert!(args.sbom_format == SbomFormat::CycloneDx);
+
+        // `--format` values depend on the command
+        let args = parse(&["sbom", "--format", "spdx", "--out", "/tmp/sbom.json"]).unwrap();
+        assert!(args.sbom_format == SbomFormat::Spdx);
+        assert!(args.graph_format == GraphFormat::Dot);
+        assert_eq!(args.graph_out.unwrap(), PathBuf::from("/tmp/sbom.json"));
+
+        assert!(parse(&["sbom", "--format", "dot"]).is_err());
+        assert!(parse(&["graph", "--format", "spdx"]).is_err());
+        assert!(parse(&["sbom", "--external"]).is_err());
+    }
+
+    #[test]
+    fn test_export() {
+        let args = parse(&["export"]).unwrap();
+        assert!(args.command == AppArgCommands::Export);
+        assert!(args.export_format == ExportFormat::Markdown);
+
+        let args = parse(&["export", "--format", "md", "--out", "/tmp/stack.md"]).unwrap();
+        assert!(args.export_format == ExportFormat::Markdown);
+        assert_eq!(args.graph_out.unwrap(), PathBuf::from("/tmp/stack.md"));
+
+        // `export-data` is a different command
+        assert!(parse(&["export-data"]).unwrap().command == AppArgCommands::ExportData);
+        assert!(parse(&["export", "--format", "spdx"]).is_err());
+        assert!(parse(&["export", "--external"]).is_err());
+    }
+
+    #[test]
+    fn test_show() {
+        let args = parse(&["show", "--hotspots", "--project", "/tmp/project"]).unwrap();
+        assert!(args.command == AppArgCommands::Show);
+        assert!(args.show_hotspots);
+        assert!(!args.show_modules);
+        assert!(parse(&["show", "--modules"]).unwrap().show_modules);
+
+        assert!(parse(&["show"]).is_err());
+        // hotspots are printed by `show` command only
+        assert!(parse(&["--hotspots"]).is_err());
+        assert!(parse(&["graph", "--modules"]).is_err());
+    }
+
+    #[test]
+    fn test_audit() {
+        let args = parse(&["audit"]).unwrap();
+        assert!(args.command == AppArgCommands::Audit);
+        assert!(args.audit_last.is_none());
+
+        assert_eq!(parse(&["audit", "--last", "5"]).unwrap().audit_last, Some(5));
+        assert!(parse(&["audit", "--last", "0"]).is_err());
+        // the number of entries is meaningless for munching
+        assert!(parse(&["--last", "5"]).is_err());
+    }
+
+    #[test]
+    fn test_status() {
+        assert!(parse(&["status"]).unwrap().command == AppArgCommands::Status);
+        assert!(parse(&["status", "--last", "5"]).is_err());
+    }
+
+    #[test]
+    fn test_doctor() {
+        let args = parse(&["doctor"]).unwrap();
+        assert!(args.command == AppArgCommands::Doctor);
+        assert!(!args.fix);
+        assert!(parse(&["doctor", "--fix", "--project", "/tmp"]).unwrap().fix);
+        // only doctor repairs files
+        assert!(parse(&["--fix"]).is_err());
+        assert!(parse(&["doctor", "--dryrun"]).is_err());
+    }
+
+    #[test]
+    fn test_rules() {
+        let args = parse(&["rules", "check"]).unwrap();
+        assert!(args.command == AppArgCommands::Rules);
+        assert!(args.rules_action == Some(RulesAction::Check));
+        assert!(args.rules_dir.is_none());
+
+        let args = parse(&["rules", "check", "--rules", "stackmuncher_lib/stm_rules"]).unwrap();
+        assert_eq!(args.rules_dir.unwrap(), PathBuf::from("stackmuncher_lib/stm_rules"));
+
+        let args = parse(&["rules", "test", "rust.rs", "/tmp/samples"]).unwrap();
+        assert!(args.rules_action == Some(RulesAction::Test));
+        assert_eq!(args.rules_muncher.unwrap(), "rust.rs");
+        assert_eq!(args.rules_sample_dir.unwrap(), PathBuf::from("/tmp/samples"));
+        assert!(parse(&["rules", "test", "rust.rs"]).is_err());
+        assert!(parse(&["rules", "test"]).is_err());
+
+        assert!(parse(&["rules"]).is_err());
+        assert!(parse(&["rules", "fix"]).is_err());
+        // the rules folder is meaningless for munching
+        assert!(parse(&["--rules", "/tmp"]).is_err());
+    }
+
+    #[test]
+    fn test_help() {
+        let args = parse(&["--help"]).unwrap();
+        assert!(args.command == AppArgCommands::Help);
+        assert!(args.help_topic.is_none());
+
+        let args = parse(&["config", "-h"]).unwrap();
+        assert!(args.command == AppArgCommands::Help);
+        assert!(args.help_topic == Some(AppArgCommands::ViewConfig));
+
+        let args = parse(&["help", "github"]).unwrap();
+        assert!(args.command == AppArgCommands::Help);
+        assert!(args.help_topic == Some(AppArgCommands::GitGHubConfig));
+
+        // help ignores params that are invalid for the command
+        assert!(parse(&["config", "--help", "--dryrun"]).is_ok());
+        assert!(parse(&["help", "nonsense"]).is_err());
+    }
 }
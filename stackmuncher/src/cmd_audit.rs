@@ -0,0 +1,188 @@
+use crate::config::AppConfig;
+use serde::{Deserialize, Serialize};
+#[cfg(feature = "cloud")]
+use sha2::{Digest, Sha256};
+#[cfg(feature = "cloud")]
+use stackmuncher_lib::report::Report;
+#[cfg(feature = "cloud")]
+use std::fs::OpenOptions;
+#[cfg(feature = "cloud")]
+use std::io::Write;
+use std::path::PathBuf;
+#[cfg(feature = "cloud")]
+use tracing::debug;
+use tracing::error;
+
+/// The name of the submission audit log in the config folder.
+const AUDIT_LOG_FILE_NAME: &str = "audit.log";
+/// Long server responses are cut to this many characters in the log.
+#[cfg(feature = "cloud")]
+const MAX_RESPONSE_LENGTH: usize = 200;
+
+/// A record of a single report submission. The log has one JSON record per line and is only ever appended to.
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub(crate) struct AuditEntry {
+    /// The time of the submission in RFC3339 format, UTC.
+    pub timestamp: String,
+    /// The URL the report was sent to.
+    pub endpoint: String,
+    /// SHA256 of the report JSON before compression, hex-encoded. Matches the payload saved with `--show-what-is-sent`.
+    pub payload_sha256: String,
+    /// Size of the report JSON before compression.
+    pub payload_bytes: usize,
+    /// Names of the report sections included in the submission, e.g. `tech`, `commit_cadence`.
+    pub data_categories: Vec<String>,
+    pub report_id: String,
+    /// The HTTP status with the start of the response body or the error if the request failed, e.g. `200 OK`.
+    pub response: String,
+}
+
+#[cfg(feature = "cloud")]
+impl AuditEntry {
+    /// Creates an entry for the report before it is sent. The response is added when the entry is saved.
+    pub(crate) fn new(report: &Report, endpoint: &str) -> Self {
+        let payload = report.to_payload().unwrap_or_default();
+
+        // the top level sections of the report are what the serializer left in after skipping empty values
+        let data_categories = match serde_json::from_slice::<serde_json::Value>(&payload) {
+            Ok(serde_json::Value::Object(sections)) => sections.keys().cloned().collect::<Vec<String>>(),
+            _ => Vec::new(),
+        };
+
+        Self {
+            timestamp: chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Secs, true),
+            endpoint: endpoint.to_owned(),
+            payload_sha256: Sha256::digest(&payload)
+                .iter()
+                .map(|b| format!("{:02x}", b))
+                .collect::<String>(),
+            payload_bytes: payload.len(),
+            data_categories,
+            report_id: report.report_id.clone(),
+            response: String::new(),
+        }
+    }
+
+    /// Sets the response and appends the entry to the audit log in the config folder.
+    /// Failures are logged, but do not stop the submission.
+    pub(crate) fn save(mut self, config: &AppConfig, response: String) {
+        self.response = response.chars().take(MAX_RESPONSE_LENGTH).collect::<String>();
+
+        let audit_log_file = audit_log_file(config);
+        let mut line = match serde_json::to_string(&self) {
+            Ok(v) => v,
+            Err(e) => {
+                error!("Cannot serialize audit entry due to {}. It's a bug.", e);
+                return;
+            }
+        };
+        line.push('\n');
+
+        // a single write in append mode keeps lines whole when `watch` submits several reports at once
+        match OpenOptions::new().create(true).append(true).open(&audit_log_file) {
+            Ok(mut file) => match file.write_all(line.as_bytes()) {
+                Ok(_) => debug!("Audit entry saved in {}", audit_log_file.to_string_lossy()),
+                Err(e) => error!("Cannot write to audit log {} due to {}", audit_log_file.to_string_lossy(), e),
+            },
+            Err(e) => error!("Cannot open audit log {} due to {}", audit_log_file.to_string_lossy(), e),
+        }
+    }
+}
+
+/// Prints the entries from the audit log, the most recent last. Only the last `config.audit_last` entries are
+/// printed if set.
+pub(crate) fn run(config: &AppConfig) -> Result<(), ()> {
+    let audit_log_file = audit_log_file(config);
+    let contents = match std::fs::read_to_string(&audit_log_file) {
+        Ok(v) => v,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            println!();
+            println!("    No reports were submitted from this machine yet.");
+            println!("    Audit log: {}", audit_log_file.to_string_lossy());
+            println!();
+            return Ok(());
+        }
+        Err(e) => {
+            eprintln!(
+                "STACKMUNCHER ERROR: cannot read the audit log {} due to {}",
+                audit_log_file.to_string_lossy(),
+                e
+            );
+            return Err(());
+        }
+    };
+
+    let entries = parse_audit_log(&contents);
+    let skip = match config.audit_last {
+        Some(last) => entries.len().saturating_sub(last),
+        None => 0,
+    };
+
+    println!();
+    for entry in entries.iter().skip(skip) {
+        println!("{}  {}", entry.timestamp, entry.endpoint);
+        println!("    Response:   {}", entry.response);
+        println!("    Report ID:  {}", entry.report_id);
+        println!("    Payload:    {} bytes, SHA256 {}", entry.payload_bytes, entry.payload_sha256);
+        println!("    Data:       {}", entry.data_categories.join(", "));
+        println!();
+    }
+    println!(
+        "    {} of {} submissions shown from {}",
+        entries.len() - skip,
+        entries.len(),
+        audit_log_file.to_string_lossy()
+    );
+    println!();
+
+    Ok(())
+}
+
+/// Returns the path to the audit log in the config folder.
+fn audit_log_file(config: &AppConfig) -> PathBuf {
+    config.config_file_path.with_file_name(AUDIT_LOG_FILE_NAME)
+}
+
+/// Returns the valid entries from the log contents. Lines that cannot be parsed, e.g. cut short by a crash, are skipped.
+fn parse_audit_log(contents: &str) -> Vec<AuditEntry> {
+    contents
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .filter_map(|line| match serde_json::from_str::<AuditEntry>(line) {
+            Ok(v) => Some(v),
+            Err(e) => {
+                error!("Invalid audit log entry {} due to {}", line, e);
+                None
+            }
+        })
+        .collect()
+}
+
+#[cfg(all(test, feature = "cloud"))]
+mod test_audit {
+    use super::{parse_audit_log, AuditEntry};
+    use stackmuncher_lib::report::Report;
+
+    #[test]
+    fn test_audit_entry() {
+        let report: Report =
+            serde_json::from_str(r#"{"timestamp": "2021-07-27T10:00:00+00:00", "report_id": "a1b2", "tech": []}"#)
+                .unwrap();
+
+        let entry = AuditEntry::new(&report, "https://inbox.example.com");
+        assert_eq!(entry.report_id, "a1b2");
+        assert!(entry.data_categories.contains(&"tech".to_owned()));
+        assert!(entry.data_categories.contains(&"report_id".to_owned()));
+
+        let payload = report.to_payload().unwrap();
+        assert_eq!(entry.payload_bytes, payload.len());
+        assert_eq!(entry.payload_sha256.len(), 64);
+
+        // a line cut short by a crash is skipped
+        let line = serde_json::to_string(&entry).unwrap();
+        let contents = [line.as_str(), "\n", &line[..20], "\n", line.as_str(), "\n"].concat();
+        let entries = parse_audit_log(&contents);
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[1], entry);
+    }
+}
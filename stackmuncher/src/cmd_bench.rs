@@ -0,0 +1,155 @@
+use crate::config::AppConfig;
+use stackmuncher_lib::bench::{self, BenchBaseline, BenchResult};
+use stackmuncher_lib::utils::write_atomically;
+use std::path::Path;
+
+/// Number of times each of the fast hot paths is run. Munching is run fewer times.
+const BENCH_ITERATIONS: u32 = 50;
+
+/// Runs the hot path benchmarks on a synthetic repo in a temp folder and prints the results in a format
+/// that can be pasted into a GitHub issue. Nothing is submitted to the Directory.
+/// Fails if any of the hot paths is slower than `config.bench_baseline` by more than `config.bench_max_regression`
+/// percent. The timings are saved as a new baseline in `config.graph_out`, if set.
+pub(crate) async fn run(config: AppConfig) -> Result<(), ()> {
+    // the baseline is only comparable with the timings taken on a repo of the same size
+    let baseline = match &config.bench_baseline {
+        Some(v) => Some(read_baseline(v)?),
+        None => None,
+    };
+    let spec = match &baseline {
+        Some(v) => v.spec(),
+        None => config.bench_spec,
+    };
+    let repo_dir = std::env::temp_dir().join(format!("stm_bench_{}", std::process::id()));
+
+    println!();
+    println!(
+        "    Generating a synthetic repo with {} files, {} commits and {} contributors in {}",
+        spec.files,
+        spec.commits,
+        spec.contributors,
+        repo_dir.to_string_lossy()
+    );
+
+    let results = bench::run(&repo_dir, &spec, BENCH_ITERATIONS).await;
+
+    // the repo is no longer needed, even if the benchmarks failed
+    if let Err(e) = std::fs::remove_dir_all(&repo_dir) {
+        eprintln!(
+            "STACKMUNCHER ERROR: cannot remove the synthetic repo from {} due to {}",
+            repo_dir.to_string_lossy(),
+            e
+        );
+    }
+
+    let results = match results {
+        Ok(v) => v,
+        Err(_) => {
+            eprintln!("STACKMUNCHER ERROR: the benchmarks failed. Run with `--log error` for details.");
+            return Err(());
+        }
+    };
+
+    // a markdown table ready for pasting into an issue
+    println!();
+    println!(
+        "stackmuncher {}, {} {}, {} files, {} commits, {} contributors",
+        env!("CARGO_PKG_VERSION"),
+        std::env::consts::OS,
+        std::env::consts::ARCH,
+        spec.files,
+        spec.commits,
+        spec.contributors
+    );
+    println!();
+    println!("| Hot path | Iterations | Mean, ms | Min, ms |");
+    println!("|---|--:|--:|--:|");
+    for result in &results {
+        println!(
+            "| {} | {} | {:.3} | {:.3} |",
+            result.name, result.iterations, result.mean_ms, result.min_ms
+        );
+    }
+    println!();
+
+    if let Some(out) = &config.graph_out {
+        save_baseline(out, &BenchBaseline::new(&spec, &results))?;
+    }
+
+    match baseline {
+        Some(baseline) => check_regressions(&baseline, &results, config.bench_max_regression),
+        None => {
+            println!("    Compare with the baseline at https://github.com/stackmuncher/stm_app/blob/master/stackmuncher_lib/benches/BASELINE.md");
+            Ok(())
+        }
+    }
+}
+
+/// Prints the hot paths that got slower than the baseline by more than `max_regression_pct` percent.
+/// Returns an error if there are any.
+fn check_regressions(baseline: &BenchBaseline, results: &[BenchResult], max_regression_pct: usize) -> Result<(), ()> {
+    let regressions = baseline.regressions(results, max_regression_pct);
+    if regressions.is_empty() {
+        println!("    No hot path is slower than the baseline by more than {}%", max_regression_pct);
+        return Ok(());
+    }
+
+    eprintln!(
+        "STACKMUNCHER ERROR: {} hot path(s) slower than the baseline by more than {}%:",
+        regressions.len(),
+        max_regression_pct
+    );
+    for regression in regressions {
+        eprintln!(
+            "    {}: {:.3}ms vs {:.3}ms, {:.0}% slower",
+            regression.name,
+            regression.min_ms,
+            regression.baseline_ms,
+            regression.slowdown_pct()
+        );
+    }
+    Err(())
+}
+
+/// Reads the timings saved with `bench --out`.
+fn read_baseline(baseline_file: &Path) -> Result<BenchBaseline, ()> {
+    let contents = match std::fs::read(baseline_file) {
+        Ok(v) => v,
+        Err(e) => {
+            eprintln!(
+                "STACKMUNCHER ERROR: cannot read the baseline from {} due to {}",
+                baseline_file.to_string_lossy(),
+                e
+            );
+            return Err(());
+        }
+    };
+
+    match serde_json::from_slice::<BenchBaseline>(&contents) {
+        Ok(v) => Ok(v),
+        Err(e) => {
+            eprintln!("STACKMUNCHER ERROR: {} is not a valid baseline: {}", baseline_file.to_string_lossy(), e);
+            Err(())
+        }
+    }
+}
+
+/// Saves the timings for checking later runs against with `bench --baseline`.
+fn save_baseline(baseline_file: &Path, baseline: &BenchBaseline) -> Result<(), ()> {
+    let mut contents = serde_json::to_vec_pretty(baseline).expect("Cannot serialize the bench baseline. It's a bug.");
+    contents.push(b'\n');
+    match write_atomically(baseline_file, &contents) {
+        Ok(_) => {
+            println!("    Baseline saved in {}", baseline_file.to_string_lossy());
+            Ok(())
+        }
+        Err(e) => {
+            eprintln!(
+                "STACKMUNCHER ERROR: cannot save the baseline in {} due to {}",
+                baseline_file.to_string_lossy(),
+                e
+            );
+            Err(())
+        }
+    }
+}
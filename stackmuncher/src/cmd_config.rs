@@ -1,14 +1,21 @@
+#[cfg(feature = "cloud")]
+use crate::api_client::ApiRequest;
 use crate::config::AppConfig;
+#[cfg(feature = "cloud")]
 use crate::help;
+#[cfg(feature = "cloud")]
 use crate::signing::ReportSignature;
-use hyper::{Client, Request};
-use hyper_rustls::HttpsConnectorBuilder;
+#[cfg(feature = "cloud")]
 use ring::signature::{self, Ed25519KeyPair, KeyPair};
+#[cfg(feature = "cloud")]
 use serde::Deserialize;
+#[cfg(feature = "cloud")]
 use serde_json::Value;
-use tracing::{debug, error, info, warn};
+#[cfg(feature = "cloud")]
+use tracing::{error, info};
 
 /// A "well-known" string used as the content to be signed for GH verification. The signature is uploaded to a Gist.
+#[cfg(feature = "cloud")]
 const GH_VERIFICATION_STRING_TO_SIGN: &str = "stackmuncher";
 
 #[cfg(not(target_os = "windows"))]
@@ -17,6 +24,7 @@ const PATH_SEPARATOR: &str = "/";
 const PATH_SEPARATOR: &str = "\\";
 
 /// A stripped-down representation of GH GetGist API response: Owner details.
+#[cfg(feature = "cloud")]
 #[derive(Deserialize)]
 pub(crate) struct GistOwner {
     /// GitHub login of the user, e.g. `rimutaka`.
@@ -24,6 +32,7 @@ pub(crate) struct GistOwner {
 }
 
 /// A rough top-level representation of GH GetGist API response.
+#[cfg(feature = "cloud")]
 #[derive(Deserialize)]
 pub(crate) struct RawGist {
     /// The file name is used as the property name, so it is easier to just get Value and then manually dig into it.
@@ -40,6 +49,8 @@ pub(crate) struct RawGist {
 }
 
 /// A validated Gist structure, same as Gist, but without Option<>
+#[cfg(feature = "cloud")]
+#[derive(Clone)]
 pub(crate) struct Gist {
     /// GitHub login of the Gist owner, e.g. `rimutaka`
     pub login: String,
@@ -47,6 +58,7 @@ pub(crate) struct Gist {
     pub html_url: String,
 }
 
+#[cfg(feature = "cloud")]
 pub(crate) async fn github(config: AppConfig) {
     // user signature expected in the gist
     let expected_gist_content = generate_gist_content(&config.user_key_pair);
@@ -57,7 +69,6 @@ pub(crate) async fn github(config: AppConfig) {
 /// Prints its full current configuration, file locations, profile URL and some usage info.
 pub(crate) async fn view_config(config: AppConfig) {
     // prepare values needed in println!() macros to prevent line wrapping in the code
-    let pub_key = ReportSignature::get_public_key(&config.user_key_pair);
     let reports = config
         .reports_dir
         .as_ref()
@@ -75,10 +86,57 @@ pub(crate) async fn view_config(config: AppConfig) {
         Err(_) => "unknown".to_string(),
     };
 
+    println!();
+    println!("    Primary email: {}", config.primary_email.as_ref().unwrap_or(&"not set".to_owned()));
+    println!("    Public name:   {}", non_empty_or_not_set(&config.public_name));
+    println!("    Headline:      {}", non_empty_or_not_set(&config.headline));
+    println!("    Contact:       {}", non_empty_or_not_set(&config.public_contact));
+    println!("    Available:     {}", yes_no_or_not_set(config.available));
+    println!("    Location:      {}", non_empty_or_not_set(&config.location));
+    println!("    Commit emails: {}", config.lib_config.git_identities.join(", "));
+    for (old_email, new_email) in &config.aliases {
+        println!("    Email alias:   {} -> {}", old_email, new_email);
+    }
+    if !config.exclude_emails.is_empty() {
+        println!("    Excluded:      {}", config.exclude_emails.join(", "));
+    }
+    for exclude_path in &config.exclude_paths {
+        println!("    Excluded path: {}", exclude_path);
+    }
+    #[cfg(feature = "cloud")]
+    print_profile_urls(&config).await;
+    println!();
+    println!("    Stack reports: {}{}", reports, PATH_SEPARATOR);
+    println!("    Config folder: {}{}", config_file, PATH_SEPARATOR);
+    println!("    Executable:    {}", exe_file);
+    if let Some(inbox_url) = &config.inbox_url {
+        println!("    Inbox URL:     {}", inbox_url);
+    }
+    if let Some(timestamp_url) = &config.timestamp_url {
+        println!("    Timestamps:    {}", timestamp_url);
+    }
+    println!(
+        "    Working hours: {}:00 - {}:00, {}",
+        config.workday.start_hour,
+        config.workday.end_hour,
+        if config.workday.timezones.is_empty() {
+            "all UTC offsets".to_owned()
+        } else {
+            config.workday.timezones.join(", ")
+        }
+    );
+    println!();
+}
+
+/// Prints the profile URLs and the GitHub validation details.
+#[cfg(feature = "cloud")]
+async fn print_profile_urls(config: &AppConfig) {
+    let pub_key = ReportSignature::get_public_key(&config.user_key_pair);
+
     // gh_validation_gist may already be in the config if --gist option was used and it was validated
     // otherwise we need to re-validate it and get the details from github
-    let gh_validation_gist = match config.gh_validation_gist {
-        Some(v) => Some(v),
+    let gh_validation_gist = match &config.gh_validation_gist {
+        Some(v) => Some(v.clone()),
         None => get_validated_gist(&config.gh_validation_id, &config.user_key_pair).await,
     };
 
@@ -88,18 +146,27 @@ pub(crate) async fn view_config(config: AppConfig) {
         None => ("disabled".to_owned(), "not set".to_owned()),
     };
 
-    println!();
-    println!("    Primary email: {}", config.primary_email.as_ref().unwrap_or(&"not set".to_owned()));
-    println!("    Commit emails: {}", config.lib_config.git_identities.join(", "));
     println!();
     println!("    Anonymous profile: https://stackmuncher.com/?dev={}", pub_key);
     println!("    Public profile:    {}", public_profile);
     println!("    GitHub validation: {}", github_validation);
-    println!();
-    println!("    Stack reports: {}{}", reports, PATH_SEPARATOR);
-    println!("    Config folder: {}{}", config_file, PATH_SEPARATOR);
-    println!("    Executable:    {}", exe_file);
-    println!();
+}
+
+/// Returns the value for printing or `not set` if it is None or empty.
+pub(crate) fn non_empty_or_not_set(value: &Option<String>) -> &str {
+    match value {
+        Some(v) if !v.is_empty() => v,
+        _ => "not set",
+    }
+}
+
+/// Returns `yes`, `no` or `not set` for printing a flag.
+pub(crate) fn yes_no_or_not_set(value: Option<bool>) -> &'static str {
+    match value {
+        Some(true) => "yes",
+        Some(false) => "no",
+        None => "not set",
+    }
 }
 
 /// Returns gist details, if any for the given Gist ID. Can be tested with this shell command:
@@ -108,6 +175,7 @@ pub(crate) async fn view_config(config: AppConfig) {
 ///  -H "Accept: application/vnd.github.v3+json" \
 ///  https://api.github.com/gists/GIST_ID
 /// ```
+#[cfg(feature = "cloud")]
 pub(crate) async fn get_validated_gist(gist_id: &Option<String>, user_key_pair: &Ed25519KeyPair) -> Option<Gist> {
     // check if the gist needs to be retrieved at all
     let gist_id = match gist_id.as_ref() {
@@ -127,26 +195,10 @@ pub(crate) async fn get_validated_gist(gist_id: &Option<String>, user_key_pair:
 
     let uri = ["https://api.github.com/gists/", &gist_id].concat();
 
-    // prepare the HTTP request to GitHub API
-    let req = Request::builder()
-        .uri(uri.clone())
-        .header("Accept", "application/vnd.github.v3+json")
-        .header("User-Agent", "StackMuncher App")
-        .method("GET")
-        .body(hyper::Body::empty())
-        .expect("Cannot create Gist API request");
-    debug!("Http rq: {:?}", req);
-
     // send it out, but it may fail for any number of reasons and we still have to carry on
-    let res = match Client::builder()
-        .build::<_, hyper::Body>(
-            HttpsConnectorBuilder::new()
-                .with_native_roots()
-                .https_only()
-                .enable_http1()
-                .build(),
-        )
-        .request(req)
+    let response = match ApiRequest::get(&uri)
+        .header("Accept", "application/vnd.github.v3+json")
+        .send()
         .await
     {
         Ok(v) => v,
@@ -157,35 +209,12 @@ pub(crate) async fn get_validated_gist(gist_id: &Option<String>, user_key_pair:
         }
     };
 
-    let status = res.status();
-    debug!("GH API response status: {}", status);
-
-    // Concatenate the body stream into a single buffer...
-    let buf = hyper::body::to_bytes(res)
-        .await
-        .expect("Cannot convert GH API response body to bytes. It's a bug.");
-
-    // there should be at least some data returned
-    if buf.is_empty() {
-        error!("Empty GH API response with status {}", status);
-        help::emit_gist_troubleshooting(&gist_id, &uri);
-        return None;
-    }
-
-    // any status other than 200 is an error
-    if !status.is_success() {
-        error!("Status {}", status);
-        log_http_body(&buf);
-        help::emit_gist_troubleshooting(&gist_id, &uri);
-        return None;
-    }
-
     // all responses should be JSON. If it's not JSON it's an error.
-    let gist = match serde_json::from_slice::<RawGist>(&buf) {
+    let gist = match response.json::<RawGist>() {
         Ok(v) => v,
         Err(e) => {
             error!("Failed to convert GH API response to JSON with {}", e);
-            log_http_body(&buf);
+            response.log_body();
             help::emit_gist_troubleshooting(&gist_id, &uri);
             return None;
         }
@@ -198,14 +227,14 @@ pub(crate) async fn get_validated_gist(gist_id: &Option<String>, user_key_pair:
             Some(v) => v,
             None => {
                 error!("Invalid GH API response: missing `owner/login` JSON property");
-                log_http_body(&buf);
+                response.log_body();
                 help::emit_gist_troubleshooting(&gist_id, &uri);
                 return None;
             }
         },
         None => {
             error!("Invalid GH API response: missing `owner` JSON property");
-            log_http_body(&buf);
+            response.log_body();
             help::emit_gist_troubleshooting(&gist_id, &uri);
             return None;
         }
@@ -217,7 +246,7 @@ pub(crate) async fn get_validated_gist(gist_id: &Option<String>, user_key_pair:
     let gist_contents = match gist.files {
         None => {
             error!("Invalid GH API response: missing `file` JSON property");
-            log_http_body(&buf);
+            response.log_body();
             help::emit_gist_troubleshooting(&gist_id, &uri);
             return None;
         }
@@ -253,7 +282,7 @@ pub(crate) async fn get_validated_gist(gist_id: &Option<String>, user_key_pair:
             .is_string()
     {
         error!("Invalid GH API response: invalid `file` JSON property");
-        log_http_body(&buf);
+        response.log_body();
         help::emit_gist_troubleshooting(&gist_id, &uri);
         return None;
     }
@@ -319,26 +348,8 @@ pub(crate) async fn get_validated_gist(gist_id: &Option<String>, user_key_pair:
     })
 }
 
-/// Logs the body as error!(), if possible.
-pub(crate) fn log_http_body(body_bytes: &hyper::body::Bytes) {
-    if body_bytes.is_empty() {
-        warn!("Empty response body.");
-        return;
-    }
-
-    // log the body as-is if it's not too long
-    if body_bytes.len() < 3000 {
-        let s = match std::str::from_utf8(&body_bytes).to_owned() {
-            Err(_e) => "The body is not UTF-8".to_string(),
-            Ok(v) => v.to_string(),
-        };
-        info!("Response body: {}", s);
-    } else {
-        info!("Response is too long to log: {}B", body_bytes.len());
-    }
-}
-
 /// Signs a "well-known" string with the user's key-pair to produce a unique signature.
+#[cfg(feature = "cloud")]
 fn generate_gist_content(user_key_pair: &Ed25519KeyPair) -> String {
     bs58::encode(user_key_pair.sign(GH_VERIFICATION_STRING_TO_SIGN.as_bytes()).as_ref()).into_string()
 }
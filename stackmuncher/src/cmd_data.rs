@@ -0,0 +1,604 @@
+//! `export-data` and `import-data` commands for moving the config folder with the key and all local reports
+//! to another machine in a single archive.
+use crate::cmd_identity::{decode_key, decrypt, encrypt, read_passphrase, save_key};
+use crate::config::{project_report_dir_name, AppConfig, APP_CONFIG_FILE_NAME};
+use crate::lock::LOCK_FILE_NAME;
+use crate::signing::{get_key_file_name, ReportSignature};
+use flate2::{read::GzDecoder, write::GzEncoder, Compression};
+use path_absolutize::Absolutize;
+use serde::{Deserialize, Serialize};
+use stackmuncher_lib::utils::write_atomically;
+use std::fs::File;
+use std::io::{Error, ErrorKind, Read, Write};
+use std::path::{Component, Path, PathBuf};
+use tracing::{debug, info};
+
+/// The default name of the archive in the current folder.
+pub(crate) const DATA_ARCHIVE_FILE_NAME: &str = "stm-backup.tar.gz";
+/// Describes where the data came from. It is the first entry in the archive.
+const MANIFEST_FILE_NAME: &str = "manifest.json";
+/// The contents of the config folder are placed under this folder in the archive.
+const CONFIG_ARCHIVE_DIR: &str = "config";
+/// The contents of the reports folder are placed under this folder in the archive.
+const REPORTS_ARCHIVE_DIR: &str = "reports";
+const KEY_FILE_NAME: &str = "key.txt";
+/// The name of the key file in the archive if it was encrypted with `--encrypt-key`.
+const ENCRYPTED_KEY_FILE_NAME: &str = "key.txt.enc";
+/// Changes if the layout of the archive changes.
+const ARCHIVE_FORMAT_VERSION: u32 = 1;
+
+/// The first entry in the archive. The paths are from the exporting machine and are used to rewrite
+/// the absolute paths in the config on import.
+#[derive(Serialize, Deserialize)]
+struct DataManifest {
+    format_version: u32,
+    /// RFC3339 time of the export.
+    created: String,
+    config_dir: PathBuf,
+    reports_dir: PathBuf,
+    home_dir: Option<PathBuf>,
+    /// The public key of the exported key for verifying the key file after the import.
+    public_key: String,
+    /// The key is stored as `key.txt.enc` encrypted with a passphrase if true.
+    key_encrypted: bool,
+}
+
+/// Saves the config folder, the key and all local reports in a single archive at `config.data_file`.
+pub(crate) fn export(config: &AppConfig) -> Result<(), ()> {
+    let config_dir = config
+        .config_file_path
+        .parent()
+        .expect("Cannot get the config folder from the config file path. It's a bug.")
+        .to_path_buf();
+    let reports_dir = config
+        .reports_dir
+        .clone()
+        .expect("Cannot unwrap config.reports_dir. It's a bug.");
+    let archive_file = match config.data_file.absolutize() {
+        Ok(v) => v.to_path_buf(),
+        Err(e) => {
+            eprintln!(
+                "STACKMUNCHER ERROR: {} is not a valid file name due to {}",
+                config.data_file.to_string_lossy(),
+                e
+            );
+            return Err(());
+        }
+    };
+
+    // the archive would end up inside itself
+    if archive_file.starts_with(&config_dir) || archive_file.starts_with(&reports_dir) {
+        eprintln!(
+            "STACKMUNCHER ERROR: cannot save the archive inside the config or reports folder. Use `--out` to save it elsewhere."
+        );
+        return Err(());
+    }
+
+    // the key must be valid to be of any use on the other machine
+    let key_file = get_key_file_name(&config_dir);
+    let key = match std::fs::read_to_string(&key_file) {
+        Ok(v) => v.trim().to_owned(),
+        Err(e) => {
+            eprintln!("STACKMUNCHER ERROR: cannot read the key file {} due to {}", key_file.to_string_lossy(), e);
+            return Err(());
+        }
+    };
+    let public_key = match decode_key(&key) {
+        Some(v) => ReportSignature::get_public_key(&v),
+        None => {
+            eprintln!("STACKMUNCHER ERROR: the key in {} is invalid.", key_file.to_string_lossy());
+            return Err(());
+        }
+    };
+
+    let (key_file_name, key) = if config.encrypt_key {
+        let passphrase = read_passphrase("the key", true)?;
+        (ENCRYPTED_KEY_FILE_NAME, encrypt(key.as_bytes(), &passphrase)?)
+    } else {
+        (KEY_FILE_NAME, key.into_bytes())
+    };
+
+    let manifest = DataManifest {
+        format_version: ARCHIVE_FORMAT_VERSION,
+        created: chrono::Utc::now().to_rfc3339(),
+        config_dir: config_dir.clone(),
+        reports_dir: reports_dir.clone(),
+        home_dir: home_dir(),
+        public_key,
+        key_encrypted: config.encrypt_key,
+    };
+
+    let (config_files, report_files) =
+        match write_archive(&archive_file, &manifest, key_file_name, &key, &config_dir, &reports_dir) {
+            Ok(v) => v,
+            Err(e) => {
+                eprintln!(
+                    "STACKMUNCHER ERROR: cannot save the data in {} due to {}",
+                    archive_file.to_string_lossy(),
+                    e
+                );
+                let _ = std::fs::remove_file(&archive_file);
+                return Err(());
+            }
+        };
+    info!("Exported {} config and {} report files", config_files, report_files);
+
+    println!("    Data saved in:       {}", archive_file.to_string_lossy());
+    println!("    Public key:          {}", manifest.public_key);
+    println!("    Config files:        {}", config_files);
+    println!("    Report files:        {}", report_files);
+    println!();
+    println!(
+        "    Copy the file to your other machine and run `stackmuncher import-data --file {}` there.",
+        archive_file.to_string_lossy()
+    );
+    if config.encrypt_key {
+        println!("    The key is protected with the passphrase. The reports and the config are not encrypted.");
+    } else {
+        println!("    Keep the file private. Anyone with the file can update your Directory Profile. Use `--encrypt-key` to protect the key with a passphrase.");
+    }
+    println!();
+
+    Ok(())
+}
+
+/// Writes the manifest, the key, the config folder and the reports folder into a gzipped tarball in that order.
+/// Returns the number of config and report files added.
+fn write_archive(
+    archive_file: &Path,
+    manifest: &DataManifest,
+    key_file_name: &str,
+    key: &[u8],
+    config_dir: &Path,
+    reports_dir: &Path,
+) -> std::io::Result<(usize, usize)> {
+    let mut builder = tar::Builder::new(GzEncoder::new(File::create(archive_file)?, Compression::default()));
+
+    let manifest = serde_json::to_vec_pretty(manifest).expect("Cannot serialize the data manifest. It's a bug.");
+    append_bytes(&mut builder, MANIFEST_FILE_NAME, &manifest)?;
+    append_bytes(&mut builder, &[CONFIG_ARCHIVE_DIR, "/", key_file_name].concat(), key)?;
+
+    // the key is added separately and previous keys in `key.*.bak` files are not needed on the other machine
+    let config_files = append_dir(&mut builder, config_dir, Path::new(CONFIG_ARCHIVE_DIR), &|file_name| {
+        is_temp_file(file_name)
+            || file_name == KEY_FILE_NAME
+            || (file_name.starts_with("key.") && file_name.ends_with(".bak"))
+    })?;
+    let report_files = append_dir(&mut builder, reports_dir, Path::new(REPORTS_ARCHIVE_DIR), &is_temp_file)?;
+
+    builder.into_inner()?.finish()?.flush()?;
+
+    Ok((config_files, report_files))
+}
+
+/// Adds an in-memory file to the archive.
+fn append_bytes<W: Write>(builder: &mut tar::Builder<W>, archive_path: &str, contents: &[u8]) -> std::io::Result<()> {
+    let mut header = tar::Header::new_gnu();
+    header.set_size(contents.len() as u64);
+    header.set_mode(0o600);
+    header.set_mtime(chrono::Utc::now().timestamp() as u64);
+    builder.append_data(&mut header, archive_path, contents)
+}
+
+/// Adds the files from `dir` and its sub-folders under `archive_dir`, except for the ones `skip` returns true for.
+/// Returns the number of files added.
+fn append_dir<W: Write>(
+    builder: &mut tar::Builder<W>,
+    dir: &Path,
+    archive_dir: &Path,
+    skip: &dyn Fn(&str) -> bool,
+) -> std::io::Result<usize> {
+    let mut entries = std::fs::read_dir(dir)?.collect::<std::io::Result<Vec<std::fs::DirEntry>>>()?;
+    entries.sort_by_key(|v| v.file_name());
+
+    let mut files = 0;
+    for entry in entries {
+        let file_name = entry.file_name();
+        if skip(&file_name.to_string_lossy()) {
+            debug!("Skipped {}", entry.path().to_string_lossy());
+            continue;
+        }
+
+        let archive_path = archive_dir.join(&file_name);
+        let file_type = entry.file_type()?;
+        if file_type.is_dir() {
+            files += append_dir(builder, &entry.path(), &archive_path, &is_temp_file)?;
+        } else if file_type.is_file() {
+            builder.append_path_with_name(entry.path(), &archive_path)?;
+            files += 1;
+        }
+    }
+
+    Ok(files)
+}
+
+/// Returns true for lock files and temporary files left by atomic writes, which are not worth moving.
+fn is_temp_file(file_name: &str) -> bool {
+    file_name.starts_with(LOCK_FILE_NAME) || file_name.starts_with('.')
+}
+
+/// Restores the config, the key and the reports from the archive made by `export-data`. It is done by
+/// `AppConfig::new` before the config and the key are loaded.
+/// * `reports_dir` - the root reports folder on this machine, which replaces the one from the archive
+pub(crate) fn import(archive_file: &Path, config_dir: &PathBuf, reports_dir: &Path) -> Result<(), ()> {
+    let file = match File::open(archive_file) {
+        Ok(v) => v,
+        Err(e) => {
+            eprintln!("STACKMUNCHER ERROR: cannot read {} due to {}", archive_file.to_string_lossy(), e);
+            return Err(());
+        }
+    };
+
+    let (manifest, config_files, report_files) = match extract_archive(file, config_dir, reports_dir) {
+        Ok(v) => v,
+        Err(e) => {
+            eprintln!(
+                "STACKMUNCHER ERROR: cannot import the data from {} due to {}",
+                archive_file.to_string_lossy(),
+                e
+            );
+            return Err(());
+        }
+    };
+
+    // the key file is read back to make sure it is the same key that was exported
+    let key_file = get_key_file_name(config_dir);
+    let restored_public_key = std::fs::read_to_string(&key_file)
+        .ok()
+        .and_then(|v| decode_key(v.trim()))
+        .map(|v| ReportSignature::get_public_key(&v));
+    if restored_public_key.as_ref() != Some(&manifest.public_key) {
+        eprintln!(
+            "STACKMUNCHER ERROR: the key in {} does not match the exported key {}. The previous key, if any, was saved as key.*.bak in the same folder.",
+            key_file.to_string_lossy(),
+            manifest.public_key
+        );
+        return Err(());
+    }
+
+    rewrite_paths(config_dir, reports_dir, &manifest)?;
+    info!("Imported {} config and {} report files", config_files, report_files);
+
+    println!("    Data imported from:  {}", archive_file.to_string_lossy());
+    println!("    Exported on:         {}", manifest.created);
+    println!("    Public key:          {} (verified)", manifest.public_key);
+    println!("    Config files:        {}", config_files);
+    println!("    Report files:        {}", report_files);
+    println!();
+
+    Ok(())
+}
+
+/// Prints the config after `import-data` restored it in `AppConfig::new`.
+pub(crate) fn print_import_summary(config: &AppConfig) {
+    println!("    Commit emails:       {}", config.lib_config.git_identities.join(", "));
+    if let Some(reports_dir) = &config.reports_dir {
+        println!("    Reports folder:      {}", reports_dir.to_string_lossy());
+    }
+    println!();
+    println!(
+        "    Projects that were not found at the same path need to be munched again to be included in the profile."
+    );
+    println!();
+}
+
+/// Unpacks the archive into the config and reports folders. The manifest must be the first entry and the key
+/// the second one, so that nothing is unpacked from an invalid archive or with a wrong passphrase.
+/// Returns the manifest and the number of config and report files unpacked.
+fn extract_archive<R: Read>(
+    archive: R,
+    config_dir: &PathBuf,
+    reports_dir: &Path,
+) -> std::io::Result<(DataManifest, usize, usize)> {
+    let mut archive = tar::Archive::new(GzDecoder::new(archive));
+    let mut manifest: Option<DataManifest> = None;
+    let mut key_imported = false;
+    let mut config_files = 0;
+    let mut report_files = 0;
+
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        // folders are created as needed
+        if !entry.header().entry_type().is_file() {
+            continue;
+        }
+        let path = match safe_relative_path(&entry.path()?) {
+            Some(v) => v,
+            None => {
+                return Err(Error::new(
+                    ErrorKind::InvalidData,
+                    format!("invalid path {} in the archive", entry.path()?.to_string_lossy()),
+                ))
+            }
+        };
+
+        // 1. the manifest
+        let manifest = match &manifest {
+            Some(v) => v,
+            None if path == Path::new(MANIFEST_FILE_NAME) => {
+                let v = serde_json::from_reader::<_, DataManifest>(&mut entry)
+                    .map_err(|e| Error::new(ErrorKind::InvalidData, format!("invalid manifest: {}", e)))?;
+                if v.format_version != ARCHIVE_FORMAT_VERSION {
+                    return Err(Error::new(
+                        ErrorKind::InvalidData,
+                        format!("unsupported archive format {}, update the app", v.format_version),
+                    ));
+                }
+                debug!("Importing data exported on {} from {}", v.created, v.config_dir.to_string_lossy());
+                manifest = Some(v);
+                continue;
+            }
+            None => return Err(Error::new(ErrorKind::InvalidData, "the file is not a StackMuncher data archive")),
+        };
+
+        // 2. the key
+        if !key_imported {
+            let mut key = Vec::new();
+            entry.read_to_end(&mut key)?;
+            let key = if path == Path::new(CONFIG_ARCHIVE_DIR).join(ENCRYPTED_KEY_FILE_NAME) {
+                let passphrase = read_passphrase("the key", false).map_err(|_| invalid_key_error())?;
+                decrypt(&key, &passphrase).map_err(|_| invalid_key_error())?
+            } else if path == Path::new(CONFIG_ARCHIVE_DIR).join(KEY_FILE_NAME) && !manifest.key_encrypted {
+                key
+            } else {
+                return Err(Error::new(ErrorKind::InvalidData, "the key is missing"));
+            };
+            let key = String::from_utf8(key).map_err(|_| invalid_key_error())?;
+            save_key(key.trim(), config_dir).map_err(|_| invalid_key_error())?;
+            key_imported = true;
+            continue;
+        }
+
+        // 3. config and reports files
+        let target = if let Ok(v) = path.strip_prefix(CONFIG_ARCHIVE_DIR) {
+            let target = config_dir.join(v);
+            // keep the config of this machine in case the import was a mistake
+            if v == Path::new(APP_CONFIG_FILE_NAME) && target.exists() {
+                let backup_file = config_dir.join([APP_CONFIG_FILE_NAME, ".bak"].concat());
+                std::fs::copy(&target, &backup_file)?;
+                println!("    Previous config saved in: {}", backup_file.to_string_lossy());
+            }
+            config_files += 1;
+            target
+        } else if let Ok(v) = path.strip_prefix(REPORTS_ARCHIVE_DIR) {
+            report_files += 1;
+            reports_dir.join(v)
+        } else {
+            debug!("Skipped {} / unknown folder", path.to_string_lossy());
+            continue;
+        };
+
+        if let Some(parent) = target.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        entry.unpack(&target)?;
+    }
+
+    match manifest {
+        Some(v) if key_imported => Ok((v, config_files, report_files)),
+        _ => Err(Error::new(ErrorKind::InvalidData, "the archive is incomplete")),
+    }
+}
+
+/// The details are printed by the functions that failed.
+fn invalid_key_error() -> Error {
+    Error::new(ErrorKind::InvalidData, "the key cannot be imported")
+}
+
+/// Returns the path if it only has normal components, e.g. `reports/project/report.json`, or None if it is absolute
+/// or has `..` in it.
+fn safe_relative_path(path: &Path) -> Option<PathBuf> {
+    let mut safe_path = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::Normal(v) => safe_path.push(v),
+            Component::CurDir => {}
+            Component::ParentDir | Component::RootDir | Component::Prefix(_) => return None,
+        }
+    }
+
+    if safe_path.as_os_str().is_empty() {
+        None
+    } else {
+        Some(safe_path)
+    }
+}
+
+/// Points `reports_dir` in the imported config at the reports folder on this machine and moves the watched projects
+/// from the home folder of the exporting machine to the home folder on this one if they exist there, e.g.
+/// `/home/max/rust/stm_app` -> `/Users/max/rust/stm_app`. The report folders of the moved projects are renamed to match.
+fn rewrite_paths(config_dir: &Path, reports_dir: &Path, manifest: &DataManifest) -> Result<(), ()> {
+    let config_file = config_dir.join(APP_CONFIG_FILE_NAME);
+    let mut app_config = match std::fs::read(&config_file)
+        .ok()
+        .and_then(|v| serde_json::from_slice::<serde_json::Value>(&v).ok())
+    {
+        Some(serde_json::Value::Object(v)) => v,
+        _ => {
+            debug!("No config to rewrite in {}", config_file.to_string_lossy());
+            return Ok(());
+        }
+    };
+
+    app_config.insert("reports_dir".to_owned(), serde_json::json!(reports_dir));
+
+    let home_dir = home_dir();
+    if let Some(serde_json::Value::Array(watch_projects)) = app_config.get_mut("watch_projects") {
+        for watch_project in watch_projects.iter_mut() {
+            let project = match watch_project.as_str() {
+                Some(v) => PathBuf::from(v),
+                None => continue,
+            };
+            if project.exists() {
+                continue;
+            }
+
+            let moved_project = match (&manifest.home_dir, &home_dir) {
+                (Some(old_home), Some(new_home)) => rehome(&project, old_home, new_home).filter(|v| v.exists()),
+                _ => None,
+            };
+            let moved_project = match moved_project {
+                Some(v) => v,
+                None => {
+                    println!("    Watched project not found: {}", project.to_string_lossy());
+                    continue;
+                }
+            };
+
+            let old_report_dir = reports_dir.join(project_report_dir_name(&project));
+            let new_report_dir = reports_dir.join(project_report_dir_name(&moved_project));
+            if old_report_dir.is_dir() && !new_report_dir.exists() {
+                if let Err(e) = std::fs::rename(&old_report_dir, &new_report_dir) {
+                    eprintln!(
+                        "STACKMUNCHER ERROR: cannot rename {} to {} due to {}",
+                        old_report_dir.to_string_lossy(),
+                        new_report_dir.to_string_lossy(),
+                        e
+                    );
+                    return Err(());
+                }
+            }
+
+            println!(
+                "    Watched project moved: {} -> {}",
+                project.to_string_lossy(),
+                moved_project.to_string_lossy()
+            );
+            *watch_project = serde_json::json!(moved_project);
+        }
+    }
+
+    let contents = serde_json::to_vec_pretty(&app_config).expect("Cannot serialize the config. It's a bug.");
+    if let Err(e) = write_atomically(&config_file, &contents) {
+        eprintln!("STACKMUNCHER ERROR: cannot save {} due to {}", config_file.to_string_lossy(), e);
+        return Err(());
+    }
+
+    Ok(())
+}
+
+/// Replaces `old_home` at the start of the path with `new_home`. Returns None if the path is outside `old_home`.
+fn rehome(path: &Path, old_home: &Path, new_home: &Path) -> Option<PathBuf> {
+    path.strip_prefix(old_home).ok().map(|v| new_home.join(v))
+}
+
+/// Returns the home folder of the current user from `HOME` or `HOMEDRIVE` + `HOMEPATH` on Windows.
+fn home_dir() -> Option<PathBuf> {
+    match std::env::var("HOME") {
+        Ok(v) if !v.is_empty() => Some(PathBuf::from(v)),
+        _ => match (std::env::var("HOMEDRIVE"), std::env::var("HOMEPATH")) {
+            (Ok(drive), Ok(path)) => Some(PathBuf::from([drive, path].concat())),
+            _ => None,
+        },
+    }
+}
+
+#[cfg(test)]
+mod test_cmd_data {
+    use super::{
+        extract_archive, rehome, rewrite_paths, safe_relative_path, write_archive, DataManifest,
+        ARCHIVE_FORMAT_VERSION, KEY_FILE_NAME,
+    };
+    use crate::config::APP_CONFIG_FILE_NAME;
+    use crate::lock::LOCK_FILE_NAME;
+    use crate::signing::{get_key_file_name, ReportSignature};
+    use ring::rand::SystemRandom;
+    use ring::signature::Ed25519KeyPair;
+    use std::fs::File;
+    use std::path::{Path, PathBuf};
+
+    #[test]
+    fn test_safe_relative_path() {
+        assert_eq!(
+            safe_relative_path(Path::new("reports/./project/report.json")),
+            Some(PathBuf::from("reports/project/report.json"))
+        );
+        assert!(safe_relative_path(Path::new("reports/../../etc/passwd")).is_none());
+        assert!(safe_relative_path(Path::new("/etc/passwd")).is_none());
+        assert!(safe_relative_path(Path::new("./")).is_none());
+    }
+
+    #[test]
+    fn test_rehome() {
+        assert_eq!(
+            rehome(Path::new("/home/max/rust/stm_app"), Path::new("/home/max"), Path::new("/Users/max")),
+            Some(PathBuf::from("/Users/max/rust/stm_app"))
+        );
+        assert!(rehome(Path::new("/var/projects/stm_app"), Path::new("/home/max"), Path::new("/Users/max")).is_none());
+    }
+
+    /// Returns a new key as it is saved in the key file and its public key.
+    fn new_key() -> (String, String) {
+        let pkcs8 = Ed25519KeyPair::generate_pkcs8(&SystemRandom::new()).unwrap();
+        let key_pair = Ed25519KeyPair::from_pkcs8(pkcs8.as_ref()).unwrap();
+        (bs58::encode(pkcs8.as_ref()).into_string(), ReportSignature::get_public_key(&key_pair))
+    }
+
+    #[test]
+    fn test_export_import() {
+        let test_dir = std::env::temp_dir().join(format!("stm_data_test_{}", std::process::id()));
+        let (old_config_dir, old_reports_dir) = (test_dir.join("old_config"), test_dir.join("old_reports"));
+        let (new_config_dir, new_reports_dir) = (test_dir.join("new_config"), test_dir.join("new_reports"));
+        let project_report_dir = old_reports_dir.join("project_1");
+        std::fs::create_dir_all(&old_config_dir).unwrap();
+        std::fs::create_dir_all(&project_report_dir).unwrap();
+        std::fs::create_dir_all(&new_config_dir).unwrap();
+
+        // the key and the config folder of the exporting machine, including files that should not be moved
+        let (key, public_key) = new_key();
+        std::fs::write(get_key_file_name(&old_config_dir), &key).unwrap();
+        std::fs::write(old_config_dir.join("key.abc.bak"), "old key").unwrap();
+        std::fs::write(old_config_dir.join(LOCK_FILE_NAME), "").unwrap();
+        let app_config = serde_json::json!({ "reports_dir": old_reports_dir, "watch_projects": [test_dir] });
+        std::fs::write(old_config_dir.join(APP_CONFIG_FILE_NAME), app_config.to_string()).unwrap();
+        std::fs::write(project_report_dir.join("project_report.json"), "{}").unwrap();
+
+        // a different key on this machine is replaced and backed up
+        let (current_key, current_public_key) = new_key();
+        std::fs::write(get_key_file_name(&new_config_dir), &current_key).unwrap();
+
+        let manifest = DataManifest {
+            format_version: ARCHIVE_FORMAT_VERSION,
+            created: chrono::Utc::now().to_rfc3339(),
+            config_dir: old_config_dir.clone(),
+            reports_dir: old_reports_dir.clone(),
+            home_dir: None,
+            public_key: public_key.clone(),
+            key_encrypted: false,
+        };
+        let archive_file = test_dir.join("stm-backup.tar.gz");
+        let exported =
+            write_archive(&archive_file, &manifest, KEY_FILE_NAME, key.as_bytes(), &old_config_dir, &old_reports_dir)
+                .unwrap();
+        assert_eq!(exported, (1, 1));
+
+        let (imported_manifest, config_files, report_files) =
+            extract_archive(File::open(&archive_file).unwrap(), &new_config_dir, &new_reports_dir).unwrap();
+        assert_eq!((config_files, report_files), (1, 1));
+        assert_eq!(imported_manifest.public_key, public_key);
+        assert_eq!(
+            std::fs::read_to_string(get_key_file_name(&new_config_dir))
+                .unwrap()
+                .trim(),
+            key
+        );
+        assert!(!new_config_dir.join("key.abc.bak").exists());
+        assert!(!new_config_dir.join(LOCK_FILE_NAME).exists());
+        assert!(new_reports_dir.join("project_1").join("project_report.json").exists());
+        let backup_file = new_config_dir.join(["key.", &current_public_key, ".bak"].concat());
+        assert_eq!(std::fs::read_to_string(backup_file).unwrap(), current_key);
+
+        // the config points at the reports folder of this machine and keeps the projects that exist
+        rewrite_paths(&new_config_dir, &new_reports_dir, &imported_manifest).unwrap();
+        let app_config: serde_json::Value =
+            serde_json::from_slice(&std::fs::read(new_config_dir.join(APP_CONFIG_FILE_NAME)).unwrap()).unwrap();
+        assert_eq!(app_config["reports_dir"], serde_json::json!(new_reports_dir));
+        assert_eq!(app_config["watch_projects"], serde_json::json!([test_dir]));
+
+        // an archive that does not start with the manifest is rejected
+        std::fs::write(&archive_file, "not an archive").unwrap();
+        assert!(extract_archive(File::open(&archive_file).unwrap(), &new_config_dir, &new_reports_dir).is_err());
+
+        std::fs::remove_dir_all(&test_dir).unwrap();
+    }
+}
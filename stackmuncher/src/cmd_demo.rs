@@ -0,0 +1,175 @@
+use crate::cmd_munch::get_per_tech_stats;
+use crate::cmd_show::print_hotspots;
+use crate::config::AppConfig;
+use crate::signing::ReportSignature;
+use stackmuncher_lib::analyzer::{AnalysisOptions, AnalysisOutcome, Analyzer};
+use stackmuncher_lib::bench::{generate_synthetic_repo, SyntheticRepoSpec};
+use stackmuncher_lib::code_rules::CodeRules;
+use std::path::Path;
+
+/// The sample repo is small enough to be analyzed in a few seconds.
+const DEMO_REPO_SPEC: SyntheticRepoSpec = SyntheticRepoSpec {
+    files: 60,
+    commits: 200,
+    contributors: 3,
+};
+
+/// The commit email of the sample contributor the demo reports are generated for.
+const DEMO_IDENTITY: &str = "dev0@example.com";
+
+/// Analyzes a generated sample repo in a temp folder as if the user was one of its contributors and prints the results
+/// with an explanation of every section. The repo and the reports are removed at the end. Nothing is submitted
+/// to the Directory and the reports folder is not used.
+pub(crate) async fn run(config: AppConfig) -> Result<(), ()> {
+    let demo_dir = std::env::temp_dir().join(format!("stm_demo_{}", std::process::id()));
+
+    println!();
+    println!(
+        "    This is a tour of what StackMuncher does with a sample project. Nothing is submitted to the Directory."
+    );
+    println!(
+        "    The sample has {} files and {} commits by {} developers. You are {} for the demo.",
+        DEMO_REPO_SPEC.files, DEMO_REPO_SPEC.commits, DEMO_REPO_SPEC.contributors, DEMO_IDENTITY
+    );
+
+    let analysis = analyze_sample(&config, &demo_dir).await;
+
+    // the sample is no longer needed, even if the analysis failed
+    if let Err(e) = std::fs::remove_dir_all(&demo_dir) {
+        eprintln!(
+            "STACKMUNCHER ERROR: cannot remove the sample project from {} due to {}",
+            demo_dir.to_string_lossy(),
+            e
+        );
+    }
+
+    let analysis = match analysis {
+        Ok(v) => v,
+        Err(_) => {
+            eprintln!("STACKMUNCHER ERROR: the demo failed. Run with `--log error` for details.");
+            return Err(());
+        }
+    };
+
+    print_tour(&config, &analysis);
+
+    Ok(())
+}
+
+/// Generates the sample repo in `demo_dir` and runs the same analysis as `stackmuncher` command on it.
+async fn analyze_sample(config: &AppConfig, demo_dir: &Path) -> Result<AnalysisOutcome, ()> {
+    let repo_dir = demo_dir.join("repo");
+    generate_synthetic_repo(&repo_dir, &DEMO_REPO_SPEC).await?;
+
+    let report_dir = demo_dir.join("reports");
+    if let Err(e) = std::fs::create_dir_all(&report_dir) {
+        eprintln!("STACKMUNCHER ERROR: cannot create {} due to {}", report_dir.to_string_lossy(), e);
+        return Err(());
+    }
+
+    let mut code_rules = CodeRules::new();
+    code_rules.munch_limits = config.munch_limits.clone();
+
+    let options = AnalysisOptions {
+        report_dir,
+        git_identities: vec![DEMO_IDENTITY.to_owned()],
+        co_change_limits: config.co_change_limits.clone(),
+        ..Default::default()
+    };
+
+    Analyzer::new(code_rules)
+        .analyze_repo(&repo_dir, &options)
+        .await
+        .map_err(|_| ())
+}
+
+/// Prints the sections of the sample reports with what they mean.
+fn print_tour(config: &AppConfig, analysis: &AnalysisOutcome) {
+    let project_report = &analysis.project_report;
+
+    println!();
+    println!("1. PROJECT REPORT");
+    println!();
+    println!("    Every file at HEAD is matched to a language and counted line by line. Keywords and library imports");
+    println!("    show what the code is built with. The whole project is analyzed to give your work context.");
+    println!();
+    println!("    Summary (LoC/libs):  {}", get_per_tech_stats(project_report, config.loc_weighting));
+    println!(
+        "    Project size:        {} commits, {} contributors",
+        project_report.commit_count_project.unwrap_or_default(),
+        project_report
+            .contributors
+            .as_ref()
+            .map(|v| v.len())
+            .unwrap_or_default()
+    );
+
+    println!();
+    println!("2. YOUR CONTRIBUTIONS");
+    println!();
+    println!("    Only the files you changed are counted towards your stack, as they were after your last change.");
+    println!("    StackMuncher finds your commits by the emails from `git config` or `--emails`.");
+    println!();
+    let combined_report = match &analysis.combined_report {
+        Some(v) => v,
+        None => {
+            println!("    No commits from {} were found in the sample.", DEMO_IDENTITY);
+            return;
+        }
+    };
+    println!("    Summary (LoC/libs):  {}", get_per_tech_stats(combined_report, config.loc_weighting));
+    println!(
+        "    Your commits:        {} from {} to {}",
+        combined_report.commit_count_contributor.unwrap_or_default(),
+        combined_report
+            .first_contributor_commit_date_iso
+            .as_deref()
+            .unwrap_or_default(),
+        combined_report
+            .last_contributor_commit_date_iso
+            .as_deref()
+            .unwrap_or_default()
+    );
+
+    println!();
+    println!("3. HOTSPOTS");
+    println!();
+    println!("    Large files that change often are the first candidates for refactoring. The list stays on your");
+    println!("    computer. Run `stackmuncher show --hotspots` in your project to see it.");
+    print_hotspots(project_report);
+
+    println!("4. WHAT IS SENT TO THE DIRECTORY");
+    println!();
+    println!("    Only the summary of your contributions is sent. File names are replaced with salted hashes. Commit");
+    println!("    messages and the project report never leave your computer. Nothing was sent during this demo.");
+    println!();
+    match combined_report.sanitize(ReportSignature::get_salt(&config.user_key_pair), &config.scrub_rules) {
+        Ok(sanitized_report) => {
+            let mut languages = sanitized_report
+                .tech
+                .iter()
+                .map(|tech| tech.language.as_str())
+                .collect::<Vec<&str>>();
+            languages.sort_unstable();
+            println!("    Languages:           {}", languages.join(", "));
+            println!(
+                "    File records:        {}, e.g. {}",
+                sanitized_report.per_file_tech.len(),
+                sanitized_report
+                    .per_file_tech
+                    .iter()
+                    .filter_map(|tech| tech.file_name.as_deref())
+                    .next()
+                    .unwrap_or_default()
+            );
+        }
+        Err(_) => println!("    The submission could not be prepared. Run with `--log error` for details."),
+    }
+
+    println!();
+    println!("NEXT STEPS");
+    println!();
+    println!("    Run `stackmuncher` inside one of your projects to analyze it. The first run is always a dry run.");
+    println!("    Run `stackmuncher help` for all options.");
+    println!();
+}
@@ -0,0 +1,266 @@
+use crate::cmd_rollup::{self, DuplicateClone};
+use crate::config::{self, AppConfig};
+#[cfg(feature = "cloud")]
+use crate::help;
+use crate::lock::ProjectLock;
+use crate::postprocess::RUN_LOGS_DIR_NAME;
+#[cfg(feature = "cloud")]
+use crate::signing;
+use stackmuncher_lib::cache_check::{self, CacheIssue};
+use stackmuncher_lib::config::Config;
+use stackmuncher_lib::git_log_cache::GIT_LOG_CACHE_DIR_NAME;
+use std::path::{Path, PathBuf};
+
+/// The extension added to a damaged config file set aside by `--fix`, e.g. `config.json.broken`.
+const BROKEN_FILE_EXTENSION: &str = ".broken";
+
+/// Checks the config, the key and the reports and caches of all projects in the reports folder and prints the problems.
+/// Removes the damaged reports and caches if `--fix` was used. They are regenerated on the next run.
+/// Returns an error if any problems were left unfixed.
+pub(crate) async fn run(config: &AppConfig) -> Result<(), ()> {
+    let mut unfixed = 0usize;
+
+    println!();
+    println!("Config:");
+    match config::check_config_file(&config.config_file_path) {
+        Ok(_) => println!("    {}: OK", config.config_file_path.to_string_lossy()),
+        Err(e) => {
+            println!("    {}: corrupted: {}", config.config_file_path.to_string_lossy(), e);
+            if config.fix {
+                let mut broken_file = config.config_file_path.clone().into_os_string();
+                broken_file.push(BROKEN_FILE_EXTENSION);
+                let broken_file = PathBuf::from(broken_file);
+                match std::fs::rename(&config.config_file_path, &broken_file) {
+                    Ok(_) => println!(
+                        "        moved to {}, the defaults will be used on the next run",
+                        broken_file.to_string_lossy()
+                    ),
+                    Err(e) => {
+                        println!("        cannot be moved due to {}", e);
+                        unfixed += 1;
+                    }
+                }
+            } else {
+                unfixed += 1;
+            }
+        }
+    }
+
+    // the key identifies the Directory Profile, so it is never replaced automatically
+    #[cfg(feature = "cloud")]
+    {
+        let config_dir = config
+            .config_file_path
+            .parent()
+            .unwrap_or_else(|| Path::new("."))
+            .to_path_buf();
+        let key_file = signing::get_key_file_name(&config_dir);
+        if !key_file.exists() {
+            println!(
+                "    {}: not found, a new key will be generated on the next run",
+                key_file.to_string_lossy()
+            );
+        } else if let Err(e) = signing::read_key_pair(&config_dir) {
+            println!("    {}: {}", key_file.to_string_lossy(), e);
+            help::emit_key_err_msg(&key_file.to_string_lossy());
+            unfixed += 1;
+        } else {
+            println!("    {}: OK", key_file.to_string_lossy());
+        }
+    }
+
+    let reports_dir = config
+        .reports_dir
+        .as_ref()
+        .expect("Cannot unwrap config.reports_dir. It's a bug.");
+
+    println!();
+    println!("Reports: {}", reports_dir.to_string_lossy());
+    let report_dirs = list_report_dirs(reports_dir);
+    let mut problems = 0usize;
+    for report_dir in &report_dirs {
+        let issues = cache_check::check_report_dir(report_dir);
+        if issues.is_empty() {
+            continue;
+        }
+        problems += issues.len();
+        unfixed += fix_issues(report_dir, &issues, config.fix);
+    }
+    println!("    Checked {} folders, found {} damaged or outdated files", report_dirs.len(), problems);
+
+    // clones of the same remote at different paths are counted once in rollups, but their caches take up space
+    if let Ok(combined_reports) = cmd_rollup::load_combined_reports(reports_dir) {
+        for clone in &combined_reports.clones {
+            if !config.fix {
+                println!(
+                    "    {}: a clone of {}",
+                    clone.report_dir.to_string_lossy(),
+                    clone.kept_report_dir.to_string_lossy()
+                );
+                unfixed += 1;
+                continue;
+            }
+            match merge_clone(clone) {
+                Ok(_) => println!(
+                    "    {}: a clone of {} - merged",
+                    clone.report_dir.to_string_lossy(),
+                    clone.kept_report_dir.to_string_lossy()
+                ),
+                Err(e) => {
+                    println!(
+                        "    {}: a clone of {} - cannot be merged due to {}",
+                        clone.report_dir.to_string_lossy(),
+                        clone.kept_report_dir.to_string_lossy(),
+                        e
+                    );
+                    unfixed += 1;
+                }
+            }
+        }
+    }
+
+    // the report of the current project is only reused if it was built from the same history
+    let project_dir = &config.lib_config.project_dir;
+    let project_report_file = reports_dir
+        .join(config::project_report_dir_name(project_dir))
+        .join([Config::PROJECT_REPORT_FILE_NAME, Config::REPORT_FILE_EXTENSION].concat());
+    if project_dir.join(Config::GIT_FOLDER_NAME).exists() || project_dir.join(Config::HG_FOLDER_NAME).exists() {
+        println!();
+        println!("Project: {}", project_dir.to_string_lossy());
+        match config.report_store.load(&project_report_file) {
+            None => println!("    Not analyzed yet. Run `stackmuncher` in the project folder."),
+            Some(report) => match cache_check::is_log_current(project_dir, &report).await {
+                Ok(true) => println!("    The report is up to date with the git log"),
+                Ok(false) => println!(
+                    "    The report is out of date with the git log and will be updated on the next run. Run `stackmuncher` in the project folder to update it now."
+                ),
+                Err(_) => println!("    Cannot read the git log of the project."),
+            },
+        }
+    }
+
+    println!();
+    if unfixed == 0 {
+        println!("No problems left.");
+        println!();
+        Ok(())
+    } else {
+        if !config.fix {
+            println!("Found {} problems. Run `stackmuncher doctor --fix` to remove the damaged files.", unfixed);
+        } else {
+            println!("{} problems could not be fixed.", unfixed);
+        }
+        println!();
+        Err(())
+    }
+}
+
+/// Returns the reports folder, the folders of all projects in it and their sub-folders for other refs and dates.
+fn list_report_dirs(reports_dir: &Path) -> Vec<PathBuf> {
+    let mut report_dirs = vec![reports_dir.to_path_buf()];
+    for project_dir in list_sub_dirs(reports_dir) {
+        report_dirs.extend(list_sub_dirs(&project_dir));
+        report_dirs.push(project_dir);
+    }
+    report_dirs.sort();
+
+    report_dirs
+}
+
+/// Returns the sub-folders of `dir` other than the git log cache and the run logs. Symlinks to folders of merged clones are not followed.
+/// Returns an empty list if `dir` cannot be listed.
+fn list_sub_dirs(dir: &Path) -> Vec<PathBuf> {
+    match std::fs::read_dir(dir) {
+        Ok(v) => v
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| entry.file_type().map(|v| v.is_dir()).unwrap_or_default())
+            .map(|entry| entry.path())
+            .filter(|path| !path.ends_with(GIT_LOG_CACHE_DIR_NAME) && !path.ends_with(RUN_LOGS_DIR_NAME))
+            .collect(),
+        Err(_) => Vec::new(),
+    }
+}
+
+/// Replaces the report folder of a clone with a symlink to the report folder of the more recently analyzed clone,
+/// so that both clones share the same reports and caches from the next run. A folder locked by another run is left as-is.
+fn merge_clone(clone: &DuplicateClone) -> std::io::Result<()> {
+    let mut lock = match ProjectLock::acquire(&clone.report_dir) {
+        Ok(v) => v,
+        Err(_) => {
+            return Err(std::io::Error::other(format!(
+                "a lock by another instance of the app ({})",
+                ProjectLock::holder(&clone.report_dir).unwrap_or_else(|| "unknown".to_owned())
+            )))
+        }
+    };
+
+    // the folder is moved aside with the lock in it and replaced with the symlink before the lock is released,
+    // so that another run cannot start using the folder while it is being removed
+    let mut merged_dir_name = clone.report_dir.file_name().unwrap_or_default().to_os_string();
+    merged_dir_name.push(".merged");
+    let merged_dir = clone.report_dir.with_file_name(merged_dir_name);
+    std::fs::rename(&clone.report_dir, &merged_dir)?;
+    lock.moved_to(&merged_dir);
+
+    if let Err(e) = symlink_dir(&clone.kept_report_dir, &clone.report_dir) {
+        // put the folder back as it was
+        if std::fs::rename(&merged_dir, &clone.report_dir).is_ok() {
+            lock.moved_to(&clone.report_dir);
+        }
+        return Err(e);
+    }
+    drop(lock);
+
+    std::fs::remove_dir_all(&merged_dir)
+}
+
+#[cfg(unix)]
+fn symlink_dir(original: &Path, link: &Path) -> std::io::Result<()> {
+    std::os::unix::fs::symlink(original, link)
+}
+
+#[cfg(windows)]
+fn symlink_dir(original: &Path, link: &Path) -> std::io::Result<()> {
+    std::os::windows::fs::symlink_dir(original, link)
+}
+
+/// Prints the issues and removes the damaged files if `fix` is true. A folder locked by another run is left as-is.
+/// Returns the number of issues that were not fixed.
+fn fix_issues(report_dir: &Path, issues: &[CacheIssue], fix: bool) -> usize {
+    println!("    {}", report_dir.to_string_lossy());
+
+    // the files may be in use by another run
+    let lock = if fix {
+        match ProjectLock::acquire(report_dir) {
+            Ok(v) => Some(v),
+            Err(_) => {
+                println!(
+                    "        locked by another instance of the app ({}), try again later",
+                    ProjectLock::holder(report_dir).unwrap_or_else(|| "unknown".to_owned())
+                );
+                None
+            }
+        }
+    } else {
+        None
+    };
+
+    let mut unfixed = 0;
+    for issue in issues {
+        let file_name = issue.path.file_name().unwrap_or_default().to_string_lossy();
+        if lock.is_none() {
+            println!("        {}: {}", file_name, issue.problem);
+            unfixed += 1;
+            continue;
+        }
+        match issue.fix() {
+            Ok(_) => println!("        {}: {} - removed", file_name, issue.problem),
+            Err(e) => {
+                println!("        {}: {} - cannot be removed due to {}", file_name, issue.problem, e);
+                unfixed += 1;
+            }
+        }
+    }
+
+    unfixed
+}
@@ -0,0 +1,63 @@
+use crate::app_args::ExportFormat;
+use crate::config::AppConfig;
+use stackmuncher_lib::{config::Config, report::Report};
+use std::path::Path;
+
+/// Prints a summary of the combined contributor report of the project generated by a previous run
+/// for pasting into a resume or a GitHub profile README. The summary is saved into `config.graph_out` if set.
+/// The report of a `--ref` run is taken from the ref sub-folder of the project reports.
+pub(crate) fn run(config: &AppConfig) -> Result<(), ()> {
+    let report_dir = Path::new(
+        config
+            .lib_config
+            .project_report_dir
+            .as_ref()
+            .expect("Cannot unwrap config.report_dir. It's a bug."),
+    );
+
+    let combined_report_filename = report_dir.join(
+        [
+            Config::CONTRIBUTOR_REPORT_COMBINED_FILE_NAME,
+            Config::REPORT_FILE_EXTENSION,
+        ]
+        .concat(),
+    );
+    let combined_report = match Report::from_disk(&combined_report_filename) {
+        Some(v) => v,
+        None => {
+            eprintln!(
+                "STACKMUNCHER ERROR: no contributor report for {}",
+                config.lib_config.project_dir.to_string_lossy()
+            );
+            match &config.git_ref {
+                Some(git_ref) => eprintln!(
+                    "    Run `stackmuncher --ref {}` inside the project folder to analyze your commits first.",
+                    git_ref
+                ),
+                None => eprintln!("    Run `stackmuncher` inside the project folder to analyze your commits first."),
+            }
+            return Err(());
+        }
+    };
+
+    let output = match config.export_format {
+        ExportFormat::Markdown => combined_report.to_markdown(),
+    };
+
+    match &config.graph_out {
+        None => print!("{}", output),
+        Some(out_file) => {
+            if let Err(e) = std::fs::write(out_file, output) {
+                eprintln!(
+                    "STACKMUNCHER ERROR: cannot save the summary in {} due to {}",
+                    out_file.to_string_lossy(),
+                    e
+                );
+                return Err(());
+            }
+            println!("    Summary saved in {}", out_file.to_string_lossy());
+        }
+    }
+
+    Ok(())
+}
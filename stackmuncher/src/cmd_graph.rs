@@ -0,0 +1,57 @@
+use crate::app_args::GraphFormat;
+use crate::config::AppConfig;
+use stackmuncher_lib::{config::Config, report::DependencyGraph, report::Report};
+use std::path::Path;
+use tracing::info;
+
+/// Exports the dependency graph of the project files from the project report generated by a previous run.
+/// The graph is printed to stdout or saved into `config.graph_out`.
+pub(crate) fn run(config: AppConfig) -> Result<(), ()> {
+    let report_dir = Path::new(
+        config
+            .lib_config
+            .project_report_dir
+            .as_ref()
+            .expect("Cannot unwrap config.report_dir. It's a bug."),
+    );
+
+    let project_report_filename =
+        report_dir.join([Config::PROJECT_REPORT_FILE_NAME, Config::REPORT_FILE_EXTENSION].concat());
+    let project_report = match Report::from_disk(&project_report_filename) {
+        Some(v) => v,
+        None => {
+            eprintln!(
+                "STACKMUNCHER ERROR: no project report for {}",
+                config.lib_config.project_dir.to_string_lossy()
+            );
+            eprintln!("    Run `stackmuncher` inside the project folder to analyze it first.");
+            return Err(());
+        }
+    };
+
+    let graph = DependencyGraph::from_report(&project_report, config.graph_external);
+    info!("Dependency graph: {} nodes, {} edges", graph.nodes.len(), graph.edges.len());
+
+    let output = match config.graph_format {
+        GraphFormat::Dot => graph.to_dot(),
+        GraphFormat::Json => serde_json::to_string_pretty(&graph).expect("Cannot serialize the graph. It's a bug."),
+    };
+
+    match &config.graph_out {
+        None => println!("{}", output),
+        Some(out_file) => {
+            if let Err(e) = std::fs::write(out_file, output) {
+                eprintln!("STACKMUNCHER ERROR: cannot save the graph in {} due to {}", out_file.to_string_lossy(), e);
+                return Err(());
+            }
+            println!(
+                "    Dependency graph with {} nodes and {} edges saved in {}",
+                graph.nodes.len(),
+                graph.edges.len(),
+                out_file.to_string_lossy()
+            );
+        }
+    }
+
+    Ok(())
+}
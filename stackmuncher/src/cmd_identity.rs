@@ -0,0 +1,316 @@
+use crate::app_args::IdentityAction;
+use crate::config::AppConfig;
+use crate::signing::{get_key_file_name, ReportSignature};
+use ring::aead::{Aad, LessSafeKey, Nonce, UnboundKey, CHACHA20_POLY1305, NONCE_LEN};
+use ring::rand::{SecureRandom, SystemRandom};
+use ring::{pbkdf2, signature::Ed25519KeyPair};
+use serde::{Deserialize, Serialize};
+use std::num::NonZeroU32;
+use std::path::{Path, PathBuf};
+use tracing::{debug, info};
+
+/// The default name of the identity bundle file in the current folder.
+pub(crate) const IDENTITY_BUNDLE_FILE_NAME: &str = "stackmuncher_identity.txt";
+/// An env var with the bundle passphrase for non-interactive use, e.g. in provisioning scripts.
+const PASSPHRASE_ENV_VAR: &str = "STACKMUNCHER_PASSPHRASE";
+/// The first bytes of a decoded bundle. Changes if the format or the crypto changes.
+const BUNDLE_MAGIC: &[u8] = b"STMID1";
+const SALT_LEN: usize = 16;
+const PBKDF2_ITERATIONS: u32 = 100_000;
+/// A short passphrase would make the bundle easy to brute-force if it leaks.
+const MIN_PASSPHRASE_LENGTH: usize = 8;
+
+/// Everything needed to submit under the same Directory Profile from another machine.
+/// It is serialized as JSON, encrypted with a passphrase and encoded as base58 text for easy copying.
+#[derive(Serialize, Deserialize)]
+pub(crate) struct IdentityBundle {
+    /// The contents of the key file: a base58-encoded PKCS8 document with the Ed25519 key-pair.
+    pub key: String,
+    /// Cached commit emails
+    pub git_identities: Vec<String>,
+    /// The Gist ID signed with the key from this bundle
+    pub gh_validation_id: Option<String>,
+    pub gh_login: Option<String>,
+}
+
+/// Exports the identity bundle or prints the result of the import, which is done by `AppConfig::new` before
+/// the key-pair is loaded.
+pub(crate) fn run(config: &AppConfig) -> Result<(), ()> {
+    match config.identity_action {
+        Some(IdentityAction::Export) => export(config, &config.identity_file),
+        Some(IdentityAction::Import) => {
+            println!("    Commit emails: {}", config.lib_config.git_identities.join(", "));
+            if let Some(gh_login) = &config.gh_login {
+                println!("    GitHub login:  {}", gh_login);
+            }
+            println!();
+            println!("    Reports from this machine will be submitted to the same Directory Profile as from the exporting machine.");
+            println!();
+            Ok(())
+        }
+        None => {
+            eprintln!("STACKMUNCHER ERROR: no action for `identity` command. It's a bug.");
+            Err(())
+        }
+    }
+}
+
+/// Saves the key-pair and the cached identities of this machine in an encrypted bundle at `bundle_file`.
+fn export(config: &AppConfig, bundle_file: &Path) -> Result<(), ()> {
+    let config_dir = config
+        .config_file_path
+        .parent()
+        .expect("Cannot get the config folder from the config file path. It's a bug.")
+        .to_path_buf();
+    let key_file = get_key_file_name(&config_dir);
+    let key = match std::fs::read_to_string(&key_file) {
+        Ok(v) => v.trim().to_owned(),
+        Err(e) => {
+            eprintln!("STACKMUNCHER ERROR: cannot read the key file {} due to {}", key_file.to_string_lossy(), e);
+            return Err(());
+        }
+    };
+
+    let bundle = IdentityBundle {
+        key,
+        git_identities: config.lib_config.git_identities.clone(),
+        gh_validation_id: config.gh_validation_id.clone(),
+        gh_login: config.gh_login.clone(),
+    };
+
+    let passphrase = read_passphrase("the identity bundle", true)?;
+    let encrypted = encrypt(
+        &serde_json::to_vec(&bundle).expect("Cannot serialize the identity bundle. It's a bug."),
+        &passphrase,
+    )?;
+
+    if let Err(e) = std::fs::write(bundle_file, encrypted) {
+        eprintln!(
+            "STACKMUNCHER ERROR: cannot save the identity bundle in {} due to {}",
+            bundle_file.to_string_lossy(),
+            e
+        );
+        return Err(());
+    }
+
+    println!("    Identity saved in: {}", bundle_file.to_string_lossy());
+    println!("    Public key:        {}", ReportSignature::get_public_key(&config.user_key_pair));
+    println!("    Commit emails:     {}", bundle.git_identities.join(", "));
+    println!();
+    println!(
+        "    Copy the file to your other machine and run `stackmuncher identity import --file {}` there.",
+        bundle_file.to_string_lossy()
+    );
+    println!("    Keep the file private. Anyone with the file and the passphrase can update your Directory Profile.");
+    println!();
+
+    Ok(())
+}
+
+/// Decrypts the bundle and replaces the key file in `config_dir` with the key from the bundle.
+/// The previous key is kept as `key.<public key>.bak` if it is different.
+/// Returns the bundle for merging its identities into the config cache.
+pub(crate) fn import(bundle_file: &Path, config_dir: &PathBuf) -> Result<IdentityBundle, ()> {
+    let encrypted = match std::fs::read(bundle_file) {
+        Ok(v) => v,
+        Err(e) => {
+            eprintln!(
+                "STACKMUNCHER ERROR: cannot read the identity bundle {} due to {}",
+                bundle_file.to_string_lossy(),
+                e
+            );
+            return Err(());
+        }
+    };
+
+    let passphrase = read_passphrase("the identity bundle", false)?;
+    let bundle = match serde_json::from_slice::<IdentityBundle>(&decrypt(&encrypted, &passphrase)?) {
+        Ok(v) => v,
+        Err(e) => {
+            eprintln!("STACKMUNCHER ERROR: invalid identity bundle contents due to {}", e);
+            return Err(());
+        }
+    };
+
+    let new_public_key = save_key(&bundle.key, config_dir)?;
+    info!("Imported key {} from {}", new_public_key, bundle_file.to_string_lossy());
+
+    println!("    Identity imported from: {}", bundle_file.to_string_lossy());
+    println!("    Public key:             {}", new_public_key);
+    println!();
+
+    Ok(bundle)
+}
+
+/// Replaces the key file in `config_dir` with `key`, which is a base58-encoded PKCS8 document.
+/// The previous key is kept as `key.<public key>.bak` if it is different. Returns the new public key.
+pub(crate) fn save_key(key: &str, config_dir: &PathBuf) -> Result<String, ()> {
+    // the key must be valid before it replaces the current one
+    let new_key_pair = match decode_key(key) {
+        Some(v) => v,
+        None => {
+            eprintln!("STACKMUNCHER ERROR: the key is invalid.");
+            return Err(());
+        }
+    };
+    let new_public_key = ReportSignature::get_public_key(&new_key_pair);
+
+    // keep the current key in case the import was a mistake
+    let key_file = get_key_file_name(config_dir);
+    if let Ok(current_key) = std::fs::read_to_string(&key_file) {
+        if let Some(current_key_pair) = decode_key(current_key.trim()) {
+            let current_public_key = ReportSignature::get_public_key(&current_key_pair);
+            if current_public_key == new_public_key {
+                debug!("The imported key is the same as the current key");
+            } else {
+                let backup_file = config_dir.join(["key.", &current_public_key, ".bak"].concat());
+                if let Err(e) = std::fs::copy(&key_file, &backup_file) {
+                    eprintln!(
+                        "STACKMUNCHER ERROR: cannot back up the current key to {} due to {}",
+                        backup_file.to_string_lossy(),
+                        e
+                    );
+                    return Err(());
+                }
+                println!("    Previous key saved in: {}", backup_file.to_string_lossy());
+            }
+        }
+    }
+
+    if let Err(e) = std::fs::write(&key_file, key) {
+        eprintln!("STACKMUNCHER ERROR: cannot save the key in {} due to {}", key_file.to_string_lossy(), e);
+        return Err(());
+    }
+
+    Ok(new_public_key)
+}
+
+/// Returns the key-pair from a base58-encoded PKCS8 document or None if it's invalid.
+pub(crate) fn decode_key(key: &str) -> Option<Ed25519KeyPair> {
+    let pkcs8_bytes = bs58::decode(key).into_vec().ok()?;
+    Ed25519KeyPair::from_pkcs8(&pkcs8_bytes).ok()
+}
+
+/// Reads the passphrase from the env var or prompts the user for it without echoing, e.g. for `what` = `the identity bundle`.
+/// The passphrase is entered twice if `confirm` is true.
+pub(crate) fn read_passphrase(what: &str, confirm: bool) -> Result<String, ()> {
+    if let Ok(passphrase) = std::env::var(PASSPHRASE_ENV_VAR) {
+        debug!("Using the passphrase from {}", PASSPHRASE_ENV_VAR);
+        return validate_passphrase(passphrase);
+    }
+
+    let passphrase = match rpassword::prompt_password(format!("Passphrase for {}: ", what)) {
+        Ok(v) => v,
+        Err(e) => {
+            eprintln!(
+                "STACKMUNCHER ERROR: cannot read the passphrase due to {}. Set {} env var if there is no terminal.",
+                e, PASSPHRASE_ENV_VAR
+            );
+            return Err(());
+        }
+    };
+
+    if confirm {
+        match rpassword::prompt_password("Repeat the passphrase: ") {
+            Ok(v) if v == passphrase => {}
+            Ok(_) => {
+                eprintln!("STACKMUNCHER ERROR: the passphrases do not match.");
+                return Err(());
+            }
+            Err(e) => {
+                eprintln!("STACKMUNCHER ERROR: cannot read the passphrase due to {}", e);
+                return Err(());
+            }
+        }
+    }
+
+    validate_passphrase(passphrase)
+}
+
+/// Returns the passphrase as-is if it's long enough.
+fn validate_passphrase(passphrase: String) -> Result<String, ()> {
+    if passphrase.chars().count() < MIN_PASSPHRASE_LENGTH {
+        eprintln!(
+            "STACKMUNCHER ERROR: the passphrase must be at least {} characters long.",
+            MIN_PASSPHRASE_LENGTH
+        );
+        return Err(());
+    }
+
+    Ok(passphrase)
+}
+
+/// Derives a ChaCha20-Poly1305 key from the passphrase.
+fn derive_key(passphrase: &str, salt: &[u8]) -> LessSafeKey {
+    let mut key = [0u8; 32];
+    pbkdf2::derive(
+        pbkdf2::PBKDF2_HMAC_SHA256,
+        NonZeroU32::new(PBKDF2_ITERATIONS).expect("PBKDF2_ITERATIONS is zero. It's a bug."),
+        salt,
+        passphrase.as_bytes(),
+        &mut key,
+    );
+
+    LessSafeKey::new(UnboundKey::new(&CHACHA20_POLY1305, &key).expect("Invalid key length. It's a bug."))
+}
+
+/// Returns `magic | salt | nonce | ciphertext with tag` encoded as base58.
+pub(crate) fn encrypt(plaintext: &[u8], passphrase: &str) -> Result<Vec<u8>, ()> {
+    let rng = SystemRandom::new();
+    let mut salt = [0u8; SALT_LEN];
+    let mut nonce = [0u8; NONCE_LEN];
+    if rng.fill(&mut salt).is_err() || rng.fill(&mut nonce).is_err() {
+        eprintln!("STACKMUNCHER ERROR: failed to generate random bytes for encryption.");
+        return Err(());
+    }
+
+    let mut in_out = plaintext.to_vec();
+    if derive_key(passphrase, &salt)
+        .seal_in_place_append_tag(Nonce::assume_unique_for_key(nonce), Aad::from(BUNDLE_MAGIC), &mut in_out)
+        .is_err()
+    {
+        eprintln!("STACKMUNCHER ERROR: failed to encrypt the data.");
+        return Err(());
+    }
+
+    let bundle = [BUNDLE_MAGIC, &salt, &nonce, &in_out].concat();
+    Ok(bs58::encode(bundle).into_vec())
+}
+
+/// Decodes and decrypts the output of `encrypt`.
+pub(crate) fn decrypt(encrypted: &[u8], passphrase: &str) -> Result<Vec<u8>, ()> {
+    let bundle = match bs58::decode(encrypted.trim_ascii()).into_vec() {
+        Ok(v) if v.starts_with(BUNDLE_MAGIC) && v.len() > BUNDLE_MAGIC.len() + SALT_LEN + NONCE_LEN => v,
+        _ => {
+            eprintln!("STACKMUNCHER ERROR: the data was not encrypted by StackMuncher.");
+            return Err(());
+        }
+    };
+
+    let (salt, rest) = bundle[BUNDLE_MAGIC.len()..].split_at(SALT_LEN);
+    let (nonce, ciphertext) = rest.split_at(NONCE_LEN);
+    let nonce = Nonce::try_assume_unique_for_key(nonce).expect("Invalid nonce length. It's a bug.");
+
+    let mut in_out = ciphertext.to_vec();
+    match derive_key(passphrase, salt).open_in_place(nonce, Aad::from(BUNDLE_MAGIC), &mut in_out) {
+        Ok(plaintext) => Ok(plaintext.to_vec()),
+        Err(_) => {
+            eprintln!("STACKMUNCHER ERROR: cannot decrypt the data. Is the passphrase correct?");
+            Err(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod test_cmd_identity {
+    use super::{decrypt, encrypt};
+
+    #[test]
+    fn test_encrypt_decrypt() {
+        let encrypted = encrypt(b"{\"key\":\"abc\"}", "correct horse").unwrap();
+        assert!(encrypted.iter().all(|v| v.is_ascii_alphanumeric()));
+        assert_eq!(decrypt(&encrypted, "correct horse").unwrap(), b"{\"key\":\"abc\"}");
+        assert!(decrypt(&encrypted, "wrong horse").is_err());
+        assert!(decrypt(b"not a bundle", "correct horse").is_err());
+    }
+}
@@ -1,19 +1,95 @@
 use crate::config::AppConfig;
+#[cfg(feature = "cloud")]
 use crate::help;
+use crate::lock;
+use crate::postprocess;
+use crate::report_sink;
+#[cfg(feature = "cloud")]
 use crate::signing::ReportSignature;
+#[cfg(feature = "cloud")]
 use crate::submission::submit_report;
-use futures::stream::{FuturesUnordered, StreamExt};
-use stackmuncher_lib::contributor::Contributor;
-use stackmuncher_lib::report::TechOverview;
-use stackmuncher_lib::{code_rules::CodeRules, config::Config, git, report::Report, utils::hash_str_sha1};
+#[cfg(feature = "cloud")]
+use crate::timestamp;
+use crate::ui;
+#[cfg(feature = "cloud")]
+use stackmuncher_lib::analyzer::AnalysisOutcome;
+use stackmuncher_lib::analyzer::{AnalysisError, AnalysisOptions, Analyzer};
+use stackmuncher_lib::contributor::{Contributor, FailedContributor};
+use stackmuncher_lib::file_trace::{self, FileTrace};
+use stackmuncher_lib::report::{Framework, FrameworkConfidence, LocWeighting, TechOverview, Trends};
+use stackmuncher_lib::skip_list;
+use stackmuncher_lib::vcs::{self, Vcs};
+use stackmuncher_lib::{code_rules::CodeRules, report::Report};
+#[cfg(feature = "cloud")]
+use stackmuncher_lib::{config::Config, utils::write_atomically};
+use std::collections::HashSet;
 use std::path::Path;
-use tracing::{debug, info, warn};
+use tracing::{debug, error, info, info_span, warn, Instrument};
+
+/// How complete the analysis was. Passed to post-processors as `complete` or `partial`.
+#[derive(PartialEq, Debug)]
+pub(crate) enum MunchOutcome {
+    /// All reports were generated
+    Complete,
+    /// Some of the contributor reports could not be generated and the combined report is missing their data
+    /// or only the most recently changed files were analyzed because of `munch_limits.max_files`
+    Partial,
+}
+
+/// The result of a munching run that did not fail outright.
+#[derive(Debug)]
+pub(crate) struct MunchSummary {
+    pub outcome: MunchOutcome,
+    /// The combined report was not accepted by the Directory.
+    pub submission_failed: bool,
+    /// Problems that did not stop the run, printed together at the end of it,
+    /// e.g. `Truncated history: shallow clone, run with --full-history to fetch the missing commits`.
+    pub warnings: Vec<String>,
+}
+
+impl MunchSummary {
+    /// Returns the exit code for CI pipelines to gate on. The highest code wins if several apply.
+    pub(crate) fn exit_code(&self) -> i32 {
+        if self.submission_failed {
+            crate::EXIT_CODE_SUBMISSION_FAILED
+        } else if self.outcome == MunchOutcome::Partial {
+            crate::EXIT_CODE_PARTIAL
+        } else {
+            0
+        }
+    }
+}
+
+/// Returns the exit code of a munching run for CI pipelines to gate on, e.g. `EXIT_CODE_UNSUPPORTED`.
+pub(crate) fn exit_code(result: &Result<MunchSummary, AnalysisError>) -> i32 {
+    match result {
+        Ok(summary) => summary.exit_code(),
+        Err(AnalysisError::Unsupported(_)) => crate::EXIT_CODE_UNSUPPORTED,
+        Err(AnalysisError::Failed) => crate::EXIT_CODE_FAILED,
+    }
+}
 
-pub(crate) async fn run(config: AppConfig) -> Result<(), ()> {
-    let instant = std::time::Instant::now();
+/// Analyzes the project and processes the reports. All log records are tagged with the project hash, which is
+/// the last part of the project reports folder name, e.g. `64f98583` for `.../tmp_gt_64f98583`.
+pub(crate) async fn run(config: &AppConfig) -> Result<MunchSummary, AnalysisError> {
+    let project_hash = config
+        .lib_config
+        .project_report_dir
+        .as_ref()
+        .and_then(|v| v.file_name())
+        .and_then(|v| v.to_string_lossy().rsplit('_').next().map(|v| v.to_owned()))
+        .unwrap_or_default();
+
+    munch(config)
+        .instrument(info_span!("munch", project = %project_hash))
+        .await
+}
 
+async fn munch(config: &AppConfig) -> Result<MunchSummary, AnalysisError> {
     // load code rules
     let mut code_rules = CodeRules::new();
+    code_rules.munch_limits = config.munch_limits.clone();
+    let mut analyzer = Analyzer::new(code_rules);
 
     // Reports are grouped per project with a canonical project name as the last subfolder
     let report_dir = Path::new(
@@ -25,200 +101,583 @@ pub(crate) async fn run(config: AppConfig) -> Result<(), ()> {
     );
     warn!("Reports folder: {}", report_dir.to_string_lossy());
 
-    // load a previously generated report if it exists
-    let project_report_filename =
-        report_dir.join([Config::PROJECT_REPORT_FILE_NAME, Config::REPORT_FILE_EXTENSION].concat());
-    let cached_project_report = Report::from_disk(&project_report_filename);
-
-    // get and retain a copy of the full git lot to re-use in multiple places
-    let git_log = git::get_log(&config.lib_config.project_dir, None, &code_rules.ignore_paths).await?;
-
-    let project_report = match Report::process_project(
-        &mut code_rules,
-        &config.lib_config.project_dir,
-        &cached_project_report,
-        Some(git_log.clone()),
-    )
-    .await?
-    {
-        None => {
-            // there were no changes since the previous report - it can be reused as-is
-            info!("Done in {}ms", instant.elapsed().as_millis());
-            println!("    No new commits since the last run.");
-            cached_project_report.expect("Cannot unwrap cached report. It's a bug.")
-        }
-        Some(v) => {
-            let _ = v.save_as_local_file(&project_report_filename, true);
-            info!("Project stack analyzed in {}ms", instant.elapsed().as_millis());
-            v
+    // another run on the same project would overwrite the reports mid-way, e.g. `watch` and a manual run
+    let _lock =
+        lock::lock_report_dir(report_dir, &config.lib_config.project_dir.to_string_lossy(), config.wait).await?;
+
+    // shared identities the user opted out of are not analyzed even if they are in the list of commit emails
+    let git_identities = config
+        .lib_config
+        .git_identities
+        .iter()
+        .filter(|git_id| !config.exclude_emails.contains(git_id))
+        .cloned()
+        .collect::<Vec<String>>();
+
+    // add any personal details supplied via CLI or taken from the environment
+    let options = AnalysisOptions {
+        report_dir: report_dir.to_path_buf(),
+        git_identities,
+        aliases: config.aliases.clone(),
+        exclude_emails: config.exclude_emails.clone(),
+        exclude_paths: config.exclude_paths.clone(),
+        co_change_limits: config.co_change_limits.clone(),
+        workday: config.workday.clone(),
+        retention: config.retention.clone(),
+        primary_email: config.primary_email.clone(),
+        public_name: config.public_name.clone(),
+        headline: config.headline.clone(),
+        public_contact: config.public_contact.clone(),
+        available: config.available,
+        location: config.location.clone(),
+        gh_validation_id: config.gh_validation_id.clone(),
+        full_history: config.full_history,
+        report_store: config.report_store,
+        git_ref: config.git_ref.clone(),
+    };
+    let analysis = match analyzer.analyze_repo(&config.lib_config.project_dir, &options).await {
+        Ok(v) => v,
+        Err(AnalysisError::Unsupported(reason)) => {
+            eprintln!(
+                "STACKMUNCHER ERROR: {} cannot be analyzed: {}",
+                config.lib_config.project_dir.to_string_lossy(),
+                reason
+            );
+            return Err(AnalysisError::Unsupported(reason));
         }
+        Err(e) => return Err(e),
     };
 
-    info!("Contributor reports requested for: {:?}", config.lib_config.git_identities);
-
-    // check if there are multiple contributors and generate individual reports
-    if let Some(contributors) = &project_report.contributors {
-        let last_commit_author = project_report.last_commit_author.as_ref().unwrap().clone();
+    let mut summary = MunchSummary {
+        outcome: MunchOutcome::Complete,
+        submission_failed: false,
+        warnings: Vec::new(),
+    };
 
-        // prepare a combined list of commit IDs from all known identities
-        let list_of_commits = git::get_contributor_commits_from_log(&git_log, &config.lib_config.git_identities);
+    if analysis.project_report.empty_repo {
+        println!(
+            "{}",
+            ui::msg_with("munch.empty_repo", &[("project", &config.lib_config.project_dir.to_string_lossy())])
+        );
+        return Ok(summary);
+    }
 
-        // prepare a container for async submission jobs
-        let mut submission_jobs = FuturesUnordered::new();
+    if let (Some(git_ref), Some(commit_sha1)) = (&config.git_ref, &analysis.project_report.report_commit_sha1) {
+        let commit = commit_sha1.get(..7).unwrap_or(commit_sha1);
+        let git_ref = ui::msg_with("munch.ref", &[("git_ref", git_ref), ("commit", &commit)]);
+        println!("{}", ui::summary_line("munch.label.ref", git_ref));
+    }
 
-        // a container for the combined contributor report if there are multiple identities
-        // we save all identities (for a single contributor) separate and then combine them into a single report
-        let mut contributor_reports: Vec<(Report, String)> = Vec::new();
+    if analysis.no_new_commits {
+        println!("{}", ui::msg("munch.no_new_commits"));
+    }
 
-        for contributor in contributors {
-            // only process known local identities
-            if !config
-                .lib_config
-                .git_identities
-                .contains(&contributor.git_id.trim().to_lowercase())
-            {
-                debug!("Contributor {} skipped / unknown identity", contributor.git_id);
-                continue;
-            }
+    if !config.exclude_emails.is_empty() {
+        println!("{}", ui::summary_line("munch.label.excluded_emails", config.exclude_emails.join(", ")));
+    }
+    if !config.exclude_paths.is_empty() {
+        println!("{}", ui::summary_line("munch.label.excluded_paths", config.exclude_paths.join(", ")));
+    }
 
-            let contributor_instant = std::time::Instant::now();
-            // load the previous contributor report, if any
-            let contributor_hash = hash_str_sha1(contributor.git_id.as_str());
-            let contributor_report_filename = report_dir.join(
-                [
-                    Config::CONTRIBUTOR_REPORT_FILE_NAME,
-                    contributor_hash.as_str(),
-                    Config::REPORT_FILE_EXTENSION,
-                ]
-                .concat(),
+    if let Some(problem_files) = &analysis.project_report.problem_files {
+        let skipped = problem_files.iter().filter(|v| v.skipped).count();
+        if skipped > 0 {
+            let skip_list_file = report_dir.join(skip_list::SKIP_LIST_FILE_NAME);
+            let skipped_files = ui::msg_with(
+                "munch.skipped_files",
+                &[
+                    ("count", &ui::number(skipped as u64)),
+                    ("skip_list", &skip_list_file.to_string_lossy()),
+                ],
             );
+            println!("{}", ui::summary_line("munch.label.skipped_files", skipped_files));
+            summary.warnings.push(ui::msg_with(
+                "munch.warning.skipped_files",
+                &[
+                    ("count", &ui::number(skipped as u64)),
+                    ("skip_list", &skip_list::SKIP_LIST_FILE_NAME),
+                ],
+            ));
+        }
+    }
+
+    let undecodable_files = analysis
+        .project_report
+        .tech
+        .iter()
+        .map(|v| v.undecodable_files)
+        .sum::<u64>();
+    if undecodable_files > 0 {
+        let undecodable = ui::msg_with("munch.undecodable_files", &[("count", &ui::number(undecodable_files))]);
+        println!("{}", ui::summary_line("munch.label.undecodable_files", undecodable));
+        summary.warnings.push(ui::msg_with(
+            "munch.warning.undecodable_files",
+            &[("count", &ui::number(undecodable_files))],
+        ));
+    }
+
+    let sampled_files = analysis
+        .project_report
+        .per_file_tech
+        .iter()
+        .filter(|v| v.sampled)
+        .count();
+    if sampled_files > 0 {
+        let sampled = ui::msg_with("munch.sampled_files", &[("count", &ui::number(sampled_files as u64))]);
+        println!("{}", ui::summary_line("munch.label.sampled_files", sampled));
+    }
+
+    if analysis.pruned_history.records > 0 {
+        let pruned_history = ui::msg_with(
+            "munch.pruned_history",
+            &[
+                ("records", &ui::number(analysis.pruned_history.records)),
+                ("kb", &ui::number(analysis.pruned_history.bytes / 1024)),
+            ],
+        );
+        println!("{}", ui::summary_line("munch.label.pruned_history", pruned_history));
+    }
+
+    // huge repos are analyzed partially if `munch_limits.partial` is on
+    if let Some(coverage) = analysis
+        .project_report
+        .coverage
+        .as_ref()
+        .filter(|_| analysis.project_report.partial)
+    {
+        // sparse checkouts are limited to the files in the working tree and can still be over `max_files`
+        // cached reports of older versions have no `max_files_reached`, but could only be limited by `max_files`
+        let max_files_reached = coverage.max_files_reached || !coverage.sparse_checkout;
+        let max_files_reason = match &coverage.changed_since {
+            Some(changed_since) => ui::msg_with("munch.partial_changed_since", &[("date", changed_since)]),
+            None => ui::msg("munch.partial_first_commit"),
+        };
+        let reason = match (coverage.sparse_checkout, max_files_reached) {
+            (true, true) => ui::msg_with("munch.partial_sparse_checkout_max_files", &[("reason", &max_files_reason)]),
+            (true, false) => ui::msg("munch.partial_sparse_checkout"),
+            (false, _) => max_files_reason,
+        };
+        let files_analyzed = ui::number(coverage.files_analyzed);
+        let files_total = ui::number(coverage.files_total);
+        let partial_analysis = ui::msg_with(
+            "munch.partial_analysis",
+            &[
+                ("files_analyzed", &files_analyzed),
+                ("files_total", &files_total),
+                ("reason", &reason),
+            ],
+        );
+        println!("{}", ui::summary_line("munch.label.partial_analysis", partial_analysis));
+        summary.outcome = MunchOutcome::Partial;
+        let warning_reason = match (coverage.sparse_checkout, max_files_reached) {
+            (true, true) => ui::msg("munch.warning.partial_sparse_checkout_max_files"),
+            (true, false) => ui::msg("munch.warning.partial_sparse_checkout"),
+            (false, _) => ui::msg("munch.warning.partial_max_files"),
+        };
+        summary.warnings.push(ui::msg_with(
+            "munch.warning.partial_analysis",
+            &[
+                ("files_analyzed", &files_analyzed),
+                ("files_total", &files_total),
+                ("reason", &warning_reason),
+            ],
+        ));
+    }
+
+    // shallow clones on CI have only the most recent commits
+    if analysis.project_report.history_truncated {
+        println!(
+            "{}",
+            ui::summary_line("munch.label.truncated_history", ui::msg("munch.truncated_history"))
+        );
+        summary.warnings.push(ui::msg("munch.warning.truncated_history"));
+    }
 
-            let cached_contributor_report = Report::from_disk(&contributor_report_filename);
+    // explain what happened to the files requested with --trace-file
+    if let Some(trace_filter) = &config.trace_file {
+        print_file_traces(
+            &mut analyzer.code_rules,
+            vcs::detect(&config.lib_config.project_dir).as_ref(),
+            &config.lib_config.project_dir,
+            config.git_ref.as_deref(),
+            trace_filter,
+            &analysis.project_report,
+        )
+        .await;
+    }
+
+    if !analysis.failed_contributors.is_empty() {
+        summary.outcome = MunchOutcome::Partial;
+        print_failed_contributors(&analysis.failed_contributors);
+        summary.warnings.push(ui::msg_with(
+            "munch.warning.failed_contributors",
+            &[("count", &ui::number(analysis.failed_contributors.len() as u64))],
+        ));
+    }
 
-            // if this is a single-commit update then use cached reports for all contributors other than the author of the commit
-            if project_report.is_single_commit && contributor.git_id != last_commit_author {
-                if let Some(cached_contributor_report) = cached_contributor_report {
-                    debug!("Used cached report for contributor {} / single commit", contributor.git_id);
-                    contributor_reports.push((cached_contributor_report, contributor.git_id.clone()));
-                    continue;
+    // post-processors get the combined report if there is one or the project report otherwise
+    let mut postprocess_report_file = analysis.project_report_file.clone();
+    let mut sink_report_files = vec![analysis.project_report_file.clone()];
+
+    match &analysis.combined_report {
+        None => {
+            // there were no matching contributors, unless they all failed and were reported above
+            if let Some(contributors) = &analysis.project_report.contributors {
+                if analysis.failed_contributors.is_empty() {
+                    print_no_contributions_msg(&analysis.git_identities, contributors);
+                    summary.warnings.push(ui::msg_with(
+                        "munch.warning.no_commits",
+                        &[("emails", &analysis.git_identities.join(", "))],
+                    ));
                 }
-                debug!("Missing cached report for contributor {} / single commit", contributor.git_id);
             }
+        }
+        Some(combined_report) => {
+            postprocess_report_file = analysis.combined_report_file.clone();
+            sink_report_files.push(analysis.combined_report_file.clone());
+            #[cfg(feature = "cloud")]
+            submit_combined_report(config, combined_report, &analysis, report_dir, &mut summary).await;
+            // a build without cloud support has no user key to salt the sanitized copy with and nothing to submit
+            #[cfg(not(feature = "cloud"))]
+            print_report_stats(config, combined_report, combined_report.trends.as_ref(), &analysis.project_report);
+        }
+    }
 
-            let contributor_report = project_report
-                .process_contributor(
-                    &mut code_rules,
-                    &config.lib_config.project_dir,
-                    &cached_contributor_report,
-                    contributor,
-                    project_report.tree_files.as_ref(),
-                )
-                .await?;
-
-            contributor_report.save_as_local_file(&contributor_report_filename, false);
-
-            info!(
-                "Contributor stack for {} analyzed in {}ms",
-                contributor.git_id,
-                contributor_instant.elapsed().as_millis()
-            );
+    postprocess::run(config, report_dir, &postprocess_report_file, &summary, &analysis.project_hooks).await;
 
-            // push the contributor report into a container to combine later
-            contributor_reports.push((contributor_report, contributor.git_id.clone()));
+    // CI pipelines keep the reports somewhere central because the runner is discarded after the run
+    if let Some(report_sink) = &config.report_sink {
+        let sink_report_files = sink_report_files.iter().map(|v| v.as_path()).collect::<Vec<&Path>>();
+        report_sink::save_reports(report_sink, report_dir, &sink_report_files).await?;
+    }
+
+    // print the location of the reports
+    println!("{}", ui::summary_line("munch.label.stack_reports", report_dir.to_string_lossy()));
+
+    print_warnings(&summary.warnings);
+
+    Ok(summary)
+}
+
+/// Prints all the problems of the run in one block at the end for a quick check of a CI log.
+/// Saves a sanitized copy of the combined report, prints its stats and submits it to the Directory unless it is
+/// a dry run, and then timestamps the full report if that is enabled.
+#[cfg(feature = "cloud")]
+async fn submit_combined_report(
+    config: &AppConfig,
+    combined_report: &Report,
+    analysis: &AnalysisOutcome,
+    report_dir: &Path,
+    summary: &mut MunchSummary,
+) {
+    let combined_report_id = combined_report.report_id.clone();
+    // the trends are local-only and are removed by sanitize()
+    let trends = combined_report.trends.clone();
+
+    // produce a sanitized version of the combined report, save and submit it if needed
+    if let Ok(combined_report) =
+        combined_report.sanitize(ReportSignature::get_salt(&config.user_key_pair), &config.scrub_rules)
+    {
+        // prepare the file name of the sanitized report
+        let sanitized_report_file_name = &report_dir.join(
+            [
+                Config::CONTRIBUTOR_REPORT_SANITIZED_FILE_NAME,
+                Config::REPORT_FILE_EXTENSION,
+            ]
+            .concat(),
+        );
+
+        // save the sanitized report
+        combined_report.save_as_local_file(sanitized_report_file_name, true);
+
+        // save the exact bytes that would be submitted for the user to review
+        if config.show_what_is_sent {
+            save_submission_payload(&combined_report, report_dir);
         }
 
-        // combine multiple contributor reports from different identities
-        debug!("Combining {} contributor reports", contributor_reports.len());
-        if contributor_reports.is_empty() {
-            // there were no matching contributors
-            print_no_contributions_msg(&config.lib_config.git_identities, contributors);
-        } else {
-            // seed the combined report from the 1st contributor report in the list of all contributor reports
-            let (mut combined_report, contributor_git_id) = contributor_reports.pop().unwrap();
-            combined_report.reset_combined_contributor_report(contributor_git_id, &list_of_commits, &project_report);
-            for (contributor_report, contributor_git_id) in contributor_reports.into_iter() {
-                // this only adds per-file-tech and does not affect any other part of the report
-                combined_report.merge_same_project_contributor_reports(contributor_report, contributor_git_id);
-            }
+        print_report_stats(config, &combined_report, trends.as_ref(), &analysis.project_report);
 
-            // combine all added per-file-tech into appropriate tech records
-            combined_report.recompute_tech_section();
-
-            // add any personal details supplied via CLI or taken from the environment
-            combined_report.primary_email = config.primary_email.clone();
-            combined_report.gh_validation_id = config.gh_validation_id.clone();
-
-            // check if there is a already a cached contributor report
-            // it would have to be a dry run (no submission) if it's the first time STM is run on this repo
-            let combined_report_file_name = report_dir.join(
-                [
-                    Config::CONTRIBUTOR_REPORT_COMBINED_FILE_NAME,
-                    Config::REPORT_FILE_EXTENSION,
-                ]
-                .concat(),
+        // check if the submission to the directory should go ahead
+        // it would have to be a dry run (no submission) if it's the first time STM is run on this repo
+        if config.dryrun {
+            // a dry-run was requested by the user
+            println!(
+                "{}",
+                ui::summary_line("munch.label.profile_update", ui::msg("munch.profile_update_dryrun"))
             );
-            let first_run = !combined_report_file_name.exists();
-
-            // save the combine report for inspection by the user
-            combined_report.save_as_local_file(&combined_report_file_name, true);
-
-            // produce a sanitized version of the combined report, save and submit it if needed
-            if let Ok(combined_report) = combined_report.sanitize(ReportSignature::get_salt(&config.user_key_pair)) {
-                // prepare the file name of the sanitized report
-                let sanitized_report_file_name = &report_dir.join(
-                    [
-                        Config::CONTRIBUTOR_REPORT_SANITIZED_FILE_NAME,
-                        Config::REPORT_FILE_EXTENSION,
-                    ]
-                    .concat(),
-                );
-
-                // save the sanitized report
-                combined_report.save_as_local_file(sanitized_report_file_name, true);
-
-                print_combined_stats(&combined_report);
-
-                // check if the submission to the directory should go ahead
-                if config.dryrun {
-                    // a dry-run was requested by the user
-                    println!("    Profile update:      skipped with `--dryrun` flag");
-                } else {
-                    if first_run {
-                        info!("No report submission on the first run");
-                        help::emit_dryrun_msg(&sanitized_report_file_name.to_string_lossy());
-                    } else {
-                        submission_jobs.push(submit_report(combined_report.clone(), &config));
-                    }
-                }
-            }
+        } else if config.git_ref.is_some() {
+            // the profile reflects HEAD, a release branch or a tag would overwrite it with older code
+            println!("{}", ui::summary_line("munch.label.profile_update", ui::msg("munch.profile_update_ref")));
+        } else if analysis.first_run {
+            info!("No report submission on the first run");
+            help::emit_dryrun_msg(&sanitized_report_file_name.to_string_lossy());
+        } else if submit_report(combined_report, config).await.is_ok() {
+            debug!("Combined contributor report submitted");
+        } else {
+            summary.submission_failed = true;
+            summary.warnings.push(ui::msg("munch.warning.submission_failed"));
         }
+    }
 
-        // there should be only a single submission of the combined report
-        match submission_jobs.next().await {
-            Some(_) => {
-                debug!("Combined contributor report submitted");
-            }
-            None => {
-                debug!("No combined contributor report was submitted");
-            }
+    // only the hash of the report leaves the machine, if timestamping is on
+    timestamp::timestamp_report(config, &analysis.combined_report_file, &combined_report_id).await;
+}
+
+/// Prints the stats of the combined report with its trends and the duplicates found in the project.
+fn print_report_stats(config: &AppConfig, combined_report: &Report, trends: Option<&Trends>, project_report: &Report) {
+    print_combined_stats(combined_report, config.loc_weighting);
+    if let Some(trends) = trends {
+        print_trends(trends);
+    }
+    // vendored or copy-pasted files would inflate the totals without the dedup
+    if let Some(duplicate_files) = &project_report.duplicate_files {
+        let key = if duplicate_files.counted_once {
+            "munch.duplicates_counted_once"
+        } else {
+            "munch.duplicates_counted_separately"
+        };
+        let duplicates = ui::msg_with(
+            key,
+            &[
+                ("loc", &ui::number(duplicate_files.loc)),
+                ("copies", &ui::number(duplicate_files.copies)),
+                ("blobs", &ui::number(duplicate_files.blobs)),
+            ],
+        );
+        println!("{}", ui::summary_line("munch.label.duplicates", duplicates));
+    }
+}
+
+fn print_warnings(warnings: &[String]) {
+    if warnings.is_empty() {
+        return;
+    }
+
+    println!();
+    println!("{}", ui::summary_line("munch.label.warnings", ui::number(warnings.len() as u64)));
+    for warning in warnings {
+        println!("    * {}", warning);
+    }
+}
+
+/// Prints the list of contributors that were skipped because their reports could not be generated.
+fn print_failed_contributors(failed_contributors: &[FailedContributor]) {
+    println!();
+    println!("{}", ui::msg("munch.failed_contributors"));
+    for failed_contributor in failed_contributors {
+        println!("    * {}: {}", failed_contributor.git_id, failed_contributor.reason);
+    }
+    println!();
+}
+
+/// Saves the uncompressed submission payload byte-for-byte as it would be sent to the Directory and prints its location.
+/// Does not panic or exit on error because it is an optional step.
+#[cfg(feature = "cloud")]
+fn save_submission_payload(report: &Report, report_dir: &Path) {
+    let payload = match report.to_payload() {
+        Ok(v) => v,
+        Err(_) => {
+            eprintln!("STACKMUNCHER ERROR: cannot prepare the submission payload for review.");
+            return;
         }
+    };
+
+    let payload_file_name =
+        report_dir.join([Config::SUBMISSION_PAYLOAD_FILE_NAME, Config::REPORT_FILE_EXTENSION].concat());
+
+    match write_atomically(&payload_file_name, &payload) {
+        Ok(_) => println!("{}", ui::summary_line("munch.label.what_is_sent", payload_file_name.to_string_lossy())),
+        Err(e) => eprintln!(
+            "STACKMUNCHER ERROR: cannot save the submission payload in {} due to {}",
+            payload_file_name.to_string_lossy(),
+            e
+        ),
     }
+}
 
-    // print the location of the reports
-    println!("    Stack reports:       {}", report_dir.to_string_lossy());
-    info!("Repo processed in {}ms", instant.elapsed().as_millis());
+/// Prints the evaluation stages for every file matching `trace_filter` and the number of files per disposition,
+/// e.g. `Trace summary:       12 code, 2 generated, 1 ignored`.
+async fn print_file_traces(
+    code_rules: &mut CodeRules,
+    vcs: &dyn Vcs,
+    project_dir: &Path,
+    git_ref: Option<&str>,
+    trace_filter: &str,
+    project_report: &Report,
+) {
+    // ignored files are listed as well to show which rule dropped them
+    let tree_files = match vcs
+        .get_all_tree_files(project_dir, git_ref.map(|v| v.to_owned()), &[])
+        .await
+    {
+        Ok(v) => v,
+        Err(_) => {
+            error!("Cannot list the files in the tree for `--trace-file`");
+            return;
+        }
+    };
 
-    Ok(())
+    let mut file_names = tree_files
+        .iter()
+        .filter(|file_name| file_trace::matches_filter(file_name, trace_filter))
+        .cloned()
+        .collect::<Vec<String>>();
+    file_names.sort();
+    // a path that is not in the tree is traced as-is to say so
+    if file_names.is_empty() {
+        file_names.push(trace_filter.trim_start_matches("./").trim_end_matches('/').to_owned());
+    }
+
+    println!();
+    let mut traces: Vec<FileTrace> = Vec::new();
+    for file_name in file_names {
+        let trace = FileTrace::new(code_rules, &file_name, &tree_files, project_report);
+        println!("{}", ui::msg_with("munch.trace", &[("file_name", &trace.file_name)]));
+        for (idx, stage) in trace.stages.iter().enumerate() {
+            println!("      {}. {:<16}{}", idx + 1, stage.stage, stage.outcome);
+        }
+        println!("      => {}", trace.disposition.as_str());
+        debug!("Trace for {}: {:?}", trace.file_name, trace);
+        traces.push(trace);
+    }
+
+    let summary = file_trace::count_dispositions(&traces)
+        .iter()
+        .map(|(disposition, count)| [ui::number(*count as u64).as_str(), disposition.as_str()].join(" "))
+        .collect::<Vec<String>>()
+        .join(", ");
+    println!();
+    println!("{}", ui::summary_line("munch.label.trace_summary", &summary));
+    info!(trace_file = trace_filter, dispositions = %summary, "File trace");
 }
 
 /// Prints a one-line summary of the report for the user to get an idea and not need to look up the report file
 /// E.g. `Summary (LoC/libs):  Rust 12656/26, Markdown 587, PowerShell 169`
-fn print_combined_stats(report: &Report) {
-    let report = report.get_overview();
+pub(crate) fn print_combined_stats(report: &Report, loc_weighting: LocWeighting) {
+    println!("{}", ui::summary_line("munch.label.summary", get_per_tech_stats(report, loc_weighting)));
+
+    // how much of the repo the summary represents
+    if let Some(coverage) = &report.coverage {
+        println!("{}", ui::summary_line("munch.label.coverage", coverage.summary()));
+    }
 
+    if let Some(tenure) = &report.tenure {
+        println!("{}", ui::summary_line("munch.label.tenure", tenure.summary()));
+    }
+
+    // the share of functions and types with doc comments for languages with entity rules
+    let mut tech = report.get_overview().tech.into_iter().collect::<Vec<TechOverview>>();
+    tech.sort_unstable_by_key(|t| std::cmp::Reverse(t.loc));
+    let docs_coverage = tech
+        .iter()
+        .filter_map(|t| {
+            t.docs_coverage()
+                .map(|v| [t.language.as_str(), " ", &v.to_string(), "%"].concat())
+        })
+        .collect::<Vec<String>>();
+    if !docs_coverage.is_empty() {
+        println!("{}", ui::summary_line("munch.label.docs_coverage", docs_coverage.join(", ")));
+    }
+
+    // employers search by framework rather than library names
+    let frameworks = Framework::from_tech(report.tech.iter())
+        .into_iter()
+        .map(|v| match v.confidence {
+            FrameworkConfidence::High => v.name,
+            FrameworkConfidence::Medium => ui::msg_with("munch.framework_medium", &[("name", &v.name)]),
+            FrameworkConfidence::Low => ui::msg_with("munch.framework_low", &[("name", &v.name)]),
+        })
+        .collect::<Vec<String>>();
+    if !frameworks.is_empty() {
+        println!("{}", ui::summary_line("munch.label.frameworks", frameworks.join(", ")));
+    }
+
+    // schema migrations are data-layer work that is lost in the per-language counts
+    if let Some(migrations) = &report.migrations {
+        let frameworks = migrations
+            .frameworks
+            .iter()
+            .map(|(framework, files)| [framework.as_str(), " ", files.to_string().as_str()].concat())
+            .collect::<Vec<String>>();
+        let files_loc = ui::msg_with(
+            "munch.files_loc",
+            &[
+                ("files", &ui::number(migrations.files)),
+                ("loc", &ui::number(migrations.loc)),
+                ("details", &frameworks.join(", ")),
+            ],
+        );
+        println!("{}", ui::summary_line("munch.label.migrations", files_loc));
+    }
+
+    // CI/CD and infra-as-code are counted separately for the same reason
+    if let Some(ops_tech) = &report.ops_tech {
+        let tools = ops_tech
+            .tools
+            .iter()
+            .map(|(tool, count)| [tool.as_str(), " ", count.files.to_string().as_str()].concat())
+            .collect::<Vec<String>>();
+        let files_loc = ui::msg_with(
+            "munch.files_loc",
+            &[
+                ("files", &ui::number(ops_tech.files)),
+                ("loc", &ui::number(ops_tech.loc)),
+                ("details", &tools.join(", ")),
+            ],
+        );
+        println!("{}", ui::summary_line("munch.label.ops_tech", files_loc));
+    }
+
+    // a rough measure of the known unfinished work, if counting it was enabled
+    if let Some(tech_debt) = &report.tech_debt_markers {
+        let markers = tech_debt
+            .markers
+            .iter()
+            .map(|(marker, count)| [marker.as_str(), " ", count.to_string().as_str()].concat())
+            .collect::<Vec<String>>();
+        let tech_debt = format!("{} ({})", ui::number(tech_debt.total), markers.join(", "));
+        println!("{}", ui::summary_line("munch.label.tech_debt", tech_debt));
+    }
+}
+
+/// Prints the shifts in the stack over the last few runs with the largest changes in language share first,
+/// e.g. `Trends: +1200 LoC over 5 runs since 2023-06-01: TypeScript +12% (+1200 LoC), Rust -12% (+0 LoC)`
+fn print_trends(trends: &Trends) {
+    let languages = trends
+        .languages
+        .iter()
+        .take(5)
+        .map(|t| format!("{} {:+}% ({:+} LoC)", t.language, t.share_delta, t.loc_delta))
+        .collect::<Vec<String>>();
+    let summary = ui::msg_with(
+        "munch.trends",
+        &[
+            ("loc_delta", &format!("{:+}", trends.loc_delta)),
+            ("runs", &ui::number(trends.runs)),
+            ("since", &ui::date(&trends.since)),
+        ],
+    );
+    println!(
+        "{}",
+        ui::summary_line(
+            "munch.label.trends",
+            format!("{}{}{}", summary, if languages.is_empty() { "" } else { ": " }, languages.join(", "))
+        )
+    );
+}
+
+/// Returns a one-line summary of LoC and libs per tech sorted by LoC. The LoC are verbosity-adjusted
+/// with `LocWeighting::Effective`.
+/// E.g. `Rust 12656/26, Markdown 587, PowerShell 169`
+pub(crate) fn get_per_tech_stats(report: &Report, loc_weighting: LocWeighting) -> String {
+    get_tech_overview_stats(&report.get_overview().tech, loc_weighting)
+}
+
+/// The same as `get_per_tech_stats`, but for the tech list of a project overview.
+pub(crate) fn get_tech_overview_stats(tech: &HashSet<TechOverview>, loc_weighting: LocWeighting) -> String {
     // get a summary and sort the stack by LoC
-    let mut tech = report.tech.iter().collect::<Vec<&TechOverview>>();
-    tech.sort_unstable_by(|a, b| b.loc.cmp(&a.loc));
+    let mut tech = tech.iter().collect::<Vec<&TechOverview>>();
+    tech.sort_unstable_by(|a, b| b.weighted_loc(loc_weighting).cmp(&a.weighted_loc(loc_weighting)));
 
     // prepare a single line of per-tech stats
     let per_tech_stats = tech
@@ -231,44 +690,69 @@ fn print_combined_stats(report: &Report) {
                 String::new()
             };
 
-            [t.language.as_str(), " ", t.loc.to_string().as_str(), libs.as_str()].concat()
+            [
+                t.language.as_str(),
+                " ",
+                t.weighted_loc(loc_weighting).to_string().as_str(),
+                libs.as_str(),
+            ]
+            .concat()
         })
         .collect::<Vec<String>>();
-    let per_tech_stats = per_tech_stats.as_slice().join(", ");
-    println!("    Summary (LoC/libs):  {}", per_tech_stats);
+    per_tech_stats.as_slice().join(", ")
 }
 
 /// Prints a list of contributors and git identities to help find user git identities
 fn print_no_contributions_msg(git_identities: &Vec<String>, contributors: &Vec<Contributor>) {
     // is this repo empty?
     if contributors.is_empty() {
-        println!("    This repository has no commits with identifiable committers.");
+        println!("{}", ui::msg("munch.no_committers"));
         return;
     }
 
     match git_identities.len() {
         0 => {
             println!();
-            println!("    No commits were selected for analysis.");
-            println!("    Configure `user.email` Git setting or use `--email` CLI params to add committer emails.");
+            println!("{}", ui::msg("munch.no_identities"));
             println!();
         }
         1 => {
             println!();
-            println!(
-                "Found no commits from {}. Did you make commits with a different email?",
-                git_identities[0]
-            );
-            println!("    Run `git shortlog -s -e --all` to see all committer emails in this repo.");
-            println!("    Add more of your committer emails with `stackmuncher config --emails \"me1@gmail.com,me2@gmail.com\"");
+            println!("{}", ui::msg_with("munch.no_commits_from_one", &[("email", &git_identities[0])]));
+            println!("{}", ui::msg("munch.add_more_emails"));
             println!();
         }
         _ => {
             println!();
-            println!("    Found no commits from any of: {}.", git_identities.join(", "));
-            println!("    Run `git shortlog -s -e --all` to see all committer emails in this repo.");
-            println!("    Add more of your committer emails with `stackmuncher config --emails \"me1@gmail.com,me2@gmail.com\"");
+            println!("{}", ui::msg_with("munch.no_commits_from_any", &[("emails", &git_identities.join(", "))]));
+            println!("{}", ui::msg("munch.add_more_emails"));
             println!();
         }
     }
 }
+
+#[cfg(test)]
+mod test_cmd_munch {
+    use super::{exit_code, MunchOutcome, MunchSummary};
+    use stackmuncher_lib::analyzer::AnalysisError;
+
+    fn summary(outcome: MunchOutcome, submission_failed: bool) -> Result<MunchSummary, AnalysisError> {
+        Ok(MunchSummary {
+            outcome,
+            submission_failed,
+            warnings: Vec::new(),
+        })
+    }
+
+    /// The codes are documented for CI pipelines, so they are compared to the numbers rather than the constants.
+    #[test]
+    fn test_exit_code() {
+        assert_eq!(exit_code(&summary(MunchOutcome::Complete, false)), 0);
+        assert_eq!(exit_code(&summary(MunchOutcome::Partial, false)), 2);
+        assert_eq!(exit_code(&summary(MunchOutcome::Complete, true)), 4);
+        // the highest code wins
+        assert_eq!(exit_code(&summary(MunchOutcome::Partial, true)), 4);
+        assert_eq!(exit_code(&Err(AnalysisError::Unsupported("too many files".to_owned()))), 3);
+        assert_eq!(exit_code(&Err(AnalysisError::Failed)), 1);
+    }
+}
@@ -0,0 +1,117 @@
+use crate::cmd_config::{non_empty_or_not_set, yes_no_or_not_set};
+use crate::cmd_munch::{get_per_tech_stats, get_tech_overview_stats};
+use crate::cmd_rollup::{load_combined_reports, print_duplicate_clones};
+use crate::config::AppConfig;
+use stackmuncher_lib::report::{ProjectReportOverview, Report};
+use tracing::info;
+
+/// The cross-project profile is saved in the root of the reports folder under this name.
+const PROFILE_REPORT_FILE_NAME: &str = "profile_report.json";
+
+/// The max length of the project name in the list of projects. Longer names are truncated.
+const MAX_PROJECT_NAME_LENGTH: usize = 30;
+
+/// Merges the combined reports of all analyzed projects into a single cross-project profile, saves it in the reports folder
+/// and prints the stack totals with an overview of every project. Nothing is submitted to the Directory.
+pub(crate) fn run(config: &AppConfig) -> Result<(), ()> {
+    let reports_dir = config
+        .reports_dir
+        .as_ref()
+        .expect("Cannot unwrap config.reports_dir. It's a bug.");
+
+    let mut profile: Option<Report> = None;
+    // (name to print, overview) for every project
+    let mut projects: Vec<(String, ProjectReportOverview)> = Vec::new();
+    let combined_reports = load_combined_reports(reports_dir)?;
+    print_duplicate_clones(&combined_reports.clones);
+    for (report_file, report) in combined_reports.reports {
+        // local projects without a GitHub remote have no IDs and would be merged into a single overview by `Report::merge`,
+        // so the overviews are collected separately and named after their report folders
+        let mut overview = report.get_overview();
+        overview.commits = None;
+        let project_name = match (&overview.github_repo_name, report_file.parent().and_then(|v| v.file_name())) {
+            (None, Some(report_dir_name)) => report_dir_name.to_string_lossy().to_string(),
+            _ => overview.project_name.clone(),
+        };
+        projects.push((project_name, overview));
+
+        profile = Report::merge_with_limits(profile, report, &config.merge_limits);
+    }
+
+    // the per-file details are only needed for incremental processing of individual projects
+    let mut profile = match profile {
+        Some(v) => v.abridge(),
+        None => {
+            println!("    No project reports found in {}", reports_dir.to_string_lossy());
+            println!("    Run `stackmuncher` inside a project folder to create one.");
+            return Ok(());
+        }
+    };
+
+    profile.projects_included = projects.iter().map(|(_, overview)| overview.clone()).collect();
+
+    let profile_file = reports_dir.join(PROFILE_REPORT_FILE_NAME);
+    profile.save_as_local_file(&profile_file, true);
+    info!("Profile of {} projects saved in {}", projects.len(), profile_file.to_string_lossy());
+
+    println!();
+    println!("    Profile saved in:    {}", profile_file.to_string_lossy());
+    println!("    Projects:            {}", projects.len());
+    println!("    Stack:               {}", get_per_tech_stats(&profile, config.loc_weighting));
+    if let (Some(first), Some(last)) =
+        (&profile.first_contributor_commit_date_iso, &profile.last_contributor_commit_date_iso)
+    {
+        println!("    Active:              {} - {}", short_date(first), short_date(last));
+    }
+    println!();
+
+    // the most recently active projects go first
+    projects.sort_by(|(a_name, a), (b_name, b)| {
+        b.contributor_last_commit
+            .cmp(&a.contributor_last_commit)
+            .then(a_name.cmp(b_name))
+    });
+    for (project_name, project) in projects {
+        print_project_overview(&project_name, &project, config);
+    }
+    println!();
+
+    Ok(())
+}
+
+/// Prints the public details of the Directory Profile after `profile set` saved them in the config.
+/// They are sent to the Directory with the next report.
+pub(crate) fn print_details(config: &AppConfig) {
+    println!("    Public name:   {}", non_empty_or_not_set(&config.public_name));
+    println!("    Headline:      {}", non_empty_or_not_set(&config.headline));
+    println!("    Contact:       {}", non_empty_or_not_set(&config.public_contact));
+    println!("    Available:     {}", yes_no_or_not_set(config.available));
+    println!("    Location:      {}", non_empty_or_not_set(&config.location));
+    println!();
+    println!("    The changes will be sent to the Directory with the next report. Run `stackmuncher` in any of your projects.");
+    println!();
+}
+
+/// Prints a single line about the project, e.g. `stm_app  2021-05-01 - 2022-03-04  123 commits  Rust 12000/15, Markdown 300`.
+fn print_project_overview(project_name: &str, project: &ProjectReportOverview, config: &AppConfig) {
+    let project_name = project_name.chars().take(MAX_PROJECT_NAME_LENGTH).collect::<String>();
+
+    let dates = match (&project.contributor_first_commit, &project.contributor_last_commit) {
+        (Some(first), Some(last)) => [short_date(first), " - ", short_date(last)].concat(),
+        _ => String::new(),
+    };
+
+    println!(
+        "        {:<name_width$}  {:<23}  {:>6} commits  {}",
+        project_name,
+        dates,
+        project.commit_count,
+        get_tech_overview_stats(&project.tech, config.loc_weighting),
+        name_width = MAX_PROJECT_NAME_LENGTH
+    );
+}
+
+/// Returns the date part of an RFC3339 timestamp, e.g. `2020-08-26T00:00:00+00:00` -> `2020-08-26`.
+fn short_date(date: &str) -> &str {
+    date.get(..10).unwrap_or(date)
+}
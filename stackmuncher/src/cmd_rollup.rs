@@ -0,0 +1,338 @@
+use crate::app_args::RollupGroupBy;
+use crate::cmd_munch::get_per_tech_stats;
+use crate::config::AppConfig;
+use stackmuncher_lib::{
+    config::Config,
+    report::{Report, WorkdayOverlap},
+};
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::{Path, PathBuf};
+use tracing::{debug, warn};
+
+/// The group name for projects with no recognizable remote, e.g. local-only repos.
+const NO_REMOTE_GROUP: &str = "no remote";
+
+/// The number of the most recent quarters printed in the work distribution timeline.
+const WORK_DISTRIBUTION_QUARTERS: usize = 8;
+
+/// The number of UTC offsets with the most overlap printed if no timezones were configured.
+const TOP_OVERLAP_OFFSETS: usize = 3;
+
+/// A project report folder with the combined report of a clone of the same remote as a more recently analyzed project.
+pub(crate) struct DuplicateClone {
+    /// The report folder of the clone analyzed earlier, which is merged into the report of the other clone in rollups.
+    pub report_dir: PathBuf,
+    /// The report folder of the most recently analyzed clone.
+    pub kept_report_dir: PathBuf,
+}
+
+/// The combined reports of all projects in the reports folder with one report per remote.
+pub(crate) struct CombinedReports {
+    /// Paths to the combined reports with the reports, sorted by the project folder name.
+    pub reports: Vec<(PathBuf, Report)>,
+    /// Clones merged into the report of a more recently analyzed clone of the same remote in `reports`.
+    pub clones: Vec<DuplicateClone>,
+}
+
+/// Combines the local project reports into one or more summaries and prints them.
+/// Nothing is submitted to the Directory. The grouping is done with the data stored locally in combined reports.
+pub(crate) async fn run(config: AppConfig) -> Result<(), ()> {
+    let reports_dir = config
+        .reports_dir
+        .as_ref()
+        .expect("Cannot unwrap config.reports_dir. It's a bug.");
+
+    // group name -> (number of projects, merged report)
+    let mut groups: BTreeMap<String, (usize, Option<Report>)> = BTreeMap::new();
+
+    let combined_reports = load_combined_reports(reports_dir)?;
+    print_duplicate_clones(&combined_reports.clones);
+    for (_, report) in combined_reports.reports {
+        let group_name = match config.group_by {
+            RollupGroupBy::None => String::new(),
+            RollupGroupBy::Org => report.remote_org.clone().unwrap_or_else(|| NO_REMOTE_GROUP.to_owned()),
+        };
+
+        let group = groups.entry(group_name).or_insert((0, None));
+        group.0 += 1;
+        group.1 = Report::merge_with_limits(group.1.take(), report, &config.merge_limits);
+    }
+
+    if groups.is_empty() {
+        println!("    No project reports found in {}", reports_dir.to_string_lossy());
+        println!("    Run `stackmuncher` inside a project folder to create one.");
+        return Ok(());
+    }
+
+    println!();
+    for (group_name, (project_count, report)) in groups {
+        let mut report = match report {
+            Some(v) => v,
+            None => continue,
+        };
+
+        if group_name.is_empty() {
+            println!("    All projects ({}):", project_count);
+        } else {
+            println!("    {} ({} projects):", group_name, project_count);
+        }
+        println!("        {}", get_per_tech_stats(&report, config.loc_weighting));
+        print_work_distribution(&report);
+        print_working_hours_overlap(&mut report, &config.workday);
+        println!();
+    }
+
+    Ok(())
+}
+
+/// Returns the paths to the combined reports of all projects in the reports folder, sorted by the project folder name.
+/// Every project has its own subfolder with a combined report in it. Folders without one are skipped.
+fn combined_report_files(reports_dir: &PathBuf) -> Result<Vec<PathBuf>, ()> {
+    let dir_entries = match fs::read_dir(reports_dir) {
+        Ok(v) => v,
+        Err(e) => {
+            eprintln!(
+                "STACKMUNCHER ERROR: cannot read the reports folder {} due to {}",
+                reports_dir.to_string_lossy(),
+                e
+            );
+            return Err(());
+        }
+    };
+
+    let combined_report_file_name = [
+        Config::CONTRIBUTOR_REPORT_COMBINED_FILE_NAME,
+        Config::REPORT_FILE_EXTENSION,
+    ]
+    .concat();
+
+    let mut report_files = Vec::new();
+    for dir_entry in dir_entries.filter_map(|v| v.ok()) {
+        // `doctor --fix` replaces the folders of duplicate clones with symlinks to the folder of the other clone
+        if dir_entry.file_type().map(|v| v.is_symlink()).unwrap_or_default() {
+            debug!("Skipped symlink {}", dir_entry.path().to_string_lossy());
+            continue;
+        }
+        let report_file = dir_entry.path().join(&combined_report_file_name);
+        if report_file.is_file() {
+            report_files.push(report_file);
+        } else {
+            debug!("No combined report in {}", dir_entry.path().to_string_lossy());
+        }
+    }
+    report_files.sort();
+
+    Ok(report_files)
+}
+
+/// Loads the combined reports of all projects in the reports folder, sorted by the project folder name.
+/// Clones of the same `origin` remote at different paths are included once under the most recently analyzed clone
+/// with the reports of the other clones merged into it, so that the same commits are not counted twice.
+pub(crate) fn load_combined_reports(reports_dir: &PathBuf) -> Result<CombinedReports, ()> {
+    let mut reports = Vec::new();
+    for report_file in combined_report_files(reports_dir)? {
+        match Report::from_disk(&report_file) {
+            Some(v) => reports.push((report_file, v)),
+            None => warn!("Cannot load {}", report_file.to_string_lossy()),
+        }
+    }
+
+    // the newest report of every remote is kept, ties are broken by the folder name
+    reports.sort_by(|(a_file, a), (b_file, b)| b.timestamp.cmp(&a.timestamp).then(a_file.cmp(b_file)));
+
+    // remote URL hash -> the position of the clone that was kept in unique_reports
+    let mut kept_reports: HashMap<String, usize> = HashMap::new();
+    let mut unique_reports: Vec<(PathBuf, Report)> = Vec::with_capacity(reports.len());
+    let mut clones = Vec::new();
+    for (report_file, report) in reports {
+        let hash = match report.remote_url_hash.clone() {
+            Some(v) => v,
+            None => {
+                unique_reports.push((report_file, report));
+                continue;
+            }
+        };
+
+        if let Some(kept_report) = kept_reports.get(&hash).and_then(|v| unique_reports.get_mut(*v)) {
+            let report_dir = report_file.parent().map(|v| v.to_path_buf()).unwrap_or_default();
+            let kept_report_dir = kept_report.0.parent().map(|v| v.to_path_buf()).unwrap_or_default();
+            debug!("{} is a clone of {}", report_dir.to_string_lossy(), kept_report_dir.to_string_lossy());
+            // the older clone may have commits that are not in the newer one, e.g. on a local branch
+            kept_report.1.merge_clone(report);
+            clones.push(DuplicateClone {
+                report_dir,
+                kept_report_dir,
+            });
+            continue;
+        }
+
+        kept_reports.insert(hash, unique_reports.len());
+        unique_reports.push((report_file, report));
+    }
+
+    unique_reports.sort_by(|(a, _), (b, _)| a.cmp(b));
+    clones.sort_by(|a, b| a.report_dir.cmp(&b.report_dir));
+
+    Ok(CombinedReports {
+        reports: unique_reports,
+        clones,
+    })
+}
+
+/// Prints the report folders merged into other projects as their clones, e.g.
+/// `Merged home_me_tmp_stm_app_2c9e4f01 into its clone home_me_projects_stm_app_6bdf08b3`.
+pub(crate) fn print_duplicate_clones(clones: &[DuplicateClone]) {
+    for clone in clones {
+        println!(
+            "    Merged {} into its clone {}",
+            report_dir_name(&clone.report_dir),
+            report_dir_name(&clone.kept_report_dir)
+        );
+    }
+}
+
+/// Returns the last part of the report folder path, which is the canonical name of the project.
+fn report_dir_name(report_dir: &Path) -> String {
+    report_dir
+        .file_name()
+        .map(|v| v.to_string_lossy().to_string())
+        .unwrap_or_default()
+}
+
+/// Prints the number of working hours overlapping with the recent commit times, e.g. `Europe/Berlin 6h, US/Pacific 2h`.
+/// The overlap is printed for the top few UTC offsets if no timezones were configured.
+fn print_working_hours_overlap(report: &mut Report, workday: &WorkdayOverlap) {
+    let histo = match report.commit_time_histo.as_mut() {
+        Some(v) => v,
+        None => return,
+    };
+    histo.recalculate_counts_to_percentage(workday);
+
+    let overlap = if workday.timezones.is_empty() {
+        let mut offsets = histo
+            .timezone_overlap_recent
+            .to_array()
+            .iter()
+            .enumerate()
+            .filter(|(_, hours)| **hours > 0)
+            .map(|(offset, hours)| {
+                // offsets over 12 are negative, e.g. 18 is UTC-6
+                let offset = if offset > 12 { offset as i32 - 24 } else { offset as i32 };
+                (offset, *hours)
+            })
+            .collect::<Vec<(i32, u64)>>();
+        offsets.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        offsets
+            .iter()
+            .take(TOP_OVERLAP_OFFSETS)
+            .map(|(offset, hours)| format!("UTC{:+} {}h", offset, hours))
+            .collect::<Vec<String>>()
+    } else {
+        histo
+            .timezone_overlap_zones_recent
+            .iter()
+            .map(|(tz, hours)| format!("{} {}h", tz, hours))
+            .collect::<Vec<String>>()
+    };
+
+    if overlap.is_empty() {
+        return;
+    }
+
+    println!();
+    println!(
+        "        Working hours overlap ({}:00 - {}:00): {}",
+        workday.start_hour,
+        workday.end_hour,
+        overlap.join(", ")
+    );
+}
+
+/// Prints the share of commits per project for the most recent quarters, e.g. `2022Q1  stm_app 70%, stm_server 30%`.
+fn print_work_distribution(report: &Report) {
+    let work_distribution = match &report.work_distribution {
+        Some(v) if !v.is_empty() => v,
+        _ => return,
+    };
+
+    println!();
+    for quarter in work_distribution
+        .iter()
+        .skip(work_distribution.len().saturating_sub(WORK_DISTRIBUTION_QUARTERS))
+    {
+        let projects = quarter
+            .projects
+            .iter()
+            .map(|p| format!("{} {}%", p.project_name, p.percentage))
+            .collect::<Vec<String>>()
+            .join(", ");
+        println!("        {}  {}", quarter.quarter, projects);
+    }
+}
+
+#[cfg(test)]
+mod test_cmd_rollup {
+    use super::load_combined_reports;
+    use stackmuncher_lib::config::Config;
+
+    #[test]
+    fn test_load_combined_reports() {
+        let reports_dir = std::env::temp_dir().join(format!("stm_rollup_test_{}", std::process::id()));
+
+        // a and b are clones of the same repo with a different file and commit each, c is a fork of it
+        for (project, timestamp, hash, file_name, commit) in [
+            ("a", "2023-01-01T00:00:00+00:00", "origin", "src/a.rs", "aaaaaaaa_1672531200"),
+            ("b", "2023-02-01T00:00:00+00:00", "origin", "src/b.rs", "bbbbbbbb_1675209600"),
+            ("c", "2023-03-01T00:00:00+00:00", "fork", "src/c.rs", "cccccccc_1677628800"),
+        ]
+        .iter()
+        {
+            let report = serde_json::json!({
+                "timestamp": timestamp,
+                "remote_url_hash": hash,
+                "recent_project_commits": [commit],
+                "commit_count_contributor": 1,
+                "tech": [],
+                "per_file_tech": [{"file_name": file_name, "language": "Rust", "muncher_name": "rust", "files": 1, "total_lines": 10,
+                    "blank_lines": 0, "bracket_only_lines": 0, "code_lines": 10, "inline_comments": 0, "line_comments": 0,
+                    "block_comments": 0, "docs_comments": 0}],
+            });
+            let report_dir = reports_dir.join(project);
+            std::fs::create_dir_all(&report_dir).unwrap();
+            std::fs::write(
+                report_dir.join(
+                    [
+                        Config::CONTRIBUTOR_REPORT_COMBINED_FILE_NAME,
+                        Config::REPORT_FILE_EXTENSION,
+                    ]
+                    .concat(),
+                ),
+                serde_json::to_vec(&report).unwrap(),
+            )
+            .unwrap();
+        }
+
+        let combined_reports = load_combined_reports(&reports_dir).unwrap();
+        std::fs::remove_dir_all(&reports_dir).unwrap();
+
+        // the older clone is merged into the newer one and the fork is a project of its own
+        assert_eq!(combined_reports.clones.len(), 1);
+        assert!(combined_reports.clones[0].report_dir.ends_with("a"));
+        assert!(combined_reports.clones[0].kept_report_dir.ends_with("b"));
+        assert_eq!(combined_reports.reports.len(), 2);
+
+        let (report_file, report) = &combined_reports.reports[0];
+        assert!(report_file.parent().unwrap().ends_with("b"));
+        assert_eq!(report.tech.iter().map(|tech| tech.code_lines).sum::<u64>(), 20);
+        assert_eq!(
+            report.recent_project_commits.as_ref().unwrap(),
+            &vec!["bbbbbbbb_1675209600".to_owned(), "aaaaaaaa_1672531200".to_owned()]
+        );
+        assert_eq!(report.commit_count_contributor, Some(2));
+
+        let (report_file, report) = &combined_reports.reports[1];
+        assert!(report_file.parent().unwrap().ends_with("c"));
+        assert_eq!(report.tech.iter().map(|tech| tech.code_lines).sum::<u64>(), 0);
+    }
+}
@@ -0,0 +1,231 @@
+use crate::app_args::RulesAction;
+use crate::config::AppConfig;
+use stackmuncher_lib::code_rules::{CodeRules, RulesFileValidation};
+use stackmuncher_lib::muncher::Muncher;
+use stackmuncher_lib::processors::sample::{self, SampleExpectations};
+use std::path::PathBuf;
+
+/// Validates the embedded rules or the rules from `config.rules_dir` and prints the results.
+/// Returns an error if any of the rules are invalid for scripts and CI to pick it up from the exit code.
+pub(crate) fn run(config: &AppConfig) -> Result<(), ()> {
+    match config.rules_action {
+        Some(RulesAction::Check) => check(config),
+        Some(RulesAction::Test) => test(config),
+        None => {
+            eprintln!("STACKMUNCHER ERROR: no action for `rules` command. It's a bug.");
+            Err(())
+        }
+    }
+}
+
+/// Prints errors per rules file, muncher hashes and the munchers for every file extension.
+fn check(config: &AppConfig) -> Result<(), ()> {
+    let validation = match CodeRules::validate(config.rules_dir.as_deref()) {
+        Some(v) => v,
+        None => {
+            eprintln!(
+                "STACKMUNCHER ERROR: cannot read the rules from {}. It should have `file_types` and `munchers` sub-folders.",
+                config
+                    .rules_dir
+                    .as_ref()
+                    .map(|v| v.to_string_lossy().to_string())
+                    .unwrap_or_default()
+            );
+            return Err(());
+        }
+    };
+
+    println!();
+    match &config.rules_dir {
+        Some(rules_dir) => println!("    Rules folder: {}", rules_dir.to_string_lossy()),
+        None => println!("    Rules folder: built-in"),
+    }
+    println!();
+
+    println!("MUNCHERS:");
+    for muncher in &validation.munchers {
+        let hash = muncher
+            .muncher_hash
+            .map(|v| v.to_string())
+            .unwrap_or_else(|| "-".to_owned());
+        println!("    {:<40}{}", muncher.file_name, hash);
+        print_errors(muncher);
+    }
+    println!();
+
+    println!("FILE TYPES:");
+    for (ext, munchers) in &validation.extensions {
+        println!("    {:<40}{}", ext, munchers.join(", "));
+    }
+    for file_type in &validation.file_types {
+        if !file_type.errors.is_empty() {
+            println!("    {}", file_type.file_name);
+            print_errors(file_type);
+        }
+    }
+    println!();
+
+    let error_count = validation.error_count();
+    if error_count == 0 {
+        println!(
+            "    {} munchers and {} file types are valid",
+            validation.munchers.len(),
+            validation.file_types.len()
+        );
+        println!();
+        Ok(())
+    } else {
+        eprintln!("STACKMUNCHER ERROR: found {} errors in the rules", error_count);
+        Err(())
+    }
+}
+
+/// Munches every sample file in `config.rules_sample_dir` with `config.rules_muncher` and prints PASS or FAIL per file
+/// with the mismatched counts. Samples without a sidecar file are printed with all the actual counts to start the sidecar from.
+fn test(config: &AppConfig) -> Result<(), ()> {
+    let (muncher_name, sample_dir) = match (&config.rules_muncher, &config.rules_sample_dir) {
+        (Some(muncher_name), Some(sample_dir)) => (muncher_name, sample_dir),
+        _ => {
+            eprintln!("STACKMUNCHER ERROR: no muncher or samples folder for `rules test` command. It's a bug.");
+            return Err(());
+        }
+    };
+
+    let (muncher, host_munchers) = match CodeRules::load_muncher_with_hosts(config.rules_dir.as_deref(), muncher_name) {
+        Some(v) => v,
+        None => {
+            eprintln!(
+                "STACKMUNCHER ERROR: cannot load muncher `{}`. Run `stackmuncher rules check` for the list of munchers and errors.",
+                muncher_name
+            );
+            return Err(());
+        }
+    };
+    let host_munchers = host_munchers.iter().collect::<Vec<&Muncher>>();
+
+    let samples = get_sample_files(sample_dir)?;
+    if samples.is_empty() {
+        eprintln!("STACKMUNCHER ERROR: no sample files in {}", sample_dir.to_string_lossy());
+        return Err(());
+    }
+
+    println!();
+    println!("    Muncher: {}", muncher_name);
+    println!();
+
+    let (mut passed, mut failed) = (0, 0);
+    for sample_file in samples {
+        let file_name = sample_file
+            .file_name()
+            .map(|v| v.to_string_lossy().to_string())
+            .unwrap_or_default();
+
+        let contents = match std::fs::read(&sample_file) {
+            Ok(v) => v,
+            Err(e) => {
+                println!("    FAIL {}", file_name);
+                println!("        cannot read the sample due to {}", e);
+                failed += 1;
+                continue;
+            }
+        };
+
+        let tech = match sample::munch_sample(&file_name, &contents, &muncher, &host_munchers, &config.munch_limits) {
+            Ok(mut v) if !v.is_empty() => v.remove(0),
+            Ok(_) | Err(_) => {
+                println!("    FAIL {}", file_name);
+                println!("        the muncher returned no results, run with `--log debug` for details");
+                failed += 1;
+                continue;
+            }
+        };
+
+        let mut sidecar_file = sample_file.clone().into_os_string();
+        sidecar_file.push(SampleExpectations::SIDECAR_FILE_SUFFIX);
+        let expectations = match std::fs::read_to_string(&sidecar_file) {
+            Ok(v) => match serde_json::from_str::<SampleExpectations>(&v) {
+                Ok(v) => v,
+                Err(e) => {
+                    println!("    FAIL {}", file_name);
+                    println!("        invalid {} due to {}", PathBuf::from(&sidecar_file).to_string_lossy(), e);
+                    failed += 1;
+                    continue;
+                }
+            },
+            Err(_) => {
+                println!("    FAIL {}", file_name);
+                println!(
+                    "        no {}{} with the expected counts. The actual counts are:",
+                    file_name,
+                    SampleExpectations::SIDECAR_FILE_SUFFIX
+                );
+                let actual = serde_json::to_string_pretty(&SampleExpectations::from_tech(&tech)).unwrap_or_default();
+                for line in actual.lines() {
+                    println!("        {}", line);
+                }
+                failed += 1;
+                continue;
+            }
+        };
+
+        let mismatches = expectations.compare(&tech);
+        if mismatches.is_empty() {
+            println!("    PASS {}", file_name);
+            passed += 1;
+        } else {
+            println!("    FAIL {}", file_name);
+            for mismatch in mismatches {
+                println!("        {:<40}expected {}, actual {}", mismatch.field, mismatch.expected, mismatch.actual);
+            }
+            failed += 1;
+        }
+    }
+    println!();
+
+    if failed == 0 {
+        println!("    {} samples passed", passed);
+        println!();
+        Ok(())
+    } else {
+        eprintln!("STACKMUNCHER ERROR: {} of {} samples failed", failed, passed + failed);
+        Err(())
+    }
+}
+
+/// Returns the sample files from `sample_dir`, sorted by name, without the sidecar files and sub-folders.
+fn get_sample_files(sample_dir: &PathBuf) -> Result<Vec<PathBuf>, ()> {
+    let dir = match std::fs::read_dir(sample_dir) {
+        Ok(v) => v,
+        Err(e) => {
+            eprintln!("STACKMUNCHER ERROR: cannot read {} due to {}", sample_dir.to_string_lossy(), e);
+            return Err(());
+        }
+    };
+
+    let mut samples = dir
+        .filter_map(|v| v.ok())
+        .map(|v| v.path())
+        .filter(|v| v.is_file())
+        .filter(|v| !v.to_string_lossy().ends_with(SampleExpectations::SIDECAR_FILE_SUFFIX))
+        .collect::<Vec<PathBuf>>();
+    samples.sort();
+
+    Ok(samples)
+}
+
+/// Prints the errors of a rules file, if any, indented under the file name.
+fn print_errors(file: &RulesFileValidation) {
+    for error in &file.errors {
+        match error.line {
+            Some(line) => println!("        ERROR at line {}:", line),
+            None => println!("        ERROR:"),
+        }
+        if let Some(context) = &error.context {
+            println!("            {}", context);
+        }
+        // regex errors span several lines with a pointer to the invalid part
+        for line in error.message.lines() {
+            println!("            {}", line);
+        }
+    }
+}
@@ -0,0 +1,164 @@
+use crate::app_args::SbomFormat;
+use crate::config::AppConfig;
+use stackmuncher_lib::git::{GitBlob, ListOfBlobs};
+use stackmuncher_lib::report::sbom::LOCK_FILES;
+use stackmuncher_lib::{config::Config, report::Report, report::Sbom, vcs};
+use std::path::Path;
+use tracing::{info, warn};
+
+/// Exports the libraries used by the project from the project report generated by a previous run as a CycloneDX
+/// or SPDX document. Lock files in the project root at the commit of the report add exact versions.
+/// The document is printed to stdout or saved into `config.graph_out`.
+pub(crate) async fn run(config: AppConfig) -> Result<(), ()> {
+    let report_dir = Path::new(
+        config
+            .lib_config
+            .project_report_dir
+            .as_ref()
+            .expect("Cannot unwrap config.report_dir. It's a bug."),
+    );
+
+    let project_report_filename =
+        report_dir.join([Config::PROJECT_REPORT_FILE_NAME, Config::REPORT_FILE_EXTENSION].concat());
+    let project_report = match Report::from_disk(&project_report_filename) {
+        Some(v) => v,
+        None => {
+            eprintln!(
+                "STACKMUNCHER ERROR: no project report for {}",
+                config.lib_config.project_dir.to_string_lossy()
+            );
+            eprintln!("    Run `stackmuncher` inside the project folder to analyze it first.");
+            return Err(());
+        }
+    };
+
+    let project_dir = &config.lib_config.project_dir;
+    let project_name = project_dir
+        .file_name()
+        .map(|v| v.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let mut sbom = Sbom::from_report(&project_report, &project_name);
+
+    for (lock_file, contents) in read_lock_files(project_dir, project_report.report_commit_sha1.as_deref()).await {
+        info!("Adding packages from {}", lock_file);
+        sbom.add_lock_file(&lock_file, &contents);
+    }
+
+    let tool_version = env!("CARGO_PKG_VERSION");
+    let output = match config.sbom_format {
+        SbomFormat::CycloneDx => sbom.to_cyclonedx(tool_version),
+        SbomFormat::Spdx => sbom.to_spdx(tool_version),
+    };
+    let output = serde_json::to_string_pretty(&output).expect("Cannot serialize the SBOM. It's a bug.");
+
+    match &config.graph_out {
+        None => println!("{}", output),
+        Some(out_file) => {
+            if let Err(e) = std::fs::write(out_file, output) {
+                eprintln!("STACKMUNCHER ERROR: cannot save the SBOM in {} due to {}", out_file.to_string_lossy(), e);
+                return Err(());
+            }
+            println!(
+                "    SBOM with {} components saved in {}",
+                sbom.components.len(),
+                out_file.to_string_lossy()
+            );
+        }
+    }
+
+    Ok(())
+}
+
+/// Returns the names and contents of `LOCK_FILES` in the tree at `commit_sha1` or HEAD if None. The working directory
+/// may be at a different commit or have uncommitted changes, which would not match the report.
+async fn read_lock_files(project_dir: &Path, commit_sha1: Option<&str>) -> Vec<(String, Vec<u8>)> {
+    let vcs = vcs::detect(project_dir);
+    let commit_sha1 = match vcs.get_head_commit(project_dir, commit_sha1).await {
+        Ok(v) => v,
+        Err(_) => {
+            warn!("Cannot find the commit of the report. No lock files are added.");
+            return Vec::new();
+        }
+    };
+
+    let lock_file_blobs = LOCK_FILES
+        .iter()
+        .map(|lock_file| {
+            let blob = GitBlob {
+                sha1: String::new(),
+                commit_sha1: commit_sha1.clone(),
+                commit_date_epoch: 0,
+                commit_date_iso: String::new(),
+            };
+            (lock_file.to_string(), blob)
+        })
+        .collect::<ListOfBlobs>();
+    let ignore_case = vcs.is_ignore_case(project_dir).await;
+    let lock_file_blobs = match vcs
+        .populate_blob_sha1(project_dir, lock_file_blobs, Some(commit_sha1), ignore_case)
+        .await
+    {
+        Ok(v) => v,
+        Err(_) => return Vec::new(),
+    };
+
+    let mut lock_files = Vec::new();
+    for (lock_file, blob) in lock_file_blobs {
+        match vcs
+            .get_blob_contents(project_dir, &lock_file, &blob.sha1, &blob.commit_sha1)
+            .await
+        {
+            Ok(contents) => lock_files.push((lock_file, contents)),
+            Err(_) => warn!("Cannot read {}", lock_file),
+        }
+    }
+    lock_files.sort();
+
+    lock_files
+}
+
+#[cfg(test)]
+mod test_cmd_sbom {
+    use super::read_lock_files;
+    use std::process::Command;
+
+    #[tokio::test]
+    async fn test_read_lock_files() {
+        let repo_dir = std::env::temp_dir().join(format!("stm_sbom_test_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&repo_dir);
+        std::fs::create_dir_all(&repo_dir).unwrap();
+        let git = |args: &[&str]| {
+            let output = Command::new("git")
+                .args(["-c", "user.name=a", "-c", "user.email=a@example.com"])
+                .args(args)
+                .current_dir(&repo_dir)
+                .output()
+                .unwrap();
+            assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+            String::from_utf8_lossy(&output.stdout).trim().to_owned()
+        };
+
+        git(&["init", "-q"]);
+        std::fs::write(repo_dir.join("Cargo.lock"), "version = 1\n").unwrap();
+        git(&["add", "-A"]);
+        git(&["commit", "-q", "-m", "first"]);
+        let first_commit = git(&["rev-parse", "HEAD"]);
+        std::fs::write(repo_dir.join("Cargo.lock"), "version = 2\n").unwrap();
+        git(&["commit", "-q", "-am", "second"]);
+
+        // uncommitted changes and untracked lock files are not part of the report
+        std::fs::write(repo_dir.join("Cargo.lock"), "version = 3\n").unwrap();
+        std::fs::write(repo_dir.join("composer.lock"), "{}").unwrap();
+
+        assert_eq!(
+            read_lock_files(&repo_dir, Some(&first_commit)).await,
+            vec![("Cargo.lock".to_owned(), b"version = 1\n".to_vec())]
+        );
+        assert_eq!(
+            read_lock_files(&repo_dir, None).await,
+            vec![("Cargo.lock".to_owned(), b"version = 2\n".to_vec())]
+        );
+
+        let _ = std::fs::remove_dir_all(&repo_dir);
+    }
+}
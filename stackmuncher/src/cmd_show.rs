@@ -0,0 +1,104 @@
+use crate::config::AppConfig;
+use stackmuncher_lib::{config::Config, report::Report};
+use std::path::Path;
+
+/// Prints the sections of the project report generated by a previous run requested in `config`, e.g. hotspots.
+pub(crate) fn run(config: &AppConfig) -> Result<(), ()> {
+    let report_dir = Path::new(
+        config
+            .lib_config
+            .project_report_dir
+            .as_ref()
+            .expect("Cannot unwrap config.report_dir. It's a bug."),
+    );
+
+    let project_report_filename =
+        report_dir.join([Config::PROJECT_REPORT_FILE_NAME, Config::REPORT_FILE_EXTENSION].concat());
+    let project_report = match Report::from_disk(&project_report_filename) {
+        Some(v) => v,
+        None => {
+            eprintln!(
+                "STACKMUNCHER ERROR: no project report for {}",
+                config.lib_config.project_dir.to_string_lossy()
+            );
+            eprintln!("    Run `stackmuncher` inside the project folder to analyze it first.");
+            return Err(());
+        }
+    };
+
+    if config.show_hotspots {
+        print_hotspots(&project_report);
+    }
+
+    if config.show_modules {
+        print_central_modules(&project_report);
+    }
+
+    Ok(())
+}
+
+/// Prints the hotspots as a table with the hottest file first.
+pub(crate) fn print_hotspots(report: &Report) {
+    println!();
+    let hotspots = match &report.hotspots {
+        Some(v) if !v.files.is_empty() => v,
+        _ => {
+            println!("    No hotspots found. Run `stackmuncher` inside the project folder to update the report.");
+            println!();
+            return;
+        }
+    };
+
+    println!(
+        "    Hotspots: {} files changed most often × their LoC from {} commits",
+        hotspots.files.len(),
+        hotspots.commits_analyzed
+    );
+    println!();
+    println!("    {:>3}  {:>7}  {:>7}  {:>9}  File", "#", "Commits", "LoC", "Score");
+    for (idx, hotspot) in hotspots.files.iter().enumerate() {
+        println!(
+            "    {:>3}  {:>7}  {:>7}  {:>9}  {}",
+            idx + 1,
+            hotspot.commits,
+            hotspot.loc,
+            hotspot.score,
+            hotspot.file_name
+        );
+    }
+    println!();
+}
+
+/// Prints the files most imported by or importing other project files as a table with the most central file first.
+fn print_central_modules(report: &Report) {
+    println!();
+    let import_graph = match &report.import_graph {
+        Some(v) if !v.central_modules.is_empty() => v,
+        _ => {
+            println!("    No imports between project files found. Run `stackmuncher` inside the project folder to update the report.");
+            println!();
+            return;
+        }
+    };
+
+    println!(
+        "    Central modules: {} of {} files with {} imports between them",
+        import_graph.central_modules.len(),
+        import_graph.modules,
+        import_graph.imports
+    );
+    println!();
+    println!("    {:>3}  {:>6}  {:>7}  {:>8}  {:>7}  File", "#", "Fan-in", "Fan-out", "Ext refs", "LoC");
+    for (idx, module) in import_graph.central_modules.iter().enumerate() {
+        println!(
+            "    {:>3}  {:>6}  {:>7}  {:>8}  {:>7}  {}",
+            idx + 1,
+            module.fan_in,
+            module.fan_out,
+            module.ext_refs,
+            module.loc,
+            module.file_name
+        );
+    }
+    println!();
+}
@@ -0,0 +1,245 @@
+use crate::cmd_audit::AuditEntry;
+use crate::config::{AppConfig, CONFIG_LOCK_WAIT};
+use crate::lock::ProjectLock;
+use crate::signing::ReportSignature;
+use path_absolutize::Absolutize;
+use serde::{Deserialize, Serialize};
+use stackmuncher_lib::utils::write_atomically;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use tracing::{debug, error};
+
+/// The name of the file in the config folder with the outcome of the last submissions per project.
+const RECEIPTS_FILE_NAME: &str = "receipts.json";
+
+/// The response of the Directory to a single submission.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub(crate) struct Receipt {
+    /// The time of the submission in RFC3339 format, UTC.
+    pub timestamp: String,
+    pub endpoint: String,
+    /// Same as `AuditEntry.payload_sha256`.
+    pub payload_sha256: String,
+    pub report_id: String,
+    /// The HTTP status with the start of the response body or the error if the request failed, e.g. `200 OK`.
+    pub response: String,
+}
+
+/// The last accepted and the last failed submission of a single project.
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq)]
+pub(crate) struct ProjectReceipts {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_success: Option<Receipt>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_failure: Option<Receipt>,
+}
+
+impl ProjectReceipts {
+    /// Returns true if the last submission failed. The report is submitted again after the next commit.
+    fn is_pending(&self) -> bool {
+        match (&self.last_success, &self.last_failure) {
+            (_, None) => false,
+            (None, Some(_)) => true,
+            (Some(success), Some(failure)) => failure.timestamp >= success.timestamp,
+        }
+    }
+}
+
+/// Records the response of the Directory for the current project in the receipts file in the config folder.
+/// `accepted` is true if the Directory accepted the report. Failures are logged, but do not stop the submission.
+pub(crate) async fn save_receipt(config: &AppConfig, audit_entry: &AuditEntry, response: &str, accepted: bool) {
+    let receipt = Receipt {
+        timestamp: audit_entry.timestamp.clone(),
+        endpoint: audit_entry.endpoint.clone(),
+        payload_sha256: audit_entry.payload_sha256.clone(),
+        report_id: audit_entry.report_id.clone(),
+        response: response.to_owned(),
+    };
+    add_receipt(&receipts_file(config), project_key(&config.lib_config.project_dir), receipt, accepted).await;
+}
+
+/// Adds the receipt for `project` to the receipts file. The file is shared by all projects, so it is updated
+/// under the config folder lock to keep concurrent runs from overwriting each other's receipts.
+async fn add_receipt(receipts_file: &Path, project: String, receipt: Receipt, accepted: bool) {
+    let config_dir = receipts_file.parent().unwrap_or_else(|| Path::new("."));
+    let _lock = match ProjectLock::acquire_with_wait(config_dir, CONFIG_LOCK_WAIT).await {
+        Ok(Some(v)) => v,
+        _ => {
+            error!(
+                "Cannot lock {} to save the receipt. Held by {}.",
+                config_dir.to_string_lossy(),
+                ProjectLock::holder(config_dir).unwrap_or_else(|| "unknown".to_owned())
+            );
+            return;
+        }
+    };
+
+    let mut receipts = read_receipts(receipts_file);
+    let project_receipts = receipts.entry(project).or_default();
+    if accepted {
+        project_receipts.last_success = Some(receipt);
+    } else {
+        project_receipts.last_failure = Some(receipt);
+    }
+
+    let contents = match serde_json::to_vec_pretty(&receipts) {
+        Ok(v) => v,
+        Err(e) => {
+            error!("Cannot serialize submission receipts due to {}. It's a bug.", e);
+            return;
+        }
+    };
+    match write_atomically(receipts_file, &contents) {
+        Ok(_) => debug!("Receipt saved in {}", receipts_file.to_string_lossy()),
+        Err(e) => error!("Cannot save receipts in {} due to {}", receipts_file.to_string_lossy(), e),
+    }
+}
+
+/// Prints the profile URLs, the number of projects waiting for a successful submission and the last accepted
+/// submission per project. Nothing is sent to the Directory.
+pub(crate) fn run(config: &AppConfig) -> Result<(), ()> {
+    let receipts_file = receipts_file(config);
+    let receipts = read_receipts(&receipts_file);
+    let public_key = ReportSignature::get_public_key(&config.user_key_pair);
+
+    println!();
+    match &config.gh_login {
+        Some(login) if !login.is_empty() => {
+            println!("    Public profile:      https://stackmuncher.com/{}", login)
+        }
+        _ => println!("    Public profile:      not set up, see `stackmuncher github --help`"),
+    }
+    println!("    Anonymous profile:   https://stackmuncher.com/?dev={}", public_key);
+
+    if receipts.is_empty() {
+        println!();
+        println!("    No reports were submitted from this machine yet.");
+        println!();
+        return Ok(());
+    }
+
+    let pending = receipts.values().filter(|v| v.is_pending()).count();
+    println!("    Pending submissions: {} (resubmitted after the next commit)", pending);
+    println!();
+
+    for (project, project_receipts) in &receipts {
+        println!("{}", project);
+        match &project_receipts.last_success {
+            Some(v) => {
+                println!("    Last accepted:  {}  {}", v.timestamp, v.response);
+                println!("    Report:         {}, SHA256 {}", v.report_id, v.payload_sha256);
+            }
+            None => println!("    Last accepted:  never"),
+        }
+        if project_receipts.is_pending() {
+            if let Some(v) = &project_receipts.last_failure {
+                println!("    Last failure:   {}  {}", v.timestamp, v.response);
+            }
+        }
+        println!();
+    }
+    println!("    Details of all submissions: `stackmuncher audit`");
+    println!();
+
+    Ok(())
+}
+
+/// Returns the path to the receipts file in the config folder.
+fn receipts_file(config: &AppConfig) -> PathBuf {
+    config.config_file_path.with_file_name(RECEIPTS_FILE_NAME)
+}
+
+/// Returns the absolute path of the project to key the receipts by.
+fn project_key(project_dir: &Path) -> String {
+    match project_dir.absolutize() {
+        Ok(v) => v.to_string_lossy().to_string(),
+        Err(_) => project_dir.to_string_lossy().to_string(),
+    }
+}
+
+/// Returns the receipts per project or an empty list if the file does not exist or is invalid.
+fn read_receipts(receipts_file: &Path) -> BTreeMap<String, ProjectReceipts> {
+    let contents = match std::fs::read(receipts_file) {
+        Ok(v) => v,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return BTreeMap::new(),
+        Err(e) => {
+            error!("Cannot read {} due to {}", receipts_file.to_string_lossy(), e);
+            return BTreeMap::new();
+        }
+    };
+
+    match serde_json::from_slice::<BTreeMap<String, ProjectReceipts>>(&contents) {
+        Ok(v) => v,
+        Err(e) => {
+            error!("Invalid receipts file {} due to {}", receipts_file.to_string_lossy(), e);
+            BTreeMap::new()
+        }
+    }
+}
+
+#[cfg(test)]
+mod test_status {
+    use super::{add_receipt, read_receipts, ProjectReceipts, Receipt};
+
+    fn receipt(timestamp: &str) -> Option<Receipt> {
+        Some(Receipt {
+            timestamp: timestamp.to_owned(),
+            endpoint: "https://inbox.example.com".to_owned(),
+            payload_sha256: String::new(),
+            report_id: String::new(),
+            response: String::new(),
+        })
+    }
+
+    #[test]
+    fn test_is_pending() {
+        assert!(!ProjectReceipts::default().is_pending());
+
+        let mut project_receipts = ProjectReceipts {
+            last_success: None,
+            last_failure: receipt("2021-07-27T10:00:00Z"),
+        };
+        assert!(project_receipts.is_pending());
+
+        // a later success clears the failure
+        project_receipts.last_success = receipt("2021-07-28T10:00:00Z");
+        assert!(!project_receipts.is_pending());
+
+        project_receipts.last_failure = receipt("2021-07-29T10:00:00Z");
+        assert!(project_receipts.is_pending());
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_add_receipt() {
+        let config_dir = std::env::temp_dir().join(format!("stm_receipts_test_{}", std::process::id()));
+        std::fs::create_dir_all(&config_dir).unwrap();
+        let receipts_file = config_dir.join("receipts.json");
+
+        // concurrent runs for different projects keep each other's receipts
+        let mut tasks = Vec::new();
+        for i in 0..4 {
+            let receipts_file = receipts_file.clone();
+            tasks.push(tokio::spawn(async move {
+                let accepted = i < 2;
+                add_receipt(
+                    &receipts_file,
+                    format!("/projects/{}", i),
+                    receipt("2021-07-27T10:00:00Z").unwrap(),
+                    accepted,
+                )
+                .await;
+            }));
+        }
+        for task in tasks {
+            task.await.unwrap();
+        }
+
+        let receipts = read_receipts(&receipts_file);
+        assert_eq!(receipts.len(), 4);
+        assert!(receipts["/projects/0"].last_success.is_some());
+        assert!(receipts["/projects/3"].is_pending());
+        assert!(!config_dir.join(crate::lock::LOCK_FILE_NAME).exists());
+
+        std::fs::remove_dir_all(&config_dir).unwrap();
+    }
+}
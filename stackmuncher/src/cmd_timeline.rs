@@ -0,0 +1,288 @@
+use crate::cmd_munch::{get_per_tech_stats, print_combined_stats};
+use crate::config::AppConfig;
+use crate::lock;
+use chrono::{DateTime, Months, NaiveDate, Utc};
+use stackmuncher_lib::analyzer::{AnalysisOptions, AnalysisOutcome, Analyzer};
+use stackmuncher_lib::code_rules::CodeRules;
+use stackmuncher_lib::git::{self, GitLogEntry};
+use stackmuncher_lib::vcs::{self, VcsKind};
+use std::path::{Path, PathBuf};
+
+/// The prefix of the project report subfolders with the snapshots, e.g. `as_of_2023-06-01`.
+const SNAPSHOT_FOLDER_PREFIX: &str = "as_of_";
+
+/// Analyzes the project as it was on `as_of` date and prints the summary. The reports are saved in a dated subfolder
+/// of the project reports, so the reports and caches of the current state are left as-is. Nothing is submitted.
+pub(crate) async fn run_as_of(config: &AppConfig, as_of: NaiveDate) -> Result<(), ()> {
+    let git_log = get_project_log(config).await?;
+    let commit = match find_commit_as_of(&git_log, as_of) {
+        Some(v) => v,
+        None => {
+            eprintln!(
+                "STACKMUNCHER ERROR: there are no commits in {} on or before {}.",
+                config.lib_config.project_dir.to_string_lossy(),
+                as_of
+            );
+            return Err(());
+        }
+    };
+
+    let snapshot_dir = get_snapshot_dir(config, as_of);
+    let analysis = analyze_snapshot(config, commit, &snapshot_dir).await?;
+
+    println!(
+        "    Snapshot:            {} at commit {} of {}",
+        as_of,
+        short_sha1(&commit.sha1),
+        epoch_to_date(commit.date_epoch)
+            .map(|v| v.to_string())
+            .unwrap_or_default()
+    );
+    match &analysis.combined_report {
+        Some(combined_report) => print_combined_stats(combined_report, config.loc_weighting),
+        None => println!("    No commits from {} were found by then.", analysis.git_identities.join(", ")),
+    }
+    println!("    Snapshot reports:    {}", snapshot_dir.to_string_lossy());
+
+    Ok(())
+}
+
+/// Analyzes the project as it was at `config.timeline_interval` months apart from the first commit until today and
+/// prints a one-line summary per snapshot. The snapshots are saved and reused the same way as with `--as-of`.
+pub(crate) async fn run(config: &AppConfig) -> Result<(), ()> {
+    let git_log = get_project_log(config).await?;
+    let first_commit_date = match git_log.iter().map(|v| v.date_epoch).min().and_then(epoch_to_date) {
+        Some(v) => v,
+        None => {
+            println!(
+                "    No commits yet in {}. Nothing to analyze.",
+                config.lib_config.project_dir.to_string_lossy()
+            );
+            return Ok(());
+        }
+    };
+
+    let snapshot_dates = get_snapshot_dates(first_commit_date, Utc::now().date_naive(), config.timeline_interval);
+    println!(
+        "    Your stack every {} months since {} (LoC/libs):",
+        config.timeline_interval, first_commit_date
+    );
+
+    // consecutive snapshots with no commits in between are the same
+    let mut previous_sha1: Option<&str> = None;
+    for snapshot_date in snapshot_dates {
+        let commit = match find_commit_as_of(&git_log, snapshot_date) {
+            Some(v) => v,
+            None => continue,
+        };
+        if previous_sha1 == Some(commit.sha1.as_str()) {
+            println!("    {}  {}  no new commits", snapshot_date, short_sha1(&commit.sha1));
+            continue;
+        }
+        previous_sha1 = Some(commit.sha1.as_str());
+
+        let summary = match analyze_snapshot(config, commit, &get_snapshot_dir(config, snapshot_date)).await {
+            Ok(AnalysisOutcome {
+                combined_report: Some(combined_report),
+                ..
+            }) => get_per_tech_stats(&combined_report, config.loc_weighting),
+            Ok(_) => "none of your commits yet".to_owned(),
+            Err(_) => "failed, run with `--log error` for details".to_owned(),
+        };
+        println!("    {}  {}  {}", snapshot_date, short_sha1(&commit.sha1), summary);
+    }
+
+    println!();
+    println!(
+        "    Snapshot reports:    {}",
+        config
+            .lib_config
+            .project_report_dir
+            .as_ref()
+            .expect("Cannot unwrap config.report_dir. It's a bug.")
+            .join([SNAPSHOT_FOLDER_PREFIX, "*"].concat())
+            .to_string_lossy()
+    );
+
+    Ok(())
+}
+
+/// Returns the log of the project, latest commit first. Only Git repos can be analyzed at a past commit.
+async fn get_project_log(config: &AppConfig) -> Result<Vec<GitLogEntry>, ()> {
+    let project_dir = &config.lib_config.project_dir;
+    let vcs = vcs::detect(project_dir);
+    if vcs.kind() != VcsKind::Git {
+        eprintln!(
+            "STACKMUNCHER ERROR: {} is not a Git repo. Only Git repos can be analyzed at a past date.",
+            project_dir.to_string_lossy()
+        );
+        return Err(());
+    }
+
+    let git_log = vcs.get_log(project_dir, None, None, &[]).await;
+    if git_log.is_err() {
+        eprintln!(
+            "STACKMUNCHER ERROR: cannot read the commit history of {}. Run with `--log error` for details.",
+            project_dir.to_string_lossy()
+        );
+    }
+
+    git_log
+}
+
+/// Returns the folder for the reports of the snapshot on `as_of` date inside the project reports folder.
+fn get_snapshot_dir(config: &AppConfig, as_of: NaiveDate) -> PathBuf {
+    config
+        .lib_config
+        .project_report_dir
+        .as_ref()
+        .expect("Cannot unwrap config.report_dir. It's a bug.")
+        .join([SNAPSHOT_FOLDER_PREFIX, &as_of.to_string()].concat())
+}
+
+/// Clones the project at `commit` into a temp folder and analyzes the clone with the reports saved in `snapshot_dir`.
+/// The clone is removed at the end. A snapshot that was analyzed before is reused from `snapshot_dir`.
+async fn analyze_snapshot(
+    config: &AppConfig,
+    commit: &GitLogEntry,
+    snapshot_dir: &Path,
+) -> Result<AnalysisOutcome, ()> {
+    if let Err(e) = std::fs::create_dir_all(snapshot_dir) {
+        eprintln!("STACKMUNCHER ERROR: cannot create {} due to {}", snapshot_dir.to_string_lossy(), e);
+        return Err(());
+    }
+
+    // two snapshots for the same date would overwrite each other's reports
+    let _lock = lock::lock_report_dir(snapshot_dir, &snapshot_dir.to_string_lossy(), config.wait).await?;
+
+    let clone_dir = std::env::temp_dir().join(format!("stm_snapshot_{}", std::process::id()));
+    let analysis = match git::clone_at_commit(&config.lib_config.project_dir, &commit.sha1, &clone_dir).await {
+        Ok(_) => {
+            let mut code_rules = CodeRules::new();
+            code_rules.munch_limits = config.munch_limits.clone();
+
+            let options = AnalysisOptions {
+                report_dir: snapshot_dir.to_path_buf(),
+                git_identities: config.lib_config.git_identities.clone(),
+                aliases: config.aliases.clone(),
+                exclude_emails: config.exclude_emails.clone(),
+                exclude_paths: config.exclude_paths.clone(),
+                co_change_limits: config.co_change_limits.clone(),
+                workday: config.workday.clone(),
+                retention: config.retention.clone(),
+                report_store: config.report_store,
+                ..Default::default()
+            };
+
+            Analyzer::new(code_rules)
+                .analyze_repo(&clone_dir, &options)
+                .await
+                .map_err(|_| ())
+        }
+        Err(_) => {
+            eprintln!(
+                "STACKMUNCHER ERROR: cannot clone the project at commit {} into {}.",
+                commit.sha1,
+                clone_dir.to_string_lossy()
+            );
+            Err(())
+        }
+    };
+
+    // the clone is no longer needed, even if the analysis failed
+    if clone_dir.exists() {
+        if let Err(e) = std::fs::remove_dir_all(&clone_dir) {
+            eprintln!(
+                "STACKMUNCHER ERROR: cannot remove the snapshot clone from {} due to {}",
+                clone_dir.to_string_lossy(),
+                e
+            );
+        }
+    }
+
+    analysis
+}
+
+/// Returns the latest commit made on or before the end of `as_of` day in UTC or None if the project had no commits by then.
+/// Commits made within the same second are picked in the log order, which is latest first.
+fn find_commit_as_of(git_log: &[GitLogEntry], as_of: NaiveDate) -> Option<&GitLogEntry> {
+    let cut_off = as_of.succ_opt()?.and_hms_opt(0, 0, 0)?.and_utc().timestamp();
+
+    // `max_by_key` returns the last of equal elements
+    git_log
+        .iter()
+        .rev()
+        .filter(|v| v.date_epoch < cut_off)
+        .max_by_key(|v| v.date_epoch)
+}
+
+/// Returns the dates of the snapshots `interval` months apart, oldest first, ending with `today` and starting
+/// no earlier than `first_commit_date`.
+fn get_snapshot_dates(first_commit_date: NaiveDate, today: NaiveDate, interval: u32) -> Vec<NaiveDate> {
+    let mut snapshot_dates = vec![today];
+    let mut snapshot_date = today;
+    while let Some(v) = snapshot_date.checked_sub_months(Months::new(interval)) {
+        if v < first_commit_date {
+            break;
+        }
+        snapshot_dates.push(v);
+        snapshot_date = v;
+    }
+
+    snapshot_dates.reverse();
+    snapshot_dates
+}
+
+/// Converts a commit timestamp into a UTC date.
+fn epoch_to_date(epoch: i64) -> Option<NaiveDate> {
+    DateTime::<Utc>::from_timestamp(epoch, 0).map(|v| v.date_naive())
+}
+
+/// Returns the abbreviated commit SHA1, e.g. `f527864`.
+fn short_sha1(sha1: &str) -> &str {
+    sha1.get(..7).unwrap_or(sha1)
+}
+
+#[cfg(test)]
+mod test_timeline {
+    use super::{find_commit_as_of, get_snapshot_dates};
+    use chrono::NaiveDate;
+    use stackmuncher_lib::git::GitLogEntry;
+
+    fn date(y: i32, m: u32, d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(y, m, d).unwrap()
+    }
+
+    #[test]
+    fn test_find_commit_as_of() {
+        let mut git_log = Vec::new();
+        // 2023-06-01 10:00, 2023-06-02 00:00 and 2023-05-01 00:00 UTC with `a` and `a0` made within the same second
+        for (sha1, date_epoch) in [
+            ("b", 1685613600),
+            ("c", 1685664000),
+            ("a", 1682899200),
+            ("a0", 1682899200),
+        ] {
+            let mut entry = GitLogEntry::new();
+            entry.sha1 = sha1.to_owned();
+            entry.date_epoch = date_epoch;
+            git_log.push(entry);
+        }
+
+        assert_eq!(find_commit_as_of(&git_log, date(2023, 6, 1)).unwrap().sha1, "b");
+        assert_eq!(find_commit_as_of(&git_log, date(2023, 6, 2)).unwrap().sha1, "c");
+        assert_eq!(find_commit_as_of(&git_log, date(2023, 5, 15)).unwrap().sha1, "a");
+        assert!(find_commit_as_of(&git_log, date(2023, 4, 30)).is_none());
+    }
+
+    #[test]
+    fn test_get_snapshot_dates() {
+        assert_eq!(
+            get_snapshot_dates(date(2021, 3, 1), date(2023, 6, 1), 12),
+            vec![date(2021, 6, 1), date(2022, 6, 1), date(2023, 6, 1)]
+        );
+        assert_eq!(get_snapshot_dates(date(2023, 6, 1), date(2023, 6, 1), 6), vec![date(2023, 6, 1)]);
+        // a project younger than the interval still gets a snapshot
+        assert_eq!(get_snapshot_dates(date(2023, 5, 1), date(2023, 6, 1), 12), vec![date(2023, 6, 1)]);
+    }
+}
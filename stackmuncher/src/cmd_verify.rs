@@ -0,0 +1,78 @@
+use crate::config::AppConfig;
+use crate::timestamp;
+use ring::digest::{digest, SHA256};
+use stackmuncher_lib::config::Config;
+use std::path::PathBuf;
+
+/// Checks the timestamp token saved next to the report against the contents of the report and prints when
+/// the token was issued. Uses the combined report of the project unless `--file` was given. Works offline.
+pub(crate) fn run(config: &AppConfig) -> Result<(), ()> {
+    let report_file = match &config.verify_file {
+        Some(v) => v.clone(),
+        None => PathBuf::from(
+            config
+                .lib_config
+                .project_report_dir
+                .as_ref()
+                .expect("Cannot unwrap config.report_dir. It's a bug."),
+        )
+        .join(
+            [
+                Config::CONTRIBUTOR_REPORT_COMBINED_FILE_NAME,
+                Config::REPORT_FILE_EXTENSION,
+            ]
+            .concat(),
+        ),
+    };
+
+    let report = match std::fs::read(&report_file) {
+        Ok(v) => v,
+        Err(e) => {
+            eprintln!("STACKMUNCHER ERROR: cannot read {} due to {}", report_file.to_string_lossy(), e);
+            eprintln!("    Run `stackmuncher` inside the project folder to analyze it first.");
+            return Err(());
+        }
+    };
+
+    let token_file = timestamp::token_file_name(&report_file);
+    let token = match std::fs::read(&token_file) {
+        Ok(v) => v,
+        Err(_) => {
+            eprintln!("STACKMUNCHER ERROR: no timestamp token for {}", report_file.to_string_lossy());
+            eprintln!("    Use `--timestamp-url` to have new reports timestamped.");
+            return Err(());
+        }
+    };
+
+    println!();
+    println!("    Report:              {}", report_file.to_string_lossy());
+    println!(
+        "    SHA256:              {}",
+        digest(&SHA256, &report)
+            .as_ref()
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect::<String>()
+    );
+
+    match timestamp::verify_token(&token, &report, None) {
+        Ok(v) => {
+            println!("    Timestamp:           {}", v.gen_time);
+            println!(
+                "    Issued by:           {}, serial number {}",
+                v.tsa_name.as_deref().unwrap_or("unnamed TSA"),
+                v.serial_number
+            );
+            println!();
+            println!("    The token matches the report and is signed by the TSA certificate included in it.");
+            println!("    The certificate itself is not checked. Use `openssl ts -verify` with the TSA's CA certificate for that.");
+            println!();
+            Ok(())
+        }
+        Err(e) => {
+            println!();
+            eprintln!("STACKMUNCHER ERROR: invalid timestamp in {}: {}", token_file.to_string_lossy(), e);
+            Err(())
+        }
+    }
+}
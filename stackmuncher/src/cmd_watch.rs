@@ -0,0 +1,162 @@
+use crate::cmd_munch;
+use crate::config::{self, AppConfig};
+use stackmuncher_lib::analyzer::AnalysisError;
+use stackmuncher_lib::vcs;
+use std::path::PathBuf;
+use std::time::Duration;
+use tokio::time::Instant;
+use tracing::{debug, info, warn};
+
+/// The longest delay before retrying a project that keeps failing.
+const MAX_BACKOFF: Duration = Duration::from_secs(4 * 60 * 60);
+
+/// The state of a single project in the watch list.
+struct WatchedProject {
+    project_dir: PathBuf,
+    report_dir: PathBuf,
+    /// The commit the project was last munched at. None until the first successful run.
+    last_head: Option<String>,
+    /// Number of consecutive failures, reset on success.
+    failures: u32,
+    next_check: Instant,
+}
+
+/// Stays resident and re-munches the watched projects every time their HEAD moves to a new commit.
+/// All projects are munched on start to catch up with any commits made while the app was not running.
+/// Returns only if interrupted with Ctrl-C.
+pub(crate) async fn run(mut config: AppConfig) -> Result<(), ()> {
+    // watch the current folder if no projects were added with `--project`
+    let watch_projects = if config.watch_projects.is_empty() {
+        vec![config::validate_project_dir(
+            config.lib_config.project_dir.clone(),
+            None,
+        )]
+    } else {
+        config.watch_projects.clone()
+    };
+
+    let root_report_dir = config
+        .reports_dir
+        .as_ref()
+        .expect("Cannot unwrap config.reports_dir. It's a bug.")
+        .clone();
+
+    let mut projects = watch_projects
+        .into_iter()
+        .map(|project_dir| WatchedProject {
+            report_dir: config::validate_or_create_project_report_dir(&project_dir, &root_report_dir),
+            project_dir,
+            last_head: None,
+            failures: 0,
+            next_check: Instant::now(),
+        })
+        .collect::<Vec<WatchedProject>>();
+
+    println!(
+        "Watching {} project(s) for new commits every {} min. Press Ctrl-C to stop.",
+        projects.len(),
+        config.watch_interval.as_secs() / 60
+    );
+    for project in &projects {
+        println!("    {}", project.project_dir.to_string_lossy());
+    }
+    println!();
+
+    loop {
+        for project in projects.iter_mut() {
+            if project.next_check > Instant::now() {
+                continue;
+            }
+
+            let head = match vcs::detect(&project.project_dir)
+                .get_head_commit(&project.project_dir, None)
+                .await
+            {
+                Ok(v) => v,
+                Err(_) => {
+                    // the repo may be gone, mid-clone or have no commits yet
+                    warn!("Cannot get HEAD of {}", project.project_dir.to_string_lossy());
+                    project.failures += 1;
+                    project.next_check = Instant::now() + get_backoff(config.watch_interval, project.failures);
+                    continue;
+                }
+            };
+
+            if project.last_head.as_ref() == Some(&head) {
+                debug!("No new commits in {}", project.project_dir.to_string_lossy());
+                project.next_check = Instant::now() + config.watch_interval;
+                continue;
+            }
+
+            println!("New commits in {}", project.project_dir.to_string_lossy());
+            config.lib_config.project_dir = project.project_dir.clone();
+            config.lib_config.project_report_dir = Some(project.report_dir.clone());
+
+            // the lock held by the munching run is released if it is interrupted
+            let result = tokio::select! {
+                v = cmd_munch::run(&config) => v,
+                _ = tokio::signal::ctrl_c() => {
+                    println!("Stopped watching.");
+                    return Ok(());
+                }
+            };
+
+            match result {
+                // partial results are not retried because the same contributors would fail again
+                // and an unsupported project stays so until the next commit
+                Ok(_) | Err(AnalysisError::Unsupported(_)) => {
+                    project.last_head = Some(head);
+                    project.failures = 0;
+                    project.next_check = Instant::now() + config.watch_interval;
+                }
+                Err(AnalysisError::Failed) => {
+                    project.failures += 1;
+                    let backoff = get_backoff(config.watch_interval, project.failures);
+                    eprintln!(
+                        "STACKMUNCHER ERROR: failed to process {}. Will retry in {} min.",
+                        project.project_dir.to_string_lossy(),
+                        backoff.as_secs() / 60
+                    );
+                    project.next_check = Instant::now() + backoff;
+                }
+            }
+            println!();
+        }
+
+        // sleep until the next project is due for a check
+        let next_check = projects
+            .iter()
+            .map(|p| p.next_check)
+            .min()
+            .expect("Cannot unwrap the next check time. It's a bug.");
+        info!("Next check in {}s", next_check.saturating_duration_since(Instant::now()).as_secs());
+
+        tokio::select! {
+            _ = tokio::time::sleep_until(next_check) => {},
+            _ = tokio::signal::ctrl_c() => {
+                println!("Stopped watching.");
+                return Ok(());
+            }
+        };
+    }
+}
+
+/// Returns the delay before the next attempt after a number of consecutive `failures`.
+/// The delay doubles with every failure starting from `interval` and is capped at `MAX_BACKOFF`.
+fn get_backoff(interval: Duration, failures: u32) -> Duration {
+    // 2^10 is enough to reach the cap from any sensible interval
+    let multiplier = 2u32.pow(failures.saturating_sub(1).min(10));
+    (interval * multiplier).min(MAX_BACKOFF).max(interval)
+}
+
+#[test]
+fn test_get_backoff() {
+    let interval = Duration::from_secs(5 * 60);
+    assert_eq!(get_backoff(interval, 0), interval);
+    assert_eq!(get_backoff(interval, 1), interval);
+    assert_eq!(get_backoff(interval, 2), interval * 2);
+    assert_eq!(get_backoff(interval, 3), interval * 4);
+    assert_eq!(get_backoff(interval, 100), MAX_BACKOFF);
+    // an interval longer than the cap is not shortened
+    assert_eq!(get_backoff(MAX_BACKOFF * 2, 3), MAX_BACKOFF * 2);
+}
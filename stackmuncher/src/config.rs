@@ -1,20 +1,45 @@
-use crate::{app_args::AppArgCommands, app_args::AppArgs, help};
+#[cfg(feature = "cloud")]
+use crate::app_args::IdentityAction;
+#[cfg(feature = "cloud")]
+use crate::cmd_data::DATA_ARCHIVE_FILE_NAME;
+#[cfg(feature = "cloud")]
+use crate::cmd_identity::IDENTITY_BUNDLE_FILE_NAME;
+use crate::lock::ProjectLock;
+use crate::log_redaction::LogWriter;
+use crate::postprocess::Hooks;
+#[cfg(feature = "cloud")]
+use crate::submission::STM_REPORT_SUBMISSION_URL;
+use crate::ui;
+use crate::{
+    app_args::AppArgCommands, app_args::AppArgs, app_args::ExportFormat, app_args::GraphFormat, app_args::LogFormat,
+    app_args::ProfileAction, app_args::RollupGroupBy, app_args::RulesAction, app_args::SbomFormat, help,
+};
+use chrono::NaiveDate;
 use path_absolutize::{self, Absolutize};
 use regex::Regex;
+#[cfg(feature = "cloud")]
 use ring::signature::Ed25519KeyPair;
 use serde::{Deserialize, Serialize};
-use serde_json;
+use serde_json::{self, Value};
 use stackmuncher_lib::{
-    config::Config as LibConfig, git::check_git_version, git::get_local_identities, utils::hash_str_sha1,
+    bench::SyntheticRepoSpec, config::Config as LibConfig, git::check_git_version, muncher::MunchLimits,
+    report::CoChangeLimits, report::LocWeighting, report::MergeLimits, report::Retention, report::ScrubRules,
+    report::WorkdayOverlap, report_store::ReportStore, utils::hash_str_sha1, utils::write_atomically, vcs,
 };
+use std::collections::BTreeMap;
 use std::env::consts::EXE_SUFFIX;
 use std::path::{Path, PathBuf};
 use std::process::exit;
 use std::str::FromStr;
+use std::time::Duration;
 use tracing::debug;
+use tracing_subscriber::fmt::format::FmtSpan;
 
 /// Name of the file stored in a predefined folder: config.json
-const APP_CONFIG_FILE_NAME: &str = "config.json";
+pub(crate) const APP_CONFIG_FILE_NAME: &str = "config.json";
+
+/// How long to wait for another instance of the app to finish saving the config.
+pub(crate) const CONFIG_LOCK_WAIT: Duration = Duration::from_secs(30);
 
 /// The location of user config and keys for signing STM Inbox messages: `.stm_config`
 pub(crate) const CONFIG_FOLDER_NAME_DEBUG: &'static str = ".stm_config";
@@ -34,9 +59,30 @@ pub(crate) const REPORT_FOLDER_NAME_WIN: &'static str = "stackmuncher\\reports";
 /// See HELP module for explanation of what different config flags and params do.
 pub(crate) struct AppConfig {
     pub command: AppArgCommands,
+    /// The command the user asked help for, if any. See `AppArgs::help_topic`.
+    pub help_topic: Option<AppArgCommands>,
     pub dryrun: bool,
+    /// Fetch the missing history of a shallow clone before munching
+    pub full_history: bool,
+    /// Wait for another instance of the app to finish with the project instead of exiting with an error. Not cached.
+    pub wait: bool,
+    /// Remove or set aside the damaged files found by `doctor` command. Not cached.
+    pub fix: bool,
+    /// Save the exact submission payload next to the other reports for review
+    #[cfg(feature = "cloud")]
+    pub show_what_is_sent: bool,
     // An empty string means NO CONTACT
     pub primary_email: Option<String>,
+    /// The name shown in the Directory Profile. An empty string means it should be removed from the profile.
+    pub public_name: Option<String>,
+    /// A one-line summary shown in the Directory Profile. An empty string means it should be removed from the profile.
+    pub headline: Option<String>,
+    /// A URL or an email shown in the Directory Profile. An empty string means it should be removed from the profile.
+    pub public_contact: Option<String>,
+    /// Open to job offers. Shown in the Directory Profile if set.
+    pub available: Option<bool>,
+    /// A free-form location shown in the Directory Profile. An empty string means it should be removed from the profile.
+    pub location: Option<String>,
     /// A 32-byte long hex string of the Gist ID with the validation string for the user GH account
     /// E.g. `fb8fc0f87ee78231f064131022c8154a`
     /// It is validated on change and then cached in config.json
@@ -46,14 +92,109 @@ pub(crate) struct AppConfig {
     /// Core config from stackmuncher_lib
     pub lib_config: LibConfig,
     /// Extracted from the key file stored next to the config file
+    #[cfg(feature = "cloud")]
     pub user_key_pair: Ed25519KeyPair,
     /// The full path to the config file.
     pub config_file_path: PathBuf,
     /// A stash for validation Gist details to avoid going to GitHub twice
     /// Not cached and can only be present if --gist param was used to link to a new github a/c
+    #[cfg(feature = "cloud")]
     pub gh_validation_gist: Option<crate::cmd_config::Gist>,
     // The location of `reports` folder. Projects will be placed in subfolders under that folder.
     pub reports_dir: Option<PathBuf>,
+    /// How `rollup` command groups project reports. Not cached.
+    pub group_by: RollupGroupBy,
+    /// The size of the synthetic repo for `bench` command. Not cached.
+    pub bench_spec: SyntheticRepoSpec,
+    /// The timings to check `bench` results against. Not cached.
+    pub bench_baseline: Option<PathBuf>,
+    /// How many percent slower than `bench_baseline` a hot path may get. Not cached.
+    pub bench_max_regression: usize,
+    /// User-defined rules for removing sensitive info from submissions. Can only be set by editing config.json.
+    pub scrub_rules: ScrubRules,
+    /// Absolute paths to projects re-munched by `watch` command on new commits. Added with `watch --project`.
+    pub watch_projects: Vec<PathBuf>,
+    /// How often `watch` command checks the projects for new commits. Not cached.
+    pub watch_interval: Duration,
+    /// Analyze the project as it was on this date instead of HEAD. Not cached.
+    pub as_of: Option<NaiveDate>,
+    /// A branch, tag or commit to analyze instead of HEAD. Not cached.
+    pub git_ref: Option<String>,
+    /// Months between the snapshots of `timeline` command. Not cached.
+    pub timeline_interval: u32,
+    /// A self-hosted submission endpoint. None for the default STM Inbox.
+    pub inbox_url: Option<String>,
+    /// An RFC3161 timestamping authority for the hashes of new reports. None if timestamping is off.
+    pub timestamp_url: Option<String>,
+    /// Where copies of the project reports are saved after munching, e.g. `s3://bucket/prefix`. Not cached.
+    pub report_sink: Option<String>,
+    /// Commit emails merged into other emails before generating reports, e.g. `old@x.com` -> `new@y.com`.
+    /// Set with `--alias` and applied on top of `.mailmap`.
+    pub aliases: BTreeMap<String, String>,
+    /// Commit emails left out of the combined report, e.g. a shared identity. Set with `--exclude-emails`.
+    pub exclude_emails: Vec<String>,
+    /// Regex strings for file paths left out of the contributor reports. Set with `--exclude-paths`.
+    pub exclude_paths: Vec<String>,
+    /// Caps on the co-change coupling calculation for huge histories. Can only be set by editing config.json.
+    pub co_change_limits: CoChangeLimits,
+    /// Raw or verbosity-adjusted LoC for the per-language summaries. Can only be set by editing config.json.
+    pub loc_weighting: LocWeighting,
+    /// Per-file timeout and line length limit for munchers. Can only be set by editing config.json.
+    pub munch_limits: MunchLimits,
+    /// How long records for deleted files are kept in cached reports. Can only be set by editing config.json.
+    pub retention: Retention,
+    /// Caps on the keyword lists in `profile` and `rollup` reports. Can only be set by editing config.json.
+    pub merge_limits: MergeLimits,
+    /// Where cached reports are kept between runs, `json` or `sqlite`. Can only be set by editing config.json.
+    pub report_store: ReportStore,
+    /// The working day and timezones for the working hours overlap in `rollup`. Set with `--workday` and `--timezones`.
+    pub workday: WorkdayOverlap,
+    /// Executables run after each analysis with the report. Can only be set by editing config.json.
+    pub hooks: Hooks,
+    /// The output format of `graph` command. Not cached.
+    pub graph_format: GraphFormat,
+    /// The file `graph`, `sbom`, `export` or `bench` command saves its output into. Printed to stdout if None.
+    /// Not cached.
+    pub graph_out: Option<PathBuf>,
+    /// Include external packages in `graph` output. Not cached.
+    pub graph_external: bool,
+    /// The output format of `sbom` command. Not cached.
+    pub sbom_format: SbomFormat,
+    /// The output format of `export` command. Not cached.
+    pub export_format: ExportFormat,
+    /// Print the hotspots section in `show` command. Not cached.
+    pub show_hotspots: bool,
+    /// Print the most central files from the import graph in `show` command. Not cached.
+    pub show_modules: bool,
+    /// A file or a folder to print the evaluation stages for during munching. Not cached.
+    pub trace_file: Option<String>,
+    /// What `identity` command does. Not cached.
+    #[cfg(feature = "cloud")]
+    pub identity_action: Option<IdentityAction>,
+    /// The identity bundle file for `identity` command. Not cached.
+    #[cfg(feature = "cloud")]
+    pub identity_file: PathBuf,
+    /// The archive for `export-data` and `import-data` commands. Not cached.
+    #[cfg(feature = "cloud")]
+    pub data_file: PathBuf,
+    /// Encrypt the key in `export-data` archive with a passphrase. Not cached.
+    #[cfg(feature = "cloud")]
+    pub encrypt_key: bool,
+    /// The report for `verify` command. The combined project report if None. Not cached.
+    #[cfg(feature = "cloud")]
+    pub verify_file: Option<PathBuf>,
+    /// The number of the most recent entries printed by `audit` command, all if None. Not cached.
+    pub audit_last: Option<usize>,
+    /// What `rules` command does. Not cached.
+    pub rules_action: Option<RulesAction>,
+    /// What `profile` command does. Not cached.
+    pub profile_action: Option<ProfileAction>,
+    /// The folder with the rules for `rules` command, the embedded rules if None. Not cached.
+    pub rules_dir: Option<PathBuf>,
+    /// The muncher tested by `rules test`, e.g. `rust.rs`. Not cached.
+    pub rules_muncher: Option<String>,
+    /// The folder with the sample files for `rules test`. Not cached.
+    pub rules_sample_dir: Option<PathBuf>,
 }
 
 /// A container for storing some config info locally as a file.
@@ -61,12 +202,75 @@ pub(crate) struct AppConfig {
 struct AppConfigCache {
     /// An empty string means NO CONTACT
     pub primary_email: Option<String>,
+    /// An empty string means the name was removed by the user
+    #[serde(default)]
+    pub public_name: Option<String>,
+    /// An empty string means the headline was removed by the user
+    #[serde(default)]
+    pub headline: Option<String>,
+    /// An empty string means the public contact was removed by the user
+    #[serde(default)]
+    pub public_contact: Option<String>,
+    /// Set with `profile set --available yes|no`. None if it was never set.
+    #[serde(default)]
+    pub available: Option<bool>,
+    /// An empty string means the location was removed by the user
+    #[serde(default)]
+    pub location: Option<String>,
     pub gh_validation_id: Option<String>,
     /// It is a derivitive value. Used for displaying a profile URL only.
     pub gh_login: Option<String>,
     pub git_identities: Vec<String>,
     /// The location of `reports` folder. Projects will be placed in subfolders under that folder.
     pub reports_dir: Option<PathBuf>,
+    /// A list of regex strings for file paths to be excluded from submissions. Edited manually.
+    #[serde(default)]
+    pub scrub_file_paths: Vec<String>,
+    /// A list of regex strings for library names to be hashed before submission, e.g. private packages. Edited manually.
+    #[serde(default)]
+    pub scrub_pkgs: Vec<String>,
+    /// A self-hosted submission endpoint set with `--inbox-url`. None for the default STM Inbox.
+    #[serde(default)]
+    pub inbox_url: Option<String>,
+    /// An RFC3161 timestamping authority set with `--timestamp-url`. None if timestamping is off.
+    #[serde(default)]
+    pub timestamp_url: Option<String>,
+    /// Commit emails merged into other emails set with `--alias`, e.g. `old@x.com` -> `new@y.com`.
+    #[serde(default)]
+    pub aliases: BTreeMap<String, String>,
+    /// Commit emails left out of the combined report set with `--exclude-emails`.
+    #[serde(default)]
+    pub exclude_emails: Vec<String>,
+    /// Regex strings for file paths left out of the contributor reports set with `--exclude-paths`.
+    #[serde(default)]
+    pub exclude_paths: Vec<String>,
+    /// Caps on the co-change coupling calculation. Edited manually.
+    #[serde(default)]
+    pub co_change: CoChangeLimits,
+    /// `loc` or `effective` for the share of languages in the summaries. Edited manually.
+    #[serde(default)]
+    pub loc_weighting: LocWeighting,
+    /// Per-file timeout and line length limit for munchers. Edited manually.
+    #[serde(default)]
+    pub munch_limits: MunchLimits,
+    /// The number of runs records for deleted files are kept in cached reports. Edited manually.
+    #[serde(default)]
+    pub retention: Retention,
+    /// The max number of keywords per tech in merged reports. Edited manually.
+    #[serde(default)]
+    pub merge_limits: MergeLimits,
+    /// `json` files or a `sqlite` database for the cached reports. Edited manually.
+    #[serde(default)]
+    pub report_store: ReportStore,
+    /// The working day and timezones for the working hours overlap set with `--workday` and `--timezones`.
+    #[serde(default)]
+    pub workday: WorkdayOverlap,
+    /// Executables run after each analysis with the report. Edited manually.
+    #[serde(default)]
+    pub hooks: Hooks,
+    /// Absolute paths to projects re-munched by `watch` command. Added with `watch --project`, removed by editing the file.
+    #[serde(default)]
+    pub watch_projects: Vec<PathBuf>,
 }
 
 impl AppConfig {
@@ -103,10 +307,7 @@ impl AppConfig {
 
         // init the subscriber now if the logging level is known from the CLI param
         if let Some(log_level) = &app_args.log {
-            tracing_subscriber::fmt()
-                .with_max_level(log_level.clone())
-                .with_ansi(false)
-                .init();
+            init_tracing(log_level.clone(), &app_args);
         }
 
         // get config defaults from the environment - may panic
@@ -119,10 +320,7 @@ impl AppConfig {
             lib_config.log_level = log_level;
         } else {
             // using the default logging level - initialize for the first time
-            tracing_subscriber::fmt()
-                .with_max_level(lib_config.log_level.clone())
-                .with_ansi(false)
-                .init();
+            init_tracing(lib_config.log_level.clone(), &app_args);
         };
 
         // config folder is needed to read or generate a user key-pair and allow caching of some config values in the same folder
@@ -137,6 +335,33 @@ impl AppConfig {
         let config_file_path = config_dir.join(APP_CONFIG_FILE_NAME);
         let app_config_cache = AppConfigCache::read_from_disk(&config_file_path);
 
+        // the identity bundle file is either from the CLI or in the current folder
+        #[cfg(feature = "cloud")]
+        let identity_file = app_args
+            .identity_file
+            .clone()
+            .unwrap_or_else(|| PathBuf::from(IDENTITY_BUNDLE_FILE_NAME));
+
+        // an imported identity replaces the key file and is merged into the cache before anything reads them
+        #[cfg(feature = "cloud")]
+        let app_config_cache = if app_args.identity_action == Some(IdentityAction::Import) {
+            let bundle = match crate::cmd_identity::import(&identity_file, &config_dir) {
+                Ok(v) => v,
+                Err(_) => exit(1),
+            };
+            let mut git_identities = app_config_cache.git_identities.clone();
+            git_identities.extend(bundle.git_identities);
+            // a Gist signed with the previous key cannot be validated with the imported one
+            AppConfigCache {
+                git_identities,
+                gh_validation_id: bundle.gh_validation_id,
+                gh_login: bundle.gh_login,
+                ..app_config_cache
+            }
+        } else {
+            app_config_cache
+        };
+
         // individual project reports are placed in subfolders under the root reports folder
         // which is cached in config.json
         let root_reports_dir = match app_args.reports {
@@ -163,43 +388,103 @@ impl AppConfig {
             },
         };
 
-        // only validate project, rules and report if code analysis is to be done
+        // the data archive file is either from the CLI or in the current folder
+        #[cfg(feature = "cloud")]
+        let data_file = app_args
+            .data_file
+            .clone()
+            .unwrap_or_else(|| PathBuf::from(DATA_ARCHIVE_FILE_NAME));
+
+        // imported data replaces the config, the key and the reports, so the cache is re-read after the import
+        // the reports go into the reports folder of this machine, not the one in the archive
+        #[cfg(feature = "cloud")]
+        let app_config_cache = if app_args.command == AppArgCommands::ImportData {
+            if crate::cmd_data::import(&data_file, &config_dir, &root_reports_dir).is_err() {
+                exit(1);
+            }
+            AppConfigCache::read_from_disk(&config_file_path)
+        } else {
+            app_config_cache
+        };
+
+        // `watch` command processes all the projects added to the watch list over time
+        let mut watch_projects = app_config_cache.watch_projects.clone();
+        if app_args.command == AppArgCommands::Watch {
+            if let Some(project) = app_args.project.clone() {
+                let project = absolutize_project_dir(validate_project_dir(project, None));
+                if !watch_projects.contains(&project) {
+                    println!("{} was added to the list of watched projects.", project.to_string_lossy());
+                    watch_projects.push(project);
+                }
+            }
+        }
+
+        // `doctor` checks the report of the project if there is one, but does not require a valid project
+        if app_args.command == AppArgCommands::Doctor {
+            if let Some(project) = app_args.project.clone() {
+                lib_config.project_dir = absolutize_project_dir(project);
+            }
+        }
+
+        // only validate project, rules and report if code analysis is to be done or its results are needed
         // config should be validated regardless because nothing functions without it
-        if app_args.command == AppArgCommands::Munch {
+        if app_args.command == AppArgCommands::Munch
+            || app_args.command == AppArgCommands::Graph
+            || app_args.command == AppArgCommands::Sbom
+            || app_args.command == AppArgCommands::Export
+            || app_args.command == AppArgCommands::Show
+            || app_args.command == AppArgCommands::Timeline
+            || (app_args.command == AppArgCommands::Verify && app_args.verify_file.is_none())
+        {
             // only `project` folder is being validated - not much difference if it's done now or later
             // replace default config with user values from the CLI
 
             // check the project folder for existence and if it has .git in it
             if let Some(project) = app_args.project {
-                lib_config.project_dir = validate_project_dir(project)
+                lib_config.project_dir = validate_project_dir(project, app_args.git_dir.as_deref())
             } else {
                 // validate the default value
-                lib_config.project_dir = validate_project_dir(lib_config.project_dir);
+                lib_config.project_dir = validate_project_dir(lib_config.project_dir, app_args.git_dir.as_deref());
+            }
+
+            // git is run from the project folder, so both paths have to be absolute for git to find them
+            if let Some(git_dir) = &app_args.git_dir {
+                std::env::set_var("GIT_DIR", absolutize_project_dir(git_dir.clone()));
+                std::env::set_var("GIT_WORK_TREE", absolutize_project_dir(lib_config.project_dir.clone()));
+                debug!("GIT_DIR set to {}", git_dir.to_string_lossy());
             }
 
             // project reports folder may need to be created under the reports root folder
-            lib_config.project_report_dir =
-                Some(validate_or_create_project_report_dir(&lib_config.project_dir, &root_reports_dir));
+            let project_report_dir = validate_or_create_project_report_dir(&lib_config.project_dir, &root_reports_dir);
+
+            // the reports and caches of other refs are kept apart from those of HEAD
+            lib_config.project_report_dir = match &app_args.git_ref {
+                Some(git_ref) => Some(validate_or_create_ref_report_dir(&project_report_dir, git_ref)),
+                None => Some(project_report_dir),
+            };
         };
 
         // get existing or generate new key pair
         // it will create STMKEYa directory needed for storing the config cache
-        let user_key_pair = crate::signing::get_key_pair(&config_dir);
+        // a damaged key is reported by `doctor` instead of stopping the app
+        #[cfg(feature = "cloud")]
+        let user_key_pair = if app_args.command == AppArgCommands::Doctor {
+            crate::signing::read_key_pair(&config_dir).unwrap_or_else(|_| crate::signing::new_ephemeral_key_pair())
+        } else {
+            crate::signing::get_key_pair(&config_dir)
+        };
 
         // primary_email, public_name and public_contact may come from the cache, CLI or git IDs
         let primary_email = if let Some(prim_email_arg) = app_args.primary_email {
             if prim_email_arg.is_empty() {
                 // reset the value to NULL if `--primary_email ""`
                 debug!("Resetting primary_email to an empty string");
-                println!("Your primary email address for notifications from the Directory was removed.");
+                println!("{}", ui::msg("config.primary_email_removed"));
                 println!();
                 Some(String::new())
             } else {
                 // some new value from the CLI
-                println!(
-                    "{} will be used for notifications about your Directory Profile views and employer interest.",
-                    prim_email_arg
-                );
+                println!("{}", ui::msg_with("config.primary_email_set", &[("email", &prim_email_arg)]));
                 Some(prim_email_arg)
             }
         } else if app_config_cache.primary_email.is_some() {
@@ -208,32 +493,210 @@ impl AppConfig {
         } else if !lib_config.git_identities.is_empty() {
             // setting the email from GIT IDs
             println!();
-            println!("{} is your default Git commit email and will be used for notifications about your Directory Profile views and employer interest.",lib_config.git_identities[0]);
             println!(
-                "    Run `stackmuncher{} --primary_email me@example.com` to set your preferred contact email. It will not be published or shared with anyone.",
-                EXE_SUFFIX
+                "{}",
+                ui::msg_with(
+                    "config.primary_email_from_git",
+                    &[("email", &lib_config.git_identities[0]), ("exe", &EXE_SUFFIX)]
+                )
             );
             println!();
             Some(lib_config.git_identities[0].clone())
         } else {
-            println!("Missing preferred contact email. Your profile will not be updated. You can still generate and view your stack reports locally.");
+            println!("{}", ui::msg_with("config.primary_email_missing", &[("exe", &EXE_SUFFIX)]));
             println!();
+            None
+        };
+
+        // public_name and headline are optional and only come from the CLI or the cache
+        let public_name = if let Some(public_name_arg) = app_args.public_name {
+            if public_name_arg.is_empty() {
+                // reset the value to NULL if `--public_name ""`
+                debug!("Resetting public_name to an empty string");
+                println!("{}", ui::msg("config.public_name_removed"));
+            } else {
+                println!("{}", ui::msg_with("config.public_name_set", &[("name", &public_name_arg)]));
+            }
+            println!();
+            Some(public_name_arg)
+        } else {
+            app_config_cache.public_name.clone()
+        };
+
+        let headline = if let Some(headline_arg) = app_args.headline {
+            if headline_arg.is_empty() {
+                // reset the value to NULL if `--headline ""`
+                debug!("Resetting headline to an empty string");
+                println!("{}", ui::msg("config.headline_removed"));
+            } else {
+                println!("{}", ui::msg_with("config.headline_set", &[("headline", &headline_arg)]));
+            }
+            println!();
+            Some(headline_arg)
+        } else {
+            app_config_cache.headline.clone()
+        };
+
+        // public_contact, available and location are set with `profile set` and only come from the CLI or the cache
+        let public_contact = if let Some(public_contact_arg) = app_args.public_contact {
+            if public_contact_arg.is_empty() {
+                debug!("Resetting public_contact to an empty string");
+                println!("{}", ui::msg("config.public_contact_removed"));
+            } else {
+                println!("{}", ui::msg_with("config.public_contact_set", &[("contact", &public_contact_arg)]));
+            }
+            println!();
+            Some(public_contact_arg)
+        } else {
+            app_config_cache.public_contact.clone()
+        };
+
+        let available = if let Some(available_arg) = app_args.available {
+            if available_arg {
+                println!("{}", ui::msg("config.available_yes"));
+            } else {
+                println!("{}", ui::msg("config.available_no"));
+            }
+            println!();
+            Some(available_arg)
+        } else {
+            app_config_cache.available
+        };
+
+        let location = if let Some(location_arg) = app_args.location {
+            if location_arg.is_empty() {
+                debug!("Resetting location to an empty string");
+                println!("{}", ui::msg("config.location_removed"));
+            } else {
+                println!("{}", ui::msg_with("config.location_set", &[("location", &location_arg)]));
+            }
+            println!();
+            Some(location_arg)
+        } else {
+            app_config_cache.location.clone()
+        };
+
+        // reports can be submitted to a self-hosted inbox instead of the default one
+        let inbox_url = match app_args.inbox_url {
+            Some(inbox_url_arg) if inbox_url_arg.is_empty() => {
+                #[cfg(feature = "cloud")]
+                {
+                    println!("{}", ui::msg_with("config.inbox_url_default", &[("url", &STM_REPORT_SUBMISSION_URL)]));
+                    println!();
+                }
+                None
+            }
+            Some(inbox_url_arg) => {
+                println!("{}", ui::msg_with("config.inbox_url_set", &[("url", &inbox_url_arg)]));
+                println!();
+                Some(inbox_url_arg)
+            }
+            None => app_config_cache.inbox_url.clone(),
+        };
+
+        // only the hashes of new reports are sent to the timestamping authority
+        let timestamp_url = match app_args.timestamp_url {
+            Some(timestamp_url_arg) if timestamp_url_arg.is_empty() => {
+                println!("{}", ui::msg("config.timestamp_url_removed"));
+                println!();
+                None
+            }
+            Some(timestamp_url_arg) => {
+                println!("{}", ui::msg_with("config.timestamp_url_set", &[("url", &timestamp_url_arg)]));
+                println!();
+                Some(timestamp_url_arg)
+            }
+            None => app_config_cache.timestamp_url.clone(),
+        };
+
+        // aliases are merged with the cached ones, an empty new email removes the alias
+        let mut aliases = app_config_cache.aliases.clone();
+        match app_args.aliases {
+            Some(aliases_arg) if aliases_arg.is_empty() => {
+                println!("{}", ui::msg("config.aliases_removed"));
+                println!();
+                aliases.clear();
+            }
+            Some(aliases_arg) => {
+                for (old_email, new_email) in aliases_arg {
+                    if new_email.is_empty() {
+                        println!("{}", ui::msg_with("config.alias_removed", &[("old_email", &old_email)]));
+                        aliases.remove(&old_email);
+                    } else {
+                        println!(
+                            "{}",
+                            ui::msg_with("config.alias_set", &[("old_email", &old_email), ("new_email", &new_email)])
+                        );
+                        aliases.insert(old_email, new_email);
+                    }
+                }
+                println!();
+            }
+            None => {}
+        }
+
+        // exclusions replace the cached ones, an empty list removes all of them
+        let exclude_emails = match app_args.exclude_emails {
+            Some(exclude_emails) => {
+                if exclude_emails.is_empty() {
+                    println!("{}", ui::msg("config.exclude_emails_removed"));
+                } else {
+                    println!(
+                        "{}",
+                        ui::msg_with("config.exclude_emails_set", &[("emails", &exclude_emails.join(", "))])
+                    );
+                }
+                println!();
+                exclude_emails
+            }
+            None => app_config_cache.exclude_emails.clone(),
+        };
+        let exclude_paths = match app_args.exclude_paths {
+            Some(exclude_paths) => {
+                if exclude_paths.is_empty() {
+                    println!("{}", ui::msg("config.exclude_paths_removed"));
+                } else {
+                    println!("{}", ui::msg_with("config.exclude_paths_set", &[("paths", &exclude_paths.join(", "))]));
+                }
+                println!();
+                exclude_paths
+            }
+            None => app_config_cache.exclude_paths.clone(),
+        };
+
+        // the working day and timezones for the working hours overlap
+        let mut workday = app_config_cache.workday.clone();
+        if let Some((start_hour, end_hour)) = app_args.workday {
+            workday.start_hour = start_hour;
+            workday.end_hour = end_hour;
             println!(
-                "    Run `stackmuncher{} --primary_email me@example.com` to set your preferred contact email for notifications about profile views and employer interest.",
-                EXE_SUFFIX
+                "{}",
+                ui::msg_with("config.workday_set", &[("start_hour", &start_hour), ("end_hour", &end_hour)])
             );
             println!();
-            None
-        };
+        }
+        if let Some(timezones) = app_args.timezones {
+            if timezones.is_empty() {
+                println!("{}", ui::msg("config.timezones_removed"));
+            } else {
+                println!("{}", ui::msg_with("config.timezones_set", &[("timezones", &timezones.join(", "))]));
+            }
+            println!();
+            workday.timezones = timezones;
+        }
 
         // print a message about multiple git IDs on the first run
         if lib_config.git_identities.len() > 0
             && app_args.emails.is_none()
             && app_config_cache.git_identities.is_empty()
         {
-            println!("Only commits from {} will be analyzed. Did you use any other email addresses for Git commits in the past?",lib_config.git_identities[0]);
-            println!("    1. Run `git shortlog -s -e --all` to check if you made commits under other email addresses.");
-            println!("    2. Run `stackmuncher{} --emails \"me@example.com, old@example.com\"` once to add more of your emails for this and future runs.", EXE_SUFFIX);
+            println!(
+                "{}",
+                ui::msg_with(
+                    "config.single_identity",
+                    &[("email", &lib_config.git_identities[0]), ("exe", &EXE_SUFFIX)]
+                )
+            );
             println!();
         }
 
@@ -251,17 +714,14 @@ impl AppConfig {
 
         // warn the user if there are no identities to work with
         if lib_config.git_identities.is_empty() {
-            println!("Cannot identify which commits are yours without knowing your email address.");
-            println!();
-            println!("    1. Add your email with `git configure --global user.email me@example.com` to identify your future commits.");
-            println!("    2. Run `git shortlog -s -e --all` to check if you made commits under other email addresses.");
-            println!("    3. Run `stackmuncher{} --emails \"me@example.com, old@example.com\"` once to add more of your emails for this and future runs.", EXE_SUFFIX);
+            println!("{}", ui::msg_with("config.no_identities", &[("exe", &EXE_SUFFIX)]));
             println!();
         }
 
         // GitHub login validation - use the validated ID or None if --gist param was provided
         // It means that the user requested a change of sorts.
         // Otherwise use what is in the cache without any validation.
+        #[cfg(feature = "cloud")]
         let (gh_validation_id, gh_login, gh_validation_gist) = if app_args.gh_validation_id.is_some() {
             // --gist was present - so a change was requested by the user
             match crate::cmd_config::get_validated_gist(&app_args.gh_validation_id, &user_key_pair).await {
@@ -273,26 +733,163 @@ impl AppConfig {
             // --gist was not present - use what's in cache
             (app_config_cache.gh_validation_id.clone(), app_config_cache.gh_login.clone(), None)
         };
+        // the Gist cannot be fetched from GitHub without the HTTP client
+        #[cfg(not(feature = "cloud"))]
+        let (gh_validation_id, gh_login) = if app_args.gh_validation_id.is_some() {
+            help::emit_no_cloud_msg();
+            exit(1);
+        } else {
+            (app_config_cache.gh_validation_id.clone(), app_config_cache.gh_login.clone())
+        };
+
+        // scrubbing rules can only be set in config.json and an invalid regex would leak what the user wanted removed
+        let scrub_rules = match ScrubRules::new(&app_config_cache.scrub_file_paths, &app_config_cache.scrub_pkgs) {
+            Ok(v) => v,
+            Err(e) => {
+                eprintln!(
+                    "STACKMUNCHER CONFIG ERROR: {} in `scrub_file_paths` or `scrub_pkgs` of {}",
+                    e,
+                    config_file_path.to_string_lossy()
+                );
+                exit(1);
+            }
+        };
+
+        // the SQLite store is an optional feature and the user should know their setting has no effect
+        if let Err(e) = app_config_cache.report_store.validate() {
+            eprintln!(
+                "STACKMUNCHER CONFIG ERROR: {} for `report_store` in {}",
+                e,
+                config_file_path.to_string_lossy()
+            );
+            exit(1);
+        }
 
         let app_config = AppConfig {
             command: app_args.command,
+            help_topic: app_args.help_topic,
             dryrun: app_args.dryrun,
+            full_history: app_args.full_history,
+            wait: app_args.wait,
+            fix: app_args.fix,
+            #[cfg(feature = "cloud")]
+            show_what_is_sent: app_args.show_what_is_sent,
             primary_email,
+            public_name,
+            headline,
+            public_contact,
+            available,
+            location,
             gh_validation_id,
             lib_config,
+            #[cfg(feature = "cloud")]
             user_key_pair,
             config_file_path,
+            #[cfg(feature = "cloud")]
             gh_validation_gist,
             gh_login,
             reports_dir: Some(root_reports_dir),
+            group_by: app_args.group_by,
+            bench_spec: app_args.bench_spec,
+            bench_baseline: app_args.bench_baseline,
+            bench_max_regression: app_args.bench_max_regression,
+            scrub_rules,
+            watch_projects,
+            watch_interval: Duration::from_secs(app_args.watch_interval as u64 * 60),
+            as_of: app_args.as_of,
+            git_ref: app_args.git_ref,
+            timeline_interval: app_args.timeline_interval as u32,
+            inbox_url,
+            timestamp_url,
+            report_sink: app_args.report_sink,
+            aliases,
+            exclude_emails,
+            exclude_paths,
+            co_change_limits: app_config_cache.co_change.clone(),
+            loc_weighting: app_config_cache.loc_weighting,
+            munch_limits: app_config_cache.munch_limits.clone(),
+            retention: app_config_cache.retention.clone(),
+            merge_limits: app_config_cache.merge_limits.clone(),
+            report_store: app_config_cache.report_store,
+            workday,
+            hooks: app_config_cache.hooks.clone(),
+            graph_format: app_args.graph_format,
+            graph_out: app_args.graph_out,
+            graph_external: app_args.graph_external,
+            sbom_format: app_args.sbom_format,
+            export_format: app_args.export_format,
+            show_hotspots: app_args.show_hotspots,
+            show_modules: app_args.show_modules,
+            trace_file: app_args.trace_file,
+            #[cfg(feature = "cloud")]
+            identity_action: app_args.identity_action,
+            #[cfg(feature = "cloud")]
+            identity_file,
+            #[cfg(feature = "cloud")]
+            data_file,
+            #[cfg(feature = "cloud")]
+            encrypt_key: app_args.encrypt_key,
+            #[cfg(feature = "cloud")]
+            verify_file: app_args.verify_file,
+            audit_last: app_args.audit_last,
+            rules_action: app_args.rules_action,
+            profile_action: app_args.profile_action,
+            rules_dir: app_args.rules_dir,
+            rules_muncher: app_args.rules_muncher,
+            rules_sample_dir: app_args.rules_sample_dir,
         };
 
-        app_config_cache.save(&app_config);
+        // `doctor` may set aside a damaged config file, which would be replaced with the blank cache on save
+        if app_config.command != AppArgCommands::Doctor {
+            app_config_cache.save(&app_config).await;
+        }
 
         app_config
     }
 }
 
+/// Returns `on_disk` config with the top-level values that differ between `loaded` and `updated` copied from `updated`.
+/// Values removed from `updated` are removed from `on_disk`. The rest of `on_disk` is left as-is.
+fn merge_config_changes(loaded: &Value, updated: &Value, mut on_disk: Value) -> Value {
+    let (loaded, updated, on_disk_map) = match (loaded.as_object(), updated.as_object(), on_disk.as_object_mut()) {
+        (Some(loaded), Some(updated), Some(on_disk_map)) => (loaded, updated, on_disk_map),
+        _ => return updated.clone(),
+    };
+
+    for key in loaded.keys().chain(updated.keys()) {
+        if loaded.get(key) == updated.get(key) {
+            continue;
+        }
+        match updated.get(key) {
+            Some(v) => on_disk_map.insert(key.clone(), v.clone()),
+            None => on_disk_map.remove(key),
+        };
+    }
+
+    on_disk
+}
+
+/// Initializes the global log subscriber. It can only be done once. Spans log their duration when they close,
+/// e.g. `phase{phase="munch" files=12}: close time.busy=10ms time.idle=2ms`. JSON output has span fields at the top level
+/// for ingestion into ELK and similar. Emails, absolute paths and secrets are redacted unless `--log-raw` is set.
+fn init_tracing(log_level: tracing::Level, app_args: &AppArgs) {
+    let subscriber = tracing_subscriber::fmt()
+        .with_max_level(log_level)
+        .with_ansi(false)
+        .with_span_events(FmtSpan::CLOSE)
+        .with_writer(LogWriter::new(app_args.log_raw, app_args.log_file.as_deref()));
+
+    match app_args.log_format {
+        LogFormat::Text => subscriber.init(),
+        LogFormat::Json => subscriber
+            .json()
+            .flatten_event(true)
+            .with_current_span(true)
+            .with_span_list(true)
+            .init(),
+    }
+}
+
 /// Generate a new Config struct with the default values from the environment. May panic if the environment is not accessible.
 pub(crate) async fn new_lib_config_with_defaults(current_dir: PathBuf) -> (LibConfig, PathBuf) {
     // check if the app was compiled for release, but is still sitting in target/release/ folder
@@ -363,7 +960,7 @@ pub(crate) async fn new_lib_config_with_defaults(current_dir: PathBuf) -> (LibCo
     };
 
     // find out what email addresses are known from Git for processing contributors individually as the default option
-    let git_identities = match get_local_identities(&current_dir).await {
+    let git_identities = match vcs::detect(&current_dir).get_local_identities(&current_dir).await {
         Ok(v) => v,
         Err(_) => Vec::new(),
     };
@@ -408,7 +1005,8 @@ fn trim_canonical_project_name(name: String) -> String {
 
 /// Returns a validated config.project_dir or exits with an error message
 /// The output path is absolute.
-fn validate_project_dir(project: PathBuf) -> PathBuf {
+/// * `git_dir` - the git folder from `--gitdir` to use instead of the one `.git` file points at
+pub(crate) fn validate_project_dir(project: PathBuf, git_dir: Option<&Path>) -> PathBuf {
     // the project dir at this point is either a tested param from the CLI or the current dir
     // a full-trust app is guaranteed access to the current dir
     // a restricted app would need to test if the dir is actually accessible, but it may fail over even earlier when it tried to get the current dir name
@@ -422,18 +1020,114 @@ fn validate_project_dir(project: PathBuf) -> PathBuf {
     // check if there is .git subfolder in the project dir
     // it can also be `.git` text file that contains a pointer to the parent repo
     // in a multi-repo set up
-    if !project.join(".git").exists() {
-        // there is no sign of git here
-        eprintln!("STACKMUNCHER ERROR: No Git repository found in {}", project.to_string_lossy());
-        eprintln!("    * either run the app from the root of a project with a Git repository");
+    // Mercurial repos have .hg subfolder instead
+    if !project.join(LibConfig::GIT_FOLDER_NAME).exists() && !project.join(LibConfig::HG_FOLDER_NAME).exists() {
+        // there is no sign of git or hg here
+        eprintln!(
+            "STACKMUNCHER ERROR: No Git or Mercurial repository found in {}",
+            project.to_string_lossy()
+        );
+        eprintln!("    * either run the app from the root of a project with a Git or Mercurial repository");
         eprintln!("    * or add `--project path_to_project` param to run from anywhere else");
         help::emit_usage_msg();
-        exit(1);
+        exit(crate::EXIT_CODE_UNSUPPORTED);
+    }
+
+    // `.git` file of a worktree or a submodule points at a git folder elsewhere, which may have been moved or be on
+    // an unmounted volume, so it is checked now instead of failing on one of the git calls halfway through the run
+    let git_file = project.join(LibConfig::GIT_FOLDER_NAME);
+    match git_dir {
+        Some(git_dir) => {
+            if let Err(e) = check_git_dir(git_dir) {
+                eprintln!("STACKMUNCHER CONFIG ERROR: invalid `--gitdir` value: {}", e);
+                exit(1);
+            }
+        }
+        None if git_file.is_file() => {
+            if let Err(e) = resolve_git_file(&git_file) {
+                eprintln!("STACKMUNCHER ERROR: cannot use the Git repository in {}", project.to_string_lossy());
+                eprintln!("    {}", e);
+                eprintln!("    `.git` in the project folder is a file that points at the git folder of a worktree or a submodule.");
+                eprintln!("    * mount or move the main repository back and run `git worktree repair` in it");
+                eprintln!("    * or add `--gitdir \"path to the moved git folder\"` to use it as is");
+                exit(1);
+            }
+        }
+        None => {}
     }
 
     project
 }
 
+/// Returns the git folder from `.git` file of a worktree or a submodule, e.g. `gitdir: ../main/.git/worktrees/feature`.
+/// Relative paths are resolved against the project folder. Returns an error message with the resolved path if
+/// the git folder is missing or incomplete.
+fn resolve_git_file(git_file: &Path) -> Result<PathBuf, String> {
+    let contents = std::fs::read_to_string(git_file)
+        .map_err(|e| format!("cannot read {} due to {}", git_file.to_string_lossy(), e))?;
+
+    let git_dir = contents
+        .lines()
+        .find_map(|line| line.strip_prefix("gitdir:"))
+        .map(|v| v.trim())
+        .filter(|v| !v.is_empty())
+        .ok_or_else(|| format!("{} has no `gitdir:` line", git_file.to_string_lossy()))?;
+
+    // an absolute `gitdir` replaces the project folder in the join
+    let git_dir = git_file.parent().unwrap_or_else(|| Path::new("")).join(git_dir);
+    let git_dir = match git_dir.absolutize() {
+        Ok(v) => v.to_path_buf(),
+        Err(_) => git_dir,
+    };
+
+    check_git_dir(&git_dir)?;
+
+    Ok(git_dir)
+}
+
+/// Checks that the folder has HEAD and the repository objects. Worktrees only keep their own HEAD and index
+/// and share the objects with the main repository via `commondir` file.
+fn check_git_dir(git_dir: &Path) -> Result<(), String> {
+    if !git_dir.is_dir() {
+        return Err(format!("{} does not exist or is not accessible", git_dir.to_string_lossy()));
+    }
+
+    if !git_dir.join("HEAD").is_file() {
+        return Err(format!("{} is not a git folder", git_dir.to_string_lossy()));
+    }
+
+    let common_dir = match std::fs::read_to_string(git_dir.join("commondir")) {
+        Ok(v) => git_dir.join(v.trim()),
+        Err(_) => git_dir.to_path_buf(),
+    };
+    let objects_dir = common_dir.join("objects");
+    if !objects_dir.is_dir() {
+        let objects_dir = match objects_dir.absolutize() {
+            Ok(v) => v.to_path_buf(),
+            Err(_) => objects_dir,
+        };
+        return Err(format!("{} does not exist or is not accessible", objects_dir.to_string_lossy()));
+    }
+
+    Ok(())
+}
+
+/// Converts a validated project path into an absolute path or exits with an error message.
+fn absolutize_project_dir(project: PathBuf) -> PathBuf {
+    match project.absolutize() {
+        Ok(v) => v.to_path_buf(),
+        Err(e) => {
+            eprintln!(
+                "STACKMUNCHER CONFIG ERROR. {} seems to be invalid ({}). Try using an absolute path.",
+                project.to_string_lossy(),
+                e
+            );
+            help::emit_usage_msg();
+            exit(1);
+        }
+    }
+}
+
 /// Validates the value for the config dir, creates the directory if needed and returns its absolute path.
 /// Prints error messages and exits on error.
 fn validate_or_create_config_dir(config_dir: &PathBuf) -> PathBuf {
@@ -523,7 +1217,65 @@ fn validate_or_create_root_report_dir(report_root_dir: PathBuf) -> PathBuf {
 
 /// Validates the value for the reports dir, adds the project component to it and creates the directory if needed.
 /// Prints error messages and exits on error.
-fn validate_or_create_project_report_dir(project: &PathBuf, report_root_dir: &PathBuf) -> PathBuf {
+pub(crate) fn validate_or_create_project_report_dir(project: &PathBuf, report_root_dir: &PathBuf) -> PathBuf {
+    // append the project report subfolder name to the reports root folder
+    let report_dir = report_root_dir.join(project_report_dir_name(project));
+
+    // check if the project report folder exists or create it if possible
+    if !report_dir.is_dir() {
+        if report_dir.exists() {
+            // the path exists as something else
+            eprintln!(
+                "STACKMUNCHER CONFIG ERROR. The path to report directory exists, but it is not a directory: {}",
+                report_dir.to_string_lossy()
+            );
+            help::emit_report_dir_msg();
+            exit(1);
+        }
+        // create it
+        if let Err(e) = std::fs::create_dir_all(report_dir.clone()) {
+            eprintln!(
+                "STACKMUNCHER CONFIG ERROR. Cannot create reports directory at {} due to {}",
+                report_dir.to_string_lossy(),
+                e
+            );
+            help::emit_report_dir_msg();
+            exit(1);
+        };
+    }
+
+    // save the project report path in config as String
+    report_dir
+}
+
+/// Adds the ref subfolder to the project reports dir and creates it if needed, e.g. `.../ref_release_1_2_5c3f0a2e`.
+/// Prints error messages and exits on error.
+fn validate_or_create_ref_report_dir(project_report_dir: &Path, git_ref: &str) -> PathBuf {
+    // refs differing only in punctuation, e.g. `release/1.2` and `release-1.2`, are told apart by the hash
+    let canonical_ref_name = Regex::new(r#"\W+"#)
+        .expect("Invalid canonical ref name regex. It's a bug.")
+        .replace_all(git_ref, "_")
+        .trim_matches('_')
+        .to_lowercase();
+    let report_dir =
+        project_report_dir.join(["ref_", &canonical_ref_name, "_", &hash_str_sha1(git_ref)[0..8]].concat());
+
+    if let Err(e) = std::fs::create_dir_all(&report_dir) {
+        eprintln!(
+            "STACKMUNCHER CONFIG ERROR. Cannot create reports directory at {} due to {}",
+            report_dir.to_string_lossy(),
+            e
+        );
+        help::emit_report_dir_msg();
+        exit(1);
+    };
+
+    report_dir
+}
+
+/// Returns the name of the project report folder built out of the absolute project path and its hash.
+/// E.g. `/home/ubuntu/projects/some-project-name` -> `home_ubuntu_projects_some_project_name_6bdf08b3`.
+pub(crate) fn project_report_dir_name(project: &PathBuf) -> String {
     // individual project reports are grouped in their own folders - build that path here
     // this can be relative or absolute, which should be converted into absolute in a canonical form as a single folder name
     // e.g. /var/tmp/stackmuncher/reports/home_ubuntu_projects_some_project_name_1_6bdf08b3 were the last part is a canonical project name built
@@ -551,36 +1303,21 @@ fn validate_or_create_project_report_dir(project: &PathBuf, report_root_dir: &Pa
     // append its own hash at the end
     let canonical_project_name_hash = hash_str_sha1(&canonical_project_name)[0..8].to_string();
     let canonical_project_name = [canonical_project_name, canonical_project_name_hash].join("_");
-    let canonical_project_name = trim_canonical_project_name(canonical_project_name);
-
-    // append the project report subfolder name to the reports root folder
-    let report_dir = report_root_dir.join(canonical_project_name);
+    trim_canonical_project_name(canonical_project_name)
+}
 
-    // check if the project report folder exists or create it if possible
-    if !report_dir.is_dir() {
-        if report_dir.exists() {
-            // the path exists as something else
-            eprintln!(
-                "STACKMUNCHER CONFIG ERROR. The path to report directory exists, but it is not a directory: {}",
-                report_dir.to_string_lossy()
-            );
-            help::emit_report_dir_msg();
-            exit(1);
-        }
-        // create it
-        if let Err(e) = std::fs::create_dir_all(report_dir.clone()) {
-            eprintln!(
-                "STACKMUNCHER CONFIG ERROR. Cannot create reports directory at {} due to {}",
-                report_dir.to_string_lossy(),
-                e
-            );
-            help::emit_report_dir_msg();
-            exit(1);
-        };
-    }
+/// Returns the reason as an error if the config file exists, but cannot be read or parsed.
+/// The app replaces such a file with default values on the next run.
+pub(crate) fn check_config_file(config_file_path: &Path) -> Result<(), String> {
+    let contents = match std::fs::read(config_file_path) {
+        Ok(v) => v,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+        Err(e) => return Err(e.to_string()),
+    };
 
-    // save the project report path in config as String
-    report_dir
+    serde_json::from_slice::<AppConfigCache>(&contents)
+        .map(|_| ())
+        .map_err(|e| e.to_string())
 }
 
 impl AppConfigCache {
@@ -589,10 +1326,31 @@ impl AppConfigCache {
         // create a blank dummy to return in case of a problem
         let app_config_cache = AppConfigCache {
             primary_email: None,
+            public_name: None,
+            headline: None,
+            public_contact: None,
+            available: None,
+            location: None,
             gh_validation_id: None,
             gh_login: None,
             git_identities: Vec::new(),
             reports_dir: None,
+            scrub_file_paths: Vec::new(),
+            scrub_pkgs: Vec::new(),
+            inbox_url: None,
+            timestamp_url: None,
+            aliases: BTreeMap::new(),
+            exclude_emails: Vec::new(),
+            exclude_paths: Vec::new(),
+            co_change: CoChangeLimits::default(),
+            loc_weighting: LocWeighting::default(),
+            munch_limits: MunchLimits::default(),
+            retention: Retention::default(),
+            merge_limits: MergeLimits::default(),
+            report_store: ReportStore::default(),
+            workday: WorkdayOverlap::default(),
+            hooks: Hooks::default(),
+            watch_projects: Vec::new(),
         };
 
         // check if the file exists
@@ -636,14 +1394,45 @@ impl AppConfigCache {
 
     /// Extracts persistent parts from `AppConfig` and saves them as a file in `.stm_config` folder.
     /// Does not panic. May print a message on error.
-    fn save(self, app_config: &AppConfig) {
+    async fn save(self, app_config: &AppConfig) {
         // prepare the data to save
         let app_config_cache = AppConfigCache {
             primary_email: app_config.primary_email.clone(),
+            public_name: app_config.public_name.clone(),
+            headline: app_config.headline.clone(),
+            public_contact: app_config.public_contact.clone(),
+            available: app_config.available,
+            location: app_config.location.clone(),
             gh_validation_id: app_config.gh_validation_id.clone(),
             git_identities: app_config.lib_config.git_identities.clone(),
             gh_login: app_config.gh_login.clone(),
             reports_dir: app_config.reports_dir.clone(),
+            scrub_file_paths: app_config
+                .scrub_rules
+                .file_paths
+                .iter()
+                .map(|r| r.as_str().to_owned())
+                .collect(),
+            scrub_pkgs: app_config
+                .scrub_rules
+                .pkgs
+                .iter()
+                .map(|r| r.as_str().to_owned())
+                .collect(),
+            inbox_url: app_config.inbox_url.clone(),
+            timestamp_url: app_config.timestamp_url.clone(),
+            aliases: app_config.aliases.clone(),
+            exclude_emails: app_config.exclude_emails.clone(),
+            exclude_paths: app_config.exclude_paths.clone(),
+            co_change: app_config.co_change_limits.clone(),
+            loc_weighting: app_config.loc_weighting,
+            munch_limits: app_config.munch_limits.clone(),
+            retention: app_config.retention.clone(),
+            merge_limits: app_config.merge_limits.clone(),
+            report_store: app_config.report_store,
+            workday: app_config.workday.clone(),
+            hooks: app_config.hooks.clone(),
+            watch_projects: app_config.watch_projects.clone(),
         };
 
         // proceed only if there were any changes to the config or if the config file doesn't exist to create a stub the user can edit
@@ -652,10 +1441,40 @@ impl AppConfigCache {
             return;
         }
 
+        // another instance may be saving the config at the same time, e.g. a cron job and a manual run
+        let config_dir = app_config.config_file_path.parent().unwrap_or_else(|| Path::new("."));
+        let _lock = match ProjectLock::acquire_with_wait(config_dir, CONFIG_LOCK_WAIT).await {
+            Ok(Some(v)) => v,
+            _ => {
+                eprintln!(
+                    "STACKMUNCHER ERROR: {} is being updated by another instance of the app ({}). The config changes were not saved.",
+                    app_config.config_file_path.to_string_lossy(),
+                    ProjectLock::holder(config_dir).unwrap_or_else(|| "unknown".to_owned())
+                );
+                return;
+            }
+        };
+
+        // the other instance may have saved its changes since the config was read, so only the values
+        // changed by this run are written over the config on disk
+        let app_config_cache = if app_config.config_file_path.exists() {
+            let on_disk = Self::read_from_disk(&app_config.config_file_path);
+            match (
+                serde_json::to_value(&self),
+                serde_json::to_value(&app_config_cache),
+                serde_json::to_value(&on_disk),
+            ) {
+                (Ok(loaded), Ok(updated), Ok(on_disk)) => merge_config_changes(&loaded, &updated, on_disk),
+                _ => serde_json::to_value(&app_config_cache).unwrap_or_default(),
+            }
+        } else {
+            serde_json::to_value(&app_config_cache).unwrap_or_default()
+        };
+
         // try to serialize and save the config cache
         match serde_json::to_vec_pretty(&app_config_cache) {
             Ok(app_config_cache) => {
-                if let Err(e) = std::fs::write(app_config.config_file_path.clone(), app_config_cache) {
+                if let Err(e) = write_atomically(&app_config.config_file_path, &app_config_cache) {
                     eprintln!(
                         "STACKMUNCHER ERROR: failed to save config cache in {}.\n\n    Reason: {}\n\n    It's a bug.",
                         app_config.config_file_path.to_string_lossy(),
@@ -677,3 +1496,23 @@ impl AppConfigCache {
         }
     }
 }
+
+#[cfg(test)]
+mod test_config {
+    use super::merge_config_changes;
+    use serde_json::json;
+
+    #[test]
+    fn test_merge_config_changes() {
+        let loaded = json!({"primary_email": "a@x.com", "git_identities": ["a@x.com"], "inbox_url": "https://a"});
+        let updated = json!({"primary_email": "b@x.com", "git_identities": ["a@x.com"]});
+        // another instance added an identity after the config was loaded
+        let on_disk =
+            json!({"primary_email": "a@x.com", "git_identities": ["a@x.com", "c@x.com"], "inbox_url": "https://a"});
+
+        assert_eq!(
+            merge_config_changes(&loaded, &updated, on_disk),
+            json!({"primary_email": "b@x.com", "git_identities": ["a@x.com", "c@x.com"]})
+        );
+    }
+}
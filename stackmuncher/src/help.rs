@@ -1,38 +1,43 @@
+use crate::app_args::AppArgCommands;
 use crate::config::{self, AppConfig};
+#[cfg(feature = "cloud")]
 use crate::signing::ReportSignature;
+use crate::ui;
 
 /// Prints out a standard multi-line message on how to use the app and where to find more info
 pub(crate) fn emit_usage_msg() {
     println!();
-    println!("    Run `stackmuncher help` for detailed usage info.");
+    println!("{}", ui::msg("help.run_help"));
     println!();
     emit_support_msg();
 }
 
 /// Prints out a standard multi-line message on where to find more info
 pub(crate) fn emit_support_msg() {
-    println!("Source code and support: https://github.com/stackmuncher/stm_app or info@stackmuncher.com");
+    println!("{}", ui::msg("help.support"));
 }
 
 /// Prints out info on where the reports can be saved
 pub(crate) fn emit_report_dir_msg() {
     println!("");
     if cfg!(debug_assertions) {
-        println!("The default location for StackMuncher reports in DEBUGGING MODE is `{}` sub-folder of the current working directory.", config::REPORT_FOLDER_NAME_DEBUG);
+        println!(
+            "{}",
+            ui::msg_with("help.reports_dir_debug", &[("folder", &config::REPORT_FOLDER_NAME_DEBUG)])
+        );
     } else if cfg!(target_os = "linux") {
         println!(
-            "The default location for StackMuncher reports on Linux is `{}` folder.",
-            config::REPORT_FOLDER_NAME_LINUX
+            "{}",
+            ui::msg_with("help.reports_dir_linux", &[("folder", &config::REPORT_FOLDER_NAME_LINUX)])
         );
     } else if cfg!(target_os = "windows") {
         println!(
-            "The default location for StackMuncher reports on Windows is `{}` folder.",
-            config::REPORT_FOLDER_NAME_WIN
+            "{}",
+            ui::msg_with("help.reports_dir_windows", &[("folder", &config::REPORT_FOLDER_NAME_WIN)])
         );
     }
     println!();
-    println!("    To specify a different location use `--reports` param followed by a relative or absolute path to the reports folder.");
-    println!("    The path will be saved and used for any subsequent runs.");
+    println!("{}", ui::msg("help.reports_dir_param"));
     println!();
     emit_support_msg();
 }
@@ -41,95 +46,73 @@ pub(crate) fn emit_report_dir_msg() {
 pub(crate) fn emit_config_dir_msg() {
     println!("");
     if cfg!(debug_assertions) {
-        println!("The default location for StackMuncher config in DEBUGGING MODE is `{}` sub-folder of the current working directory.", config::CONFIG_FOLDER_NAME_DEBUG);
+        println!(
+            "{}",
+            ui::msg_with("help.config_dir_debug", &[("folder", &config::CONFIG_FOLDER_NAME_DEBUG)])
+        );
     } else if cfg!(target_os = "linux") {
         println!(
-            "The default location for StackMuncher config on Linux is `{}` folder.",
-            config::CONFIG_FOLDER_NAME_LINUX
+            "{}",
+            ui::msg_with("help.config_dir_linux", &[("folder", &config::CONFIG_FOLDER_NAME_LINUX)])
         );
     } else if cfg!(target_os = "windows") {
         println!(
-            "The default location for StackMuncher config on Windows is `{}` folder.",
-            config::CONFIG_FOLDER_NAME_WIN
+            "{}",
+            ui::msg_with("help.config_dir_windows", &[("folder", &config::CONFIG_FOLDER_NAME_WIN)])
         );
     }
     println!();
-    println!("    To specify a different location use `--config` param followed by a relative or absolute path to the config folder.");
+    println!("{}", ui::msg("help.config_dir_param"));
     println!();
     emit_support_msg();
 }
 
 /// Prints out either Win or nix/Mac msg with --log info instructions on getting more info
+#[cfg(feature = "cloud")]
 pub(crate) fn emit_detailed_output_msg() {
-    eprintln!(
-        "To see detailed output run `stackmuncher --log info` from the project root directory (where .git folder is)."
-    );
+    eprintln!("{}", ui::msg("help.detailed_output"));
+}
+
+/// Prints a message about a command that needs the `cloud` feature in a build without it.
+#[cfg(not(feature = "cloud"))]
+pub(crate) fn emit_no_cloud_msg() {
+    eprintln!("{}", ui::msg("help.no_cloud"));
 }
 
 /// Prints a message about invalid args and exits with code 1.
 pub(crate) fn emit_cli_err_msg() {
-    eprintln!("Cannot parse the parameters from the command line. Run `stackmuncher help` for usage details.");
+    eprintln!("{}", ui::msg("help.cli_error"));
 }
 
 /// Prints a message about an invalid private key.
+#[cfg(feature = "cloud")]
 pub(crate) fn emit_key_err_msg(key_file_path: &str) {
     eprintln!();
-    eprintln!("    1. Did you manually copied the contents of `key.txt`? It is invalid. Try again.");
-    eprintln!();
-    eprintln!(
-        "    2. If you didn't edit {} you can delete it and the app will generate a new one.",
-        key_file_path
-    );
-    eprintln!("    The side effect of that is that the app will also create a new Directory Profile for you.");
-    eprintln!("    If you think you lost your original key, just delete the file and contact us on info@stackmuncher.com to link your existing Directory Profile to the new key.");
-    eprintln!("    We apologize for not automating this step yet.");
+    eprintln!("{}", ui::msg_with("help.key_error", &[("key_file", &key_file_path)]));
     eprintln!();
 }
 
 /// Prints a message about a the first run over a repo.
+#[cfg(feature = "cloud")]
 pub(crate) fn emit_dryrun_msg(report_file_path: &str) {
     eprintln!();
-    eprintln!("A stack report was generated, but NOT submitted.");
-    eprintln!();
-    eprintln!(
-        "    If your project code is commercially sensitive you can check {} file to make sure the stack report is safe to submit.",
-        report_file_path
-    );
-    eprintln!();
-    eprintln!("    The app will add this project to your profile at the next run unless `--dryrun` flag is specified.");
-    eprintln!("    Run `stackmuncher` again to add it now.");
+    eprintln!("{}", ui::msg_with("help.dryrun", &[("report_file", &report_file_path)]));
     eprintln!();
 }
 
 /// Prints a message about validation Gist troubleshooting.
+#[cfg(feature = "cloud")]
 pub(crate) fn emit_gist_troubleshooting(gist_id: &str, uri: &str) {
     eprintln!();
-    eprintln!("Troubleshooting:");
-    eprintln!("    1. Try again if it was a networking or some other intermittent problem.");
-    eprintln!(
-        "    2. Check the response with `curl -i -H 'Accept: application/vnd.github.v3+json' {}",
-        uri
-    );
-    eprintln!(
-        "       The Gist ID ({}) was taken from `--gist` CLI param. Check if it is correct.",
-        gist_id
-    );
+    eprintln!("{}", ui::msg_with("help.gist_troubleshooting", &[("uri", &uri), ("gist_id", &gist_id)]));
     eprintln!();
 }
 
 /// Prints a message about validation Gist signature troubleshooting.
-pub(crate) fn emit_gist_instructions(gist_content: &String) {
+#[cfg(feature = "cloud")]
+pub(crate) fn emit_gist_instructions(gist_content: &str) {
     eprintln!();
-    eprintln!("GitHub account validation steps:");
-    eprintln!("    1. Navigate to https://gist.github.com to create a new Gist.");
-    eprintln!("      * description: \"stm validation\" or any other value");
-    eprintln!("      * file name: \"stm.txt\" or any other value");
-    eprintln!("      * content: \"{}\"", gist_content);
-    eprintln!("    2. Use the URL of the saved Gist to run `stackmuncher config --gist [URL goes here]'");
-    eprintln!();
-    eprintln!(
-        "Example: `stackmuncher config --gist https://gist.github.com/rimutaka/fb8fc0f87ee78231f064131022c8154a`"
-    );
+    eprintln!("{}", ui::msg_with("help.gist_instructions", &[("gist_content", &gist_content)]));
     eprintln!();
 }
 
@@ -138,7 +121,10 @@ pub(crate) fn emit_welcome_msg(config: AppConfig) {
     // directory profile URL depends on if it's public or not
     let dir_profile_url = match &config.gh_login {
         Some(login) if !login.is_empty() => format!("https://stackmuncher.com/{}", login),
+        #[cfg(feature = "cloud")]
         _ => format!("https://stackmuncher.com/?dev={}", ReportSignature::get_public_key(&config.user_key_pair)),
+        #[cfg(not(feature = "cloud"))]
+        _ => ui::msg("help.no_cloud"),
     };
 
     let profile_msg = match &config.gh_login {
@@ -167,23 +153,295 @@ CODE PRIVACY:
 
 OPTIONS:
     --emails \"me@example.com,me@google.com\"       a list of all your commit emails, only need to use it once, defaults to `git config user.email`
+    --alias \"old@example.com=me@example.com\"      count commits from an old or noreply email as yours, on top of the project's .mailmap
+    --exclude-emails \"pair@example.com\"           leave a shared identity, e.g. on a pairing workstation, out of your reports
+    --exclude-paths \"^pairing/\"                   leave files matching these comma-separated regex out of your reports
 
     --primary_email \"me@example.com\"              for Directory notifications only, defaults to the address in `git config user.email` setting
+    --public_name \"Jane Doe\"                      your name as shown in your Directory Profile, use \"\" to remove
+    --headline \"Backend engineer - Rust/Go\"       a one-line summary shown in your Directory Profile, use \"\" to remove
     --gist                                         a URL of your GitHub login validation Gist, run `stackmuncher github` for details
 
     --project \"path to project to be analyzed\"    can be relative or absolute, defaults to the current working directory
+    --gitdir \"path to .git folder\"                the moved git folder of a worktree or a submodule if `.git` file points elsewhere
     --reports \"path to reports folder\"            can be relative or absolute, defaults to the application folder
     --config \"path to config folder\"              can be relative or absolute, defaults to the application folder
 
     --log error|warn|info|debug|trace             defaults to `error` for least verbose output
+    --log-format text|json                        JSON log lines with the project hash, phase, duration and counts for log collectors
+    --log-raw                                     keep emails, absolute paths and secrets in the log, which are redacted by default
+    --log-file \"path to log file\"                 append an unredacted copy of the log to a file
+    --trace-file \"src/main.rs\"                   explain which rules kept or dropped the file or all files in the folder, use `.` for all files
+    --full-history                                fetch the missing commits of a shallow clone, e.g. `git clone --depth 50` on CI
+    --wait                                        wait up to an hour for another instance of the app to finish with the project
     --dryrun                                      skip updating your Directory Profile (no data leaves your computer)
+    --show-what-is-sent                           save the exact data sent to the Directory in the reports folder for review
+    --inbox-url \"https://inbox.example.com\"      a self-hosted endpoint for report submissions, use \"\" to revert to the default
+    --timestamp-url \"https://freetsa.org/tsr\"     timestamp the hash of every new report with an RFC3161 authority, use \"\" to stop
+
+    --workday 9-17                                working hours for the timezone overlap in your reports, defaults to 8-18
+    --timezones \"Europe/Berlin,US/Pacific\"       calculate the overlap only for these timezones, use \"\" to revert to all UTC offsets
+
+    HTTPS_PROXY and NO_PROXY env vars are honoured for all outgoing requests.
 
 MORE INFO:
 
+    stackmuncher demo                   analyzes a sample project and explains what the app does, nothing is submitted
     stackmuncher config                 prints the URL of your Directory Profile and other configuration details
+    stackmuncher rollup --group-by org  summarizes all analyzed projects grouped by the org of their git remote
+    stackmuncher profile                merges all analyzed projects into a single local profile with per-project overviews
+    stackmuncher profile set            updates your public name, contact, availability and location in the Directory
+    stackmuncher watch                  stays running and updates your Directory Profile when you make new commits
+    stackmuncher graph --out deps.dot   exports the dependency graph of the project files for Graphviz
+    stackmuncher sbom --format spdx     exports the libraries used by the project as a CycloneDX or SPDX SBOM
+    stackmuncher export --format md     prints a summary of your stack in the project for a resume or a profile README
+    stackmuncher show --hotspots        lists large files that change often as candidates for refactoring
+    stackmuncher show --modules         lists the files most imported by or importing other project files
+    stackmuncher timeline               shows how your stack in the project changed over the years
+    stackmuncher identity export        saves your identity into a file for using the same profile on another machine
+    stackmuncher audit --last 10        lists what was sent to the Directory, when and what the response was
+    stackmuncher status                 shows if the Directory accepted the last report of every project
+    stackmuncher export-data            saves your config, key and reports into a single file for moving to another machine
+    stackmuncher verify                 checks the timestamp of the project report when timestamping is on
+    stackmuncher doctor --fix           finds and removes cached reports damaged by a crash or a full disk
+    stackmuncher [command] --help       displays the options valid for that command
     stackmuncher help                   displays this message
 
     https://stackmuncher.com/about      about the Directory
     https://github.com/stackmuncher     source code, issues and more
     ", dir_profile_url=dir_profile_url, profile_msg=profile_msg);
 }
+
+/// Prints usage and valid options for a single command, e.g. `stackmuncher config --help`.
+pub(crate) fn emit_command_help(command: AppArgCommands) {
+    let (usage, description, options) = match command {
+        AppArgCommands::Munch | AppArgCommands::Help => (
+            "stackmuncher [munch] [OPTIONS]",
+            "Analyzes the Git repo in the current folder and creates or updates your Directory Profile.",
+            "\
+    --emails \"me@example.com,me@google.com\"       a list of all your commit emails, only need to use it once
+    --alias \"old@example.com=me@example.com\"      merge commits from the old email into the new one, on top of .mailmap
+    --exclude-emails \"pair@example.com\"           leave a shared identity out of your reports
+    --exclude-paths \"^pairing/\"                   leave files matching these comma-separated regex out of your reports
+    --primary_email \"me@example.com\"              for Directory notifications only
+    --public_name \"Jane Doe\"                      your name as shown in your Directory Profile
+    --headline \"Backend engineer - Rust/Go\"       a one-line summary shown in your Directory Profile
+    --gist                                         a URL of your GitHub login validation Gist
+    --project \"path to project to be analyzed\"    can be relative or absolute, defaults to the current working directory
+    --gitdir \"path to .git folder\"                the moved git folder of a worktree or a submodule if `.git` file points elsewhere
+    --trace-file \"src/main.rs\"                   explain which rules kept or dropped the file or all files in the folder
+    --full-history                                fetch the missing commits of a shallow clone before munching
+    --wait                                        wait up to an hour for another instance of the app to finish with the project
+    --as-of 2023-06-01                            analyze the project as it was on that date, nothing is submitted
+    --ref release/1.2                             analyze a branch, a tag or a commit instead of HEAD, nothing is submitted
+    --dryrun                                      skip updating your Directory Profile (no data leaves your computer)
+    --show-what-is-sent                           save the exact data sent to the Directory in the reports folder for review
+    --inbox-url \"https://inbox.example.com\"      a self-hosted endpoint for report submissions
+    --timestamp-url \"https://freetsa.org/tsr\"     timestamp the hash of every new report with an RFC3161 authority
+    --report-sink \"s3://bucket/prefix\"            also save the reports in an S3 bucket, an HTTPS endpoint or a folder
+    --workday 9-17                                working hours for the timezone overlap, defaults to 8-18
+    --timezones \"Europe/Berlin,US/Pacific\"       calculate the overlap only for these timezones",
+        ),
+        AppArgCommands::ViewConfig => (
+            "stackmuncher config [OPTIONS]",
+            "Updates the config with the values of the options, if any, and prints the URL of your Directory Profile and other configuration details.",
+            "\
+    --emails \"me@example.com,me@google.com\"       a list of all your commit emails, only need to use it once
+    --alias \"old@example.com=me@example.com\"      merge commits from the old email into the new one, use \"old@example.com=\" to remove
+    --exclude-emails \"pair@example.com\"           leave a shared identity out of your reports, use \"\" to remove
+    --exclude-paths \"^pairing/\"                   leave files matching these comma-separated regex out of your reports, use \"\" to remove
+    --primary_email \"me@example.com\"              for Directory notifications only, use \"\" to remove
+    --public_name \"Jane Doe\"                      your name as shown in your Directory Profile, use \"\" to remove
+    --headline \"Backend engineer - Rust/Go\"       a one-line summary shown in your Directory Profile, use \"\" to remove
+    --gist                                         a URL of your GitHub login validation Gist, use \"\" to unlink
+    --inbox-url \"https://inbox.example.com\"      a self-hosted endpoint for report submissions, use \"\" to revert to the default
+    --timestamp-url \"https://freetsa.org/tsr\"     timestamp the hash of every new report with an RFC3161 authority, use \"\" to stop
+    --workday 9-17                                working hours for the timezone overlap, defaults to 8-18
+    --timezones \"Europe/Berlin,US/Pacific\"       calculate the overlap only for these timezones, use \"\" to revert to all UTC offsets",
+        ),
+        AppArgCommands::GitGHubConfig => (
+            "stackmuncher github [OPTIONS]",
+            "Prints instructions for linking your Directory Profile to your GitHub account.",
+            "\
+    --gist                                         a URL of your GitHub login validation Gist, use \"\" to unlink",
+        ),
+        AppArgCommands::MakeAnon => (
+            "stackmuncher makeanon [OPTIONS]",
+            "Removes your name and contact details from the Directory making your profile anonymous.",
+            "",
+        ),
+        AppArgCommands::DeleteProfile => (
+            "stackmuncher delete [OPTIONS]",
+            "Completely deletes your profile from the Directory.",
+            "",
+        ),
+        AppArgCommands::Rollup => (
+            "stackmuncher rollup [OPTIONS]",
+            "Combines the local reports of all analyzed projects into a summary. Nothing is submitted to the Directory.",
+            "\
+    --group-by org|none                           group projects by the org or namespace of their git remote, e.g. github.com/rust-lang
+    --workday 9-17                                working hours for the timezone overlap, defaults to 8-18
+    --timezones \"Europe/Berlin,US/Pacific\"       calculate the overlap only for these timezones, use \"\" to revert to all UTC offsets",
+        ),
+        AppArgCommands::Bench => (
+            "stackmuncher bench [OPTIONS]",
+            "Benchmarks the app on a synthetic repo and prints the timings for sharing in a bug report or checking for performance regressions. Nothing is submitted to the Directory.",
+            "\
+    --files 500                                   number of files in the synthetic repo
+    --commits 1000                                number of commits in the synthetic repo
+    --contributors 10                             number of commit authors in the synthetic repo
+    --out \"baseline.json\"                         save the timings as a baseline for `--baseline`
+    --baseline \"baseline.json\"                    fail if any timing is slower than in the baseline, the repo size is taken from it
+    --max-regression 25                           how many percent slower than the baseline a timing may get, defaults to 50",
+        ),
+        AppArgCommands::Watch => (
+            "stackmuncher watch [OPTIONS]",
+            "Stays running and re-analyzes the watched projects when new commits appear. Watches the current folder if no projects were added.",
+            "\
+    --project \"path to project to be watched\"     adds the project to the watch list, only need to use it once per project
+    --interval 5                                  minutes between checks for new commits
+    --inbox-url \"https://inbox.example.com\"      a self-hosted endpoint for report submissions
+    --timestamp-url \"https://freetsa.org/tsr\"     timestamp the hash of every new report with an RFC3161 authority
+    --report-sink \"s3://bucket/prefix\"            also save the reports in an S3 bucket, an HTTPS endpoint or a folder
+    --full-history                                fetch the missing commits of shallow clones before munching
+    --wait                                        wait up to an hour for another instance of the app to finish with the project
+    --dryrun                                      skip updating your Directory Profile (no data leaves your computer)",
+        ),
+        AppArgCommands::Graph => (
+            "stackmuncher graph [OPTIONS]",
+            "Exports the dependency graph of the project files with LoC and owners from the last analysis of the project. Run `stackmuncher` in the project folder first. Nothing is submitted to the Directory.",
+            "\
+    --format dot|json                             Graphviz DOT or JSON, defaults to dot
+    --out \"deps.dot\"                              the file to save the graph into, defaults to printing it
+    --external                                    add edges to external packages and namespaces
+    --project \"path to project\"                   can be relative or absolute, defaults to the current working directory
+    --gitdir \"path to .git folder\"                the moved git folder of a worktree or a submodule if `.git` file points elsewhere",
+        ),
+        AppArgCommands::Sbom => (
+            "stackmuncher sbom [OPTIONS]",
+            "Exports the libraries used by the project from the last analysis of the project as a software bill of materials. Versions are taken from Cargo.lock, package-lock.json or composer.lock in the project folder if present. Run `stackmuncher` in the project folder first. Nothing is submitted to the Directory.",
+            "\
+    --format cyclonedx|spdx                       CycloneDX 1.5 or SPDX 2.3 JSON, defaults to cyclonedx
+    --out \"sbom.json\"                             the file to save the SBOM into, defaults to printing it
+    --project \"path to project\"                   can be relative or absolute, defaults to the current working directory
+    --gitdir \"path to .git folder\"                the moved git folder of a worktree or a submodule if `.git` file points elsewhere",
+        ),
+        AppArgCommands::Export => (
+            "stackmuncher export [OPTIONS]",
+            "Prints a Markdown summary of your contributions to the project from the last analysis: the top languages with lines of code and the years you used them in, the top libraries and how often you commit. Paste it into a resume or a GitHub profile README. Run `stackmuncher` in the project folder first. Nothing is submitted to the Directory.",
+            "\
+    --format md                                   Markdown, the only format for now
+    --out \"stack.md\"                              the file to save the summary into, defaults to printing it
+    --project \"path to project\"                   can be relative or absolute, defaults to the current working directory
+    --gitdir \"path to .git folder\"                the moved git folder of a worktree or a submodule if `.git` file points elsewhere",
+        ),
+        AppArgCommands::Show => (
+            "stackmuncher show --hotspots|--modules [OPTIONS]",
+            "Prints sections of the project report from the last analysis of the project. Run `stackmuncher` in the project folder first. Nothing is submitted to the Directory.",
+            "\
+    --hotspots                                    large files that change often, ranked by the number of commits × lines of code
+    --modules                                     the most central files, ranked by the number of imports from and to other project files
+    --project \"path to project\"                   can be relative or absolute, defaults to the current working directory
+    --gitdir \"path to .git folder\"                the moved git folder of a worktree or a submodule if `.git` file points elsewhere",
+        ),
+        AppArgCommands::Identity => (
+            "stackmuncher identity export|import [OPTIONS]",
+            "Exports your key and commit emails into a passphrase-protected file or imports them on another machine, so that all your machines update the same Directory Profile. Set STACKMUNCHER_PASSPHRASE env var to skip the passphrase prompt.",
+            "\
+    --file \"stackmuncher_identity.txt\"            the identity file to export into or import from, defaults to stackmuncher_identity.txt in the current folder",
+        ),
+        AppArgCommands::Demo => (
+            "stackmuncher demo [OPTIONS]",
+            "Analyzes a generated sample project in a temp folder as if you were one of its developers and explains every section of the results. The sample and its reports are removed at the end. Nothing is submitted to the Directory.",
+            "",
+        ),
+        AppArgCommands::Timeline => (
+            "stackmuncher timeline [OPTIONS]",
+            "Analyzes the project as it was at regular intervals since the first commit and prints how your stack changed over time. The snapshots are saved in as_of_<date> sub-folders of the project reports and reused on the next run. Nothing is submitted to the Directory.",
+            "\
+    --interval 12                                 months between the snapshots, defaults to 12
+    --wait                                        wait up to an hour for another instance of the app to finish with the project
+    --emails \"me@example.com,me@google.com\"       a list of all your commit emails, only need to use it once
+    --alias \"old@example.com=me@example.com\"      merge commits from the old email into the new one, on top of .mailmap
+    --exclude-emails \"pair@example.com\"           leave a shared identity out of your reports
+    --exclude-paths \"^pairing/\"                   leave files matching these comma-separated regex out of your reports
+    --project \"path to project\"                   can be relative or absolute, defaults to the current working directory",
+        ),
+        AppArgCommands::Profile => (
+            "stackmuncher profile [set] [OPTIONS]",
+            "Merges the reports of all analyzed projects into a single cross-project profile with your stack totals and an overview of every project. The profile is saved as profile_report.json in the reports folder and printed. Nothing is submitted to the Directory.\n\n`stackmuncher profile set` saves the public details of your Directory Profile in the config instead. They are sent with the next report.",
+            "\
+    --public_name \"Jane Doe\"                      with `set`: your name as shown in your Directory Profile, use \"\" to remove
+    --headline \"Backend engineer - Rust/Go\"       with `set`: a one-line summary shown in your Directory Profile, use \"\" to remove
+    --public_contact \"https://example.com/me\"     with `set`: a URL or an email shown in your Directory Profile, use \"\" to remove
+    --available yes|no                            with `set`: show that you are open to job offers
+    --location \"Berlin, Germany\"                 with `set`: your location as shown in your Directory Profile, use \"\" to remove",
+        ),
+        AppArgCommands::ExportData => (
+            "stackmuncher export-data [OPTIONS]",
+            "Saves the config folder with your key, commit emails and the audit log, and all local project reports into a single archive for moving them to another machine with `import-data`. Set STACKMUNCHER_PASSPHRASE env var to skip the passphrase prompt.",
+            "\
+    --out \"stm-backup.tar.gz\"                     the archive to save into, defaults to stm-backup.tar.gz in the current folder
+    --encrypt-key                                 protect the key with a passphrase, the reports and the config are not encrypted",
+        ),
+        AppArgCommands::ImportData => (
+            "stackmuncher import-data [OPTIONS]",
+            "Restores the config, the key and the reports from an archive made by `export-data`. The reports are placed into the reports folder of this machine and the watched projects are moved to your home folder if they are found there. The current config and key are kept as .bak files.",
+            "\
+    --file \"stm-backup.tar.gz\"                    the archive to import from, defaults to stm-backup.tar.gz in the current folder",
+        ),
+        AppArgCommands::Verify => (
+            "stackmuncher verify [OPTIONS]",
+            "Checks that the timestamp token saved next to the report matches the report and is signed by the timestamping authority certificate included in the token, and prints when it was issued. Tokens are only saved if `--timestamp-url` is set. Works offline.",
+            "\
+    --file \"combined_report.json\"                 the report to check, defaults to the combined report of the project
+    --project \"path to project\"                   can be relative or absolute, defaults to the current working directory
+    --gitdir \"path to .git folder\"                the moved git folder of a worktree or a submodule if `.git` file points elsewhere",
+        ),
+        AppArgCommands::Audit => (
+            "stackmuncher audit [OPTIONS]",
+            "Lists the reports submitted from this machine with the time, the endpoint, the SHA256 of the payload, the report sections included and the server response. The log is kept in audit.log in the config folder. Nothing is submitted to the Directory.",
+            "\
+    --last 10                                     only list this many most recent submissions, defaults to all",
+        ),
+        AppArgCommands::Status => (
+            "stackmuncher status [OPTIONS]",
+            "Prints your public and anonymous profile URLs, the number of projects whose last submission failed and the last submission accepted by the Directory for every project. The responses are kept in receipts.json in the config folder. Nothing is submitted to the Directory.",
+            "",
+        ),
+        AppArgCommands::Rules => (
+            "stackmuncher rules check|test <muncher> <sample_dir> [OPTIONS]",
+            "`check` compiles all file-type and muncher rules and prints errors with the line they are on, muncher hashes and which munchers handle which file extensions. `test` runs the muncher, e.g. `rust.rs`, over every file in the samples folder and compares the counts with the expected ones from the sidecar file next to the sample, e.g. `main.rs.expected.json`. Missing sidecars are printed with the actual counts to start from. Exits with an error if any of the rules are invalid or any of the counts do not match. Nothing is submitted to the Directory.",
+            "\
+    --rules \"path to stm_rules folder\"            a folder with file_types and munchers sub-folders to check instead of the built-in rules",
+        ),
+        AppArgCommands::Doctor => (
+            "stackmuncher doctor [OPTIONS]",
+            "Checks the config, the key and the cached reports of all projects for damage, e.g. after a crash or a full disk, and if the report of the project is up to date with its git log. Damaged reports and caches are regenerated on the next run once removed. A damaged key is never removed automatically. Nothing is submitted to the Directory.",
+            "\
+    --fix                                         remove the damaged reports and caches and set aside a damaged config
+    --project \"path to project\"                   also check if the report is up to date, defaults to the current working directory",
+        ),
+    };
+
+    println!();
+    println!("{}", description);
+    println!();
+    println!("USAGE:");
+    println!("    {}", usage);
+    println!();
+    println!("OPTIONS:");
+    if !options.is_empty() {
+        // the line continuation in the option literals strips the indent of the first line
+        println!("    {}", options);
+        println!();
+    }
+    println!("    --reports \"path to reports folder\"            can be relative or absolute, defaults to the application folder");
+    println!("    --config \"path to config folder\"              can be relative or absolute, defaults to the application folder");
+    println!("    --log error|warn|info|debug|trace             defaults to `error` for least verbose output");
+    println!("    --log-format text|json                        defaults to `text`, use `json` for log collectors");
+    println!("    --log-raw                                     keep emails, absolute paths and secrets in the log");
+    println!("    --log-file \"path to log file\"                 append an unredacted copy of the log to a file");
+    println!();
+    emit_support_msg();
+}
@@ -0,0 +1,396 @@
+use stackmuncher_lib::utils::host_name;
+use std::fs::OpenOptions;
+use std::io::{ErrorKind, Write};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, RecvTimeoutError, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant, SystemTime};
+use tracing::{debug, error, warn};
+
+/// Name of the lock file placed in the project report folder while the project is being processed
+/// and in the config folder while the config is being saved.
+pub(crate) const LOCK_FILE_NAME: &str = "stackmuncher.lock";
+
+/// A lock file older than this is assumed to be left behind by a run that crashed or was killed.
+const STALE_LOCK_AGE: Duration = Duration::from_secs(6 * 60 * 60);
+
+/// How often the run holding the lock refreshes the modification time of the lock file, so that a long run
+/// is not mistaken for a crashed one by `STALE_LOCK_AGE`.
+const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(10 * 60);
+
+/// How often a run waiting for the lock checks if it was released.
+const WAIT_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// How long a run started with `--wait` waits for another run to release the project lock before giving up.
+pub(crate) const MAX_LOCK_WAIT: Duration = Duration::from_secs(60 * 60);
+
+/// An advisory lock on a project report folder that prevents concurrent runs on the same repo,
+/// e.g. `watch` and a manual run, or two machines sharing the reports folder over NFS or SMB.
+/// The same lock on the config folder keeps concurrent runs from overwriting each other's config changes.
+/// The lock file is removed when the value is dropped.
+///
+/// The lock file contains `host pid` of the run holding it. A lock is stale if it was not modified for longer
+/// than `STALE_LOCK_AGE` or if it was created on this host by a process that is no longer running. Locks held
+/// by other hosts can only go stale with age because their processes cannot be checked, so the holder touches
+/// the file every `HEARTBEAT_INTERVAL` from a background thread.
+pub(crate) struct ProjectLock {
+    /// Shared with the heartbeat thread so that it follows the lock file if the folder is renamed.
+    lock_file: Arc<Mutex<PathBuf>>,
+    /// Dropping the sender stops the heartbeat thread.
+    heartbeat: Option<(Sender<()>, JoinHandle<()>)>,
+}
+
+/// The contents and the modification time of a lock file to tell if it was replaced or touched by another run.
+#[derive(PartialEq, Debug)]
+struct LockState {
+    contents: String,
+    modified: SystemTime,
+}
+
+impl ProjectLock {
+    /// Creates a lock file in `report_dir`. Returns an error if another run holds the lock or the file cannot be created.
+    /// A stale lock file is replaced.
+    pub(crate) fn acquire(report_dir: &Path) -> Result<Self, ()> {
+        match Self::try_acquire(report_dir)? {
+            Some(v) => Ok(v),
+            None => {
+                warn!(
+                    "The project is locked by another run: {}",
+                    report_dir.join(LOCK_FILE_NAME).to_string_lossy()
+                );
+                Err(())
+            }
+        }
+    }
+
+    /// The same as `acquire`, but waits for the other run to release the lock for up to `max_wait`.
+    /// Returns None if the lock is still held after `max_wait` and an error straight away if the lock file
+    /// cannot be created for any other reason.
+    pub(crate) async fn acquire_with_wait(report_dir: &Path, max_wait: Duration) -> Result<Option<Self>, ()> {
+        let started = Instant::now();
+        loop {
+            if let Some(v) = Self::try_acquire(report_dir)? {
+                return Ok(Some(v));
+            }
+            if started.elapsed() >= max_wait {
+                warn!(
+                    "Gave up waiting for the lock after {}s: {}",
+                    started.elapsed().as_secs(),
+                    report_dir.join(LOCK_FILE_NAME).to_string_lossy()
+                );
+                return Ok(None);
+            }
+            debug!("Waiting for the lock in {}", report_dir.to_string_lossy());
+            tokio::time::sleep(WAIT_POLL_INTERVAL.min(max_wait)).await;
+        }
+    }
+
+    /// Creates a lock file in `report_dir`. Returns None if another run holds the lock and an error if the file
+    /// cannot be created for any other reason. A stale lock file is replaced.
+    fn try_acquire(report_dir: &Path) -> Result<Option<Self>, ()> {
+        let lock_file = report_dir.join(LOCK_FILE_NAME);
+
+        if let Some(stale_state) = stale_lock_state(&lock_file) {
+            warn!("Removing stale lock file {}", lock_file.to_string_lossy());
+            // the rename succeeds for only one of the runs competing for the stale lock,
+            // so that a run cannot delete a fresh lock created by the winner
+            let stale_lock_file = report_dir.join(
+                [
+                    LOCK_FILE_NAME,
+                    ".",
+                    &host_name(),
+                    ".",
+                    &std::process::id().to_string(),
+                    ".stale",
+                ]
+                .concat(),
+            );
+            match std::fs::rename(&lock_file, &stale_lock_file) {
+                Ok(_) => {
+                    remove_stale_lock(&lock_file, &stale_lock_file, &stale_state);
+                }
+                Err(e) if e.kind() == ErrorKind::NotFound => {
+                    debug!("The stale lock was removed by another run");
+                }
+                Err(e) => {
+                    error!("Cannot remove stale lock file {} due to {}", lock_file.to_string_lossy(), e);
+                    return Err(());
+                }
+            }
+        }
+
+        // `create_new` fails if the file exists, which makes the check and the creation a single step
+        // it is atomic on local filesystems, NFSv3+ and SMB
+        match OpenOptions::new().write(true).create_new(true).open(&lock_file) {
+            Ok(mut file) => {
+                // the host and PID are for the user to find the process that holds the lock
+                let _ = write!(file, "{} {}", host_name(), std::process::id());
+                debug!("Lock acquired: {}", lock_file.to_string_lossy());
+                let lock_file = Arc::new(Mutex::new(lock_file));
+                let heartbeat = start_heartbeat(lock_file.clone());
+                Ok(Some(Self { lock_file, heartbeat }))
+            }
+            Err(e) if e.kind() == ErrorKind::AlreadyExists => Ok(None),
+            Err(e) => {
+                error!("Cannot create lock file {} due to {}", lock_file.to_string_lossy(), e);
+                Err(())
+            }
+        }
+    }
+
+    /// Updates the path to the lock file after the folder holding it was renamed to `new_report_dir`.
+    pub(crate) fn moved_to(&mut self, new_report_dir: &Path) {
+        if let Ok(mut lock_file) = self.lock_file.lock() {
+            *lock_file = new_report_dir.join(LOCK_FILE_NAME);
+        }
+    }
+
+    /// Returns the path to the lock file.
+    pub(crate) fn lock_file(report_dir: &Path) -> PathBuf {
+        report_dir.join(LOCK_FILE_NAME)
+    }
+
+    /// Returns the host and PID of the run holding the lock, e.g. `laptop, PID 1234`, or None if there is no lock.
+    pub(crate) fn holder(report_dir: &Path) -> Option<String> {
+        let (host, pid) = read_lock_file(&report_dir.join(LOCK_FILE_NAME))?;
+        Some(format!("{}, PID {}", host, pid))
+    }
+}
+
+impl Drop for ProjectLock {
+    fn drop(&mut self) {
+        // stop the heartbeat first so that it does not touch the file after it is removed
+        if let Some((stop, handle)) = self.heartbeat.take() {
+            drop(stop);
+            let _ = handle.join();
+        }
+
+        let lock_file = match self.lock_file.lock() {
+            Ok(v) => v,
+            Err(e) => e.into_inner(),
+        };
+        match std::fs::remove_file(&*lock_file) {
+            Ok(_) => debug!("Lock released: {}", lock_file.to_string_lossy()),
+            Err(e) => error!("Cannot remove lock file {} due to {}", lock_file.to_string_lossy(), e),
+        }
+    }
+}
+
+/// Locks the project report folder for the rest of the run. Waits up to `MAX_LOCK_WAIT` for the other run holding
+/// the lock to finish if `wait` is set. Otherwise prints an error with the holder of the lock and returns straight away.
+/// `project_name` is for the messages, e.g. the project folder.
+pub(crate) async fn lock_report_dir(report_dir: &Path, project_name: &str, wait: bool) -> Result<ProjectLock, ()> {
+    if let Ok(v) = ProjectLock::acquire(report_dir) {
+        return Ok(v);
+    }
+
+    if wait {
+        println!(
+            "    Waiting for another instance of the app ({}) to finish with {}",
+            ProjectLock::holder(report_dir).unwrap_or_else(|| "unknown".to_owned()),
+            project_name
+        );
+        match ProjectLock::acquire_with_wait(report_dir, MAX_LOCK_WAIT).await {
+            Ok(Some(v)) => return Ok(v),
+            Ok(None) => {
+                eprintln!(
+                    "STACKMUNCHER ERROR: gave up waiting for {} after {} minutes. It is still being processed by another instance of the app ({}).",
+                    project_name,
+                    MAX_LOCK_WAIT.as_secs() / 60,
+                    ProjectLock::holder(report_dir).unwrap_or_else(|| "unknown".to_owned())
+                );
+                eprintln!(
+                    "    Delete {} if no other instance is running.",
+                    ProjectLock::lock_file(report_dir).to_string_lossy()
+                );
+            }
+            Err(_) => eprintln!(
+                "STACKMUNCHER ERROR: cannot create the lock file {}. Run with `--log error` for details.",
+                ProjectLock::lock_file(report_dir).to_string_lossy()
+            ),
+        }
+        return Err(());
+    }
+
+    eprintln!(
+        "STACKMUNCHER ERROR: {} is being processed by another instance of the app ({}). Try again later or add `--wait`.",
+        project_name,
+        ProjectLock::holder(report_dir).unwrap_or_else(|| "unknown".to_owned())
+    );
+    eprintln!(
+        "    Delete {} if no other instance is running.",
+        ProjectLock::lock_file(report_dir).to_string_lossy()
+    );
+
+    Err(())
+}
+
+/// Starts a thread that refreshes the modification time of the lock file every `HEARTBEAT_INTERVAL`
+/// until the returned sender is dropped. Returns None if the thread cannot be started.
+fn start_heartbeat(lock_file: Arc<Mutex<PathBuf>>) -> Option<(Sender<()>, JoinHandle<()>)> {
+    let (stop, stopped) = mpsc::channel::<()>();
+    let handle = std::thread::Builder::new()
+        .name("lock-heartbeat".to_owned())
+        .spawn(move || {
+            while let Err(RecvTimeoutError::Timeout) = stopped.recv_timeout(HEARTBEAT_INTERVAL) {
+                if let Ok(lock_file) = lock_file.lock() {
+                    touch_lock_file(&lock_file);
+                }
+            }
+        });
+
+    match handle {
+        Ok(v) => Some((stop, v)),
+        Err(e) => {
+            warn!("Cannot start the lock heartbeat due to {}. A very long run may lose the lock.", e);
+            None
+        }
+    }
+}
+
+/// Sets the modification time of the lock file to now.
+fn touch_lock_file(lock_file: &Path) {
+    match OpenOptions::new()
+        .write(true)
+        .open(lock_file)
+        .and_then(|file| file.set_modified(SystemTime::now()))
+    {
+        Ok(_) => debug!("Lock refreshed: {}", lock_file.to_string_lossy()),
+        Err(e) => warn!("Cannot refresh lock file {} due to {}", lock_file.to_string_lossy(), e),
+    }
+}
+
+/// Deletes `stale_lock_file` that was renamed from `lock_file` if it is still the same stale lock as `stale_state`.
+/// Another run may have replaced the stale lock with its own or its holder may have touched it between the check
+/// and the rename, in which case it is renamed back and left alone.
+fn remove_stale_lock(lock_file: &Path, stale_lock_file: &Path, stale_state: &LockState) {
+    if read_lock_state(stale_lock_file).as_ref() == Some(stale_state) {
+        let _ = std::fs::remove_file(stale_lock_file);
+        return;
+    }
+
+    warn!("The lock was taken by another run: {}", lock_file.to_string_lossy());
+    if let Err(e) = std::fs::rename(stale_lock_file, lock_file) {
+        error!(
+            "Cannot restore lock file {} from {} due to {}",
+            lock_file.to_string_lossy(),
+            stale_lock_file.to_string_lossy(),
+            e
+        );
+    }
+}
+
+/// Returns the contents and the modification time of the lock file, or None if it cannot be read.
+fn read_lock_state(lock_file: &Path) -> Option<LockState> {
+    let modified = std::fs::metadata(lock_file).and_then(|m| m.modified()).ok()?;
+    let contents = std::fs::read_to_string(lock_file).ok()?;
+
+    Some(LockState { contents, modified })
+}
+
+/// Returns the host and the PID from the lock file. Lock files from older versions only have the PID.
+fn read_lock_file(lock_file: &Path) -> Option<(String, u32)> {
+    parse_lock_contents(&std::fs::read_to_string(lock_file).ok()?)
+}
+
+/// Returns the host and the PID from the contents of a lock file.
+fn parse_lock_contents(contents: &str) -> Option<(String, u32)> {
+    let mut parts = contents.split_whitespace().rev();
+    let pid = parts.next()?.parse::<u32>().ok()?;
+    let host = parts.next().unwrap_or_default().to_owned();
+
+    Some((host, pid))
+}
+
+/// Returns the state of the lock file if it exists and was not modified for longer than `STALE_LOCK_AGE`
+/// or was created on this host by a process that is no longer running. Returns None otherwise.
+fn stale_lock_state(lock_file: &Path) -> Option<LockState> {
+    let state = read_lock_state(lock_file)?;
+    // the file is from the future if the age cannot be calculated - leave it alone
+    let age = state.modified.elapsed().ok()?;
+
+    if age > STALE_LOCK_AGE {
+        return Some(state);
+    }
+
+    match parse_lock_contents(&state.contents) {
+        Some((host, pid)) if host == host_name() && !is_process_running(pid) => Some(state),
+        _ => None,
+    }
+}
+
+/// Returns false if the process is known to be not running. Always true on platforms where it cannot be checked.
+fn is_process_running(pid: u32) -> bool {
+    if cfg!(target_os = "linux") {
+        Path::new("/proc").join(pid.to_string()).exists()
+    } else {
+        true
+    }
+}
+
+#[tokio::test]
+async fn test_project_lock() {
+    let report_dir = std::env::temp_dir().join(format!("stm_lock_test_{}", std::process::id()));
+    std::fs::create_dir_all(&report_dir).unwrap();
+
+    let lock = ProjectLock::acquire(&report_dir).unwrap();
+    assert!(ProjectLock::lock_file(&report_dir).exists());
+    assert!(ProjectLock::acquire(&report_dir).is_err());
+    assert_eq!(
+        ProjectLock::holder(&report_dir).unwrap(),
+        format!("{}, PID {}", host_name(), std::process::id())
+    );
+
+    assert!(ProjectLock::acquire_with_wait(&report_dir, Duration::from_millis(10))
+        .await
+        .unwrap()
+        .is_none());
+
+    drop(lock);
+    assert!(!ProjectLock::lock_file(&report_dir).exists());
+    assert!(ProjectLock::acquire(&report_dir).is_ok());
+    assert!(ProjectLock::acquire_with_wait(&report_dir, Duration::from_millis(10))
+        .await
+        .unwrap()
+        .is_some());
+
+    // a lock left behind by a dead process on this host is replaced, but a lock from another host is not
+    if cfg!(target_os = "linux") {
+        let dead_pid = u32::MAX;
+        std::fs::write(ProjectLock::lock_file(&report_dir), format!("{} {}", host_name(), dead_pid)).unwrap();
+        assert!(ProjectLock::acquire(&report_dir).is_ok());
+
+        std::fs::write(ProjectLock::lock_file(&report_dir), format!("other-host {}", dead_pid)).unwrap();
+        assert!(ProjectLock::acquire(&report_dir).is_err());
+
+        // a lock from another host goes stale with age unless its holder refreshes it
+        let lock_file = ProjectLock::lock_file(&report_dir);
+        let file = OpenOptions::new().write(true).open(&lock_file).unwrap();
+        file.set_modified(SystemTime::now() - STALE_LOCK_AGE * 2).unwrap();
+        drop(file);
+        touch_lock_file(&lock_file);
+        assert!(stale_lock_state(&lock_file).is_none());
+        let file = OpenOptions::new().write(true).open(&lock_file).unwrap();
+        file.set_modified(SystemTime::now() - STALE_LOCK_AGE * 2).unwrap();
+        drop(file);
+        let stale_state = stale_lock_state(&lock_file).unwrap();
+
+        // the stale lock is left alone if another run replaced it before the rename
+        let stale_lock_file = report_dir.join("stale.lock");
+        std::fs::write(&lock_file, format!("other-host {}", std::process::id())).unwrap();
+        std::fs::rename(&lock_file, &stale_lock_file).unwrap();
+        remove_stale_lock(&lock_file, &stale_lock_file, &stale_state);
+        assert_eq!(std::fs::read_to_string(&lock_file).unwrap(), format!("other-host {}", std::process::id()));
+        assert!(!stale_lock_file.exists());
+
+        // and is deleted if it is still the same
+        let stale_state = read_lock_state(&lock_file).unwrap();
+        std::fs::rename(&lock_file, &stale_lock_file).unwrap();
+        remove_stale_lock(&lock_file, &stale_lock_file, &stale_state);
+        assert!(!lock_file.exists());
+        assert!(!stale_lock_file.exists());
+    }
+
+    let _ = std::fs::remove_dir_all(&report_dir);
+}
@@ -1,13 +1,59 @@
 use crate::config::AppConfig;
 use tracing::info;
 
+#[cfg(feature = "cloud")]
+mod api_client;
 mod app_args;
+mod cmd_audit;
+mod cmd_bench;
 mod cmd_config;
+#[cfg(feature = "cloud")]
+mod cmd_data;
+#[cfg(feature = "cloud")]
+mod cmd_demo;
+mod cmd_doctor;
+mod cmd_export;
+mod cmd_graph;
+#[cfg(feature = "cloud")]
+mod cmd_identity;
 mod cmd_munch;
+mod cmd_profile;
+mod cmd_rollup;
+mod cmd_rules;
+mod cmd_sbom;
+mod cmd_show;
+#[cfg(feature = "cloud")]
+mod cmd_status;
+mod cmd_timeline;
+#[cfg(feature = "cloud")]
+mod cmd_verify;
+mod cmd_watch;
 mod config;
 mod help;
+#[cfg(feature = "cloud")]
+mod http_client;
+mod lock;
+mod log_redaction;
+mod postprocess;
+mod report_sink;
+#[cfg(feature = "s3")]
+mod report_sink_s3;
+#[cfg(feature = "cloud")]
 mod signing;
+#[cfg(feature = "cloud")]
 mod submission;
+#[cfg(feature = "cloud")]
+mod timestamp;
+mod ui;
+
+/// The exit code for a run that failed for any other reason. A success exits with 0.
+pub(crate) const EXIT_CODE_FAILED: i32 = 1;
+/// The exit code for a run that produced a report, but some of the user's commits or files could not be analyzed.
+pub(crate) const EXIT_CODE_PARTIAL: i32 = 2;
+/// The exit code for a project that cannot be analyzed, e.g. it has no repo or has too many files.
+pub(crate) const EXIT_CODE_UNSUPPORTED: i32 = 3;
+/// The exit code for a run that produced a report, but the Directory did not accept it.
+pub(crate) const EXIT_CODE_SUBMISSION_FAILED: i32 = 4;
 
 #[tokio::main]
 async fn main() -> Result<(), ()> {
@@ -37,9 +83,13 @@ async fn main() -> Result<(), ()> {
     info!("Running in debug mode");
 
     match config.command {
-        app_args::AppArgCommands::Munch => {
-            cmd_munch::run(config).await?;
-        }
+        app_args::AppArgCommands::Munch => match config.as_of {
+            Some(as_of) => cmd_timeline::run_as_of(&config, as_of).await?,
+            None => match cmd_munch::exit_code(&cmd_munch::run(&config).await) {
+                0 => {}
+                code => std::process::exit(code),
+            },
+        },
         app_args::AppArgCommands::DeleteProfile => {
             delete_profile();
         }
@@ -49,12 +99,87 @@ async fn main() -> Result<(), ()> {
         app_args::AppArgCommands::ViewConfig => {
             cmd_config::view_config(config).await;
         }
-        app_args::AppArgCommands::Help => {
-            help::emit_welcome_msg(config);
-        }
+        app_args::AppArgCommands::Help => match config.help_topic {
+            Some(topic) => help::emit_command_help(topic),
+            None => help::emit_welcome_msg(config),
+        },
+        #[cfg(feature = "cloud")]
         app_args::AppArgCommands::GitGHubConfig => {
             cmd_config::github(config).await;
         }
+        app_args::AppArgCommands::Rollup => {
+            cmd_rollup::run(config).await?;
+        }
+        app_args::AppArgCommands::Bench => {
+            cmd_bench::run(config).await?;
+        }
+        app_args::AppArgCommands::Watch => {
+            cmd_watch::run(config).await?;
+        }
+        app_args::AppArgCommands::Graph => {
+            cmd_graph::run(config)?;
+        }
+        app_args::AppArgCommands::Sbom => {
+            cmd_sbom::run(config).await?;
+        }
+        app_args::AppArgCommands::Export => {
+            cmd_export::run(&config)?;
+        }
+        #[cfg(feature = "cloud")]
+        app_args::AppArgCommands::Identity => {
+            cmd_identity::run(&config)?;
+        }
+        app_args::AppArgCommands::Audit => {
+            cmd_audit::run(&config)?;
+        }
+        #[cfg(feature = "cloud")]
+        app_args::AppArgCommands::Status => {
+            cmd_status::run(&config)?;
+        }
+        app_args::AppArgCommands::Rules => {
+            cmd_rules::run(&config)?;
+        }
+        app_args::AppArgCommands::Show => {
+            cmd_show::run(&config)?;
+        }
+        #[cfg(feature = "cloud")]
+        app_args::AppArgCommands::ExportData => {
+            cmd_data::export(&config)?;
+        }
+        #[cfg(feature = "cloud")]
+        app_args::AppArgCommands::ImportData => {
+            cmd_data::print_import_summary(&config);
+        }
+        app_args::AppArgCommands::Profile => match config.profile_action {
+            Some(app_args::ProfileAction::Set) => cmd_profile::print_details(&config),
+            None => cmd_profile::run(&config)?,
+        },
+        #[cfg(feature = "cloud")]
+        app_args::AppArgCommands::Verify => {
+            cmd_verify::run(&config)?;
+        }
+        #[cfg(feature = "cloud")]
+        app_args::AppArgCommands::Demo => {
+            cmd_demo::run(config).await?;
+        }
+        app_args::AppArgCommands::Timeline => {
+            cmd_timeline::run(&config).await?;
+        }
+        app_args::AppArgCommands::Doctor => {
+            cmd_doctor::run(&config).await?;
+        }
+        // these commands need the Directory, GitHub or a timestamping authority
+        #[cfg(not(feature = "cloud"))]
+        app_args::AppArgCommands::GitGHubConfig
+        | app_args::AppArgCommands::Identity
+        | app_args::AppArgCommands::Status
+        | app_args::AppArgCommands::ExportData
+        | app_args::AppArgCommands::ImportData
+        | app_args::AppArgCommands::Verify
+        | app_args::AppArgCommands::Demo => {
+            help::emit_no_cloud_msg();
+            return Err(());
+        }
     };
 
     Ok(())
@@ -71,5 +196,5 @@ fn delete_profile() {
 fn make_anon() {
     println!("MAKE ANON: not implemented yet.");
     println!();
-    println!("    Run `stackmuncher{} --public_name \"\" --public_contact \"\"` to remove your public details and make your profile anonymous.", std::env::consts::EXE_SUFFIX);
+    println!("    Run `stackmuncher{} --public_name \"\" --headline \"\"` to remove your public details and make your profile anonymous.", std::env::consts::EXE_SUFFIX);
 }
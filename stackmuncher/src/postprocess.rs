@@ -0,0 +1,415 @@
+use crate::cmd_munch::{MunchOutcome, MunchSummary};
+use crate::config::{self, AppConfig};
+use serde::{Deserialize, Serialize};
+use stackmuncher_lib::project_config::PROJECT_CONFIG_FILE_NAME;
+use stackmuncher_lib::report::Report;
+use stackmuncher_lib::utils::host_name;
+use std::collections::BTreeMap;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::time::{Duration, Instant};
+use tokio::io::AsyncWriteExt;
+use tokio::process::Command;
+use tracing::{debug, error, info, warn};
+
+/// The folder in the config dir with user executables run after each analysis.
+pub(crate) const POSTPROCESS_DIR_NAME: &str = "postprocess.d";
+/// The folder in the reports folder with a sub-folder per host for the logs of post-processor runs, one per project.
+/// Hosts sharing the reports folder never write into the same log.
+pub(crate) const RUN_LOGS_DIR_NAME: &str = "run_logs";
+/// The extension of a run log, e.g. `run_logs/laptop/home_ubuntu_projects_stm_6bdf08b3.log`.
+const RUN_LOG_FILE_EXTENSION: &str = ".log";
+/// A post-processor that takes longer than this is killed and logged as failed, unless set in config.json.
+const DEFAULT_HOOK_TIMEOUT_SECS: u64 = 300;
+
+/// Executables run after each analysis on top of those in `postprocess.d`, e.g. for posting to Slack.
+/// Can only be set in `hooks` section of config.json.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(default)]
+pub(crate) struct Hooks {
+    /// Paths to the executables, e.g. `/usr/local/bin/stm-slack`. Relative paths start at the config folder.
+    pub commands: Vec<PathBuf>,
+    /// Hooks and post-processors running longer than this are stopped.
+    pub timeout_secs: u64,
+    /// Also run `hooks` from `.stackmuncher.toml` of the project. Off by default because anyone who can commit
+    /// to the project could run their code on this machine otherwise.
+    pub allow_project_hooks: bool,
+    /// Record the exit codes of post-processors and hooks in `run_logs` of the reports folder.
+    pub run_logs: bool,
+}
+
+impl Default for Hooks {
+    fn default() -> Self {
+        Self {
+            commands: Vec::new(),
+            timeout_secs: DEFAULT_HOOK_TIMEOUT_SECS,
+            allow_project_hooks: false,
+            run_logs: true,
+        }
+    }
+}
+
+/// The summary of the analysis passed to post-processors and hooks on stdin as JSON.
+#[derive(Serialize, Debug)]
+pub(crate) struct HookInput<'a> {
+    /// The same as the only argument.
+    pub report: &'a Path,
+    pub reports_dir: &'a Path,
+    pub project_dir: &'a Path,
+    /// `complete` or `partial`.
+    pub outcome: &'a str,
+    pub dryrun: bool,
+    /// The Directory did not accept the report.
+    pub submission_failed: bool,
+    /// The problems listed under `Warnings` at the end of the run.
+    pub warnings: &'a [String],
+    /// Lines of code per language from the report.
+    pub loc: BTreeMap<String, u64>,
+    pub version: &'static str,
+}
+
+/// A record of running all post-processors after a single analysis. The log has one JSON record per line.
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub(crate) struct RunLogEntry {
+    /// The time of the run in RFC3339 format, UTC.
+    pub timestamp: String,
+    /// `complete` or `partial`, same as `STACKMUNCHER_OUTCOME` passed to post-processors.
+    pub outcome: String,
+    /// The report passed to post-processors as the only argument.
+    pub report: PathBuf,
+    pub postprocessors: Vec<PostprocessorResult>,
+}
+
+/// The result of running a single post-processor.
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub(crate) struct PostprocessorResult {
+    /// The file name of the executable in `postprocess.d`.
+    pub name: String,
+    /// The exit code or None if the post-processor could not be started, timed out or was killed by a signal.
+    pub exit_code: Option<i32>,
+    /// The reason for the failure if the post-processor did not exit on its own.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    pub duration_ms: u128,
+}
+
+/// Runs all executables from `postprocess.d` in the config folder in the alphabetical order, then the hooks
+/// from config.json and then the hooks from `.stackmuncher.toml` of the project, if allowed. Each gets the path to
+/// `report_file` as the only argument and `HookInput` as JSON on stdin. Failures are logged and recorded
+/// in the run log, but do not fail the analysis.
+pub(crate) async fn run(
+    config: &AppConfig,
+    report_dir: &Path,
+    report_file: &Path,
+    summary: &MunchSummary,
+    project_hooks: &[String],
+) {
+    let mut postprocessors = list_postprocessors(&config.config_file_path.with_file_name(POSTPROCESS_DIR_NAME));
+
+    let config_dir = config.config_file_path.parent().unwrap_or_else(|| Path::new("."));
+    postprocessors.extend(config.hooks.commands.iter().map(|hook| config_dir.join(hook)));
+
+    if config.hooks.allow_project_hooks {
+        postprocessors.extend(
+            project_hooks
+                .iter()
+                .map(|hook| config.lib_config.project_dir.join(hook)),
+        );
+    } else if !project_hooks.is_empty() {
+        println!(
+            "    Project hooks:       {} in {} skipped, set `hooks.allow_project_hooks` in config.json to run them",
+            project_hooks.len(),
+            PROJECT_CONFIG_FILE_NAME
+        );
+    }
+
+    if postprocessors.is_empty() {
+        debug!("No post-processors found");
+        return;
+    }
+
+    let outcome = match summary.outcome {
+        MunchOutcome::Complete => "complete",
+        MunchOutcome::Partial => "partial",
+    };
+    let loc = match Report::from_disk(report_file) {
+        Some(report) => report
+            .get_overview()
+            .tech
+            .into_iter()
+            .map(|tech| (tech.language, tech.loc))
+            .collect(),
+        None => BTreeMap::new(),
+    };
+    let input = HookInput {
+        report: report_file,
+        reports_dir: report_dir,
+        project_dir: &config.lib_config.project_dir,
+        outcome,
+        dryrun: config.dryrun,
+        submission_failed: summary.submission_failed,
+        warnings: &summary.warnings,
+        loc,
+        version: env!("CARGO_PKG_VERSION"),
+    };
+
+    let mut results: Vec<PostprocessorResult> = Vec::new();
+    for postprocessor in postprocessors {
+        let result = run_postprocessor(&postprocessor, config, &input).await;
+        match (&result.exit_code, &result.error) {
+            (Some(0), _) => println!("    Post-processor:      {} OK", result.name),
+            (Some(code), _) => println!("    Post-processor:      {} failed with exit code {}", result.name, code),
+            (None, Some(e)) => println!("    Post-processor:      {} failed: {}", result.name, e),
+            (None, None) => println!("    Post-processor:      {} failed", result.name),
+        }
+        results.push(result);
+    }
+
+    if !config.hooks.run_logs {
+        debug!("Run logs are off");
+        return;
+    }
+    let reports_dir = config.reports_dir.as_deref().unwrap_or(report_dir);
+    save_run_log_entry(
+        &run_log_file(reports_dir, &config.lib_config.project_dir),
+        RunLogEntry {
+            timestamp: chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Secs, true),
+            outcome: outcome.to_owned(),
+            report: report_file.to_path_buf(),
+            postprocessors: results,
+        },
+    );
+}
+
+/// Returns the paths of the files in `postprocess_dir` sorted by name. Hidden files, folders and, on Unix,
+/// files without the executable bit are skipped. Returns an empty list if the folder does not exist.
+fn list_postprocessors(postprocess_dir: &Path) -> Vec<PathBuf> {
+    let dir_entries = match std::fs::read_dir(postprocess_dir) {
+        Ok(v) => v,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Vec::new(),
+        Err(e) => {
+            error!("Cannot read {} due to {}", postprocess_dir.to_string_lossy(), e);
+            return Vec::new();
+        }
+    };
+
+    let mut postprocessors = dir_entries
+        .filter_map(|dir_entry| dir_entry.ok())
+        .map(|dir_entry| dir_entry.path())
+        .filter(|path| {
+            // editors and the OS leave all sorts of hidden files behind
+            !path
+                .file_name()
+                .map(|v| v.to_string_lossy().starts_with('.'))
+                .unwrap_or(true)
+        })
+        .filter(|path| match std::fs::metadata(path) {
+            Ok(metadata) if metadata.is_file() => {
+                if is_executable(&metadata) {
+                    true
+                } else {
+                    warn!("Post-processor {} is not executable", path.to_string_lossy());
+                    false
+                }
+            }
+            _ => false,
+        })
+        .collect::<Vec<PathBuf>>();
+    postprocessors.sort();
+
+    postprocessors
+}
+
+#[cfg(unix)]
+fn is_executable(metadata: &std::fs::Metadata) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    metadata.permissions().mode() & 0o111 != 0
+}
+
+#[cfg(not(unix))]
+fn is_executable(_metadata: &std::fs::Metadata) -> bool {
+    true
+}
+
+/// Runs a single post-processor with `input` on stdin and waits for it to exit or time out. Its output is only logged.
+async fn run_postprocessor(postprocessor: &Path, config: &AppConfig, input: &HookInput<'_>) -> PostprocessorResult {
+    let name = postprocessor
+        .file_name()
+        .map(|v| v.to_string_lossy().to_string())
+        .unwrap_or_default();
+    info!("Running post-processor {}", postprocessor.to_string_lossy());
+
+    let mut cmd = Command::new(postprocessor);
+    cmd.arg(input.report)
+        .current_dir(input.reports_dir)
+        .env("STACKMUNCHER_REPORT", input.report)
+        .env("STACKMUNCHER_REPORTS_DIR", input.reports_dir)
+        .env("STACKMUNCHER_PROJECT_DIR", input.project_dir)
+        .env("STACKMUNCHER_OUTCOME", input.outcome)
+        .env("STACKMUNCHER_DRYRUN", if input.dryrun { "1" } else { "0" })
+        .env("STACKMUNCHER_VERSION", input.version)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true);
+
+    let stdin_contents = serde_json::to_vec(input).unwrap_or_default();
+    let timeout = Duration::from_secs(config.hooks.timeout_secs);
+
+    let instant = Instant::now();
+    let (exit_code, error) = match tokio::time::timeout(timeout, spawn_with_stdin(cmd, &stdin_contents)).await {
+        Ok(Ok(output)) => {
+            debug!(
+                "Post-processor {} exited with {}. Stdout: {}. Stderr: {}",
+                name,
+                output.status,
+                String::from_utf8_lossy(&output.stdout),
+                String::from_utf8_lossy(&output.stderr)
+            );
+            match output.status.code() {
+                Some(code) => (Some(code), None),
+                None => (None, Some(format!("terminated with {}", output.status))),
+            }
+        }
+        Ok(Err(e)) => {
+            error!("Cannot run post-processor {} due to {}", postprocessor.to_string_lossy(), e);
+            (None, Some(e.to_string()))
+        }
+        Err(_) => {
+            error!("Post-processor {} timed out", postprocessor.to_string_lossy());
+            (None, Some(format!("timed out after {}s", timeout.as_secs())))
+        }
+    };
+
+    PostprocessorResult {
+        name,
+        exit_code,
+        error,
+        duration_ms: instant.elapsed().as_millis(),
+    }
+}
+
+/// Starts `cmd`, writes `stdin_contents` into its stdin and waits for it to exit.
+/// Post-processors that do not read their stdin are not treated as failed.
+async fn spawn_with_stdin(mut cmd: Command, stdin_contents: &[u8]) -> Result<std::process::Output, std::io::Error> {
+    let mut child = cmd.spawn()?;
+    if let Some(mut stdin) = child.stdin.take() {
+        if let Err(e) = stdin.write_all(stdin_contents).await {
+            debug!("Cannot write to the post-processor stdin due to {}", e);
+        }
+        // the post-processor gets EOF when the pipe is closed
+        drop(stdin);
+    }
+
+    child.wait_with_output().await
+}
+
+/// Returns the path to the run log of the project for this host, e.g. `[reports]/run_logs/laptop/[project].log`.
+fn run_log_file(reports_dir: &Path, project_dir: &Path) -> PathBuf {
+    let project_report_dir_name = config::project_report_dir_name(&project_dir.to_path_buf());
+    reports_dir
+        .join(RUN_LOGS_DIR_NAME)
+        .join(host_name())
+        .join([project_report_dir_name.as_str(), RUN_LOG_FILE_EXTENSION].concat())
+}
+
+/// Appends the entry to the run log, creating the folders for it if needed. Failures are logged and ignored.
+fn save_run_log_entry(run_log_file: &Path, entry: RunLogEntry) {
+    if let Some(run_log_dir) = run_log_file.parent() {
+        if let Err(e) = std::fs::create_dir_all(run_log_dir) {
+            error!("Cannot create {} due to {}", run_log_dir.to_string_lossy(), e);
+            return;
+        }
+    }
+    let mut line = match serde_json::to_string(&entry) {
+        Ok(v) => v,
+        Err(e) => {
+            error!("Cannot serialize run log entry due to {}. It's a bug.", e);
+            return;
+        }
+    };
+    line.push('\n');
+
+    match OpenOptions::new().create(true).append(true).open(run_log_file) {
+        Ok(mut file) => match file.write_all(line.as_bytes()) {
+            Ok(_) => debug!("Run log entry saved in {}", run_log_file.to_string_lossy()),
+            Err(e) => error!("Cannot write to run log {} due to {}", run_log_file.to_string_lossy(), e),
+        },
+        Err(e) => error!("Cannot open run log {} due to {}", run_log_file.to_string_lossy(), e),
+    }
+}
+
+#[cfg(test)]
+mod test_postprocess {
+    use super::{list_postprocessors, run_log_file, save_run_log_entry, Hooks, RunLogEntry, DEFAULT_HOOK_TIMEOUT_SECS};
+    use stackmuncher_lib::utils::host_name;
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_list_postprocessors() {
+        let postprocess_dir = std::env::temp_dir().join(format!("stm_postprocess_test_{}", std::process::id()));
+        std::fs::create_dir_all(postprocess_dir.join("subfolder")).unwrap();
+        for name in ["20-notify", "10-export", ".10-export.swp"].iter() {
+            std::fs::write(postprocess_dir.join(name), "#!/bin/sh\n").unwrap();
+        }
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            for name in ["20-notify", "10-export"].iter() {
+                let file_name = postprocess_dir.join(name);
+                std::fs::set_permissions(&file_name, std::fs::Permissions::from_mode(0o755)).unwrap();
+            }
+            std::fs::write(postprocess_dir.join("readme.txt"), "not executable").unwrap();
+        }
+
+        let postprocessors = list_postprocessors(&postprocess_dir)
+            .iter()
+            .map(|v| v.file_name().unwrap().to_string_lossy().to_string())
+            .collect::<Vec<String>>();
+        assert_eq!(postprocessors, vec!["10-export", "20-notify"]);
+
+        assert!(list_postprocessors(&postprocess_dir.join("missing")).is_empty());
+
+        std::fs::remove_dir_all(&postprocess_dir).unwrap();
+    }
+
+    #[test]
+    fn test_hooks_defaults() {
+        // config.json files saved before `hooks` was added have no such section or only some of its fields
+        let hooks: Hooks = serde_json::from_str(r#"{"commands":["notify.sh"]}"#).unwrap();
+        assert_eq!(hooks.commands.len(), 1);
+        assert_eq!(hooks.timeout_secs, DEFAULT_HOOK_TIMEOUT_SECS);
+        assert!(!hooks.allow_project_hooks);
+        assert!(hooks.run_logs);
+    }
+
+    #[test]
+    fn test_save_run_log_entry() {
+        let reports_dir = std::env::temp_dir().join(format!("stm_run_log_test_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&reports_dir);
+
+        // every host appends to its own log, outside of the project reports folder
+        let log_file = run_log_file(&reports_dir, &PathBuf::from("/home/dev/stm"));
+        assert!(log_file.starts_with(reports_dir.join("run_logs").join(host_name())));
+        for outcome in ["complete", "partial"] {
+            let entry = RunLogEntry {
+                timestamp: "2021-10-01T00:00:00Z".to_owned(),
+                outcome: outcome.to_owned(),
+                report: PathBuf::from("project_report.json"),
+                postprocessors: Vec::new(),
+            };
+            save_run_log_entry(&log_file, entry);
+        }
+        let outcomes = std::fs::read_to_string(&log_file)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str::<RunLogEntry>(line).unwrap().outcome)
+            .collect::<Vec<String>>();
+        assert_eq!(outcomes, vec!["complete", "partial"]);
+
+        let _ = std::fs::remove_dir_all(&reports_dir);
+    }
+}
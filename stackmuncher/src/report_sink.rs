@@ -0,0 +1,213 @@
+#[cfg(feature = "cloud")]
+use crate::api_client::ApiRequest;
+use crate::app_args::is_https_url;
+use async_trait::async_trait;
+use stackmuncher_lib::utils::write_atomically;
+use std::path::{Path, PathBuf};
+#[cfg(feature = "cloud")]
+use tracing::debug;
+use tracing::info;
+
+/// An optional bearer token for HTTP PUT sinks, e.g. an Artifactory or Nexus API token.
+#[cfg(feature = "cloud")]
+const SINK_TOKEN_ENV_VAR: &str = "STACKMUNCHER_SINK_TOKEN";
+
+/// A place where reports are saved in addition to the local reports folder, e.g. a bucket shared by CI pipelines.
+/// Set with `--report-sink`.
+#[async_trait]
+pub(crate) trait ReportSink: Send + Sync {
+    /// Saves `contents` as `name` relative to the sink location, e.g. `tmp_stm_64f98583/project_report.json`.
+    /// Errors are printed for the user.
+    async fn put(&self, name: &str, contents: Vec<u8>) -> Result<(), ()>;
+
+    /// The location for user messages, e.g. `s3://bucket/prefix`. It must not contain any credentials.
+    fn location(&self) -> String;
+}
+
+/// Saves reports in a local folder, e.g. a mounted network share or a CI cache folder.
+pub(crate) struct LocalSink {
+    dir: PathBuf,
+}
+
+#[async_trait]
+impl ReportSink for LocalSink {
+    async fn put(&self, name: &str, contents: Vec<u8>) -> Result<(), ()> {
+        let file_name = self.dir.join(name);
+        if let Some(parent) = file_name.parent() {
+            if let Err(e) = std::fs::create_dir_all(parent) {
+                eprintln!(
+                    "STACKMUNCHER ERROR: cannot create report sink folder {} due to {}",
+                    parent.to_string_lossy(),
+                    e
+                );
+                return Err(());
+            }
+        }
+
+        write_atomically(&file_name, &contents).map_err(|e| {
+            eprintln!("STACKMUNCHER ERROR: cannot save {} due to {}", file_name.to_string_lossy(), e);
+        })
+    }
+
+    fn location(&self) -> String {
+        self.dir.to_string_lossy().to_string()
+    }
+}
+
+/// Uploads reports with HTTP PUT to `base_url/name`, e.g. to a generic artifact repository.
+/// Sends `STACKMUNCHER_SINK_TOKEN` env var as a bearer token, if set.
+#[cfg(feature = "cloud")]
+pub(crate) struct HttpSink {
+    base_url: String,
+}
+
+#[cfg(feature = "cloud")]
+#[async_trait]
+impl ReportSink for HttpSink {
+    async fn put(&self, name: &str, contents: Vec<u8>) -> Result<(), ()> {
+        let url = [self.base_url.trim_end_matches('/'), "/", name].concat();
+        let mut request = ApiRequest::put(&url, contents).header("Content-Type", "application/json");
+        if let Ok(token) = std::env::var(SINK_TOKEN_ENV_VAR) {
+            request = request.header("Authorization", &["Bearer ", &token].concat());
+        }
+
+        match request.send().await {
+            Ok(response) => {
+                debug!("PUT {}: {}", url, response.status);
+                Ok(())
+            }
+            Err(e) => {
+                eprintln!("STACKMUNCHER ERROR: cannot upload {} due to {}", url, e);
+                Err(())
+            }
+        }
+    }
+
+    fn location(&self) -> String {
+        self.base_url.clone()
+    }
+}
+
+/// Returns an error message if the location is not a valid value for `--report-sink`:
+/// * `s3://bucket/prefix` for an S3 bucket
+/// * `https://example.com/reports` for HTTP PUT
+/// * a local folder, with or without `file://`
+pub(crate) fn validate_location(location: &str) -> Result<(), String> {
+    if let Some(bucket_and_prefix) = location.strip_prefix("s3://") {
+        if bucket_and_prefix.split('/').next().unwrap_or_default().is_empty() {
+            return Err("the bucket name is missing, e.g. `s3://bucket/prefix`".to_owned());
+        }
+        if cfg!(not(feature = "s3")) {
+            return Err("this build of stackmuncher has no S3 support, rebuild it with `--features s3`".to_owned());
+        }
+        Ok(())
+    } else if location.starts_with("https://") {
+        if !is_https_url(location) {
+            return Err("it is not a valid HTTPS URL".to_owned());
+        }
+        if cfg!(not(feature = "cloud")) {
+            return Err(
+                "this build of stackmuncher has no HTTPS support, rebuild it with `--features cloud`".to_owned()
+            );
+        }
+        Ok(())
+    } else if location.contains("://") && !location.starts_with("file://") {
+        Err("only s3://, https:// and file:// locations are supported".to_owned())
+    } else if location.trim_start_matches("file://").is_empty() {
+        Err("the folder name is missing".to_owned())
+    } else {
+        Ok(())
+    }
+}
+
+/// Returns the sink for a location validated by `validate_location`. S3 credentials are taken from the environment.
+pub(crate) fn new_sink(location: &str) -> Result<Box<dyn ReportSink>, ()> {
+    #[cfg(feature = "s3")]
+    if let Some(bucket_and_prefix) = location.strip_prefix("s3://") {
+        return Ok(Box::new(crate::report_sink_s3::S3Sink::from_env(bucket_and_prefix)?));
+    }
+
+    #[cfg(feature = "cloud")]
+    if location.starts_with("https://") {
+        return Ok(Box::new(HttpSink {
+            base_url: location.to_owned(),
+        }));
+    }
+
+    Ok(Box::new(LocalSink {
+        dir: PathBuf::from(location.trim_start_matches("file://")),
+    }))
+}
+
+/// Saves the report files in the sink under the name of the project reports folder, e.g.
+/// `s3://bucket/prefix/tmp_stm_64f98583/project_report.json`, and prints the outcome.
+pub(crate) async fn save_reports(location: &str, report_dir: &Path, report_files: &[&Path]) -> Result<(), ()> {
+    let sink = new_sink(location)?;
+    let project_dir_name = report_dir
+        .file_name()
+        .expect("Cannot get the name of the project reports folder. It's a bug.")
+        .to_string_lossy()
+        .to_string();
+
+    for report_file in report_files {
+        let contents = match std::fs::read(report_file) {
+            Ok(v) => v,
+            Err(e) => {
+                eprintln!("STACKMUNCHER ERROR: cannot read {} due to {}", report_file.to_string_lossy(), e);
+                return Err(());
+            }
+        };
+        let file_name = report_file
+            .file_name()
+            .expect("Cannot get the name of the report file. It's a bug.")
+            .to_string_lossy();
+        sink.put(&[project_dir_name.as_str(), "/", &file_name].concat(), contents)
+            .await?;
+        info!("{} saved in {}", file_name, sink.location());
+    }
+
+    println!(
+        "    Report sink:         {} report(s) saved in {}/{}",
+        report_files.len(),
+        sink.location().trim_end_matches('/'),
+        project_dir_name
+    );
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test_report_sink {
+    use super::{new_sink, validate_location};
+
+    #[test]
+    fn test_validate_location() {
+        assert!(validate_location("/mnt/reports").is_ok());
+        assert!(validate_location("file:///mnt/reports").is_ok());
+        assert!(validate_location("reports").is_ok());
+
+        assert!(validate_location("s3:///prefix").is_err());
+        assert!(validate_location("http://example.com/stm").is_err());
+        assert!(validate_location("gs://bucket/prefix").is_err());
+        assert!(validate_location("file://").is_err());
+
+        #[cfg(feature = "s3")]
+        assert!(validate_location("s3://bucket/prefix").is_ok());
+        #[cfg(not(feature = "s3"))]
+        assert!(validate_location("s3://bucket/prefix").is_err());
+        #[cfg(feature = "cloud")]
+        assert!(validate_location("https://artifacts.example.com/stm").is_ok());
+        #[cfg(not(feature = "cloud"))]
+        assert!(validate_location("https://artifacts.example.com/stm").is_err());
+    }
+
+    #[test]
+    fn test_new_sink() {
+        assert_eq!(new_sink("file:///mnt/reports").unwrap().location(), "/mnt/reports");
+        #[cfg(feature = "cloud")]
+        assert_eq!(
+            new_sink("https://artifacts.example.com/stm/").unwrap().location(),
+            "https://artifacts.example.com/stm/"
+        );
+    }
+}
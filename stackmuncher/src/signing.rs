@@ -81,27 +81,45 @@ pub(crate) fn get_key_pair(keys_dir: &PathBuf) -> Ed25519KeyPair {
         }
     };
 
-    // decode the bs58-encoded key
-    let pkcs8_bytes = match bs58::decode(pkcs8_bytes).into_vec() {
+    match decode_key_pair(&pkcs8_bytes) {
         Ok(v) => v,
         Err(e) => {
-            error!("Failed to decode {} from base58 due to {}", key_file_path_str, e);
+            error!("Invalid key file {}: {}", key_file_path_str, e);
             help::emit_key_err_msg(&key_file_path_str);
             exit(1);
         }
-    };
+    }
+}
 
-    // extract the key pair from the contents of the key file
-    let key_pair = match signature::Ed25519KeyPair::from_pkcs8(pkcs8_bytes.as_ref()) {
-        Err(e) => {
-            warn!("Invalid key-pair in {} due to {}", key_file_path_str, e);
-            help::emit_key_err_msg(&key_file_path_str);
-            exit(1);
-        }
-        Ok(v) => v,
-    };
+/// Reads an existing key-pair from the disk without generating a new one if it is missing or invalid.
+/// Returns the reason as an error.
+pub(crate) fn read_key_pair(keys_dir: &PathBuf) -> Result<Ed25519KeyPair, String> {
+    let key_file_path = get_key_file_name(keys_dir);
+    let contents = std::fs::read(&key_file_path).map_err(|e| format!("cannot read the key file due to {}", e))?;
+
+    decode_key_pair(&contents)
+}
+
+/// Generates a key-pair that is not saved anywhere, e.g. for commands that only need a placeholder.
+/// Panics on unrecoverable errors.
+pub(crate) fn new_ephemeral_key_pair() -> Ed25519KeyPair {
+    let rng = rand::SystemRandom::new();
+    signature::Ed25519KeyPair::generate_pkcs8(&rng)
+        .ok()
+        .and_then(|pkcs8| signature::Ed25519KeyPair::from_pkcs8(pkcs8.as_ref()).ok())
+        .expect("Failed to generate a PKCS8 key. It's a bug.")
+}
 
-    key_pair
+/// Extracts the key pair from the base58-encoded contents of a key file.
+/// Returns the reason as an error if the contents are not a valid key.
+pub(crate) fn decode_key_pair(key_file_contents: &[u8]) -> Result<Ed25519KeyPair, String> {
+    // decode the bs58-encoded key
+    let pkcs8_bytes = bs58::decode(key_file_contents)
+        .into_vec()
+        .map_err(|e| format!("failed to decode from base58 due to {}", e))?;
+
+    // extract the key pair from the contents of the key file
+    signature::Ed25519KeyPair::from_pkcs8(pkcs8_bytes.as_ref()).map_err(|e| format!("invalid key-pair due to {}", e))
 }
 
 /// Generates a new PKCS8 file and saves it in a common location with the hash as its name for future retrieval.
@@ -137,7 +155,7 @@ fn generate_and_save_new_pkcs8(key_file_name: &PathBuf) -> Vec<u8> {
 }
 
 /// Returns the name of the key file for the normalized_email_hash for consistency.
-fn get_key_file_name(keys_dir: &PathBuf) -> PathBuf {
+pub(crate) fn get_key_file_name(keys_dir: &PathBuf) -> PathBuf {
     // check if the keys directory exists
     if !keys_dir.is_dir() {
         eprintln!("STACKMUNCHER ERROR: config folder {} not found.", keys_dir.to_string_lossy());
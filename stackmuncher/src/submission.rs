@@ -1,111 +1,91 @@
+use crate::api_client::{ApiError, ApiRequest};
+use crate::cmd_audit::AuditEntry;
+use crate::cmd_status::save_receipt;
 use crate::help;
 use crate::signing::ReportSignature;
 use crate::AppConfig;
-use hyper::{Client, Request};
-use hyper_rustls::HttpsConnectorBuilder;
 use stackmuncher_lib::report::Report;
 use tracing::{debug, info, warn};
 
 //const STM_REPORT_SUBMISSION_URL: &str = "https://emvu2i81ec.execute-api.us-east-1.amazonaws.com";
-const STM_REPORT_SUBMISSION_URL: &str = "https://inbox.stackmuncher.com";
-const HEADER_USER_PUB_KEY: &str = "stackmuncher_key";
-const HEADER_USER_SIGNATURE: &str = "stackmuncher_sig";
+/// The default submission endpoint. It can be changed with `--inbox-url` for self-hosted deployments.
+pub(crate) const STM_REPORT_SUBMISSION_URL: &str = "https://inbox.stackmuncher.com";
 
 /// Submits the serialized report to STM or some other web service. Includes signing.
+/// Network errors and 5xx responses are retried a few times before giving up.
+/// The outcome of the submission is recorded in the audit log and in the receipts for `status` command.
+/// Returns an error if the report was not accepted, including any non-2xx response. The details are printed.
 /// May panic if the signing fails (missing keys, can't access keystore).
-pub(crate) async fn submit_report(report: Report, config: &AppConfig) {
+pub(crate) async fn submit_report(report: Report, config: &AppConfig) -> Result<(), ()> {
+    let endpoint = config.inbox_url.as_deref().unwrap_or(STM_REPORT_SUBMISSION_URL);
+    let audit_entry = AuditEntry::new(&report, endpoint);
+
     // compress the report
     let report = match report.gzip() {
         Ok(v) => v,
         Err(_) => {
             eprintln!("STACKMUNCHER: no report was submitted.");
-            return;
+            return Err(());
         }
     };
 
-    // sign the report
-    let report_sig = ReportSignature::sign(&report, &config.user_key_pair);
-
-    // prepare HTTP request which should go without a hitch unless the report or one of the headers is somehow invalid
-    let req = Request::builder()
-        .method("POST")
-        .uri(STM_REPORT_SUBMISSION_URL)
-        .header(HEADER_USER_PUB_KEY, report_sig.public_key.clone())
-        .header(HEADER_USER_SIGNATURE, report_sig.signature.clone())
-        .body(hyper::Body::from(report))
-        .expect("Invalid report submission payload. It's a bug.");
-
-    debug!("Http rq: {:?}", req);
-
-    // send out the request
-    info!("Sending request to INBOX for {}", report_sig.public_key.clone());
-    let res = match Client::builder()
-        .build::<_, hyper::Body>(
-            HttpsConnectorBuilder::new()
-                .with_native_roots()
-                .https_only()
-                .enable_http1()
-                .build(),
-        )
-        .request(req)
+    let public_key = ReportSignature::get_public_key(&config.user_key_pair);
+    info!("Sending request to INBOX for {}", public_key);
+    // the inbox drops a repeat of the same report if the first attempt went through, but the response was lost
+    let response = match ApiRequest::post(endpoint, report)
+        .idempotency_key(&audit_entry.payload_sha256)
+        .signed(&config.user_key_pair)
+        .send()
         .await
     {
+        Ok(v) => v,
+        Err(ApiError::Status(response)) => {
+            save_receipt(config, &audit_entry, &response.summary(), false).await;
+            audit_entry.save(config, response.summary());
+            warn!("StackMuncher report submission failed with {}", response.status);
+            if let Some(body) = response.body_text() {
+                warn!("StackMuncher server response: {}", body);
+                eprintln!("{}", body);
+            }
+            return Err(());
+        }
         Err(e) => {
+            save_receipt(config, &audit_entry, &e.to_string(), false).await;
+            audit_entry.save(config, e.to_string());
             warn!("StackMuncher report submission failed due to: {}.", e);
             eprintln!("Sending the stack report to stackmuncher.com failed. It may go through with the next commit.");
             help::emit_detailed_output_msg();
-            return;
+            return Err(());
         }
-        Ok(v) => v,
     };
 
-    let status = res.status();
-    info!("stm_inbox response arrived, status: {}", status,);
-
-    // Concatenate the body stream into a single buffer...
-    let buf = match hyper::body::to_bytes(res).await {
-        Err(e) => {
-            warn!("Failed to convert StackMuncher report to bytes due to: {}. It's a bug", e);
-            eprintln!("Failed to convert StackMuncher report to bytes due to: {}. It's a bug", e);
-            help::emit_detailed_output_msg();
-            return;
-        }
-        Ok(v) => v,
-    };
+    info!("stm_inbox response arrived, status: {}", response.status);
+    save_receipt(config, &audit_entry, &response.summary(), response.status.is_success()).await;
+    audit_entry.save(config, response.summary());
 
     // a 200 OK body can be empty if everything is OK
-    if status.as_u16() == 200 && buf.is_empty() {
+    if response.status.as_u16() == 200 && response.body.is_empty() {
         debug!("Empty response body, 200 OK");
 
         // public profile is preferred, but not be enabled
         if let Some(gh_login) = &config.gh_login {
             println!("    Project added to:    https://stackmuncher.com/{}", gh_login);
         } else {
-            println!("    Project added to:    https://stackmuncher.com/?dev={}", report_sig.public_key);
+            println!("    Project added to:    https://stackmuncher.com/?dev={}", public_key);
         }
 
-        return;
+        return Ok(());
     }
 
-    if !buf.is_empty() {
-        log_http_body(&buf);
+    if let Some(body) = response.body_text() {
+        warn!("StackMuncher server response: {}", body);
+        eprintln!("{}", body);
     }
-}
 
-/// Logs the body as warn!() and prints out for the user, if possible.
-fn log_http_body(body_bytes: &hyper::body::Bytes) {
-    // log the body as-is if it's not too long
-    if body_bytes.len() < 5000 {
-        let s = match std::str::from_utf8(&body_bytes).to_owned() {
-            Err(_e) => "The body is not UTF-8".to_string(),
-            Ok(v) => v.to_string(),
-        };
-        warn!("StackMuncher server response: {}", s);
-        eprintln!("{}", s);
+    // a 2xx response with a message is a warning from the server, anything else is a rejection
+    if response.status.is_success() {
+        Ok(())
     } else {
-        warn!(
-            "StackMuncher server response is too long to log: {}B. Something's broken at their end.",
-            body_bytes.len()
-        );
+        Err(())
     }
 }
@@ -0,0 +1,674 @@
+use crate::api_client::{ApiError, ApiRequest};
+use crate::cmd_audit::AuditEntry;
+use crate::config::AppConfig;
+use ring::digest::{digest, SHA256, SHA384, SHA512};
+use ring::rand::{SecureRandom, SystemRandom};
+use ring::signature::{self, UnparsedPublicKey, VerificationAlgorithm};
+use stackmuncher_lib::utils::write_atomically;
+use std::path::{Path, PathBuf};
+use tracing::{debug, info, warn};
+
+/// The token is saved next to the report with this extension added to the report file name,
+/// e.g. `combined_report.json.tsr`. It is the full RFC3161 response as returned by the TSA.
+const TOKEN_FILE_EXTENSION: &str = ".tsr";
+const CONTENT_TYPE_QUERY: &str = "application/timestamp-query";
+
+/// DER tags used in timestamp requests and tokens.
+const TAG_BOOLEAN: u8 = 0x01;
+const TAG_INTEGER: u8 = 0x02;
+const TAG_BIT_STRING: u8 = 0x03;
+const TAG_OCTET_STRING: u8 = 0x04;
+const TAG_NULL: u8 = 0x05;
+const TAG_OID: u8 = 0x06;
+const TAG_GENERALIZED_TIME: u8 = 0x18;
+const TAG_SEQUENCE: u8 = 0x30;
+const TAG_SET: u8 = 0x31;
+const TAG_CONTEXT_0: u8 = 0xa0;
+
+/// DER-encoded values of the OIDs, without the tag and the length.
+const OID_SHA256: &[u8] = &[0x60, 0x86, 0x48, 0x01, 0x65, 0x03, 0x04, 0x02, 0x01];
+const OID_SHA384: &[u8] = &[0x60, 0x86, 0x48, 0x01, 0x65, 0x03, 0x04, 0x02, 0x02];
+const OID_SHA512: &[u8] = &[0x60, 0x86, 0x48, 0x01, 0x65, 0x03, 0x04, 0x02, 0x03];
+const OID_SIGNED_DATA: &[u8] = &[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x07, 0x02];
+const OID_TST_INFO: &[u8] = &[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x09, 0x10, 0x01, 0x04];
+const OID_MESSAGE_DIGEST: &[u8] = &[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x09, 0x04];
+const OID_RSA: &[u8] = &[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x01, 0x01];
+const OID_EC_PUBLIC_KEY: &[u8] = &[0x2a, 0x86, 0x48, 0xce, 0x3d, 0x02, 0x01];
+const OID_P256: &[u8] = &[0x2a, 0x86, 0x48, 0xce, 0x3d, 0x03, 0x01, 0x07];
+const OID_P384: &[u8] = &[0x2b, 0x81, 0x04, 0x00, 0x22];
+const OID_COMMON_NAME: &[u8] = &[0x55, 0x04, 0x03];
+
+/// The details of a timestamp token that matches the report.
+#[derive(Debug, PartialEq)]
+pub(crate) struct TimestampInfo {
+    /// The time the TSA issued the token in RFC3339 format, UTC, e.g. `2021-05-01T10:20:30Z`.
+    pub gen_time: String,
+    /// The serial number of the token assigned by the TSA, hex-encoded.
+    pub serial_number: String,
+    /// The common name of the TSA certificate that signed the token, if it has one.
+    pub tsa_name: Option<String>,
+}
+
+/// Returns the name of the token file for the report, e.g. `combined_report.json` -> `combined_report.json.tsr`.
+pub(crate) fn token_file_name(report_file: &Path) -> PathBuf {
+    let mut token_file = report_file.as_os_str().to_owned();
+    token_file.push(TOKEN_FILE_EXTENSION);
+    PathBuf::from(token_file)
+}
+
+/// Sends the SHA256 hash of the report file to the TSA set with `--timestamp-url` and saves the token next to the report.
+/// Nothing else about the report leaves the machine. Does nothing if timestamping is off.
+/// Failures are logged and printed, but do not stop the analysis.
+pub(crate) async fn timestamp_report(config: &AppConfig, report_file: &Path, report_id: &str) {
+    let tsa_url = match &config.timestamp_url {
+        Some(v) => v,
+        None => {
+            debug!("Timestamping is off");
+            return;
+        }
+    };
+
+    let report = match std::fs::read(report_file) {
+        Ok(v) => v,
+        Err(e) => {
+            warn!("Cannot read {} for timestamping due to {}", report_file.to_string_lossy(), e);
+            println!("    Timestamp:           failed, the report cannot be read");
+            return;
+        }
+    };
+
+    // a positive integer with no leading zeros, as required by DER
+    let mut nonce = [0u8; 8];
+    if SystemRandom::new().fill(&mut nonce).is_err() {
+        warn!("Cannot generate a nonce for the timestamp request");
+        println!("    Timestamp:           failed, see the log for details");
+        return;
+    }
+    nonce[0] = (nonce[0] & 0x7f) | 0x40;
+
+    let request = timestamp_request(digest(&SHA256, &report).as_ref(), &nonce);
+    let audit_entry = AuditEntry {
+        timestamp: chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Secs, true),
+        endpoint: tsa_url.clone(),
+        payload_sha256: to_hex(digest(&SHA256, &request).as_ref()),
+        payload_bytes: request.len(),
+        data_categories: vec!["report_sha256".to_owned()],
+        report_id: report_id.to_owned(),
+        response: String::new(),
+    };
+
+    info!("Requesting a timestamp from {}", tsa_url);
+    let response = match ApiRequest::post(tsa_url, request)
+        .header("Content-Type", CONTENT_TYPE_QUERY)
+        .send()
+        .await
+    {
+        Ok(v) => v,
+        Err(ApiError::Status(response)) => {
+            audit_entry.save(config, format!("{}, {} bytes", response.status, response.body.len()));
+            warn!("The TSA responded with {}", response.summary());
+            println!("    Timestamp:           failed, {} responded with {}", tsa_url, response.status);
+            return;
+        }
+        Err(e) => {
+            audit_entry.save(config, e.to_string());
+            warn!("Timestamp request to {} failed due to {}", tsa_url, e);
+            println!("    Timestamp:           failed, {} is unreachable", tsa_url);
+            return;
+        }
+    };
+    audit_entry.save(config, format!("{}, {} bytes", response.status, response.body.len()));
+    let response = response.body;
+
+    // a bad response must not replace a valid token from an earlier run
+    let timestamp = match verify_token(&response, &report, Some(&nonce)) {
+        Ok(v) => v,
+        Err(e) => {
+            warn!("Invalid timestamp response from {}: {}", tsa_url, e);
+            println!("    Timestamp:           failed, {}", e);
+            return;
+        }
+    };
+
+    let token_file = token_file_name(report_file);
+    if let Err(e) = write_atomically(&token_file, &response) {
+        warn!("Cannot save the timestamp token in {} due to {}", token_file.to_string_lossy(), e);
+        println!("    Timestamp:           failed, the token cannot be saved");
+        return;
+    }
+
+    println!(
+        "    Timestamp:           {} by {}",
+        timestamp.gen_time,
+        timestamp.tsa_name.as_deref().unwrap_or(tsa_url)
+    );
+}
+
+/// Returns a DER-encoded RFC3161 TimeStampReq for the SHA256 hash of the report. The TSA is asked to include
+/// its certificate in the token so that it can be verified later without going online.
+fn timestamp_request(report_hash: &[u8], nonce: &[u8]) -> Vec<u8> {
+    let hash_algorithm =
+        der_encode(TAG_SEQUENCE, &[der_encode(TAG_OID, OID_SHA256), der_encode(TAG_NULL, &[])].concat());
+    let message_imprint =
+        der_encode(TAG_SEQUENCE, &[hash_algorithm, der_encode(TAG_OCTET_STRING, report_hash)].concat());
+
+    der_encode(
+        TAG_SEQUENCE,
+        &[
+            der_encode(TAG_INTEGER, &[1]),
+            message_imprint,
+            der_encode(TAG_INTEGER, nonce),
+            der_encode(TAG_BOOLEAN, &[0xff]),
+        ]
+        .concat(),
+    )
+}
+
+/// Checks that the RFC3161 response was granted, the token is for the exact contents of the report and is signed
+/// by the certificate included in the token. The nonce is only checked for fresh responses.
+/// The TSA certificate is not checked against any trusted roots.
+pub(crate) fn verify_token(response: &[u8], report: &[u8], nonce: Option<&[u8]>) -> Result<TimestampInfo, String> {
+    let malformed = || "the token is not a valid RFC3161 timestamp".to_owned();
+
+    // TimeStampResp ::= SEQUENCE { status PKIStatusInfo, timeStampToken ContentInfo OPTIONAL }
+    let resp = Der::parse(response)
+        .and_then(|v| v.children(TAG_SEQUENCE))
+        .ok_or_else(malformed)?;
+    let status_info = resp
+        .first()
+        .and_then(|v| v.children(TAG_SEQUENCE))
+        .ok_or_else(malformed)?;
+    match status_info.first().map(|v| (v.tag, v.value)) {
+        // granted or granted with modifications
+        Some((TAG_INTEGER, [0])) | Some((TAG_INTEGER, [1])) => {}
+        Some((TAG_INTEGER, status)) => {
+            return Err(format!("the TSA rejected the request with status {}", to_hex(status)));
+        }
+        _ => return Err(malformed()),
+    }
+
+    // ContentInfo ::= SEQUENCE { contentType OID, content [0] EXPLICIT SignedData }
+    let content_info = resp
+        .get(1)
+        .and_then(|v| v.children(TAG_SEQUENCE))
+        .ok_or_else(malformed)?;
+    if content_info.first().map(|v| v.value) != Some(OID_SIGNED_DATA) {
+        return Err(malformed());
+    }
+    let signed_data = content_info
+        .get(1)
+        .and_then(|v| v.children(TAG_CONTEXT_0))
+        .and_then(|v| v.first()?.children(TAG_SEQUENCE))
+        .ok_or_else(malformed)?;
+
+    // SignedData ::= SEQUENCE { version, digestAlgorithms, encapContentInfo, certificates [0] OPTIONAL,
+    // crls [1] OPTIONAL, signerInfos }
+    let encap_content_info = signed_data
+        .get(2)
+        .and_then(|v| v.children(TAG_SEQUENCE))
+        .ok_or_else(malformed)?;
+    if encap_content_info.first().map(|v| v.value) != Some(OID_TST_INFO) {
+        return Err(malformed());
+    }
+    let tst_info = encap_content_info
+        .get(1)
+        .and_then(|v| v.children(TAG_CONTEXT_0))
+        .and_then(|v| v.first()?.expect(TAG_OCTET_STRING))
+        .ok_or_else(malformed)?
+        .value;
+    let certificates = signed_data
+        .iter()
+        .skip(3)
+        .find(|v| v.tag == TAG_CONTEXT_0)
+        .and_then(|v| v.children(TAG_CONTEXT_0))
+        .unwrap_or_default();
+    let signer_info = signed_data
+        .last()
+        .and_then(|v| v.children(TAG_SET))
+        .and_then(|v| v.first()?.children(TAG_SEQUENCE))
+        .ok_or_else(malformed)?;
+
+    // TSTInfo ::= SEQUENCE { version, policy, messageImprint, serialNumber, genTime, accuracy OPTIONAL,
+    // ordering DEFAULT FALSE, nonce OPTIONAL, ... }
+    let tst_info = Der::parse(tst_info)
+        .and_then(|v| v.children(TAG_SEQUENCE))
+        .ok_or_else(malformed)?;
+    let message_imprint = tst_info
+        .get(2)
+        .and_then(|v| v.children(TAG_SEQUENCE))
+        .ok_or_else(malformed)?;
+    let imprint_algorithm = message_imprint
+        .first()
+        .and_then(|v| v.children(TAG_SEQUENCE))
+        .and_then(|v| Some(v.first()?.value))
+        .ok_or_else(malformed)?;
+    let imprint_algorithm =
+        digest_algorithm(imprint_algorithm).ok_or("the token uses an unsupported hash algorithm")?;
+    let hashed_message = message_imprint
+        .get(1)
+        .and_then(|v| v.expect(TAG_OCTET_STRING))
+        .ok_or_else(malformed)?;
+    if digest(imprint_algorithm, report).as_ref() != hashed_message.value {
+        return Err("the report was changed after it was timestamped".to_owned());
+    }
+    let serial_number = tst_info
+        .get(3)
+        .and_then(|v| v.expect(TAG_INTEGER))
+        .ok_or_else(malformed)?;
+    let gen_time = tst_info
+        .get(4)
+        .and_then(|v| v.expect(TAG_GENERALIZED_TIME))
+        .and_then(|v| to_rfc3339(v.value))
+        .ok_or_else(malformed)?;
+    if let Some(nonce) = nonce {
+        if tst_info.iter().skip(5).find(|v| v.tag == TAG_INTEGER).map(|v| v.value) != Some(nonce) {
+            return Err("the TSA response is for a different request".to_owned());
+        }
+    }
+
+    // SignerInfo ::= SEQUENCE { version, sid, digestAlgorithm, signedAttrs [0] IMPLICIT, signatureAlgorithm,
+    // signature, ... }
+    let signer_digest_algorithm = signer_info
+        .get(2)
+        .and_then(|v| v.children(TAG_SEQUENCE))
+        .and_then(|v| Some(v.first()?.value))
+        .ok_or_else(malformed)?;
+    let signer_digest_algorithm =
+        digest_algorithm(signer_digest_algorithm).ok_or("the token uses an unsupported hash algorithm")?;
+    let signed_attrs = signer_info
+        .get(3)
+        .and_then(|v| v.expect(TAG_CONTEXT_0))
+        .ok_or_else(malformed)?;
+    let signature = signer_info
+        .get(5)
+        .and_then(|v| v.expect(TAG_OCTET_STRING))
+        .ok_or_else(malformed)?;
+
+    // the signature covers the signed attributes and one of them is the digest of TSTInfo
+    let message_digest = signed_attrs
+        .children(TAG_CONTEXT_0)
+        .ok_or_else(malformed)?
+        .into_iter()
+        .filter_map(|v| v.children(TAG_SEQUENCE))
+        .find(|attr| attr.first().map(|v| v.value) == Some(OID_MESSAGE_DIGEST))
+        .and_then(|attr| attr.get(1)?.children(TAG_SET)?.first()?.expect(TAG_OCTET_STRING))
+        .ok_or_else(malformed)?;
+    let tst_info_der = encap_content_info
+        .get(1)
+        .and_then(|v| v.children(TAG_CONTEXT_0))
+        .and_then(|v| Some(v.first()?.value))
+        .ok_or_else(malformed)?;
+    if digest(signer_digest_algorithm, tst_info_der).as_ref() != message_digest.value {
+        return Err("the token contents do not match its signature".to_owned());
+    }
+
+    // the attributes are signed as a SET rather than with the implicit [0] tag they are stored with
+    let mut signed_message = signed_attrs.raw.to_vec();
+    signed_message[0] = TAG_SET;
+
+    if certificates.is_empty() {
+        return Err("the token has no TSA certificate to check the signature with".to_owned());
+    }
+    let signer = certificates
+        .iter()
+        .filter_map(TsaCertificate::parse)
+        .find(|cert| cert.verify(signer_digest_algorithm, &signed_message, signature.value))
+        .ok_or("the token signature does not match any of its certificates")?;
+
+    Ok(TimestampInfo {
+        gen_time,
+        serial_number: to_hex(serial_number.value),
+        tsa_name: signer.common_name,
+    })
+}
+
+/// The parts of an X.509 certificate needed to check the token signature.
+struct TsaCertificate<'a> {
+    /// The OID of the key algorithm from SubjectPublicKeyInfo.
+    key_algorithm: &'a [u8],
+    /// The OID of the curve for EC keys.
+    curve: Option<&'a [u8]>,
+    /// The contents of subjectPublicKey BIT STRING: RSAPublicKey for RSA or the EC point.
+    public_key: &'a [u8],
+    common_name: Option<String>,
+}
+
+impl<'a> TsaCertificate<'a> {
+    /// Returns None if the certificate cannot be parsed.
+    fn parse(cert: &Der<'a>) -> Option<Self> {
+        // Certificate ::= SEQUENCE { tbsCertificate, signatureAlgorithm, signatureValue }
+        let tbs = cert.children(TAG_SEQUENCE)?.first()?.children(TAG_SEQUENCE)?;
+        // TBSCertificate ::= SEQUENCE { version [0] EXPLICIT DEFAULT v1, serialNumber, signature, issuer, validity,
+        // subject, subjectPublicKeyInfo, ... }
+        let offset = if tbs.first()?.tag == TAG_CONTEXT_0 { 1 } else { 0 };
+        let subject = tbs.get(offset + 4)?.children(TAG_SEQUENCE)?;
+        let spki = tbs.get(offset + 5)?.children(TAG_SEQUENCE)?;
+        let algorithm = spki.first()?.children(TAG_SEQUENCE)?;
+        let public_key = match spki.get(1)?.expect(TAG_BIT_STRING)?.value {
+            [0, public_key @ ..] => public_key,
+            _ => return None,
+        };
+
+        // Name ::= SEQUENCE OF SET OF SEQUENCE { type OID, value }
+        let common_name = subject
+            .iter()
+            .filter_map(|rdn| rdn.children(TAG_SET))
+            .flatten()
+            .filter_map(|attr| attr.children(TAG_SEQUENCE))
+            .find(|attr| attr.first().map(|v| v.value) == Some(OID_COMMON_NAME))
+            .and_then(|attr| Some(String::from_utf8_lossy(attr.get(1)?.value).to_string()));
+
+        Some(Self {
+            key_algorithm: algorithm.first()?.expect(TAG_OID)?.value,
+            curve: algorithm.get(1).filter(|v| v.tag == TAG_OID).map(|v| v.value),
+            public_key,
+            common_name,
+        })
+    }
+
+    /// Returns true if the signature was made with the key of this certificate. Only RSA PKCS#1 and ECDSA P-256/P-384
+    /// keys are supported.
+    fn verify(&self, digest_algorithm: &'static ring::digest::Algorithm, message: &[u8], sig: &[u8]) -> bool {
+        let sha = match digest_algorithm.output_len {
+            32 => 256,
+            48 => 384,
+            _ => 512,
+        };
+        let algorithm: &'static dyn VerificationAlgorithm = match (self.key_algorithm, self.curve, sha) {
+            (OID_RSA, _, 256) => &signature::RSA_PKCS1_2048_8192_SHA256,
+            (OID_RSA, _, 384) => &signature::RSA_PKCS1_2048_8192_SHA384,
+            (OID_RSA, _, _) => &signature::RSA_PKCS1_2048_8192_SHA512,
+            (OID_EC_PUBLIC_KEY, Some(OID_P256), 256) => &signature::ECDSA_P256_SHA256_ASN1,
+            (OID_EC_PUBLIC_KEY, Some(OID_P256), 384) => &signature::ECDSA_P256_SHA384_ASN1,
+            (OID_EC_PUBLIC_KEY, Some(OID_P384), 256) => &signature::ECDSA_P384_SHA256_ASN1,
+            (OID_EC_PUBLIC_KEY, Some(OID_P384), 384) => &signature::ECDSA_P384_SHA384_ASN1,
+            _ => {
+                debug!("Unsupported TSA key algorithm");
+                return false;
+            }
+        };
+
+        UnparsedPublicKey::new(algorithm, self.public_key)
+            .verify(message, sig)
+            .is_ok()
+    }
+}
+
+/// A single DER-encoded ASN.1 value. Only single-byte tags are supported, which is all timestamp tokens use.
+#[derive(Clone, Copy, Debug)]
+struct Der<'a> {
+    tag: u8,
+    /// The contents without the tag and the length.
+    value: &'a [u8],
+    /// The entire encoding with the tag and the length.
+    raw: &'a [u8],
+}
+
+impl<'a> Der<'a> {
+    /// Reads a single value from the start of the input and returns it with the rest of the input.
+    fn read(input: &'a [u8]) -> Option<(Self, &'a [u8])> {
+        let tag = *input.first()?;
+        if tag & 0x1f == 0x1f {
+            return None;
+        }
+
+        let (len, header_len) = match *input.get(1)? as usize {
+            len if len < 0x80 => (len, 2),
+            // the indefinite length (0x80) is not allowed in DER
+            len_bytes @ 0x81..=0x84 => {
+                let len_bytes = len_bytes & 0x7f;
+                let len = input
+                    .get(2..2 + len_bytes)?
+                    .iter()
+                    .fold(0usize, |len, b| (len << 8) | *b as usize);
+                (len, 2 + len_bytes)
+            }
+            _ => return None,
+        };
+
+        let end = header_len.checked_add(len)?;
+        let raw = input.get(..end)?;
+        Some((
+            Self {
+                tag,
+                value: &raw[header_len..],
+                raw,
+            },
+            &input[end..],
+        ))
+    }
+
+    /// Parses the entire input as a single value. Returns None if there is anything after it.
+    fn parse(input: &'a [u8]) -> Option<Self> {
+        match Self::read(input)? {
+            (v, []) => Some(v),
+            _ => None,
+        }
+    }
+
+    /// Returns self if it has the expected tag.
+    fn expect(self, tag: u8) -> Option<Self> {
+        if self.tag == tag {
+            Some(self)
+        } else {
+            None
+        }
+    }
+
+    /// Returns the values nested inside a constructed value with the expected tag, e.g. the members of a SEQUENCE.
+    fn children(&self, tag: u8) -> Option<Vec<Der<'a>>> {
+        if self.tag != tag {
+            return None;
+        }
+
+        let mut children = Vec::new();
+        let mut rest = self.value;
+        while !rest.is_empty() {
+            let (child, tail) = Self::read(rest)?;
+            children.push(child);
+            rest = tail;
+        }
+
+        Some(children)
+    }
+}
+
+/// Returns the DER encoding of a value with the given tag and contents.
+fn der_encode(tag: u8, value: &[u8]) -> Vec<u8> {
+    let mut der = vec![tag];
+    if value.len() < 0x80 {
+        der.push(value.len() as u8);
+    } else {
+        let len = value.len().to_be_bytes();
+        let len = &len[len.iter().take_while(|b| **b == 0).count()..];
+        der.push(0x80 | len.len() as u8);
+        der.extend_from_slice(len);
+    }
+    der.extend_from_slice(value);
+
+    der
+}
+
+/// Returns the digest algorithm for its OID or None if it is not supported.
+fn digest_algorithm(oid: &[u8]) -> Option<&'static ring::digest::Algorithm> {
+    match oid {
+        OID_SHA256 => Some(&SHA256),
+        OID_SHA384 => Some(&SHA384),
+        OID_SHA512 => Some(&SHA512),
+        _ => None,
+    }
+}
+
+/// Converts GeneralizedTime, e.g. `20210501102030Z` or `20210501102030.123Z`, into `2021-05-01T10:20:30Z`.
+/// Fractions of a second are dropped.
+fn to_rfc3339(generalized_time: &[u8]) -> Option<String> {
+    let t = std::str::from_utf8(generalized_time).ok()?;
+    if t.len() < 15 || !t.ends_with('Z') || !t[..14].bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+
+    Some(format!(
+        "{}-{}-{}T{}:{}:{}Z",
+        &t[0..4],
+        &t[4..6],
+        &t[6..8],
+        &t[8..10],
+        &t[10..12],
+        &t[12..14]
+    ))
+}
+
+/// Returns the bytes as a lower case hex string.
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect::<String>()
+}
+
+#[cfg(test)]
+mod test_timestamp {
+    use super::{
+        der_encode, timestamp_request, token_file_name, verify_token, Der, OID_COMMON_NAME, OID_EC_PUBLIC_KEY,
+        OID_MESSAGE_DIGEST, OID_P256, OID_SHA256, OID_SIGNED_DATA, OID_TST_INFO, TAG_BIT_STRING, TAG_CONTEXT_0,
+        TAG_GENERALIZED_TIME, TAG_INTEGER, TAG_OCTET_STRING, TAG_OID, TAG_SEQUENCE, TAG_SET,
+    };
+    use ring::digest::{digest, SHA256};
+    use ring::rand::SystemRandom;
+    use ring::signature::{EcdsaKeyPair, KeyPair, ECDSA_P256_SHA256_ASN1_SIGNING};
+    use std::path::{Path, PathBuf};
+
+    /// Returns a TimeStampResp signed with a new P-256 key for the report hash with a self-made TSA certificate.
+    fn make_response(report: &[u8], nonce: &[u8]) -> Vec<u8> {
+        let rng = SystemRandom::new();
+        let pkcs8 = EcdsaKeyPair::generate_pkcs8(&ECDSA_P256_SHA256_ASN1_SIGNING, &rng).unwrap();
+        let key_pair = EcdsaKeyPair::from_pkcs8(&ECDSA_P256_SHA256_ASN1_SIGNING, pkcs8.as_ref()).unwrap();
+
+        let seq = |parts: &[Vec<u8>]| der_encode(TAG_SEQUENCE, &parts.concat());
+        let sha256 = seq(&[der_encode(TAG_OID, OID_SHA256)]);
+
+        let tst_info = seq(&[
+            der_encode(TAG_INTEGER, &[1]),
+            der_encode(TAG_OID, &[0x2a, 0x03]),
+            seq(&[
+                sha256.clone(),
+                der_encode(TAG_OCTET_STRING, digest(&SHA256, report).as_ref()),
+            ]),
+            der_encode(TAG_INTEGER, &[0x12, 0x34]),
+            der_encode(TAG_GENERALIZED_TIME, b"20210501102030.5Z"),
+            der_encode(TAG_INTEGER, nonce),
+        ]);
+
+        let subject = seq(&[der_encode(
+            TAG_SET,
+            &seq(&[der_encode(TAG_OID, OID_COMMON_NAME), der_encode(0x0c, b"Test TSA")]),
+        )]);
+        let spki = seq(&[
+            seq(&[der_encode(TAG_OID, OID_EC_PUBLIC_KEY), der_encode(TAG_OID, OID_P256)]),
+            der_encode(TAG_BIT_STRING, &[&[0u8][..], key_pair.public_key().as_ref()].concat()),
+        ]);
+        let tbs = seq(&[
+            der_encode(TAG_INTEGER, &[7]),
+            seq(&[]),
+            seq(&[]),
+            seq(&[]),
+            subject,
+            spki,
+        ]);
+        let cert = seq(&[tbs, seq(&[]), der_encode(TAG_BIT_STRING, &[0])]);
+
+        let message_digest = seq(&[
+            der_encode(TAG_OID, OID_MESSAGE_DIGEST),
+            der_encode(TAG_SET, &der_encode(TAG_OCTET_STRING, digest(&SHA256, &tst_info).as_ref())),
+        ]);
+        let signed_attrs = der_encode(TAG_SET, &message_digest);
+        let sig = key_pair.sign(&rng, &signed_attrs).unwrap();
+        let mut signed_attrs = signed_attrs;
+        signed_attrs[0] = TAG_CONTEXT_0;
+
+        let signer_info = seq(&[
+            der_encode(TAG_INTEGER, &[1]),
+            seq(&[]),
+            sha256.clone(),
+            signed_attrs,
+            seq(&[]),
+            der_encode(TAG_OCTET_STRING, sig.as_ref()),
+        ]);
+        let signed_data = seq(&[
+            der_encode(TAG_INTEGER, &[3]),
+            der_encode(TAG_SET, &sha256),
+            seq(&[
+                der_encode(TAG_OID, OID_TST_INFO),
+                der_encode(TAG_CONTEXT_0, &der_encode(TAG_OCTET_STRING, &tst_info)),
+            ]),
+            der_encode(TAG_CONTEXT_0, &cert),
+            der_encode(TAG_SET, &signer_info),
+        ]);
+        let token = seq(&[
+            der_encode(TAG_OID, OID_SIGNED_DATA),
+            der_encode(TAG_CONTEXT_0, &signed_data),
+        ]);
+
+        seq(&[seq(&[der_encode(TAG_INTEGER, &[0])]), token])
+    }
+
+    #[test]
+    fn test_verify_token() {
+        let report = br#"{"tech":[]}"#;
+        let nonce = [0x41, 0x02, 0x03];
+        let response = make_response(report, &nonce);
+
+        let timestamp = verify_token(&response, report, Some(&nonce)).unwrap();
+        assert_eq!(timestamp.gen_time, "2021-05-01T10:20:30Z");
+        assert_eq!(timestamp.serial_number, "1234");
+        assert_eq!(timestamp.tsa_name.unwrap(), "Test TSA");
+        assert!(verify_token(&response, report, None).is_ok());
+
+        assert_eq!(
+            verify_token(&response, br#"{"tech":[1]}"#, None).unwrap_err(),
+            "the report was changed after it was timestamped"
+        );
+        assert_eq!(
+            verify_token(&response, report, Some(&[0x41])).unwrap_err(),
+            "the TSA response is for a different request"
+        );
+
+        // a different serial number in TSTInfo breaks the digest in the signed attributes
+        let mut tampered = response.clone();
+        let serial_idx = tampered
+            .windows(4)
+            .position(|v| v == [TAG_INTEGER, 2, 0x12, 0x34])
+            .unwrap();
+        tampered[serial_idx + 2] = 0x13;
+        assert_eq!(
+            verify_token(&tampered, report, None).unwrap_err(),
+            "the token contents do not match its signature"
+        );
+
+        let rejection = der_encode(TAG_SEQUENCE, &der_encode(TAG_SEQUENCE, &der_encode(TAG_INTEGER, &[2])));
+        assert_eq!(
+            verify_token(&rejection, report, None).unwrap_err(),
+            "the TSA rejected the request with status 02"
+        );
+        assert!(verify_token(b"<html>", report, None).is_err());
+        assert!(verify_token(&response[..response.len() - 1], report, None).is_err());
+    }
+
+    #[test]
+    fn test_timestamp_request() {
+        let hash = digest(&SHA256, b"report");
+        let request = timestamp_request(hash.as_ref(), &[0x41, 0x42]);
+        let request = Der::parse(&request).unwrap().children(TAG_SEQUENCE).unwrap();
+        assert_eq!(request.len(), 4);
+        let imprint = request[1].children(TAG_SEQUENCE).unwrap();
+        assert_eq!(imprint[1].value, hash.as_ref());
+        assert_eq!(request[2].value, [0x41, 0x42]);
+
+        // long-form lengths
+        let long = der_encode(TAG_OCTET_STRING, &[7u8; 300]);
+        assert_eq!(&long[..4], &[TAG_OCTET_STRING, 0x82, 0x01, 0x2c]);
+        assert_eq!(Der::parse(&long).unwrap().value.len(), 300);
+
+        assert_eq!(
+            token_file_name(Path::new("/tmp/combined_report.json")),
+            PathBuf::from("/tmp/combined_report.json.tsr")
+        );
+    }
+}
@@ -0,0 +1,195 @@
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::sync::OnceLock;
+use tracing::{debug, error};
+
+/// Message catalogs by language code. Every catalog is a flat JSON map of message keys to texts with `{name}`
+/// placeholders, e.g. `"config.location_set": "{location} will be shown as your location in your Directory Profile."`.
+/// Add a file with the same keys to `locales` and a line here to support another language.
+const CATALOGS: [(&str, &str); 1] = [("en", include_str!("../locales/en.json"))];
+
+/// The language used if the user's language has no catalog and for keys missing from other catalogs.
+const DEFAULT_LANG: &str = "en";
+
+/// The env var that overrides the language of the OS, e.g. `STACKMUNCHER_LANG=en`.
+pub(crate) const LANG_ENV_VAR: &str = "STACKMUNCHER_LANG";
+
+/// Env vars with the user's locale in the order of precedence, e.g. `de_DE.UTF-8`.
+const LOCALE_ENV_VARS: [&str; 3] = ["LC_ALL", "LC_MESSAGES", "LANG"];
+
+/// Labels of the summary lines of `munch` are padded to this width to line up the values,
+/// e.g. `    Summary (LoC/libs):  Rust 12656/26`.
+const LABEL_WIDTH: usize = 20;
+
+/// Messages of the user's language with the default language as a fallback.
+struct Catalog {
+    messages: HashMap<String, String>,
+    fallback: HashMap<String, String>,
+}
+
+static CATALOG: OnceLock<Catalog> = OnceLock::new();
+
+/// Returns the message for `key` in the user's language. Returns the key itself if the message is missing,
+/// which is a bug.
+pub(crate) fn msg(key: &str) -> String {
+    let catalog = CATALOG.get_or_init(|| load_catalog(&detect_lang()));
+    match catalog.messages.get(key).or_else(|| catalog.fallback.get(key)) {
+        Some(v) => v.clone(),
+        None => {
+            error!("Missing message {} in the catalog. It's a bug.", key);
+            key.to_owned()
+        }
+    }
+}
+
+/// Same as `msg`, but with `{name}` placeholders replaced with the values of `args`,
+/// e.g. `msg_with("config.alias_set", &[("old_email", &old), ("new_email", &new)])`.
+/// Placeholders with no matching arg are left as-is.
+pub(crate) fn msg_with(key: &str, args: &[(&str, &dyn Display)]) -> String {
+    fill(msg(key), args)
+}
+
+/// Returns a summary line with the label from the catalog padded to line up with the other lines,
+/// e.g. `    Coverage:            12 of 34 files`.
+pub(crate) fn summary_line(label_key: &str, value: impl Display) -> String {
+    format!("    {:<width$} {}", msg(label_key), value, width = LABEL_WIDTH)
+}
+
+/// Returns the date part of an RFC3339 timestamp, e.g. `2023-06-01` for `2023-06-01T10:00:00+00:00`.
+/// Dates are printed in ISO 8601 in all languages, same as numbers. See `number()`.
+pub(crate) fn date(timestamp: &str) -> &str {
+    timestamp.get(..10).unwrap_or(timestamp)
+}
+
+/// Returns a count or a number of LoC for the output, e.g. `12656`. Numbers are printed with ASCII digits and
+/// no digit grouping in all languages, so that the output reads the same whatever the locale of the machine
+/// and can be parsed by scripts.
+pub(crate) fn number(value: u64) -> String {
+    value.to_string()
+}
+
+/// Replaces `{name}` placeholders in `text` with the values of `args`.
+fn fill(mut text: String, args: &[(&str, &dyn Display)]) -> String {
+    for (name, value) in args {
+        text = text.replace(&["{", name, "}"].concat(), &value.to_string());
+    }
+
+    text
+}
+
+/// Returns the language code from `STACKMUNCHER_LANG` or the locale of the OS, e.g. `de` for `de_DE.UTF-8`.
+/// Returns the default language if none is set.
+fn detect_lang() -> String {
+    std::iter::once(LANG_ENV_VAR)
+        .chain(LOCALE_ENV_VARS.iter().copied())
+        .filter_map(|env_var| std::env::var(env_var).ok())
+        .find_map(|locale| lang_from_locale(&locale))
+        .unwrap_or_else(|| DEFAULT_LANG.to_owned())
+}
+
+/// Extracts the language code from a POSIX locale, e.g. `pt` for `pt_BR.UTF-8@euro`.
+/// Returns None for an empty locale and for `C` and `POSIX`, which have no language.
+fn lang_from_locale(locale: &str) -> Option<String> {
+    let lang = locale
+        .split(['_', '-', '.', '@'])
+        .next()
+        .unwrap_or_default()
+        .to_lowercase();
+    if lang.is_empty() || lang == "c" || lang == "posix" {
+        None
+    } else {
+        Some(lang)
+    }
+}
+
+/// Loads the catalog for `lang` with the default one as the fallback. Languages with no catalog get the default one.
+fn load_catalog(lang: &str) -> Catalog {
+    let fallback = parse_catalog(DEFAULT_LANG);
+    let messages = if lang == DEFAULT_LANG {
+        HashMap::new()
+    } else {
+        debug!("UI language: {}", lang);
+        parse_catalog(lang)
+    };
+
+    Catalog { messages, fallback }
+}
+
+/// Returns the messages of the catalog for `lang` or an empty list if there is no such catalog or it is invalid.
+fn parse_catalog(lang: &str) -> HashMap<String, String> {
+    let contents = match CATALOGS.iter().find(|(catalog_lang, _)| *catalog_lang == lang) {
+        Some((_, contents)) => contents,
+        None => {
+            debug!("No message catalog for {}", lang);
+            return HashMap::new();
+        }
+    };
+
+    match serde_json::from_str::<HashMap<String, String>>(contents) {
+        Ok(v) => v,
+        Err(e) => {
+            error!("Cannot parse the message catalog for {} due to {}. It's a bug.", lang, e);
+            HashMap::new()
+        }
+    }
+}
+
+#[cfg(test)]
+mod test_ui {
+    use super::{fill, lang_from_locale, parse_catalog, CATALOGS, DEFAULT_LANG};
+    use regex::Regex;
+
+    #[test]
+    fn test_lang_from_locale() {
+        assert_eq!(lang_from_locale("de_DE.UTF-8"), Some("de".to_owned()));
+        assert_eq!(lang_from_locale("pt-BR"), Some("pt".to_owned()));
+        assert_eq!(lang_from_locale("EN"), Some("en".to_owned()));
+        assert_eq!(lang_from_locale("C.UTF-8"), None);
+        assert_eq!(lang_from_locale("POSIX"), None);
+        assert_eq!(lang_from_locale(""), None);
+    }
+
+    #[test]
+    fn test_fill() {
+        let text = fill("{old} into {new}, {missing}".to_owned(), &[("old", &"a@b.c"), ("new", &42)]);
+        assert_eq!(text, "a@b.c into 42, {missing}");
+    }
+
+    #[test]
+    fn test_catalogs() {
+        let default_catalog = parse_catalog(DEFAULT_LANG);
+        assert!(!default_catalog.is_empty());
+
+        // translations must have the same placeholders as the default catalog
+        let placeholder = Regex::new(r"\{[a-z_]+\}").unwrap();
+        let placeholders = |text: &str| {
+            let mut v = placeholder
+                .find_iter(text)
+                .map(|m| m.as_str().to_owned())
+                .collect::<Vec<String>>();
+            v.sort();
+            v
+        };
+        for (lang, _) in CATALOGS.iter() {
+            let catalog = parse_catalog(lang);
+            assert!(!catalog.is_empty(), "{}", lang);
+            for (key, text) in catalog {
+                let default_text = default_catalog.get(&key).expect(&key);
+                assert_eq!(placeholders(&text), placeholders(default_text), "{}: {}", lang, key);
+            }
+        }
+
+        // every key used in the code must be in the default catalog
+        let key = Regex::new(r#"ui::(?:msg|msg_with|summary_line)\(\s*"([^"]+)""#).unwrap();
+        let src_dir = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("src");
+        let mut keys = 0;
+        for file in std::fs::read_dir(src_dir).unwrap() {
+            let contents = std::fs::read_to_string(file.unwrap().path()).unwrap();
+            for cap in key.captures_iter(&contents) {
+                assert!(default_catalog.contains_key(&cap[1]), "{}", &cap[1]);
+                keys += 1;
+            }
+        }
+        assert!(keys > 0);
+    }
+}
@@ -0,0 +1,53 @@
+//! Criterion benchmarks of the hot paths on a synthetic repo.
+//! The size of the repo is set via env vars, e.g.
+//! `STM_BENCH_FILES=2000 STM_BENCH_COMMITS=5000 STM_BENCH_CONTRIBUTORS=20 cargo bench -p stackmuncher_lib`
+//! or with `shell/bench.sh`. Compare the results with `BASELINE.md` in this folder.
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use stackmuncher_lib::bench::{BenchFixture, SyntheticRepoSpec};
+use std::path::PathBuf;
+use tokio::runtime::Runtime;
+
+/// Returns the value of an env var as a number or the default if it's not set or not a number.
+fn env_or(name: &str, default: usize) -> usize {
+    std::env::var(name)
+        .ok()
+        .and_then(|v| v.parse::<usize>().ok())
+        .unwrap_or(default)
+}
+
+fn hot_paths(c: &mut Criterion) {
+    let default_spec = SyntheticRepoSpec::default();
+    let spec = SyntheticRepoSpec {
+        files: env_or("STM_BENCH_FILES", default_spec.files),
+        commits: env_or("STM_BENCH_COMMITS", default_spec.commits),
+        contributors: env_or("STM_BENCH_CONTRIBUTORS", default_spec.contributors),
+    };
+
+    let repo_dir = std::env::temp_dir().join(["stm_bench_", &uuid::Uuid::new_v4().to_string()].concat());
+    let rt = Runtime::new().expect("Cannot start tokio runtime");
+    let mut fixture = rt
+        .block_on(BenchFixture::new(&repo_dir, &spec))
+        .expect("Cannot create the bench fixture");
+
+    let mut group = c.benchmark_group(format!("{}f_{}c_{}a", spec.files, spec.commits, spec.contributors));
+    group.bench_function("log_parsing", |b| b.iter(|| fixture.log_parsing()));
+    group.bench_function("tree_listing", |b| b.iter(|| fixture.tree_listing()));
+    group.bench_function("blob_selection", |b| b.iter(|| fixture.blob_selection()));
+    group.bench_function("cache_copy", |b| b.iter(|| fixture.cache_copy()));
+    group.bench_function("report_merge", |b| b.iter(|| fixture.report_merge()));
+    group.sample_size(10);
+    group.bench_function("munching", |b| b.iter(|| rt.block_on(fixture.munching()).unwrap()));
+    group.finish();
+
+    remove_repo(repo_dir);
+}
+
+fn remove_repo(repo_dir: PathBuf) {
+    if let Err(e) = std::fs::remove_dir_all(&repo_dir) {
+        eprintln!("Cannot remove {}: {}", repo_dir.to_string_lossy(), e);
+    }
+}
+
+criterion_group!(benches, hot_paths);
+criterion_main!(benches);
@@ -0,0 +1,1002 @@
+use crate::code_rules::CodeRules;
+use crate::config::Config;
+use crate::contributor::FailedContributor;
+use crate::git;
+use crate::git_log_cache;
+use crate::mailmap::Mailmap;
+use crate::project_config::{Attribution, ProjectConfig, PROJECT_CONFIG_FILE_NAME};
+use crate::report::trends::TREND_RUNS;
+use crate::report::{
+    CoChangeLimits, OverviewHistory, OverviewSnapshot, PrunedHistory, Report, Retention, WorkPattern, WorkdayOverlap,
+};
+use crate::report_store::ReportStore;
+use crate::skip_list::SkipList;
+use crate::utils::hash_str_sha1;
+use crate::vcs::{self, VcsKind};
+use regex::Regex;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use tracing::{debug, error, field, info, info_span, warn, Instrument};
+
+/// Settings for a single analysis of a project by `Analyzer::analyze_repo`.
+#[derive(Debug, Clone, Default)]
+pub struct AnalysisOptions {
+    /// The folder for the reports and caches of this project. Reports from a previous analysis in this folder
+    /// are reused for the unchanged parts of the project. Only one analysis per folder should run at a time.
+    pub report_dir: PathBuf,
+    /// Commit emails of the contributor to generate the combined report for.
+    pub git_identities: Vec<String>,
+    /// Commit emails to merge into other emails on top of `.mailmap` in the project root, e.g. `old@example.com` -> `me@example.com`.
+    pub aliases: BTreeMap<String, String>,
+    /// Commit emails left out of the combined report even if they are in `git_identities`, e.g. a shared identity
+    /// on a pairing workstation. Matched against the identities after `.mailmap` and the aliases are applied.
+    pub exclude_emails: Vec<String>,
+    /// Regex strings for file paths left out of the contributor reports, e.g. `^pairing/`. Invalid regex are
+    /// logged and skipped. The project report is not affected.
+    pub exclude_paths: Vec<String>,
+    pub co_change_limits: CoChangeLimits,
+    /// The working day for the after-hours share of commits in `Report.work_pattern`.
+    pub workday: WorkdayOverlap,
+    /// How long the records for deleted files are kept in cached contributor reports.
+    pub retention: Retention,
+    /// Personal details copied into the combined report as-is.
+    pub primary_email: Option<String>,
+    pub public_name: Option<String>,
+    pub headline: Option<String>,
+    pub public_contact: Option<String>,
+    pub available: Option<bool>,
+    pub location: Option<String>,
+    pub gh_validation_id: Option<String>,
+    /// Fetch the missing history of a shallow clone from its remote before the analysis. The clone is analyzed
+    /// as-is if the fetch fails.
+    pub full_history: bool,
+    /// Where the reports in `report_dir` are cached between runs. The project and combined reports are always
+    /// saved as JSON files as well.
+    pub report_store: ReportStore,
+    /// A branch, tag or commit to analyze the project at instead of HEAD, e.g. `release/1.2`. The caches in
+    /// `report_dir` are only valid for the same ref, so every ref should have its own `report_dir`.
+    pub git_ref: Option<String>,
+}
+
+/// The reports produced by `Analyzer::analyze_repo`. All of them are also saved in `AnalysisOptions.report_dir`.
+#[derive(Debug)]
+pub struct AnalysisOutcome {
+    /// The report for the entire project, saved as `project_report.json`.
+    pub project_report: Report,
+    pub project_report_file: PathBuf,
+    /// The contributor reports of all `git_identities` merged into one, saved as `combined_report.json`.
+    /// None if the identities have no commits in the project or none of their reports could be generated.
+    pub combined_report: Option<Report>,
+    pub combined_report_file: PathBuf,
+    /// `AnalysisOptions.git_identities` merged with `.mailmap` and the aliases.
+    pub git_identities: Vec<String>,
+    /// Contributors whose reports could not be generated. The combined report is missing their data if not empty.
+    pub failed_contributors: Vec<FailedContributor>,
+    /// The project had no new commits since the previous analysis and its project report was reused.
+    pub no_new_commits: bool,
+    /// There was no combined report in the report folder before this analysis.
+    pub first_run: bool,
+    /// Records for deleted files removed from cached contributor reports by `AnalysisOptions.retention`.
+    pub pruned_history: PrunedHistory,
+    /// `hooks` from `.stackmuncher.toml` of the project. They are not run by the analyzer.
+    pub project_hooks: Vec<String>,
+}
+
+/// Why `Analyzer::analyze_repo` could not produce the project report.
+#[derive(Debug, Clone, PartialEq)]
+pub enum AnalysisError {
+    /// The project cannot be analyzed as-is, e.g. it has more files than `munch_limits.max_files`.
+    /// Retrying makes no difference until the project or the settings change.
+    Unsupported(String),
+    /// Any other failure, details are logged.
+    Failed,
+}
+
+/// Most of the analysis steps log the details of their failures and return `Err(())`.
+impl From<()> for AnalysisError {
+    fn from(_: ()) -> Self {
+        Self::Failed
+    }
+}
+
+/// Runs the full analysis of a project: the project report, the contributor reports and the combined report
+/// with the reports and caches of the previous run reused where possible. Does not print anything or submit reports.
+pub struct Analyzer {
+    pub code_rules: CodeRules,
+}
+
+impl Analyzer {
+    pub fn new(code_rules: CodeRules) -> Self {
+        Self { code_rules }
+    }
+
+    /// Analyzes the Git or Mercurial repository in `project_dir` and saves the reports in `options.report_dir`.
+    /// Returns an error if the project report could not be generated. Contributors that failed are listed in the outcome.
+    pub async fn analyze_repo(
+        &mut self,
+        project_dir: &Path,
+        options: &AnalysisOptions,
+    ) -> Result<AnalysisOutcome, AnalysisError> {
+        let report_dir = options.report_dir.as_path();
+        // the project settings and exclusions only apply to this repo, so they go into a copy of the shared rules
+        let mut code_rules = self.code_rules.clone();
+        let code_rules = &mut code_rules;
+
+        // load a previously generated report if it exists
+        let project_report_file =
+            report_dir.join([Config::PROJECT_REPORT_FILE_NAME, Config::REPORT_FILE_EXTENSION].concat());
+        let cached_project_report = options.report_store.load(&project_report_file);
+
+        // get and retain a copy of the full git lot to re-use in multiple places
+        // git logs are cached next to the project report to parse only new commits on subsequent runs
+        let vcs = vcs::detect(project_dir);
+
+        let combined_report_file = report_dir.join(
+            [
+                Config::CONTRIBUTOR_REPORT_COMBINED_FILE_NAME,
+                Config::REPORT_FILE_EXTENSION,
+            ]
+            .concat(),
+        );
+        // there is no report to compare the new one with on the first run
+        let first_run = !combined_report_file.exists();
+
+        // a freshly initialized repo has nothing to analyze, but the caller still gets a blank project report
+        // any other repo without a HEAD commit is damaged and cannot be analyzed
+        if vcs.get_head_commit(project_dir, None).await.is_err() {
+            if !vcs.is_empty_repo(project_dir).await {
+                error!("Cannot resolve HEAD in {}", project_dir.to_string_lossy());
+                return Err(AnalysisError::Failed);
+            }
+            info!("No commits in {}", project_dir.to_string_lossy());
+            let mut project_report = Report::new();
+            project_report.empty_repo = true;
+            options
+                .report_store
+                .save(&project_report, &project_report_file, true, true);
+
+            return Ok(AnalysisOutcome {
+                project_report,
+                project_report_file,
+                combined_report: None,
+                combined_report_file,
+                git_identities: options.git_identities.clone(),
+                failed_contributors: Vec::new(),
+                no_new_commits: false,
+                first_run,
+                pruned_history: PrunedHistory::default(),
+                project_hooks: Vec::new(),
+            });
+        }
+
+        // the ref is resolved once, so that the log, the tree and the blobs are all taken at the same commit
+        let rev = match &options.git_ref {
+            Some(git_ref) => match vcs.get_head_commit(project_dir, Some(git_ref)).await {
+                Ok(v) => {
+                    info!("Analyzing {} at {}", git_ref, v);
+                    Some(v)
+                }
+                Err(_) => {
+                    error!("No commit for {} in {}", git_ref, project_dir.to_string_lossy());
+                    return Err(AnalysisError::Failed);
+                }
+            },
+            None => None,
+        };
+        let rev = rev.as_deref();
+
+        // per-project settings checked into the repo extend the ignore list and override some of the limits
+        let project_config = ProjectConfig::from_repo(project_dir)?;
+        project_config.apply(code_rules);
+        let project_config_hash = project_config.hash();
+
+        // the cached report cannot be reused if the settings changed since it was generated
+        // the log cache has its own check for changes in the ignore list
+        let cached_project_report = match cached_project_report {
+            Some(v) if v.project_config_hash != project_config_hash => {
+                info!("{} changed since the last run", PROJECT_CONFIG_FILE_NAME);
+                None
+            }
+            v => v,
+        };
+
+        if options.full_history && vcs.kind() == VcsKind::Git && git::is_shallow_repo(project_dir).await {
+            let unshallowed = git::unshallow(project_dir).await;
+            if unshallowed.is_err() {
+                warn!("Cannot fetch the full history. Only the commits in the shallow clone are analyzed.");
+            }
+        }
+        let log_fetch_span = info_span!("phase", phase = "log_fetch", commits = field::Empty);
+        let git_log = async {
+            if vcs.kind() == VcsKind::Git {
+                // only the history reachable from HEAD is analyzed, but even that gets slower with a very large number of refs
+                if let Some(ref_count) = git::count_refs(project_dir).await {
+                    if ref_count >= git::MANY_REFS_THRESHOLD {
+                        warn!("The repo has {} refs. Only the history reachable from HEAD is analyzed.", ref_count);
+                    }
+                }
+                git_log_cache::get_log(
+                    project_dir,
+                    rev,
+                    &code_rules.ignore_paths,
+                    report_dir,
+                    cached_project_report.as_ref(),
+                )
+                .await
+            } else {
+                vcs.get_log(project_dir, None, rev, &code_rules.ignore_paths).await
+            }
+        }
+        .instrument(log_fetch_span.clone())
+        .await?;
+        log_fetch_span.record("commits", git_log.len());
+        drop(log_fetch_span);
+
+        // merge identities of contributors who committed under several emails before any reports are generated
+        // `.mailmap` can be turned off in the project settings, but the user aliases always apply
+        let mailmap = match project_config.attribution {
+            Attribution::Mailmap => Mailmap::from_repo(project_dir),
+            Attribution::Raw => Mailmap::default(),
+        };
+        let mailmap = mailmap.with_aliases(&options.aliases);
+        let git_log = mailmap.apply(git_log);
+        let mut git_identities = mailmap.map_identities(&options.git_identities);
+
+        // shared identities and paths the user opted out of are removed from the contributor reports
+        let exclude_emails = options
+            .exclude_emails
+            .iter()
+            .map(|email| email.trim().to_lowercase())
+            .collect::<Vec<String>>();
+        git_identities.retain(|git_id| !exclude_emails.contains(git_id));
+        code_rules.contributor_ignore_paths = options
+            .exclude_paths
+            .iter()
+            .filter_map(|exclude_path| match Regex::new(exclude_path) {
+                Ok(v) => Some(v),
+                Err(e) => {
+                    warn!("Invalid regex in exclude_paths: {}", e);
+                    None
+                }
+            })
+            .collect::<Vec<Regex>>();
+
+        // files that failed or took too long on previous runs are not munched again
+        let mut skip_list = SkipList::from_disk(report_dir);
+
+        let mut no_new_commits = false;
+        let project_report = match Report::process_project(
+            code_rules,
+            project_dir,
+            rev,
+            &cached_project_report,
+            Some(git_log.clone()),
+            &mut skip_list,
+        )
+        .instrument(info_span!("phase", phase = "project"))
+        .await?
+        {
+            None => {
+                // there were no changes since the previous report - it can be reused as-is
+                no_new_commits = true;
+                let cached_project_report = cached_project_report.expect("Cannot unwrap cached report. It's a bug.");
+                // reports from older versions have no co-change, collaboration, hotspots or import graph sections
+                let problem_files = skip_list.problem_files(cached_project_report.tree_files.as_ref());
+                if cached_project_report.co_change.is_none()
+                    || cached_project_report.collaboration.is_none()
+                    || cached_project_report.hotspots.is_none()
+                    || cached_project_report.import_graph.is_none()
+                    || cached_project_report.problem_files != problem_files
+                    || cached_project_report.work_pattern != WorkPattern::from_log(&git_log, &options.workday)
+                {
+                    let v = cached_project_report
+                        .add_co_change(&git_log, &options.co_change_limits)
+                        .add_work_pattern(&git_log, &options.workday)
+                        .add_collaboration(&git_log)
+                        .add_hotspots(&git_log)
+                        .add_import_graph()
+                        .add_schema_migrations()
+                        .add_ops_tech()
+                        .add_tech_debt_markers()
+                        .add_problem_files(&skip_list);
+                    options.report_store.save(&v, &project_report_file, true, true);
+                    v
+                } else {
+                    cached_project_report
+                }
+            }
+            Some(mut v) => {
+                v.project_config_hash = project_config_hash;
+                v.git_ref = options.git_ref.clone();
+                let v = v
+                    .add_co_change(&git_log, &options.co_change_limits)
+                    .add_work_pattern(&git_log, &options.workday)
+                    .add_collaboration(&git_log)
+                    .add_hotspots(&git_log)
+                    .add_import_graph()
+                    .add_schema_migrations()
+                    .add_ops_tech()
+                    .add_tech_debt_markers()
+                    .add_problem_files(&skip_list);
+                options.report_store.save(&v, &project_report_file, true, true);
+                v
+            }
+        };
+
+        info!("Contributor reports requested for: {:?}", git_identities);
+
+        // a container for the contributor reports of all identities of the same contributor to combine into a single report
+        let mut contributor_reports: Vec<(Report, String)> = Vec::new();
+
+        // contributors that failed to process are skipped to let the others through
+        let mut failed_contributors: Vec<FailedContributor> = Vec::new();
+
+        // space saved by the retention policy in the cached contributor reports
+        let mut pruned_history = PrunedHistory::default();
+
+        let contributors = project_report.contributors.as_deref().unwrap_or_default();
+        for contributor in contributors {
+            // only process known local identities
+            if !git_identities.contains(&contributor.git_id.trim().to_lowercase()) {
+                debug!("Contributor {} skipped / unknown identity", contributor.git_id);
+                continue;
+            }
+
+            // load the previous contributor report, if any
+            let contributor_hash = hash_str_sha1(contributor.git_id.as_str());
+            let contributor_report_file = report_dir.join(
+                [
+                    Config::CONTRIBUTOR_REPORT_FILE_NAME,
+                    contributor_hash.as_str(),
+                    Config::REPORT_FILE_EXTENSION,
+                ]
+                .concat(),
+            );
+
+            let cached_contributor_report = options.report_store.load(&contributor_report_file);
+
+            // if this is a single-commit update then use cached reports for all contributors other than the author of the commit
+            if project_report.is_single_commit
+                && Some(&contributor.git_id) != project_report.last_commit_author.as_ref()
+            {
+                if let Some(cached_contributor_report) = cached_contributor_report {
+                    debug!("Used cached report for contributor {} / single commit", contributor.git_id);
+                    contributor_reports.push((cached_contributor_report, contributor.git_id.clone()));
+                    continue;
+                }
+                debug!("Missing cached report for contributor {} / single commit", contributor.git_id);
+            }
+
+            let mut contributor_report = match project_report
+                .process_contributor(
+                    code_rules,
+                    project_dir,
+                    &cached_contributor_report,
+                    contributor,
+                    project_report.tree_files.as_ref(),
+                    &mut skip_list,
+                )
+                .instrument(info_span!("phase", phase = "contributor", contributor = %contributor_hash))
+                .await
+            {
+                Ok(v) => v,
+                Err(_) => {
+                    let reason = get_contributor_failure_reason(vcs.kind(), project_dir).await;
+                    error!("Failed to process contributor {}: {}", contributor.git_id, reason);
+                    failed_contributors.push(FailedContributor {
+                        git_id: contributor.git_id.clone(),
+                        reason,
+                    });
+                    continue;
+                }
+            };
+
+            // records for files deleted since the contribution go into a separate history file to keep the report small
+            if let Some(tree_files) = &project_report.tree_files {
+                contributor_report.compact_per_file_tech(tree_files);
+            }
+            pruned_history.add(contributor_report.prune_per_file_tech_history(&options.retention));
+            options
+                .report_store
+                .save(&contributor_report, &contributor_report_file, false, false);
+
+            // push the contributor report into a container to combine later
+            contributor_reports.push((contributor_report, contributor.git_id.clone()));
+        }
+
+        // combine multiple contributor reports from different identities
+        debug!("Combining {} contributor reports", contributor_reports.len());
+        let combined_report = match contributor_reports.pop() {
+            Some((mut combined_report, contributor_git_id)) => {
+                let merge_span =
+                    info_span!("phase", phase = "merge", reports = contributor_reports.len() + 1).entered();
+                // seed the combined report from the last contributor report in the list of all contributor reports
+                let list_of_commits = git::get_contributor_commits_from_log(&git_log, &git_identities);
+                combined_report.reset_combined_contributor_report(
+                    contributor_git_id,
+                    &list_of_commits,
+                    &project_report,
+                );
+                for (contributor_report, contributor_git_id) in contributor_reports.into_iter() {
+                    // this only adds per-file-tech and does not affect any other part of the report
+                    combined_report.merge_same_project_contributor_reports(contributor_report, contributor_git_id);
+                }
+
+                // combine all added per-file-tech into appropriate tech records
+                combined_report.recompute_tech_section();
+                let mut combined_report = combined_report
+                    .add_work_pattern(&list_of_commits, &options.workday)
+                    .add_schema_migrations()
+                    .add_ops_tech()
+                    .add_tech_debt_markers();
+                drop(merge_span);
+
+                combined_report.primary_email = options.primary_email.clone();
+                combined_report.public_name = options.public_name.clone();
+                combined_report.headline = options.headline.clone();
+                combined_report.public_contact = options.public_contact.clone();
+                combined_report.available = options.available;
+                combined_report.location = options.location.clone();
+                combined_report.gh_validation_id = options.gh_validation_id.clone();
+                combined_report.failed_contributors = failed_contributors.clone();
+
+                // the org and the remote hash are used for local rollups and are removed by sanitize() before submission
+                if vcs.kind() == VcsKind::Git {
+                    combined_report.remote_org = git::get_remote_org(project_dir).await;
+                    combined_report.remote_url_hash = git::get_remote_url_hash(project_dir).await;
+                }
+
+                // a rolling history of the stack, one snapshot per HEAD commit, for the trends over the last few runs
+                let mut overview_history = OverviewHistory::from_disk(report_dir);
+                overview_history.add(OverviewSnapshot::from_report(
+                    &combined_report,
+                    project_report.report_commit_sha1.as_deref().unwrap_or_default(),
+                ));
+                overview_history.save(report_dir);
+                combined_report.trends = overview_history.trends(TREND_RUNS);
+
+                // save the combined report for inspection by the user
+                if let Some(tree_files) = &project_report.tree_files {
+                    combined_report.compact_per_file_tech(tree_files);
+                }
+                options
+                    .report_store
+                    .save(&combined_report, &combined_report_file, true, true);
+
+                Some(combined_report)
+            }
+            None => None,
+        };
+
+        // failures from contributor reports are only listed in the project report on the next run
+        skip_list.save(report_dir);
+
+        // munchers are loaded on demand and can be reused for the next repo
+        self.code_rules.munchers = std::mem::take(&mut code_rules.munchers);
+
+        Ok(AnalysisOutcome {
+            project_report,
+            project_report_file,
+            combined_report,
+            combined_report_file,
+            git_identities,
+            failed_contributors,
+            no_new_commits,
+            first_run,
+            pruned_history,
+            project_hooks: project_config.hooks,
+        })
+    }
+}
+
+/// Returns a best guess of why a contributor report could not be generated for the user to act on.
+/// The details of the failure are in the log.
+async fn get_contributor_failure_reason(vcs_kind: VcsKind, project_dir: &Path) -> String {
+    if vcs_kind == VcsKind::Git && git::is_shallow_repo(project_dir).await {
+        return "the repo is a shallow clone with some commits missing. Run `git fetch --unshallow` to get the full history.".to_owned();
+    }
+
+    "some commits or files could not be read from the repo, e.g. after aggressive garbage collection. Run with `--log error` for details.".to_owned()
+}
+
+#[cfg(test)]
+mod test_analyzer {
+    use super::{AnalysisError, AnalysisOptions, Analyzer};
+    use crate::bench::{generate_synthetic_repo, SyntheticRepoSpec};
+    use crate::cache_check::is_log_current;
+    use crate::code_rules::CodeRules;
+    use crate::git;
+    use std::path::Path;
+
+    /// Runs a git command in `repo_dir` as the committer with `email` and panics if it fails.
+    fn run_git(repo_dir: &Path, email: &str, args: &[&str]) {
+        let status = std::process::Command::new("git")
+            .args(["-c", "user.name=dev", "-c"])
+            .arg(["user.email=", email].concat())
+            .args(args)
+            .current_dir(repo_dir)
+            .status()
+            .unwrap();
+        assert!(status.success(), "git {:?}", args);
+    }
+
+    #[tokio::test]
+    async fn test_analyze_repo() {
+        let test_dir = std::env::temp_dir().join(format!("stm_analyzer_test_{}", std::process::id()));
+        let project_dir = test_dir.join("project");
+        let _ = std::fs::remove_dir_all(&test_dir);
+        std::fs::create_dir_all(&project_dir).unwrap();
+        let spec = SyntheticRepoSpec {
+            files: 5,
+            commits: 4,
+            contributors: 2,
+        };
+        generate_synthetic_repo(&project_dir, &spec).await.unwrap();
+
+        let options = AnalysisOptions {
+            report_dir: test_dir.join("reports"),
+            git_identities: vec!["dev1@example.com".to_owned()],
+            primary_email: Some("dev1@example.com".to_owned()),
+            ..Default::default()
+        };
+        std::fs::create_dir_all(&options.report_dir).unwrap();
+
+        let mut analyzer = Analyzer::new(CodeRules::new());
+        let outcome = analyzer.analyze_repo(&project_dir, &options).await.unwrap();
+        assert!(outcome.first_run);
+        assert!(!outcome.no_new_commits);
+        assert!(outcome.failed_contributors.is_empty());
+        assert_eq!(outcome.project_report.contributors.as_ref().unwrap().len(), 2);
+        let combined_report = outcome.combined_report.unwrap();
+        assert_eq!(combined_report.primary_email.as_deref(), Some("dev1@example.com"));
+        assert!(outcome.project_report_file.exists());
+        assert!(outcome.combined_report_file.exists());
+
+        // the second run reuses the reports of the first one
+        let outcome = analyzer.analyze_repo(&project_dir, &options).await.unwrap();
+        assert!(!outcome.first_run);
+        assert!(outcome.no_new_commits);
+        assert!(outcome.combined_report.is_some());
+
+        // no combined report for identities without commits
+        let options = AnalysisOptions {
+            git_identities: vec!["nobody@example.com".to_owned()],
+            ..options
+        };
+        assert!(analyzer
+            .analyze_repo(&project_dir, &options)
+            .await
+            .unwrap()
+            .combined_report
+            .is_none());
+
+        let _ = std::fs::remove_dir_all(&test_dir);
+    }
+
+    #[tokio::test]
+    async fn test_analyze_worktree() {
+        let test_dir = std::env::temp_dir().join(format!("stm_analyzer_worktree_test_{}", std::process::id()));
+        let project_dir = test_dir.join("project");
+        let worktree_dir = test_dir.join("worktree");
+        let _ = std::fs::remove_dir_all(&test_dir);
+        std::fs::create_dir_all(&project_dir).unwrap();
+        let spec = SyntheticRepoSpec {
+            files: 3,
+            commits: 2,
+            contributors: 2,
+        };
+        generate_synthetic_repo(&project_dir, &spec).await.unwrap();
+
+        // `.git` in the worktree is a file pointing at `project/.git/worktrees/worktree`
+        let status = std::process::Command::new("git")
+            .args(["worktree", "add", "-q", "--detach"])
+            .arg(&worktree_dir)
+            .current_dir(&project_dir)
+            .status()
+            .unwrap();
+        assert!(status.success());
+        assert!(worktree_dir.join(".git").is_file());
+
+        let options = AnalysisOptions {
+            report_dir: test_dir.join("reports"),
+            git_identities: vec!["dev1@example.com".to_owned()],
+            ..Default::default()
+        };
+        std::fs::create_dir_all(&options.report_dir).unwrap();
+
+        let mut analyzer = Analyzer::new(CodeRules::new());
+        let outcome = analyzer.analyze_repo(&worktree_dir, &options).await.unwrap();
+        assert!(outcome.first_run);
+        assert_eq!(outcome.project_report.contributors.as_ref().unwrap().len(), 2);
+        assert!(outcome.combined_report.is_some());
+
+        // the cached reports are reused the same way as for a regular checkout
+        let outcome = analyzer.analyze_repo(&worktree_dir, &options).await.unwrap();
+        assert!(outcome.no_new_commits);
+
+        let _ = std::fs::remove_dir_all(&test_dir);
+    }
+
+    #[tokio::test]
+    async fn test_analyze_exclusions() {
+        let test_dir = std::env::temp_dir().join(format!("stm_analyzer_exclusions_test_{}", std::process::id()));
+        let project_dir = test_dir.join("project");
+        let _ = std::fs::remove_dir_all(&test_dir);
+        std::fs::create_dir_all(&project_dir).unwrap();
+        let spec = SyntheticRepoSpec {
+            files: 3,
+            commits: 4,
+            contributors: 2,
+        };
+        generate_synthetic_repo(&project_dir, &spec).await.unwrap();
+
+        // a shared identity is left out of the combined report, but not out of the project report
+        let options = AnalysisOptions {
+            report_dir: test_dir.join("reports"),
+            git_identities: vec!["dev1@example.com".to_owned(), "dev2@example.com".to_owned()],
+            exclude_emails: vec!["DEV2@example.com".to_owned()],
+            ..Default::default()
+        };
+        std::fs::create_dir_all(&options.report_dir).unwrap();
+        let mut analyzer = Analyzer::new(CodeRules::new());
+        let outcome = analyzer.analyze_repo(&project_dir, &options).await.unwrap();
+        assert_eq!(outcome.git_identities, vec!["dev1@example.com"]);
+        assert_eq!(outcome.project_report.contributors.as_ref().unwrap().len(), 2);
+        let combined_report = outcome.combined_report.unwrap();
+        assert!(!combined_report.per_file_tech.is_empty());
+        assert!(!combined_report.git_ids_included.contains("dev2@example.com"));
+
+        // excluded files are not in the contributor reports
+        let options = AnalysisOptions {
+            exclude_paths: vec![".".to_owned(), "(invalid".to_owned()],
+            ..options
+        };
+        let outcome = analyzer.analyze_repo(&project_dir, &options).await.unwrap();
+        assert!(!outcome.project_report.per_file_tech.is_empty());
+        assert!(outcome.combined_report.unwrap().per_file_tech.is_empty());
+
+        let _ = std::fs::remove_dir_all(&test_dir);
+    }
+
+    #[tokio::test]
+    async fn test_analyze_ref() {
+        let test_dir = std::env::temp_dir().join(format!("stm_analyzer_ref_test_{}", std::process::id()));
+        let project_dir = test_dir.join("project");
+        let _ = std::fs::remove_dir_all(&test_dir);
+        std::fs::create_dir_all(&project_dir).unwrap();
+        let spec = SyntheticRepoSpec {
+            files: 3,
+            commits: 4,
+            contributors: 2,
+        };
+        generate_synthetic_repo(&project_dir, &spec).await.unwrap();
+        let head = git::get_head_commit(&project_dir, None).await.unwrap();
+        let parent = git::get_head_commit(&project_dir, Some("HEAD~1")).await.unwrap();
+
+        let options = AnalysisOptions {
+            report_dir: test_dir.join("reports"),
+            git_identities: vec!["dev1@example.com".to_owned(), "dev2@example.com".to_owned()],
+            git_ref: Some("HEAD~1".to_owned()),
+            ..Default::default()
+        };
+        std::fs::create_dir_all(&options.report_dir).unwrap();
+
+        // the commits after the ref are not analyzed
+        let mut analyzer = Analyzer::new(CodeRules::new());
+        let outcome = analyzer.analyze_repo(&project_dir, &options).await.unwrap();
+        assert_eq!(outcome.project_report.report_commit_sha1.as_deref(), Some(parent.as_str()));
+        assert_eq!(outcome.project_report.git_ref.as_deref(), Some("HEAD~1"));
+        assert_eq!(outcome.combined_report.unwrap().git_ref.as_deref(), Some("HEAD~1"));
+        assert_ne!(parent, head);
+
+        // an unknown ref is an error rather than an empty repo
+        let options = AnalysisOptions {
+            git_ref: Some("no-such-branch".to_owned()),
+            ..options
+        };
+        assert!(analyzer.analyze_repo(&project_dir, &options).await.is_err());
+
+        let _ = std::fs::remove_dir_all(&test_dir);
+    }
+
+    #[tokio::test]
+    async fn test_analyze_deleted_file() {
+        let test_dir = std::env::temp_dir().join(format!("stm_analyzer_deleted_test_{}", std::process::id()));
+        let project_dir = test_dir.join("project");
+        let _ = std::fs::remove_dir_all(&test_dir);
+        std::fs::create_dir_all(&project_dir).unwrap();
+        run_git(&project_dir, "dev1@example.com", &["init", "-q"]);
+        std::fs::write(project_dir.join("main.rs"), "fn main() {\n    println!(\"main\");\n}\n").unwrap();
+        std::fs::write(project_dir.join("old.rs"), "fn old() {\n    println!(\"old\");\n}\n").unwrap();
+        run_git(&project_dir, "dev1@example.com", &["add", "-A"]);
+        run_git(&project_dir, "dev1@example.com", &["commit", "-q", "-m", "add"]);
+        // the file is deleted by someone else, so the last version of it is still the contribution of dev1
+        run_git(&project_dir, "dev2@example.com", &["rm", "-q", "old.rs"]);
+        run_git(&project_dir, "dev2@example.com", &["commit", "-q", "-m", "delete"]);
+
+        let options = AnalysisOptions {
+            report_dir: test_dir.join("reports"),
+            git_identities: vec!["dev1@example.com".to_owned()],
+            ..Default::default()
+        };
+        std::fs::create_dir_all(&options.report_dir).unwrap();
+
+        // the only contributor report is the combined report, with the deleted file still counted
+        let mut analyzer = Analyzer::new(CodeRules::new());
+        let combined_report = analyzer
+            .analyze_repo(&project_dir, &options)
+            .await
+            .unwrap()
+            .combined_report
+            .unwrap();
+        assert!(combined_report
+            .per_file_tech_history
+            .iter()
+            .any(|tech| tech.file_name.as_deref() == Some("old.rs")));
+        let rust = combined_report
+            .tech
+            .iter()
+            .find(|tech| tech.language == "Rust")
+            .unwrap();
+        assert_eq!(rust.files, 2);
+
+        let _ = std::fs::remove_dir_all(&test_dir);
+    }
+
+    #[tokio::test]
+    async fn test_analyze_two_repos() {
+        let test_dir = std::env::temp_dir().join(format!("stm_analyzer_two_repos_test_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&test_dir);
+
+        // the same files in both repos, but only the first one ignores `vendor` in its project settings
+        for (project, project_config) in [("ignoring", "ignore_paths = [\"^vendor/\"]\n"), ("plain", "")] {
+            let project_dir = test_dir.join(project);
+            std::fs::create_dir_all(project_dir.join("vendor")).unwrap();
+            run_git(&project_dir, "dev1@example.com", &["init", "-q"]);
+            std::fs::write(project_dir.join("main.rs"), "fn main() {}\n").unwrap();
+            std::fs::write(project_dir.join("vendor/lib.rs"), "fn lib() {}\n").unwrap();
+            if !project_config.is_empty() {
+                std::fs::write(project_dir.join(".stackmuncher.toml"), project_config).unwrap();
+            }
+            run_git(&project_dir, "dev1@example.com", &["add", "-A"]);
+            run_git(&project_dir, "dev1@example.com", &["commit", "-q", "-m", "add"]);
+        }
+
+        let mut analyzer = Analyzer::new(CodeRules::new());
+        let ignore_paths = analyzer.code_rules.ignore_paths.len();
+        let mut vendored = Vec::new();
+        for project in ["ignoring", "plain"] {
+            let options = AnalysisOptions {
+                report_dir: test_dir.join("reports").join(project),
+                git_identities: vec!["dev1@example.com".to_owned()],
+                ..Default::default()
+            };
+            std::fs::create_dir_all(&options.report_dir).unwrap();
+            let outcome = analyzer.analyze_repo(&test_dir.join(project), &options).await.unwrap();
+            vendored.push(
+                outcome
+                    .project_report
+                    .per_file_tech
+                    .iter()
+                    .any(|tech| tech.file_name.as_deref() == Some("vendor/lib.rs")),
+            );
+        }
+        assert_eq!(vendored, vec![false, true]);
+        assert_eq!(analyzer.code_rules.ignore_paths.len(), ignore_paths);
+
+        let _ = std::fs::remove_dir_all(&test_dir);
+    }
+
+    #[tokio::test]
+    async fn test_analyze_sparse_checkout() {
+        let test_dir = std::env::temp_dir().join(format!("stm_analyzer_sparse_test_{}", std::process::id()));
+        let project_dir = test_dir.join("project");
+        let _ = std::fs::remove_dir_all(&test_dir);
+        std::fs::create_dir_all(project_dir.join("src")).unwrap();
+        std::fs::create_dir_all(project_dir.join("docs")).unwrap();
+        run_git(&project_dir, "dev1@example.com", &["init", "-q"]);
+        std::fs::write(project_dir.join("src/main.rs"), "fn main() {}\n").unwrap();
+        std::fs::write(project_dir.join("docs/lib.rs"), "fn lib() {}\n").unwrap();
+        run_git(&project_dir, "dev1@example.com", &["add", "-A"]);
+        run_git(&project_dir, "dev1@example.com", &["commit", "-q", "-m", "add"]);
+        run_git(&project_dir, "dev1@example.com", &["sparse-checkout", "set", "src"]);
+
+        // the sparse checkout limits the analysis of HEAD, but not of other refs
+        let mut analyzer = Analyzer::new(CodeRules::new());
+        let mut partial = Vec::new();
+        for git_ref in [None, Some("HEAD".to_owned())] {
+            let options = AnalysisOptions {
+                report_dir: test_dir
+                    .join("reports")
+                    .join(if git_ref.is_some() { "ref" } else { "head" }),
+                git_identities: vec!["dev1@example.com".to_owned()],
+                git_ref,
+                ..Default::default()
+            };
+            std::fs::create_dir_all(&options.report_dir).unwrap();
+            let outcome = analyzer.analyze_repo(&project_dir, &options).await.unwrap();
+            let coverage = outcome.project_report.coverage.unwrap();
+            partial.push((outcome.project_report.partial, coverage.sparse_checkout, coverage.files_analyzed));
+        }
+        assert_eq!(partial, vec![(true, true, 1), (false, false, 2)]);
+
+        let _ = std::fs::remove_dir_all(&test_dir);
+    }
+
+    #[tokio::test]
+    async fn test_analyze_log_is_current() {
+        let test_dir = std::env::temp_dir().join(format!("stm_analyzer_log_current_test_{}", std::process::id()));
+        let project_dir = test_dir.join("project");
+        let _ = std::fs::remove_dir_all(&test_dir);
+        std::fs::create_dir_all(project_dir.join("vendor")).unwrap();
+        run_git(&project_dir, "dev1@example.com", &["init", "-q"]);
+        std::fs::write(project_dir.join(".stackmuncher.toml"), "ignore_paths = [\"^vendor/\"]\n").unwrap();
+        std::fs::write(project_dir.join("main.rs"), "fn main() {}\n").unwrap();
+        run_git(&project_dir, "dev1@example.com", &["add", "-A"]);
+        run_git(&project_dir, "dev1@example.com", &["commit", "-q", "-m", "add"]);
+        // a commit to an ignored path is not in the log the report was built from
+        std::fs::write(project_dir.join("vendor/lib.rs"), "fn lib() {}\n").unwrap();
+        run_git(&project_dir, "dev1@example.com", &["add", "-A"]);
+        run_git(&project_dir, "dev1@example.com", &["commit", "-q", "-m", "vendor"]);
+        std::fs::write(project_dir.join("main.rs"), "fn main() {\n}\n").unwrap();
+        run_git(&project_dir, "dev1@example.com", &["commit", "-q", "-a", "-m", "update"]);
+
+        let mut analyzer = Analyzer::new(CodeRules::new());
+        for git_ref in [None, Some("HEAD~1".to_owned())] {
+            let options = AnalysisOptions {
+                report_dir: test_dir.join("reports").join(git_ref.as_deref().unwrap_or("head")),
+                git_identities: vec!["dev1@example.com".to_owned()],
+                git_ref,
+                ..Default::default()
+            };
+            std::fs::create_dir_all(&options.report_dir).unwrap();
+            let outcome = analyzer.analyze_repo(&project_dir, &options).await.unwrap();
+            assert!(is_log_current(&project_dir, &outcome.project_report).await.unwrap());
+        }
+
+        // a new commit makes the report of HEAD out of date
+        let options = AnalysisOptions {
+            report_dir: test_dir.join("reports/head"),
+            git_identities: vec!["dev1@example.com".to_owned()],
+            ..Default::default()
+        };
+        let outcome = analyzer.analyze_repo(&project_dir, &options).await.unwrap();
+        std::fs::write(project_dir.join("main.rs"), "fn main() {\n\n}\n").unwrap();
+        run_git(&project_dir, "dev1@example.com", &["commit", "-q", "-a", "-m", "update again"]);
+        assert!(!is_log_current(&project_dir, &outcome.project_report).await.unwrap());
+
+        let _ = std::fs::remove_dir_all(&test_dir);
+    }
+
+    #[tokio::test]
+    async fn test_analyze_tech_debt_markers_toggle() {
+        let test_dir = std::env::temp_dir().join(format!("stm_analyzer_markers_test_{}", std::process::id()));
+        let project_dir = test_dir.join("project");
+        let _ = std::fs::remove_dir_all(&test_dir);
+        std::fs::create_dir_all(&project_dir).unwrap();
+        run_git(&project_dir, "dev1@example.com", &["init", "-q"]);
+        std::fs::write(project_dir.join("main.rs"), "// TODO: add args\nfn main() {}\n").unwrap();
+        run_git(&project_dir, "dev1@example.com", &["add", "-A"]);
+        run_git(&project_dir, "dev1@example.com", &["commit", "-q", "-m", "add"]);
+
+        let options = AnalysisOptions {
+            report_dir: test_dir.join("reports"),
+            git_identities: vec!["dev1@example.com".to_owned()],
+            ..Default::default()
+        };
+        std::fs::create_dir_all(&options.report_dir).unwrap();
+
+        // the second run reuses the cached report of the first one with the markers turned on in the config
+        let mut analyzer = Analyzer::new(CodeRules::new());
+        let mut markers = Vec::new();
+        for tech_debt_markers in [false, true] {
+            analyzer.code_rules.munch_limits.tech_debt_markers = tech_debt_markers;
+            let outcome = analyzer.analyze_repo(&project_dir, &options).await.unwrap();
+            markers.push(
+                outcome
+                    .project_report
+                    .per_file_tech
+                    .iter()
+                    .map(|tech| tech.tech_debt_markers.len())
+                    .sum::<usize>(),
+            );
+        }
+        assert_eq!(markers, vec![0, 1]);
+
+        let _ = std::fs::remove_dir_all(&test_dir);
+    }
+
+    #[tokio::test]
+    async fn test_analyze_duplicate_files() {
+        let test_dir = std::env::temp_dir().join(format!("stm_analyzer_duplicates_test_{}", std::process::id()));
+        let project_dir = test_dir.join("project");
+        let _ = std::fs::remove_dir_all(&test_dir);
+        std::fs::create_dir_all(&project_dir).unwrap();
+        run_git(&project_dir, "dev1@example.com", &["init", "-q"]);
+        let contents = "fn lib() {\n    println!(\"lib\");\n}\n";
+
+        // `a/lib.rs` is the counted copy in the project report, but dev1 only committed `b/lib.rs`
+        for (email, file_name) in [("dev2@example.com", "a/lib.rs"), ("dev1@example.com", "b/lib.rs")] {
+            let file_path = project_dir.join(file_name);
+            std::fs::create_dir_all(file_path.parent().unwrap()).unwrap();
+            std::fs::write(file_path, contents).unwrap();
+            run_git(&project_dir, email, &["add", "-A"]);
+            run_git(&project_dir, email, &["commit", "-q", "-m", file_name]);
+        }
+
+        let options = AnalysisOptions {
+            report_dir: test_dir.join("reports"),
+            git_identities: vec!["dev1@example.com".to_owned()],
+            ..Default::default()
+        };
+        std::fs::create_dir_all(&options.report_dir).unwrap();
+
+        let outcome = Analyzer::new(CodeRules::new())
+            .analyze_repo(&project_dir, &options)
+            .await
+            .unwrap();
+        let rust_files = |report: &crate::report::Report| {
+            report
+                .tech
+                .iter()
+                .filter(|tech| tech.language == "Rust")
+                .map(|tech| tech.files)
+                .sum::<u64>()
+        };
+        assert_eq!(outcome.project_report.duplicate_files.as_ref().unwrap().copies, 1);
+        assert_eq!(rust_files(&outcome.project_report), 1);
+        assert_eq!(rust_files(&outcome.combined_report.unwrap()), 1);
+
+        let _ = std::fs::remove_dir_all(&test_dir);
+    }
+
+    #[tokio::test]
+    async fn test_analyze_empty_repo() {
+        let test_dir = std::env::temp_dir().join(format!("stm_analyzer_empty_test_{}", std::process::id()));
+        let project_dir = test_dir.join("project");
+        let _ = std::fs::remove_dir_all(&test_dir);
+        std::fs::create_dir_all(&project_dir).unwrap();
+        let status = std::process::Command::new("git")
+            .args(["init", "-q"])
+            .current_dir(&project_dir)
+            .status()
+            .unwrap();
+        assert!(status.success());
+
+        let options = AnalysisOptions {
+            report_dir: test_dir.join("reports"),
+            git_identities: vec!["dev1@example.com".to_owned()],
+            ..Default::default()
+        };
+        std::fs::create_dir_all(&options.report_dir).unwrap();
+
+        let outcome = Analyzer::new(CodeRules::new())
+            .analyze_repo(&project_dir, &options)
+            .await
+            .unwrap();
+        assert!(outcome.project_report.empty_repo);
+        assert!(outcome.project_report_file.exists());
+        assert!(outcome.combined_report.is_none());
+
+        // a branch ref pointing at a missing commit is a damaged repo, not an empty one
+        std::fs::write(project_dir.join("main.rs"), "fn main() {}\n").unwrap();
+        run_git(&project_dir, "dev1@example.com", &["add", "."]);
+        run_git(&project_dir, "dev1@example.com", &["commit", "-q", "-m", "add"]);
+        let output = std::process::Command::new("git")
+            .args(["symbolic-ref", "HEAD"])
+            .current_dir(&project_dir)
+            .output()
+            .unwrap();
+        let branch_ref = String::from_utf8_lossy(&output.stdout).trim().to_owned();
+        std::fs::write(project_dir.join(".git").join(branch_ref), "1234567890123456789012345678901234567890\n")
+            .unwrap();
+        assert!(!git::is_unborn_head(&project_dir).await);
+
+        let outcome = Analyzer::new(CodeRules::new())
+            .analyze_repo(&project_dir, &options)
+            .await;
+        assert_eq!(outcome.err(), Some(AnalysisError::Failed));
+
+        let _ = std::fs::remove_dir_all(&test_dir);
+    }
+}
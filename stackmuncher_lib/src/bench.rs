@@ -0,0 +1,518 @@
+//! Benchmarks of the hot paths on a synthetic repo with a predictable size and shape.
+//! Used by `benches/hot_paths.rs` and the hidden `stackmuncher bench` command so that the numbers
+//! reported by users are comparable with the baseline in `stackmuncher_lib/benches/BASELINE.md`.
+
+use crate::code_rules::CodeRules;
+use crate::git::{self, GitLogEntry, ListOfBlobs};
+use crate::report::Report;
+use crate::skip_list::SkipList;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::time::Instant;
+use tokio::io::AsyncWriteExt;
+use tokio::process::Command;
+use tracing::{debug, error, info};
+
+/// The size of a synthetic repo generated for benchmarking.
+#[derive(Debug, Clone, Copy)]
+pub struct SyntheticRepoSpec {
+    /// Number of files in the HEAD tree
+    pub files: usize,
+    /// Number of commits, including the initial one that adds all the files
+    pub commits: usize,
+    /// Number of distinct commit authors. Commits are assigned to them in turn.
+    pub contributors: usize,
+}
+
+impl Default for SyntheticRepoSpec {
+    fn default() -> Self {
+        Self {
+            files: 500,
+            commits: 1000,
+            contributors: 10,
+        }
+    }
+}
+
+/// Timing of a single benchmark.
+#[derive(Debug, Clone)]
+pub struct BenchResult {
+    pub name: &'static str,
+    pub iterations: u32,
+    pub mean_ms: f64,
+    pub min_ms: f64,
+}
+
+/// A hot path may be slower than its baseline by this many percent before it is reported as a regression.
+/// The fastest runs of the same build vary by up to 30% between runs for the hot paths that take a few ms.
+pub const DEFAULT_MAX_REGRESSION_PCT: usize = 50;
+
+/// Timings of the hot paths recorded with `stackmuncher bench --out` for checking later runs against,
+/// e.g. `stackmuncher_lib/benches/baseline.json`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct BenchBaseline {
+    /// The size of the synthetic repo the timings were taken on, see `SyntheticRepoSpec`.
+    pub files: usize,
+    pub commits: usize,
+    pub contributors: usize,
+    /// The fastest run of every hot path in ms by its name. The fastest run is less affected by other processes
+    /// than the mean.
+    pub min_ms: BTreeMap<String, f64>,
+}
+
+/// A hot path that got slower than its baseline by more than the allowed margin.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BenchRegression {
+    pub name: &'static str,
+    pub baseline_ms: f64,
+    pub min_ms: f64,
+}
+
+impl BenchRegression {
+    /// Returns how much slower the hot path got in percent of the baseline.
+    pub fn slowdown_pct(&self) -> f64 {
+        (self.min_ms / self.baseline_ms - 1.0) * 100.0
+    }
+}
+
+impl BenchBaseline {
+    /// Records the fastest runs from `results` taken on a repo generated with `spec`.
+    pub fn new(spec: &SyntheticRepoSpec, results: &[BenchResult]) -> Self {
+        Self {
+            files: spec.files,
+            commits: spec.commits,
+            contributors: spec.contributors,
+            min_ms: results
+                .iter()
+                // microseconds are well below the noise
+                .map(|result| (result.name.to_owned(), (result.min_ms * 1000.0).round() / 1000.0))
+                .collect(),
+        }
+    }
+
+    /// Returns the size of the repo the baseline was recorded on. Other sizes are not comparable.
+    pub fn spec(&self) -> SyntheticRepoSpec {
+        SyntheticRepoSpec {
+            files: self.files,
+            commits: self.commits,
+            contributors: self.contributors,
+        }
+    }
+
+    /// Returns the hot paths from `results` that are slower than the baseline by more than `max_regression_pct`
+    /// percent. Hot paths with no baseline timing are skipped.
+    pub fn regressions(&self, results: &[BenchResult], max_regression_pct: usize) -> Vec<BenchRegression> {
+        let max_ratio = 1.0 + max_regression_pct as f64 / 100.0;
+        results
+            .iter()
+            .filter_map(|result| {
+                let baseline_ms = *self.min_ms.get(result.name)?;
+                if baseline_ms > 0.0 && result.min_ms > baseline_ms * max_ratio {
+                    Some(BenchRegression {
+                        name: result.name,
+                        baseline_ms,
+                        min_ms: result.min_ms,
+                    })
+                } else {
+                    None
+                }
+            })
+            .collect()
+    }
+}
+
+/// Extensions of the generated files. They have to have munchers to exercise the munching path.
+const FILE_EXTENSIONS: [&str; 5] = ["rs", "py", "js", "cs", "md"];
+
+/// Number of files modified by every commit after the initial one.
+const FILES_PER_COMMIT: usize = 5;
+
+/// The epoch of the first synthetic commit. Subsequent commits are an hour apart.
+const FIRST_COMMIT_EPOCH: i64 = 1_600_000_000;
+
+/// A minimal deterministic PRNG so that the repo is the same on every machine.
+struct Lcg(u64);
+
+impl Lcg {
+    fn next(&mut self) -> usize {
+        self.0 = self
+            .0
+            .wrapping_mul(6364136223846793005)
+            .wrapping_add(1442695040888963407);
+        (self.0 >> 33) as usize
+    }
+}
+
+/// Returns the path of the n-th generated file, e.g. `src/mod_3/file_42.rs`.
+fn synthetic_file_name(n: usize) -> String {
+    let ext = FILE_EXTENSIONS[n % FILE_EXTENSIONS.len()];
+    format!("src/mod_{}/file_{}.{}", n / 50, n, ext)
+}
+
+/// Returns the contents of the file for the given revision. The size grows slightly with every revision.
+fn synthetic_file_contents(file_name: &str, revision: usize) -> String {
+    let mut contents = String::new();
+    let lines = 40 + revision % 20;
+    if file_name.ends_with(".rs") {
+        contents.push_str("use std::collections::HashMap;\nuse serde::{Deserialize, Serialize};\n\n");
+        for i in 0..lines {
+            contents.push_str(&format!(
+                "/// Doc comment {i}\npub fn func_{i}(v: &HashMap<String, u64>) -> Option<u64> {{\n    // line comment\n    v.get(\"k{i}\").copied()\n}}\n\n",
+                i = i
+            ));
+        }
+    } else if file_name.ends_with(".py") {
+        contents.push_str("import os\nfrom collections import defaultdict\n\n");
+        for i in 0..lines {
+            contents.push_str(&format!(
+                "# comment {i}\ndef func_{i}(v):\n    return os.path.join(v, \"{i}\")\n\n",
+                i = i
+            ));
+        }
+    } else if file_name.ends_with(".js") {
+        contents.push_str("import React from 'react';\nconst fs = require('fs');\n\n");
+        for i in 0..lines {
+            contents.push_str(&format!(
+                "// comment {i}\nexport function func{i}(v) {{\n  return v.map((x) => x + {i});\n}}\n\n",
+                i = i
+            ));
+        }
+    } else if file_name.ends_with(".cs") {
+        contents
+            .push_str("using System;\nusing System.Linq;\n\nnamespace Bench\n{\n    public class Synthetic\n    {\n");
+        for i in 0..lines {
+            contents.push_str(&format!(
+                "        /// <summary>Doc {i}</summary>\n        public static int Func{i}(int[] v) => v.Where(x => x > {i}).Count();\n\n",
+                i = i
+            ));
+        }
+        contents.push_str("    }\n}\n");
+    } else {
+        contents.push_str("# Synthetic\n\n");
+        for i in 0..lines {
+            contents.push_str(&format!("* item {i}\n", i = i));
+        }
+    }
+    contents.push_str(&format!("\n// revision {}\n", revision));
+    contents
+}
+
+/// Returns a `git fast-import` stream for the whole synthetic repo. Much faster than running git per commit.
+fn fast_import_stream(spec: &SyntheticRepoSpec) -> Vec<u8> {
+    let mut stream: Vec<u8> = Vec::new();
+    let mut rng = Lcg(42);
+    // the number of times each file was changed
+    let mut revisions = vec![0usize; spec.files];
+
+    for commit in 0..spec.commits.max(1) {
+        let contributor = commit % spec.contributors.max(1);
+        let epoch = FIRST_COMMIT_EPOCH + commit as i64 * 3600;
+        let msg = format!("Synthetic commit {}", commit);
+
+        stream.extend_from_slice(
+            format!(
+                "commit refs/heads/master\nmark :{}\nauthor Dev {c} <dev{c}@example.com> {e} +0000\ncommitter Dev {c} <dev{c}@example.com> {e} +0000\ndata {}\n{}\n",
+                commit + 1,
+                msg.len(),
+                msg,
+                c = contributor,
+                e = epoch
+            )
+            .as_bytes(),
+        );
+        if commit > 0 {
+            stream.extend_from_slice(format!("from :{}\n", commit).as_bytes());
+        }
+
+        // the first commit adds all the files, the rest modify a few random ones
+        let files = if commit == 0 {
+            (0..spec.files).collect::<Vec<usize>>()
+        } else {
+            (0..FILES_PER_COMMIT.min(spec.files))
+                .map(|_| rng.next() % spec.files)
+                .collect::<Vec<usize>>()
+        };
+
+        for file in files {
+            revisions[file] += 1;
+            let file_name = synthetic_file_name(file);
+            let contents = synthetic_file_contents(&file_name, revisions[file]);
+            stream.extend_from_slice(format!("M 100644 inline {}\ndata {}\n", file_name, contents.len()).as_bytes());
+            stream.extend_from_slice(contents.as_bytes());
+            stream.push(b'\n');
+        }
+    }
+
+    stream
+}
+
+/// Creates a git repo in `repo_dir` with the history defined by `spec`. The folder must not contain a repo.
+/// Only the git objects are created. There are no files in the working directory because the app doesn't read them.
+pub async fn generate_synthetic_repo(repo_dir: &Path, spec: &SyntheticRepoSpec) -> Result<(), ()> {
+    info!("Generating a synthetic repo in {}: {:?}", repo_dir.to_string_lossy(), spec);
+
+    if let Err(e) = std::fs::create_dir_all(repo_dir) {
+        error!("Cannot create {} due to {}", repo_dir.to_string_lossy(), e);
+        return Err(());
+    }
+
+    git::execute_git_command(vec!["init".into(), "--quiet".into()], repo_dir, false).await?;
+    git::execute_git_command(vec!["symbolic-ref".into(), "HEAD".into(), "refs/heads/master".into()], repo_dir, false)
+        .await?;
+
+    let mut child = match Command::new("git")
+        .args(["fast-import", "--quiet"])
+        .current_dir(repo_dir)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .spawn()
+    {
+        Ok(v) => v,
+        Err(e) => {
+            error!("Cannot start git fast-import due to {}", e);
+            return Err(());
+        }
+    };
+
+    if let Some(mut stdin) = child.stdin.take() {
+        if let Err(e) = stdin.write_all(&fast_import_stream(spec)).await {
+            error!("Cannot write to git fast-import due to {}", e);
+            return Err(());
+        }
+    }
+
+    match child.wait().await {
+        Ok(status) if status.success() => Ok(()),
+        Ok(status) => {
+            error!("git fast-import failed with {}", status);
+            Err(())
+        }
+        Err(e) => {
+            error!("git fast-import failed with {}", e);
+            Err(())
+        }
+    }
+}
+
+/// Pre-computed inputs for benchmarking individual hot paths without re-running the steps before them.
+pub struct BenchFixture {
+    pub repo_dir: PathBuf,
+    pub code_rules: CodeRules,
+    raw_log: String,
+    raw_tree: Vec<u8>,
+    git_log: Vec<GitLogEntry>,
+    all_project_blobs: ListOfBlobs,
+    project_report: Report,
+}
+
+impl BenchFixture {
+    /// Generates a synthetic repo in `repo_dir` and runs a full project analysis once to get the inputs for the benchmarks.
+    pub async fn new(repo_dir: &Path, spec: &SyntheticRepoSpec) -> Result<Self, ()> {
+        generate_synthetic_repo(repo_dir, spec).await?;
+
+        let mut code_rules = CodeRules::new();
+        let raw_log = git::get_raw_log(repo_dir, None, None, None).await?;
+        let git_log = git::parse_log(&raw_log, &code_rules.ignore_paths);
+        let raw_tree = git::execute_git_command(
+            vec![
+                "ls-tree".into(),
+                "-r".into(),
+                "-z".into(),
+                "--full-tree".into(),
+                "HEAD".into(),
+            ],
+            repo_dir,
+            false,
+        )
+        .await?;
+        let all_project_blobs = git::log_entries_to_list_of_blobs(&git_log);
+        let project_report = match Report::process_project(
+            &mut code_rules,
+            repo_dir,
+            None,
+            &None,
+            Some(git_log.clone()),
+            &mut SkipList::default(),
+        )
+        .await
+        .map_err(|_| ())?
+        {
+            Some(v) => v,
+            None => {
+                error!("No report was generated for the synthetic repo. It's a bug.");
+                return Err(());
+            }
+        };
+
+        Ok(Self {
+            repo_dir: repo_dir.to_path_buf(),
+            code_rules,
+            raw_log,
+            raw_tree,
+            git_log,
+            all_project_blobs,
+            project_report,
+        })
+    }
+
+    /// Parses the raw git log. Returns the number of commits.
+    pub fn log_parsing(&self) -> usize {
+        git::parse_log(&self.raw_log, &self.code_rules.ignore_paths).len()
+    }
+
+    /// Parses the raw HEAD tree listing and drops ignored files. Returns the number of files.
+    pub fn tree_listing(&self) -> usize {
+        git::tree_files_from_ls_tree(&self.raw_tree, &self.code_rules.ignore_paths).len()
+    }
+
+    /// Selects the latest revision of every file from the log. Returns the number of blobs.
+    pub fn blob_selection(&self) -> usize {
+        git::log_entries_to_list_of_blobs(&self.git_log).len()
+    }
+
+    /// Munches the entire repo from scratch. Returns the number of per-file records.
+    pub async fn munching(&mut self) -> Result<usize, ()> {
+        let report = Report::process_project(
+            &mut self.code_rules,
+            &self.repo_dir,
+            None,
+            &None,
+            Some(self.git_log.clone()),
+            &mut SkipList::default(),
+        )
+        .await
+        .map_err(|_| ())?;
+        Ok(report.map(|v| v.per_file_tech.len()).unwrap_or_default())
+    }
+
+    /// Copies all per-file records from the cached project report. Returns the number of records copied.
+    pub fn cache_copy(&mut self) -> usize {
+        let (_, copied) = Report::new().copy_cached_data_from_another_report(
+            &mut self.code_rules,
+            Some(&self.project_report),
+            &self.all_project_blobs,
+        );
+        copied.len()
+    }
+
+    /// Merges the project report into a combined report twice. Returns the number of tech records.
+    pub fn report_merge(&self) -> usize {
+        Report::merge_many(vec![self.project_report.clone(), self.project_report.clone()])
+            .map(|v| v.tech.len())
+            .unwrap_or_default()
+    }
+}
+
+/// Times `f` over `iterations` runs.
+fn time_it<F: FnMut()>(name: &'static str, iterations: u32, mut f: F) -> BenchResult {
+    let iterations = iterations.max(1);
+    let mut total_ms = 0f64;
+    let mut min_ms = f64::MAX;
+    for _ in 0..iterations {
+        let instant = Instant::now();
+        f();
+        let elapsed = instant.elapsed().as_secs_f64() * 1000.0;
+        total_ms += elapsed;
+        min_ms = min_ms.min(elapsed);
+    }
+
+    debug!("{}: {:.2}ms over {} iterations", name, total_ms, iterations);
+    BenchResult {
+        name,
+        iterations,
+        mean_ms: total_ms / iterations as f64,
+        min_ms,
+    }
+}
+
+/// Runs all hot path benchmarks on a synthetic repo generated in `repo_dir`. The repo is left in place.
+/// Munching is much slower than the rest and is run fewer times.
+pub async fn run(repo_dir: &Path, spec: &SyntheticRepoSpec, iterations: u32) -> Result<Vec<BenchResult>, ()> {
+    let mut fixture = BenchFixture::new(repo_dir, spec).await?;
+
+    let mut results = vec![
+        time_it("log_parsing", iterations, || {
+            fixture.log_parsing();
+        }),
+        time_it("tree_listing", iterations, || {
+            fixture.tree_listing();
+        }),
+        time_it("blob_selection", iterations, || {
+            fixture.blob_selection();
+        }),
+        time_it("cache_copy", iterations, || {
+            fixture.cache_copy();
+        }),
+        time_it("report_merge", iterations, || {
+            fixture.report_merge();
+        }),
+    ];
+
+    // async closures cannot be passed to time_it
+    let munching_iterations = (iterations / 10).max(1);
+    let mut total_ms = 0f64;
+    let mut min_ms = f64::MAX;
+    for _ in 0..munching_iterations {
+        let instant = Instant::now();
+        fixture.munching().await?;
+        let elapsed = instant.elapsed().as_secs_f64() * 1000.0;
+        total_ms += elapsed;
+        min_ms = min_ms.min(elapsed);
+    }
+    results.push(BenchResult {
+        name: "munching",
+        iterations: munching_iterations,
+        mean_ms: total_ms / munching_iterations as f64,
+        min_ms,
+    });
+
+    Ok(results)
+}
+
+#[test]
+fn test_bench_regressions() {
+    let result = |name: &'static str, min_ms: f64| BenchResult {
+        name,
+        iterations: 1,
+        mean_ms: min_ms,
+        min_ms,
+    };
+    let spec = SyntheticRepoSpec::default();
+    let baseline = BenchBaseline::new(&spec, &[result("log_parsing", 10.0), result("munching", 800.0)]);
+    assert_eq!(baseline.spec().files, spec.files);
+    assert_eq!(
+        serde_json::from_str::<BenchBaseline>(&serde_json::to_string(&baseline).unwrap()).unwrap(),
+        baseline
+    );
+
+    // within the margin, over it and with no baseline timing
+    let results = vec![
+        result("log_parsing", 12.4),
+        result("munching", 1200.0),
+        result("tree_listing", 5.0),
+    ];
+    let regressions = baseline.regressions(&results, 25);
+    assert_eq!(regressions.len(), 1);
+    assert_eq!(regressions[0].name, "munching");
+    assert_eq!(regressions[0].slowdown_pct().round(), 50.0);
+    assert_eq!(baseline.regressions(&results, 10).len(), 2);
+}
+
+#[test]
+fn test_fast_import_stream() {
+    let spec = SyntheticRepoSpec {
+        files: 10,
+        commits: 20,
+        contributors: 3,
+    };
+    let stream = String::from_utf8(fast_import_stream(&spec)).unwrap();
+
+    assert_eq!(stream.matches("commit refs/heads/master\n").count(), 20);
+    assert_eq!(stream.matches("author Dev 2 <dev2@example.com>").count(), 6);
+    // the initial commit adds all the files
+    assert_eq!(stream.matches("M 100644 inline ").count(), 10 + 19 * FILES_PER_COMMIT);
+    // the repo must be identical on every run
+    assert_eq!(stream, String::from_utf8(fast_import_stream(&spec)).unwrap());
+}
@@ -0,0 +1,263 @@
+//! Integrity checks of the cached reports in a project reports folder. Caches damaged by a crash or a power loss
+//! are either silently discarded, which forces a full reprocessing, or make the app fail on every run.
+use crate::config::Config;
+use crate::git_log_cache::{self, GIT_LOG_CACHE_DIR_NAME};
+use crate::ignore_paths;
+use crate::project_config::ProjectConfig;
+use crate::report::{migration, Report, Tech};
+use crate::skip_list::SKIP_LIST_FILE_NAME;
+use crate::utils::{self, TEMP_FILE_EXTENSION};
+use crate::vcs;
+use std::collections::HashSet;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use tracing::debug;
+
+/// What is wrong with a file or a folder in a project reports folder.
+#[derive(Debug, Clone, PartialEq)]
+pub enum CacheProblem {
+    /// A temp file left behind by a write that was interrupted, e.g. `.project_report.json.laptop.1234.tmp`.
+    TempFile,
+    /// The file cannot be read or parsed, e.g. it was partially written or truncated.
+    Corrupted(String),
+    /// The report is in an old format that cannot be upgraded and the project will be processed in full.
+    OutdatedFormat,
+    /// The cached git log was built from a different history than the project report, e.g. after an interrupted run.
+    LogCacheMismatch,
+}
+
+impl fmt::Display for CacheProblem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::TempFile => write!(f, "left over from an interrupted write"),
+            Self::Corrupted(e) => write!(f, "corrupted: {}", e),
+            Self::OutdatedFormat => write!(f, "outdated report format, the project will be processed in full"),
+            Self::LogCacheMismatch => write!(f, "git log cache is out of sync with the project report"),
+        }
+    }
+}
+
+/// A problem found by `check_report_dir`. Removing `path` fixes it. The data is regenerated on the next run.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CacheIssue {
+    /// A file or the git log cache folder.
+    pub path: PathBuf,
+    pub problem: CacheProblem,
+}
+
+impl CacheIssue {
+    /// Removes the file or the folder with the problem.
+    pub fn fix(&self) -> Result<(), std::io::Error> {
+        if self.path.is_dir() {
+            std::fs::remove_dir_all(&self.path)
+        } else {
+            std::fs::remove_file(&self.path)
+        }
+    }
+}
+
+/// Checks the reports and caches saved directly in `report_dir`. Sub-folders of other refs and dates are not checked.
+/// Returns an empty list if all files are intact or the folder cannot be listed.
+pub fn check_report_dir(report_dir: &Path) -> Vec<CacheIssue> {
+    let dir_entries = match std::fs::read_dir(report_dir) {
+        Ok(v) => v,
+        Err(e) => {
+            debug!("Cannot list {} due to {}", report_dir.to_string_lossy(), e);
+            return Vec::new();
+        }
+    };
+
+    let mut files = dir_entries
+        .filter_map(|v| v.ok())
+        .map(|v| v.path())
+        .filter(|v| v.is_file())
+        .collect::<Vec<PathBuf>>();
+    files.sort();
+
+    let mut issues: Vec<CacheIssue> = Vec::new();
+    let mut project_report: Option<Report> = None;
+    for file in files {
+        let file_name = file.file_name().unwrap_or_default().to_string_lossy().to_string();
+        let problem = if file_name.ends_with(TEMP_FILE_EXTENSION) {
+            Some(CacheProblem::TempFile)
+        } else if file_name.ends_with(".history.json") {
+            check_json::<HashSet<Tech>>(&file)
+        } else if is_report_file_name(&file_name) {
+            match check_report(&file) {
+                Ok(report) => {
+                    if file_name == [Config::PROJECT_REPORT_FILE_NAME, Config::REPORT_FILE_EXTENSION].concat() {
+                        project_report = Some(report);
+                    }
+                    None
+                }
+                Err(problem) => Some(problem),
+            }
+        } else if file_name == SKIP_LIST_FILE_NAME || file_name.ends_with(Config::REPORT_FILE_EXTENSION) {
+            check_json::<serde_json::Value>(&file)
+        } else {
+            None
+        };
+
+        if let Some(problem) = problem {
+            issues.push(CacheIssue { path: file, problem });
+        }
+    }
+
+    // the SQLite store keeps all reports in a single file, so any damage in it is fixed by removing the whole database
+    #[cfg(feature = "sqlite-store")]
+    match crate::report_store_sqlite::verify(report_dir) {
+        Ok(Some(report)) => {
+            if project_report.is_none() {
+                project_report = Some(report);
+            }
+        }
+        Ok(None) => {}
+        Err(e) => issues.push(CacheIssue {
+            path: report_dir.join(crate::report_store_sqlite::DATABASE_FILE_NAME),
+            problem: CacheProblem::Corrupted(e),
+        }),
+    }
+
+    // the log cache is only reused with the project report it was built with
+    let log_cache_dir = report_dir.join(GIT_LOG_CACHE_DIR_NAME);
+    match git_log_cache::verify(report_dir) {
+        Ok(Some(log_hash)) => {
+            if project_report.is_some_and(|report| report.log_hash.as_ref() != Some(&log_hash)) {
+                issues.push(CacheIssue {
+                    path: log_cache_dir,
+                    problem: CacheProblem::LogCacheMismatch,
+                });
+            }
+        }
+        Ok(None) => {}
+        Err(e) => issues.push(CacheIssue {
+            path: log_cache_dir,
+            problem: CacheProblem::Corrupted(e),
+        }),
+    }
+
+    issues
+}
+
+/// Returns true if `log_hash` of `report` matches the current log of the project at the ref the report was generated
+/// for, i.e. there were no new commits or history rewrites since then. Returns an error if the log cannot be read.
+pub async fn is_log_current(project_dir: &Path, report: &Report) -> Result<bool, ()> {
+    // must be calculated the same way as `Report.log_hash`, which excludes commits that only touched files ignored
+    // by default or in `.stackmuncher.toml`
+    let mut ignore_paths = ignore_paths::compile_ignore_paths();
+    ProjectConfig::from_repo(project_dir)?.extend_ignore_paths(&mut ignore_paths);
+
+    let vcs = vcs::detect(project_dir);
+    let rev = match &report.git_ref {
+        Some(git_ref) => Some(vcs.get_head_commit(project_dir, Some(git_ref)).await?),
+        None => None,
+    };
+    let git_log = vcs.get_log(project_dir, None, rev.as_deref(), &ignore_paths).await?;
+    let log_hash = utils::hash_vec_sha1(git_log.iter().map(|entry| entry.sha1.clone()).collect::<Vec<String>>());
+
+    Ok(report.log_hash.as_ref() == Some(&log_hash))
+}
+
+/// Returns true for the project, combined and contributor reports, e.g. `contributor_[hash].json`.
+fn is_report_file_name(file_name: &str) -> bool {
+    let report_file_names = [
+        Config::PROJECT_REPORT_FILE_NAME,
+        Config::CONTRIBUTOR_REPORT_COMBINED_FILE_NAME,
+    ]
+    .iter()
+    .map(|v| [v, Config::REPORT_FILE_EXTENSION].concat())
+    .collect::<Vec<String>>();
+
+    report_file_names.iter().any(|v| v == file_name)
+        || (file_name.starts_with(Config::CONTRIBUTOR_REPORT_FILE_NAME)
+            && file_name.ends_with(Config::REPORT_FILE_EXTENSION))
+}
+
+/// Loads the report the same way as the cache does. Reports in a format that cannot be upgraded are still loaded,
+/// but are replaced on the next run.
+fn check_report(file: &Path) -> Result<Report, CacheProblem> {
+    let contents = std::fs::read(file).map_err(|e| CacheProblem::Corrupted(e.to_string()))?;
+    let mut contents =
+        serde_json::from_slice::<serde_json::Value>(&contents).map_err(|e| CacheProblem::Corrupted(e.to_string()))?;
+    if !migration::upgrade(&mut contents) {
+        return Err(CacheProblem::OutdatedFormat);
+    }
+
+    serde_json::from_value::<Report>(contents).map_err(|e| CacheProblem::Corrupted(e.to_string()))
+}
+
+/// Returns a problem if the file cannot be read or deserialized into `T`.
+fn check_json<T: serde::de::DeserializeOwned>(file: &Path) -> Option<CacheProblem> {
+    let contents = match std::fs::read(file) {
+        Ok(v) => v,
+        Err(e) => return Some(CacheProblem::Corrupted(e.to_string())),
+    };
+
+    serde_json::from_slice::<T>(&contents)
+        .err()
+        .map(|e| CacheProblem::Corrupted(e.to_string()))
+}
+
+#[cfg(test)]
+mod test_cache_check {
+    use super::{check_report_dir, CacheProblem};
+    use crate::report::Report;
+
+    #[test]
+    fn test_check_report_dir() {
+        let report_dir = std::env::temp_dir().join(format!("stm_cache_check_test_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&report_dir);
+        std::fs::create_dir_all(&report_dir).unwrap();
+
+        Report::new().save_as_local_file(&report_dir.join("project_report.json"), false);
+        std::fs::write(report_dir.join("skip_list.json"), "{}").unwrap();
+        std::fs::write(report_dir.join("run.log"), "not json").unwrap();
+        assert!(check_report_dir(&report_dir).is_empty());
+
+        // a partial write and a temp file left behind by it
+        std::fs::write(report_dir.join("contributor_abc.json"), r#"{"tech":["#).unwrap();
+        std::fs::write(report_dir.join(".contributor_abc.json.laptop.1234.tmp"), "").unwrap();
+        std::fs::write(report_dir.join("combined_report.json"), r#"{"timestamp":"2021-10-01T00:00:00+00:00"}"#)
+            .unwrap();
+        let issues = check_report_dir(&report_dir);
+        assert_eq!(issues.len(), 3);
+        assert_eq!(issues[0].problem, CacheProblem::TempFile);
+        assert_eq!(issues[1].problem, CacheProblem::OutdatedFormat);
+        assert!(matches!(issues[2].problem, CacheProblem::Corrupted(_)));
+        assert!(issues[2].path.ends_with("contributor_abc.json"));
+
+        for issue in issues {
+            issue.fix().unwrap();
+        }
+        assert!(check_report_dir(&report_dir).is_empty());
+        assert!(report_dir.join("project_report.json").exists());
+
+        let _ = std::fs::remove_dir_all(&report_dir);
+    }
+
+    #[cfg(feature = "sqlite-store")]
+    #[test]
+    fn test_check_report_dir_sqlite() {
+        use crate::report_store::ReportStore;
+
+        let report_dir = std::env::temp_dir().join(format!("stm_cache_check_sqlite_test_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&report_dir);
+        std::fs::create_dir_all(&report_dir).unwrap();
+
+        ReportStore::Sqlite.save(&Report::new(), &report_dir.join("project_report.json"), false, false);
+        assert!(report_dir.join("reports.sqlite").exists());
+        assert!(check_report_dir(&report_dir).is_empty());
+
+        // a truncated database
+        std::fs::write(report_dir.join("reports.sqlite"), "SQLite format 3").unwrap();
+        let issues = check_report_dir(&report_dir);
+        assert_eq!(issues.len(), 1);
+        assert!(matches!(issues[0].problem, CacheProblem::Corrupted(_)));
+        assert!(issues[0].path.ends_with("reports.sqlite"));
+
+        issues[0].fix().unwrap();
+        assert!(check_report_dir(&report_dir).is_empty());
+
+        let _ = std::fs::remove_dir_all(&report_dir);
+    }
+}
@@ -1,9 +1,11 @@
 use super::file_type::FileType;
-use super::muncher::Muncher;
+use super::muncher::{MunchLimits, Muncher};
+use super::project_config::Duplicates;
 use regex::Regex;
 use rust_embed::RustEmbed;
-use std::collections::{BTreeMap, HashSet};
-use tracing::{debug, info, trace};
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+use std::path::Path;
+use tracing::{debug, error, info, trace};
 
 /// A container for embedded file_type rules
 #[derive(RustEmbed)]
@@ -15,6 +17,51 @@ struct EmbeddedCodeRulesFileTypes;
 #[folder = "stm_rules/munchers"]
 struct EmbeddedCodeRulesMunchers;
 
+/// Names of the sub-folders of a rules folder, e.g. `stm_rules/munchers`, same as the embedded ones.
+const FILE_TYPES_DIR: &str = "file_types";
+const MUNCHERS_DIR: &str = "munchers";
+
+/// A problem with a rules file found by `CodeRules::validate`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RulesError {
+    /// 1-based line number in the rules file, if the problem can be traced to a line.
+    pub line: Option<usize>,
+    /// The trimmed contents of that line.
+    pub context: Option<String>,
+    pub message: String,
+}
+
+/// Validation results for a single file-type or muncher file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RulesFileValidation {
+    /// Name of the rules file, e.g. `go.json`
+    pub file_name: String,
+    /// The hash stored in reports as `muncher_hash`. Munchers only. None if the file cannot be parsed.
+    pub muncher_hash: Option<u64>,
+    pub errors: Vec<RulesError>,
+}
+
+/// Results of `CodeRules::validate` for all rules files.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RulesValidation {
+    pub file_types: Vec<RulesFileValidation>,
+    pub munchers: Vec<RulesFileValidation>,
+    /// File extensions, or file names without an extension, e.g. `dockerfile`, with the names of munchers they map to.
+    /// Munchers with `in_path` conditions only apply to some of the files with that extension.
+    pub extensions: BTreeMap<String, Vec<String>>,
+}
+
+impl RulesValidation {
+    /// Returns the total number of errors in all files.
+    pub fn error_count(&self) -> usize {
+        self.file_types
+            .iter()
+            .chain(self.munchers.iter())
+            .map(|file| file.errors.len())
+            .sum()
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct CodeRules {
     /// All file types are added at init time
@@ -33,6 +80,19 @@ pub struct CodeRules {
 
     /// Compiled regex for file names and paths that should be ignored regardless of any other rules
     pub ignore_paths: Vec<Regex>,
+
+    /// Timeout and line length limits applied to every muncher
+    pub munch_limits: MunchLimits,
+
+    /// Names of munchers disabled in `.stackmuncher.toml` of the project. Files matched to them have no muncher.
+    pub disabled_munchers: HashSet<String>,
+
+    /// How files with identical contents under several paths are counted, set from `.stackmuncher.toml` of the project.
+    pub duplicates: Duplicates,
+
+    /// Compiled regex for file paths left out of contributor reports, but not the project report, set from
+    /// `AnalysisOptions.exclude_paths`.
+    pub contributor_ignore_paths: Vec<Regex>,
 }
 
 impl CodeRules {
@@ -59,9 +119,13 @@ impl CodeRules {
             // c:/dir/.bar -> bar
             // c:/dir/foo -> foo
             // dir\foo -> foo
-            file_ext_regex: Regex::new(r#"[\.\\/][a-zA-Z0-1_]+$|^[a-zA-Z0-1_]+$"#).unwrap(),
+            file_ext_regex: Regex::new(r#"[\.\\/][a-zA-Z0-9_]+$|^[a-zA-Z0-9_]+$"#).unwrap(),
             new_munchers: None,
             ignore_paths: crate::ignore_paths::compile_ignore_paths(),
+            munch_limits: MunchLimits::default(),
+            duplicates: Duplicates::default(),
+            disabled_munchers: HashSet::new(),
+            contributor_ignore_paths: Vec::new(),
         };
 
         // load the contents of file_type definitions one by one
@@ -81,57 +145,463 @@ impl CodeRules {
         code_rules
     }
 
+    /// Checks all file-type and muncher files for parsing errors, invalid regex and references to missing munchers.
+    /// Validates the embedded rules if `rules_dir` is None or the files from `file_types` and `munchers` sub-folders
+    /// of `rules_dir` otherwise, e.g. `stackmuncher_lib/stm_rules`. Unlike loading the rules for processing,
+    /// it does not stop at the first error.
+    /// Returns None if the files cannot be read.
+    pub fn validate(rules_dir: Option<&Path>) -> Option<RulesValidation> {
+        let file_types = read_rules_files::<EmbeddedCodeRulesFileTypes>(rules_dir, FILE_TYPES_DIR).ok()?;
+        let munchers = read_rules_files::<EmbeddedCodeRulesMunchers>(rules_dir, MUNCHERS_DIR).ok()?;
+
+        let mut validation = RulesValidation::default();
+
+        let muncher_names = munchers
+            .iter()
+            .map(|(file_name, _)| rules_name(file_name).to_owned())
+            .collect::<HashSet<String>>();
+
+        for (file_name, contents) in &munchers {
+            validation
+                .munchers
+                .push(validate_muncher(file_name, contents, &muncher_names));
+        }
+
+        for (file_name, contents) in &file_types {
+            let mut file_validation = RulesFileValidation {
+                file_name: file_name.clone(),
+                muncher_hash: None,
+                errors: Vec::new(),
+            };
+
+            let file_type = match serde_json::from_str::<FileType>(contents) {
+                Ok(v) => v,
+                Err(e) => {
+                    file_validation.errors.push(json_error(contents, &e));
+                    validation.file_types.push(file_validation);
+                    continue;
+                }
+            };
+
+            let ext = rules_name(file_name).to_lowercase();
+            for file_type_match in file_type.matches.unwrap_or_default() {
+                match &file_type_match.muncher {
+                    Some(muncher_name) => {
+                        if !muncher_names.contains(muncher_name) {
+                            file_validation.errors.push(line_error(
+                                contents,
+                                muncher_name,
+                                format!("muncher `{}` does not exist", muncher_name),
+                            ));
+                        }
+                        let ext_munchers = validation.extensions.entry(ext.clone()).or_default();
+                        if !ext_munchers.contains(muncher_name) {
+                            ext_munchers.push(muncher_name.clone());
+                        }
+                    }
+                    None => file_validation.errors.push(RulesError {
+                        line: None,
+                        context: None,
+                        message: "a match has no muncher name".to_owned(),
+                    }),
+                }
+
+                for in_path in file_type_match.in_path.iter().flatten() {
+                    if let Err(e) = Regex::new(in_path) {
+                        file_validation
+                            .errors
+                            .push(line_error(contents, in_path, format!("in_path: {}", e)));
+                    }
+                }
+            }
+
+            validation.file_types.push(file_validation);
+        }
+
+        Some(validation)
+    }
+
+    /// Returns the muncher with `muncher_name`, e.g. `rust.rs`, and the munchers it names as `host_muncher`
+    /// or `kernel_munchers` from `rules_dir` or from the embedded rules if `rules_dir` is None.
+    /// It is for munching sample files with a particular muncher, unlike `get_munchers` that picks them by the file name.
+    /// Returns None if the muncher does not exist or cannot be loaded. Missing host munchers are skipped.
+    pub fn load_muncher_with_hosts(rules_dir: Option<&Path>, muncher_name: &str) -> Option<(Muncher, Vec<Muncher>)> {
+        let munchers = read_rules_files::<EmbeddedCodeRulesMunchers>(rules_dir, MUNCHERS_DIR).ok()?;
+        let load = |name: &str| {
+            let (_, contents) = munchers.iter().find(|(file_name, _)| rules_name(file_name) == name)?;
+            Muncher::new(contents, &name.to_owned())
+        };
+
+        let muncher = load(muncher_name)?;
+        let host_munchers = muncher
+            .host_muncher
+            .iter()
+            .chain(muncher.kernel_munchers.iter().flat_map(|v| v.values()))
+            .collect::<BTreeSet<&String>>()
+            .into_iter()
+            .filter_map(|name| load(name))
+            .collect::<Vec<Muncher>>();
+
+        Some((muncher, host_munchers))
+    }
+
     /// Return the right muncher for the file extension extracted from the full path.
     pub fn get_muncher(&mut self, file_path: &String) -> Option<&Muncher> {
         debug!("Getting a muncher for: {}", file_path);
+        match self.find_muncher_name(file_path) {
+            Some(muncher_name) if self.disabled_munchers.contains(&muncher_name) => {
+                debug!("Muncher {} is disabled for {}", muncher_name, file_path);
+                None
+            }
+            Some(muncher_name) => self.load_muncher(&muncher_name),
+            None => {
+                debug!("No muncher found for {}", file_path);
+                None
+            }
+        }
+    }
+
+    /// Same as `get_muncher`, but also returns the muncher for the host language of template files, e.g. `html` for
+    /// `index.html.erb`. The host language comes from the extension before the template one or from `host_muncher`
+    /// of the template muncher if there is no such extension, e.g. `index.hbs`.
+    /// There is no host muncher for non-template files and for host languages with no muncher, e.g. `config.yaml.j2`.
+    /// Notebooks get all their `kernel_munchers` as host munchers because the kernel is only known from the file contents.
+    pub fn get_munchers(&mut self, file_path: &String) -> Option<(&Muncher, Vec<&Muncher>)> {
+        let muncher = self.get_muncher(file_path)?;
+        let muncher_name = muncher.muncher_name.clone();
+        let host_muncher_names = if muncher.is_template() {
+            let default_host_muncher_name = muncher.host_muncher.clone();
+            self.find_host_muncher_name(file_path, default_host_muncher_name)
+                .into_iter()
+                .collect::<BTreeSet<String>>()
+        } else if let Some(kernel_munchers) = &muncher.kernel_munchers {
+            let kernel_muncher_names = kernel_munchers.values().cloned().collect::<BTreeSet<String>>();
+            for kernel_muncher_name in &kernel_muncher_names {
+                self.load_muncher(kernel_muncher_name);
+            }
+            kernel_muncher_names
+        } else {
+            BTreeSet::new()
+        };
+
+        let munchers = &self.munchers;
+        let muncher = munchers.get(&muncher_name)?.as_ref()?;
+        let host_munchers = host_muncher_names
+            .iter()
+            .filter(|name| !self.disabled_munchers.contains(*name))
+            .filter_map(|name| munchers.get(name))
+            .filter_map(|muncher| muncher.as_ref())
+            .collect::<Vec<&Muncher>>();
+
+        Some((muncher, host_munchers))
+    }
+
+    /// Returns the name of the muncher for the inner file name of a template, e.g. `html` for `views/index.html.erb`,
+    /// or `default_host_muncher_name` if the inner name has no extension. Templates of templates, e.g. `new.erb.tt`,
+    /// also fall back to the default. The muncher is loaded if needed.
+    fn find_host_muncher_name(&mut self, file_path: &str, default_host_muncher_name: Option<String>) -> Option<String> {
+        // e.g. `views/index.html.erb` -> `views/index.html`
+        let inner_file_path = &file_path[..self.file_ext_regex.find(file_path)?.start()];
+
+        let host_muncher_name = match self.find_muncher_name(inner_file_path) {
+            Some(v) => v,
+            None => match self.get_file_ext(inner_file_path) {
+                // a known file type without a muncher is not counted as the default host language
+                Some(ext) if self.files_types.contains_key(&ext) => return None,
+                _ => default_host_muncher_name.clone()?,
+            },
+        };
+
+        match self.load_muncher(&host_muncher_name) {
+            Some(v) if v.is_template() => {
+                let default_host_muncher_name = default_host_muncher_name?;
+                self.load_muncher(&default_host_muncher_name)?;
+                Some(default_host_muncher_name)
+            }
+            Some(_) => Some(host_muncher_name),
+            None => None,
+        }
+    }
+
+    /// Returns the name of the muncher for the file from its file-type rules.
+    fn find_muncher_name(&self, file_path: &str) -> Option<String> {
         // try to get file extension or the file name if it has no extension like Dockerfile
-        if let Some(ext) = self.file_ext_regex.find(&file_path) {
-            // the file ext regex returns the ext with the separator, which is a ., but if the file has no extension it returns
-            // the file name with the leading separator, which can be / or \
-            // if the file has chars outside what the regex expects in a valid ext or file name it returns nothing
-            let ext = ext
-                .as_str()
-                .trim_start_matches(".")
-                .trim_start_matches("\\")
-                .trim_start_matches("/")
-                .to_lowercase();
-            debug!("Extracted file extension: {}", ext);
-            // try to find a file_type match for the ext
-            if let Some(file_type) = self.files_types.get(&ext) {
-                debug!("Matching file-type: {}", file_type.file_ext);
-                // try to find a matching muncher
-                if let Some(muncher_name) = file_type.get_muncher_name(file_path) {
-                    // load the muncher from its file on the first use
-                    if !self.munchers.contains_key(&muncher_name) {
-                        // all muncher definition files have .json ext
-                        let muncher_file_name = [&muncher_name, ".json"].concat();
-                        trace!("Loading muncher {} for the 1st time", muncher_file_name);
-
-                        let contents = EmbeddedCodeRulesMunchers::get(&muncher_file_name)
-                            .expect(format!("Missing embedded muncher contents: {}", muncher_file_name).as_str());
-                        let contents = std::str::from_utf8(contents.data.as_ref())
-                            .expect(format!("Invalid muncher contents: {}", muncher_file_name).as_str());
-
-                        // Insert None if the muncher could not be loaded so that it doesn't try to load it again
-                        self.munchers
-                            .insert(muncher_name.clone(), Muncher::new(contents, &muncher_name));
-
-                        // indicate to the caller that there were new munchers added so they can be shared with other threads
-                        if self.new_munchers.is_none() {
-                            self.new_munchers = Some(HashSet::new());
-                        }
-                        self.new_munchers.as_mut().unwrap().insert(muncher_name.clone());
-                    }
+        let ext = self.get_file_ext(file_path)?;
 
-                    return self.munchers.get(&muncher_name).unwrap().as_ref();
-                }
-            } else {
+        // try to find a file_type match for the ext
+        let file_type = match self.files_types.get(&ext) {
+            Some(v) => v,
+            None => {
                 debug!("File-type is unknown");
+                return None;
+            }
+        };
+        debug!("Matching file-type: {}", file_type.file_ext);
+
+        // try to find a matching muncher
+        file_type
+            .get_muncher_match(file_path)
+            .map(|(_, muncher_name, _)| muncher_name.to_owned())
+    }
+
+    /// Returns the muncher by its name, e.g. `rust.rs`. It is loaded from its file on the first use.
+    /// Returns None if the muncher could not be loaded.
+    fn load_muncher(&mut self, muncher_name: &str) -> Option<&Muncher> {
+        if !self.munchers.contains_key(muncher_name) {
+            // all muncher definition files have .json ext
+            let muncher_file_name = [muncher_name, ".json"].concat();
+            trace!("Loading muncher {} for the 1st time", muncher_file_name);
+
+            let contents = EmbeddedCodeRulesMunchers::get(&muncher_file_name)
+                .expect(format!("Missing embedded muncher contents: {}", muncher_file_name).as_str());
+            let contents = std::str::from_utf8(contents.data.as_ref())
+                .expect(format!("Invalid muncher contents: {}", muncher_file_name).as_str());
+
+            // Insert None if the muncher could not be loaded so that it doesn't try to load it again
+            self.munchers
+                .insert(muncher_name.to_owned(), Muncher::new(contents, &muncher_name.to_owned()));
+
+            // indicate to the caller that there were new munchers added so they can be shared with other threads
+            if self.new_munchers.is_none() {
+                self.new_munchers = Some(HashSet::new());
+            }
+            self.new_munchers.as_mut().unwrap().insert(muncher_name.to_owned());
+        }
+
+        self.munchers.get(muncher_name).unwrap().as_ref()
+    }
+
+    /// Returns the lower-case file extension or the file name if it has no extension, e.g. `rs` or `dockerfile`.
+    /// The extension is the key for `files_types`.
+    pub fn get_file_ext(&self, file_path: &str) -> Option<String> {
+        // the file ext regex returns the ext with the separator, which is a ., but if the file has no extension it returns
+        // the file name with the leading separator, which can be / or \
+        // if the file has chars outside what the regex expects in a valid ext or file name it returns nothing
+        let ext = self
+            .file_ext_regex
+            .find(file_path)?
+            .as_str()
+            .trim_start_matches(".")
+            .trim_start_matches("\\")
+            .trim_start_matches("/")
+            .to_lowercase();
+        debug!("Extracted file extension: {}", ext);
+
+        Some(ext)
+    }
+}
+
+/// Returns the names and contents of `.json` files from `sub_dir` of `rules_dir` or from the embedded rules
+/// if `rules_dir` is None, sorted by name.
+fn read_rules_files<T: RustEmbed>(rules_dir: Option<&Path>, sub_dir: &str) -> Result<Vec<(String, String)>, ()> {
+    let mut files: Vec<(String, String)> = Vec::new();
+
+    match rules_dir {
+        None => {
+            for file_name in T::iter().filter(|file_name| file_name.ends_with(".json")) {
+                let contents = T::get(&file_name).expect("Missing embedded rules file contents. It's a bug.");
+                files.push((file_name.to_string(), String::from_utf8_lossy(contents.data.as_ref()).to_string()));
+            }
+        }
+        Some(rules_dir) => {
+            let dir = rules_dir.join(sub_dir);
+            let entries = match std::fs::read_dir(&dir) {
+                Ok(v) => v,
+                Err(e) => {
+                    error!("Cannot read rules folder {} due to {}", dir.to_string_lossy(), e);
+                    return Err(());
+                }
+            };
+            for entry in entries.flatten() {
+                let file_name = entry.file_name().to_string_lossy().to_string();
+                if !file_name.ends_with(".json") {
+                    continue;
+                }
+                match std::fs::read_to_string(entry.path()) {
+                    Ok(contents) => files.push((file_name, contents)),
+                    Err(e) => {
+                        error!("Cannot read rules file {} due to {}", entry.path().to_string_lossy(), e);
+                        return Err(());
+                    }
+                }
             }
         }
+    }
+
+    files.sort_unstable_by(|a, b| a.0.cmp(&b.0));
+    Ok(files)
+}
+
+/// Returns the name of the rules file without `.json`, which is the muncher name or the file extension.
+fn rules_name(file_name: &str) -> &str {
+    file_name.strip_suffix(".json").unwrap_or(file_name)
+}
+
+/// Parses the muncher and compiles all its regex strings, including keywords that are skipped silently during processing.
+/// * `muncher_names` - names of all munchers for checking `host_muncher`
+fn validate_muncher(file_name: &str, contents: &str, muncher_names: &HashSet<String>) -> RulesFileValidation {
+    let mut validation = RulesFileValidation {
+        file_name: file_name.to_owned(),
+        muncher_hash: None,
+        errors: Vec::new(),
+    };
+
+    let mut muncher = match serde_json::from_str::<Muncher>(contents) {
+        Ok(v) => v,
+        Err(e) => {
+            validation.errors.push(json_error(contents, &e));
+            return validation;
+        }
+    };
+    muncher.muncher_name = rules_name(file_name).to_owned();
+    validation.muncher_hash = Some(muncher.rules_hash());
+
+    for (property, regex) in muncher.regex_sources() {
+        if let Err(e) = Muncher::compile_regex(regex) {
+            validation
+                .errors
+                .push(line_error(contents, regex, format!("{}: {}", property, e)));
+        }
+    }
+
+    if let Some(host_muncher) = &muncher.host_muncher {
+        if !muncher_names.contains(host_muncher) {
+            validation.errors.push(line_error(
+                contents,
+                host_muncher,
+                format!("host_muncher `{}` does not exist", host_muncher),
+            ));
+        }
+    }
+
+    for kernel_muncher in muncher.kernel_munchers.iter().flat_map(|v| v.values()) {
+        if !muncher_names.contains(kernel_muncher) {
+            validation.errors.push(line_error(
+                contents,
+                kernel_muncher,
+                format!("kernel_munchers: `{}` does not exist", kernel_muncher),
+            ));
+        }
+    }
+
+    validation
+}
+
+/// Converts a JSON parsing error into `RulesError` with the line it points at.
+fn json_error(contents: &str, e: &serde_json::Error) -> RulesError {
+    RulesError {
+        line: Some(e.line()),
+        context: contents
+            .lines()
+            .nth(e.line().saturating_sub(1))
+            .map(|v| v.trim().to_owned()),
+        message: e.to_string(),
+    }
+}
+
+/// Returns an error pointing at the first line with `value` as a JSON string, if any.
+fn line_error(contents: &str, value: &str, message: String) -> RulesError {
+    // the value is searched for in its escaped form, e.g. `\\.rs$` for `\.rs$`
+    let json_value = serde_json::to_string(value).unwrap_or_default();
+    let line = contents
+        .lines()
+        .enumerate()
+        .find(|(_, line)| line.contains(&json_value));
+
+    RulesError {
+        line: line.map(|(idx, _)| idx + 1),
+        context: line.map(|(_, line)| line.trim().to_owned()),
+        message,
+    }
+}
+
+#[cfg(test)]
+mod test_code_rules {
+    use super::CodeRules;
+    use crate::muncher::Muncher;
+
+    #[test]
+    fn test_validate() {
+        // the embedded rules must always be valid
+        let validation = CodeRules::validate(None).unwrap();
+        assert_eq!(validation.error_count(), 0);
+        assert!(validation.munchers.iter().all(|muncher| muncher.muncher_hash.is_some()));
+        assert!(validation.extensions["rs"].contains(&"rust.rs".to_owned()));
+
+        // the hash must be the same as the one stored in reports
+        let go = validation.munchers.iter().find(|v| v.file_name == "go.json").unwrap();
+        let muncher = Muncher::new(include_str!("../stm_rules/munchers/go.json"), &"go".to_owned()).unwrap();
+        assert_eq!(go.muncher_hash, Some(muncher.muncher_hash));
+
+        let rules_dir = std::env::temp_dir().join(format!("stm_rules_test_{}", std::process::id()));
+        std::fs::create_dir_all(rules_dir.join("munchers")).unwrap();
+        std::fs::create_dir_all(rules_dir.join("file_types")).unwrap();
+        std::fs::write(
+            rules_dir.join("munchers/broken.json"),
+            "{\n  \"language\": \"Broken\",\n  \"refs\": [\"^use (\"]\n}",
+        )
+        .unwrap();
+        std::fs::write(rules_dir.join("munchers/bad_json.json"), "{\n  \"language\": \n}").unwrap();
+        std::fs::write(
+            rules_dir.join("file_types/brk.json"),
+            "{\"matches\": [{\"muncher\": \"broken\"}, {\"muncher\": \"missing\"}]}",
+        )
+        .unwrap();
+
+        let validation = CodeRules::validate(Some(&rules_dir)).unwrap();
+        assert_eq!(validation.error_count(), 3);
+
+        let bad_json = &validation.munchers[0];
+        assert_eq!(bad_json.file_name, "bad_json.json");
+        assert!(bad_json.muncher_hash.is_none());
+        assert_eq!(bad_json.errors[0].line, Some(3));
+
+        let broken = &validation.munchers[1];
+        assert!(broken.muncher_hash.is_some());
+        assert_eq!(broken.errors[0].line, Some(3));
+        assert!(broken.errors[0].message.starts_with("refs: "));
+
+        assert!(validation.file_types[0].errors[0].message.contains("`missing`"));
+        assert_eq!(validation.extensions["brk"], vec!["broken".to_owned(), "missing".to_owned()]);
+
+        assert!(CodeRules::validate(Some(&rules_dir.join("nowhere"))).is_none());
+
+        let _ = std::fs::remove_dir_all(&rules_dir);
+    }
+
+    #[test]
+    fn test_get_munchers() {
+        let mut code_rules = CodeRules::new();
+        let mut munchers = |file_path: &str| {
+            code_rules
+                .get_munchers(&file_path.to_owned())
+                .map(|(muncher, host)| (muncher.muncher_name.clone(), host.first().map(|v| v.muncher_name.clone())))
+        };
+
+        // the host language comes from the inner extension
+        assert_eq!(munchers("views/index.html.erb"), Some(("ruby.erb".to_owned(), Some("html".to_owned()))));
+        assert_eq!(munchers("templates/base.html.j2"), Some(("jinja".to_owned(), Some("html".to_owned()))));
+        // or from the template muncher if there is no inner extension
+        assert_eq!(munchers("views/page.hbs"), Some(("handlebars".to_owned(), Some("html".to_owned()))));
+        // a known file type without a muncher is not counted as the host
+        assert_eq!(munchers("deploy/config.yaml.j2"), Some(("jinja".to_owned(), None)));
+        // non-template files have no host
+        assert_eq!(munchers("src/main.rs"), Some(("rust.rs".to_owned(), None)));
+        assert!(munchers("README").is_none());
 
-        debug!("No muncher found for {}", file_path);
+        // ops files are matched by their location, the most specific match wins
+        assert_eq!(munchers(".gitlab-ci.yml"), Some(("cicd.gitlab.yml".to_owned(), None)));
+        assert_eq!(munchers(".circleci/config.yml"), Some(("cicd.circleci.yml".to_owned(), None)));
+        assert_eq!(munchers("deploy/k8s/deployment.yaml"), Some(("k8s.yml".to_owned(), None)));
+        assert_eq!(munchers("charts/api/templates/service.yaml"), Some(("helm.yml".to_owned(), None)));
+        assert_eq!(munchers("roles/web/tasks/main.yml"), Some(("ansible.yml".to_owned(), None)));
+        assert_eq!(munchers("infra/prod.tfvars"), Some(("terraform".to_owned(), None)));
+        assert!(munchers("config/settings.yml").is_none());
 
-        None
+        // notebooks get all their kernel munchers
+        let (muncher, kernel_munchers) = code_rules.get_munchers(&"notebooks/eda.ipynb".to_owned()).unwrap();
+        assert!(muncher.is_notebook());
+        assert!(kernel_munchers.iter().any(|v| v.muncher_name == "python.py"));
+        assert!(kernel_munchers.iter().all(|v| !v.is_notebook() && !v.is_template()));
     }
 }
@@ -26,8 +26,11 @@ impl Config {
     pub const CONTRIBUTOR_REPORT_FILE_NAME: &'static str = "contributor_";
     pub const CONTRIBUTOR_REPORT_COMBINED_FILE_NAME: &'static str = "combined_report";
     pub const CONTRIBUTOR_REPORT_SANITIZED_FILE_NAME: &'static str = "submission";
+    /// The exact payload of the submission before compression, saved only if requested by the user.
+    pub const SUBMISSION_PAYLOAD_FILE_NAME: &'static str = "submission_payload";
     pub const REPORT_FILE_EXTENSION: &'static str = ".json";
     pub const GIT_FOLDER_NAME: &'static str = ".git";
+    pub const HG_FOLDER_NAME: &'static str = ".hg";
 
     /// Returns a minimal version of Self with no validation and default values.
     /// It compiles some regex and should be cached
@@ -5,6 +5,7 @@ use std::collections::{HashMap, HashSet};
 /// A GIT author or committer. E.g. `Author: rimutaka <max@onebro.me>` from `git log`.
 /// It contains extended info like what was committed, when, contact details.
 #[derive(Serialize, Deserialize, Debug, Clone)]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
 pub struct Contributor {
     /// Email is the preferred ID, but it can be just the name if the email is missing, e.g. `max@onebro.me` for `Author: rimutaka <max@onebro.me>`
     ///
@@ -22,17 +23,29 @@ pub struct Contributor {
     pub last_commit_date: String,
     /// The number of commits by the contributor.
     /// This can be taken from counting the number of entries in `commits` property, but that may be capped
-    /// in the future, so it's easier to have a separate counter.
+    /// in the future and commits with invalid SHA1 are not listed there, so it's easier to have a separate counter.
     #[serde(default)]
     pub commit_count: u64,
     /// The list of files touched by this contributor as FileName/CommitSHA1 tuple.
     pub touched_files: HashSet<ContributorFile>,
-    /// A list of pointers at contributor commits in recent project commits member of Report.
-    #[serde(skip_serializing_if = "Vec::is_empty", default = "Vec::new")]
-    pub commits: Vec<u64>,
+    /// Contributor commits in the same format as `Report.recent_project_commits`, e.g. `e29d17e6_1627380297`, newest first.
+    /// They were indexes into `recent_project_commits` before `2021-12-01T00:00:00+00:00` report format.
+    #[serde(skip_serializing_if = "Vec::is_empty", default)]
+    pub commits: Vec<String>,
+}
+
+/// A contributor whose report could not be generated. The reports they are part of are incomplete.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
+pub struct FailedContributor {
+    /// Same as `Contributor::git_id`
+    pub git_id: String,
+    /// A human-readable explanation of the failure with a hint on how to fix it, if known.
+    pub reason: String,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone, Eq)]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
 pub struct ContributorFile {
     /// The file name extracted from GIT, including the relative path, e.g. `myproject/src/main.rs`
     pub name: String,
@@ -72,7 +85,7 @@ impl Contributor {
         // each contributor has a hashmap with file as the key and commit/date/timestamp tuple that gets converted into an Vec for touched_files property
         let mut contributors: HashMap<String, (Contributor, HashMap<String, (String, String, i64)>)> = HashMap::new();
 
-        for (commit_idx, commit) in commits.into_iter().enumerate() {
+        for commit in commits {
             // skip commits with no author details
             if commit.author_name_email.0.is_empty() && commit.author_name_email.1.is_empty() {
                 continue;
@@ -80,6 +93,8 @@ impl Contributor {
 
             // choose the preferred identity for this contributor
             let git_identity = Self::git_identity_from_name_email_pair(&commit.author_name_email);
+            // a stable reference to the commit, e.g. `e29d17e6_1627380297`
+            let commit_ref = commit.join_commit_with_ts();
 
             // check if the contributor is already in the output collector
             if let Some((contributor, touched_files)) = contributors.get_mut(&git_identity) {
@@ -96,8 +111,10 @@ impl Contributor {
                 }
 
                 // add the commit to the list of contributor commits
-                // the cast should be safe because the max number of commits within a project is well within u64 bounds
-                contributor.commits.push(commit_idx as u64);
+                if let Some(commit_ref) = commit_ref {
+                    contributor.commits.push(commit_ref);
+                }
+                contributor.commit_count += 1;
             } else {
                 // it's a new contributor - add as-is
 
@@ -114,7 +131,7 @@ impl Contributor {
                 }
 
                 // add the commit to the list of contributor commits
-                let contr_commits_list = vec![commit_idx as u64];
+                let contr_commits_list = commit_ref.into_iter().collect::<Vec<String>>();
 
                 // init the contributor
                 let contributor = Contributor {
@@ -147,12 +164,12 @@ impl Contributor {
                 })
                 .collect::<HashSet<ContributorFile>>();
 
-            // this line will need to move if the list of commits is capped
-            contributor.commit_count = contributor.commits.len() as u64;
-
             output_collector.push(contributor);
         }
 
+        // the hashmap has no particular order, but the reports should not change between runs if the history didn't
+        output_collector.sort_unstable_by(|a, b| b.commit_count.cmp(&a.commit_count).then(a.git_id.cmp(&b.git_id)));
+
         output_collector
     }
 
@@ -165,3 +182,45 @@ impl Contributor {
         }
     }
 }
+
+#[test]
+fn test_contributor_commits() {
+    // newest first, as returned by git log
+    let log_entry = |sha1: &str, date_epoch: i64, email: &str| {
+        let mut entry = GitLogEntry::new();
+        entry.sha1 = format!("{:0<40}", sha1);
+        entry.date_epoch = date_epoch;
+        entry.author_name_email = ("dev".to_owned(), email.to_owned());
+        entry
+    };
+    let git_log = vec![
+        log_entry("a5", 1627380500, "a@example.com"),
+        log_entry("b4", 1627380400, "b@example.com"),
+        log_entry("a3", 1627380300, "a@example.com"),
+        log_entry("c2", 1627380200, "c@example.com"),
+        log_entry("b1", 1627380100, "b@example.com"),
+    ];
+
+    let contributors = Contributor::from_commit_history(git_log.clone());
+    let git_ids = contributors.iter().map(|c| c.git_id.as_str()).collect::<Vec<&str>>();
+    // the most commits first, then by git_id
+    assert_eq!(git_ids, vec!["a@example.com", "b@example.com", "c@example.com"]);
+    assert_eq!(contributors[1].commits, vec!["b4000000_1627380400", "b1000000_1627380100"]);
+    assert_eq!(contributors[1].commit_count, 2);
+
+    // a commit with an invalid SHA1 is counted, but doesn't shift the other commits
+    let mut git_log_invalid = git_log.clone();
+    git_log_invalid[0].sha1 = "invalid".to_owned();
+    let contributors_invalid = Contributor::from_commit_history(git_log_invalid);
+    assert_eq!(contributors_invalid[0].commits, vec!["a3000000_1627380300"]);
+    assert_eq!(contributors_invalid[0].commit_count, 2);
+    assert_eq!(contributors_invalid[1].commits, contributors[1].commits);
+
+    // a truncated or rewritten history doesn't change the refs to the remaining commits
+    let mut git_log_rewritten = git_log[1..].to_vec();
+    git_log_rewritten[1].sha1 = format!("{:0<40}", "d3");
+    let contributors_rewritten = Contributor::from_commit_history(git_log_rewritten);
+    assert_eq!(contributors_rewritten[0].git_id, "b@example.com");
+    assert_eq!(contributors_rewritten[0].commits, contributors[1].commits);
+    assert_eq!(contributors_rewritten[1].commits, vec!["d3000000_1627380300"]);
+}
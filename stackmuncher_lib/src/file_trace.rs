@@ -0,0 +1,343 @@
+//! Explains why a file made it into the project report or was dropped from it. The stages are evaluated in the same
+//! order as during the analysis and the outcome of the munching is taken from the report.
+use crate::code_rules::CodeRules;
+use crate::generated_code::GENERATED_MARKER_LINES;
+use crate::git::find_ignore_rule;
+use crate::ignore_paths::IGNORE_PATHS;
+use crate::project_config::PROJECT_CONFIG_FILE_NAME;
+use crate::report::Report;
+use std::collections::{BTreeMap, HashSet};
+
+/// The final outcome for a file in the order of the evaluation stages that produce it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Disposition {
+    /// Matched one of `ignore_paths` and was dropped before any other checks.
+    Ignored,
+    /// Not in the tree at HEAD, e.g. a deleted file or a typo in the path.
+    NotInTree,
+    /// There is no file type for the extension or no muncher for the path.
+    NoMuncher,
+    /// Failed or took too long to munch on previous runs and is in the skip list.
+    Skipped,
+    /// Has a muncher, but there is no record for it in the report.
+    NotMunched,
+    /// Empty, binary or could not be decoded.
+    Empty,
+    /// Counted as `generated_loc` instead of LoC.
+    Generated,
+    /// Counted as LoC and `test_loc`.
+    Test,
+    /// Counted as LoC.
+    Code,
+}
+
+impl Disposition {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Ignored => "ignored",
+            Self::NotInTree => "not_in_tree",
+            Self::NoMuncher => "no_muncher",
+            Self::Skipped => "skipped",
+            Self::NotMunched => "not_munched",
+            Self::Empty => "empty",
+            Self::Generated => "generated",
+            Self::Test => "test",
+            Self::Code => "code",
+        }
+    }
+}
+
+/// The outcome of a single evaluation stage, e.g. `ignore_paths` / `no match`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TraceStage {
+    pub stage: &'static str,
+    /// What the stage decided with the rule that matched and where the rule came from.
+    pub outcome: String,
+}
+
+/// All evaluation stages for a single file, stopping at the stage that dropped it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FileTrace {
+    pub file_name: String,
+    pub stages: Vec<TraceStage>,
+    pub disposition: Disposition,
+}
+
+impl FileTrace {
+    /// Evaluates the rules for the file in the order of the analysis and looks up the outcome in the project report.
+    /// `tree_files` is the list of files at HEAD, including the ignored ones.
+    pub fn new(code_rules: &mut CodeRules, file_name: &str, tree_files: &HashSet<String>, report: &Report) -> Self {
+        let mut trace = Self {
+            file_name: file_name.to_owned(),
+            stages: Vec::new(),
+            disposition: Disposition::Code,
+        };
+
+        // 1. ignored files never make it into the log or the list of tree files
+        match find_ignore_rule(&code_rules.ignore_paths, file_name) {
+            Some(rule) => {
+                let source = if IGNORE_PATHS.contains(&rule.as_str()) {
+                    "built-in"
+                } else {
+                    PROJECT_CONFIG_FILE_NAME
+                };
+                return trace.stop("ignore_paths", format!("matched `{}` ({})", rule, source), Disposition::Ignored);
+            }
+            None => trace.add("ignore_paths", "no match".to_owned()),
+        }
+
+        // 2. only files at HEAD are munched
+        if !tree_files.contains(file_name) {
+            return trace.stop("tree", "not in the tree at HEAD".to_owned(), Disposition::NotInTree);
+        }
+        trace.add("tree", "in the tree at HEAD".to_owned());
+
+        // 3. the file extension or the file name without one picks the file type
+        let ext = match code_rules.get_file_ext(file_name) {
+            Some(v) => v,
+            None => return trace.stop("file_type", "no file extension".to_owned(), Disposition::NoMuncher),
+        };
+        let file_type_source = ["file_types/", &ext, ".json"].concat();
+        let muncher_match = match code_rules.files_types.get(&ext) {
+            Some(file_type) => file_type
+                .get_muncher_match(file_name)
+                .map(|(idx, muncher_name, in_path)| (idx, muncher_name.to_owned(), in_path.map(|v| v.to_string()))),
+            None => return trace.stop("file_type", format!("no file type for `{}`", ext), Disposition::NoMuncher),
+        };
+        trace.add("file_type", format!("`{}` from {}", ext, file_type_source));
+
+        // 4. the last matching entry in the file type picks the muncher
+        let (idx, muncher_name, in_path) = match muncher_match {
+            Some(v) => v,
+            None => {
+                return trace.stop(
+                    "muncher",
+                    format!("no match for the path in {}", file_type_source),
+                    Disposition::NoMuncher,
+                )
+            }
+        };
+        let in_path = in_path.map(|v| format!(", in_path `{}`", v)).unwrap_or_default();
+        if code_rules.disabled_munchers.contains(&muncher_name) {
+            return trace.stop(
+                "muncher",
+                format!("`{}` is disabled in {}", muncher_name, PROJECT_CONFIG_FILE_NAME),
+                Disposition::NoMuncher,
+            );
+        }
+        let muncher = match code_rules.get_muncher(&file_name.to_owned()) {
+            Some(v) => v,
+            None => return trace.stop("muncher", format!("`{}` failed to load", muncher_name), Disposition::NoMuncher),
+        };
+        trace.add(
+            "muncher",
+            format!("`{}` from match #{} in {}{}", muncher_name, idx + 1, file_type_source, in_path),
+        );
+        let generated_path_rule = muncher
+            .find_generated_path_rule(file_name)
+            .map(|(rule, is_built_in)| (rule.to_string(), is_built_in));
+        let test_path_rule = muncher.find_test_path_rule(file_name).map(|v| v.to_string());
+
+        // 5. the contents are munched, unless the file is empty, binary or in the skip list
+        if let Some(problem_file) = report
+            .problem_files
+            .iter()
+            .flatten()
+            .find(|v| v.skipped && v.file_name == file_name)
+        {
+            let reason = match (&problem_file.error, problem_file.duration_ms) {
+                // timeouts have both
+                (Some(error), Some(_)) => error.clone(),
+                (Some(error), None) => format!("failed {} times: {}", problem_file.failures, error),
+                (None, Some(duration_ms)) => format!("took {}ms", duration_ms),
+                (None, None) => "failed".to_owned(),
+            };
+            return trace.stop("contents", format!("in the skip list, {}", reason), Disposition::Skipped);
+        }
+        let tech = match report
+            .per_file_tech
+            .iter()
+            .find(|tech| tech.file_name.as_deref() == Some(file_name))
+        {
+            Some(v) => v,
+            None => return trace.stop("contents", "no record in the report".to_owned(), Disposition::NotMunched),
+        };
+        if tech.undecodable_files > 0 {
+            return trace.stop("contents", "binary or could not be decoded".to_owned(), Disposition::Empty);
+        }
+        if tech.total_lines == 0 {
+            return trace.stop("contents", "empty".to_owned(), Disposition::Empty);
+        }
+        let encoding = tech.encoding.as_ref().map(|v| format!(", {}", v)).unwrap_or_default();
+        let sampled = if tech.sampled { ", estimated from samples" } else { "" };
+        trace.add(
+            "contents",
+            format!(
+                "{} lines, {} code lines{}{}",
+                tech.total_lines,
+                tech.code_lines + tech.generated_loc,
+                encoding,
+                sampled
+            ),
+        );
+
+        // 6. generated code is detected by the path or by markers at the top of the file
+        match generated_path_rule {
+            Some((rule, true)) => {
+                return trace.stop("generated_code", format!("matched `{}` (built-in)", rule), Disposition::Generated)
+            }
+            Some((rule, false)) => {
+                return trace.stop(
+                    "generated_code",
+                    format!("matched `{}` from munchers/{}.json", rule, muncher_name),
+                    Disposition::Generated,
+                )
+            }
+            None if tech.generated_loc > 0 => {
+                return trace.stop(
+                    "generated_code",
+                    format!("markers or minified code in the first {} lines", GENERATED_MARKER_LINES),
+                    Disposition::Generated,
+                )
+            }
+            None => trace.add("generated_code", "no match".to_owned()),
+        }
+
+        // 7. test code is still counted as LoC
+        match test_path_rule {
+            Some(rule) => trace.stop(
+                "test_code",
+                format!("matched `{}` from munchers/{}.json", rule, muncher_name),
+                Disposition::Test,
+            ),
+            None if tech.test_files > 0 => trace.stop(
+                "test_code",
+                format!("test markers from munchers/{}.json", muncher_name),
+                Disposition::Test,
+            ),
+            None => trace.stop("test_code", "no match".to_owned(), Disposition::Code),
+        }
+    }
+
+    fn add(&mut self, stage: &'static str, outcome: String) {
+        self.stages.push(TraceStage { stage, outcome });
+    }
+
+    /// Adds the last stage and sets the disposition.
+    fn stop(mut self, stage: &'static str, outcome: String, disposition: Disposition) -> Self {
+        self.add(stage, outcome);
+        self.disposition = disposition;
+        self
+    }
+}
+
+/// Returns true if `file_name` is `filter` or is inside `filter` folder. Both are relative to the project root,
+/// e.g. `src/main.rs` matches `src/main.rs`, `src`, `src/` and `.`.
+pub fn matches_filter(file_name: &str, filter: &str) -> bool {
+    let filter = filter.trim_start_matches("./").trim_end_matches('/');
+    filter.is_empty()
+        || filter == "."
+        || file_name == filter
+        || (file_name.starts_with(filter) && file_name[filter.len()..].starts_with('/'))
+}
+
+/// Returns the number of files per disposition.
+pub fn count_dispositions(traces: &[FileTrace]) -> BTreeMap<Disposition, usize> {
+    let mut counts: BTreeMap<Disposition, usize> = BTreeMap::new();
+    for trace in traces {
+        *counts.entry(trace.disposition).or_default() += 1;
+    }
+    counts
+}
+
+#[cfg(test)]
+mod test_file_trace {
+    use super::{count_dispositions, matches_filter, Disposition, FileTrace};
+    use crate::code_rules::CodeRules;
+    use crate::report::{Report, Tech};
+    use std::collections::HashSet;
+
+    #[test]
+    fn test_file_trace() {
+        let mut code_rules = CodeRules::new();
+        let mut report = Report::new();
+        let rust = Tech::test("Rust", "rust");
+        report.per_file_tech.insert(Tech {
+            code_lines: 8,
+            ..rust.clone().with_file("src/main.rs").with_loc(10)
+        });
+        report.per_file_tech.insert(rust.clone().with_file("src/empty.rs"));
+        report.per_file_tech.insert(Tech {
+            code_lines: 0,
+            generated_loc: 8,
+            ..rust.with_file("src/schema.rs").with_loc(10)
+        });
+        let tree_files = vec![
+            "src/main.rs",
+            "src/empty.rs",
+            "src/schema.rs",
+            "src/new.rs",
+            "src/data.xyz",
+            "logo.png",
+        ]
+        .into_iter()
+        .map(|v| v.to_owned())
+        .collect::<HashSet<String>>();
+
+        let mut traces = Vec::new();
+        for file_name in &[
+            "src/main.rs",
+            "src/empty.rs",
+            "src/schema.rs",
+            "src/new.rs",
+            "src/data.xyz",
+            "logo.png",
+            "src/deleted.rs",
+        ] {
+            traces.push(FileTrace::new(&mut code_rules, file_name, &tree_files, &report));
+        }
+
+        let dispositions = traces.iter().map(|v| v.disposition).collect::<Vec<Disposition>>();
+        assert_eq!(
+            dispositions,
+            vec![
+                Disposition::Code,
+                Disposition::Empty,
+                Disposition::Generated,
+                Disposition::NotMunched,
+                Disposition::NoMuncher,
+                Disposition::Ignored,
+                Disposition::NotInTree
+            ]
+        );
+
+        // all stages are listed for a munched file and only the ones up to the decision for a dropped one
+        let stages = traces[0].stages.iter().map(|v| v.stage).collect::<Vec<&str>>();
+        assert_eq!(
+            stages,
+            vec![
+                "ignore_paths",
+                "tree",
+                "file_type",
+                "muncher",
+                "contents",
+                "generated_code",
+                "test_code"
+            ]
+        );
+        assert_eq!(traces[5].stages.len(), 1);
+        assert!(traces[5].stages[0].outcome.contains("png"));
+
+        assert_eq!(count_dispositions(&traces).get(&Disposition::Code), Some(&1));
+    }
+
+    #[test]
+    fn test_matches_filter() {
+        assert!(matches_filter("src/main.rs", "src/main.rs"));
+        assert!(matches_filter("src/main.rs", "./src/"));
+        assert!(matches_filter("src/main.rs", "src"));
+        assert!(matches_filter("src/main.rs", "."));
+        assert!(!matches_filter("src2/main.rs", "src"));
+        assert!(!matches_filter("src/main.rs", "src/main"));
+    }
+}
@@ -85,33 +85,33 @@ impl FileType {
     /// It picks the last match that meets the conditions.
     /// Only conditions included in `FileTypeMatch` struct are checked. The schema may have more, but they are not implemented.
     pub fn get_muncher_name(&self, file_name_with_path: &String) -> Option<String> {
-        let mut best_match: Option<String> = None;
+        self.get_muncher_match(file_name_with_path)
+            .map(|(_, muncher_name, _)| muncher_name.to_owned())
+    }
+
+    /// Same as `get_muncher_name()`, but also returns the 0-based index of the match in `matches`
+    /// and the `in_path` regex that matched, if any.
+    pub fn get_muncher_match(&self, file_name_with_path: &str) -> Option<(usize, &str, Option<&Regex>)> {
+        let mut best_match: Option<(usize, &str, Option<&Regex>)> = None;
         if let Some(muncher_matches) = self.matches.as_ref() {
             // check all the matches and pick the last match that meets the conditions
-            for muncher_match in muncher_matches {
+            for (idx, muncher_match) in muncher_matches.iter().enumerate() {
                 let muncher_name = muncher_match
                     .muncher
                     .as_ref()
                     .expect("Missing muncher name. It's a bug.");
                 // if in_path is specified it must match
                 if let Some(in_paths) = &muncher_match.in_path_regex {
-                    for in_path in in_paths {
-                        if in_path.is_match(file_name_with_path) {
-                            best_match = Some(muncher_name.clone());
-                            break;
-                        }
+                    if let Some(in_path) = in_paths.iter().find(|in_path| in_path.is_match(file_name_with_path)) {
+                        best_match = Some((idx, muncher_name, Some(in_path)));
                     }
                 } else {
                     // if no in_path is in the match return it as the default
-                    best_match = Some(muncher_name.clone());
+                    best_match = Some((idx, muncher_name, None));
                 }
             }
         }
 
-        // otherwise return None
-        // if best_match.is_none() {
-        //     warn!("No matching muncher found for {}.", file_name_with_path);
-        // }
         best_match
     }
 }
@@ -0,0 +1,76 @@
+/// Only this many lines at the top of the file are checked for `GENERATED_MARKERS` and minified code.
+pub(crate) const GENERATED_MARKER_LINES: usize = 10;
+
+/// A line longer than this at the top of the file is a sign of minified or otherwise machine-produced code.
+pub(crate) const MINIFIED_LINE_LENGTH: usize = 1000;
+
+/// Built-in path patterns for generated and vendored code. They apply to all munchers in addition to
+/// muncher-specific `generated_paths`. Files matching any of these are counted as `generated_loc` instead of LoC.
+pub(crate) const GENERATED_PATHS: [&str; 15] = [
+    // vendored dependencies committed by accident
+    r#"(?i)(^|/)vendor/"#,
+    r#"(?i)(^|/)third[_-]?party/"#,
+    r#"(?i)(^|/)bower_components/"#,
+    r#"(^|/)Pods/"#,
+    // build output
+    r#"(?i)(^|/)dist/"#,
+    // minified JS and CSS
+    r#"(?i)[.-]min\.(js|css)$"#,
+    r#"(?i)\.bundle\.js$"#,
+    // protobuf / gRPC
+    r#"\.pb\.(go|cc|h|swift)$"#,
+    r#"\.pb\.gw\.go$"#,
+    r#"_pb2(_grpc)?\.pyi?$"#,
+    r#"_grpc_pb\.(js|d\.ts)$"#,
+    // code generators for Dart, C#, etc.
+    r#"\.(g|freezed)\.dart$"#,
+    r#"(?i)\.designer\.cs$"#,
+    r#"\.g\.(i\.)?cs$"#,
+    r#"(?i)\.generated\.[^/]+$"#,
+];
+
+/// Built-in content markers placed by code generators at the top of the file, e.g. `// Code generated by protoc-gen-go. DO NOT EDIT.`
+/// They apply to all munchers in addition to muncher-specific `generated_markers`.
+pub(crate) const GENERATED_MARKERS: [&str; 4] = [
+    r#"@generated\b"#,
+    r#"\bDO NOT EDIT\b"#,
+    r#"(?i)<auto-?generated"#,
+    r#"(?i)^\W*(this (file|code) (is|was) )?auto-?generated\b"#,
+];
+
+#[cfg(test)]
+mod test_generated_code {
+    use super::{GENERATED_MARKERS, GENERATED_PATHS};
+    use regex::Regex;
+
+    /// Returns true if any of the patterns matches the text.
+    fn is_match(patterns: &[&str], text: &str) -> bool {
+        patterns.iter().any(|p| Regex::new(p).unwrap().is_match(text))
+    }
+
+    #[test]
+    fn test_generated_paths() {
+        assert!(is_match(&GENERATED_PATHS, "vendor/github.com/pkg/errors/errors.go"));
+        assert!(is_match(&GENERATED_PATHS, "src/third_party/lib.c"));
+        assert!(is_match(&GENERATED_PATHS, "static/js/jquery-3.6.0.min.js"));
+        assert!(is_match(&GENERATED_PATHS, "api/v1/service.pb.go"));
+        assert!(is_match(&GENERATED_PATHS, "proto/service_pb2_grpc.py"));
+        assert!(is_match(&GENERATED_PATHS, "lib/models/user.g.dart"));
+        assert!(is_match(&GENERATED_PATHS, "Forms/Main.Designer.cs"));
+
+        assert!(!is_match(&GENERATED_PATHS, "src/vendors.rs"));
+        assert!(!is_match(&GENERATED_PATHS, "src/admin.js"));
+        assert!(!is_match(&GENERATED_PATHS, "src/main.go"));
+    }
+
+    #[test]
+    fn test_generated_markers() {
+        assert!(is_match(&GENERATED_MARKERS, "// Code generated by protoc-gen-go. DO NOT EDIT."));
+        assert!(is_match(&GENERATED_MARKERS, " * @generated by graphql-codegen"));
+        assert!(is_match(&GENERATED_MARKERS, "// <auto-generated>"));
+        assert!(is_match(&GENERATED_MARKERS, "# This file was autogenerated"));
+
+        assert!(!is_match(&GENERATED_MARKERS, "// Please, do not edit the config below manually"));
+        assert!(!is_match(&GENERATED_MARKERS, "let generated = generate_id();"));
+    }
+}
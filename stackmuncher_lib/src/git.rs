@@ -1,7 +1,12 @@
+use crate::utils::hash_str_sha1;
 use regex::Regex;
+use serde::{Deserialize, Serialize};
 use std::collections::{HashMap, HashSet};
 use std::path::Path;
+use std::process::Stdio;
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
 use tokio::process::Command;
+use tokio::sync::mpsc;
 use tracing::{debug, error, info, trace, warn};
 
 /// An alias for String used for file paths to help with visual type identification.
@@ -27,6 +32,18 @@ pub type BlobSHA1 = String;
 /// E.g. `037498fba1ca5b3662963c848158b7b678adbbf3    .gitignore`.
 pub type ListOfBlobs = HashMap<FilePath, GitBlob>;
 
+/// Repos with this many refs or more are flagged in the log, e.g. Gerrit mirrors with a ref per change.
+pub const MANY_REFS_THRESHOLD: usize = 10000;
+
+/// Options that make git walk or list all refs matching a pattern instead of the explicitly given revisions.
+/// The app only analyzes the history reachable from HEAD and an all-ref walk can take very long on repos with
+/// tens of thousands of refs, so `execute_git_command()` refuses to run commands with any of these.
+const ALL_REFS_OPTIONS: [&str; 5] = ["--all", "--branches", "--tags", "--remotes", "--glob"];
+
+/// The number of parsed log entries `stream_log()` can get ahead of the receiver. Git is paused by the full pipe
+/// after that, which bounds the memory use on repos with millions of commits.
+const LOG_CHANNEL_CAPACITY: usize = 1000;
+
 /// A a structured representation of `git log` output. E.g.
 /// ```
 /// commit f527864cc944d52887d7cc26e79781ac1b01abc2
@@ -41,7 +58,7 @@ pub type ListOfBlobs = HashMap<FilePath, GitBlob>;
 /// stackmuncher/src/report.rs
 /// stmapp/src/main.rs
 /// ```
-#[derive(Clone)]
+#[derive(Clone, Serialize, Deserialize)]
 pub struct GitLogEntry {
     pub sha1: String,
     pub date_epoch: i64,
@@ -90,6 +107,12 @@ pub async fn execute_git_command(
     repo_dir: &Path,
     expect_blank_err_msg: bool,
 ) -> Result<Vec<u8>, ()> {
+    // all git calls must name the revisions they need explicitly
+    if walks_all_refs(&args) {
+        error!("Git command with an all-refs option: {:?}. It's a bug.", args);
+        return Err(());
+    }
+
     // build `git ...` command
     let mut cmd = Command::new("git");
     cmd.args(args);
@@ -133,6 +156,29 @@ pub async fn execute_git_command(
     Ok(git_output.stdout)
 }
 
+/// Returns true if `args` contain any of `ALL_REFS_OPTIONS`, e.g. `--all` or `--branches=feature/*`.
+fn walks_all_refs(args: &[String]) -> bool {
+    args.iter().any(|arg| {
+        ALL_REFS_OPTIONS
+            .iter()
+            .any(|option| arg == option || arg.starts_with(&[option, "="].concat()))
+    })
+}
+
+/// Returns the number of refs in the repo, including tags, remote branches and any custom refs like Gerrit changes.
+/// Returns None if the refs cannot be listed.
+pub async fn count_refs(repo_dir: &Path) -> Option<usize> {
+    let git_args = vec!["for-each-ref".into(), "--format=%(refname)".into()];
+    let git_output = execute_git_command(git_args, repo_dir, false).await.ok()?;
+    let ref_count = String::from_utf8_lossy(&git_output)
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .count();
+
+    debug!("Found {} refs", ref_count);
+    Some(ref_count)
+}
+
 /// Returns the current git version installed on the machine
 pub async fn check_git_version(dir: &Path) -> Result<String, ()> {
     let version = execute_git_command(vec!["--version".into()], dir, false).await?;
@@ -144,22 +190,437 @@ pub async fn check_git_version(dir: &Path) -> Result<String, ()> {
     Ok(version)
 }
 
+/// Returns the value of `core.ignorecase` setting for the repo. Git sets it to `true` on init or clone
+/// on case-insensitive file systems (Windows, macOS). Defaults to `false` if the setting is missing or cannot be read.
+pub async fn is_ignore_case(repo_dir: &Path) -> bool {
+    let git_args = vec!["config".into(), "--get".into(), "core.ignorecase".into()];
+    match execute_git_command(git_args, repo_dir, true).await {
+        Ok(v) => String::from_utf8_lossy(&v).trim().eq_ignore_ascii_case("true"),
+        Err(_) => false,
+    }
+}
+
+/// Returns `true` if the repo was cloned or fetched with `--depth` and some of the history is missing.
+/// Defaults to `false` if the state cannot be determined.
+pub async fn is_shallow_repo(repo_dir: &Path) -> bool {
+    let git_args = vec!["rev-parse".into(), "--is-shallow-repository".into()];
+    match execute_git_command(git_args, repo_dir, false).await {
+        Ok(v) => String::from_utf8_lossy(&v).trim() == "true",
+        Err(_) => false,
+    }
+}
+
+/// Returns SHA1s of the boundary commits of a shallow clone listed in `.git/shallow`. Their parents are missing,
+/// so git reports every file in the tree as added by them. Returns an empty list for repos with the full history.
+pub async fn get_shallow_commits(repo_dir: &Path) -> HashSet<String> {
+    // `.git` may be a file pointing elsewhere in worktrees and submodules
+    let git_args = vec!["rev-parse".into(), "--git-path".into(), "shallow".into()];
+    let shallow_file = match execute_git_command(git_args, repo_dir, false).await {
+        Ok(v) => repo_dir.join(String::from_utf8_lossy(&v).trim()),
+        Err(_) => return HashSet::new(),
+    };
+
+    match std::fs::read_to_string(&shallow_file) {
+        Ok(v) => v
+            .lines()
+            .map(|line| line.trim().to_owned())
+            .filter(|line| !line.is_empty())
+            .collect(),
+        Err(_) => HashSet::new(),
+    }
+}
+
+/// Fetches the missing history of a shallow clone from its remote. Logs and returns an error if the fetch fails,
+/// e.g. there is no network or no remote.
+pub async fn unshallow(repo_dir: &Path) -> Result<(), ()> {
+    info!("Fetching the full history of {}", repo_dir.to_string_lossy());
+    let git_args = vec!["fetch".into(), "--unshallow".into(), "--quiet".into()];
+    execute_git_command(git_args, repo_dir, false).await?;
+    Ok(())
+}
+
+/// Returns the filter the repo was cloned with, e.g. `blob:none` for `git clone --filter=blob:none`.
+/// Blobs missing from such a partial clone are fetched from the remote on demand. Returns None for full clones.
+pub async fn get_partial_clone_filter(repo_dir: &Path) -> Option<String> {
+    get_promisor_remote(repo_dir).await.map(|(_, filter)| filter)
+}
+
+/// Returns the name of the remote a partial clone fetches the missing blobs from and its filter,
+/// e.g. `("origin", "blob:none")`. Returns None for full clones.
+async fn get_promisor_remote(repo_dir: &Path) -> Option<(String, String)> {
+    let git_args = vec![
+        "config".into(),
+        "--get-regexp".into(),
+        r"^remote\..*\.partialclonefilter$".into(),
+    ];
+    let git_output = execute_git_command(git_args, repo_dir, true).await.ok()?;
+
+    // e.g. `remote.origin.partialclonefilter blob:none`
+    String::from_utf8_lossy(&git_output).lines().find_map(|line| {
+        let (key, filter) = line.split_once(' ')?;
+        let remote = key.strip_prefix("remote.")?.strip_suffix(".partialclonefilter")?;
+        let filter = filter.trim();
+        if filter.is_empty() {
+            None
+        } else {
+            Some((remote.to_owned(), filter.to_owned()))
+        }
+    })
+}
+
+/// Fetches the blobs missing from a partial clone in a single `git fetch`. Otherwise `git cat-file` fetches them
+/// from the remote one at a time. Only the blobs in the tree at `rev` or HEAD are checked. Failures are logged and
+/// the blobs are left to be fetched on demand.
+pub(crate) async fn prefetch_missing_blobs(repo_dir: &Path, rev: Option<&str>, blob_sha1s: &[&str]) {
+    let (remote, filter) = match get_promisor_remote(repo_dir).await {
+        Some(v) => v,
+        None => return,
+    };
+
+    // the tree is listed without fetching anything, the missing objects are prefixed with `?`
+    let git_args = vec![
+        "rev-list".into(),
+        "--objects".into(),
+        "--missing=print".into(),
+        "--no-object-names".into(),
+        "--no-walk".into(),
+        rev.unwrap_or("HEAD").into(),
+    ];
+    let git_output = match execute_git_command(git_args, repo_dir, false).await {
+        Ok(v) => v,
+        Err(_) => return,
+    };
+    let missing_sha1s = parse_missing_objects(&git_output);
+    let missing_sha1s = blob_sha1s
+        .iter()
+        .filter(|sha1| missing_sha1s.contains(**sha1))
+        .copied()
+        .collect::<Vec<&str>>();
+    if missing_sha1s.is_empty() {
+        debug!("No blobs to prefetch");
+        return;
+    }
+    info!("Fetching {} missing blobs from {}", missing_sha1s.len(), remote);
+
+    // the same options git uses for fetching a single missing blob, the list can be too long for the command line
+    let mut cmd = Command::new("git");
+    cmd.args(["-c", "fetch.negotiationAlgorithm=noop", "fetch", &remote])
+        .args([
+            "--no-tags",
+            "--no-write-fetch-head",
+            "--recurse-submodules=no",
+            "--quiet",
+            "--stdin",
+        ])
+        .arg(["--filter=", &filter].concat())
+        .current_dir(repo_dir)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true);
+    let mut child = match cmd.spawn() {
+        Ok(v) => v,
+        Err(e) => {
+            warn!("Cannot fetch missing blobs due to {}", e);
+            return;
+        }
+    };
+
+    // git reads the whole list before fetching anything, so it can be written out before waiting on git
+    let mut stdin = child.stdin.take().expect("Cannot take git stdin. It's a bug.");
+    let input = [missing_sha1s.join("\n").as_str(), "\n"].concat();
+    if let Err(e) = stdin.write_all(input.as_bytes()).await {
+        warn!("Cannot write the blob list to git due to {}", e);
+    }
+    drop(stdin);
+
+    match child.wait_with_output().await {
+        Ok(v) if v.status.success() => debug!("Fetched {} blobs", missing_sha1s.len()),
+        Ok(v) => warn!(
+            "Cannot fetch missing blobs. Status: {}. Stderr: {}",
+            v.status,
+            String::from_utf8_lossy(&v.stderr)
+        ),
+        Err(e) => warn!("Cannot fetch missing blobs due to {}", e),
+    }
+}
+
+/// Returns the IDs of the objects marked as missing in `git rev-list --missing=print` output, e.g. `?8c1384d8...`.
+fn parse_missing_objects(git_output: &[u8]) -> HashSet<String> {
+    String::from_utf8_lossy(git_output)
+        .lines()
+        .filter_map(|line| line.strip_prefix('?'))
+        .map(|sha1| sha1.trim().to_owned())
+        .collect()
+}
+
+/// Returns the files in the index that are outside of the sparse checkout, i.e. have the skip-worktree bit set.
+/// Returns None if the repo is not a sparse checkout or the index cannot be listed.
+pub async fn get_sparse_checkout_exclusions(repo_dir: &Path) -> Option<HashSet<String>> {
+    let git_args = vec![
+        "config".into(),
+        "--bool".into(),
+        "--get".into(),
+        "core.sparseCheckout".into(),
+    ];
+    let git_output = execute_git_command(git_args, repo_dir, true).await.ok()?;
+    if String::from_utf8_lossy(&git_output).trim() != "true" {
+        return None;
+    }
+
+    // -t tags every file with its status, `S` is for skip-worktree, -z stops git from quoting the names
+    let git_args = vec!["ls-files".into(), "-t".into(), "-z".into()];
+    let git_output = execute_git_command(git_args, repo_dir, false).await.ok()?;
+
+    Some(parse_ls_files_skip_worktree(&git_output))
+}
+
+/// Returns the names of files tagged with `S` in `git ls-files -t -z` output, e.g. `S docs/index.md\0`.
+fn parse_ls_files_skip_worktree(git_output: &[u8]) -> HashSet<String> {
+    git_output
+        .split(|v| *v == 0)
+        .filter_map(|entry| entry.strip_prefix(b"S "))
+        .map(decode_git_path)
+        .collect()
+}
+
+/// Returns the contents of the blobs by their SHA1 from a single `git cat-file --batch` call, which is much faster
+/// than starting git for every blob and lets git fetch the blobs missing from a partial clone in one session.
+/// Blobs that are missing and cannot be fetched are not in the list.
+pub(crate) async fn get_blobs_contents(repo_dir: &Path, blob_sha1s: &[&str]) -> Result<HashMap<String, Vec<u8>>, ()> {
+    if blob_sha1s.is_empty() {
+        return Ok(HashMap::new());
+    }
+
+    let mut cmd = Command::new("git");
+    cmd.args(["cat-file", "--batch"])
+        .current_dir(repo_dir)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true);
+    let mut child = match cmd.spawn() {
+        Ok(v) => v,
+        Err(e) => {
+            error!("Git command failed with {}", e);
+            return Err(());
+        }
+    };
+
+    // git reads the list while it writes the contents out, so the list has to be written in a separate task
+    // to avoid a deadlock on full pipes
+    let mut stdin = child.stdin.take().expect("Cannot take git stdin. It's a bug.");
+    let input = [blob_sha1s.join("\n").as_str(), "\n"].concat();
+    let writer = tokio::spawn(async move {
+        if let Err(e) = stdin.write_all(input.as_bytes()).await {
+            warn!("Cannot write the blob list to git due to {}", e);
+        }
+    });
+
+    let git_output = match child.wait_with_output().await {
+        Ok(v) => v,
+        Err(e) => {
+            error!("Git command failed with {}", e);
+            return Err(());
+        }
+    };
+    let _ = writer.await;
+
+    if !git_output.status.success() {
+        warn!(
+            "Git cat-file failed. Status: {}. Stderr: {}",
+            git_output.status,
+            String::from_utf8_lossy(&git_output.stderr)
+        );
+        return Err(());
+    }
+
+    let blobs = parse_cat_file_batch(&git_output.stdout);
+    debug!("Read {} of {} blobs in a batch", blobs.len(), blob_sha1s.len());
+    Ok(blobs)
+}
+
+/// Parses `git cat-file --batch` output, e.g. `037498fba1ca5b3662963c848158b7b678adbbf3 blob 12\n<contents>\n`
+/// or `037498fba1ca5b3662963c848158b7b678adbbf3 missing\n`. Returns the contents by the object SHA1.
+fn parse_cat_file_batch(git_output: &[u8]) -> HashMap<String, Vec<u8>> {
+    let mut blobs = HashMap::new();
+    let mut rest = git_output;
+
+    while let Some(idx) = rest.iter().position(|v| *v == b'\n') {
+        let header = String::from_utf8_lossy(&rest[..idx]).to_string();
+        rest = &rest[idx + 1..];
+
+        let mut parts = header.split(' ');
+        let sha1 = parts.next().unwrap_or_default();
+        let size = match (parts.next(), parts.next().and_then(|v| v.parse::<usize>().ok())) {
+            (Some(_), Some(size)) => size,
+            _ => {
+                // e.g. `missing` or `ambiguous`
+                debug!("Blob not read: {}", header);
+                continue;
+            }
+        };
+        if rest.len() < size {
+            warn!("Truncated git cat-file output for {}", sha1);
+            break;
+        }
+
+        blobs.insert(sha1.to_owned(), rest[..size].to_vec());
+        // the contents are followed by LF
+        rest = rest.get(size + 1..).unwrap_or_default();
+    }
+
+    blobs
+}
+
+/// Clones the repo into `target_dir` with HEAD detached at `commit_sha1` for analyzing the project as it was then.
+/// The clone borrows the objects of `repo_dir` with `--shared`, so it is quick and leaves `repo_dir` as-is.
+/// `target_dir` must not exist or be empty.
+pub async fn clone_at_commit(repo_dir: &Path, commit_sha1: &str, target_dir: &Path) -> Result<(), ()> {
+    let git_args = vec![
+        "clone".into(),
+        "--quiet".into(),
+        "--shared".into(),
+        "--no-checkout".into(),
+        repo_dir.to_string_lossy().to_string(),
+        target_dir.to_string_lossy().to_string(),
+    ];
+    execute_git_command(git_args, repo_dir, false).await?;
+
+    let git_args = vec![
+        "-c".into(),
+        "advice.detachedHead=false".into(),
+        "checkout".into(),
+        "--quiet".into(),
+        "--detach".into(),
+        commit_sha1.to_owned(),
+        "--".into(),
+    ];
+    execute_git_command(git_args, target_dir, false).await?;
+
+    Ok(())
+}
+
+/// Returns the SHA1 of the commit `rev` points at, e.g. a branch, a tag or an abbreviated SHA1, or of HEAD if None.
+/// Fails if the repo has no commits yet or there is no such commit.
+pub async fn get_head_commit(repo_dir: &Path, rev: Option<&str>) -> Result<String, ()> {
+    // `^{commit}` peels annotated tags down to the commit they point at
+    let rev = [rev.unwrap_or("HEAD"), "^{commit}"].concat();
+    let git_args = vec!["rev-parse".into(), "--verify".into(), "--quiet".into(), rev.clone()];
+    let git_output = execute_git_command(git_args, repo_dir, true).await?;
+    let sha1 = String::from_utf8_lossy(&git_output).trim().to_owned();
+
+    if sha1.is_empty() {
+        debug!("No {} commit in {}", rev, repo_dir.to_string_lossy());
+        return Err(());
+    }
+
+    Ok(sha1)
+}
+
+/// Returns true if HEAD points at a branch with no commits yet, e.g. right after `git init`. A repo with a damaged
+/// or missing HEAD or branch ref is not empty and returns false, as does any other git error.
+pub async fn is_unborn_head(repo_dir: &Path) -> bool {
+    // a failed command with no error message returns blank output
+    // a branch ref pointing at a missing object still resolves without `^{commit}`
+    let git_args = vec!["rev-parse".into(), "--verify".into(), "--quiet".into(), "HEAD".into()];
+    match execute_git_command(git_args, repo_dir, true).await {
+        Ok(v) if v.is_empty() => {}
+        _ => return false,
+    }
+
+    let git_args = vec!["symbolic-ref".into(), "--quiet".into(), "HEAD".into()];
+    let branch_ref = match execute_git_command(git_args, repo_dir, true).await {
+        Ok(v) => String::from_utf8_lossy(&v).trim().to_owned(),
+        Err(_) => return false,
+    };
+    if branch_ref.is_empty() {
+        return false;
+    }
+
+    debug!("Unborn {} in {}", branch_ref, repo_dir.to_string_lossy());
+    true
+}
+
+/// Returns true if `commit_sha1` is reachable from `rev` or HEAD if None, i.e. the history was not rewritten
+/// since that commit. Returns false if the commit is unknown or on any other error.
+pub(crate) async fn is_ancestor_of_head(repo_dir: &Path, commit_sha1: &str, rev: Option<&str>) -> bool {
+    let git_args = vec![
+        "merge-base".into(),
+        commit_sha1.to_owned(),
+        rev.unwrap_or("HEAD").to_owned(),
+    ];
+    match execute_git_command(git_args, repo_dir, true).await {
+        Ok(v) => String::from_utf8_lossy(&v).trim() == commit_sha1,
+        Err(_) => false,
+    }
+}
+
+/// Rewrites file paths in the log that differ from a file in `tree_files` only by case to the spelling used in the tree.
+/// E.g. `Foo.cs` and `foo.cs` in the history of a repo on a case-insensitive file system are the same file,
+/// which would otherwise be counted twice. Files not in the tree are left as-is.
+/// Logs a warning with the colliding paths.
+pub(crate) fn fold_case_collisions(git_log: Vec<GitLogEntry>, tree_files: &HashSet<String>) -> Vec<GitLogEntry> {
+    // lower case -> the spelling from the tree
+    let tree_files_folded = tree_files
+        .iter()
+        .map(|file| (file.to_lowercase(), file))
+        .collect::<HashMap<String, &String>>();
+
+    // tree file -> all other spellings found in the log
+    let mut collisions: HashMap<String, HashSet<String>> = HashMap::new();
+
+    let git_log = git_log
+        .into_iter()
+        .map(|mut log_entry| {
+            if log_entry.files.iter().all(|file| tree_files.contains(file)) {
+                return log_entry;
+            }
+
+            log_entry.files = log_entry
+                .files
+                .into_iter()
+                .map(|file| {
+                    if tree_files.contains(&file) {
+                        return file;
+                    }
+                    match tree_files_folded.get(&file.to_lowercase()) {
+                        Some(tree_file) => {
+                            collisions.entry((*tree_file).clone()).or_default().insert(file);
+                            (*tree_file).clone()
+                        }
+                        None => file,
+                    }
+                })
+                .collect::<HashSet<String>>();
+
+            log_entry
+        })
+        .collect::<Vec<GitLogEntry>>();
+
+    for (tree_file, other_spellings) in &collisions {
+        let mut other_spellings = other_spellings.iter().map(|v| v.as_str()).collect::<Vec<&str>>();
+        other_spellings.sort_unstable();
+        warn!("Paths differing only by case merged into {}: {}", tree_file, other_spellings.join(", "));
+    }
+
+    git_log
+}
+
 /// Populates blob's sha1 property at the point of the given commit.
 /// Only one `git ls-tree` call is used to get the data.
 /// * `blobs` param: Must be a ListOfBlobs with commit details populated per file. This function only adds the blob SHA1.
 /// The commit details can be taken from `git log` or contributor section of the report.
 /// * `commit_sha1` param: either specify a commit SHA1 or None for HEAD.
+/// * `ignore_case` param: match file names case-insensitively because the spelling at the commit may differ
+///   from the one requested, see `fold_case_collisions()`. The blobs are returned under the requested spelling.
 ///
-/// The raw git output looks like this:
-/// ```
-/// 100644 blob a28b99eae8417ac31293a332ef1a125b8772032d    Cargo.toml
-/// 100644 blob f288702d2fa16d3cdf0035b15a9fcbc552cd88e7    LICENSE
-/// 100644 blob 9da69050aa4d1f6488a258a221217a4dd9e73b71    assets/file-types/cs.json
-/// ```
+/// See `parse_ls_tree()` for the format of git output.
 pub(crate) async fn populate_blob_sha1(
     dir: &Path,
     blobs: ListOfBlobs,
     commit_sha1: Option<String>,
+    ignore_case: bool,
 ) -> Result<ListOfBlobs, ()> {
     // use HEAD if no commit was specified
     let commit_sha1 = match commit_sha1 {
@@ -167,35 +628,50 @@ pub(crate) async fn populate_blob_sha1(
         None => "HEAD".into(),
     };
 
-    let all_objects =
-        execute_git_command(vec!["ls-tree".into(), "-r".into(), "--full-tree".into(), commit_sha1.clone()], dir, false)
-            .await?;
-    let all_objects = String::from_utf8_lossy(&all_objects);
+    let all_objects = execute_git_command(
+        vec!["ls-tree".into(), "-r".into(), "-z".into(), "--full-tree".into(), commit_sha1.clone()],
+        dir,
+        false,
+    )
+    .await?;
 
     trace!("{:?}", blobs);
 
-    let updated_blobs = all_objects
-        .lines()
-        .filter_map(|v| {
-            trace! {"get_all_tree_files: {}", v};
-            if &v[7..11] == "blob" {
-                let file_name = v[53..].to_owned();
-                // cloning everything here seems to be inefficient
-                if let Some(blob) = blobs.get(&file_name) {
-                    Some((
-                        file_name,
-                        GitBlob {
-                            sha1: v[12..52].to_owned(),
-                            commit_sha1: blob.commit_sha1.clone(),
-                            commit_date_epoch: blob.commit_date_epoch.clone(),
-                            commit_date_iso: blob.commit_date_iso.clone(),
-                        },
-                    ))
-                } else {
-                    trace!("Ignored {}, in the tree, not requested", file_name);
-                    None
-                }
+    // lower case -> requested spelling, only needed if the repo is on a case-insensitive file system
+    let blobs_folded = if ignore_case {
+        blobs
+            .keys()
+            .map(|file| (file.to_lowercase(), file.clone()))
+            .collect::<HashMap<String, String>>()
+    } else {
+        HashMap::new()
+    };
+
+    let updated_blobs = parse_ls_tree(&all_objects)
+        .into_iter()
+        .filter_map(|(blob_sha1, file_name)| {
+            // use the requested spelling if the file name differs only by case
+            let file_name = if ignore_case && !blobs.contains_key(&file_name) {
+                blobs_folded
+                    .get(&file_name.to_lowercase())
+                    .cloned()
+                    .unwrap_or(file_name)
+            } else {
+                file_name
+            };
+            // cloning everything here seems to be inefficient
+            if let Some(blob) = blobs.get(&file_name) {
+                Some((
+                    file_name,
+                    GitBlob {
+                        sha1: blob_sha1,
+                        commit_sha1: blob.commit_sha1.clone(),
+                        commit_date_epoch: blob.commit_date_epoch.clone(),
+                        commit_date_iso: blob.commit_date_iso.clone(),
+                    },
+                ))
             } else {
+                trace!("Ignored {}, in the tree, not requested", file_name);
                 None
             }
         })
@@ -212,43 +688,34 @@ pub(crate) async fn populate_blob_sha1(
 
 /// Get the list of files from the current GIT tree for a given commit relative to the current directory.
 /// Use HEAD if no commit was specified.
-/// The raw git output looks like this:
-/// ```
-/// 100644 blob a28b99eae8417ac31293a332ef1a125b8772032d    Cargo.toml
-/// 100644 blob f288702d2fa16d3cdf0035b15a9fcbc552cd88e7    LICENSE
-/// 100644 blob 9da69050aa4d1f6488a258a221217a4dd9e73b71    assets/file-types/cs.json
-/// ```
+/// See `parse_ls_tree()` for the format of git output.
 pub(crate) async fn get_all_tree_files(
     dir: &Path,
     commit_sha1: Option<String>,
-    ignore_paths: &Vec<Regex>,
+    ignore_paths: &[Regex],
 ) -> Result<HashSet<String>, ()> {
     // use HEAD by default
     let commit_sha1 = commit_sha1.unwrap_or("HEAD".to_owned());
 
-    let all_objects =
-        execute_git_command(vec!["ls-tree".into(), "-r".into(), "--full-tree".into(), commit_sha1], dir, false).await?;
-    let all_objects = String::from_utf8_lossy(&all_objects);
+    let all_objects = execute_git_command(
+        vec!["ls-tree".into(), "-r".into(), "-z".into(), "--full-tree".into(), commit_sha1],
+        dir,
+        false,
+    )
+    .await?;
 
-    let files = all_objects
-        .lines()
-        .filter_map(|v| {
-            trace! {"get_all_tree_files: {}", v};
-            if &v[7..11] == "blob" {
-                Some(v[53..].to_owned())
-            } else {
-                None
-            }
-        })
-        .collect::<HashSet<String>>();
-    let tree_all = files.len();
+    Ok(tree_files_from_ls_tree(&all_objects, ignore_paths))
+}
 
-    // remove encoded files
-    let files = files
+/// Converts the raw output of `git ls-tree -r -z` into a list of file names, minus the ignored ones.
+/// Split from `get_all_tree_files()` so that the parsing can be benchmarked without calling git.
+pub(crate) fn tree_files_from_ls_tree(all_objects: &[u8], ignore_paths: &[Regex]) -> HashSet<String> {
+    let tree_all = all_objects.split(|b| *b == 0).filter(|v| !v.is_empty()).count();
+    let files = parse_ls_tree(all_objects)
         .into_iter()
-        .filter_map(|file_path| octal_to_unicode_string(file_path))
+        .map(|(_, file_path)| file_path)
         .collect::<HashSet<String>>();
-    let encoded_count = tree_all - files.len();
+    let non_blob_count = tree_all - files.len();
 
     // remove ignored files
     let files = files
@@ -263,52 +730,151 @@ pub(crate) async fn get_all_tree_files(
         .collect::<HashSet<String>>();
 
     info!(
-        "Objects in the GIT tree: {}, encoded paths: {}, ignored: {}, processing: {}",
+        "Objects in the GIT tree: {}, non-blobs: {}, ignored: {}, processing: {}",
         tree_all,
-        encoded_count,
+        non_blob_count,
         tree_all - files.len(),
         files.len(),
     );
 
-    Ok(files)
+    files
+}
+
+/// Parses the output of `git ls-tree -r -z` into a list of blob SHA1 / file name pairs. Trees and submodules are skipped.
+/// Records are separated by NUL and file names are not quoted, so they can contain spaces, quotes and non-ASCII glyphs.
+/// Non-UTF8 names are kept with the invalid bytes escaped by `decode_git_path()`, the same as in `git log` output.
+/// The raw git output looks like this with `\0` for NUL:
+/// ```text
+/// 100644 blob a28b99eae8417ac31293a332ef1a125b8772032d\tCargo.toml\0100644 blob f288702d2fa16d3cdf0035b15a9fcbc552cd88e7\tmy "docs"/read me.md\0
+/// ```
+pub(crate) fn parse_ls_tree(git_output: &[u8]) -> Vec<(BlobSHA1, FilePath)> {
+    git_output
+        .split(|b| *b == 0)
+        .filter(|record| !record.is_empty())
+        .filter_map(|record| {
+            // <mode> SP <type> SP <object> TAB <file>
+            let tab_idx = match record.iter().position(|b| *b == b'\t') {
+                Some(v) => v,
+                None => {
+                    warn!("Invalid ls-tree record: {}", String::from_utf8_lossy(record));
+                    return None;
+                }
+            };
+            let (meta, file_name) = (&record[..tab_idx], &record[tab_idx + 1..]);
+
+            let meta = String::from_utf8_lossy(meta);
+            let mut meta = meta.split_ascii_whitespace();
+            let (object_type, blob_sha1) = (meta.nth(1)?, meta.next()?);
+            if object_type != "blob" {
+                return None;
+            }
+
+            let file_name = decode_git_path(file_name);
+            trace!("ls-tree: {} {}", blob_sha1, file_name);
+            Some((blob_sha1.to_owned(), file_name))
+        })
+        .collect()
 }
 
-/// Checks if the file name was encoded by GIT using octal sequences for non-ASCII glyphs and attempt a conversion to a normal UTF-8 string.
-/// E.g. `"LINQ\343\202\265\343\203\263\343\203\227\343\203\253.cs/.vs/LINQ\343\202\265\343\203\263\343\203\227\343\203\253.cs/v16/.suo"`
-/// Returns None if the string cannot be converted.
-fn octal_to_unicode_string(file_path: String) -> Option<String> {
+/// Converts a raw file name from git output into a string. Names are bytes in git and may be in a legacy encoding,
+/// e.g. `caf\xe9.txt` in Latin-1, so the bytes that are not valid UTF-8 are kept as `\xNN` escapes instead of
+/// being replaced with U+FFFD. Different names stay different and the same name decodes the same way
+/// whichever git command it came from.
+fn decode_git_path(file_name: &[u8]) -> String {
+    let mut decoded = String::with_capacity(file_name.len());
+    let mut rest = file_name;
+    loop {
+        match std::str::from_utf8(rest) {
+            Ok(v) => {
+                decoded.push_str(v);
+                return decoded;
+            }
+            Err(e) => {
+                let (valid, invalid) = rest.split_at(e.valid_up_to());
+                // the bytes up to `valid_up_to()` are valid UTF-8
+                decoded.push_str(&String::from_utf8_lossy(valid));
+                // a truncated sequence at the end has no error length
+                let invalid_len = e.error_len().unwrap_or(invalid.len());
+                for b in &invalid[..invalid_len] {
+                    decoded.push_str(&format!("\\x{:02x}", b));
+                }
+                rest = &invalid[invalid_len..];
+            }
+        }
+    }
+}
+
+/// Decodes a file name quoted by GIT in `git log` output. GIT quotes names with non-ASCII glyphs, control characters,
+/// `"` and `\` using C-style escapes and octal sequences for bytes, e.g. `"LINQ\343\202\265.cs"` for `LINQサ.cs`.
+/// Names that are not quoted are returned as-is. Bytes that are not valid UTF-8 after decoding are escaped
+/// by `decode_git_path()`. Returns None if the name was not valid UTF-8 in the raw output.
+fn unquote_git_path(file_path: &str) -> Option<String> {
     // return as-is if not encoded
-    if !file_path.starts_with("\"") {
-        return Some(file_path);
+    if !file_path.starts_with('"') {
+        // invalid UTF-8 was replaced with U+FFFD when the output was converted to a string, which may happen if quoting
+        // is off in the git config, and such a name would not match the same file in `ls-tree` output
+        if file_path.contains(char::REPLACEMENT_CHARACTER) {
+            debug!("Non-UTF8 file name in git output: {}", file_path);
+            return None;
+        }
+        return Some(file_path.to_owned());
     }
     // the string must be enclosed in "
-    if !file_path.ends_with("\"") {
+    if file_path.len() < 2 || !file_path.ends_with('"') {
         error!("Invalid file name in git-log: {}", file_path);
         return None;
     }
 
-    // the decoding should take place here
-    // TODO: see https://github.com/stackmuncher/stm_app/issues/42
+    let quoted = &file_path.as_bytes()[1..file_path.len() - 1];
+    let mut decoded: Vec<u8> = Vec::with_capacity(quoted.len());
+    let mut idx = 0;
+    while idx < quoted.len() {
+        if quoted[idx] != b'\\' {
+            decoded.push(quoted[idx]);
+            idx += 1;
+            continue;
+        }
 
-    debug!("Encoded file name in git output: {}", file_path);
+        // an escape sequence: \ooo for a byte in octal or a single character
+        let escaped = *quoted.get(idx + 1)?;
+        if escaped.is_ascii_digit() {
+            let octal = std::str::from_utf8(quoted.get(idx + 1..idx + 4)?).ok()?;
+            decoded.push(u8::from_str_radix(octal, 8).ok()?);
+            idx += 4;
+        } else {
+            decoded.push(match escaped {
+                b'a' => 7,
+                b'b' => 8,
+                b't' => b'\t',
+                b'n' => b'\n',
+                b'v' => 11,
+                b'f' => 12,
+                b'r' => b'\r',
+                v => v,
+            });
+            idx += 2;
+        }
+    }
 
-    None
+    Some(decode_git_path(&decoded))
 }
 
 /// Returns TRUE if the file matches any of the ignore regex rules from `ignore_paths` module.
 #[inline]
-fn is_in_ignore_list(ignore_paths: &Vec<Regex>, file_path: &str) -> bool {
-    // check if the path is in the ignore list
-    for ignore_regex in ignore_paths {
-        if ignore_regex.is_match(file_path) {
-            debug!("Path ignored: {}", file_path);
-            return true;
-        }
+pub(crate) fn is_in_ignore_list(ignore_paths: &[Regex], file_path: &str) -> bool {
+    if find_ignore_rule(ignore_paths, file_path).is_some() {
+        debug!("Path ignored: {}", file_path);
+        return true;
     }
 
     false
 }
 
+/// Returns the first rule from the ignore list that matches the path.
+pub(crate) fn find_ignore_rule<'a>(ignore_paths: &'a [Regex], file_path: &str) -> Option<&'a Regex> {
+    ignore_paths.iter().find(|ignore_regex| ignore_regex.is_match(file_path))
+}
+
 /// Get the contents of the Git blob as text.
 pub(crate) async fn get_blob_contents(dir: &Path, blob_sha1: &String) -> Result<Vec<u8>, ()> {
     let blob_contents = execute_git_command(vec!["cat-file".into(), "-p".into(), blob_sha1.into()], dir, false).await?;
@@ -317,72 +883,254 @@ pub(crate) async fn get_blob_contents(dir: &Path, blob_sha1: &String) -> Result<
 }
 
 /// Extracts and parses GIT log into who, what, when. Removes ignored files. No de-duping or optimisation is done. All log data is copied into the structs as-is.
-/// Merge commits are excluded.
+/// Merge commits are included with only the files that differ from all their parents, so that the code written by
+/// the one who merged is told apart from the merged-in code, see `log_args()`. Only the history reachable from `rev`
+/// is included, HEAD if None.
 pub async fn get_log(
     repo_dir: &Path,
     contributor_git_identity: Option<&String>,
-    ignore_paths: &Vec<Regex>,
+    rev: Option<&str>,
+    ignore_paths: &[Regex],
 ) -> Result<Vec<GitLogEntry>, ()> {
+    collect_log(stream_log(repo_dir, contributor_git_identity, None, rev, ignore_paths).await?).await
+}
+
+/// Starts `git log` with the same arguments as `get_raw_log()` and parses its stdout line by line as it is read,
+/// so that the log is never held in memory in full. Parsed entries are sent through the returned channel in the
+/// log order, newest first. An `Err` is sent last if git failed.
+/// Dropping the receiver stops reading and kills git, e.g. once an incremental update has what it needs.
+pub(crate) async fn stream_log(
+    repo_dir: &Path,
+    contributor_git_identity: Option<&String>,
+    since_commit: Option<&str>,
+    rev: Option<&str>,
+    ignore_paths: &[Regex],
+) -> Result<mpsc::Receiver<Result<GitLogEntry, ()>>, ()> {
+    debug!("Streaming git log");
+
+    let git_args = log_args(contributor_git_identity, since_commit, rev);
+    trace!("GIT LOG: {:?}", git_args);
+    if walks_all_refs(&git_args) {
+        error!("Git command with an all-refs option: {:?}. It's a bug.", git_args);
+        return Err(());
+    }
+
+    let mut cmd = Command::new("git");
+    cmd.args(git_args)
+        .current_dir(repo_dir)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true);
+
+    let mut child = match cmd.spawn() {
+        Ok(v) => v,
+        Err(e) => {
+            error!("Git command failed with {}", e);
+            return Err(());
+        }
+    };
+    let stdout = child.stdout.take().expect("Cannot take git stdout. It's a bug.");
+
+    // stderr is read at the same time as stdout, otherwise git would block on a full stderr pipe
+    // while the reader waits for more stdout, e.g. with a lot of warnings about missing objects
+    let mut stderr = child.stderr.take().expect("Cannot take git stderr. It's a bug.");
+    let stderr_reader = tokio::spawn(async move {
+        let mut stderr_output = Vec::new();
+        if let Err(e) = stderr.read_to_end(&mut stderr_output).await {
+            warn!("Cannot read git log stderr due to {}", e);
+        }
+        stderr_output
+    });
+
+    let ignore_paths = ignore_paths.to_vec();
+    let (tx, rx) = mpsc::channel(LOG_CHANNEL_CAPACITY);
+
+    tokio::spawn(async move {
+        let mut parser = LogParser::new(&ignore_paths);
+        let mut reader = BufReader::new(stdout);
+        let mut line = Vec::new();
+        let mut log_entry_count = 0usize;
+
+        loop {
+            line.clear();
+            match reader.read_until(b'\n', &mut line).await {
+                Ok(0) => break,
+                Ok(_) => {}
+                Err(e) => {
+                    warn!("Cannot read git log output due to {}", e);
+                    let _ = tx.send(Err(())).await;
+                    return;
+                }
+            }
+
+            let line = String::from_utf8_lossy(&line);
+            if let Some(log_entry) = parser.push_line(line.trim_end_matches(&['\r', '\n'][..])) {
+                log_entry_count += 1;
+                // the receiver is gone if the send failed and `child` is killed on return
+                if tx.send(Ok(log_entry)).await.is_err() {
+                    debug!("Git log receiver dropped after {} commits", log_entry_count);
+                    return;
+                }
+            }
+        }
+
+        if let Some(log_entry) = parser.finish() {
+            log_entry_count += 1;
+            if tx.send(Ok(log_entry)).await.is_err() {
+                return;
+            }
+        }
+
+        match child.wait().await {
+            Ok(status) if status.success() => {
+                debug!("Found {} commits of interest", log_entry_count);
+            }
+            Ok(status) => {
+                let stderr_output = stderr_reader.await.unwrap_or_default();
+                // keep the logging level at warn because it fails on trivial errors like an empty repo
+                warn!("Git log failed. Status: {}. Stderr: {}", status, String::from_utf8_lossy(&stderr_output));
+                let _ = tx.send(Err(())).await;
+            }
+            Err(e) => {
+                error!("Git command failed with {}", e);
+                let _ = tx.send(Err(())).await;
+            }
+        }
+    });
+
+    Ok(rx)
+}
+
+/// Collects all entries from `stream_log()` into a list. Returns an error if git failed at any point.
+pub(crate) async fn collect_log(mut rx: mpsc::Receiver<Result<GitLogEntry, ()>>) -> Result<Vec<GitLogEntry>, ()> {
+    let mut log_entries = Vec::new();
+    while let Some(log_entry) = rx.recv().await {
+        log_entries.push(log_entry?);
+    }
+
+    Ok(log_entries)
+}
+
+/// Returns the raw output of `git log` in the format expected by `parse_log()`.
+/// Only commits made after `since_commit` are included if it is set, e.g. `since_commit..HEAD`.
+/// The log ends at `rev` if it is set or at HEAD otherwise.
+pub(crate) async fn get_raw_log(
+    repo_dir: &Path,
+    contributor_git_identity: Option<&String>,
+    since_commit: Option<&str>,
+    rev: Option<&str>,
+) -> Result<String, ()> {
     debug!("Extracting git log");
 
+    let git_args = log_args(contributor_git_identity, since_commit, rev);
+
+    // this trace may be needed for unusual `author` values
+    trace!("GIT LOG: {:?}", git_args);
+
+    // get the raw stdout output from GIT
+    let git_output = execute_git_command(git_args, repo_dir, false).await?;
+
+    Ok(String::from_utf8_lossy(&git_output).to_string())
+}
+
+/// Returns the arguments for `git log` in `get_raw_log()`. The revision range is always explicit, e.g. `HEAD` or
+/// `since_commit..HEAD`, and is followed by `--` so that it cannot be mistaken for a file name.
+/// `rev` replaces HEAD in the range if set and is expected to be a resolved SHA1, see `get_head_commit()`.
+/// Decorations are disabled because they require loading all refs in the repo.
+/// `.mailmap` is applied by `mailmap::Mailmap` after parsing so that the cached logs keep the original identities.
+/// File names are always quoted, whatever `core.quotepath` is in the user config, so that non-ASCII names are decoded
+/// the same way on every machine.
+/// Merge commits are compared with every parent (`--cc`) and only list the files that differ from all of them,
+/// e.g. conflict resolutions. The rest of the changes arriving with a merge are listed under the merged commits
+/// and are credited to their authors rather than to the one who merged them.
+fn log_args(contributor_git_identity: Option<&String>, since_commit: Option<&str>, rev: Option<&str>) -> Vec<String> {
     // prepare the command that may optionally include the author name to limit commits just to that contributor
     let mut git_args = vec![
+        "-c".into(),
+        "core.quotepath=on".into(),
         "log".into(),
         "--no-decorate".into(),
         "--name-only".into(),
+        "--cc".into(),
         "--encoding=utf-8".into(),
+        "--no-use-mailmap".into(),
     ];
     if let Some(author) = contributor_git_identity {
         git_args.push([r#"--author=""#, author, r#"""#].concat());
     };
+    let rev = rev.unwrap_or("HEAD");
+    match since_commit {
+        Some(since_commit) => git_args.push([since_commit, "..", rev].concat()),
+        None => git_args.push(rev.into()),
+    };
+    git_args.push("--".into());
 
-    // this trace may be needed for unusual `author` values
-    trace!("GIT LOG: {:?}", git_args);
-
-    // get the raw stdout output from GIT
-    let git_output = execute_git_command(git_args, repo_dir, false).await?;
+    git_args
+}
 
-    // try to convert the commits into a list of lines
-    let mut log_entries: Vec<GitLogEntry> = Vec::new();
-    let git_output = String::from_utf8_lossy(&git_output);
-    if git_output.len() == 0 {
+/// Parses the raw output of `git log --no-decorate --name-only` into a list of log entries. Removes ignored files
+/// and commits with no files left after that. Split from `get_log()` to be benchmarked without calling git.
+pub(crate) fn parse_log(git_output: &str, ignore_paths: &[Regex]) -> Vec<GitLogEntry> {
+    if git_output.is_empty() {
         warn!("Zero-length git log");
-        return Ok(log_entries);
+        return Vec::new();
     }
 
-    let mut current_log_entry = GitLogEntry::new();
+    let mut parser = LogParser::new(ignore_paths);
+    let mut log_entries = git_output
+        .lines()
+        .filter_map(|line| parser.push_line(line))
+        .collect::<Vec<GitLogEntry>>();
+    log_entries.extend(parser.finish());
 
-    for line in git_output.lines() {
+    debug!("Found {} commits of interest", log_entries.len());
+    log_entries
+}
+
+/// Parses `git log --no-decorate --name-only` output one line at a time, so that the log can be parsed as it is read
+/// from git. See `parse_log()` and `stream_log()`.
+struct LogParser<'a> {
+    ignore_paths: &'a [Regex],
+    current_log_entry: GitLogEntry,
+}
+
+impl<'a> LogParser<'a> {
+    fn new(ignore_paths: &'a [Regex]) -> Self {
+        Self {
+            ignore_paths,
+            current_log_entry: GitLogEntry::new(),
+        }
+    }
+
+    /// Adds a line of the log to the current entry. Returns the previous entry when a new one starts, unless it had
+    /// no files left after removing ignored files.
+    fn push_line(&mut self, line: &str) -> Option<GitLogEntry> {
+        // `lines()` only removes one CR before LF, but the output may have been converted to CRLF more than once
+        let line = line.trim_end_matches('\r');
         trace!("{}", line);
+        let current_log_entry = &mut self.current_log_entry;
         if line.is_empty() {
             // one empty line is after DATE and one is before COMMIT
-            continue;
         } else if line.starts_with("Merge:") {
-            // We don't use merge info for any particular purpose at the moment
-            // potentially, the committer of the merge should get at least some credit for it
-            continue;
+            // Merge: 307c444 0a37e25
+            // the files of a merge are only those changed on top of all parents, see `log_args()`
         } else if line.len() == 47 && line.starts_with("commit ") {
             // commit d5e742de653954bfae88f0e5f6c8f0a7a5f6c437
-            // save the previous commit details and start a new one
-            // the very first entry will be always blank, it is remove outside the loop
-            if current_log_entry.files.len() > 0 {
-                // do not add a commit if a commit consists entirely of ignored files or has no files for another reason
-                log_entries.push(current_log_entry);
-            }
-            current_log_entry = GitLogEntry::new();
-            if line.len() > 8 {
-                current_log_entry.sha1 = line[7..].to_owned();
+            // return the previous commit details and start a new one
+            // the very first entry will be always blank, it is dropped for having no files
+            let mut log_entry = GitLogEntry::new();
+            log_entry.sha1 = line[7..].to_owned();
+            let previous_log_entry = std::mem::replace(current_log_entry, log_entry);
+            // do not add a commit if a commit consists entirely of ignored files or has no files for another reason
+            if !previous_log_entry.files.is_empty() {
+                return Some(previous_log_entry);
             }
         } else if line.starts_with("Author: ") {
             // the author line looks something like this
             //Lorenzo Baboollie <lorenzo@xamsie.be>
-            if line.len() < 9 {
-                warn!("Corrupt Author line: {}", line);
-                continue;
-            }
             let author = line[8..].trim();
             if author.is_empty() {
-                continue;
+                return None;
             }
             trace!("Author: {}", author);
             // try to split the author details into name and email
@@ -393,7 +1141,7 @@ pub async fn get_log(
                     let author_e = author_e.trim().trim_end_matches(">").trim_start_matches("<");
                     debug!("Author split: {}|{}", author_n, author_e);
                     current_log_entry.author_name_email = (author_n.to_owned(), author_e.to_owned());
-                    continue;
+                    return None;
                 };
             }
             // name/email split failed - add the entire line
@@ -403,14 +1151,14 @@ pub async fn get_log(
             // Date:   Tue Dec 22 17:43:07 2020 +0000
             if line.len() < 9 {
                 warn!("Corrupt Date line: {}", line);
-                continue;
+                return None;
             }
             let date = line[6..].trim();
             trace!("Date: {}", date);
             // go to the next line if there is no date (impossible?)
             if date.is_empty() {
                 warn!("Encountered a commit with no date: {}", line);
-                continue;
+                return None;
             }
 
             // Formatter: https://docs.rs/chrono/0.4.15/chrono/format/strftime/index.html
@@ -419,53 +1167,33 @@ pub async fn get_log(
                 trace!("Parsed as: {}", d.to_rfc3339());
                 current_log_entry.date = d.to_rfc3339();
                 current_log_entry.date_epoch = d.timestamp();
-                continue;
             } else {
                 error! {"Invalid commit date format: {}", date};
             };
         } else if line.starts_with("    ") {
             // log messages are indented with 4 spaces, including blank lines
-            if line.len() < 4 {
-                warn!("Corrupt comment line: {}", line);
-                continue;
-            }
-            current_log_entry.msg = [current_log_entry.msg, line[3..].to_owned()].join("\n");
+            current_log_entry.msg = [current_log_entry.msg.as_str(), &line[3..]].join("\n");
         } else {
             // the only remaining type of data should be the list of files
             // they are not tagged or indented - the entire line is the file name with the relative path
             // file names are displayed only with --name-only option
-            if !is_in_ignore_list(ignore_paths, line) {
+            // and may be quoted if they contain non-ASCII glyphs or special characters
+            let file_path = unquote_git_path(line)?;
+            if !is_in_ignore_list(self.ignore_paths, &file_path) {
                 trace!("Added as a file");
-                current_log_entry.files.insert(line.into());
+                current_log_entry.files.insert(file_path);
             } else {
                 trace!("Ignored");
             }
         }
-    }
-
-    // the very last commit has to be pushed outside the loop
-    log_entries.push(current_log_entry);
-
-    debug!("Found {} commits of interest", log_entries.len());
 
-    // remove encoded files
-    let log_entries = log_entries
-        .into_iter()
-        .filter_map(|mut log_entry| {
-            log_entry.files = log_entry
-                .files
-                .into_iter()
-                .filter_map(|file_path| octal_to_unicode_string(file_path))
-                .collect::<HashSet<String>>();
-            if log_entry.files.is_empty() {
-                None
-            } else {
-                Some(log_entry)
-            }
-        })
-        .collect::<Vec<GitLogEntry>>();
+        None
+    }
 
-    Ok(log_entries)
+    /// Returns the very last entry of the log, unless it had no files left after removing ignored files.
+    fn finish(self) -> Option<GitLogEntry> {
+        Some(self.current_log_entry).filter(|log_entry| !log_entry.files.is_empty())
+    }
 }
 
 /// Extracts all contributor commits from the full log. `git_identities` should be lowercase.
@@ -557,3 +1285,431 @@ pub(crate) fn log_entries_to_list_of_blobs(git_log: &Vec<GitLogEntry>) -> ListOf
     debug!("list_of_files_with_commits_from_git_log collected {} files from git log", blobs.len());
     blobs
 }
+
+/// Returns the list of remote URLs from the repo config with `origin` first, if present.
+/// E.g. `git@github.com:stackmuncher/stm_app.git` or `https://github.com/stackmuncher/stm_app.git`.
+pub async fn get_remote_urls(repo_dir: &Path) -> Result<Vec<String>, ()> {
+    // the output looks like `remote.origin.url git@github.com:stackmuncher/stm_app.git`, one remote per line
+    // git returns an empty error stream if there are no remotes
+    let git_args = vec!["config".into(), "--get-regexp".into(), r"^remote\..*\.url$".into()];
+    let git_output = execute_git_command(git_args, repo_dir, true).await?;
+    let git_output = String::from_utf8_lossy(&git_output);
+
+    let mut remotes = git_output
+        .lines()
+        .filter_map(|line| line.split_once(' '))
+        .map(|(key, url)| (key.trim().to_owned(), url.trim().to_owned()))
+        .filter(|(_, url)| !url.is_empty())
+        .collect::<Vec<(String, String)>>();
+
+    // origin is the most likely candidate for the owner of the repo
+    remotes.sort_by_key(|(key, _)| key != "remote.origin.url");
+
+    debug!("Found {} remotes", remotes.len());
+    Ok(remotes.into_iter().map(|(_, url)| url).collect())
+}
+
+/// Returns `host/org` of the first remote that has one, e.g. `github.com/stackmuncher`. The value is for local use only
+/// and should never be submitted.
+pub async fn get_remote_org(repo_dir: &Path) -> Option<String> {
+    get_remote_urls(repo_dir)
+        .await
+        .ok()?
+        .iter()
+        .find_map(|url| org_from_remote_url(url))
+}
+
+/// Returns SHA1 hash of the `origin` remote URL in the form returned by `normalize_remote_url`, so that clones of
+/// the same remote have the same hash whichever protocol they were cloned with. Other remotes are ignored because
+/// forks usually have the repo they were forked from as `upstream`. Repos with no `origin` use their first remote.
+/// The hash is for local use only and should never be submitted.
+pub async fn get_remote_url_hash(repo_dir: &Path) -> Option<String> {
+    let url = get_remote_urls(repo_dir).await.ok()?.into_iter().next()?;
+
+    Some(hash_str_sha1(&normalize_remote_url(&url)?))
+}
+
+/// Extracts the host and the owning org or user from a remote URL as `host/org`. Nested groups are retained.
+/// Returns None for local paths and URLs with no org segment.
+/// * `https://github.com/stackmuncher/stm_app.git` -> `github.com/stackmuncher`
+/// * `git@github.com:stackmuncher/stm_app.git` -> `github.com/stackmuncher`
+/// * `ssh://git@gitlab.com:2222/group/subgroup/repo.git` -> `gitlab.com/group/subgroup`
+pub fn org_from_remote_url(url: &str) -> Option<String> {
+    let (host, segments) = split_remote_url(url)?;
+    if segments.len() < 2 {
+        return None;
+    }
+
+    Some([host.as_str(), "/", segments[..segments.len() - 1].join("/").as_str()].concat())
+}
+
+/// Returns the remote URL as `host/path` in lower case with no protocol, user, port or `.git`, so that the same repo
+/// has the same URL over HTTPS and SSH. Returns None for local paths.
+/// * `git@github.com:stackmuncher/stm_app.git` -> `github.com/stackmuncher/stm_app`
+/// * `https://user@GitHub.com/stackmuncher/stm_app/` -> `github.com/stackmuncher/stm_app`
+pub fn normalize_remote_url(url: &str) -> Option<String> {
+    let (host, segments) = split_remote_url(url)?;
+    let path = segments.join("/").to_lowercase();
+    let path = path.strip_suffix(".git").unwrap_or(&path);
+    if path.is_empty() {
+        return None;
+    }
+
+    Some([host.as_str(), "/", path].concat())
+}
+
+/// Splits a remote URL into the host in lower case and the non-empty segments of the path.
+/// Returns None for local paths and URLs with no host.
+fn split_remote_url(url: &str) -> Option<(String, Vec<&str>)> {
+    let url = url.trim();
+
+    // split the URL into the host part and the path
+    let (authority, path) = if let Some((scheme, rest)) = url.split_once("://") {
+        // local repos have no org
+        if scheme.eq_ignore_ascii_case("file") {
+            return None;
+        }
+        rest.split_once('/')?
+    } else {
+        // scp-like syntax, e.g. `git@github.com:org/repo.git`
+        // a `/` before the `:` means it is a local path
+        let (authority, path) = url.split_once(':')?;
+        if authority.contains('/') || authority.contains('\\') || authority.len() < 2 {
+            return None;
+        }
+        (authority, path)
+    };
+
+    // remove the user name and the port, e.g. `git@github.com:2222` -> `github.com`
+    let host = authority.rsplit_once('@').map(|(_, host)| host).unwrap_or(authority);
+    let host = host.split(':').next().unwrap_or_default().to_lowercase();
+    if host.is_empty() {
+        return None;
+    }
+
+    // the last segment of the path is the repo name, everything before it is the org
+    let segments = path
+        .trim_matches('/')
+        .split('/')
+        .filter(|s| !s.is_empty())
+        .collect::<Vec<&str>>();
+
+    Some((host, segments))
+}
+
+#[test]
+fn test_org_from_remote_url() {
+    assert_eq!(
+        org_from_remote_url("https://github.com/stackmuncher/stm_app.git").unwrap(),
+        "github.com/stackmuncher"
+    );
+    assert_eq!(
+        org_from_remote_url("git@github.com:stackmuncher/stm_app.git").unwrap(),
+        "github.com/stackmuncher"
+    );
+    assert_eq!(
+        org_from_remote_url("ssh://git@GitLab.com:2222/group/subgroup/repo.git").unwrap(),
+        "gitlab.com/group/subgroup"
+    );
+    assert_eq!(org_from_remote_url("https://user@bitbucket.org/org/repo").unwrap(), "bitbucket.org/org");
+    assert!(org_from_remote_url("https://github.com/stm_app").is_none());
+    assert!(org_from_remote_url("/home/user/repos/stm_app").is_none());
+    assert!(org_from_remote_url("file:///home/user/repos/stm_app").is_none());
+    assert!(org_from_remote_url("C:\\repos\\stm_app").is_none());
+}
+
+#[test]
+fn test_normalize_remote_url() {
+    assert_eq!(
+        normalize_remote_url("git@github.com:stackmuncher/stm_app.git").unwrap(),
+        "github.com/stackmuncher/stm_app"
+    );
+    assert_eq!(
+        normalize_remote_url("https://user@GitHub.com/StackMuncher/stm_app/").unwrap(),
+        "github.com/stackmuncher/stm_app"
+    );
+    assert_eq!(
+        normalize_remote_url("ssh://git@gitlab.com:2222/group/subgroup/repo.git").unwrap(),
+        "gitlab.com/group/subgroup/repo"
+    );
+    assert!(normalize_remote_url("https://github.com/").is_none());
+    assert!(normalize_remote_url("/home/user/repos/stm_app").is_none());
+    assert!(normalize_remote_url("file:///home/user/repos/stm_app").is_none());
+}
+
+#[test]
+fn test_fold_case_collisions() {
+    let mut older = GitLogEntry::new();
+    older.files.insert("src/Foo.cs".to_owned());
+    older.files.insert("src/deleted.cs".to_owned());
+    let mut newer = GitLogEntry::new();
+    newer.files.insert("src/foo.cs".to_owned());
+    newer.files.insert("README.md".to_owned());
+
+    let tree_files = ["src/foo.cs", "README.md"]
+        .iter()
+        .map(|v| v.to_string())
+        .collect::<HashSet<String>>();
+
+    let git_log = fold_case_collisions(vec![newer, older], &tree_files);
+    assert!(git_log[0].files.contains("src/foo.cs"));
+    assert!(git_log[1].files.contains("src/foo.cs"));
+    assert!(!git_log[1].files.contains("src/Foo.cs"));
+    // files that are not in the tree are left alone
+    assert!(git_log[1].files.contains("src/deleted.cs"));
+
+    // the same file name should only appear once in the list of blobs
+    let blobs = log_entries_to_list_of_blobs(&git_log);
+    assert_eq!(blobs.len(), 3);
+}
+
+#[test]
+fn test_parse_ls_tree() {
+    let mut git_output = b"100644 blob a28b99eae8417ac31293a332ef1a125b8772032d\tCargo.toml\0".to_vec();
+    git_output.extend_from_slice("100644 blob f288702d2fa16d3cdf0035b15a9fcbc552cd88e7\tmy \"docs\"/read me.md\0".as_bytes());
+    git_output.extend_from_slice("100644 blob 9da69050aa4d1f6488a258a221217a4dd9e73b71\tLINQサンプル.cs\0".as_bytes());
+    git_output.extend_from_slice(b"160000 commit 5a6f0c1e0f2b4cd1d2b0d6c6bb2b5a7f1b8e2f3a\tsubmodule\0");
+    git_output.extend_from_slice(b"100644 blob 037498fba1ca5b3662963c848158b7b678adbbf3\tcaf\xe9.txt\0");
+
+    let blobs = parse_ls_tree(&git_output);
+    assert_eq!(blobs.len(), 4);
+    assert_eq!(blobs[0].0, "a28b99eae8417ac31293a332ef1a125b8772032d");
+    assert_eq!(blobs[0].1, "Cargo.toml");
+    assert_eq!(blobs[1].1, "my \"docs\"/read me.md");
+    assert_eq!(blobs[2].1, "LINQサンプル.cs");
+    // a Latin-1 name is kept and matches the same name quoted by `git log`
+    assert_eq!(blobs[3].1, "caf\\xe9.txt");
+    assert_eq!(unquote_git_path(r#""caf\351.txt""#).unwrap(), blobs[3].1);
+}
+
+#[test]
+fn test_unquote_git_path() {
+    assert_eq!(unquote_git_path("src/main.rs").unwrap(), "src/main.rs");
+    assert_eq!(unquote_git_path("read me.md").unwrap(), "read me.md");
+    assert_eq!(
+        unquote_git_path(r#""LINQ\343\202\265\343\203\263\343\203\227\343\203\253.cs""#).unwrap(),
+        "LINQサンプル.cs"
+    );
+    assert_eq!(unquote_git_path(r#""my \"docs\"\\tab\t.md""#).unwrap(), "my \"docs\"\\tab\t.md");
+    assert_eq!(unquote_git_path(r#""bad\377.txt""#).unwrap(), "bad\\xff.txt");
+    assert_eq!(unquote_git_path(r#""\303\251\303""#).unwrap(), "é\\xc3");
+    assert!(unquote_git_path(&String::from_utf8_lossy(b"bad\xff.txt")).is_none());
+    assert!(unquote_git_path(r#""unterminated"#).is_none());
+}
+
+#[test]
+fn test_parse_log_line_endings() {
+    // the same history as seen by git on Linux and on Windows with the output converted to CRLF
+    let lf_output = "commit d5e742de653954bfae88f0e5f6c8f0a7a5f6c437
+Author: Max <max@onebro.me>
+Date:   Tue Dec 22 17:43:07 2020 +0000
+
+    Add the docs
+
+\"docs/caf\\303\\251.md\"
+README.md
+
+commit 105eaf871c7248c93ae2f13337e9881caf89d489
+Author: Max <max@onebro.me>
+Date:   Mon Dec 21 10:00:00 2020 +0000
+
+    Initial commit
+
+src/main.rs
+";
+    let crlf_output = lf_output.replace('\n', "\r\n");
+    let double_crlf_output = lf_output.replace('\n', "\r\r\n");
+
+    let lf_log = parse_log(lf_output, &[]);
+    assert_eq!(lf_log.len(), 2);
+    assert_eq!(lf_log[0].sha1, "d5e742de653954bfae88f0e5f6c8f0a7a5f6c437");
+    assert!(lf_log[0].files.contains("docs/café.md"));
+    assert_eq!(lf_log[1].files.len(), 1);
+
+    let lf_hash = crate::utils::hash_vec_sha1(lf_log.iter().map(|v| v.sha1.clone()).collect());
+    for output in [crlf_output, double_crlf_output] {
+        let log = parse_log(&output, &[]);
+        assert_eq!(log.len(), lf_log.len());
+        for (entry, lf_entry) in log.iter().zip(lf_log.iter()) {
+            assert_eq!(entry.sha1, lf_entry.sha1);
+            assert_eq!(entry.files, lf_entry.files);
+            assert_eq!(entry.author_name_email, lf_entry.author_name_email);
+            assert_eq!(entry.date_epoch, lf_entry.date_epoch);
+        }
+        assert_eq!(crate::utils::hash_vec_sha1(log.iter().map(|v| v.sha1.clone()).collect()), lf_hash);
+    }
+
+    // hashes of lists with stray line endings match the clean ones
+    assert_eq!(
+        crate::utils::hash_vec_sha1(vec!["d5e742de\r\n".to_owned(), "105eaf87\r".to_owned()]),
+        crate::utils::hash_vec_sha1(vec!["d5e742de".to_owned(), "105eaf87".to_owned()])
+    );
+}
+
+#[tokio::test]
+async fn test_get_log_merges() {
+    let repo_dir = std::env::temp_dir().join(format!("stm_git_merges_test_{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&repo_dir);
+    std::fs::create_dir_all(&repo_dir).unwrap();
+    let git = |email: &str, args: &[&str]| {
+        let output = std::process::Command::new("git")
+            .args(["-c", "user.name=dev", "-c", "merge.conflictStyle=merge", "-c"])
+            .arg(["user.email=", email].concat())
+            .args(args)
+            .current_dir(&repo_dir)
+            .output()
+            .unwrap();
+        output.status.success()
+    };
+
+    // `feature` adds b.txt and changes c.txt, the merge into `main` has a conflict in c.txt resolved by the merger
+    assert!(git("merger@example.com", &["init", "-q", "-b", "main"]));
+    std::fs::write(repo_dir.join("a.txt"), "1\n").unwrap();
+    std::fs::write(repo_dir.join("c.txt"), "1\n").unwrap();
+    assert!(git("merger@example.com", &["add", "-A"]));
+    assert!(git("merger@example.com", &["commit", "-q", "-m", "init"]));
+    assert!(git("merger@example.com", &["checkout", "-q", "-b", "feature"]));
+    std::fs::write(repo_dir.join("b.txt"), "2\n").unwrap();
+    std::fs::write(repo_dir.join("c.txt"), "feature\n").unwrap();
+    assert!(git("dev@example.com", &["add", "-A"]));
+    assert!(git("dev@example.com", &["commit", "-q", "-m", "feature"]));
+    assert!(git("merger@example.com", &["checkout", "-q", "main"]));
+    std::fs::write(repo_dir.join("c.txt"), "main\n").unwrap();
+    assert!(git("merger@example.com", &["commit", "-q", "-a", "-m", "main"]));
+    assert!(!git("merger@example.com", &["merge", "-q", "feature"]));
+    std::fs::write(repo_dir.join("c.txt"), "resolved\n").unwrap();
+    assert!(git("merger@example.com", &["commit", "-q", "-a", "-m", "merge"]));
+
+    let log = get_log(&repo_dir, None, None, &[]).await.unwrap();
+    let files = |msg: &str| {
+        let log_entry = log.iter().find(|v| v.msg.trim() == msg).unwrap();
+        let mut files = log_entry.files.iter().map(|v| v.as_str()).collect::<Vec<&str>>();
+        files.sort_unstable();
+        files
+    };
+    // b.txt arrived with the merge, but was written by the author of `feature`
+    assert_eq!(files("merge"), vec!["c.txt"]);
+    assert_eq!(files("feature"), vec!["b.txt", "c.txt"]);
+
+    let contributors = crate::contributor::Contributor::from_commit_history(log);
+    let merger = contributors.iter().find(|v| v.git_id == "merger@example.com").unwrap();
+    assert!(!merger.touched_files.iter().any(|v| v.name == "b.txt"));
+
+    let _ = std::fs::remove_dir_all(&repo_dir);
+}
+
+#[test]
+fn test_explicit_refs() {
+    let args = log_args(None, None, None);
+    assert_eq!(args[args.len() - 2..], ["HEAD".to_owned(), "--".to_owned()]);
+    let args = log_args(Some(&"max@onebro.me".to_owned()), Some("a1b2c3"), None);
+    assert_eq!(args[args.len() - 2..], ["a1b2c3..HEAD".to_owned(), "--".to_owned()]);
+    assert!(!walks_all_refs(&args));
+    let args = log_args(None, Some("a1b2c3"), Some("d4e5f6"));
+    assert_eq!(args[args.len() - 2..], ["a1b2c3..d4e5f6".to_owned(), "--".to_owned()]);
+
+    let args = ["log".to_owned(), "--branches=feature/*".to_owned()];
+    assert!(walks_all_refs(&args));
+    assert!(walks_all_refs(&["rev-list".to_owned(), "--all".to_owned()]));
+    assert!(!walks_all_refs(&["log".to_owned(), "--author=\"--all\"".to_owned()]));
+}
+
+#[tokio::test]
+async fn test_stream_log() {
+    // the repo this crate is in
+    let repo_dir = Path::new(env!("CARGO_MANIFEST_DIR"));
+
+    let raw_log = get_raw_log(repo_dir, None, None, None).await.unwrap();
+    let parsed_log = parse_log(&raw_log, &[]);
+    let streamed_log = collect_log(stream_log(repo_dir, None, None, None, &[]).await.unwrap())
+        .await
+        .unwrap();
+    assert_eq!(
+        streamed_log.iter().map(|v| &v.sha1).collect::<Vec<&String>>(),
+        parsed_log.iter().map(|v| &v.sha1).collect::<Vec<&String>>()
+    );
+
+    // the receiver can be dropped before the log ends
+    let mut rx = stream_log(repo_dir, None, None, None, &[]).await.unwrap();
+    assert_eq!(rx.recv().await.unwrap().unwrap().sha1, parsed_log[0].sha1);
+    drop(rx);
+
+    // an unknown revision is an error
+    let rx = stream_log(repo_dir, None, None, Some("0000000000000000000000000000000000000000"), &[])
+        .await
+        .unwrap();
+    assert!(collect_log(rx).await.is_err());
+}
+
+#[test]
+fn test_parse_cat_file_batch() {
+    let mut git_output = b"a28b99eae8417ac31293a332ef1a125b8772032d blob 11\nfn main() {\n".to_vec();
+    git_output.extend_from_slice(b"f288702d2fa16d3cdf0035b15a9fcbc552cd88e7 missing\n");
+    git_output.extend_from_slice(b"9da69050aa4d1f6488a258a221217a4dd9e73b71 blob 0\n\n");
+    git_output.extend_from_slice(b"037498fba1ca5b3662963c848158b7b678adbbf3 blob 4\n\x00\xff\n\r\n");
+
+    let blobs = parse_cat_file_batch(&git_output);
+    assert_eq!(blobs.len(), 3);
+    assert_eq!(blobs["a28b99eae8417ac31293a332ef1a125b8772032d"], b"fn main() {");
+    assert!(blobs["9da69050aa4d1f6488a258a221217a4dd9e73b71"].is_empty());
+    assert_eq!(blobs["037498fba1ca5b3662963c848158b7b678adbbf3"], b"\x00\xff\n\r");
+
+    // truncated output keeps the complete blobs
+    let blobs = parse_cat_file_batch(&git_output[..git_output.len() - 3]);
+    assert_eq!(blobs.len(), 2);
+}
+
+#[test]
+fn test_parse_ls_files_skip_worktree() {
+    let files =
+        parse_ls_files_skip_worktree(b"H src/main.rs\0S docs/read me.md\0S docs/LINQ\xe3\x82\xb5.cs\0H Cargo.toml\0");
+    assert_eq!(files.len(), 2);
+    assert!(files.contains("docs/read me.md"));
+    assert!(files.contains("docs/LINQサ.cs"));
+}
+
+#[tokio::test]
+async fn test_prefetch_missing_blobs() {
+    let test_dir = std::env::temp_dir().join(format!("stm_git_prefetch_test_{}", std::process::id()));
+    let src_dir = test_dir.join("src");
+    let clone_dir = test_dir.join("clone");
+    let _ = std::fs::remove_dir_all(&test_dir);
+    std::fs::create_dir_all(&src_dir).unwrap();
+    let git = |repo_dir: &Path, args: &[&str]| {
+        let output = std::process::Command::new("git")
+            .args(["-c", "user.name=dev", "-c", "user.email=dev@example.com"])
+            .args(args)
+            .current_dir(repo_dir)
+            .output()
+            .unwrap();
+        assert!(output.status.success(), "git {:?}", args);
+        String::from_utf8_lossy(&output.stdout).trim().to_owned()
+    };
+
+    git(&src_dir, &["init", "-q"]);
+    git(&src_dir, &["config", "uploadpack.allowFilter", "true"]);
+    for version in ["1", "2"] {
+        std::fs::write(src_dir.join("a.txt"), version).unwrap();
+        std::fs::write(src_dir.join("b.txt"), ["b", version].concat()).unwrap();
+        git(&src_dir, &["add", "-A"]);
+        git(&src_dir, &["commit", "-q", "-m", version]);
+    }
+    let src_url = ["file://", &src_dir.to_string_lossy()].concat();
+    git(&test_dir, &["clone", "-q", "--no-checkout", "--filter=blob:none", &src_url, "clone"]);
+    let missing = || {
+        let git_output = git(&clone_dir, &["rev-list", "--objects", "--missing=print", "HEAD"]);
+        parse_missing_objects(git_output.as_bytes())
+    };
+    assert_eq!(missing().len(), 4);
+
+    // only the requested blobs of the tree at the rev are fetched
+    let a_sha1 = git(&clone_dir, &["rev-parse", "HEAD~1:a.txt"]);
+    let b_sha1 = git(&clone_dir, &["rev-parse", "HEAD:b.txt"]);
+    prefetch_missing_blobs(&clone_dir, Some("HEAD~1"), &[&a_sha1, &b_sha1]).await;
+    let missing = missing();
+    assert_eq!(missing.len(), 3);
+    assert!(!missing.contains(&a_sha1));
+    assert!(missing.contains(&b_sha1));
+
+    let _ = std::fs::remove_dir_all(&test_dir);
+}
@@ -0,0 +1,421 @@
+//! Caches parsed `git log` on disk in chunks to avoid re-parsing very long histories on every run.
+//! Only the commits made since the last run are parsed and stitched with the cached chunks.
+use crate::git::{self, GitLogEntry};
+use crate::report::Report;
+use crate::utils;
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io::{Read, Write};
+use std::path::Path;
+use tokio::sync::mpsc;
+use tracing::{debug, info, warn};
+
+/// The name of the folder inside the project report folder with the cached log chunks and the manifest.
+pub const GIT_LOG_CACHE_DIR_NAME: &str = "git_log_cache";
+
+/// The name of the file listing the chunks in the cache.
+const MANIFEST_FILE_NAME: &str = "manifest.json";
+
+/// The max number of commits per chunk. Chunks are never split, so a chunk may be smaller after a full re-parse.
+const CHUNK_SIZE: usize = 10000;
+
+/// A batch of consecutive log entries stored in a separate file.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+struct LogChunk {
+    /// SHA1 of the latest commit in the chunk
+    newest_sha1: String,
+    /// SHA1 of the earliest commit in the chunk
+    oldest_sha1: String,
+    /// Number of log entries in the chunk
+    commits: usize,
+}
+
+/// The list of chunks that make up the cached log and the values it is valid for.
+#[derive(Serialize, Deserialize, Debug)]
+struct LogCacheManifest {
+    /// Same as `Report.log_hash` for the entire cached log. A mismatch with the project report means the cache
+    /// is out of sync or the history was rewritten.
+    log_hash: String,
+    /// A hash of the ignore rules the log was parsed with. Ignored files are not cached.
+    ignore_paths_hash: String,
+    /// Chunks in the log order, the latest first.
+    chunks: Vec<LogChunk>,
+}
+
+impl LogChunk {
+    /// Returns the file name of the chunk as `git_log_[newest_sha1]_[oldest_sha1].json.gz`.
+    /// Full SHA1s are used because short ones may collide in very long histories.
+    fn file_name(&self) -> String {
+        ["git_log_", &self.newest_sha1, "_", &self.oldest_sha1, ".json.gz"].concat()
+    }
+}
+
+/// Returns the full git log using the cache in `report_dir` if it is in sync with `cached_report`. Only the commits
+/// made after `cached_report.report_commit_sha1` are parsed. The entire log is parsed if the cache cannot be used,
+/// e.g. after a history rewrite. The cache is updated with the new commits. Cache errors are logged and ignored.
+/// The log ends at `rev`, a resolved commit SHA1, or at HEAD if None. Different revs should be cached in different folders.
+pub async fn get_log(
+    repo_dir: &Path,
+    rev: Option<&str>,
+    ignore_paths: &[Regex],
+    report_dir: &Path,
+    cached_report: Option<&Report>,
+) -> Result<Vec<GitLogEntry>, ()> {
+    let cache_dir = report_dir.join(GIT_LOG_CACHE_DIR_NAME);
+    let ignore_paths_hash = utils::hash_vec_sha1(ignore_paths.iter().map(|r| r.as_str().to_owned()).collect());
+
+    if let Some(manifest) = load_valid_manifest(repo_dir, rev, &cache_dir, &ignore_paths_hash, cached_report).await {
+        if let Some(cached_log) = load_chunks(&cache_dir, &manifest) {
+            let newest_cached_sha1 = manifest.chunks[0].newest_sha1.as_str();
+            let new_log =
+                git::collect_log(git::stream_log(repo_dir, None, Some(newest_cached_sha1), rev, ignore_paths).await?)
+                    .await?;
+            info!("Parsed {} new log entries, {} cached", new_log.len(), cached_log.len());
+
+            if new_log.is_empty() {
+                return Ok(cached_log);
+            }
+
+            // the latest chunk is re-saved together with the new commits while it is small to avoid
+            // having many tiny chunks after frequent runs
+            let mut chunks = manifest.chunks;
+            if chunks[0].commits + new_log.len() <= CHUNK_SIZE {
+                chunks.remove(0);
+            }
+            let unchanged_len = chunks.iter().map(|v| v.commits).sum::<usize>();
+
+            // the new commits go in front of the cached ones because the log is sorted latest first
+            let mut git_log = new_log;
+            git_log.extend(cached_log);
+
+            let mut new_chunks = save_chunks(&cache_dir, &git_log[..git_log.len() - unchanged_len]);
+            new_chunks.extend(chunks);
+            save_manifest(&cache_dir, &git_log, &ignore_paths_hash, new_chunks);
+
+            return Ok(git_log);
+        }
+    }
+
+    get_full_log(repo_dir, rev, ignore_paths, &cache_dir, &ignore_paths_hash).await
+}
+
+/// Parses the entire log and replaces the cache with it. The chunks are saved as the log is read from git.
+async fn get_full_log(
+    repo_dir: &Path,
+    rev: Option<&str>,
+    ignore_paths: &[Regex],
+    cache_dir: &Path,
+    ignore_paths_hash: &str,
+) -> Result<Vec<GitLogEntry>, ()> {
+    // old chunks are of no use after a history rewrite or a change of rules
+    let save_to_cache = match fs::remove_dir_all(cache_dir) {
+        Ok(_) => true,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => true,
+        Err(e) => {
+            warn!("Cannot clear git log cache in {}: {}", cache_dir.to_string_lossy(), e);
+            false
+        }
+    };
+
+    let rx = git::stream_log(repo_dir, None, None, rev, ignore_paths).await?;
+    let (git_log, chunks) = receive_log(rx, if save_to_cache { Some(cache_dir) } else { None }).await?;
+    info!("Parsed the full git log: {} entries", git_log.len());
+
+    if save_to_cache {
+        save_manifest(cache_dir, &git_log, ignore_paths_hash, chunks);
+    }
+
+    Ok(git_log)
+}
+
+/// Collects the log entries from `git::stream_log()` and saves every `CHUNK_SIZE` of them in `cache_dir` as soon as
+/// they arrive, so that only one chunk at a time is serialized. Nothing is saved if `cache_dir` is None.
+/// Returns the log with the list of saved chunks, which is empty if any of them could not be saved.
+async fn receive_log(
+    mut rx: mpsc::Receiver<Result<GitLogEntry, ()>>,
+    cache_dir: Option<&Path>,
+) -> Result<(Vec<GitLogEntry>, Vec<LogChunk>), ()> {
+    let mut git_log: Vec<GitLogEntry> = Vec::new();
+    let mut chunks: Option<Vec<LogChunk>> = cache_dir.map(|_| Vec::new());
+
+    let mut unsaved_len = 0usize;
+    while let Some(log_entry) = rx.recv().await {
+        git_log.push(log_entry?);
+        unsaved_len += 1;
+        if unsaved_len == CHUNK_SIZE {
+            chunks = save_next_chunk(cache_dir, &git_log, chunks);
+            unsaved_len = 0;
+        }
+    }
+    if unsaved_len > 0 {
+        chunks = save_next_chunk(cache_dir, &git_log, chunks);
+    }
+
+    Ok((git_log, chunks.unwrap_or_default()))
+}
+
+/// Saves the entries of `git_log` that are not in `chunks` yet as a new chunk. Returns None if this or any of
+/// the previous chunks could not be saved.
+fn save_next_chunk(
+    cache_dir: Option<&Path>,
+    git_log: &[GitLogEntry],
+    chunks: Option<Vec<LogChunk>>,
+) -> Option<Vec<LogChunk>> {
+    let mut chunks = chunks?;
+    let saved_len = chunks.iter().map(|v| v.commits).sum::<usize>();
+    let new_chunks = save_chunks(cache_dir?, &git_log[saved_len..]);
+    if new_chunks.is_empty() {
+        return None;
+    }
+    chunks.extend(new_chunks);
+
+    Some(chunks)
+}
+
+/// Returns the manifest if the cache can be extended with new commits, None otherwise.
+async fn load_valid_manifest(
+    repo_dir: &Path,
+    rev: Option<&str>,
+    cache_dir: &Path,
+    ignore_paths_hash: &str,
+    cached_report: Option<&Report>,
+) -> Option<LogCacheManifest> {
+    // the cache is only valid for the log the cached report was built from
+    let cached_report = cached_report?;
+    let report_commit_sha1 = cached_report.report_commit_sha1.as_ref()?;
+
+    let manifest_file = cache_dir.join(MANIFEST_FILE_NAME);
+    let manifest = match fs::read(&manifest_file) {
+        Ok(v) => match serde_json::from_slice::<LogCacheManifest>(&v) {
+            Ok(v) => v,
+            Err(e) => {
+                warn!("Invalid git log cache manifest {}: {}", manifest_file.to_string_lossy(), e);
+                return None;
+            }
+        },
+        Err(_) => {
+            debug!("No git log cache in {}", cache_dir.to_string_lossy());
+            return None;
+        }
+    };
+
+    if manifest.chunks.is_empty()
+        || manifest.ignore_paths_hash != ignore_paths_hash
+        || Some(&manifest.log_hash) != cached_report.log_hash.as_ref()
+        || &manifest.chunks[0].newest_sha1 != report_commit_sha1
+    {
+        info!("Git log cache is out of date");
+        return None;
+    }
+
+    // a shallow clone may have been deepened since, which adds commits at the bottom of the log
+    if cached_report.history_truncated {
+        info!("Git log cache is for a shallow clone");
+        return None;
+    }
+
+    // a rebase, amend or a force-pull makes the cached commits unreachable
+    if !git::is_ancestor_of_head(repo_dir, report_commit_sha1, rev).await {
+        info!("History rewrite detected. Git log cache is invalid.");
+        return None;
+    }
+
+    Some(manifest)
+}
+
+/// Loads all chunks listed in the manifest in the log order. Returns None if any of them is missing or invalid.
+fn load_chunks(cache_dir: &Path, manifest: &LogCacheManifest) -> Option<Vec<GitLogEntry>> {
+    let mut git_log: Vec<GitLogEntry> = Vec::new();
+
+    for chunk in &manifest.chunks {
+        let chunk_file = cache_dir.join(chunk.file_name());
+        let log_entries = match read_chunk(&chunk_file) {
+            Some(v) => v,
+            None => {
+                warn!("Cannot read git log cache chunk {}", chunk_file.to_string_lossy());
+                return None;
+            }
+        };
+
+        // check the boundaries to make sure the chunk is the one listed in the manifest
+        if log_entries.len() != chunk.commits
+            || log_entries.first().map(|v| &v.sha1) != Some(&chunk.newest_sha1)
+            || log_entries.last().map(|v| &v.sha1) != Some(&chunk.oldest_sha1)
+        {
+            warn!("Git log cache chunk mismatch: {}", chunk_file.to_string_lossy());
+            return None;
+        }
+
+        git_log.extend(log_entries);
+    }
+
+    Some(git_log)
+}
+
+/// Checks that the cache in `report_dir` can be loaded in full and returns its `log_hash`. Returns None if there is
+/// no cache and an error with the reason if it is broken, e.g. a chunk is missing or was partially written.
+pub(crate) fn verify(report_dir: &Path) -> Result<Option<String>, String> {
+    let cache_dir = report_dir.join(GIT_LOG_CACHE_DIR_NAME);
+    let manifest = match fs::read(cache_dir.join(MANIFEST_FILE_NAME)) {
+        Ok(v) => serde_json::from_slice::<LogCacheManifest>(&v).map_err(|e| format!("invalid manifest: {}", e))?,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(format!("cannot read the manifest: {}", e)),
+    };
+
+    match load_chunks(&cache_dir, &manifest) {
+        Some(_) => Ok(Some(manifest.log_hash)),
+        None => Err("missing or invalid log chunks".to_owned()),
+    }
+}
+
+/// Reads and decompresses a single chunk file.
+fn read_chunk(chunk_file: &Path) -> Option<Vec<GitLogEntry>> {
+    let compressed = fs::read(chunk_file).ok()?;
+    let mut json = Vec::new();
+    GzDecoder::new(compressed.as_slice()).read_to_end(&mut json).ok()?;
+    serde_json::from_slice::<Vec<GitLogEntry>>(&json).ok()
+}
+
+/// Splits `git_log` into chunks of `CHUNK_SIZE`, saves them in `cache_dir` and returns their list in the log order.
+/// Chunks that could not be saved are not returned, which makes the cache incomplete and it will be rebuilt next time.
+fn save_chunks(cache_dir: &Path, git_log: &[GitLogEntry]) -> Vec<LogChunk> {
+    if let Err(e) = fs::create_dir_all(cache_dir) {
+        warn!("Cannot create git log cache folder {}: {}", cache_dir.to_string_lossy(), e);
+        return Vec::new();
+    }
+
+    let mut chunks: Vec<LogChunk> = Vec::new();
+    for log_entries in git_log.chunks(CHUNK_SIZE) {
+        let chunk = LogChunk {
+            newest_sha1: log_entries[0].sha1.clone(),
+            oldest_sha1: log_entries[log_entries.len() - 1].sha1.clone(),
+            commits: log_entries.len(),
+        };
+
+        let chunk_file = cache_dir.join(chunk.file_name());
+        if let Err(e) = write_gz_json(&chunk_file, &log_entries) {
+            warn!("Cannot save git log cache chunk {}: {}", chunk_file.to_string_lossy(), e);
+            return Vec::new();
+        }
+        chunks.push(chunk);
+    }
+
+    chunks
+}
+
+/// Saves the manifest for `git_log` made of `chunks` and removes chunk files that are no longer listed.
+/// No manifest is saved if the chunks do not add up to the log.
+fn save_manifest(cache_dir: &Path, git_log: &[GitLogEntry], ignore_paths_hash: &str, chunks: Vec<LogChunk>) {
+    if git_log.is_empty() || chunks.iter().map(|v| v.commits).sum::<usize>() != git_log.len() {
+        debug!("Git log cache was not saved");
+        let _ = fs::remove_file(cache_dir.join(MANIFEST_FILE_NAME));
+        return;
+    }
+
+    let manifest = LogCacheManifest {
+        // must be calculated the same way as `Report.log_hash`
+        log_hash: utils::hash_vec_sha1(git_log.iter().map(|entry| entry.sha1.clone()).collect::<Vec<String>>()),
+        ignore_paths_hash: ignore_paths_hash.to_owned(),
+        chunks,
+    };
+
+    let manifest_file = cache_dir.join(MANIFEST_FILE_NAME);
+    let json = serde_json::to_vec(&manifest).expect("Cannot serialize git log cache manifest. It's a bug.");
+    if let Err(e) = utils::write_atomically(&manifest_file, &json) {
+        warn!("Cannot save git log cache manifest {}: {}", manifest_file.to_string_lossy(), e);
+        return;
+    }
+
+    // remove chunks replaced by newer ones
+    let chunk_files = manifest.chunks.iter().map(|v| v.file_name()).collect::<Vec<String>>();
+    if let Ok(dir_entries) = fs::read_dir(cache_dir) {
+        for dir_entry in dir_entries.filter_map(|v| v.ok()) {
+            let file_name = dir_entry.file_name().to_string_lossy().to_string();
+            // temp files may belong to another run writing into a shared folder
+            if file_name != MANIFEST_FILE_NAME
+                && !chunk_files.contains(&file_name)
+                && !file_name.ends_with(utils::TEMP_FILE_EXTENSION)
+            {
+                debug!("Removing stale git log cache chunk {}", file_name);
+                let _ = fs::remove_file(dir_entry.path());
+            }
+        }
+    }
+
+    debug!("Git log cache saved in {}", cache_dir.to_string_lossy());
+}
+
+/// Writes the value into a file as gzipped JSON.
+fn write_gz_json<T: Serialize>(file: &Path, value: &T) -> Result<(), std::io::Error> {
+    let json = serde_json::to_vec(value)?;
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(&json)?;
+    utils::write_atomically(file, &encoder.finish()?)
+}
+
+#[test]
+fn test_chunks_roundtrip() {
+    let cache_dir = std::env::temp_dir().join(format!("stm_git_log_cache_test_{}", std::process::id()));
+    let git_log = (0..CHUNK_SIZE + 5)
+        .map(|i| {
+            let mut entry = GitLogEntry::new();
+            entry.sha1 = format!("{:040x}", i);
+            entry
+        })
+        .collect::<Vec<GitLogEntry>>();
+
+    let chunks = save_chunks(&cache_dir, &git_log);
+    assert_eq!(chunks.len(), 2);
+    assert_eq!(chunks[1].commits, 5);
+    assert_eq!(chunks[1].oldest_sha1, git_log[CHUNK_SIZE + 4].sha1);
+
+    save_manifest(&cache_dir, &git_log, "", chunks);
+    let manifest: LogCacheManifest =
+        serde_json::from_slice(&fs::read(cache_dir.join(MANIFEST_FILE_NAME)).unwrap()).unwrap();
+    let cached_log = load_chunks(&cache_dir, &manifest).unwrap();
+    assert_eq!(cached_log.len(), git_log.len());
+    assert_eq!(
+        manifest.log_hash,
+        utils::hash_vec_sha1(cached_log.iter().map(|v| v.sha1.clone()).collect::<Vec<String>>())
+    );
+
+    // a chunk that does not add up to the log invalidates the manifest
+    save_manifest(&cache_dir, &git_log, "", manifest.chunks[..1].to_vec());
+    assert!(!cache_dir.join(MANIFEST_FILE_NAME).exists());
+
+    let _ = fs::remove_dir_all(&cache_dir);
+}
+
+#[tokio::test]
+async fn test_receive_log() {
+    let cache_dir = std::env::temp_dir().join(format!("stm_git_log_cache_receive_test_{}", std::process::id()));
+    let _ = fs::remove_dir_all(&cache_dir);
+    let (tx, rx) = mpsc::channel(10);
+    tokio::spawn(async move {
+        for i in 0..CHUNK_SIZE + 5 {
+            let mut entry = GitLogEntry::new();
+            entry.sha1 = format!("{:040x}", i);
+            tx.send(Ok(entry)).await.unwrap();
+        }
+    });
+
+    // the first chunk is saved before the rest of the log is received
+    let (git_log, chunks) = receive_log(rx, Some(&cache_dir)).await.unwrap();
+    assert_eq!(git_log.len(), CHUNK_SIZE + 5);
+    assert_eq!(chunks.iter().map(|v| v.commits).collect::<Vec<usize>>(), vec![CHUNK_SIZE, 5]);
+    assert_eq!(chunks[1].newest_sha1, git_log[CHUNK_SIZE].sha1);
+    assert!(cache_dir.join(chunks[0].file_name()).exists());
+
+    // an error from git fails the whole log
+    let (tx, rx) = mpsc::channel(10);
+    tx.send(Ok(GitLogEntry::new())).await.unwrap();
+    tx.send(Err(())).await.unwrap();
+    drop(tx);
+    assert!(receive_log(rx, None).await.is_err());
+
+    let _ = fs::remove_dir_all(&cache_dir);
+}
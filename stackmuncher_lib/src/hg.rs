@@ -0,0 +1,371 @@
+use crate::git::{is_in_ignore_list, GitBlob, GitLogEntry, ListOfBlobs};
+use crate::vcs::{Vcs, VcsKind};
+use async_trait::async_trait;
+use chrono::TimeZone;
+use regex::Regex;
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+use tokio::process::Command;
+use tracing::{debug, error, info, trace, warn};
+
+/// Separates fields of a single changeset in `hg log` output.
+const FIELD_SEPARATOR: char = '\x1f';
+/// Separates file names in the list of files of a changeset in `hg log` output.
+const FILE_SEPARATOR: char = '\x1e';
+/// Separates changesets in `hg log` output. Commit messages can span multiple lines, so `\n` cannot be used.
+const RECORD_SEPARATOR: char = '\x1d';
+
+/// A template for `hg log` with one changeset per record:
+/// `node|epoch tz_offset|author name|author email|message|file1 file2` with control chars as separators.
+const LOG_TEMPLATE: &str =
+    r"{node}\x1f{date|hgdate}\x1f{author|person}\x1f{author|email}\x1f{desc}\x1f{join(files, '\x1e')}\x1d";
+
+/// Mercurial implementation of `Vcs`. Requires `hg` to be installed and on the PATH.
+/// Changesets are mapped to `GitLogEntry` and file revisions (filenodes) to `GitBlob`.
+pub struct Mercurial;
+
+/// Executes an hg command in the specified dir. Returns stdout or an error if the command failed.
+/// Set `expect_blank_err_msg` to `true` if a non-zero exit code with no error message is an expected outcome,
+/// e.g. `hg config` for a missing setting.
+pub async fn execute_hg_command(args: Vec<String>, repo_dir: &Path, expect_blank_err_msg: bool) -> Result<Vec<u8>, ()> {
+    // build `hg ...` command
+    let mut cmd = Command::new("hg");
+    cmd.args(args);
+    cmd.current_dir(repo_dir);
+    // disables user settings that change the output format, e.g. aliases, localization, default templates
+    cmd.env("HGPLAIN", "1");
+
+    let hg_output = match cmd.output().await {
+        Err(e) => {
+            error!("Hg command failed with {}", e);
+            return Err(());
+        }
+        Ok(v) => v,
+    };
+
+    let status = hg_output.status.to_string();
+    debug!("Status: {}, stdout len: {}", status, hg_output.stdout.len());
+
+    if hg_output.status.code() != Some(0) {
+        let std_err = String::from_utf8_lossy(&hg_output.stderr);
+        if expect_blank_err_msg && std_err.trim().is_empty() {
+            debug!("Hg command returned blank stderr. Status: {}. Command: {:?}", status, cmd);
+            return Ok(vec![]);
+        }
+        warn!("Hg command failed. Status: {}. Stderr: {}. Command: {:?}", status, std_err, cmd);
+        return Err(());
+    }
+
+    Ok(hg_output.stdout)
+}
+
+/// Converts an optional commit ID into an hg revision. None is the parent of the working directory, same as HEAD in Git.
+fn rev_or_head(commit_sha1: Option<String>) -> String {
+    commit_sha1.unwrap_or_else(|| ".".to_owned())
+}
+
+/// Parses a single record of `hg log` output produced with `LOG_TEMPLATE`. Returns None if the record is corrupt.
+fn parse_log_record(record: &str, ignore_paths: &[Regex]) -> Option<GitLogEntry> {
+    let fields = record.split(FIELD_SEPARATOR).collect::<Vec<&str>>();
+    if fields.len() != 6 {
+        warn!("Corrupt hg log record: {}", record);
+        return None;
+    }
+
+    let mut log_entry = GitLogEntry::new();
+    log_entry.sha1 = fields[0].trim().to_owned();
+
+    // hgdate is `1609627414 -46800` where the offset is in seconds west of UTC
+    let (epoch, tz_offset) = fields[1].trim().split_once(' ')?;
+    let epoch = epoch.parse::<i64>().ok()?;
+    let tz_offset = tz_offset.parse::<i32>().ok()?;
+    let date = match chrono::FixedOffset::west_opt(tz_offset).and_then(|tz| tz.timestamp_opt(epoch, 0).single()) {
+        Some(v) => v,
+        None => {
+            error!("Invalid commit date: {}", fields[1]);
+            return None;
+        }
+    };
+    log_entry.date_epoch = epoch;
+    log_entry.date = date.to_rfc3339();
+
+    log_entry.author_name_email = (fields[2].trim().to_owned(), fields[3].trim().to_owned());
+    log_entry.msg = fields[4].to_owned();
+
+    log_entry.files = fields[5]
+        .split(FILE_SEPARATOR)
+        .filter(|file| !file.is_empty() && !is_in_ignore_list(ignore_paths, file))
+        .map(|file| file.to_owned())
+        .collect::<HashSet<String>>();
+
+    // commits consisting entirely of ignored files are of no interest
+    if log_entry.files.is_empty() {
+        None
+    } else {
+        Some(log_entry)
+    }
+}
+
+/// Parses `hg manifest --debug` output into (filenode, path) pairs. The lines look like this:
+/// ```
+/// 0b9e3a7a1c6d0a7e2d7b1cb0c1dfa4e8f16a3f22 644   Cargo.toml
+/// 5f3f0e2c2b1e0a4b8fbb8d2b5e2d7fd4b1e7d3c1 755 * build.sh
+/// ```
+fn parse_manifest_line(line: &str) -> Option<(&str, &str)> {
+    // 40 chars of the hash, a space, 3 chars of mode, a space, a flag char or space, a space
+    if line.len() < 48 || !line.is_char_boundary(47) {
+        warn!("Corrupt hg manifest line: {}", line);
+        return None;
+    }
+
+    Some((&line[..40], &line[47..]))
+}
+
+#[async_trait]
+impl Vcs for Mercurial {
+    fn kind(&self) -> VcsKind {
+        VcsKind::Mercurial
+    }
+
+    async fn get_log(
+        &self,
+        repo_dir: &Path,
+        contributor_git_identity: Option<&String>,
+        rev: Option<&str>,
+        ignore_paths: &[Regex],
+    ) -> Result<Vec<GitLogEntry>, ()> {
+        debug!("Extracting hg log");
+
+        let mut hg_args: Vec<String> = vec![
+            "log".into(),
+            "--no-merges".into(),
+            "--template".into(),
+            LOG_TEMPLATE.into(),
+        ];
+        // the ancestors of the revision, latest first, the same as `git log rev`
+        if let Some(rev) = rev {
+            hg_args.push("--rev".into());
+            hg_args.push(["reverse(::", rev, ")"].concat());
+        }
+        if let Some(author) = contributor_git_identity {
+            hg_args.push("--user".into());
+            hg_args.push(author.clone());
+        };
+
+        let hg_output = execute_hg_command(hg_args, repo_dir, false).await?;
+        let hg_output = String::from_utf8_lossy(&hg_output);
+
+        // hg lists the changesets latest first, same as git
+        let log_entries = hg_output
+            .split(RECORD_SEPARATOR)
+            .filter(|record| !record.trim().is_empty())
+            .filter_map(|record| parse_log_record(record, ignore_paths))
+            .collect::<Vec<GitLogEntry>>();
+
+        debug!("Found {} commits of interest", log_entries.len());
+
+        Ok(log_entries)
+    }
+
+    async fn get_all_tree_files(
+        &self,
+        repo_dir: &Path,
+        commit_sha1: Option<String>,
+        ignore_paths: &[Regex],
+    ) -> Result<HashSet<String>, ()> {
+        let hg_args = vec!["manifest".into(), "--rev".into(), rev_or_head(commit_sha1)];
+        let all_files = execute_hg_command(hg_args, repo_dir, false).await?;
+        let all_files = String::from_utf8_lossy(&all_files);
+
+        let files = all_files
+            .lines()
+            .filter(|file| !file.is_empty())
+            .map(|file| file.to_owned())
+            .collect::<HashSet<String>>();
+        let tree_all = files.len();
+
+        let files = files
+            .into_iter()
+            .filter(|file| !is_in_ignore_list(ignore_paths, file))
+            .collect::<HashSet<String>>();
+
+        info!(
+            "Files in the hg manifest: {}, ignored: {}, processing: {}",
+            tree_all,
+            tree_all - files.len(),
+            files.len(),
+        );
+
+        Ok(files)
+    }
+
+    async fn populate_blob_sha1(
+        &self,
+        repo_dir: &Path,
+        blobs: ListOfBlobs,
+        commit_sha1: Option<String>,
+        _ignore_case: bool,
+    ) -> Result<ListOfBlobs, ()> {
+        let rev = rev_or_head(commit_sha1);
+        let hg_args = vec!["manifest".into(), "--debug".into(), "--rev".into(), rev.clone()];
+        let all_files = execute_hg_command(hg_args, repo_dir, false).await?;
+        let all_files = String::from_utf8_lossy(&all_files);
+
+        let updated_blobs = all_files
+            .lines()
+            .filter_map(|line| {
+                trace!("populate_blob_sha1: {}", line);
+                let (filenode, file_name) = parse_manifest_line(line)?;
+                let blob = blobs.get(file_name)?;
+                Some((
+                    file_name.to_owned(),
+                    GitBlob {
+                        sha1: filenode.to_owned(),
+                        commit_sha1: blob.commit_sha1.clone(),
+                        commit_date_epoch: blob.commit_date_epoch,
+                        commit_date_iso: blob.commit_date_iso.clone(),
+                    },
+                ))
+            })
+            .collect::<ListOfBlobs>();
+
+        debug!(
+            "Setting filenodes for rev {}. Requested: {}, set: {}",
+            rev,
+            blobs.len(),
+            updated_blobs.len()
+        );
+
+        Ok(updated_blobs)
+    }
+
+    async fn get_blob_contents(
+        &self,
+        repo_dir: &Path,
+        file_name: &str,
+        _blob_sha1: &str,
+        commit_sha1: &str,
+    ) -> Result<Vec<u8>, ()> {
+        // `path:` prefix stops hg from interpreting the file name as a pattern
+        let hg_args = vec![
+            "cat".into(),
+            "--rev".into(),
+            commit_sha1.to_owned(),
+            ["path:", file_name].concat(),
+        ];
+        execute_hg_command(hg_args, repo_dir, false).await
+    }
+
+    async fn get_blobs_contents(&self, _repo_dir: &Path, _blob_sha1s: &[&str]) -> HashMap<String, Vec<u8>> {
+        // file revisions are read by the file name and the changeset, one at a time
+        HashMap::new()
+    }
+
+    async fn get_local_identities(&self, repo_dir: &Path) -> Result<Vec<String>, ()> {
+        debug!("Extracting hg identities");
+
+        // the username is usually in `Name <email>` format, but can be anything
+        let hg_args = vec!["config".into(), "ui.username".into()];
+        let hg_output = execute_hg_command(hg_args, repo_dir, true).await?;
+        let user_name = String::from_utf8_lossy(&hg_output).trim().to_lowercase();
+        if user_name.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let identity = match (user_name.rfind('<'), user_name.rfind('>')) {
+            (Some(start), Some(end)) if start < end && end - start > 1 => user_name[start + 1..end].trim().to_owned(),
+            _ => user_name,
+        };
+
+        Ok(vec![identity])
+    }
+
+    async fn is_ignore_case(&self, _repo_dir: &Path) -> bool {
+        // hg stores the case of the file names as committed and folds them on its own
+        false
+    }
+
+    async fn get_head_commit(&self, repo_dir: &Path, rev: Option<&str>) -> Result<String, ()> {
+        let hg_args = vec![
+            "log".into(),
+            "-r".into(),
+            rev_or_head(rev.map(|v| v.to_owned())),
+            "--template".into(),
+            "{node}".into(),
+        ];
+        let hg_output = execute_hg_command(hg_args, repo_dir, false).await?;
+        let node = String::from_utf8_lossy(&hg_output).trim().to_owned();
+
+        // an empty repo has no changesets and reports a null node
+        if node.is_empty() || node.chars().all(|c| c == '0') {
+            debug!("No changesets in {}", repo_dir.to_string_lossy());
+            return Err(());
+        }
+
+        Ok(node)
+    }
+
+    async fn is_empty_repo(&self, repo_dir: &Path) -> bool {
+        // the tip of a repo without changesets is the null revision
+        let hg_args = vec![
+            "log".into(),
+            "-r".into(),
+            "tip".into(),
+            "--template".into(),
+            "{rev}".into(),
+        ];
+        match execute_hg_command(hg_args, repo_dir, false).await {
+            Ok(v) => String::from_utf8_lossy(&v).trim() == "-1",
+            Err(_) => false,
+        }
+    }
+
+    async fn get_shallow_commits(&self, _repo_dir: &Path) -> HashSet<String> {
+        // hg has no shallow clones without extensions the app does not support
+        HashSet::new()
+    }
+
+    async fn get_partial_clone_filter(&self, _repo_dir: &Path) -> Option<String> {
+        // narrow and remotefilelog clones need extensions the app does not support
+        None
+    }
+
+    async fn prefetch_missing_blobs(&self, _repo_dir: &Path, _rev: Option<&str>, _blob_sha1s: &[&str]) {
+        // all file revisions are local, see `get_partial_clone_filter`
+    }
+
+    async fn get_sparse_checkout_exclusions(&self, _repo_dir: &Path) -> Option<HashSet<String>> {
+        None
+    }
+}
+
+#[test]
+fn test_parse_hg_log_record() {
+    let record = [
+        "d5e742de653954bfae88f0e5f6c8f0a7a5f6c437",
+        "1609627414 -46800",
+        "rimutaka",
+        "max@onebro.me",
+        "Switched to hg\n\nMulti-line message",
+        "src/main.rs\x1etarget/debug/app",
+    ]
+    .join("\x1f");
+    let ignore_paths = vec![Regex::new("^target/").unwrap()];
+
+    let log_entry = parse_log_record(&record, &ignore_paths).unwrap();
+    assert_eq!(log_entry.sha1, "d5e742de653954bfae88f0e5f6c8f0a7a5f6c437");
+    assert_eq!(log_entry.date_epoch, 1609627414);
+    assert_eq!(log_entry.date, "2021-01-03T11:43:34+13:00");
+    assert_eq!(log_entry.author_name_email, ("rimutaka".to_owned(), "max@onebro.me".to_owned()));
+    assert_eq!(log_entry.files.len(), 1);
+    assert!(log_entry.files.contains("src/main.rs"));
+
+    // a commit with ignored files only is skipped
+    let record = record.replace("src/main.rs\x1e", "");
+    assert!(parse_log_record(&record, &ignore_paths).is_none());
+
+    assert_eq!(
+        parse_manifest_line("5f3f0e2c2b1e0a4b8fbb8d2b5e2d7fd4b1e7d3c1 755 * build.sh").unwrap(),
+        ("5f3f0e2c2b1e0a4b8fbb8d2b5e2d7fd4b1e7d3c1", "build.sh")
+    );
+}
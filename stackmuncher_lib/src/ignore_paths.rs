@@ -16,7 +16,7 @@ fn test_compile_ignore_paths() {
 
 /// A list of path fragments, file names, file extensions as Regex.
 /// Files with the path matching any of regex from this list are ignored.
-const IGNORE_PATHS: [&str; 59] = [
+pub(crate) const IGNORE_PATHS: [&str; 59] = [
     // known framework paths
     r#"(?i)node_modules[/\\]"#,
     r#"(?i)angular[/\\]README\.md"#,
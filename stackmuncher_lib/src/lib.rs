@@ -1,21 +1,44 @@
+use analyzer::AnalysisError;
 use chrono::TimeZone;
-use contributor::Contributor;
+use contributor::{Contributor, ContributorFile};
 use git::{log_entries_to_list_of_blobs, GitBlob, GitLogEntry, ListOfBlobs};
-use report::Report;
+use processors::{MunchError, MunchOptions};
+use project_config::Duplicates;
+use report::{commit_cadence::CommitCadence, commit_time_histo::CommitTimeHisto, Coverage, Report, Tenure};
+use skip_list::SkipList;
+use vcs::Vcs;
 use std::collections::{HashMap, HashSet};
 use std::path::Path;
-use tracing::{debug, info, trace, warn};
+use std::time::Instant;
+use tracing::{debug, error, field, info, info_span, trace, warn, Instrument};
 
+pub mod analyzer;
+pub mod bench;
+pub mod cache_check;
 pub mod code_rules;
 pub mod config;
 pub mod contributor;
+pub mod file_trace;
 pub mod file_type;
+mod generated_code;
 pub mod git;
+pub mod git_log_cache;
+mod hg;
 mod ignore_paths;
+pub mod mailmap;
 pub mod muncher;
 pub mod processors;
+pub mod project_config;
 pub mod report;
+pub mod report_store;
+#[cfg(feature = "sqlite-store")]
+mod report_store_sqlite;
+pub mod skip_list;
 pub mod utils;
+pub mod vcs;
+
+/// The number of blobs read from the VCS in one go by `process_project_files`.
+const BLOB_BATCH_SIZE: usize = 500;
 
 impl Report {
     /// Processes the entire repo with or without a previous report. If the report is present and the munchers
@@ -23,31 +46,152 @@ impl Report {
     /// * it's a new repo
     /// * the munchers changed and the entire repo needs to be reprocessed
     /// * `git_log` must contain the entire log for the project or the function will get the log as needed if None
+    /// * `rev` - the resolved SHA1 of the commit to analyze the project at or None for HEAD, see `Vcs::get_head_commit`
     /// ## Return values
-    /// * `Err` - something went wrong, error details logged
+    /// * `Err` - something went wrong, error details logged, or the repo is too large for `munch_limits`
     /// * `None` - no changes, use the cached report
     /// * `Some` - an updated report
     pub async fn process_project(
         code_rules: &mut code_rules::CodeRules,
         project_dir: &Path,
+        rev: Option<&str>,
         old_report: &Option<report::Report>,
         git_log: Option<Vec<GitLogEntry>>,
-    ) -> Result<Option<report::Report>, ()> {
-        let report = report::Report::new();
+        skip_list: &mut SkipList,
+    ) -> Result<Option<report::Report>, AnalysisError> {
+        let mut report = report::Report::new();
+        let vcs = vcs::detect(project_dir);
+
+        // a freshly initialized repo has no HEAD to take the log or the tree from
+        if vcs.get_head_commit(project_dir, rev).await.is_err() {
+            if !vcs.is_empty_repo(project_dir).await {
+                error!("No commit for {} in {}", rev.unwrap_or("HEAD"), project_dir.to_string_lossy());
+                return Err(AnalysisError::Failed);
+            }
+            info!("No commits in {}", project_dir.to_string_lossy());
+            report.empty_repo = true;
+            return Ok(Some(report));
+        }
+
+        // some of the oldest commits are missing in shallow clones, e.g. `git clone --depth 50` on CI
+        let shallow_commits = vcs.get_shallow_commits(project_dir).await;
+        report.history_truncated = !shallow_commits.is_empty();
 
         // get the full git log if none was supplied
         let git_log = match git_log {
             Some(v) => v,
-            None => git::get_log(project_dir, None, &code_rules.ignore_paths).await?,
+            None => {
+                let span = info_span!("phase", phase = "log_fetch", commits = field::Empty);
+                let git_log = vcs
+                    .get_log(project_dir, None, rev, &code_rules.ignore_paths)
+                    .instrument(span.clone())
+                    .await?;
+                span.record("commits", git_log.len());
+                git_log
+            }
+        };
+
+        // get the list of files in the tree at HEAD or `rev`
+        // ignored files are removed here rather than by the VCS to count them for the coverage and assets sections
+        let span = info_span!("phase", phase = "tree_scan", files = field::Empty);
+        let all_tree_files = vcs
+            .get_all_tree_files(project_dir, rev.map(|v| v.to_owned()), &[])
+            .instrument(span.clone())
+            .await?;
+        let all_head_files = all_tree_files
+            .iter()
+            .filter(|file_path| !git::is_in_ignore_list(&code_rules.ignore_paths, file_path))
+            .cloned()
+            .collect::<HashSet<String>>();
+        debug!(
+            "Files in the tree: {}, ignored: {}",
+            all_tree_files.len(),
+            all_tree_files.len() - all_head_files.len()
+        );
+        span.record("files", all_head_files.len());
+        drop(span);
+
+        // blobs missing from partial clones are fetched from the remote on demand
+        let partial_clone_filter = vcs.get_partial_clone_filter(project_dir).await;
+        if let Some(filter) = &partial_clone_filter {
+            info!("Partial clone with filter {}", filter);
+        }
+
+        // only the files in a sparse checkout are analyzed, the rest of the tree is counted as not analyzed
+        // the sparse checkout describes the working tree, so it does not apply to other refs
+        let sparse_checkout_exclusions = match rev {
+            Some(_) => None,
+            None => vcs.get_sparse_checkout_exclusions(project_dir).await,
         };
+        let sparse_files = match sparse_checkout_exclusions {
+            Some(exclusions) => {
+                let sparse_files = all_head_files
+                    .iter()
+                    .filter(|file_path| !exclusions.contains(*file_path))
+                    .cloned()
+                    .collect::<HashSet<String>>();
+                info!("Sparse checkout: {} of {} files", sparse_files.len(), all_head_files.len());
+                Some(sparse_files)
+            }
+            None => None,
+        };
+        let analyzable_files = sparse_files.as_ref().unwrap_or(&all_head_files);
 
-        // get the list of files in the tree at HEAD
-        let all_head_files = git::get_all_tree_files(project_dir, None, &code_rules.ignore_paths).await?;
-        if all_head_files.len() as u64 > Report::MAX_FILES_PER_REPO {
-            warn!("Repo ignored. Too many files: {}", all_head_files.len());
-            return Err(());
+        let max_files = code_rules.munch_limits.max_files;
+        if analyzable_files.len() as u64 > max_files && !code_rules.munch_limits.partial {
+            warn!(
+                "Repo ignored. Too many files: {}, max_files: {}. Enable `partial` in `munch_limits` to analyze the most recently changed files only.",
+                analyzable_files.len(),
+                max_files
+            );
+            return Err(AnalysisError::Unsupported(format!(
+                "too many files: {}, max_files: {}, enable `partial` in `munch_limits` to analyze the most recently changed files only",
+                analyzable_files.len(),
+                max_files
+            )));
         }
 
+        // the same file can appear in the history under different cases on case-insensitive file systems
+        let ignore_case = vcs.is_ignore_case(project_dir).await;
+        let git_log = if ignore_case {
+            git::fold_case_collisions(git_log, &all_head_files)
+        } else {
+            git_log
+        };
+
+        // pick the most recently changed files for repos over the limit
+        let (selected_files, coverage) = if analyzable_files.len() as u64 > max_files {
+            let (selected_files, coverage) =
+                Coverage::select_recent_files(&git_log, analyzable_files, max_files as usize);
+            warn!(
+                "Partial analysis: {} of {} files changed since {}",
+                coverage.files_analyzed,
+                coverage.files_total,
+                coverage.changed_since.as_deref().unwrap_or("the first commit")
+            );
+            let coverage = Coverage {
+                files_total: all_head_files.len() as u64,
+                files_in_tree: all_tree_files.len() as u64,
+                files_ignored: (all_tree_files.len() - all_head_files.len()) as u64,
+                max_files_reached: true,
+                ..coverage
+            };
+            (Some(selected_files), coverage)
+        } else if let Some(sparse_files) = &sparse_files {
+            let coverage = Coverage {
+                files_analyzed: sparse_files.len() as u64,
+                ..Coverage::new(all_tree_files.len() as u64, all_head_files.len() as u64)
+            };
+            (Some(sparse_files.clone()), coverage)
+        } else {
+            (None, Coverage::new(all_tree_files.len() as u64, all_head_files.len() as u64))
+        };
+        let coverage = Coverage {
+            sparse_checkout: sparse_files.is_some(),
+            partial_clone_filter,
+            ..coverage
+        };
+
         // get the list of all files that ever existed in the repo, including renamed and deleted
         let all_project_blobs = log_entries_to_list_of_blobs(&git_log);
         debug!(
@@ -55,11 +199,15 @@ impl Report {
             all_project_blobs.len(),
             all_head_files.len()
         );
-        // filter out blobs that are no longer in the HEAD tree
+        // filter out blobs that are no longer in the HEAD tree or were not selected for partial analysis
         let all_project_blobs = all_project_blobs
             .into_iter()
             .filter_map(|(file_name, blob)| {
-                if all_head_files.contains(&file_name) {
+                if all_head_files.contains(&file_name)
+                    && selected_files
+                        .as_ref()
+                        .is_none_or(|selected_files| selected_files.contains(&file_name))
+                {
                     Some((file_name, blob))
                 } else {
                     None
@@ -68,7 +216,10 @@ impl Report {
             .collect::<ListOfBlobs>();
 
         let report = report.set_single_commit_flag(&git_log, &old_report);
-        let report = report.add_commits_history(git_log).await;
+        let report = report
+            .add_commits_history(git_log)
+            .await
+            .adjust_for_truncated_history(old_report, &shallow_commits);
 
         // check if there were any contents or muncher changes since the last commit
         // this is the cheapest check we can do to determine if there were an changes that need to be reprocessed
@@ -80,6 +231,11 @@ impl Report {
         let (report, reused_per_file_tech) =
             report.copy_cached_data_from_another_report(code_rules, old_report.as_ref(), &all_project_blobs);
 
+        // add documentation facts while the README blob is still in the list
+        let report = report
+            .with_documentation(vcs.as_ref(), project_dir, rev, &all_head_files, &all_project_blobs, ignore_case)
+            .await;
+
         // select blobs that could not be copied from the old report and need to be munched because either the blob or the muncher changed
         let blobs_to_munch = all_project_blobs
             .into_iter()
@@ -109,69 +265,318 @@ impl Report {
         // group contributor files by commit to get the blob IDs with min number of git requests later
 
         // populate blob sha1 from head commit for blobs that need to be munched
-        let blobs_to_munch = git::populate_blob_sha1(project_dir, blobs_to_munch, None).await?;
+        let blobs_to_munch = vcs
+            .populate_blob_sha1(project_dir, blobs_to_munch, rev.map(|v| v.to_owned()), ignore_case)
+            .await?;
+
+        // blobs missing from a partial clone are fetched in one request rather than one by one as they are read
+        if coverage.partial_clone_filter.is_some() {
+            let blob_sha1s = blobs_to_munch
+                .values()
+                .map(|blob| blob.sha1.as_str())
+                .collect::<Vec<&str>>();
+            vcs.prefetch_missing_blobs(project_dir, rev, &blob_sha1s).await;
+        }
 
         // generate the report
         let report = report
-            .process_project_files(code_rules, project_dir, &blobs_to_munch, Some(&all_head_files))
+            .process_project_files(
+                code_rules,
+                vcs.as_ref(),
+                project_dir,
+                &blobs_to_munch,
+                Some(&all_head_files),
+                skip_list,
+            )
             .await?;
 
-        // update lists of files (unprocessed and project tree)
-        let report = report.update_project_file_lists(all_head_files);
+        // update lists of files (unprocessed and project tree) and count what was munched
+        let report = report.update_project_file_lists(all_head_files, selected_files.as_ref(), coverage, code_rules);
+
+        // binary files are either ignored, not munched or munched into blank records
+        let report = report.with_assets(project_dir, &all_tree_files);
+
+        // identical files under several paths, e.g. vendored libraries, share the same blob
+        let report = report
+            .with_duplicate_files(vcs.as_ref(), project_dir, rev, ignore_case, code_rules.duplicates)
+            .await;
+
+        // lock files are on the ignore list, so they are retrieved separately from the munched files
+        let report = report.with_licenses(vcs.as_ref(), project_dir, rev, ignore_case).await;
 
         // add various metadata based on the final report
-        let report = report.with_summary();
+        let mut report = report.with_summary();
+        report.partial = selected_files.is_some();
 
         Ok(Some(report))
     }
 
+    /// Adds `assets` section for files in the tree at HEAD that have no text contents in the report.
+    /// Uses the tree without `ignore_paths` because most images, fonts and archives are on that list.
+    fn with_assets(self, project_dir: &Path, all_tree_files: &HashSet<String>) -> Self {
+        let mut report = self;
+
+        let text_files = report
+            .per_file_tech
+            .iter()
+            .filter(|tech| tech.total_lines > 0)
+            .filter_map(|tech| tech.file_name.as_ref())
+            .collect::<HashSet<&String>>();
+        let assets = report::Assets::from_files(
+            project_dir,
+            all_tree_files.iter().filter(|file_name| !text_files.contains(file_name)),
+        );
+        debug!("Assets: {:?}", assets);
+
+        report.assets = assets;
+        report
+    }
+
+    /// Adds `duplicate_files` section for munched files with identical contents under several paths at HEAD or `rev`.
+    /// The extra copies are left out of `tech` totals unless `duplicates` is `All`. The report is returned as-is
+    /// if the blob SHA1s cannot be retrieved.
+    async fn with_duplicate_files(
+        self,
+        vcs: &dyn Vcs,
+        project_dir: &Path,
+        rev: Option<&str>,
+        ignore_case: bool,
+        duplicates: Duplicates,
+    ) -> Self {
+        let mut report = self;
+
+        let blobs = report
+            .per_file_tech
+            .iter()
+            .filter_map(|tech| tech.file_name.as_ref())
+            .map(|file_name| {
+                let blob = GitBlob {
+                    sha1: String::new(),
+                    commit_sha1: String::new(),
+                    commit_date_epoch: 0,
+                    commit_date_iso: String::new(),
+                };
+                (file_name.clone(), blob)
+            })
+            .collect::<ListOfBlobs>();
+        let blobs = match vcs
+            .populate_blob_sha1(project_dir, blobs, rev.map(|v| v.to_owned()), ignore_case)
+            .await
+        {
+            Ok(v) => v,
+            Err(_) => return report,
+        };
+
+        let counted_once = duplicates == Duplicates::Once;
+        if let Some((duplicate_files, duplicate_file_groups)) =
+            report::DuplicateFiles::from_blobs(&blobs, &report.per_file_tech, counted_once)
+        {
+            debug!("Duplicate files: {:?}", duplicate_files);
+            if counted_once {
+                report.duplicate_file_groups = duplicate_file_groups;
+                report.recompute_tech_section();
+            }
+            report.duplicate_files = Some(duplicate_files);
+        }
+
+        report
+    }
+
+    /// Adds `licenses` section from the license files, manifests and lock files in the root of the project at HEAD
+    /// or `rev`. The section is left out if there is no license information or the files cannot be retrieved.
+    async fn with_licenses(self, vcs: &dyn Vcs, project_dir: &Path, rev: Option<&str>, ignore_case: bool) -> Self {
+        let mut report = self;
+
+        let all_tree_files = match vcs
+            .get_all_tree_files(project_dir, rev.map(|v| v.to_owned()), &[])
+            .await
+        {
+            Ok(v) => v,
+            Err(_) => return report,
+        };
+        let head_commit_sha1 = match vcs.get_head_commit(project_dir, rev).await {
+            Ok(v) => v,
+            Err(_) => return report,
+        };
+
+        let license_blobs = all_tree_files
+            .into_iter()
+            .filter(|file_name| report::Licenses::is_license_source(file_name))
+            .map(|file_name| {
+                let blob = GitBlob {
+                    sha1: String::new(),
+                    commit_sha1: head_commit_sha1.clone(),
+                    commit_date_epoch: 0,
+                    commit_date_iso: String::new(),
+                };
+                (file_name, blob)
+            })
+            .collect::<ListOfBlobs>();
+        if license_blobs.is_empty() {
+            return report;
+        }
+
+        let mut contents: Vec<(String, Vec<u8>)> = Vec::new();
+        if let Ok(license_blobs) = vcs
+            .populate_blob_sha1(project_dir, license_blobs, rev.map(|v| v.to_owned()), ignore_case)
+            .await
+        {
+            for (file_name, blob) in license_blobs {
+                if let Ok(file_contents) = vcs
+                    .get_blob_contents(project_dir, &file_name, &blob.sha1, &blob.commit_sha1)
+                    .await
+                {
+                    contents.push((file_name, file_contents));
+                }
+            }
+        }
+
+        report.licenses =
+            report::Licenses::from_files(contents.iter().map(|(file_name, v)| (file_name.as_str(), v.as_slice())));
+        debug!("Licenses: {:?}", report.licenses);
+
+        report
+    }
+
+    /// Adds `documentation` section based on the list of files in the tree and the README contents at HEAD or `rev`.
+    /// README stats are left blank if the file cannot be retrieved.
+    async fn with_documentation(
+        self,
+        vcs: &dyn Vcs,
+        project_dir: &Path,
+        rev: Option<&str>,
+        all_head_files: &HashSet<String>,
+        all_project_blobs: &ListOfBlobs,
+        ignore_case: bool,
+    ) -> Self {
+        let mut report = self;
+        let mut documentation = report::Documentation::from_tree_files(all_head_files);
+
+        let readme_blob = documentation
+            .readme_file
+            .as_ref()
+            .and_then(|file_name| all_project_blobs.get_key_value(file_name))
+            .map(|(file_name, blob)| (file_name.clone(), blob.clone()))
+            .into_iter()
+            .collect::<ListOfBlobs>();
+
+        if let Ok(readme_blob) = vcs
+            .populate_blob_sha1(project_dir, readme_blob, rev.map(|v| v.to_owned()), ignore_case)
+            .await
+        {
+            for (file_name, blob) in readme_blob {
+                if let Ok(contents) = vcs
+                    .get_blob_contents(project_dir, &file_name, &blob.sha1, &blob.commit_sha1)
+                    .await
+                {
+                    documentation.add_readme_stats(&contents);
+                }
+            }
+        }
+        debug!("Documentation: {:?}", documentation);
+
+        report.documentation = Some(documentation);
+        report
+    }
+
     /// Processes specified files from the repo and returns a report with Tech and Tech per file sections.
-    /// * `project_dir` - needed for git
+    /// * `vcs` and `project_dir` - needed for getting the file contents
     /// * `blobs_to_process` - list of blobs that need to be processed, must have SHA1 set
+    /// * `skip_list` - files to skip, updated with files that failed or took too long
     pub(crate) async fn process_project_files(
         self,
         code_rules: &mut code_rules::CodeRules,
+        vcs: &dyn Vcs,
         project_dir: &Path,
         blobs_to_process: &ListOfBlobs,
         all_tree_files: Option<&HashSet<String>>,
+        skip_list: &mut SkipList,
     ) -> Result<report::Report, ()> {
         info!("Processing individual project files from {}", project_dir.to_string_lossy());
+        let span = info_span!("phase", phase = "munch", files = blobs_to_process.len());
 
-        // result collectors
-        let mut report = self;
+        async move {
+            // result collectors
+            let mut report = self;
 
-        // loop through all the files supplied by the caller and process them one by one
-        for (file_name, blob) in blobs_to_process {
-            debug!("Blob {}/{}", file_name, blob.sha1);
-            // fetch the right muncher
-            if let Some(muncher) = code_rules.get_muncher(file_name) {
-                // process the file with the rules from the muncher
-                if let Ok(tech) = processors::process_file(
-                    file_name,
-                    &blob.sha1,
-                    muncher,
-                    project_dir,
-                    &blob.commit_sha1,
-                    blob.commit_date_epoch,
-                    &blob.commit_date_iso,
-                    all_tree_files,
-                )
-                .await
-                {
-                    report.per_file_tech.insert(tech.clone());
-                    report.merge_tech_record(tech.reset_file_and_commit_info());
+            // the limits are copied because the muncher borrows code_rules
+            let munch_limits = code_rules.munch_limits.clone();
+            let munch_options = MunchOptions {
+                limits: &munch_limits,
+                all_tree_files,
+            };
+
+            // the contents are read in batches to avoid starting the VCS for every file, which is especially slow
+            // in partial clones where every missing blob is fetched from the remote
+            let blobs_to_process = blobs_to_process.iter().collect::<Vec<(&String, &GitBlob)>>();
+            for batch in blobs_to_process.chunks(BLOB_BATCH_SIZE) {
+                let blob_sha1s = batch.iter().map(|(_, blob)| blob.sha1.as_str()).collect::<Vec<&str>>();
+                let mut batch_contents = vcs.get_blobs_contents(project_dir, &blob_sha1s).await;
+
+                // process the files in the batch one by one
+                for (file_name, blob) in batch {
+                    debug!("Blob {}/{}", file_name, blob.sha1);
+                    // fetch the right muncher and the host language munchers for templates and notebooks
+                    let (muncher, host_munchers) = match code_rules.get_munchers(file_name) {
+                        Some(v) => v,
+                        None => continue,
+                    };
+                    // files that failed or took too long on previous runs are not retried until the muncher changes
+                    let muncher_hash = muncher.muncher_hash;
+                    if skip_list.is_skipped(file_name, muncher_hash) {
+                        debug!("Skipped {} / skip list", file_name);
+                        continue;
+                    }
+
+                    // blobs that were not in the batch output are read one by one, e.g. copies of the same blob
+                    // under several paths
+                    let contents = match batch_contents.remove(&blob.sha1) {
+                        Some(v) => Ok(v),
+                        None => {
+                            vcs.get_blob_contents(project_dir, file_name, &blob.sha1, &blob.commit_sha1)
+                                .await
+                        }
+                    };
+
+                    // process the file with the rules from the muncher
+                    // a file that cannot be read is a failure, unlike an empty or binary file that is munched into a blank record
+                    let instant = Instant::now();
+                    let techs = match contents {
+                        Ok(contents) => {
+                            processors::process_file(file_name, &contents, muncher, &host_munchers, blob, &munch_options)
+                        }
+                        Err(_) => Err(MunchError::Unreadable(format!("cannot read blob {}", blob.sha1))),
+                    };
+                    match techs {
+                        Ok(techs) => {
+                            skip_list.record_success(file_name, muncher_hash, instant.elapsed());
+                            for tech in techs {
+                                report.per_file_tech.insert(tech.clone());
+                                report.merge_tech_record(tech.reset_file_and_commit_info());
+                            }
+                        }
+                        Err(MunchError::Unreadable(e)) => {
+                            warn!("Cannot munch {} due to {}", file_name, e);
+                            skip_list.record_failure(file_name, muncher_hash, e);
+                        }
+                        Err(MunchError::Timeout(e)) => {
+                            warn!("Cannot munch {}: {}", file_name, e);
+                            skip_list.record_timeout(file_name, muncher_hash, e, instant.elapsed());
+                        }
+                    }
                 }
             }
-        }
 
-        info!("Analysis finished");
-        Ok(report)
+            Ok(report)
+        }
+        .instrument(span)
+        .await
     }
 
     /// Copies per-file tech sections for `blobs_to_process` that can be taken from the cached report without reprocessing.
     /// The records must match on the file name, commit SHA1 and muncher hash with the latest muncher from the rules collection.
     /// Returns an updated report and a list of cached files added to it.
-    fn copy_cached_data_from_another_report(
+    pub(crate) fn copy_cached_data_from_another_report(
         self,
         code_rules: &mut code_rules::CodeRules,
         other_report: Option<&report::Report>,
@@ -188,20 +593,24 @@ impl Report {
 
         // result collector
         let mut report = self;
+        let munch_limits = code_rules.munch_limits.clone();
 
-        // prepare a list of file names already in the target report, just copied or already existed or should not be copied
-        let mut copied_per_file_techs: HashSet<String> = report
+        // prepare a list of file names already in the target report, which should not be copied
+        let existing_per_file_techs: HashSet<String> = report
             .per_file_tech
             .iter()
             .filter_map(|tech| tech.file_name.clone())
             .collect();
+        // the same list with files that were just copied
+        // template files may have several records, e.g. eRuby and HTML for `index.html.erb`
+        let mut copied_per_file_techs = existing_per_file_techs.clone();
 
-        // loop thru all the cached per-file techs
-        for tech in &other_report.per_file_tech {
+        // loop thru all the cached per-file techs, including those moved into the history for files no longer in HEAD
+        for tech in other_report.per_file_tech.iter().chain(other_report.per_file_tech_history.iter()) {
             // unwrap the file name - there should always be one
             if let Some(file_tech_file_name) = tech.file_name.clone() {
-                // skip the file if it's already present in the target report
-                if copied_per_file_techs.contains(&file_tech_file_name) {
+                // skip the file if it was present in the target report before copying
+                if existing_per_file_techs.contains(&file_tech_file_name) {
                     continue;
                 }
                 // check if there is a corresponding blob for that file name
@@ -212,12 +621,12 @@ impl Report {
                         if blob.commit_sha1 == *file_tech_commit_sha1 {
                             // ... and the muncher hash matches the one in per_file_tech copy the record over
                             if let Some(muncher) = code_rules.get_muncher(&file_tech_file_name) {
-                                if muncher.muncher_hash == tech.muncher_hash {
+                                if muncher.cache_hash(&munch_limits) == tech.muncher_hash {
                                     debug!("Copied {} file-tech section from cached data", file_tech_file_name);
-                                    // copy the record
-                                    report.per_file_tech.insert(tech.clone());
-                                    // merge it at the tech level
-                                    report.merge_tech_record(tech.clone());
+                                    // copy the record and merge it at the tech level unless it was copied already
+                                    if report.per_file_tech.insert(tech.clone()) {
+                                        report.merge_tech_record(tech.clone());
+                                    }
                                     // store the file name, so we know what was copied
                                     copied_per_file_techs.insert(file_tech_file_name.clone());
                                 }
@@ -232,6 +641,7 @@ impl Report {
     }
 
     /// Process only files touched by the contributor at the point of the contribution.
+    /// Files matching `code_rules.contributor_ignore_paths` are left out as if the contributor never touched them.
     pub async fn process_contributor(
         &self,
         code_rules: &mut code_rules::CodeRules,
@@ -239,6 +649,7 @@ impl Report {
         old_contributor_report: &Option<report::Report>,
         contributor: &Contributor,
         all_tree_files: Option<&HashSet<String>>,
+        skip_list: &mut SkipList,
     ) -> Result<report::Report, ()> {
         debug!("Processing contributor: {}", contributor.git_id);
 
@@ -248,8 +659,12 @@ impl Report {
         let mut last_contributor_commit_sha1 = String::new();
         let mut last_contributor_commit_date_epoch = 0i64;
         let mut last_contributor_commit_date_iso: Option<String> = None;
-        let contributor_blobs = &contributor
+        let contributor_files = contributor
             .touched_files
+            .iter()
+            .filter(|file| !git::is_in_ignore_list(&code_rules.contributor_ignore_paths, &file.name))
+            .collect::<Vec<&ContributorFile>>();
+        let contributor_blobs = &contributor_files
             .iter()
             .map(|file| {
                 // find the latest commit SHA1 and date for this contributor while it's iterating through them all anyway
@@ -270,7 +685,18 @@ impl Report {
             })
             .collect::<ListOfBlobs>();
 
-        let report = report::Report::new();
+        let mut report = report::Report::new();
+        report.partial = project_report.partial;
+        report.coverage = project_report.coverage.clone();
+        // files pruned from the history by the retention policy stay out of the report until they reappear in the tree
+        if let Some(old_contributor_report) = old_contributor_report {
+            report.pruned_file_names = old_contributor_report
+                .pruned_file_names
+                .iter()
+                .filter(|file_name| !all_tree_files.is_some_and(|tree_files| tree_files.contains(*file_name)))
+                .cloned()
+                .collect();
+        }
         // copy cached data processed earlier
         // first from the old contributor report
         let (report, reused_per_file_tech_contributor) = report.copy_cached_data_from_another_report(
@@ -303,11 +729,29 @@ impl Report {
             reused_per_file_tech_project.len()
         );
 
+        // only the files analyzed in the project report are munched if the project was too big for full analysis
+        let partial_project_files = if project_report.partial {
+            Some(
+                project_report
+                    .per_file_tech
+                    .iter()
+                    .filter_map(|tech| tech.file_name.as_ref())
+                    .collect::<HashSet<&String>>(),
+            )
+        } else {
+            None
+        };
+
         // remove blobs that have no munchers - there is no point in getting the contents
         let blobs_to_munch = blobs_to_munch
             .into_iter()
             .filter_map(|(file_name, blob)| {
-                if code_rules.get_muncher(&file_name).is_some() {
+                if code_rules.get_muncher(&file_name).is_some()
+                    && !report.pruned_file_names.contains(&file_name)
+                    && partial_project_files
+                        .as_ref()
+                        .is_none_or(|project_files| project_files.contains(&file_name))
+                {
                     Some((file_name, blob))
                 } else {
                     None
@@ -337,10 +781,13 @@ impl Report {
         debug!("Found {} contributor commits for looking up blob SHA1s", blobs_by_commit.len());
 
         // loop through the commits and update blob SHA1s for commit-associated files
+        let vcs = vcs::detect(project_dir);
+        let ignore_case = vcs.is_ignore_case(project_dir).await;
         let mut blobs_to_munch = ListOfBlobs::new();
         for (commit_sha1, commit_blobs) in blobs_by_commit {
             // populate blob sha1 from head commit for blobs that need to be munched
-            let commit_blobs = git::populate_blob_sha1(project_dir, commit_blobs, Some(commit_sha1.clone())).await?;
+            let commit_blobs =
+                vcs.populate_blob_sha1(project_dir, commit_blobs, Some(commit_sha1.clone()), ignore_case).await?;
             for (file_name, blob) in commit_blobs {
                 if !blob.sha1.is_empty() {
                     // store the entire list of blobs for analyzing them later
@@ -351,33 +798,36 @@ impl Report {
 
         debug!(
             "Contributor files: {}, blobs to munch: {}",
-            contributor.touched_files.len(),
+            contributor_files.len(),
             blobs_to_munch.len(),
         );
 
         // generate the report
         let mut report = report
-            .process_project_files(code_rules, project_dir, &blobs_to_munch, all_tree_files)
+            .process_project_files(code_rules, vcs.as_ref(), project_dir, &blobs_to_munch, all_tree_files, skip_list)
             .await?;
 
         // count all file extensions from contributor files
-        for contributor_file in &contributor.touched_files {
+        for contributor_file in &contributor_files {
             report.add_file_type(&contributor_file.name);
         }
 
-        // copy all contributor commits from the list of project commits by commit idx
+        // copy all contributor commits that are still in the list of project commits
+        // the lists may be out of sync if the contributor came from a cached report and the history was rewritten since
         if let Some(project_commits) = &project_report.recent_project_commits {
+            let project_commits = project_commits.iter().collect::<HashSet<&String>>();
             let contributor_commits = contributor
                 .commits
                 .iter()
-                .filter_map(|idx| {
-                    if project_commits.len() as u64 > *idx {
-                        Some(project_commits[*idx as usize].clone())
-                    } else {
-                        None
-                    }
-                })
+                .filter(|commit| project_commits.contains(commit))
+                .cloned()
                 .collect::<Vec<String>>();
+            if contributor_commits.len() < contributor.commits.len() {
+                warn!(
+                    "{} contributor commits are not in the list of project commits",
+                    contributor.commits.len() - contributor_commits.len()
+                );
+            }
 
             // add meta for the first commit
             if let Some(first_commit) = contributor_commits.iter().last() {
@@ -392,6 +842,9 @@ impl Report {
                 }
             }
 
+            report.commit_cadence = CommitCadence::from_commits(&contributor_commits, chrono::Utc::now());
+            report.tenure = Tenure::from_commits(&contributor_commits);
+            report.commit_time_histo = CommitTimeHisto::from_commits(&contributor_commits, chrono::Utc::now());
             report.recent_project_commits = Some(contributor_commits);
         } else {
             warn!("No project commits to copy to contributor");
@@ -406,6 +859,7 @@ impl Report {
         report.report_commit_sha1 = project_report.report_commit_sha1.clone();
         report.log_hash = project_report.log_hash.clone();
         report.is_single_commit = project_report.is_single_commit;
+        report.history_truncated = project_report.history_truncated;
         report.last_commit_author = project_report.last_commit_author.clone();
         report.git_ids_included.insert(contributor.git_id.clone());
         report.contributor_count = project_report.contributor_count.clone();
@@ -414,6 +868,9 @@ impl Report {
         report.last_contributor_commit_date_epoch = Some(last_contributor_commit_date_epoch);
         report.loc_project = project_report.loc_project.clone();
         report.libs_project = project_report.libs_project.clone();
+        report.documentation = project_report.documentation.clone();
+        report.assets = project_report.assets.clone();
+        report.licenses = project_report.licenses.clone();
         report.commit_count_project = project_report.commit_count_project.clone();
         report.commit_count_contributor = Some(contributor.commit_count.clone());
 
@@ -437,6 +894,12 @@ impl Report {
         }
         let old_report = old_report.as_ref().unwrap();
 
+        // the commit history details change when the missing commits of a shallow clone are fetched
+        if old_report.history_truncated != self.history_truncated {
+            info!("The history was deepened or truncated since the cached report");
+            return true;
+        }
+
         // check if the report is in an older format and has to be reprocessed regardless
         if old_report.is_outdated_format() {
             warn!("Full reprocessing due to new report format: {}", Report::REPORT_FORMAT_VERSION);
@@ -454,6 +917,12 @@ impl Report {
             return true;
         }
 
+        // contributors can change without any new commits if .mailmap or aliases were edited
+        if self.contributor_git_ids != old_report.contributor_git_ids {
+            info!("Contributor identities changed since the last run");
+            return true;
+        }
+
         // collects hashes of munchers that should be ignored for this project because they have
         // not changed since the last processing of the repo
         let mut old_munchers: std::collections::HashSet<u64> = std::collections::HashSet::new();
@@ -463,13 +932,14 @@ impl Report {
             }
         }
         debug!("Found {} muncher hashes in the old report", old_munchers.len());
+        let munch_limits = code_rules.munch_limits.clone();
 
         // loop through all the files supplied by the caller and process them one by one
         for blob in files {
             // fetch the right muncher
             if let Some(muncher) = code_rules.get_muncher(&blob.0) {
                 // check if the file in the old report was processed by the same muncher and can be skipped
-                if old_munchers.contains(&muncher.muncher_hash) {
+                if old_munchers.contains(&muncher.cache_hash(&munch_limits)) {
                     debug!("Unchanged muncher for {}", blob.0);
                     continue;
                 }
@@ -520,7 +990,8 @@ impl Report {
 
     /// Sets `is_single_commit` flag to `true` if there was only a single-commit change between the old and the current repos.
     /// It will be set to false in case of merge, rebase or any other history re-write. This function looks at commit SHA1s and
-    /// ignores commit messages, dates or any other info.
+    /// ignores commit messages, dates or any other info. Only the overlapping part of the logs is compared if either of them
+    /// is truncated because the oldest commits of a shallow clone change with every fetch.
     pub(crate) fn set_single_commit_flag(
         self,
         git_log: &Vec<GitLogEntry>,
@@ -540,27 +1011,33 @@ impl Report {
         }
 
         // compare the SHA1s of the 2nd commit and the old report
-        let old_report_sha1 = old_report
-            .as_ref()
-            .unwrap()
-            .report_commit_sha1
-            .clone()
-            .unwrap_or_default();
-        let old_report_log_hash = old_report.as_ref().unwrap().log_hash.clone().unwrap_or_default();
+        let old_report = old_report.as_ref().unwrap();
+        let old_report_sha1 = old_report.report_commit_sha1.clone().unwrap_or_default();
+        let old_report_log_hash = old_report.log_hash.clone().unwrap_or_default();
+
+        let same_history = if report.history_truncated || old_report.history_truncated {
+            old_report.recent_project_commits.as_ref().is_some_and(|old_commits| {
+                git_log
+                    .iter()
+                    .skip(1)
+                    .filter_map(|entry| entry.join_commit_with_ts())
+                    .zip(old_commits.iter())
+                    .all(|(commit, old_commit)| &commit == old_commit)
+            })
+        } else {
+            !old_report_log_hash.is_empty()
+                && old_report_log_hash
+                    == utils::hash_vec_sha1(
+                        git_log
+                            .iter()
+                            .skip(1)
+                            .map(|entry| entry.sha1.clone())
+                            .collect::<Vec<String>>(),
+                    )
+        };
 
         // heck if there are any history rewrites in the order of complexity check
-        if !old_report_sha1.is_empty()
-            && !old_report_log_hash.is_empty()
-            && old_report_sha1 == git_log[1].sha1
-            && old_report_log_hash
-                == utils::hash_vec_sha1(
-                    git_log
-                        .iter()
-                        .skip(1)
-                        .map(|entry| entry.sha1.clone())
-                        .collect::<Vec<String>>(),
-                )
-        {
+        if !old_report_sha1.is_empty() && old_report_sha1 == git_log[1].sha1 && same_history {
             debug!("set_single_commit_flag -> true, commits: {}", git_log.len());
             report.is_single_commit = true;
         }
@@ -0,0 +1,248 @@
+use crate::git::GitLogEntry;
+use std::collections::BTreeMap;
+use std::path::Path;
+use tracing::{debug, warn};
+
+/// The name of the file in the root of the repo that maps commit identities to canonical ones.
+pub const MAILMAP_FILE_NAME: &str = ".mailmap";
+
+/// Maps commit author identities to canonical ones to merge contributors who committed under several emails,
+/// e.g. an old work email and a GitHub noreply address. The mapping comes from `.mailmap` in the root of the repo
+/// and from user-defined aliases, which take precedence.
+/// See https://git-scm.com/docs/gitmailmap for the file format.
+#[derive(Debug, Default, Clone)]
+pub struct Mailmap {
+    entries: Vec<MailmapEntry>,
+}
+
+/// A single line of `.mailmap` or an alias. Only the email of the commit is required.
+#[derive(Debug, Clone, PartialEq)]
+struct MailmapEntry {
+    proper_name: Option<String>,
+    proper_email: Option<String>,
+    /// Lower case. The entry only applies to commits with this name if set.
+    commit_name: Option<String>,
+    /// Lower case.
+    commit_email: String,
+}
+
+impl Mailmap {
+    /// Reads `.mailmap` from the root of the project. Returns an empty map if there is no such file.
+    pub fn from_repo(project_dir: &Path) -> Self {
+        let mailmap_file = project_dir.join(MAILMAP_FILE_NAME);
+        match std::fs::read_to_string(&mailmap_file) {
+            Ok(v) => {
+                let mailmap = Self::parse(&v);
+                debug!("Loaded {} entries from {}", mailmap.entries.len(), mailmap_file.to_string_lossy());
+                mailmap
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Self::default(),
+            Err(e) => {
+                warn!("Cannot read {} due to {}", mailmap_file.to_string_lossy(), e);
+                Self::default()
+            }
+        }
+    }
+
+    /// Parses the contents of `.mailmap`. Invalid lines are logged and skipped.
+    pub fn parse(contents: &str) -> Self {
+        let entries = contents
+            .lines()
+            .filter_map(|line| {
+                let entry = MailmapEntry::parse(line);
+                if entry.is_none() && !line.trim().is_empty() && !line.trim_start().starts_with('#') {
+                    warn!("Invalid mailmap line: {}", line);
+                }
+                entry
+            })
+            .collect::<Vec<MailmapEntry>>();
+
+        Self { entries }
+    }
+
+    /// Adds `old email -> new email` aliases on top of the existing entries so that they take precedence.
+    pub fn with_aliases(mut self, aliases: &BTreeMap<String, String>) -> Self {
+        for (commit_email, proper_email) in aliases {
+            self.entries.push(MailmapEntry {
+                proper_name: None,
+                proper_email: Some(proper_email.trim().to_lowercase()),
+                commit_name: None,
+                commit_email: commit_email.trim().to_lowercase(),
+            });
+        }
+        self
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Returns the canonical name and email for the commit identity or None if there is no mapping for it.
+    /// Entries matching both the name and the email take precedence. Later entries override earlier ones, same as in git.
+    pub fn map(&self, name: &str, email: &str) -> Option<(String, String)> {
+        let name_lc = name.to_lowercase();
+        let email_lc = email.to_lowercase();
+
+        let entry = self
+            .entries
+            .iter()
+            .rev()
+            .find(|entry| entry.commit_email == email_lc && entry.commit_name.as_ref() == Some(&name_lc))
+            .or_else(|| {
+                self.entries
+                    .iter()
+                    .rev()
+                    .find(|entry| entry.commit_email == email_lc && entry.commit_name.is_none())
+            })?;
+
+        Some((
+            entry.proper_name.clone().unwrap_or_else(|| name.to_owned()),
+            entry.proper_email.clone().unwrap_or_else(|| email.to_owned()),
+        ))
+    }
+
+    /// Replaces the author identities in the log with the canonical ones.
+    pub fn apply(&self, git_log: Vec<GitLogEntry>) -> Vec<GitLogEntry> {
+        if self.is_empty() {
+            return git_log;
+        }
+
+        let mut mapped_commits = 0usize;
+        let git_log = git_log
+            .into_iter()
+            .map(|mut entry| {
+                if let Some(author_name_email) = self.map(&entry.author_name_email.0, &entry.author_name_email.1) {
+                    if author_name_email != entry.author_name_email {
+                        entry.author_name_email = author_name_email;
+                        mapped_commits += 1;
+                    }
+                }
+                entry
+            })
+            .collect::<Vec<GitLogEntry>>();
+        debug!("Mapped authors of {} commits", mapped_commits);
+
+        git_log
+    }
+
+    /// Adds the canonical emails for the identities in the list, which should be lower case.
+    /// E.g. `old@example.com` -> `old@example.com, new@example.com`.
+    pub fn map_identities(&self, git_identities: &[String]) -> Vec<String> {
+        let mut mapped = git_identities.to_vec();
+        for git_identity in git_identities {
+            if let Some((_, email)) = self.map("", git_identity) {
+                mapped.push(email.to_lowercase());
+            }
+        }
+        mapped.sort();
+        mapped.dedup();
+
+        mapped
+    }
+}
+
+impl MailmapEntry {
+    /// Parses one of the `.mailmap` line formats. Returns None for blank lines, comments and invalid lines.
+    /// * `Proper Name <commit@email>`
+    /// * `<proper@email> <commit@email>`
+    /// * `Proper Name <proper@email> <commit@email>`
+    /// * `Proper Name <proper@email> Commit Name <commit@email>`
+    fn parse(line: &str) -> Option<Self> {
+        // everything after # is a comment
+        let line = line.split('#').next().unwrap_or_default();
+
+        // split into (name, email) pairs, e.g. `Max <max@onebro.me>` -> ("Max", "max@onebro.me")
+        let mut pairs: Vec<(Option<String>, String)> = Vec::new();
+        let mut rest = line;
+        while let Some(start) = rest.find('<') {
+            let end = start + rest[start..].find('>')?;
+            let name = rest[..start].trim();
+            let name = if name.is_empty() { None } else { Some(name.to_owned()) };
+            pairs.push((name, rest[start + 1..end].trim().to_owned()));
+            rest = &rest[end + 1..];
+        }
+        if !rest.trim().is_empty() {
+            return None;
+        }
+
+        match pairs.as_slice() {
+            [(proper_name, commit_email)] if proper_name.is_some() => Some(Self {
+                proper_name: proper_name.clone(),
+                proper_email: None,
+                commit_name: None,
+                commit_email: commit_email.to_lowercase(),
+            }),
+            [(proper_name, proper_email), (commit_name, commit_email)] => Some(Self {
+                proper_name: proper_name.clone(),
+                proper_email: Some(proper_email.clone()),
+                commit_name: commit_name.as_ref().map(|v| v.to_lowercase()),
+                commit_email: commit_email.to_lowercase(),
+            }),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test_mailmap {
+    use super::Mailmap;
+    use crate::git::GitLogEntry;
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn test_mailmap_parse_and_map() {
+        let mailmap = Mailmap::parse(
+            r#"# a comment
+Max V <max@onebro.me>
+<max@onebro.me> <12345+rimutaka@users.noreply.github.com>
+Max V <max@onebro.me> ubuntu <admin@localhost>
+Jane <jane@example.com> <JANE@old.example.com> # trailing comment
+not a valid line
+"#,
+        );
+
+        assert_eq!(
+            mailmap.map("Max", "max@onebro.me"),
+            Some(("Max V".to_owned(), "max@onebro.me".to_owned()))
+        );
+        // the mapped email is not mapped again, same as in git
+        assert_eq!(
+            mailmap.map("rimutaka", "12345+rimutaka@users.noreply.github.com"),
+            Some(("rimutaka".to_owned(), "max@onebro.me".to_owned()))
+        );
+        // name + email entries only match commits with the same name
+        assert_eq!(
+            mailmap.map("Ubuntu", "admin@localhost"),
+            Some(("Max V".to_owned(), "max@onebro.me".to_owned()))
+        );
+        assert_eq!(mailmap.map("root", "admin@localhost"), None);
+        // emails are case-insensitive
+        assert_eq!(
+            mailmap.map("J", "jane@Old.example.com"),
+            Some(("Jane".to_owned(), "jane@example.com".to_owned()))
+        );
+        assert_eq!(mailmap.map("Bob", "bob@example.com"), None);
+    }
+
+    #[test]
+    fn test_mailmap_aliases() {
+        let mut aliases = BTreeMap::new();
+        aliases.insert("old@x.com".to_owned(), "new@y.com".to_owned());
+        let mailmap = Mailmap::parse("<jane@example.com> <old@x.com>").with_aliases(&aliases);
+
+        // aliases override .mailmap
+        let mut entry = GitLogEntry::new();
+        entry.author_name_email = ("Jane".to_owned(), "Old@x.com".to_owned());
+        let mut other = GitLogEntry::new();
+        other.author_name_email = ("Bob".to_owned(), "bob@example.com".to_owned());
+
+        let git_log = mailmap.apply(vec![entry, other]);
+        assert_eq!(git_log[0].author_name_email, ("Jane".to_owned(), "new@y.com".to_owned()));
+        assert_eq!(git_log[1].author_name_email, ("Bob".to_owned(), "bob@example.com".to_owned()));
+
+        assert_eq!(
+            mailmap.map_identities(&["old@x.com".to_owned(), "bob@example.com".to_owned()]),
+            vec!["bob@example.com", "new@y.com", "old@x.com"]
+        );
+    }
+}
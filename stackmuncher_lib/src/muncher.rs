@@ -1,8 +1,63 @@
-use regex::Regex;
-use serde::Deserialize;
+use crate::generated_code::{GENERATED_MARKERS, GENERATED_PATHS};
+use crate::report::Report;
+use regex::{Regex, RegexBuilder};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::hash::{Hash, Hasher};
 use tracing::{error, trace};
 
+/// The max size of a compiled muncher regex in bytes. Rules that blow up into a huge automaton fail to compile
+/// instead of slowing down every line they are matched against.
+pub const REGEX_SIZE_LIMIT: usize = 1024 * 1024;
+
+/// Built-in regex for tech-debt markers in comments of any language with the marker as the capture group.
+/// Only upper case markers are counted to avoid matching words like `todo` in prose.
+pub(crate) const TECH_DEBT_MARKERS: [&str; 1] = [r"\b(TODO|FIXME|HACK|XXX)\b"];
+
+/// Limits that stop a bad muncher rule from hanging the run on a large or minified file or a huge repo.
+/// Can be changed in `munch_limits` section of config.json.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(default)]
+pub struct MunchLimits {
+    /// Processing of a single file stops after this many milliseconds and the file is added to the skip list.
+    pub timeout_ms: u64,
+    /// Only this many bytes from the start of a line are matched against the muncher rules.
+    pub max_line_length: usize,
+    /// Repos with more files at HEAD are either ignored or analyzed partially, depending on `partial`.
+    pub max_files: u64,
+    /// Analyze only `max_files` most recently changed files in larger repos and mark the report as `partial`.
+    pub partial: bool,
+    /// Files larger than this many bytes are counted, but not munched. 0 means no limit.
+    pub max_file_size: u64,
+    /// Files larger than this many bytes are munched from samples at the start and the end of the file with the line
+    /// counts extrapolated and the record marked as `Tech.sampled`. 0 means no sampling by size.
+    pub sample_file_size: u64,
+    /// Files with longer lines on average are sampled the same way as files over `sample_file_size`,
+    /// e.g. minified bundles. 0 means no sampling by line length.
+    pub sample_line_length: u64,
+    /// The number of bytes munched from the start and from the end of a sampled file.
+    /// Files that are not much larger than the samples are munched in full.
+    pub sample_size: u64,
+    /// Count TODO, FIXME and other tech-debt markers in comments, see `Tech.tech_debt_markers`.
+    pub tech_debt_markers: bool,
+}
+
+impl Default for MunchLimits {
+    fn default() -> Self {
+        Self {
+            timeout_ms: 5000,
+            max_line_length: 10000,
+            max_files: Report::MAX_FILES_PER_REPO,
+            partial: false,
+            max_file_size: 0,
+            sample_file_size: 4 * 1024 * 1024,
+            sample_line_length: 1000,
+            sample_size: 64 * 1024,
+            tech_debt_markers: false,
+        }
+    }
+}
+
 // ===================================================================
 // IMPORTANT: update the hashing function after adding any new members
 // ===================================================================
@@ -20,6 +75,46 @@ pub struct Muncher {
     pub block_comments_end: Option<Vec<String>>,
     pub refs: Option<Vec<String>>,
     pub packages: Option<Vec<String>>,
+    /// Regex for code lines at a boundary with another language by the name of that language, e.g. `C` for `extern "C"`
+    /// in Rust or `ctypes.CDLL` in Python. A line is counted once per language in `Tech.interop`.
+    pub interop: Option<BTreeMap<String, Vec<String>>>,
+    /// Regex for the language version or edition the code is written for with the version as a capture group,
+    /// e.g. `edition = "2021"` in Cargo.toml or `go 1.22` in go.mod. Counted per version in `Tech.language_version`.
+    pub language_version: Option<Vec<String>>,
+    /// Regex for code lines declaring a function, a type or another entity that is expected to have docs,
+    /// e.g. `fn` or `struct` in Rust. Counted in `Tech.entities`.
+    pub entity_declarations: Option<Vec<String>>,
+    /// Regex for comment lines documenting the declaration below them, e.g. `///` in Rust or `/**` in Java.
+    /// Declarations with such a comment above them are counted in `Tech.documented_entities`.
+    pub entity_docs: Option<Vec<String>>,
+    /// Regex for the first line after the declaration documenting it, e.g. a docstring in Python.
+    pub entity_docstrings: Option<Vec<String>>,
+    /// Regex for attribute lines allowed between the docs and the declaration, e.g. `#[derive(Debug)]` or `@Override`.
+    pub entity_attributes: Option<Vec<String>>,
+    /// Regex for file paths of test code, e.g. `_test\.go$`. The entire file is counted as test code on a match.
+    pub test_paths: Option<Vec<String>>,
+    /// Regex for code lines that only appear in test code, e.g. `#[test]`. The entire file is counted as test code on a match.
+    pub test_markers: Option<Vec<String>>,
+    /// Regex for file paths of generated or vendored code in addition to the built-in list, e.g. `_pb2\.py$`.
+    /// The entire file is counted as `generated_loc` on a match.
+    pub generated_paths: Option<Vec<String>>,
+    /// Regex for lines at the top of the file placed there by code generators in addition to the built-in list,
+    /// e.g. `Generated by Django`. The entire file is counted as `generated_loc` on a match.
+    pub generated_markers: Option<Vec<String>>,
+    /// Regex for opening template tags, e.g. `<%` in ERB or `\{[%{#]` in Jinja. Lines with template tags are counted
+    /// with the rules of this muncher and the rest of the file with the rules of the host language.
+    pub template_start: Option<Vec<String>>,
+    /// Regex for closing template tags, e.g. `%>` in ERB. A tag left open makes the lines that follow template lines.
+    pub template_end: Option<Vec<String>>,
+    /// Regex for tech-debt markers in comments in addition to the built-in list with the marker as a capture group,
+    /// e.g. `@(deprecated)` in JSDoc. Counted per marker in `Tech.tech_debt_markers`.
+    pub tech_debt_markers: Option<Vec<String>>,
+    /// The muncher for the lines outside template tags if the file name does not tell the host language,
+    /// e.g. `html` for `page.hbs`. The host language of `page.html.erb` is taken from the inner extension.
+    pub host_muncher: Option<String>,
+    /// Munchers for code cells of notebooks by the kernel language from the notebook metadata, e.g. `python` -> `python.py`.
+    /// Files of this muncher are parsed as Jupyter notebooks with code cells counted as the kernel language.
+    pub kernel_munchers: Option<BTreeMap<String, String>>,
     // REMEMBER TO ADD ANY NEW MEMBERS TO HASH TRAIT!!!
 
     // Regex section is compiled once from the above properties
@@ -39,10 +134,40 @@ pub struct Muncher {
     pub refs_regex: Option<Vec<Regex>>,
     #[serde(skip)]
     pub packages_regex: Option<Vec<Regex>>,
+    /// Compiled `interop` with the name of the other language for every regex
+    #[serde(skip)]
+    pub interop_regex: Option<Vec<(String, Regex)>>,
+    #[serde(skip)]
+    pub language_version_regex: Option<Vec<Regex>>,
+    #[serde(skip)]
+    pub entity_declarations_regex: Option<Vec<Regex>>,
+    #[serde(skip)]
+    pub entity_docs_regex: Option<Vec<Regex>>,
+    #[serde(skip)]
+    pub entity_docstrings_regex: Option<Vec<Regex>>,
+    #[serde(skip)]
+    pub entity_attributes_regex: Option<Vec<Regex>>,
     #[serde(skip)]
     pub blank_line_regex: Option<Vec<Regex>>,
     #[serde(skip)]
     pub keywords_regex: Option<Vec<Regex>>,
+    #[serde(skip)]
+    pub test_paths_regex: Option<Vec<Regex>>,
+    #[serde(skip)]
+    pub test_markers_regex: Option<Vec<Regex>>,
+    /// Includes the built-in `GENERATED_PATHS`
+    #[serde(skip)]
+    pub generated_paths_regex: Option<Vec<Regex>>,
+    /// Includes the built-in `GENERATED_MARKERS`
+    #[serde(skip)]
+    pub generated_markers_regex: Option<Vec<Regex>>,
+    #[serde(skip)]
+    pub template_start_regex: Option<Vec<Regex>>,
+    #[serde(skip)]
+    pub template_end_regex: Option<Vec<Regex>>,
+    /// Includes the built-in `TECH_DEBT_MARKERS`
+    #[serde(skip)]
+    pub tech_debt_markers_regex: Option<Vec<Regex>>,
     /// Set to true for newly added munchers to help upstream code
     /// identify them and share with other threads
     #[serde(skip)]
@@ -71,9 +196,7 @@ impl Muncher {
         conf.brand_new = true;
 
         // hash the muncher to ID the rules and avoid reprocessing
-        let mut hasher = std::collections::hash_map::DefaultHasher::new();
-        conf.hash(&mut hasher);
-        conf.muncher_hash = hasher.finish();
+        conf.muncher_hash = conf.rules_hash();
 
         // compile all regex strings
         if conf.compile_all_regex().is_err() {
@@ -83,6 +206,103 @@ impl Muncher {
         Some(conf)
     }
 
+    /// Returns the hash of the rules that is stored in the report as `muncher_hash`.
+    pub(crate) fn rules_hash(&self) -> u64 {
+        let mut hasher = std::collections::hash_map::DefaultHasher::new();
+        self.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    /// Returns the hash of the rules combined with the limits that change the munched data, e.g. sampling of large files.
+    /// It is stored in per-file records as `Tech.muncher_hash`, so that cached records are reprocessed when either changes.
+    pub(crate) fn cache_hash(&self, limits: &MunchLimits) -> u64 {
+        let mut hasher = std::collections::hash_map::DefaultHasher::new();
+        self.muncher_hash.hash(&mut hasher);
+        limits.sample_file_size.hash(&mut hasher);
+        limits.sample_line_length.hash(&mut hasher);
+        limits.sample_size.hash(&mut hasher);
+        limits.tech_debt_markers.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    /// Returns all regex strings from the muncher file with the names of their properties, e.g. `("refs", "^using ")`.
+    /// The built-in rules are not included.
+    pub(crate) fn regex_sources(&self) -> Vec<(&'static str, &String)> {
+        let properties = [
+            ("keywords", &self.keywords),
+            ("bracket_only", &self.bracket_only),
+            ("line_comments", &self.line_comments),
+            ("inline_comments", &self.inline_comments),
+            ("doc_comments", &self.doc_comments),
+            ("block_comments_start", &self.block_comments_start),
+            ("block_comments_end", &self.block_comments_end),
+            ("refs", &self.refs),
+            ("packages", &self.packages),
+            ("language_version", &self.language_version),
+            ("entity_declarations", &self.entity_declarations),
+            ("entity_docs", &self.entity_docs),
+            ("entity_docstrings", &self.entity_docstrings),
+            ("entity_attributes", &self.entity_attributes),
+            ("test_paths", &self.test_paths),
+            ("test_markers", &self.test_markers),
+            ("generated_paths", &self.generated_paths),
+            ("generated_markers", &self.generated_markers),
+            ("template_start", &self.template_start),
+            ("template_end", &self.template_end),
+            ("tech_debt_markers", &self.tech_debt_markers),
+        ];
+
+        properties
+            .iter()
+            .filter_map(|(name, list)| list.as_ref().map(|list| (*name, list)))
+            .flat_map(|(name, list)| list.iter().map(move |regex| (name, regex)))
+            .chain(
+                self.interop
+                    .iter()
+                    .flat_map(|v| v.values().flatten())
+                    .map(|regex| ("interop", regex)),
+            )
+            .collect()
+    }
+
+    /// Returns all compiled regex matched against file contents with the names of their properties,
+    /// including the built-in rules, e.g. `("generated_markers", "(?i)do not edit")`.
+    pub(crate) fn line_regex(&self) -> Vec<(&'static str, &Regex)> {
+        let properties = [
+            ("generated_markers", &self.generated_markers_regex),
+            ("block_comments_start", &self.block_comments_start_regex),
+            ("block_comments_end", &self.block_comments_end_regex),
+            ("doc_comments", &self.doc_comments_regex),
+            ("line_comments", &self.line_comments_regex),
+            ("inline_comments", &self.inline_comments_regex),
+            ("bracket_only", &self.bracket_only_regex),
+            ("refs", &self.refs_regex),
+            ("packages", &self.packages_regex),
+            ("language_version", &self.language_version_regex),
+            ("entity_declarations", &self.entity_declarations_regex),
+            ("entity_docs", &self.entity_docs_regex),
+            ("entity_docstrings", &self.entity_docstrings_regex),
+            ("entity_attributes", &self.entity_attributes_regex),
+            ("keywords", &self.keywords_regex),
+            ("test_markers", &self.test_markers_regex),
+            ("template_start", &self.template_start_regex),
+            ("template_end", &self.template_end_regex),
+            ("tech_debt_markers", &self.tech_debt_markers_regex),
+        ];
+
+        properties
+            .iter()
+            .filter_map(|(name, list)| list.as_ref().map(|list| (*name, list)))
+            .flat_map(|(name, list)| list.iter().map(move |regex| (name, regex)))
+            .chain(self.interop_regex.iter().flatten().map(|(_, regex)| ("interop", regex)))
+            .collect()
+    }
+
+    /// Compiles a muncher rule with `REGEX_SIZE_LIMIT`.
+    pub(crate) fn compile_regex(regex: &str) -> Result<Regex, regex::Error> {
+        RegexBuilder::new(regex).size_limit(REGEX_SIZE_LIMIT).build()
+    }
+
     /// Compiles regex strings.
     fn compile_all_regex(&mut self) -> Result<(), ()> {
         trace!("Compiling regex for {}", self.muncher_name);
@@ -140,6 +360,103 @@ impl Muncher {
             }
         }
 
+        if let Some(v) = self.interop.as_ref() {
+            let mut interop_regex: Option<Vec<Regex>> = None;
+            let mut languages: Vec<String> = Vec::new();
+            for (language, list) in v {
+                for s in list {
+                    if Muncher::add_regex_to_list(&mut interop_regex, s) {
+                        languages.push(language.clone());
+                    } else {
+                        compilation_success = false;
+                    }
+                }
+            }
+            self.interop_regex = interop_regex.map(|v| languages.into_iter().zip(v).collect());
+        }
+
+        if let Some(v) = self.language_version.as_ref() {
+            for s in v {
+                compilation_success &= Muncher::add_regex_to_list(&mut self.language_version_regex, s);
+            }
+        }
+
+        if let Some(v) = self.entity_declarations.as_ref() {
+            for s in v {
+                compilation_success &= Muncher::add_regex_to_list(&mut self.entity_declarations_regex, s);
+            }
+        }
+
+        if let Some(v) = self.entity_docs.as_ref() {
+            for s in v {
+                compilation_success &= Muncher::add_regex_to_list(&mut self.entity_docs_regex, s);
+            }
+        }
+
+        if let Some(v) = self.entity_docstrings.as_ref() {
+            for s in v {
+                compilation_success &= Muncher::add_regex_to_list(&mut self.entity_docstrings_regex, s);
+            }
+        }
+
+        if let Some(v) = self.entity_attributes.as_ref() {
+            for s in v {
+                compilation_success &= Muncher::add_regex_to_list(&mut self.entity_attributes_regex, s);
+            }
+        }
+
+        if let Some(v) = self.test_paths.as_ref() {
+            for s in v {
+                compilation_success &= Muncher::add_regex_to_list(&mut self.test_paths_regex, s);
+            }
+        }
+
+        if let Some(v) = self.test_markers.as_ref() {
+            for s in v {
+                compilation_success &= Muncher::add_regex_to_list(&mut self.test_markers_regex, s);
+            }
+        }
+
+        // the built-in rules for generated code apply to all munchers
+        for s in GENERATED_PATHS.iter() {
+            compilation_success &= Muncher::add_regex_to_list(&mut self.generated_paths_regex, &s.to_string());
+        }
+        if let Some(v) = self.generated_paths.as_ref() {
+            for s in v {
+                compilation_success &= Muncher::add_regex_to_list(&mut self.generated_paths_regex, s);
+            }
+        }
+
+        for s in GENERATED_MARKERS.iter() {
+            compilation_success &= Muncher::add_regex_to_list(&mut self.generated_markers_regex, &s.to_string());
+        }
+        if let Some(v) = self.generated_markers.as_ref() {
+            for s in v {
+                compilation_success &= Muncher::add_regex_to_list(&mut self.generated_markers_regex, s);
+            }
+        }
+
+        if let Some(v) = self.template_start.as_ref() {
+            for s in v {
+                compilation_success &= Muncher::add_regex_to_list(&mut self.template_start_regex, s);
+            }
+        }
+
+        if let Some(v) = self.template_end.as_ref() {
+            for s in v {
+                compilation_success &= Muncher::add_regex_to_list(&mut self.template_end_regex, s);
+            }
+        }
+
+        for s in TECH_DEBT_MARKERS.iter() {
+            compilation_success &= Muncher::add_regex_to_list(&mut self.tech_debt_markers_regex, &s.to_string());
+        }
+        if let Some(v) = self.tech_debt_markers.as_ref() {
+            for s in v {
+                compilation_success &= Muncher::add_regex_to_list(&mut self.tech_debt_markers_regex, s);
+            }
+        }
+
         if let Some(v) = self.keywords.as_ref() {
             for s in v {
                 Muncher::add_regex_to_list(&mut self.keywords_regex, s);
@@ -158,11 +475,47 @@ impl Muncher {
         }
     }
 
+    /// Returns true for template languages that split the file into template tags and the host language around them.
+    pub fn is_template(&self) -> bool {
+        self.template_start_regex.is_some()
+    }
+
+    /// Returns true for notebook formats with code cells in other languages, e.g. Jupyter.
+    pub fn is_notebook(&self) -> bool {
+        self.kernel_munchers.is_some()
+    }
+
+    /// Returns true if the file path matches any of `test_paths` rules.
+    pub fn is_test_path(&self, file_name: &str) -> bool {
+        self.find_test_path_rule(file_name).is_some()
+    }
+
+    /// Returns the first of `test_paths` rules that matches the file path.
+    pub fn find_test_path_rule(&self, file_name: &str) -> Option<&Regex> {
+        self.test_paths_regex.as_ref()?.iter().find(|r| r.is_match(file_name))
+    }
+
+    /// Returns true if the file path matches any of `generated_paths` rules, including the built-in ones.
+    pub fn is_generated_path(&self, file_name: &str) -> bool {
+        self.find_generated_path_rule(file_name).is_some()
+    }
+
+    /// Returns the first of `generated_paths` rules that matches the file path and `true` if it is one of
+    /// the built-in `GENERATED_PATHS` rather than a muncher rule.
+    pub fn find_generated_path_rule(&self, file_name: &str) -> Option<(&Regex, bool)> {
+        self.generated_paths_regex
+            .as_ref()?
+            .iter()
+            .enumerate()
+            .find(|(_, r)| r.is_match(file_name))
+            .map(|(idx, r)| (r, idx < GENERATED_PATHS.len()))
+    }
+
     /// Adds the `regex` to the supplied `list`. Creates an instance of Vec<Regex> on the first insert.
     /// Always returns Some(). Returns FALSE on regex compilation error.
     pub fn add_regex_to_list(list: &mut Option<Vec<Regex>>, regex: &String) -> bool {
         // try to compile the regex
-        let compiled_regex = match Regex::new(regex) {
+        let compiled_regex = match Muncher::compile_regex(regex) {
             Ok(r) => r,
             Err(e) => {
                 error!("Failed to compile regex {} with {}", regex, e);
@@ -194,5 +547,147 @@ impl Hash for Muncher {
         self.block_comments_end.hash(state);
         self.refs.hash(state);
         self.packages.hash(state);
+        self.interop.hash(state);
+        self.language_version.hash(state);
+        self.entity_declarations.hash(state);
+        self.entity_docs.hash(state);
+        self.entity_docstrings.hash(state);
+        self.entity_attributes.hash(state);
+        self.test_paths.hash(state);
+        self.test_markers.hash(state);
+        self.generated_paths.hash(state);
+        self.generated_markers.hash(state);
+        self.template_start.hash(state);
+        self.template_end.hash(state);
+        self.tech_debt_markers.hash(state);
+        self.host_muncher.hash(state);
+        self.kernel_munchers.hash(state);
+        // changes to the built-in rules should trigger reprocessing the same way as changes to the muncher
+        GENERATED_PATHS.hash(state);
+        GENERATED_MARKERS.hash(state);
+        TECH_DEBT_MARKERS.hash(state);
+    }
+}
+
+#[cfg(test)]
+mod test_muncher {
+    use super::{MunchLimits, Muncher};
+    use crate::report::Tech;
+
+    #[test]
+    fn test_cache_hash() {
+        let rules = include_str!("../stm_rules/munchers/rust.rs.json");
+        let muncher = Muncher::new(rules, &"rust.rs.json".to_owned()).expect("Invalid muncher");
+        let limits = MunchLimits::default();
+        let cache_hash = muncher.cache_hash(&limits);
+        assert_eq!(muncher.cache_hash(&limits.clone()), cache_hash);
+
+        // the limits that change the counts invalidate the cached records
+        for changed_limits in [
+            MunchLimits {
+                sample_file_size: 0,
+                ..limits.clone()
+            },
+            MunchLimits {
+                sample_line_length: 0,
+                ..limits.clone()
+            },
+            MunchLimits {
+                sample_size: 1024,
+                ..limits.clone()
+            },
+            MunchLimits {
+                tech_debt_markers: true,
+                ..limits.clone()
+            },
+        ]
+        .iter()
+        {
+            assert_ne!(muncher.cache_hash(changed_limits), cache_hash);
+        }
+
+        // the limits that only stop the processing do not
+        let timeout_limits = MunchLimits {
+            timeout_ms: 1,
+            ..limits.clone()
+        };
+        assert_eq!(muncher.cache_hash(&timeout_limits), cache_hash);
+    }
+
+    #[test]
+    fn test_is_test_path() {
+        let rules = include_str!("../stm_rules/munchers/go.json");
+        let muncher = Muncher::new(rules, &"go.json".to_owned()).expect("Invalid muncher");
+        assert!(muncher.is_test_path("pkg/server/handler_test.go"));
+        assert!(!muncher.is_test_path("pkg/server/handler.go"));
+
+        let rules = include_str!("../stm_rules/munchers/rust.rs.json");
+        let muncher = Muncher::new(rules, &"rust.rs.json".to_owned()).expect("Invalid muncher");
+        assert!(muncher.is_test_path("tests/integration.rs"));
+        assert!(muncher.is_test_path("stackmuncher_lib/tests/integration.rs"));
+        assert!(!muncher.is_test_path("src/contests/mod.rs"));
+        assert!(muncher.test_markers_regex.as_ref().unwrap()[0].is_match("    #[tokio::test]"));
+    }
+
+    #[test]
+    fn test_interop() {
+        let rules = include_str!("../stm_rules/munchers/rust.rs.json");
+        let muncher = Muncher::new(rules, &"rust.rs.json".to_owned()).expect("Invalid muncher");
+        let mut tech = Tech::test("Rust", "rust.rs");
+
+        for line in [
+            r#"extern "C" {"#,
+            r#"pub unsafe extern "C" fn stm_free(ptr: *mut libc::c_char) {"#,
+            "#[pyfunction]",
+            "use pyo3::prelude::*;",
+            "fn main() {",
+        ] {
+            tech.count_interop(&muncher.interop_regex, line);
+        }
+
+        let mut interop = tech
+            .interop
+            .iter()
+            .map(|v| (v.k.as_str(), v.c))
+            .collect::<Vec<(&str, u64)>>();
+        interop.sort();
+        // a line with several C markers is counted once
+        assert_eq!(interop, vec![("C", 2), ("Python", 2)]);
+    }
+
+    #[test]
+    fn test_language_version() {
+        let rules = include_str!("../stm_rules/munchers/rust.cargo.toml.json");
+        let muncher = Muncher::new(rules, &"rust.cargo.toml.json".to_owned()).expect("Invalid muncher");
+        let mut tech = Tech::test("Rust", "rust.cargo.toml");
+
+        for line in [
+            "[package]",
+            r#"edition = "2021""#,
+            r#"version = "0.2.1""#,
+            "edition.workspace = true",
+        ] {
+            tech.count_language_version(&muncher.language_version_regex, &line.to_owned());
+        }
+
+        let versions = tech
+            .language_version
+            .iter()
+            .map(|v| (v.k.as_str(), v.c))
+            .collect::<Vec<(&str, u64)>>();
+        assert_eq!(versions, vec![("2021", 1)]);
+
+        // the patch version is dropped
+        let rules = include_str!("../stm_rules/munchers/go.mod.json");
+        let muncher = Muncher::new(rules, &"go.mod.json".to_owned()).expect("Invalid muncher");
+        assert_eq!(
+            muncher.language_version_regex.as_ref().unwrap()[0]
+                .captures("go 1.22.1")
+                .unwrap()
+                .get(1)
+                .unwrap()
+                .as_str(),
+            "1.22"
+        );
     }
 }
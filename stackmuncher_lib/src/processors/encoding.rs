@@ -0,0 +1,157 @@
+use encoding_rs::{Encoding, GBK, SHIFT_JIS, UTF_16BE, UTF_16LE, UTF_8, WINDOWS_1252};
+use tracing::{debug, warn};
+
+/// The number of bytes at the start of the file checked for NUL bytes and UTF-16 without BOM, same as Git does
+/// for its binary file check.
+const SNIFF_LEN: usize = 8000;
+
+/// Encodings tried if the file has no BOM and is not valid UTF-8, in the order of preference for equal scores.
+/// WINDOWS-1252 decodes any input and is the fallback.
+const LEGACY_ENCODINGS: [&Encoding; 3] = [SHIFT_JIS, GBK, WINDOWS_1252];
+
+/// Decodes the file contents into a string and returns it with the detected encoding.
+/// 1. a BOM is trusted over any guesses, e.g. UTF-16LE for `FF FE`
+/// 2. UTF-16 without BOM is detected by NUL bytes at every other position
+/// 3. any other NUL bytes mean a binary file
+/// 4. valid UTF-8 is UTF-8
+/// 5. the best scoring of `LEGACY_ENCODINGS`, see `score`
+///
+/// Returns an error for binary files and files that are invalid in the encoding of their BOM or in UTF-16.
+pub(crate) fn decode(file_name: &str, contents: &[u8]) -> Result<(String, &'static Encoding), ()> {
+    if let Some((encoding, bom_len)) = Encoding::for_bom(contents) {
+        return decode_strict(file_name, &contents[bom_len..], encoding);
+    }
+
+    let head = &contents[..contents.len().min(SNIFF_LEN)];
+    if let Some(encoding) = sniff_utf16(head) {
+        return decode_strict(file_name, contents, encoding);
+    }
+
+    if head.contains(&0) {
+        debug!("{} is binary", file_name);
+        return Err(());
+    }
+
+    if let Ok(v) = std::str::from_utf8(contents) {
+        return Ok((v.to_owned(), UTF_8));
+    }
+
+    // `max_by_key` returns the last of equal elements
+    LEGACY_ENCODINGS
+        .iter()
+        .rev()
+        .filter_map(|encoding| {
+            let text = encoding.decode_without_bom_handling_and_without_replacement(contents)?;
+            Some((score(&text), text.into_owned(), *encoding))
+        })
+        .max_by_key(|(score, _, _)| *score)
+        .map(|(_, text, encoding)| {
+            debug!("{} decoded as {}", file_name, encoding.name());
+            (text, encoding)
+        })
+        .ok_or(())
+}
+
+/// Decodes the contents without the BOM with no replacement of malformed sequences.
+fn decode_strict(
+    file_name: &str,
+    contents: &[u8],
+    encoding: &'static Encoding,
+) -> Result<(String, &'static Encoding), ()> {
+    match encoding.decode_without_bom_handling_and_without_replacement(contents) {
+        Some(v) => Ok((v.into_owned(), encoding)),
+        None => {
+            warn!("Cannot decode {} as {}", file_name, encoding.name());
+            Err(())
+        }
+    }
+}
+
+/// Returns UTF-16LE or UTF-16BE if most of the high bytes of the code units at the start of the file are NUL,
+/// as in ASCII text, and the low bytes are not.
+fn sniff_utf16(head: &[u8]) -> Option<&'static Encoding> {
+    let units = head.len() / 2;
+    if units == 0 {
+        return None;
+    }
+
+    let even_nuls = head.iter().step_by(2).take(units).filter(|v| **v == 0).count();
+    let odd_nuls = head.iter().skip(1).step_by(2).filter(|v| **v == 0).count();
+    let is_mostly_nul = |nuls: usize| nuls * 10 >= units * 6;
+    let is_rarely_nul = |nuls: usize| nuls * 10 < units;
+
+    if is_mostly_nul(odd_nuls) && is_rarely_nul(even_nuls) {
+        Some(UTF_16LE)
+    } else if is_mostly_nul(even_nuls) && is_rarely_nul(odd_nuls) {
+        Some(UTF_16BE)
+    } else {
+        None
+    }
+}
+
+/// Scores the text by how much its non-ASCII chars look like words rather than a misread of another encoding.
+/// Kana and CJK chars score only next to other non-ASCII chars, as in Japanese or Chinese words, with kana
+/// scoring higher to tell Shift_JIS from GBK. Accented Latin letters score only next to ASCII letters, as in
+/// European words. Control chars, private use chars and half-width katakana are rare in text and are penalized.
+fn score(text: &str) -> i64 {
+    let chars = text.chars().collect::<Vec<char>>();
+    let mut score = 0;
+
+    for (idx, c) in chars.iter().enumerate().filter(|(_, c)| !c.is_ascii()) {
+        let neighbors = [idx.checked_sub(1).and_then(|v| chars.get(v)), chars.get(idx + 1)];
+        let in_word = neighbors.iter().flatten().any(|v| !v.is_ascii());
+        let in_latin_word = neighbors.iter().flatten().any(|v| v.is_ascii_alphabetic());
+
+        score += match c {
+            '\u{3040}'..='\u{30ff}' if in_word => 3,
+            '\u{3000}'..='\u{303f}' | '\u{4e00}'..='\u{9fff}' | '\u{ff01}'..='\u{ff5e}' if in_word => 2,
+            '\u{c0}'..='\u{ff}' if in_latin_word => 1,
+            '\u{80}'..='\u{9f}' | '\u{e000}'..='\u{f8ff}' | '\u{ff61}'..='\u{ff9f}' => -5,
+            _ => 0,
+        };
+    }
+
+    score
+}
+
+#[cfg(test)]
+mod test_encoding {
+    use super::decode;
+    use encoding_rs::{GBK, SHIFT_JIS, UTF_16BE, UTF_16LE, UTF_8, WINDOWS_1252};
+
+    #[test]
+    fn test_decode() {
+        let japanese = "// 挨拶を表示する関数です\nfn main() {\n    println!(\"こんにちは、世界\");\n}\n";
+        let (encoded, _, _) = SHIFT_JIS.encode(japanese);
+        assert_eq!(decode("a.rs", &encoded).unwrap(), (japanese.to_owned(), SHIFT_JIS));
+
+        let chinese = "// 打印问候语的函数\nfn main() {\n    println!(\"你好，世界\");\n}\n";
+        let (encoded, _, _) = GBK.encode(chinese);
+        assert_eq!(decode("a.rs", &encoded).unwrap(), (chinese.to_owned(), GBK));
+
+        let french = "// Affiche « crème brûlée » à l'écran\nfn main() {}\n";
+        let (encoded, _, _) = WINDOWS_1252.encode(french);
+        assert_eq!(decode("a.rs", &encoded).unwrap(), (french.to_owned(), WINDOWS_1252));
+
+        let utf16le = chinese
+            .encode_utf16()
+            .flat_map(|v| v.to_le_bytes())
+            .collect::<Vec<u8>>();
+        assert_eq!(decode("a.rs", &utf16le).unwrap(), (chinese.to_owned(), UTF_16LE));
+        let utf16be = chinese
+            .encode_utf16()
+            .flat_map(|v| v.to_be_bytes())
+            .collect::<Vec<u8>>();
+        assert_eq!(decode("a.rs", &utf16be).unwrap(), (chinese.to_owned(), UTF_16BE));
+
+        // the BOM is removed
+        let utf8_bom = [&[0xEF, 0xBB, 0xBF], chinese.as_bytes()].concat();
+        assert_eq!(decode("a.rs", &utf8_bom).unwrap(), (chinese.to_owned(), UTF_8));
+        assert_eq!(decode("a.rs", b"").unwrap(), (String::new(), UTF_8));
+
+        // binary, an invalid UTF-8 after a BOM and an odd number of bytes in UTF-16
+        assert!(decode("a.rs", b"fn main() {}\n\x00\x01\x02\x03").is_err());
+        assert!(decode("a.rs", &[0xEF, 0xBB, 0xBF, b'a', 0xFF]).is_err());
+        assert!(decode("a.rs", &[b'a', 0, b'b', 0, b'c']).is_err());
+    }
+}
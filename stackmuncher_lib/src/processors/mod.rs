@@ -1,43 +1,91 @@
-use super::muncher::Muncher;
-use crate::git::get_blob_contents;
+use super::muncher::{MunchLimits, Muncher};
+use crate::generated_code::{GENERATED_MARKER_LINES, MINIFIED_LINE_LENGTH};
+use crate::git::GitBlob;
 use crate::report::Tech;
-use encoding_rs as _;
-use encoding_rs::WINDOWS_1252;
-use encoding_rs_io::{DecodeReaderBytes, DecodeReaderBytesBuilder};
+use encoding_rs::UTF_8;
+use notebook::Notebook;
 use regex::Regex;
 use std::collections::HashSet;
-use std::io::Read;
-use std::path::Path;
-use tracing::{debug, trace, warn};
-
-/// Extract the file as git blob contents from the repository and perform the analysis.
-/// * **all_tree_files***: needed to remove local imports that match the local file name, e.g. as in Python or Rust
-pub(crate) async fn process_file(
-    file_name: &String,
-    blob_sha1: &String,
+use std::time::{Duration, Instant};
+use tracing::{debug, trace};
+
+mod encoding;
+mod notebook;
+pub mod sample;
+
+/// Settings for `process_file` that are the same for all files in a run.
+pub(crate) struct MunchOptions<'a> {
+    /// Processing stops with `MunchError::Timeout` after `timeout_ms` and long lines are truncated before matching.
+    pub limits: &'a MunchLimits,
+    /// Needed to remove local imports that match the local file name, e.g. as in Python or Rust.
+    pub all_tree_files: Option<&'a HashSet<String>>,
+}
+
+/// The reason a file could not be munched.
+#[derive(Debug)]
+pub enum MunchError {
+    /// The blob could not be read, e.g. a missing object in a shallow clone.
+    Unreadable(String),
+    /// Processing took longer than `MunchLimits.timeout_ms`. Includes the slowest rule for the muncher authors to fix.
+    Timeout(String),
+}
+
+/// Performs the analysis of the file contents read from the repository as a blob.
+/// Returns a single tech record for most files. Template files get a record for the template language and another one
+/// for the host language if there is a host muncher, e.g. eRuby and HTML for `index.html.erb`. Notebooks get a record
+/// for the notebook with its markdown cells and another one for the code cells in the kernel language, e.g. Jupyter and Python.
+/// * **host_rules***: the muncher for the lines outside template tags or the kernel munchers of notebooks,
+///   see `CodeRules::get_munchers`
+/// * **blob**: the commit details of the blob are copied into the records
+/// * **options**: the limits and the repo tree, see `MunchOptions`
+pub(crate) fn process_file(
+    file_name: &str,
+    contents: &[u8],
     rules: &Muncher,
-    project_dir: &Path,
-    commit_sha1: &String,
-    commit_date_epoch: i64,
-    commit_date_iso: &String,
-    all_tree_files: Option<&HashSet<String>>,
-) -> Result<Tech, String> {
+    host_rules: &[&Muncher],
+    blob: &GitBlob,
+    options: &MunchOptions,
+) -> Result<Vec<Tech>, MunchError> {
     debug!("Muncher: {}", rules.muncher_name);
 
     // prepare the blank structure
-    let mut tech = Tech {
+    let mut blank_tech = blank_tech(rules, file_name);
+    blank_tech.muncher_hash = rules.cache_hash(options.limits);
+    blank_tech.commit_sha1 = Some(blob.commit_sha1.clone());
+    blank_tech.commit_date_epoch = Some(blob.commit_date_epoch);
+    blank_tech.commit_date_iso = Some(blob.commit_date_iso.clone());
+
+    munch_contents(file_name, contents, rules, host_rules, options.limits, blank_tech, options.all_tree_files)
+}
+
+/// Returns a blank tech record for the file with no commit details.
+fn blank_tech(rules: &Muncher, file_name: &str) -> Tech {
+    Tech {
         language: rules.language.clone(),
         muncher_name: rules.muncher_name.clone(),
-        file_name: Some(file_name.clone()),
-        commit_sha1: Some(commit_sha1.clone()),
-        commit_date_epoch: Some(commit_date_epoch),
-        commit_date_iso: Some(commit_date_iso.clone()),
+        file_name: Some(file_name.to_owned()),
+        commit_sha1: None,
+        commit_date_epoch: None,
+        commit_date_iso: None,
         files: 1,
         total_lines: 0,
         code_lines: 0,
         line_comments: 0,
         block_comments: 0,
         docs_comments: 0,
+        test_files: 0,
+        test_loc: 0,
+        generated_loc: 0,
+        entities: 0,
+        documented_entities: 0,
+        code_cells: 0,
+        markdown_cells: 0,
+        overflow_distinct: 0,
+        overflow_total: 0,
+        absent_runs: 0,
+        encoding: None,
+        undecodable_files: 0,
+        sampled: false,
         inline_comments: 0,
         blank_lines: 0,
         bracket_only_lines: 0,
@@ -46,63 +94,421 @@ pub(crate) async fn process_file(
         refs_kw: None,
         pkgs: HashSet::new(), // they should be Option<>
         pkgs_kw: None,
+        interop: HashSet::new(),
+        language_version: HashSet::new(),
+        tech_debt_markers: HashSet::new(),
+        local_deps: HashSet::new(),
         muncher_hash: rules.muncher_hash,
         history: None,
-    };
+    }
+}
 
-    // get file contents as UTF
-    let lines = match get_file_lines(file_name, blob_sha1, project_dir, false).await {
-        Ok(v) => v,
-        Err(_) => {
-            // try ANSI if that fails
-            match get_file_lines(file_name, blob_sha1, project_dir, true).await {
-                Err(_) => {
-                    // exit now if the file is either empty or binary
-                    trace!("Empty or binary file - not processing.");
-                    return Ok(tech);
-                }
-                Ok(v) => v,
+/// Munches the contents of a file already read from the repo or from disk. See `process_file` for details.
+/// * **blank_tech**: the record for `rules` to add the counts to, with the file and commit details already set
+fn munch_contents(
+    file_name: &str,
+    contents: &[u8],
+    rules: &Muncher,
+    host_rules: &[&Muncher],
+    limits: &MunchLimits,
+    blank_tech: Tech,
+    all_tree_files: Option<&HashSet<String>>,
+) -> Result<Vec<Tech>, MunchError> {
+    let mut counter = LineCounter::new(rules, blank_tech.clone(), limits.tech_debt_markers);
+
+    // very large files are usually data or vendored code and are counted as blank records
+    if limits.max_file_size > 0 && contents.len() as u64 > limits.max_file_size {
+        debug!("{} is over max_file_size: {} bytes - not processing.", file_name, contents.len());
+        return Ok(vec![counter.tech]);
+    }
+
+    // get file contents as UTF-8 from whatever encoding they are in
+    let text = match encoding::decode(file_name, contents) {
+        Ok((text, encoding)) => {
+            if encoding != UTF_8 {
+                counter.tech.encoding = Some(encoding.name().to_owned());
             }
+            text
+        }
+        Err(_) => {
+            // binary or invalid files are counted, but not processed
+            trace!("Binary or undecodable file - not processing.");
+            counter.tech.undecodable_files = 1;
+            return Ok(vec![counter.tech]);
+        }
+    };
+
+    // SQL dumps, minified bundles and other huge files take too long to munch line by line and are munched from samples
+    // notebooks have to be parsed in full
+    let sample = if rules.is_notebook() {
+        None
+    } else {
+        sample_lines(&text, limits)
+    };
+    let (lines, file_lines) = match sample {
+        Some(sample) => {
+            debug!("Sampled {} of {} lines", sample.lines.len(), sample.file_lines);
+            (sample.lines, Some(sample.file_lines))
         }
+        None => (text.lines().map(|v| v.to_owned()).collect::<Vec<String>>(), None),
     };
+    drop(text);
+    let sampled_line_count = lines.len() as u64;
     if lines.len() == 0 {
         // no point processing an empty file further
         trace!("The file is empty - not processing.");
-        return Ok(tech);
+        return Ok(vec![counter.tech]);
     }
 
-    // get total lines
-    tech.total_lines = lines.len() as u64;
+    // notebooks are reduced to the lines of their code cells, which are counted as the kernel language
+    let (lines, host_rules) = if rules.is_notebook() {
+        let notebook = match Notebook::parse(&lines.join("\n")) {
+            Some(v) => v,
+            None => {
+                debug!("Invalid notebook - not processing.");
+                return Ok(vec![counter.tech]);
+            }
+        };
+        notebook.count_cells(&mut counter.tech);
+
+        let kernel_muncher_name = notebook
+            .kernel_language
+            .as_ref()
+            .and_then(|kernel_language| rules.kernel_munchers.as_ref()?.get(kernel_language));
+        let kernel_rules = host_rules
+            .iter()
+            .find(|host_rules| Some(&host_rules.muncher_name) == kernel_muncher_name);
+        if kernel_rules.is_none() {
+            debug!("No muncher for kernel {:?}", notebook.kernel_language);
+        }
+
+        (notebook.code_lines(), kernel_rules.copied())
+    } else {
+        (lines, host_rules.first().copied())
+    };
+
+    // the host record is matched to the file by the hash of the template muncher when the cached data is reused
+    let mut host_counter = host_rules.map(|host_rules| {
+        let tech = Tech {
+            language: host_rules.language.clone(),
+            muncher_name: host_rules.muncher_name.clone(),
+            ..blank_tech.clone()
+        };
+        LineCounter::new(host_rules, tech, limits.tech_debt_markers)
+    });
+
+    // the file is test code if either the path or any of the code lines say so
+    // generated code is detected by the path or by markers and minified code at the top of the file
+    let mut flags = FileFlags {
+        is_test_code: rules.is_test_path(file_name),
+        is_generated_code: rules.is_generated_path(file_name),
+    };
+
+    // set to true when the line is inside template tags that were opened on one of the previous lines
+    let mut inside_template = false;
 
-    // set to true when the line is inside a block comment
-    let mut inside_block_comment = false;
+    // a single bad rule should not hang the entire run on a large file
+    let deadline = Instant::now() + Duration::from_millis(limits.timeout_ms);
 
     // evaluate every line
-    for line in lines {
+    for (line_idx, line) in lines.into_iter().enumerate() {
         trace!("{}", line);
+
+        // very long lines are usually minified code and only the start of them is matched against the rules
+        let is_minified = line.len() > MINIFIED_LINE_LENGTH;
+        let line = truncate_line(line, limits.max_line_length);
+
+        // lines outside template tags and code cells of notebooks belong to the host language, if there is one
+        let line_counter = match host_counter.as_mut() {
+            Some(host_counter) if rules.is_notebook() || !is_template_line(rules, &line, &mut inside_template) => {
+                host_counter
+            }
+            _ => &mut counter,
+        };
+
+        if Instant::now() > deadline {
+            return Err(MunchError::Timeout(timeout_reason(
+                line_counter.rules,
+                &line,
+                line_idx,
+                limits.timeout_ms,
+            )));
+        }
+
+        line_counter.count_line(&line, line_idx, is_minified, &mut flags);
+    }
+
+    // the counts from the samples are scaled up to the size of the file
+    if let Some(file_lines) = file_lines {
+        extrapolate_sample(&mut counter.tech, sampled_line_count, file_lines);
+        if let Some(host_counter) = host_counter.as_mut() {
+            extrapolate_sample(&mut host_counter.tech, sampled_line_count, file_lines);
+        }
+    }
+
+    // the host record is only needed if there were lines outside template tags or in code cells
+    let mut techs = vec![counter.tech];
+    if let Some(host_counter) = host_counter {
+        if host_counter.tech.total_lines > 0 {
+            techs.push(host_counter.tech);
+        }
+    }
+
+    let techs = techs
+        .into_iter()
+        .map(|mut tech| {
+            if flags.is_generated_code {
+                // generated code is reported separately and does not count towards the LoC or libraries
+                debug!("Generated code: {}", file_name);
+                tech.generated_loc = tech.code_lines;
+                tech.code_lines = 0;
+                tech.keywords.clear();
+                tech.refs.clear();
+                tech.pkgs.clear();
+                tech.interop.clear();
+                tech.tech_debt_markers.clear();
+                tech.entities = 0;
+                tech.documented_entities = 0;
+            } else if flags.is_test_code {
+                debug!("Test code: {}", file_name);
+                tech.test_files = 1;
+                tech.test_loc = tech.code_lines;
+                // files with some tests in them are mostly code that is expected to have docs, e.g. Rust modules
+                if rules.is_test_path(file_name) {
+                    tech.entities = 0;
+                    tech.documented_entities = 0;
+                }
+            }
+
+            // remove refs names that match local file names
+            tech.remove_local_imports(all_tree_files)
+        })
+        .collect();
+
+    Ok(techs)
+}
+
+/// Lines from the start and the end of a huge file to munch instead of the entire file.
+struct Sample {
+    /// The first and the last lines of the file, in that order.
+    lines: Vec<String>,
+    /// The number of lines in the entire file.
+    file_lines: u64,
+}
+
+/// Returns up to `MunchLimits.sample_size` bytes of whole lines from the start and from the end of the file if it is
+/// over `sample_file_size` or its lines are longer than `sample_line_length` on average.
+/// Returns None if the file should be munched in full, including files that are not much larger than the samples.
+fn sample_lines(text: &str, limits: &MunchLimits) -> Option<Sample> {
+    let file_size = text.len() as u64;
+    if file_size <= limits.sample_size * 2 {
+        return None;
+    }
+
+    let file_lines = text.lines().count();
+    let is_large = limits.sample_file_size > 0 && file_size > limits.sample_file_size;
+    let has_long_lines =
+        limits.sample_line_length > 0 && file_size / (file_lines.max(1) as u64) > limits.sample_line_length;
+    if !is_large && !has_long_lines {
+        return None;
+    }
+
+    // the samples must not overlap in files with a few very long lines
+    let head_lines = sample_line_count(text.lines(), limits.sample_size);
+    let tail_lines = sample_line_count(text.lines().rev().take(file_lines - head_lines), limits.sample_size);
+    if head_lines + tail_lines >= file_lines {
+        return None;
+    }
+
+    let mut lines = text
+        .lines()
+        .take(head_lines)
+        .map(|v| v.to_owned())
+        .collect::<Vec<String>>();
+    let mut tail = text
+        .lines()
+        .rev()
+        .take(tail_lines)
+        .map(|v| v.to_owned())
+        .collect::<Vec<String>>();
+    tail.reverse();
+    lines.append(&mut tail);
+
+    Some(Sample {
+        lines,
+        file_lines: file_lines as u64,
+    })
+}
+
+/// Returns the number of lines that fit into `sample_size` bytes. The last line may go over the limit.
+fn sample_line_count<'a>(lines: impl Iterator<Item = &'a str>, sample_size: u64) -> usize {
+    let mut size = 0u64;
+    lines
+        .take_while(|line| {
+            let fits = size < sample_size;
+            size += line.len() as u64 + 1;
+            fits
+        })
+        .count()
+}
+
+/// Scales the line counts of a record munched from samples up to the number of lines in the entire file and marks it
+/// as sampled. Keywords, libraries and other lists are left as they were found in the samples.
+fn extrapolate_sample(tech: &mut Tech, sample_lines: u64, file_lines: u64) {
+    tech.sampled = true;
+    if sample_lines == 0 {
+        return;
+    }
+
+    let scale = |count: u64| (count as f64 * file_lines as f64 / sample_lines as f64).round() as u64;
+    tech.total_lines = scale(tech.total_lines);
+    tech.blank_lines = scale(tech.blank_lines);
+    tech.bracket_only_lines = scale(tech.bracket_only_lines);
+    tech.code_lines = scale(tech.code_lines);
+    tech.inline_comments = scale(tech.inline_comments);
+    tech.line_comments = scale(tech.line_comments);
+    tech.block_comments = scale(tech.block_comments);
+    tech.docs_comments = scale(tech.docs_comments);
+}
+
+/// Properties of the entire file detected from any of its lines.
+struct FileFlags {
+    is_test_code: bool,
+    is_generated_code: bool,
+}
+
+/// Counts lines of a file with the rules of a single muncher. Template files have one counter for the template
+/// tags and another one for the host language around them.
+struct LineCounter<'a> {
+    rules: &'a Muncher,
+    tech: Tech,
+    /// Set to true when the line is inside a block comment
+    inside_block_comment: bool,
+    /// Set to true by entity docs that were not followed by a declaration yet
+    entity_docs_above: bool,
+    /// Set to true by a declaration with no docs above it that may have a docstring on the next line
+    undocumented_entity: bool,
+    /// Set to true by a test marker that was not followed by a declaration yet, e.g. `#[test]`
+    test_marker_above: bool,
+    /// Comment lines are checked for tech-debt markers, see `MunchLimits.tech_debt_markers`
+    count_tech_debt_markers: bool,
+}
+
+impl<'a> LineCounter<'a> {
+    fn new(rules: &'a Muncher, tech: Tech, count_tech_debt_markers: bool) -> Self {
+        Self {
+            rules,
+            tech,
+            inside_block_comment: false,
+            entity_docs_above: false,
+            undocumented_entity: false,
+            test_marker_above: false,
+            count_tech_debt_markers,
+        }
+    }
+
+    /// Counts TODO, FIXME and other markers in a comment line if that is enabled.
+    fn count_comment_markers(&mut self, line: &str) {
+        if self.count_tech_debt_markers {
+            self.tech
+                .count_tech_debt_markers(&self.rules.tech_debt_markers_regex, line);
+        }
+    }
+
+    /// Counts entity declarations and whether they have docs above or a docstring right below them.
+    /// Blank lines, comments and attributes between the docs and the declaration are allowed.
+    /// Declarations marked as tests, e.g. with `#[test]`, are not counted.
+    fn count_entity(&mut self, line: &String) {
+        let rules = self.rules;
+        if match_line(&rules.blank_line_regex, line) {
+            return;
+        }
+
+        // a docstring has to be on the first line after the declaration
+        let undocumented_entity = std::mem::replace(&mut self.undocumented_entity, false);
+        if undocumented_entity && match_line(&rules.entity_docstrings_regex, line) {
+            self.tech.documented_entities += 1;
+            return;
+        }
+
+        if match_line(&rules.entity_docs_regex, line) {
+            self.entity_docs_above = true;
+            return;
+        }
+
+        if match_line(&rules.test_markers_regex, line) {
+            self.test_marker_above = true;
+            return;
+        }
+
+        if match_line(&rules.entity_declarations_regex, line) {
+            if std::mem::replace(&mut self.test_marker_above, false) {
+                self.entity_docs_above = false;
+                return;
+            }
+            self.tech.entities += 1;
+            if self.entity_docs_above {
+                self.tech.documented_entities += 1;
+            } else {
+                self.undocumented_entity = true;
+            }
+            self.entity_docs_above = false;
+            return;
+        }
+
+        // the docs were for something else, e.g. a field or a constant
+        if !self.inside_block_comment && !match_line(&rules.entity_attributes_regex, line) {
+            self.entity_docs_above = false;
+            self.test_marker_above = false;
+        }
+    }
+
+    /// Classifies the line as code, a comment, a blank line, etc. and updates the counts in `tech`.
+    /// * `line_idx` - the 0-based index of the line in the file for detecting generated code at the top of the file
+    /// * `is_minified` - the line was too long before it was truncated
+    fn count_line(&mut self, line: &String, line_idx: usize, is_minified: bool, flags: &mut FileFlags) {
+        let rules = self.rules;
+        let tech = &mut self.tech;
+        tech.total_lines += 1;
+
+        if !flags.is_generated_code
+            && line_idx < GENERATED_MARKER_LINES
+            && (is_minified || match_line(&rules.generated_markers_regex, line))
+        {
+            trace!("generated_markers");
+            flags.is_generated_code = true;
+        }
+
+        if rules.entity_declarations_regex.is_some() {
+            self.count_entity(line);
+        }
+        let tech = &mut self.tech;
+
         // check for non-code parts
 
         // check if it's inside a block comment
-        if inside_block_comment {
+        if self.inside_block_comment {
             tech.block_comments += 1;
             trace!("block_comments");
             // is it a closing block?
-            if match_line(&rules.block_comments_end_regex, &line) {
-                inside_block_comment = false;
+            if match_line(&rules.block_comments_end_regex, line) {
+                self.inside_block_comment = false;
             }
-            continue;
+            self.count_comment_markers(line);
+            return;
         }
 
-        if match_line(&rules.block_comments_start_regex, &line) {
+        if match_line(&rules.block_comments_start_regex, line) {
             tech.block_comments += 1;
             trace!("block_comments");
 
             // mark it as the start of the block if there is no closing part on the same line
-            if !match_line(&rules.block_comments_end_regex, &line) {
-                inside_block_comment = true;
+            if !match_line(&rules.block_comments_end_regex, line) {
+                self.inside_block_comment = true;
             }
+            self.count_comment_markers(line);
 
-            continue;
+            return;
 
             // It is possible that some code may have multiple opening / closing comments on the same page.
             // That would probably be just messy code that can be ignored.
@@ -110,34 +516,37 @@ pub(crate) async fn process_file(
             // The same applies to other types of comments - they can be inside " ... "
         }
 
-        if match_line(&rules.doc_comments_regex, &line) {
+        if match_line(&rules.doc_comments_regex, line) {
             tech.docs_comments += 1;
             trace!("doc_comments");
-            continue;
+            self.count_comment_markers(line);
+            return;
         }
 
-        if match_line(&rules.line_comments_regex, &line) {
+        if match_line(&rules.line_comments_regex, line) {
             tech.line_comments += 1;
             trace!("line_comments");
-            continue;
+            self.count_comment_markers(line);
+            return;
         }
 
-        if match_line(&rules.inline_comments_regex, &line) {
+        if match_line(&rules.inline_comments_regex, line) {
             tech.inline_comments += 1;
             trace!("inline_comments");
-            continue;
+            self.count_comment_markers(line);
+            return;
         }
 
-        if match_line(&rules.bracket_only_regex, &line) {
+        if match_line(&rules.bracket_only_regex, line) {
             tech.bracket_only_lines += 1;
             trace!("bracket_only_lines");
-            continue;
+            return;
         }
 
-        if match_line(&rules.blank_line_regex, &line) {
+        if match_line(&rules.blank_line_regex, line) {
             tech.blank_lines += 1;
             trace!("blank_lines");
-            continue;
+            return;
         }
 
         // this is a code line of sorts
@@ -145,58 +554,82 @@ pub(crate) async fn process_file(
         trace!("code_lines");
 
         // count keywords and package references
-        tech.count_refs(&rules.refs_regex, &line);
-        tech.count_pkgs(&rules.packages_regex, &line);
-        tech.count_keywords(&rules.keywords_regex, &line);
-    }
-
-    // remove refs names that match local file names
-    tech = tech.remove_local_imports(all_tree_files);
-
-    Ok(tech)
-}
-
-/// Returns multiple lines from a text file, if the encoding is UTF-something.
-/// Returns an error if the file cannot be read or cannot be decoded.
-/// ANSI files may be incompatible with UTF, so use it with try_ansi=false first
-/// and then try_ansi=true to read it as WINDOWS_1252
-async fn get_file_lines(
-    file_name: &String,
-    blob_sha1: &String,
-    project_dir: &Path,
-    try_ansi: bool,
-) -> Result<Vec<String>, ()> {
-    // read the file
-    let file = get_blob_contents(project_dir, &blob_sha1).await?;
-    // this decoder is required to read non-UTF-8 files
-    let mut decoder = if try_ansi {
-        DecodeReaderBytesBuilder::new()
-            .encoding(Some(WINDOWS_1252))
-            .build(&file[..])
-    } else {
-        DecodeReaderBytes::new(&file[..])
-    };
+        tech.count_refs(&rules.refs_regex, line);
+        tech.count_pkgs(&rules.packages_regex, line);
+        tech.count_keywords(&rules.keywords_regex, line);
+        tech.count_interop(&rules.interop_regex, line);
+        tech.count_language_version(&rules.language_version_regex, line);
 
-    // output collector
-    let mut lines: Vec<String> = Vec::new();
-
-    // try to read the file
-    let mut utf8_string = String::new();
-    if let Err(e) = decoder.read_to_string(&mut utf8_string) {
-        // log an error only on the 2nd run of this function when ANSI is ON
-        if try_ansi {
-            warn!("Cannot decode {} as UTF due to {} with ANSI={}", file_name, e, try_ansi);
+        if !flags.is_test_code && match_line(&rules.test_markers_regex, line) {
+            trace!("test_markers");
+            flags.is_test_code = true;
         }
+    }
+}
 
-        return Err(());
-    };
+/// Returns true if the line has template tags or is inside tags opened on one of the previous lines.
+/// `inside_template` is updated with the state of the tags at the end of the line, e.g. it is set to true
+/// by `<% if user %>` in ERB for the lines that follow.
+fn is_template_line(rules: &Muncher, line: &str, inside_template: &mut bool) -> bool {
+    let last_start = last_match_position(&rules.template_start_regex, line);
+    let last_end = last_match_position(&rules.template_end_regex, line);
+    let is_template = *inside_template || last_start.is_some();
 
-    // convert the file into a collection of lines
-    for line in utf8_string.as_str().lines() {
-        lines.push(line.into());
+    match (last_start, last_end) {
+        (Some(start), Some(end)) => *inside_template = start > end,
+        (Some(_), None) => *inside_template = true,
+        (None, Some(_)) => *inside_template = false,
+        (None, None) => {}
+    }
+
+    is_template
+}
+
+/// Returns the start of the last match of any of the regex in the line.
+fn last_match_position(regex: &Option<Vec<Regex>>, line: &str) -> Option<usize> {
+    regex
+        .as_ref()?
+        .iter()
+        .filter_map(|r| r.find_iter(line).last().map(|m| m.start()))
+        .max()
+}
+
+/// Truncates the line to `max_len` bytes at the nearest char boundary.
+fn truncate_line(mut line: String, max_len: usize) -> String {
+    if line.len() > max_len {
+        let mut idx = max_len;
+        while !line.is_char_boundary(idx) {
+            idx -= 1;
+        }
+        trace!("Truncated a line of {} bytes", line.len());
+        line.truncate(idx);
     }
+    line
+}
+
+/// Returns the timeout details with the muncher rule that takes the longest to match the line the timeout happened at.
+fn timeout_reason(rules: &Muncher, line: &str, line_idx: usize, timeout_ms: u64) -> String {
+    let slowest_rule = rules
+        .line_regex()
+        .into_iter()
+        .map(|(property, regex)| {
+            let instant = Instant::now();
+            let _ = regex.is_match(line);
+            (instant.elapsed(), property, regex)
+        })
+        .max_by_key(|(elapsed, _, _)| *elapsed);
 
-    Ok(lines)
+    match slowest_rule {
+        Some((_, property, regex)) => format!(
+            "timed out after {}ms at line {}, the slowest rule is {} `{}` in munchers/{}.json",
+            timeout_ms,
+            line_idx + 1,
+            property,
+            regex,
+            rules.muncher_name
+        ),
+        None => format!("timed out after {}ms at line {}", timeout_ms, line_idx + 1),
+    }
 }
 
 /// Returns true if there is a regex and it matches the line.
@@ -214,3 +647,193 @@ fn match_line(regex: &Option<Vec<Regex>>, line: &String) -> bool {
     // no match found
     false
 }
+
+#[cfg(test)]
+mod test_processors {
+    use super::{blank_tech, munch_contents, FileFlags, LineCounter};
+    use crate::muncher::{MunchLimits, Muncher};
+    use crate::report::kwc::KeywordCounter;
+    use crate::report::Tech;
+    use std::collections::HashSet;
+
+    /// Counts the lines with the muncher and returns the tech record with tech-debt markers counted.
+    fn count_lines(muncher_file: &str, rules: &str, lines: &[&str]) -> Tech {
+        let muncher = Muncher::new(rules, &muncher_file.to_owned()).expect("Invalid muncher");
+        let mut counter = LineCounter::new(&muncher, Tech::test("Rust", "rust.rs"), true);
+        let mut flags = FileFlags {
+            is_test_code: false,
+            is_generated_code: false,
+        };
+        for (line_idx, line) in lines.iter().enumerate() {
+            counter.count_line(&line.to_string(), line_idx, false, &mut flags);
+        }
+
+        counter.tech
+    }
+
+    /// Counts the lines with the muncher and returns the number of entities and documented entities.
+    fn count_entities(muncher_file: &str, rules: &str, lines: &[&str]) -> (u64, u64) {
+        let tech = count_lines(muncher_file, rules, lines);
+        (tech.entities, tech.documented_entities)
+    }
+
+    #[test]
+    fn test_count_tech_debt_markers() {
+        let rust = include_str!("../../stm_rules/munchers/rust.rs.json");
+        let lines = [
+            "// TODO: handle errors",
+            "fn main() {",
+            "    let todo = \"TODO in a string is code\";",
+            "    run(); // FIXME: retry",
+            "    /* HACK until the next release",
+            "       XXX and a TODO on the same line */",
+            "    // todo in lower case is prose",
+            "}",
+        ];
+        let tech = count_lines("rust.rs.json", rust, &lines);
+        let mut markers = tech
+            .tech_debt_markers
+            .iter()
+            .map(|v| (v.k.as_str(), v.c))
+            .collect::<Vec<(&str, u64)>>();
+        markers.sort();
+        assert_eq!(markers, vec![("FIXME", 1), ("HACK", 1), ("TODO", 2), ("XXX", 1)]);
+    }
+
+    #[test]
+    fn test_count_entities() {
+        let rust = include_str!("../../stm_rules/munchers/rust.rs.json");
+        let lines = [
+            "/// Returns the answer to everything.",
+            "#[inline]",
+            "pub fn answer() -> u32 {",
+            "    42",
+            "}",
+            "",
+            "/// The docs of a constant are not for the function below it.",
+            "const X: u32 = 1;",
+            "async fn undocumented() {}",
+            "",
+            "/**",
+            " * A block doc comment.",
+            " */",
+            "pub(crate) struct Documented;",
+            "",
+            "#[test]",
+            "fn test_answer() {}",
+        ];
+        assert_eq!(count_entities("rust.rs.json", rust, &lines), (3, 2));
+
+        let python = include_str!("../../stm_rules/munchers/python.py.json");
+        let lines = [
+            "class Answer:",
+            "    \"\"\"Returns the answer to everything.\"\"\"",
+            "",
+            "    def get(self):",
+            "        return 42",
+            "",
+            "    async def fetch(self):",
+            "",
+            "        '''Docstrings may follow a blank line.",
+            "        '''",
+        ];
+        assert_eq!(count_entities("python.py.json", python, &lines), (3, 2));
+    }
+
+    #[test]
+    fn test_count_packages() {
+        let keys = |v: &HashSet<KeywordCounter>| {
+            let mut keys = v.iter().map(|v| v.k.clone()).collect::<Vec<String>>();
+            keys.sort();
+            keys
+        };
+
+        let go_mod = include_str!("../../stm_rules/munchers/go.mod.json");
+        let lines = [
+            "module example.com/me/project",
+            "go 1.21",
+            "require github.com/pkg/errors v0.9.1",
+            "require (",
+            "\tgolang.org/x/sync v0.3.0 // indirect",
+            ")",
+        ];
+        let tech = count_lines("go.mod.json", go_mod, &lines);
+        assert_eq!(keys(&tech.pkgs), vec!["github.com/pkg/errors", "golang.org/x/sync"]);
+
+        let pyproject = include_str!("../../stm_rules/munchers/python.pyproject.toml.json");
+        let lines = [
+            "requires-python = \">=3.8\"",
+            "dependencies = [",
+            "    \"requests>=2.28\",",
+            "    \"uvicorn[standard] >= 0.20.0; python_version > '3.8'\",",
+            "]",
+            "classifiers = [",
+            "    \"Programming Language :: Python :: 3\",",
+            "]",
+        ];
+        let tech = count_lines("python.pyproject.toml.json", pyproject, &lines);
+        assert_eq!(keys(&tech.pkgs), vec!["requests", "uvicorn"]);
+
+        // scoped npm packages keep the scope and the name
+        let js = include_str!("../../stm_rules/munchers/js.json");
+        let lines = [
+            "import { Component } from '@angular/core';",
+            "import fp from 'lodash/fp';",
+            "const x = require(\"@babel/core/lib\");",
+        ];
+        let tech = count_lines("js.json", js, &lines);
+        assert_eq!(keys(&tech.refs), vec!["@angular/core", "@babel/core", "lodash"]);
+    }
+
+    #[test]
+    fn test_sampling() {
+        let rust = include_str!("../../stm_rules/munchers/rust.rs.json");
+        let muncher = Muncher::new(rust, &"rust.rs.json".to_owned()).expect("Invalid muncher");
+        let file_name = "src/big.rs".to_owned();
+        let limits = MunchLimits {
+            sample_file_size: 10_000,
+            sample_size: 1_000,
+            ..MunchLimits::default()
+        };
+        let munch = |contents: &str, limits: &MunchLimits| {
+            munch_contents(
+                &file_name,
+                contents.as_bytes(),
+                &muncher,
+                &[],
+                limits,
+                blank_tech(&muncher, &file_name),
+                None,
+            )
+            .unwrap()
+            .remove(0)
+        };
+
+        // 2000 lines of 20 bytes with a comment every 4th line
+        let contents = (0..2000)
+            .map(|i| {
+                if i % 4 == 0 {
+                    "// a comment line  \n"
+                } else {
+                    "let x = 1234567890;\n"
+                }
+            })
+            .collect::<String>();
+        let tech = munch(&contents, &limits);
+        assert!(tech.sampled);
+        assert_eq!(tech.total_lines, 2000);
+        assert_eq!(tech.line_comments, 500);
+        assert_eq!(tech.code_lines, 1500);
+
+        // small files and files with short lines are munched in full
+        let tech = munch(&contents[..8000], &limits);
+        assert!(!tech.sampled);
+        assert_eq!(tech.total_lines, 400);
+
+        // a few very long lines are munched in full because the samples would overlap
+        let contents = ["let x = 1;", &"1".repeat(20_000), "let y = 2;"].join("\n");
+        let tech = munch(&contents, &limits);
+        assert!(!tech.sampled);
+        assert_eq!(tech.total_lines, 3);
+    }
+}
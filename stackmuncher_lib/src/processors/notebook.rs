@@ -0,0 +1,134 @@
+use crate::report::Tech;
+use serde_json::Value;
+
+/// The parts of a Jupyter notebook that are counted. Outputs and attachments are ignored because they are generated.
+/// Only nbformat 4 is supported. Older notebooks have their cells inside `worksheets`.
+pub(crate) struct Notebook {
+    /// The kernel language in lower case from `metadata.kernelspec.language` or `metadata.language_info.name`, e.g. `python`.
+    pub kernel_language: Option<String>,
+    pub cells: Vec<NotebookCell>,
+}
+
+/// A single cell with its source split into lines.
+pub(crate) struct NotebookCell {
+    pub cell_type: CellType,
+    pub lines: Vec<String>,
+}
+
+#[derive(PartialEq, Debug)]
+pub(crate) enum CellType {
+    Code,
+    Markdown,
+    /// Raw cells are passed to the output as-is and are not counted.
+    Raw,
+}
+
+impl Notebook {
+    /// Returns None if the contents are not a valid JSON notebook with a list of cells.
+    pub(crate) fn parse(contents: &str) -> Option<Self> {
+        let notebook = serde_json::from_str::<Value>(contents).ok()?;
+
+        let metadata = notebook.get("metadata");
+        let kernel_language = metadata
+            .and_then(|v| v.pointer("/kernelspec/language"))
+            .or_else(|| metadata.and_then(|v| v.pointer("/language_info/name")))
+            .and_then(|v| v.as_str())
+            .map(|v| v.trim().to_lowercase())
+            .filter(|v| !v.is_empty());
+
+        let cells = notebook
+            .get("cells")?
+            .as_array()?
+            .iter()
+            .filter_map(|cell| {
+                let cell_type = match cell.get("cell_type")?.as_str()? {
+                    "code" => CellType::Code,
+                    "markdown" => CellType::Markdown,
+                    _ => CellType::Raw,
+                };
+
+                // the source is either a single string or a list of lines with their line endings
+                let source = match cell.get("source")? {
+                    Value::String(v) => v.clone(),
+                    Value::Array(v) => v.iter().filter_map(|line| line.as_str()).collect::<String>(),
+                    _ => return None,
+                };
+                let lines = source.lines().map(|line| line.to_owned()).collect::<Vec<String>>();
+
+                Some(NotebookCell { cell_type, lines })
+            })
+            .collect::<Vec<NotebookCell>>();
+
+        Some(Self { kernel_language, cells })
+    }
+
+    /// Returns the lines of all code cells in the order they appear in the notebook.
+    pub(crate) fn code_lines(&self) -> Vec<String> {
+        self.cells
+            .iter()
+            .filter(|cell| cell.cell_type == CellType::Code)
+            .flat_map(|cell| cell.lines.iter().cloned())
+            .collect()
+    }
+
+    /// Adds the number of code and markdown cells to the notebook record. Markdown lines are counted as docs.
+    /// Code lines are counted separately with the rules of the kernel language.
+    pub(crate) fn count_cells(&self, tech: &mut Tech) {
+        for cell in &self.cells {
+            match cell.cell_type {
+                CellType::Code => tech.code_cells += 1,
+                CellType::Markdown => {
+                    tech.markdown_cells += 1;
+                    for line in &cell.lines {
+                        tech.total_lines += 1;
+                        if line.trim().is_empty() {
+                            tech.blank_lines += 1;
+                        } else {
+                            tech.docs_comments += 1;
+                        }
+                    }
+                }
+                CellType::Raw => {}
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test_notebook {
+    use super::{CellType, Notebook};
+
+    #[test]
+    fn test_parse() {
+        let notebook = Notebook::parse(
+            r##"{
+ "cells": [
+  {"cell_type": "markdown", "metadata": {}, "source": ["# Analysis\n", "\n", "Loads the data."]},
+  {"cell_type": "code", "execution_count": 1, "metadata": {}, "outputs": [{"output_type": "stream", "text": ["ok\n"]}],
+   "source": ["import pandas as pd\n", "df = pd.read_csv(\"data.csv\")"]},
+  {"cell_type": "raw", "metadata": {}, "source": "raw text"},
+  {"cell_type": "code", "execution_count": null, "metadata": {}, "outputs": [], "source": "df.head()"}
+ ],
+ "metadata": {"kernelspec": {"display_name": "Python 3", "language": "python", "name": "python3"}},
+ "nbformat": 4,
+ "nbformat_minor": 5
+}"##,
+        )
+        .unwrap();
+
+        assert_eq!(notebook.kernel_language, Some("python".to_owned()));
+        assert_eq!(notebook.cells.len(), 4);
+        assert_eq!(notebook.cells[2].cell_type, CellType::Raw);
+        assert_eq!(
+            notebook.code_lines(),
+            vec!["import pandas as pd", "df = pd.read_csv(\"data.csv\")", "df.head()"]
+        );
+
+        // the language comes from language_info if there is no kernelspec
+        let notebook = Notebook::parse(r#"{"cells": [], "metadata": {"language_info": {"name": "R"}}}"#).unwrap();
+        assert_eq!(notebook.kernel_language, Some("r".to_owned()));
+
+        assert!(Notebook::parse(r#"{"worksheets": []}"#).is_none());
+        assert!(Notebook::parse("not a notebook").is_none());
+    }
+}
@@ -0,0 +1,226 @@
+use super::{blank_tech, munch_contents, MunchError};
+use crate::muncher::{MunchLimits, Muncher};
+use crate::report::kwc::KeywordCounter;
+use crate::report::Tech;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+
+/// Expected counts for a sample file munched by a single muncher, kept next to the sample as a sidecar file,
+/// e.g. `main.rs` -> `main.rs.expected.json`. Only the counts present in the file are checked, so a sample can
+/// focus on a single rule, e.g. `{"docs_comments": 3}`. Keyword-like lists are checked as a whole:
+/// any missing, extra or miscounted item is a mismatch.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct SampleExpectations {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub total_lines: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub blank_lines: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bracket_only_lines: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub code_lines: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub inline_comments: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub line_comments: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub block_comments: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub docs_comments: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub test_loc: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub generated_loc: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub entities: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub documented_entities: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub code_cells: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub markdown_cells: Option<u64>,
+    /// Keyword -> count, e.g. `{"fn": 2, "impl": 1}`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub keywords: Option<BTreeMap<String, u64>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub refs: Option<BTreeMap<String, u64>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pkgs: Option<BTreeMap<String, u64>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub interop: Option<BTreeMap<String, u64>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub language_version: Option<BTreeMap<String, u64>>,
+}
+
+/// A count that differs from the expected value.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SampleMismatch {
+    /// The name of the count as in `SampleExpectations`, e.g. `code_lines` or `keywords.fn`
+    pub field: String,
+    /// `-` if the item was not expected at all, e.g. an extra keyword
+    pub expected: String,
+    /// `-` if the item was not found at all, e.g. a missing keyword
+    pub actual: String,
+}
+
+impl SampleExpectations {
+    /// The suffix added to the name of the sample file for its sidecar file, e.g. `main.rs.expected.json`.
+    pub const SIDECAR_FILE_SUFFIX: &'static str = ".expected.json";
+
+    /// Returns the expectations with all the counts of `tech` for starting a new sidecar file from the actual results.
+    pub fn from_tech(tech: &Tech) -> Self {
+        Self {
+            total_lines: Some(tech.total_lines),
+            blank_lines: Some(tech.blank_lines),
+            bracket_only_lines: Some(tech.bracket_only_lines),
+            code_lines: Some(tech.code_lines),
+            inline_comments: Some(tech.inline_comments),
+            line_comments: Some(tech.line_comments),
+            block_comments: Some(tech.block_comments),
+            docs_comments: Some(tech.docs_comments),
+            test_loc: Some(tech.test_loc),
+            generated_loc: Some(tech.generated_loc),
+            entities: Some(tech.entities),
+            documented_entities: Some(tech.documented_entities),
+            code_cells: Some(tech.code_cells),
+            markdown_cells: Some(tech.markdown_cells),
+            keywords: Some(to_count_map(&tech.keywords)),
+            refs: Some(to_count_map(&tech.refs)),
+            pkgs: Some(to_count_map(&tech.pkgs)),
+            interop: Some(to_count_map(&tech.interop)),
+            language_version: Some(to_count_map(&tech.language_version)),
+        }
+    }
+
+    /// Returns the counts of `tech` that differ from the expected ones, if any.
+    pub fn compare(&self, tech: &Tech) -> Vec<SampleMismatch> {
+        let mut mismatches = Vec::new();
+
+        let counts = [
+            ("total_lines", self.total_lines, tech.total_lines),
+            ("blank_lines", self.blank_lines, tech.blank_lines),
+            ("bracket_only_lines", self.bracket_only_lines, tech.bracket_only_lines),
+            ("code_lines", self.code_lines, tech.code_lines),
+            ("inline_comments", self.inline_comments, tech.inline_comments),
+            ("line_comments", self.line_comments, tech.line_comments),
+            ("block_comments", self.block_comments, tech.block_comments),
+            ("docs_comments", self.docs_comments, tech.docs_comments),
+            ("test_loc", self.test_loc, tech.test_loc),
+            ("generated_loc", self.generated_loc, tech.generated_loc),
+            ("entities", self.entities, tech.entities),
+            ("documented_entities", self.documented_entities, tech.documented_entities),
+            ("code_cells", self.code_cells, tech.code_cells),
+            ("markdown_cells", self.markdown_cells, tech.markdown_cells),
+        ];
+        for (field, expected, actual) in counts {
+            if let Some(expected) = expected.filter(|v| *v != actual) {
+                mismatches.push(SampleMismatch {
+                    field: field.to_owned(),
+                    expected: expected.to_string(),
+                    actual: actual.to_string(),
+                });
+            }
+        }
+
+        let lists = [
+            ("keywords", &self.keywords, &tech.keywords),
+            ("refs", &self.refs, &tech.refs),
+            ("pkgs", &self.pkgs, &tech.pkgs),
+            ("interop", &self.interop, &tech.interop),
+            ("language_version", &self.language_version, &tech.language_version),
+        ];
+        for (field, expected, actual) in lists {
+            if let Some(expected) = expected {
+                compare_count_maps(field, expected, &to_count_map(actual), &mut mismatches);
+            }
+        }
+
+        mismatches
+    }
+}
+
+/// Munches the contents of a sample file with `rules` as if it was a file in a repo with no commit details.
+/// Template and notebook munchers need their host munchers in `host_rules`, see `CodeRules::load_muncher_with_hosts`.
+/// The first record in the result is always for `rules`.
+pub fn munch_sample(
+    file_name: &str,
+    contents: &[u8],
+    rules: &Muncher,
+    host_rules: &[&Muncher],
+    limits: &MunchLimits,
+) -> Result<Vec<Tech>, MunchError> {
+    // a sample has no repo tree to match local imports against
+    let no_tree_files = HashSet::new();
+    munch_contents(
+        file_name,
+        contents,
+        rules,
+        host_rules,
+        limits,
+        blank_tech(rules, file_name),
+        Some(&no_tree_files),
+    )
+}
+
+/// Converts a keyword-like list into keyword -> count.
+fn to_count_map(counters: &HashSet<KeywordCounter>) -> BTreeMap<String, u64> {
+    counters.iter().map(|v| (v.k.clone(), v.c)).collect()
+}
+
+/// Adds a mismatch for every missing, extra or miscounted item, e.g. `keywords.fn`.
+fn compare_count_maps(
+    field: &str,
+    expected: &BTreeMap<String, u64>,
+    actual: &BTreeMap<String, u64>,
+    mismatches: &mut Vec<SampleMismatch>,
+) {
+    let mut keys = expected.keys().chain(actual.keys()).collect::<Vec<&String>>();
+    keys.sort_unstable();
+    keys.dedup();
+
+    for key in keys {
+        let (expected, actual) = (expected.get(key), actual.get(key));
+        if expected != actual {
+            mismatches.push(SampleMismatch {
+                field: [field, ".", key].concat(),
+                expected: expected.map(|v| v.to_string()).unwrap_or_else(|| "-".to_owned()),
+                actual: actual.map(|v| v.to_string()).unwrap_or_else(|| "-".to_owned()),
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod test_sample {
+    use super::{munch_sample, SampleExpectations, SampleMismatch};
+    use crate::muncher::{MunchLimits, Muncher};
+
+    #[test]
+    fn test_compare() {
+        let rust = Muncher::new(include_str!("../../stm_rules/munchers/rust.rs.json"), &"rust.rs".to_owned()).unwrap();
+        let contents = "// The answer\nfn answer() -> u32 {\n    42\n}\n";
+        let techs = munch_sample("main.rs", contents.as_bytes(), &rust, &[], &MunchLimits::default()).unwrap();
+
+        // the actual counts always match themselves
+        let all_counts = SampleExpectations::from_tech(&techs[0]);
+        assert!(all_counts.compare(&techs[0]).is_empty());
+
+        let expectations: SampleExpectations =
+            serde_json::from_str(r#"{"line_comments": 2, "keywords": {"fn": 1, "impl": 1}}"#).unwrap();
+        assert_eq!(
+            expectations.compare(&techs[0]),
+            vec![
+                SampleMismatch {
+                    field: "line_comments".to_owned(),
+                    expected: "2".to_owned(),
+                    actual: "1".to_owned(),
+                },
+                SampleMismatch {
+                    field: "keywords.impl".to_owned(),
+                    expected: "1".to_owned(),
+                    actual: "-".to_owned(),
+                },
+            ]
+        );
+    }
+}
@@ -0,0 +1,190 @@
+use crate::code_rules::CodeRules;
+use crate::utils::hash_str_sha1;
+use regex::Regex;
+use serde::Deserialize;
+use std::path::Path;
+use tracing::{debug, error, info};
+
+/// The name of the file in the root of the repo with per-project settings checked in by the team.
+pub const PROJECT_CONFIG_FILE_NAME: &str = ".stackmuncher.toml";
+
+/// Per-project settings from `.stackmuncher.toml` in the root of the repo. They override the user settings
+/// from config.json and are overridden by CLI params. All members are optional, e.g.
+/// ```toml
+/// ignore_paths = ["^third_party/", "\\.pb\\.go$"]
+/// disabled_munchers = ["markdown"]
+/// attribution = "raw"
+/// max_file_size = 1048576
+/// duplicates = "all"
+/// tech_debt_markers = true
+/// hooks = ["scripts/notify.sh"]
+/// ```
+#[derive(Deserialize, Debug, Default, Clone, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct ProjectConfig {
+    /// Regex for file paths to ignore on top of the built-in list, same as `CodeRules.ignore_paths`.
+    pub ignore_paths: Vec<String>,
+    /// Names of munchers that should not be used for this project, e.g. `markdown`. Files matched to them are
+    /// not analyzed.
+    pub disabled_munchers: Vec<String>,
+    /// How commits are attributed to contributors.
+    pub attribution: Attribution,
+    /// Overrides `MunchLimits.max_file_size` from config.json.
+    pub max_file_size: Option<u64>,
+    /// How files with identical contents under several paths are counted in the tech totals.
+    pub duplicates: Duplicates,
+    /// Overrides `MunchLimits.tech_debt_markers` from config.json.
+    pub tech_debt_markers: Option<bool>,
+    /// Executables run after each analysis, relative to the root of the repo, e.g. `scripts/notify.sh`.
+    /// The app runs them only if the user allowed project hooks in config.json. They do not affect the reports.
+    pub hooks: Vec<String>,
+}
+
+/// How commit identities are mapped to contributors.
+#[derive(Deserialize, Debug, Default, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Attribution {
+    /// Merge identities with `.mailmap` from the root of the repo. The default.
+    #[default]
+    Mailmap,
+    /// Use the identities as they are in the commits and ignore `.mailmap`. User aliases still apply.
+    Raw,
+}
+
+/// How files with identical contents under several paths, e.g. vendored libraries, are counted.
+#[derive(Deserialize, Debug, Default, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Duplicates {
+    /// Count only one copy in the tech totals. The default.
+    #[default]
+    Once,
+    /// Count every copy as a separate file.
+    All,
+}
+
+impl ProjectConfig {
+    /// Reads `.stackmuncher.toml` from the root of the project. Returns the defaults if there is no such file
+    /// and an error if the file is invalid because the team expects their settings to apply.
+    pub(crate) fn from_repo(project_dir: &Path) -> Result<Self, ()> {
+        let config_file = project_dir.join(PROJECT_CONFIG_FILE_NAME);
+        let contents = match std::fs::read_to_string(&config_file) {
+            Ok(v) => v,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Self::default()),
+            Err(e) => {
+                error!("Cannot read {} due to {}", config_file.to_string_lossy(), e);
+                return Err(());
+            }
+        };
+
+        match Self::parse(&contents) {
+            Ok(v) => {
+                info!("Loaded {}: {:?}", config_file.to_string_lossy(), v);
+                Ok(v)
+            }
+            Err(e) => {
+                error!("Invalid {}: {}", config_file.to_string_lossy(), e);
+                Err(())
+            }
+        }
+    }
+
+    /// Parses the contents of `.stackmuncher.toml` and validates the regex. Returns a user-friendly error message.
+    pub fn parse(contents: &str) -> Result<Self, String> {
+        let project_config = toml::from_str::<Self>(contents).map_err(|e| e.to_string())?;
+
+        for ignore_path in &project_config.ignore_paths {
+            if let Err(e) = Regex::new(ignore_path) {
+                return Err(format!("invalid regex in ignore_paths: {}", e));
+            }
+        }
+
+        Ok(project_config)
+    }
+
+    /// Adds the ignore paths and disabled munchers to `code_rules` and overrides its limits.
+    /// Applying the same config more than once has no effect.
+    pub fn apply(&self, code_rules: &mut CodeRules) {
+        self.extend_ignore_paths(&mut code_rules.ignore_paths);
+        code_rules
+            .disabled_munchers
+            .extend(self.disabled_munchers.iter().cloned());
+        if let Some(max_file_size) = self.max_file_size {
+            code_rules.munch_limits.max_file_size = max_file_size;
+        }
+        code_rules.duplicates = self.duplicates;
+        if let Some(tech_debt_markers) = self.tech_debt_markers {
+            code_rules.munch_limits.tech_debt_markers = tech_debt_markers;
+        }
+        debug!("Applied project config to code rules");
+    }
+
+    /// Adds the ignore paths of the project to `ignore_paths`, skipping those already in the list.
+    pub(crate) fn extend_ignore_paths(&self, ignore_paths: &mut Vec<Regex>) {
+        for ignore_path in &self.ignore_paths {
+            if ignore_paths.iter().any(|v| v.as_str() == ignore_path) {
+                continue;
+            }
+            // the regex were validated by parse()
+            if let Ok(v) = Regex::new(ignore_path) {
+                ignore_paths.push(v);
+            }
+        }
+    }
+
+    /// A hash of the settings to detect changes that require reprocessing without any new commits.
+    /// Returns None for the defaults so that reports of projects without the file are not affected.
+    /// `hooks` are left out because they run after the analysis.
+    pub fn hash(&self) -> Option<String> {
+        let analysis_settings = Self {
+            hooks: Vec::new(),
+            ..self.clone()
+        };
+        if analysis_settings == Self::default() {
+            None
+        } else {
+            Some(hash_str_sha1(&format!("{:?}", analysis_settings)))
+        }
+    }
+}
+
+#[cfg(test)]
+mod test_project_config {
+    use super::{Attribution, Duplicates, ProjectConfig};
+
+    #[test]
+    fn test_parse() {
+        let project_config = ProjectConfig::parse(
+            r#"
+ignore_paths = ["^third_party/"]
+disabled_munchers = ["markdown"]
+attribution = "raw"
+max_file_size = 1000
+duplicates = "all"
+tech_debt_markers = true
+"#,
+        )
+        .unwrap();
+        assert_eq!(project_config.ignore_paths, vec!["^third_party/".to_owned()]);
+        assert_eq!(project_config.disabled_munchers, vec!["markdown".to_owned()]);
+        assert_eq!(project_config.attribution, Attribution::Raw);
+        assert_eq!(project_config.max_file_size, Some(1000));
+        assert_eq!(project_config.duplicates, Duplicates::All);
+        assert_eq!(project_config.tech_debt_markers, Some(true));
+        assert!(project_config.hash().is_some());
+
+        // hooks do not change the reports
+        let hooks_only = ProjectConfig::parse("hooks = [\"scripts/notify.sh\"]").unwrap();
+        assert_eq!(hooks_only.hooks, vec!["scripts/notify.sh".to_owned()]);
+        assert!(hooks_only.hash().is_none());
+
+        // an empty file is the same as no file
+        let project_config = ProjectConfig::parse("").unwrap();
+        assert_eq!(project_config, ProjectConfig::default());
+        assert!(project_config.hash().is_none());
+
+        // typos are errors rather than silently ignored settings
+        assert!(ProjectConfig::parse("ignore_path = [\"^docs/\"]").is_err());
+        assert!(ProjectConfig::parse("attribution = \"committer\"").is_err());
+        assert!(ProjectConfig::parse("ignore_paths = [\"(\"]").is_err());
+    }
+}
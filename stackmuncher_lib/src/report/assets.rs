@@ -0,0 +1,284 @@
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+
+/// Number of bytes read from the start of the file to detect its type.
+const HEAD_LEN: usize = 512;
+
+/// Git LFS replaces large files with a text pointer that has the real size in it.
+const LFS_POINTER_PREFIX: &[u8] = b"version https://git-lfs.github.com/spec/v1";
+
+/// Lower case file extensions per asset category.
+const IMAGE_EXTENSIONS: [&str; 19] = [
+    "png", "jpg", "jpeg", "gif", "bmp", "ico", "icns", "webp", "tif", "tiff", "psd", "xcf", "heic", "avif", "tga",
+    "dds", "exr", "hdr", "svg",
+];
+const FONT_EXTENSIONS: [&str; 6] = ["ttf", "otf", "woff", "woff2", "eot", "fon"];
+const AUDIO_EXTENSIONS: [&str; 11] = [
+    "mp3", "wav", "ogg", "flac", "aac", "m4a", "wma", "aiff", "mid", "midi", "opus",
+];
+const VIDEO_EXTENSIONS: [&str; 10] = ["mp4", "mov", "avi", "mkv", "webm", "wmv", "flv", "m4v", "mpg", "mpeg"];
+const ARCHIVE_EXTENSIONS: [&str; 15] = [
+    "zip", "gz", "tgz", "bz2", "xz", "7z", "rar", "tar", "zst", "lz4", "jar", "war", "apk", "nupkg", "whl",
+];
+const ML_MODEL_EXTENSIONS: [&str; 17] = [
+    "h5",
+    "hdf5",
+    "pt",
+    "pth",
+    "ckpt",
+    "onnx",
+    "pb",
+    "tflite",
+    "safetensors",
+    "pkl",
+    "joblib",
+    "npy",
+    "npz",
+    "gguf",
+    "ggml",
+    "mlmodel",
+    "caffemodel",
+];
+
+/// Asset categories a binary file can fall into.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum AssetCategory {
+    Image,
+    Font,
+    Audio,
+    Video,
+    Archive,
+    MlModel,
+    OtherBinary,
+}
+
+/// Binary and media files in the project tree at HEAD, including the ones skipped by `ignore_paths`.
+/// They are invisible in the tech section, but make up the bulk of game, design and data-science repos.
+/// Only counts are stored, no file names. Updated when the project is reprocessed.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, Eq, PartialEq)]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
+pub struct Assets {
+    #[serde(skip_serializing_if = "AssetCount::is_empty", default)]
+    pub images: AssetCount,
+    #[serde(skip_serializing_if = "AssetCount::is_empty", default)]
+    pub fonts: AssetCount,
+    #[serde(skip_serializing_if = "AssetCount::is_empty", default)]
+    pub audio: AssetCount,
+    #[serde(skip_serializing_if = "AssetCount::is_empty", default)]
+    pub video: AssetCount,
+    #[serde(skip_serializing_if = "AssetCount::is_empty", default)]
+    pub archives: AssetCount,
+    /// Model weights and data arrays, e.g. `.onnx`, `.safetensors`, `.h5`, `.npy`
+    #[serde(skip_serializing_if = "AssetCount::is_empty", default)]
+    pub ml_models: AssetCount,
+    /// Files with binary contents that did not match any other category
+    #[serde(skip_serializing_if = "AssetCount::is_empty", default)]
+    pub other_binary: AssetCount,
+}
+
+/// Number of files and their total size for an asset category.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, Eq, PartialEq)]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
+pub struct AssetCount {
+    pub files: u64,
+    /// Size of files in the working directory. Files stored in Git LFS are counted at their real size.
+    pub bytes: u64,
+}
+
+impl AssetCount {
+    /// Returns true if no files were counted. Used by serde to skip empty categories.
+    pub fn is_empty(&self) -> bool {
+        self.files == 0
+    }
+}
+
+impl Assets {
+    /// Categorizes `file_names` by their extension, falling back on the first few bytes of the file in `project_dir`.
+    /// Text files are not counted. Files missing from the working directory are counted by extension with zero size.
+    /// Returns None if no assets were found.
+    pub(crate) fn from_files<'a>(project_dir: &Path, file_names: impl Iterator<Item = &'a String>) -> Option<Self> {
+        let mut assets = Self::default();
+
+        for file_name in file_names {
+            let full_path = project_dir.join(file_name);
+            let bytes = std::fs::metadata(&full_path).map(|m| m.len()).unwrap_or_default();
+            let head = read_head(&full_path).unwrap_or_default();
+
+            // LFS pointers are text, so the category can only come from the extension
+            let (category, bytes) = match lfs_pointer_size(&head) {
+                Some(lfs_size) => (category_by_extension(file_name), lfs_size),
+                None => (category_by_extension(file_name).or_else(|| category_by_contents(&head)), bytes),
+            };
+
+            if let Some(category) = category {
+                let count = assets.count_mut(category);
+                count.files += 1;
+                count.bytes += bytes;
+            }
+        }
+
+        if assets == Self::default() {
+            None
+        } else {
+            Some(assets)
+        }
+    }
+
+    /// Returns the counter for the category.
+    fn count_mut(&mut self, category: AssetCategory) -> &mut AssetCount {
+        match category {
+            AssetCategory::Image => &mut self.images,
+            AssetCategory::Font => &mut self.fonts,
+            AssetCategory::Audio => &mut self.audio,
+            AssetCategory::Video => &mut self.video,
+            AssetCategory::Archive => &mut self.archives,
+            AssetCategory::MlModel => &mut self.ml_models,
+            AssetCategory::OtherBinary => &mut self.other_binary,
+        }
+    }
+}
+
+/// Returns up to `HEAD_LEN` bytes from the start of the file.
+fn read_head(full_path: &Path) -> Option<Vec<u8>> {
+    let mut head = Vec::with_capacity(HEAD_LEN);
+    File::open(full_path)
+        .ok()?
+        .take(HEAD_LEN as u64)
+        .read_to_end(&mut head)
+        .ok()?;
+    Some(head)
+}
+
+/// Returns the size of the real file if `head` is a Git LFS pointer, e.g. `size 12345` line.
+fn lfs_pointer_size(head: &[u8]) -> Option<u64> {
+    if !head.starts_with(LFS_POINTER_PREFIX) {
+        return None;
+    }
+
+    String::from_utf8_lossy(head).lines().find_map(|line| {
+        line.strip_prefix("size ")
+            .and_then(|size| size.trim().parse::<u64>().ok())
+    })
+}
+
+/// Returns the asset category based on the file extension, if it is a known one.
+fn category_by_extension(file_name: &str) -> Option<AssetCategory> {
+    let base_name = file_name.rsplit('/').next().unwrap_or(file_name);
+    let ext = base_name.rsplit_once('.')?.1.to_lowercase();
+    let ext = ext.as_str();
+
+    if IMAGE_EXTENSIONS.contains(&ext) {
+        Some(AssetCategory::Image)
+    } else if FONT_EXTENSIONS.contains(&ext) {
+        Some(AssetCategory::Font)
+    } else if AUDIO_EXTENSIONS.contains(&ext) {
+        Some(AssetCategory::Audio)
+    } else if VIDEO_EXTENSIONS.contains(&ext) {
+        Some(AssetCategory::Video)
+    } else if ARCHIVE_EXTENSIONS.contains(&ext) {
+        Some(AssetCategory::Archive)
+    } else if ML_MODEL_EXTENSIONS.contains(&ext) {
+        Some(AssetCategory::MlModel)
+    } else {
+        None
+    }
+}
+
+/// Returns the asset category based on the magic number at the start of the file. Files with a NUL byte
+/// and no known magic number are `OtherBinary`. Returns None for text and empty files.
+fn category_by_contents(head: &[u8]) -> Option<AssetCategory> {
+    let category = if head.starts_with(b"\x89PNG\r\n\x1a\n")
+        || head.starts_with(b"\xff\xd8\xff")
+        || head.starts_with(b"GIF87a")
+        || head.starts_with(b"GIF89a")
+        || (head.starts_with(b"RIFF") && head.get(8..12) == Some(b"WEBP"))
+    {
+        AssetCategory::Image
+    } else if head.starts_with(b"wOFF")
+        || head.starts_with(b"wOF2")
+        || head.starts_with(b"OTTO")
+        || head.starts_with(b"\x00\x01\x00\x00\x00")
+    {
+        AssetCategory::Font
+    } else if head.starts_with(b"ID3")
+        || head.starts_with(b"fLaC")
+        || head.starts_with(b"OggS")
+        || (head.starts_with(b"RIFF") && head.get(8..12) == Some(b"WAVE"))
+    {
+        AssetCategory::Audio
+    } else if head.get(4..8) == Some(b"ftyp")
+        || head.starts_with(b"\x1a\x45\xdf\xa3")
+        || (head.starts_with(b"RIFF") && head.get(8..12) == Some(b"AVI "))
+    {
+        AssetCategory::Video
+    } else if head.starts_with(b"PK\x03\x04")
+        || head.starts_with(b"\x1f\x8b")
+        || head.starts_with(b"7z\xbc\xaf\x27\x1c")
+        || head.starts_with(b"Rar!\x1a\x07")
+        || head.starts_with(b"BZh")
+        || head.starts_with(b"\xfd7zXZ\x00")
+        || head.starts_with(b"\x28\xb5\x2f\xfd")
+    {
+        AssetCategory::Archive
+    } else if head.starts_with(b"\x89HDF\r\n\x1a\n") || head.starts_with(b"GGUF") || head.starts_with(b"\x93NUMPY") {
+        AssetCategory::MlModel
+    } else if head.contains(&0) {
+        AssetCategory::OtherBinary
+    } else {
+        return None;
+    };
+
+    Some(category)
+}
+
+#[cfg(test)]
+mod test_assets {
+    use super::{category_by_contents, category_by_extension, lfs_pointer_size, AssetCategory, Assets};
+
+    #[test]
+    fn test_categories() {
+        assert_eq!(category_by_extension("art/Hero.PNG"), Some(AssetCategory::Image));
+        assert_eq!(category_by_extension("fonts/inter.woff2"), Some(AssetCategory::Font));
+        assert_eq!(category_by_extension("models/bert.safetensors"), Some(AssetCategory::MlModel));
+        assert_eq!(category_by_extension("dist.tar.gz"), Some(AssetCategory::Archive));
+        assert_eq!(category_by_extension("Makefile"), None);
+        assert_eq!(category_by_extension(".png/readme"), None);
+
+        assert_eq!(category_by_contents(b"\x89PNG\r\n\x1a\n\x00\x00"), Some(AssetCategory::Image));
+        assert_eq!(category_by_contents(b"PK\x03\x04\x14\x00"), Some(AssetCategory::Archive));
+        assert_eq!(category_by_contents(b"\x00\x00\x00\x18ftypmp42"), Some(AssetCategory::Video));
+        assert_eq!(category_by_contents(b"\x89HDF\r\n\x1a\n"), Some(AssetCategory::MlModel));
+        assert_eq!(category_by_contents(b"\x7fELF\x02\x01\x01\x00"), Some(AssetCategory::OtherBinary));
+        assert_eq!(category_by_contents(b"fn main() {}\n"), None);
+        assert_eq!(category_by_contents(b""), None);
+
+        let pointer = b"version https://git-lfs.github.com/spec/v1\noid sha256:4d7a21\nsize 132735\n";
+        assert_eq!(lfs_pointer_size(pointer), Some(132735));
+        assert_eq!(lfs_pointer_size(b"size 10\n"), None);
+    }
+
+    #[test]
+    fn test_from_files() {
+        let project_dir = std::env::temp_dir().join(format!("stm_assets_test_{}", std::process::id()));
+        std::fs::create_dir_all(&project_dir).unwrap();
+        std::fs::write(project_dir.join("logo.png"), b"\x89PNG\r\n\x1a\n0123456789").unwrap();
+        std::fs::write(project_dir.join("weights"), b"\x89HDF\r\n\x1a\n01").unwrap();
+        std::fs::write(project_dir.join("notes.txt"), b"plain text").unwrap();
+        let file_names = ["logo.png", "weights", "notes.txt", "missing.ttf"]
+            .iter()
+            .map(|v| v.to_string())
+            .collect::<Vec<String>>();
+
+        let assets = Assets::from_files(&project_dir, file_names.iter()).unwrap();
+        assert_eq!((assets.images.files, assets.images.bytes), (1, 18));
+        assert_eq!((assets.ml_models.files, assets.ml_models.bytes), (1, 10));
+        assert_eq!((assets.fonts.files, assets.fonts.bytes), (1, 0));
+        assert!(assets.other_binary.is_empty());
+
+        assert!(Assets::from_files(&project_dir, file_names[2..3].iter()).is_none());
+
+        let _ = std::fs::remove_dir_all(&project_dir);
+    }
+}
@@ -0,0 +1,213 @@
+use crate::git::GitLogEntry;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeSet, HashMap};
+
+/// The module name for files in the root of the project.
+const ROOT_MODULE: &str = ".";
+
+/// Limits that keep the co-change calculation fast on huge histories. Can be changed in `co_change` section of config.json.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(default)]
+pub struct CoChangeLimits {
+    /// Only this many most recent commits are analyzed.
+    pub max_commits: usize,
+    /// Commits touching more files are skipped as bulk changes, e.g. reformatting or renaming a namespace.
+    /// The number of pairs grows quadratically with the number of files in a commit.
+    pub max_files_per_commit: usize,
+    /// Pairs that changed together fewer times are ignored as a coincidence.
+    pub min_shared_commits: u64,
+    /// The max number of file pairs and module pairs in the report.
+    pub top_pairs: usize,
+}
+
+impl Default for CoChangeLimits {
+    fn default() -> Self {
+        Self {
+            max_commits: 10000,
+            max_files_per_commit: 30,
+            min_shared_commits: 3,
+            top_pairs: 20,
+        }
+    }
+}
+
+/// Files and modules (folders) that are frequently changed in the same commit. It points at hidden coupling
+/// that is not visible from imports, e.g. a handler and its config. Only present in project reports
+/// because file names are sensitive.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
+pub struct CoChange {
+    /// Number of commits included in the calculation.
+    pub commits_analyzed: u64,
+    /// Number of commits skipped for touching more than `CoChangeLimits.max_files_per_commit` files.
+    pub bulk_commits_skipped: u64,
+    /// The most coupled file pairs, the strongest first.
+    #[serde(skip_serializing_if = "Vec::is_empty", default)]
+    pub file_pairs: Vec<CoChangePair>,
+    /// The most coupled pairs of folders, the strongest first. A commit is counted once per pair of folders.
+    #[serde(skip_serializing_if = "Vec::is_empty", default)]
+    pub module_pairs: Vec<CoChangePair>,
+}
+
+/// Two files or modules that changed together. `a` is always less than `b` alphabetically.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
+pub struct CoChangePair {
+    pub a: String,
+    pub b: String,
+    /// Number of commits that changed both.
+    pub shared_commits: u64,
+    /// `shared_commits` as a percentage of the average number of commits that changed either of them, 0..100.
+    pub degree: u64,
+}
+
+impl CoChange {
+    /// Calculates co-change coupling from the log, newest commits first. Returns None if there are no commits to analyze.
+    pub fn from_log(git_log: &[GitLogEntry], limits: &CoChangeLimits) -> Option<Self> {
+        let mut commits_analyzed = 0u64;
+        let mut bulk_commits_skipped = 0u64;
+        let mut file_changes: HashMap<&str, u64> = HashMap::new();
+        let mut file_pairs: HashMap<(&str, &str), u64> = HashMap::new();
+        let mut module_changes: HashMap<&str, u64> = HashMap::new();
+        let mut module_pairs: HashMap<(&str, &str), u64> = HashMap::new();
+
+        for log_entry in git_log.iter().take(limits.max_commits) {
+            if log_entry.files.is_empty() {
+                continue;
+            }
+            if log_entry.files.len() > limits.max_files_per_commit {
+                bulk_commits_skipped += 1;
+                continue;
+            }
+            commits_analyzed += 1;
+
+            // sorted for the pairs to be in the same order in every commit
+            let files = log_entry.files.iter().map(|v| v.as_str()).collect::<BTreeSet<&str>>();
+            let modules = files.iter().map(|v| module_name(v)).collect::<BTreeSet<&str>>();
+
+            count_changes_and_pairs(&files, &mut file_changes, &mut file_pairs);
+            count_changes_and_pairs(&modules, &mut module_changes, &mut module_pairs);
+        }
+
+        if commits_analyzed == 0 && bulk_commits_skipped == 0 {
+            return None;
+        }
+
+        Some(Self {
+            commits_analyzed,
+            bulk_commits_skipped,
+            file_pairs: top_pairs(file_pairs, &file_changes, limits),
+            module_pairs: top_pairs(module_pairs, &module_changes, limits),
+        })
+    }
+}
+
+/// Returns the folder of the file, e.g. `src/report` for `src/report/tech.rs`, or `.` for files in the root.
+fn module_name(file_name: &str) -> &str {
+    match file_name.rsplit_once('/') {
+        Some((folder, _)) if !folder.is_empty() => folder,
+        _ => ROOT_MODULE,
+    }
+}
+
+/// Adds a change for every member of `names` and a shared change for every pair of them.
+fn count_changes_and_pairs<'a>(
+    names: &BTreeSet<&'a str>,
+    changes: &mut HashMap<&'a str, u64>,
+    pairs: &mut HashMap<(&'a str, &'a str), u64>,
+) {
+    for (idx, a) in names.iter().enumerate() {
+        *changes.entry(a).or_default() += 1;
+        for b in names.iter().skip(idx + 1) {
+            *pairs.entry((a, b)).or_default() += 1;
+        }
+    }
+}
+
+/// Returns pairs with at least `min_shared_commits`, sorted by the number of shared commits, then by degree.
+fn top_pairs(
+    pairs: HashMap<(&str, &str), u64>,
+    changes: &HashMap<&str, u64>,
+    limits: &CoChangeLimits,
+) -> Vec<CoChangePair> {
+    let mut pairs = pairs
+        .into_iter()
+        .filter(|(_, shared_commits)| *shared_commits >= limits.min_shared_commits)
+        .map(|((a, b), shared_commits)| {
+            let avg_changes = (changes.get(a).unwrap_or(&0) + changes.get(b).unwrap_or(&0)) as f64 / 2.0;
+            CoChangePair {
+                a: a.to_owned(),
+                b: b.to_owned(),
+                shared_commits,
+                degree: (shared_commits as f64 * 100.0 / avg_changes.max(1.0)).round() as u64,
+            }
+        })
+        .collect::<Vec<CoChangePair>>();
+
+    pairs.sort_unstable_by(|x, y| {
+        y.shared_commits
+            .cmp(&x.shared_commits)
+            .then_with(|| y.degree.cmp(&x.degree))
+            .then_with(|| x.a.cmp(&y.a))
+            .then_with(|| x.b.cmp(&y.b))
+    });
+    pairs.truncate(limits.top_pairs);
+
+    pairs
+}
+
+#[cfg(test)]
+mod test_co_change {
+    use super::{CoChange, CoChangeLimits};
+    use crate::git::GitLogEntry;
+
+    /// Returns a log entry with the given files.
+    fn commit(files: &[&str]) -> GitLogEntry {
+        let mut entry = GitLogEntry::new();
+        entry.files = files.iter().map(|v| v.to_string()).collect();
+        entry
+    }
+
+    #[test]
+    fn test_co_change() {
+        let limits = CoChangeLimits {
+            max_files_per_commit: 3,
+            min_shared_commits: 2,
+            ..CoChangeLimits::default()
+        };
+        assert!(CoChange::from_log(&[], &limits).is_none());
+
+        let git_log = vec![
+            commit(&["src/api/handler.rs", "config/routes.toml"]),
+            commit(&["src/api/handler.rs", "config/routes.toml", "README.md"]),
+            commit(&["src/api/handler.rs", "config/routes.toml"]),
+            commit(&["src/api/handler.rs"]),
+            commit(&["src/db.rs", "src/main.rs"]),
+            // bulk commit
+            commit(&["a.rs", "b.rs", "c.rs", "d.rs"]),
+        ];
+
+        let co_change = CoChange::from_log(&git_log, &limits).unwrap();
+        assert_eq!(co_change.commits_analyzed, 5);
+        assert_eq!(co_change.bulk_commits_skipped, 1);
+
+        // the handler changed 4 times and the routes 3 times, together 3 times
+        assert_eq!(co_change.file_pairs.len(), 1);
+        let pair = &co_change.file_pairs[0];
+        assert_eq!((pair.a.as_str(), pair.b.as_str()), ("config/routes.toml", "src/api/handler.rs"));
+        assert_eq!(pair.shared_commits, 3);
+        assert_eq!(pair.degree, 86);
+
+        assert_eq!(co_change.module_pairs[0].a, "config");
+        assert_eq!(co_change.module_pairs[0].b, "src/api");
+
+        // the cap on commits only leaves the newest one
+        let limits = CoChangeLimits {
+            max_commits: 1,
+            ..limits
+        };
+        let co_change = CoChange::from_log(&git_log, &limits).unwrap();
+        assert_eq!(co_change.commits_analyzed, 1);
+        assert!(co_change.file_pairs.is_empty());
+    }
+}
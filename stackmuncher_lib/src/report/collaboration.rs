@@ -0,0 +1,180 @@
+use crate::contributor::Contributor;
+use crate::git::GitLogEntry;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+/// Changes to the same file by two contributors count as collaboration if they are no further apart than this.
+pub const COLLABORATION_WINDOW_DAYS: i64 = 30;
+/// Pairs of contributors who collaborated on fewer files are ignored as a coincidence.
+pub const MIN_SHARED_FILES: u64 = 2;
+/// The max number of collaborators listed per contributor.
+pub const TOP_COLLABORATORS: usize = 5;
+/// Commits touching more files are skipped as bulk changes, e.g. reformatting, that would pair everyone with everyone.
+const MAX_FILES_PER_COMMIT: usize = 50;
+
+/// Contributors who repeatedly change the same files within `COLLABORATION_WINDOW_DAYS` of each other.
+/// Only present in project reports because the git IDs of other contributors are sensitive.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
+pub struct Collaboration {
+    /// Number of commits included in the calculation.
+    pub commits_analyzed: u64,
+    /// Number of commits skipped for touching more than `MAX_FILES_PER_COMMIT` files.
+    pub bulk_commits_skipped: u64,
+    /// Contributors with at least one collaborator, sorted by `git_id`.
+    #[serde(skip_serializing_if = "Vec::is_empty", default)]
+    pub contributors: Vec<ContributorCollaborators>,
+}
+
+/// The top collaborators of a single contributor.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
+pub struct ContributorCollaborators {
+    /// Same as `Contributor::git_id`
+    pub git_id: String,
+    /// Up to `TOP_COLLABORATORS` other contributors, the most `shared_files` first.
+    pub collaborators: Vec<Collaborator>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
+pub struct Collaborator {
+    /// Same as `Contributor::git_id`
+    pub git_id: String,
+    /// Number of files both contributors changed within `COLLABORATION_WINDOW_DAYS` of each other.
+    pub shared_files: u64,
+}
+
+impl Collaboration {
+    /// Finds pairs of contributors who changed the same files close in time from the log. Returns None if there are
+    /// no commits to analyze.
+    pub fn from_log(git_log: &[GitLogEntry]) -> Option<Self> {
+        let mut commits_analyzed = 0u64;
+        let mut bulk_commits_skipped = 0u64;
+        // file name -> (commit date, contributor) for every change of the file
+        let mut file_changes: HashMap<&str, Vec<(i64, String)>> = HashMap::new();
+
+        for log_entry in git_log {
+            if log_entry.files.is_empty() {
+                continue;
+            }
+            if log_entry.files.len() > MAX_FILES_PER_COMMIT {
+                bulk_commits_skipped += 1;
+                continue;
+            }
+            commits_analyzed += 1;
+
+            let git_id = Contributor::git_identity_from_name_email_pair(&log_entry.author_name_email);
+            for file_name in &log_entry.files {
+                file_changes
+                    .entry(file_name.as_str())
+                    .or_default()
+                    .push((log_entry.date_epoch, git_id.clone()));
+            }
+        }
+
+        if commits_analyzed == 0 && bulk_commits_skipped == 0 {
+            return None;
+        }
+
+        // (git_id a, git_id b) -> number of shared files, where a < b
+        let window = COLLABORATION_WINDOW_DAYS * 86400;
+        let mut pairs: HashMap<(String, String), u64> = HashMap::new();
+        for changes in file_changes.values_mut() {
+            changes.sort_unstable();
+
+            // a pair is counted once per file no matter how many times they changed it
+            let mut file_pairs: HashSet<(&str, &str)> = HashSet::new();
+            for (idx, (date_a, git_id_a)) in changes.iter().enumerate() {
+                for (date_b, git_id_b) in changes.iter().skip(idx + 1) {
+                    if date_b - date_a > window {
+                        break;
+                    }
+                    if git_id_a != git_id_b {
+                        file_pairs.insert(if git_id_a < git_id_b {
+                            (git_id_a, git_id_b)
+                        } else {
+                            (git_id_b, git_id_a)
+                        });
+                    }
+                }
+            }
+
+            for (a, b) in file_pairs {
+                *pairs.entry((a.to_owned(), b.to_owned())).or_default() += 1;
+            }
+        }
+
+        // git_id -> collaborators in both directions
+        let mut collaborators: BTreeMap<String, Vec<Collaborator>> = BTreeMap::new();
+        for ((a, b), shared_files) in pairs.into_iter().filter(|(_, v)| *v >= MIN_SHARED_FILES) {
+            collaborators.entry(a.clone()).or_default().push(Collaborator {
+                git_id: b.clone(),
+                shared_files,
+            });
+            collaborators.entry(b).or_default().push(Collaborator {
+                git_id: a,
+                shared_files,
+            });
+        }
+
+        let contributors = collaborators
+            .into_iter()
+            .map(|(git_id, mut collaborators)| {
+                collaborators.sort_unstable_by(|x, y| {
+                    y.shared_files
+                        .cmp(&x.shared_files)
+                        .then_with(|| x.git_id.cmp(&y.git_id))
+                });
+                collaborators.truncate(TOP_COLLABORATORS);
+                ContributorCollaborators { git_id, collaborators }
+            })
+            .collect::<Vec<ContributorCollaborators>>();
+
+        Some(Self {
+            commits_analyzed,
+            bulk_commits_skipped,
+            contributors,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test_collaboration {
+    use super::Collaboration;
+    use crate::git::GitLogEntry;
+
+    /// Returns a log entry by the author with the given files on the given day.
+    fn commit(email: &str, day: i64, files: &[&str]) -> GitLogEntry {
+        let mut entry = GitLogEntry::new();
+        entry.author_name_email = (String::new(), email.to_owned());
+        entry.date_epoch = 1609459200 + day * 86400;
+        entry.files = files.iter().map(|v| v.to_string()).collect();
+        entry
+    }
+
+    #[test]
+    fn test_collaboration() {
+        assert!(Collaboration::from_log(&[]).is_none());
+
+        let git_log = vec![
+            commit("a@example.com", 0, &["src/main.rs", "src/lib.rs", "README.md"]),
+            commit("b@example.com", 10, &["src/main.rs", "src/lib.rs"]),
+            commit("b@example.com", 11, &["src/main.rs"]),
+            // too far apart from the changes by a@ and b@
+            commit("c@example.com", 100, &["src/main.rs", "src/lib.rs"]),
+            // only one shared file
+            commit("d@example.com", 5, &["README.md"]),
+        ];
+
+        let collaboration = Collaboration::from_log(&git_log).unwrap();
+        assert_eq!(collaboration.commits_analyzed, 5);
+        assert_eq!(collaboration.contributors.len(), 2);
+        assert_eq!(collaboration.contributors[0].git_id, "a@example.com");
+        assert_eq!(collaboration.contributors[0].collaborators.len(), 1);
+        assert_eq!(collaboration.contributors[0].collaborators[0].git_id, "b@example.com");
+        assert_eq!(collaboration.contributors[0].collaborators[0].shared_files, 2);
+        assert_eq!(collaboration.contributors[1].git_id, "b@example.com");
+        assert_eq!(collaboration.contributors[1].collaborators[0].git_id, "a@example.com");
+    }
+}
@@ -0,0 +1,138 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+
+/// Number of calendar weeks in `CommitCadence.weekly_commits`.
+pub const CADENCE_WEEKS: usize = 52;
+
+/// Number of seconds in a day for converting EPOCH timestamps into day numbers.
+const SECONDS_IN_DAY: i64 = 86400;
+
+/// How regularly commits were made: weekly counts, streaks and gaps.
+/// Complements `CommitTimeHisto` which shows the time of the day.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
+pub struct CommitCadence {
+    /// Number of commits per calendar week (Mon-Sun, UTC) for the last `CADENCE_WEEKS` weeks, oldest first.
+    /// The last element is the week the report was generated in.
+    pub weekly_commits: Vec<u64>,
+    /// The longest run of consecutive days with at least one commit, UTC.
+    pub longest_streak_days: u64,
+    /// The average time between consecutive commits in hours, rounded down. Zero if there is only one commit.
+    pub avg_gap_hours: u64,
+    /// Number of calendar weeks with at least one commit over the entire history.
+    pub active_weeks: u64,
+}
+
+impl CommitCadence {
+    /// Calculates the cadence from commit timestamps in EPOCH format relative to `now`.
+    /// Returns None if there are no valid timestamps.
+    pub(crate) fn from_timestamps<I>(timestamps: I, now: DateTime<Utc>) -> Option<Self>
+    where
+        I: Iterator<Item = i64>,
+    {
+        let mut timestamps = timestamps.filter(|ts| *ts > 0).collect::<Vec<i64>>();
+        timestamps.sort_unstable();
+        let (first, last) = (*timestamps.first()?, *timestamps.last()?);
+
+        let this_week = week_start_day(now.timestamp());
+        let mut weekly_commits = vec![0u64; CADENCE_WEEKS];
+        let mut active_weeks: BTreeSet<i64> = BTreeSet::new();
+        let mut commit_days: BTreeSet<i64> = BTreeSet::new();
+
+        for ts in &timestamps {
+            let week = week_start_day(*ts);
+            active_weeks.insert(week);
+            commit_days.insert(ts.div_euclid(SECONDS_IN_DAY));
+
+            // commits in the future are counted towards the current week
+            let weeks_ago = ((this_week - week) / 7).max(0) as usize;
+            if weeks_ago < CADENCE_WEEKS {
+                weekly_commits[CADENCE_WEEKS - 1 - weeks_ago] += 1;
+            }
+        }
+
+        // days are sorted, so a streak is a run of days that differ by 1
+        let mut longest_streak_days = 0u64;
+        let mut streak = 0u64;
+        let mut prev_day: Option<i64> = None;
+        for day in commit_days {
+            streak = match prev_day {
+                Some(prev_day) if day == prev_day + 1 => streak + 1,
+                _ => 1,
+            };
+            longest_streak_days = longest_streak_days.max(streak);
+            prev_day = Some(day);
+        }
+
+        let avg_gap_hours = if timestamps.len() > 1 {
+            ((last - first) / (timestamps.len() as i64 - 1) / 3600) as u64
+        } else {
+            0
+        };
+
+        Some(Self {
+            weekly_commits,
+            longest_streak_days,
+            avg_gap_hours,
+            active_weeks: active_weeks.len() as u64,
+        })
+    }
+
+    /// Same as `from_timestamps`, but takes commits in `e29d17e6_1627380297` format. Invalid entries are skipped.
+    pub(crate) fn from_commits(commits: &[String], now: DateTime<Utc>) -> Option<Self> {
+        Self::from_timestamps(
+            commits
+                .iter()
+                .filter_map(|commit| commit.split_once('_').and_then(|(_, ts)| ts.parse::<i64>().ok())),
+            now,
+        )
+    }
+}
+
+/// Returns the day number of the Monday of the week the timestamp belongs to, UTC.
+fn week_start_day(ts: i64) -> i64 {
+    let day = ts.div_euclid(SECONDS_IN_DAY);
+    // day 0, 1970-01-01, was a Thursday, which is 3 days after Monday
+    day - (day + 3).rem_euclid(7)
+}
+
+#[cfg(test)]
+mod test_commit_cadence {
+    use super::{CommitCadence, CADENCE_WEEKS};
+    use chrono::{DateTime, Utc};
+
+    #[test]
+    fn test_commit_cadence() {
+        assert!(CommitCadence::from_timestamps(Vec::new().into_iter(), Utc::now()).is_none());
+
+        // Wed 2021-12-01 12:00
+        let now = DateTime::parse_from_rfc3339("2021-12-01T12:00:00+00:00")
+            .unwrap()
+            .with_timezone(&Utc);
+        let commits = [
+            // Mon, Tue and Wed of the current week
+            "00000001_1638187200", // 2021-11-29 12:00
+            "00000002_1638273600", // 2021-11-30 12:00
+            "00000003_1638360000", // 2021-12-01 12:00
+            // Sun of the previous week
+            "00000004_1638100800", // 2021-11-28 12:00
+            // more than a year ago
+            "00000005_1577880000", // 2020-01-01 12:00
+            "invalid",
+        ]
+        .iter()
+        .map(|v| v.to_string())
+        .collect::<Vec<String>>();
+
+        let cadence = CommitCadence::from_commits(&commits, now).unwrap();
+        assert_eq!(cadence.weekly_commits.len(), CADENCE_WEEKS);
+        assert_eq!(cadence.weekly_commits[CADENCE_WEEKS - 1], 3);
+        assert_eq!(cadence.weekly_commits[CADENCE_WEEKS - 2], 1);
+        assert_eq!(cadence.weekly_commits.iter().sum::<u64>(), 4);
+        // Sun-Wed are consecutive days across the week boundary
+        assert_eq!(cadence.longest_streak_days, 4);
+        assert_eq!(cadence.active_weeks, 3);
+        assert_eq!(cadence.avg_gap_hours, (1638360000 - 1577880000) / 4 / 3600);
+    }
+}
@@ -1,14 +1,61 @@
 use super::Report;
-use chrono::{self, Duration, TimeZone, Timelike, Utc};
+use crate::git::GitLogEntry;
+use chrono::{self, DateTime, Datelike, Duration, Offset, TimeZone, Timelike, Utc};
+use chrono_tz::Tz;
 use serde::{Deserialize, Serialize};
-use tracing::warn;
+use std::collections::BTreeMap;
+use tracing::{debug, warn};
 
 /// Number of days for including a commit in the recent counts.
 pub const RECENT_PERIOD_LENGTH_IN_DAYS: i64 = 365;
 
+/// Number of buckets in the day-of-week x hour heatmap: 7 days of 24 UTC hours, Monday first.
+pub const HEATMAP_BUCKETS: usize = 7 * 24;
+
+/// The working day and the timezones for calculating `CommitTimeHisto` overlaps, e.g. 9am - 5pm in `Europe/Berlin`.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(default)]
+pub struct WorkdayOverlap {
+    /// The first working hour, local time, e.g. `8` for 8am.
+    pub start_hour: u32,
+    /// The end of the working day, local time, e.g. `18` for 6pm. Can be less than `start_hour` for night shifts.
+    pub end_hour: u32,
+    /// IANA timezone names, e.g. `Europe/Berlin` or `US/Pacific`. The overlap is calculated for all 24 whole-hour
+    /// UTC offsets if empty.
+    pub timezones: Vec<String>,
+}
+
+impl Default for WorkdayOverlap {
+    fn default() -> Self {
+        Self {
+            start_hour: 8,
+            end_hour: 18,
+            timezones: Vec::new(),
+        }
+    }
+}
+
+impl WorkdayOverlap {
+    /// Returns true if `name` is a known IANA timezone, e.g. `Europe/Berlin`.
+    pub fn is_valid_timezone(name: &str) -> bool {
+        name.parse::<Tz>().is_ok()
+    }
+
+    /// Returns true if the local `hour` is within the working day.
+    fn is_working_hour(&self, hour: u32) -> bool {
+        if self.start_hour <= self.end_hour {
+            hour >= self.start_hour && hour < self.end_hour
+        } else {
+            // the working day goes past midnight
+            hour >= self.start_hour || hour < self.end_hour
+        }
+    }
+}
+
 /// Number of commits or percentage of commits per UTC hour.
 /// The structure is skipped in JSON if all values are zero and is initialized to all zeros to have fewer Option<T> unwraps.
 #[derive(Serialize, Deserialize, Clone, Debug)]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
 pub struct CommitTimeHistoHours {
     #[serde(skip_serializing_if = "CommitTimeHistoHours::is_zero", default = "u64::default")]
     pub h00: u64,
@@ -62,6 +109,7 @@ pub struct CommitTimeHistoHours {
 
 /// Contains members and methods related to commit time histogram
 #[derive(Serialize, Deserialize, Clone, Debug)]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
 pub struct CommitTimeHisto {
     /// The sum of all commits included in `histogram_recent`. This value is used as the 100% of all recent commits.
     /// The value is populated once after all commits have been added.
@@ -94,22 +142,38 @@ pub struct CommitTimeHisto {
     )]
     pub histogram_all: CommitTimeHistoHours,
 
-    /// Number of working hours in overlap between the dev's active time and the 8am - 6pm working day in the specified time zone.
-    /// Timezones with negative offset are represented as 24-offset. E.g. `-6` hours will be in `h18`.
-    /// Only activity within standard deviation is included.
+    /// Number of working hours in overlap between the dev's active time and the working day in the specified time zone,
+    /// 8am - 6pm by default. Timezones with negative offset are represented as 24-offset. E.g. `-6` hours will be in `h18`.
+    /// Only activity within standard deviation is included. Empty if `WorkdayOverlap.timezones` were set.
     #[serde(
         skip_serializing_if = "CommitTimeHistoHours::is_empty",
         default = "CommitTimeHistoHours::default"
     )]
     pub timezone_overlap_recent: CommitTimeHistoHours,
-    /// Number of working hours in overlap between the dev's active time and the 8am - 6pm working day in the specified time zone.
-    /// Timezones with negative offset are represented as 24-offset + the negative value. E.g. `-6` hours will be in `h18` (24-6-18).
-    /// Only activity within standard deviation is included.
+    /// Number of working hours in overlap between the dev's active time and the working day in the specified time zone,
+    /// 8am - 6pm by default. Timezones with negative offset are represented as 24-offset + the negative value.
+    /// E.g. `-6` hours will be in `h18` (24-6-18).
+    /// Only activity within standard deviation is included. Empty if `WorkdayOverlap.timezones` were set.
     #[serde(
         skip_serializing_if = "CommitTimeHistoHours::is_empty",
         default = "CommitTimeHistoHours::default"
     )]
     pub timezone_overlap_all: CommitTimeHistoHours,
+    /// Same as `timezone_overlap_recent`, but only for `WorkdayOverlap.timezones`, e.g. `Europe/Berlin: 6`.
+    /// The offsets are taken at the time of the calculation, including daylight saving.
+    #[serde(skip_serializing_if = "BTreeMap::is_empty", default)]
+    pub timezone_overlap_zones_recent: BTreeMap<String, u64>,
+    /// Same as `timezone_overlap_all`, but only for `WorkdayOverlap.timezones`, e.g. `Europe/Berlin: 6`.
+    #[serde(skip_serializing_if = "BTreeMap::is_empty", default)]
+    pub timezone_overlap_zones_all: BTreeMap<String, u64>,
+
+    /// Same as `histogram_recent`, but split by the day of the week. There are `HEATMAP_BUCKETS` values indexed as
+    /// `day * 24 + hour`, where `day` is 0 for Monday, UTC. Empty in reports from older versions.
+    #[serde(skip_serializing_if = "Vec::is_empty", default)]
+    pub heatmap_recent: Vec<u64>,
+    /// Same as `histogram_all`, but split by the day of the week. See `heatmap_recent` for the layout.
+    #[serde(skip_serializing_if = "Vec::is_empty", default)]
+    pub heatmap_all: Vec<u64>,
 }
 
 impl CommitTimeHistoHours {
@@ -285,37 +349,29 @@ impl CommitTimeHistoHours {
             + self.h23
     }
 
-    /// Calculates how many working (8am - 6pm) hours overlap between commit time and the target timezone.
+    /// Calculates how many working hours overlap between commit time and the timezone with `offset_minutes` from UTC.
     /// Only commit hours above the standard deviation (std) are included.
-    fn overlap(&self, std: f64) -> Self {
-        // copy the commit counts into an array for easy referencing in a loop
-        let commit_counts: [u64; 24] = [
-            self.h00, self.h01, self.h02, self.h03, self.h04, self.h05, self.h06, self.h07, self.h08, self.h09,
-            self.h10, self.h11, self.h12, self.h13, self.h14, self.h15, self.h16, self.h17, self.h18, self.h19,
-            self.h20, self.h21, self.h22, self.h23,
-        ];
+    fn overlap_hours(&self, std: f64, offset_minutes: i32, workday: &WorkdayOverlap) -> u64 {
+        self.to_array()
+            .iter()
+            .enumerate()
+            .filter(|(hr, v)| {
+                // normalize the UTC time of the commit counts to the local time of the target timezone
+                // e.g. 18hr UTC for UTC+12 = 30 = 6am
+                let local_hr = (*hr as i32 * 60 + offset_minutes).rem_euclid(24 * 60) / 60;
+                workday.is_working_hour(local_hr as u32) && **v as f64 > std
+            })
+            .count() as u64
+    }
 
+    /// Calculates how many working hours overlap between commit time and all 24 whole-hour timezones.
+    /// Only commit hours above the standard deviation (std) are included.
+    fn overlap(&self, std: f64, workday: &WorkdayOverlap) -> Self {
         let mut tz_overlap: [u64; 24] = [0; 24];
 
         // populate an array for all possible timezones with the number of overlapping hours
-        for tz in 0..23 {
-            tz_overlap[tz] = commit_counts
-                .iter()
-                .enumerate()
-                .map(|(hr, v)| {
-                    // normalize the UTC time of the commit counts to the working hours of the target timezone
-                    // e.g. 18hr for UTC+12 = 30
-                    let hr = hr + tz;
-                    // e.g. 30 - 24 = 6am UTC
-                    let hr = if hr > 23 { hr - 24 } else { hr };
-                    // hours between 8am and 6pm of the target timezone where the number of commits is above the standard deviation
-                    if hr >= 8 && hr < 18 && *v as f64 > std {
-                        1
-                    } else {
-                        0
-                    }
-                })
-                .sum::<u64>();
+        for tz in 0..24 {
+            tz_overlap[tz] = self.overlap_hours(std, tz as i32 * 60, workday);
         }
 
         Self {
@@ -347,6 +403,36 @@ impl CommitTimeHistoHours {
     }
 }
 
+impl CommitTimeHistoHours {
+    /// Returns the buckets as an array for easy referencing in a loop, `h00` first.
+    pub fn to_array(&self) -> [u64; 24] {
+        [
+            self.h00, self.h01, self.h02, self.h03, self.h04, self.h05, self.h06, self.h07, self.h08, self.h09,
+            self.h10, self.h11, self.h12, self.h13, self.h14, self.h15, self.h16, self.h17, self.h18, self.h19,
+            self.h20, self.h21, self.h22, self.h23,
+        ]
+    }
+
+    /// Calculates how many working hours overlap between commit time and each of `WorkdayOverlap.timezones`
+    /// with their offsets at `now`. Unknown timezones are skipped.
+    fn overlap_zones(&self, std: f64, workday: &WorkdayOverlap, now: DateTime<Utc>) -> BTreeMap<String, u64> {
+        workday
+            .timezones
+            .iter()
+            .filter_map(|name| match name.parse::<Tz>() {
+                Ok(tz) => {
+                    let offset_minutes = tz.offset_from_utc_datetime(&now.naive_utc()).fix().local_minus_utc() / 60;
+                    Some((name.clone(), self.overlap_hours(std, offset_minutes, workday)))
+                }
+                Err(_) => {
+                    warn!("Unknown timezone: {}", name);
+                    None
+                }
+            })
+            .collect()
+    }
+}
+
 impl Default for CommitTimeHistoHours {
     fn default() -> Self {
         Self {
@@ -379,43 +465,38 @@ impl Default for CommitTimeHistoHours {
 }
 
 impl CommitTimeHisto {
+    /// Returns a blank histogram with all counts at zero.
+    fn new() -> Self {
+        CommitTimeHisto {
+            histogram_recent: CommitTimeHistoHours::default(),
+            histogram_all: CommitTimeHistoHours::default(),
+            histogram_recent_sum: 0,
+            histogram_all_sum: 0,
+            timezone_overlap_recent: CommitTimeHistoHours::default(),
+            timezone_overlap_all: CommitTimeHistoHours::default(),
+            histogram_recent_std: 0.0,
+            histogram_all_std: 0.0,
+            timezone_overlap_zones_recent: BTreeMap::new(),
+            timezone_overlap_zones_all: BTreeMap::new(),
+            heatmap_recent: Vec::new(),
+            heatmap_all: Vec::new(),
+        }
+    }
+
     /// Adds the time from the list of commits to the histogram structure.
     /// Logs any errors and warnings and returns regardless of success of failure.
     pub(crate) fn add_commits(report: &mut Report, commits: &Option<Vec<String>>) {
         // is there anything to add?
         if let Some(commits) = commits {
             // init the histo structure if there is none
-            if report.commit_time_histo.is_none() {
-                report.commit_time_histo = Some(CommitTimeHisto {
-                    histogram_recent: CommitTimeHistoHours::default(),
-                    histogram_all: CommitTimeHistoHours::default(),
-                    histogram_recent_sum: 0,
-                    histogram_all_sum: 0,
-                    timezone_overlap_recent: CommitTimeHistoHours::default(),
-                    timezone_overlap_all: CommitTimeHistoHours::default(),
-                    histogram_recent_std: 0.0,
-                    histogram_all_std: 0.0,
-                });
-            }
-
-            let histo = report
-                .commit_time_histo
-                .as_mut()
-                .expect("report.commit_time_histo should exist by now. It's a bug.");
+            let histo = report.commit_time_histo.get_or_insert_with(CommitTimeHisto::new);
 
             // update the commit time histogram
             let now = Utc::now();
-            let recent_period_start = now - Duration::days(RECENT_PERIOD_LENGTH_IN_DAYS);
             for commit in commits {
                 if let Some((_, ts)) = commit.split_once("_") {
                     if let Ok(ts) = i64::from_str_radix(ts, 10) {
-                        let ts = Utc.timestamp(ts, 0);
-                        // update recent commits histo if the TS is within the recent period
-                        if ts > recent_period_start && ts < now {
-                            histo.histogram_recent.add_commit(ts.time().hour());
-                        }
-                        // update all commits histo
-                        histo.histogram_all.add_commit(ts.time().hour());
+                        histo.add_commit(ts, now);
                     } else {
                         warn!("Invalid time part in commit {}.", ts);
                     }
@@ -428,19 +509,370 @@ impl CommitTimeHisto {
         }
     }
 
-    /// Calculates the percentage of each bucket from the total sum of commits in the histogram for `_recent` and `_all`.
-    pub(crate) fn recalculate_counts_to_percentage(&mut self) {
+    /// Returns a histogram of a single contributor in a single project with the counts already converted to
+    /// percentages for the default working day. Takes commit timestamps in EPOCH format.
+    /// Returns None if there are no valid timestamps.
+    pub(crate) fn from_timestamps<I>(timestamps: I, now: DateTime<Utc>) -> Option<Self>
+    where
+        I: Iterator<Item = i64>,
+    {
+        let mut histo = CommitTimeHisto::new();
+        for ts in timestamps.filter(|ts| *ts > 0) {
+            histo.add_commit(ts, now);
+        }
+
+        if histo.histogram_all.is_empty() {
+            return None;
+        }
+
+        histo.recalculate_counts_to_percentage(&WorkdayOverlap::default());
+        Some(histo)
+    }
+
+    /// Same as `from_timestamps`, but takes commits in `e29d17e6_1627380297` format. Invalid entries are skipped.
+    pub(crate) fn from_commits(commits: &[String], now: DateTime<Utc>) -> Option<Self> {
+        Self::from_timestamps(
+            commits
+                .iter()
+                .filter_map(|commit| commit.split_once('_').and_then(|(_, ts)| ts.parse::<i64>().ok())),
+            now,
+        )
+    }
+
+    /// Counts a commit made at `ts` EPOCH in `_all` and, if it was made within `RECENT_PERIOD_LENGTH_IN_DAYS`
+    /// before `now`, in `_recent` histograms and heatmaps.
+    fn add_commit(&mut self, ts: i64, now: DateTime<Utc>) {
+        let ts = Utc.timestamp(ts, 0);
+        // update recent commits histo if the TS is within the recent period
+        if ts > now - Duration::days(RECENT_PERIOD_LENGTH_IN_DAYS) && ts < now {
+            self.histogram_recent.add_commit(ts.time().hour());
+            add_to_heatmap(&mut self.heatmap_recent, ts);
+        }
+        // update all commits histo
+        self.histogram_all.add_commit(ts.time().hour());
+        add_to_heatmap(&mut self.heatmap_all, ts);
+    }
+
+    /// Calculates the percentage of each bucket from the total sum of commits in the histogram for `_recent` and `_all`
+    /// and the overlaps with the working day. The overlaps are calculated either for all 24 offsets or only for
+    /// `workday.timezones`, if any. Must be called only once, after all commits were added.
+    pub fn recalculate_counts_to_percentage(&mut self, workday: &WorkdayOverlap) {
+        let now = Utc::now();
+
         self.histogram_recent_sum = self.histogram_recent.sum();
         let mean_recent = self.histogram_recent_sum as f64 / 24.0;
         self.histogram_recent_std = self.histogram_recent.standard_deviation(mean_recent);
-        self.timezone_overlap_recent = self.histogram_recent.overlap(self.histogram_recent_std);
+        if workday.timezones.is_empty() {
+            self.timezone_overlap_recent = self.histogram_recent.overlap(self.histogram_recent_std, workday);
+        } else {
+            self.timezone_overlap_zones_recent =
+                self.histogram_recent
+                    .overlap_zones(self.histogram_recent_std, workday, now);
+        }
         self.histogram_recent
             .convert_counts_to_percentage(self.histogram_recent_sum);
+        convert_heatmap_to_percentage(&mut self.heatmap_recent);
 
         self.histogram_all_sum = self.histogram_all.sum();
         let mean_all = self.histogram_all_sum as f64 / 24.0;
         self.histogram_all_std = self.histogram_all.standard_deviation(mean_all);
-        self.timezone_overlap_all = self.histogram_all.overlap(self.histogram_all_std);
+        if workday.timezones.is_empty() {
+            self.timezone_overlap_all = self.histogram_all.overlap(self.histogram_all_std, workday);
+        } else {
+            self.timezone_overlap_zones_all = self.histogram_all.overlap_zones(self.histogram_all_std, workday, now);
+        }
         self.histogram_all.convert_counts_to_percentage(self.histogram_all_sum);
+        convert_heatmap_to_percentage(&mut self.heatmap_all);
+    }
+}
+
+/// The share of commits made outside of the working day. The day and the hour of each commit are taken in the timezone
+/// the commit was made in, e.g. `+02:00`, so that commits of a team spread over several timezones are not counted
+/// as after hours for some of its members.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
+pub struct WorkPattern {
+    /// Percentage of commits made on Saturday or Sunday.
+    pub weekend_share: u64,
+    /// Percentage of commits made on weekdays outside of the `WorkdayOverlap` hours.
+    pub after_hours_share: u64,
+    /// The number of commits the shares were calculated from.
+    pub commits: u64,
+}
+
+impl WorkPattern {
+    /// Returns the shares of all commits in `git_log` for the `workday` hours in the local time of each commit.
+    /// Returns None if there are no commits with a valid date.
+    pub(crate) fn from_log(git_log: &[GitLogEntry], workday: &WorkdayOverlap) -> Option<Self> {
+        let mut heatmap = Vec::new();
+        for log_entry in git_log {
+            match DateTime::parse_from_rfc3339(&log_entry.date) {
+                Ok(date) => add_to_heatmap(&mut heatmap, date),
+                Err(_) => debug!("Invalid date in commit {}: {}", log_entry.sha1, log_entry.date),
+            }
+        }
+
+        Self::from_heatmap(&heatmap, workday)
+    }
+
+    /// Same as `from_log`, but takes commits in `e29d17e6_1627380297` format, which have no timezone, so the shares
+    /// are for UTC. Only for reports from older versions that have no `WorkPattern`.
+    pub(crate) fn from_commits(commits: &[String], workday: &WorkdayOverlap) -> Option<Self> {
+        Self::from_heatmap(&heatmap_from_commits(commits), workday)
+    }
+
+    /// Returns the shares from a heatmap with the number of commits or None if it is empty.
+    fn from_heatmap(heatmap: &[u64], workday: &WorkdayOverlap) -> Option<Self> {
+        let (weekend_share, after_hours_share) = weekend_and_after_hours_shares(heatmap, workday)?;
+
+        Some(Self {
+            weekend_share,
+            after_hours_share,
+            commits: heatmap.iter().sum(),
+        })
+    }
+
+    /// Adds the shares of another set of commits weighted by the number of commits in each.
+    pub(crate) fn merge(&mut self, rhs: &Self) {
+        let commits = self.commits + rhs.commits;
+        if commits == 0 {
+            return;
+        }
+        let lhs_commits = self.commits;
+        let weighted = |lhs_share: u64, rhs_share: u64| {
+            ((lhs_share * lhs_commits + rhs_share * rhs.commits) as f64 / commits as f64).round() as u64
+        };
+        self.weekend_share = weighted(self.weekend_share, rhs.weekend_share);
+        self.after_hours_share = weighted(self.after_hours_share, rhs.after_hours_share);
+        self.commits = commits;
+    }
+}
+
+/// Adds a commit made at `ts` to the day-of-week x hour heatmap in the timezone of `ts`.
+/// The heatmap is initialized on the first use.
+fn add_to_heatmap<T: TimeZone>(heatmap: &mut Vec<u64>, ts: DateTime<T>) {
+    if heatmap.len() != HEATMAP_BUCKETS {
+        *heatmap = vec![0; HEATMAP_BUCKETS];
+    }
+    heatmap[ts.weekday().num_days_from_monday() as usize * 24 + ts.hour() as usize] += 1;
+}
+
+/// Converts the heatmap values from the number of commits to percentage of the heatmap total.
+fn convert_heatmap_to_percentage(heatmap: &mut [u64]) {
+    let sum = heatmap.iter().sum::<u64>() as f64;
+    if sum == 0.0 {
+        return;
+    }
+
+    for bucket in heatmap.iter_mut() {
+        *bucket = (*bucket as f64 * 100.0 / sum).round() as u64;
+    }
+}
+
+/// Builds a day-of-week x hour heatmap with the number of commits from a list of commits
+/// in `e29d17e6_1627380297` format. Commits without a valid timestamp are skipped.
+fn heatmap_from_commits(commits: &[String]) -> Vec<u64> {
+    let mut heatmap = Vec::new();
+    for commit in commits {
+        if let Some(ts) = commit
+            .split_once('_')
+            .and_then(|(_, ts)| ts.parse::<i64>().ok())
+            .and_then(|ts| Utc.timestamp_opt(ts, 0).single())
+        {
+            add_to_heatmap(&mut heatmap, ts);
+        }
+    }
+
+    heatmap
+}
+
+/// Returns the percentage of commits made on a weekend and on weekdays outside of the `workday` hours
+/// from a heatmap with the number of commits. Returns None if there are no commits.
+fn weekend_and_after_hours_shares(heatmap: &[u64], workday: &WorkdayOverlap) -> Option<(u64, u64)> {
+    let total = heatmap.iter().sum::<u64>();
+    if total == 0 {
+        return None;
+    }
+
+    let mut weekend = 0;
+    let mut after_hours = 0;
+    for (bucket, commits) in heatmap.iter().enumerate() {
+        // days 5 and 6 are Saturday and Sunday
+        if bucket / 24 >= 5 {
+            weekend += commits;
+        } else if !workday.is_working_hour((bucket % 24) as u32) {
+            after_hours += commits;
+        }
+    }
+
+    Some((
+        (weekend as f64 * 100.0 / total as f64).round() as u64,
+        (after_hours as f64 * 100.0 / total as f64).round() as u64,
+    ))
+}
+
+#[cfg(test)]
+mod test_commit_time_histo {
+    use super::{
+        heatmap_from_commits, weekend_and_after_hours_shares, CommitTimeHisto, CommitTimeHistoHours, WorkPattern,
+        WorkdayOverlap,
+    };
+    use crate::git::GitLogEntry;
+    use chrono::{DateTime, Utc};
+
+    #[test]
+    fn test_workday_overlap() {
+        // commits between 7am and 3pm UTC
+        let hours = CommitTimeHistoHours {
+            h07: 10,
+            h08: 10,
+            h09: 10,
+            h10: 10,
+            h11: 10,
+            h12: 10,
+            h13: 10,
+            h14: 10,
+            h15: 10,
+            ..CommitTimeHistoHours::default()
+        };
+
+        // the default 8am - 6pm for all offsets
+        let overlap = hours.overlap(5.0, &WorkdayOverlap::default());
+        assert_eq!(overlap.h00, 8);
+        assert_eq!(overlap.h01, 9);
+        assert_eq!(overlap.h12, 0);
+
+        // 9am - 5pm in named zones in winter
+        let workday = WorkdayOverlap {
+            start_hour: 9,
+            end_hour: 17,
+            timezones: vec![
+                "Europe/Berlin".to_owned(),
+                "US/Pacific".to_owned(),
+                "Asia/Kolkata".to_owned(),
+                "Mars/Olympus".to_owned(),
+            ],
+        };
+        let now = DateTime::parse_from_rfc3339("2021-01-15T12:00:00+00:00")
+            .unwrap()
+            .with_timezone(&Utc);
+        let overlap = hours.overlap_zones(5.0, &workday, now);
+        assert_eq!(overlap.len(), 3);
+        assert_eq!(overlap["Europe/Berlin"], 8);
+        assert_eq!(overlap["US/Pacific"], 0);
+        // UTC+5:30 moves 7:00 UTC to 12:30
+        assert_eq!(overlap["Asia/Kolkata"], 5);
+
+        // a night shift, 10pm - 6am
+        let workday = WorkdayOverlap {
+            start_hour: 22,
+            end_hour: 6,
+            timezones: Vec::new(),
+        };
+        assert!(workday.is_working_hour(23));
+        assert!(workday.is_working_hour(0));
+        assert!(!workday.is_working_hour(6));
+
+        assert!(WorkdayOverlap::is_valid_timezone("US/Pacific"));
+        assert!(!WorkdayOverlap::is_valid_timezone("UTC+2"));
+    }
+
+    #[test]
+    fn test_heatmap() {
+        let commits = [
+            // Monday 2021-01-04 10:00 UTC
+            "e29d17e6_1609754400".to_owned(),
+            // Saturday 2021-01-09 10:00 UTC
+            "a1b2c3d4_1610186400".to_owned(),
+            // Wednesday 2021-01-06 22:00 UTC
+            "f0e1d2c3_1609970400".to_owned(),
+            "f0e1d2c3_1609970401".to_owned(),
+            "no_timestamp".to_owned(),
+        ];
+        let heatmap = heatmap_from_commits(&commits);
+        assert_eq!(heatmap.len(), 168);
+        assert_eq!(heatmap.iter().sum::<u64>(), 4);
+        assert_eq!(heatmap[10], 1);
+        assert_eq!(heatmap[5 * 24 + 10], 1);
+        assert_eq!(heatmap[2 * 24 + 22], 2);
+
+        assert_eq!(weekend_and_after_hours_shares(&heatmap, &WorkdayOverlap::default()), Some((25, 50)));
+        assert_eq!(weekend_and_after_hours_shares(&[], &WorkdayOverlap::default()), None);
+    }
+
+    #[test]
+    fn test_from_commits() {
+        let now = DateTime::parse_from_rfc3339("2021-06-01T00:00:00+00:00")
+            .unwrap()
+            .with_timezone(&Utc);
+        let commits = [
+            // 2021-01-04 10:00 UTC
+            "e29d17e6_1609754400".to_owned(),
+            // 2019-01-04 10:00 UTC is outside the recent period
+            "a1b2c3d4_1546596000".to_owned(),
+            // 2021-01-06 22:00 UTC
+            "f0e1d2c3_1609970400".to_owned(),
+            "no_timestamp".to_owned(),
+        ];
+
+        let histo = CommitTimeHisto::from_commits(&commits, now).unwrap();
+        assert_eq!(histo.histogram_all_sum, 3);
+        assert_eq!(histo.histogram_recent_sum, 2);
+        // the counts are converted to percentages
+        assert_eq!((histo.histogram_all.h10, histo.histogram_all.h22), (67, 33));
+        assert_eq!((histo.histogram_recent.h10, histo.histogram_recent.h22), (50, 50));
+        assert_eq!(histo.heatmap_recent.iter().sum::<u64>(), 100);
+
+        assert!(CommitTimeHisto::from_commits(&["no_timestamp".to_owned()], now).is_none());
+    }
+
+    #[test]
+    fn test_work_pattern() {
+        let git_log = [
+            // Monday 10:00 in Sydney is Sunday 23:00 UTC
+            "2021-01-04T10:00:00+11:00",
+            // Friday 17:00 in San Francisco is Saturday 01:00 UTC
+            "2021-01-08T17:00:00-08:00",
+            // Wednesday 19:00 in Berlin
+            "2021-01-06T19:00:00+01:00",
+            "2021-01-06T07:30:00+01:00",
+            "invalid",
+        ]
+        .iter()
+        .map(|date| {
+            let mut log_entry = GitLogEntry::new();
+            log_entry.date = date.to_string();
+            log_entry
+        })
+        .collect::<Vec<GitLogEntry>>();
+
+        let work_pattern = WorkPattern::from_log(&git_log, &WorkdayOverlap::default()).unwrap();
+        assert_eq!(
+            (work_pattern.weekend_share, work_pattern.after_hours_share, work_pattern.commits),
+            (0, 50, 4)
+        );
+
+        // the configured working day
+        let workday = WorkdayOverlap {
+            start_hour: 7,
+            end_hour: 20,
+            timezones: Vec::new(),
+        };
+        let work_pattern = WorkPattern::from_log(&git_log, &workday).unwrap();
+        assert_eq!((work_pattern.weekend_share, work_pattern.after_hours_share), (0, 0));
+        assert!(WorkPattern::from_log(&git_log[4..], &workday).is_none());
+
+        // merging weighs the shares by the number of commits
+        let mut merged = WorkPattern {
+            weekend_share: 0,
+            after_hours_share: 50,
+            commits: 4,
+        };
+        merged.merge(&WorkPattern {
+            weekend_share: 100,
+            after_hours_share: 0,
+            commits: 1,
+        });
+        assert_eq!((merged.weekend_share, merged.after_hours_share, merged.commits), (20, 40, 5));
     }
 }
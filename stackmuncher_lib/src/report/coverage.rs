@@ -0,0 +1,193 @@
+use crate::git::GitLogEntry;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+
+/// How much of the repo the stack in the report represents. All files in the tree at HEAD are either ignored,
+/// left out of a partial analysis, munched, skipped or unrecognized.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
+pub struct Coverage {
+    /// Files in the tree at HEAD, excluding `ignore_paths`.
+    pub files_total: u64,
+    /// Files selected for analysis, the most recently changed first. Same as `files_total` unless the report is `partial`.
+    pub files_analyzed: u64,
+    /// The date of the oldest commit that selected a file. All files changed since then are included.
+    /// None if no files could be selected from the log or the report is not `partial`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub changed_since: Option<String>,
+    /// All files in the tree at HEAD, including ignored ones.
+    #[serde(default)]
+    pub files_in_tree: u64,
+    /// Files matching `ignore_paths`, e.g. vendored code, lock files and images.
+    #[serde(default)]
+    pub files_ignored: u64,
+    /// Analyzed files with at least one line counted by their muncher.
+    #[serde(default)]
+    pub files_munched: u64,
+    /// Analyzed files with a muncher, but no lines counted because they are binary, empty, larger than
+    /// `MunchLimits.max_file_size`, took too long or failed.
+    #[serde(default)]
+    pub files_skipped: u64,
+    /// Analyzed files of types with no muncher.
+    #[serde(default)]
+    pub files_unrecognized: u64,
+    /// The files were selected by `munch_limits.max_files`, the most recently changed first.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub max_files_reached: bool,
+    /// The repo is a sparse checkout. Only the files in the working tree are analyzed and the rest of the tree
+    /// is counted as not analyzed.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub sparse_checkout: bool,
+    /// The filter of a partial clone, e.g. `blob:none`. Missing blobs are fetched from the remote during the analysis.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub partial_clone_filter: Option<String>,
+}
+
+impl Coverage {
+    /// Returns the coverage of a full analysis of `files_total` files. The munched, skipped and unrecognized files
+    /// are counted later by `Report::update_project_file_lists`.
+    pub fn new(files_in_tree: u64, files_total: u64) -> Self {
+        Self {
+            files_total,
+            files_analyzed: files_total,
+            files_in_tree,
+            files_ignored: files_in_tree.saturating_sub(files_total),
+            ..Default::default()
+        }
+    }
+
+    /// Returns a one-line summary for the user, e.g. `120 of 150 files munched, 10 skipped, 15 unrecognized, 5 ignored`.
+    /// Files left out of a partial analysis and the repo mode are only listed if there are any.
+    pub fn summary(&self) -> String {
+        let mut summary = format!(
+            "{} of {} files munched, {} skipped, {} unrecognized, {} ignored",
+            self.files_munched, self.files_in_tree, self.files_skipped, self.files_unrecognized, self.files_ignored
+        );
+        if self.files_total > self.files_analyzed {
+            summary.push_str(&format!(", {} not analyzed", self.files_total - self.files_analyzed));
+        }
+        if self.sparse_checkout {
+            summary.push_str(", sparse checkout");
+        }
+        if let Some(filter) = &self.partial_clone_filter {
+            summary.push_str(&format!(", partial clone ({})", filter));
+        }
+        summary
+    }
+
+    /// Selects up to `max_files` files from `all_head_files`, the most recently changed first.
+    /// Files that are not in the log are added last in alphabetical order to fill the budget.
+    pub fn select_recent_files(
+        git_log: &[GitLogEntry],
+        all_head_files: &HashSet<String>,
+        max_files: usize,
+    ) -> (HashSet<String>, Self) {
+        let mut selected: HashSet<String> = HashSet::with_capacity(max_files);
+        let mut changed_since: Option<String> = None;
+
+        // the log is usually in reverse chronological order, but it may be a merge of cached and new entries
+        let mut log_entries = git_log.iter().collect::<Vec<&GitLogEntry>>();
+        log_entries.sort_by_key(|log_entry| std::cmp::Reverse(log_entry.date_epoch));
+
+        'log: for log_entry in log_entries {
+            // files within the same commit are taken in a stable order for repeatable reports
+            let mut files = log_entry
+                .files
+                .iter()
+                .filter(|file_name| all_head_files.contains(*file_name) && !selected.contains(*file_name))
+                .collect::<Vec<&String>>();
+            files.sort();
+
+            for file_name in files {
+                if selected.len() >= max_files {
+                    break 'log;
+                }
+                selected.insert(file_name.clone());
+                changed_since = Some(log_entry.date.clone());
+            }
+        }
+
+        // files with no history, e.g. if the log is incomplete
+        if selected.len() < max_files {
+            let mut files = all_head_files
+                .iter()
+                .filter(|file_name| !selected.contains(*file_name))
+                .collect::<Vec<&String>>();
+            files.sort();
+            let budget = max_files - selected.len();
+            selected.extend(files.into_iter().take(budget).cloned());
+        }
+
+        let coverage = Self {
+            files_total: all_head_files.len() as u64,
+            files_analyzed: selected.len() as u64,
+            changed_since,
+            ..Default::default()
+        };
+
+        (selected, coverage)
+    }
+}
+
+#[cfg(test)]
+mod test_coverage {
+    use super::Coverage;
+    use crate::git::GitLogEntry;
+    use std::collections::HashSet;
+
+    fn log_entry(date_epoch: i64, files: &[&str]) -> GitLogEntry {
+        let mut log_entry = GitLogEntry::new();
+        log_entry.date_epoch = date_epoch;
+        log_entry.date = date_epoch.to_string();
+        log_entry.files = files.iter().map(|f| f.to_string()).collect::<HashSet<String>>();
+        log_entry
+    }
+
+    #[test]
+    fn test_select_recent_files() {
+        let head = ["a.rs", "b.rs", "c.rs", "d.rs", "e.rs"]
+            .iter()
+            .map(|f| f.to_string())
+            .collect::<HashSet<String>>();
+        // `deleted.rs` is not in the tree and `e.rs` has no history
+        let git_log = vec![
+            log_entry(100, &["a.rs", "b.rs"]),
+            log_entry(300, &["c.rs", "deleted.rs"]),
+            log_entry(200, &["b.rs", "d.rs"]),
+        ];
+
+        let (selected, coverage) = Coverage::select_recent_files(&git_log, &head, 3);
+        let mut selected = selected.into_iter().collect::<Vec<String>>();
+        selected.sort();
+        assert_eq!(selected, vec!["b.rs", "c.rs", "d.rs"]);
+        assert_eq!(coverage.files_total, 5);
+        assert_eq!(coverage.files_analyzed, 3);
+        assert_eq!(coverage.changed_since, Some("200".to_owned()));
+
+        // the budget is filled with files that have no history
+        let (selected, coverage) = Coverage::select_recent_files(&git_log, &head, 5);
+        assert_eq!(selected, head);
+        assert_eq!(coverage.changed_since, Some("100".to_owned()));
+    }
+
+    #[test]
+    fn test_summary() {
+        let mut coverage = Coverage::new(10, 8);
+        assert_eq!(coverage.files_analyzed, 8);
+        assert_eq!(coverage.files_ignored, 2);
+
+        coverage.files_munched = 5;
+        coverage.files_skipped = 1;
+        coverage.files_unrecognized = 2;
+        assert_eq!(coverage.summary(), "5 of 10 files munched, 1 skipped, 2 unrecognized, 2 ignored");
+
+        coverage.files_analyzed = 6;
+        assert!(coverage.summary().ends_with(", 2 not analyzed"));
+
+        coverage.sparse_checkout = true;
+        coverage.partial_clone_filter = Some("blob:none".to_owned());
+        assert!(coverage
+            .summary()
+            .ends_with(", 2 not analyzed, sparse checkout, partial clone (blob:none)"));
+    }
+}
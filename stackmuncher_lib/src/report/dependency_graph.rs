@@ -0,0 +1,238 @@
+use super::import_graph::LocalImports;
+use super::report::Report;
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+/// The max number of owners listed per file node, most recent first.
+const MAX_OWNERS_PER_NODE: usize = 5;
+
+/// A graph of dependencies between project files built from the local imports captured in `Tech.local_deps`.
+/// Optionally includes edges to external packages and namespaces from `Tech.refs` and `Tech.pkgs`.
+/// Nodes and edges are sorted for a stable output.
+#[derive(Serialize, Debug, Default)]
+pub struct DependencyGraph {
+    pub nodes: Vec<DependencyNode>,
+    pub edges: Vec<DependencyEdge>,
+}
+
+/// A project file or an external package.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct DependencyNode {
+    /// The file name with the relative path, e.g. `src/utils.py`, or the package name for external nodes.
+    pub id: String,
+    /// True for packages and namespaces outside of the project
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub external: bool,
+    /// The same as `Tech.language`. Empty for external nodes.
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub language: String,
+    /// Lines of code in the file, excluding blank lines and comments. Zero for external nodes.
+    pub loc: u64,
+    /// Git identities of contributors who touched the file, the latest first. Empty for external nodes.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub owners: Vec<String>,
+}
+
+/// A dependency of `from` file on `to` file or package.
+#[derive(Serialize, Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct DependencyEdge {
+    pub from: String,
+    pub to: String,
+}
+
+impl DependencyGraph {
+    /// Builds the graph from `per_file_tech` and `contributors` of a project report.
+    /// Files with no dependencies in either direction are not included.
+    pub fn from_report(report: &Report, include_external: bool) -> Self {
+        // file name -> (git_id, date of the last touch) for the owners attribute
+        let mut owners: HashMap<&String, Vec<(&String, i64)>> = HashMap::new();
+        if let Some(contributors) = &report.contributors {
+            for contributor in contributors {
+                for touched_file in &contributor.touched_files {
+                    owners
+                        .entry(&touched_file.name)
+                        .or_default()
+                        .push((&contributor.git_id, touched_file.date_epoch));
+                }
+            }
+        }
+
+        // the same edges and per-file LoC as in the import graph of the report
+        let local_imports = LocalImports::from_per_file_tech(&report.per_file_tech);
+        let mut edges = local_imports
+            .imports
+            .iter()
+            .map(|(from, to)| DependencyEdge {
+                from: from.to_string(),
+                to: to.to_string(),
+            })
+            .collect::<BTreeSet<DependencyEdge>>();
+
+        let mut external_nodes: BTreeSet<String> = BTreeSet::new();
+        if include_external {
+            for tech in &report.per_file_tech {
+                if let Some(file_name) = &tech.file_name {
+                    for kwc in tech.refs.iter().chain(tech.pkgs.iter()) {
+                        external_nodes.insert(kwc.k.clone());
+                        edges.insert(DependencyEdge {
+                            from: file_name.clone(),
+                            to: kwc.k.clone(),
+                        });
+                    }
+                }
+            }
+        }
+
+        // only files that are connected to something make it into the graph
+        let connected_files = edges
+            .iter()
+            .flat_map(|edge| [&edge.from, &edge.to])
+            .filter(|v| !external_nodes.contains(*v))
+            .collect::<BTreeSet<&String>>();
+
+        let mut nodes: BTreeMap<String, DependencyNode> = BTreeMap::new();
+        for file_name in connected_files {
+            let mut file_owners = owners.get(file_name).cloned().unwrap_or_default();
+            file_owners.sort_unstable_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(b.0)));
+            // a dependency may point at a file that was not munched, e.g. JSON data
+            let totals = local_imports.files.get(file_name.as_str());
+
+            nodes.insert(
+                file_name.clone(),
+                DependencyNode {
+                    id: file_name.clone(),
+                    external: false,
+                    language: totals.map(|v| v.language.to_owned()).unwrap_or_default(),
+                    loc: totals.map(|v| v.loc).unwrap_or_default(),
+                    owners: file_owners
+                        .into_iter()
+                        .take(MAX_OWNERS_PER_NODE)
+                        .map(|(git_id, _)| git_id.clone())
+                        .collect(),
+                },
+            );
+        }
+        for package in external_nodes {
+            nodes.insert(
+                package.clone(),
+                DependencyNode {
+                    id: package,
+                    external: true,
+                    language: String::new(),
+                    loc: 0,
+                    owners: Vec::new(),
+                },
+            );
+        }
+
+        Self {
+            nodes: nodes.into_values().collect(),
+            edges: edges.into_iter().collect(),
+        }
+    }
+
+    /// Returns the graph in Graphviz DOT format with LoC and owners as node attributes.
+    /// External packages are drawn as boxes.
+    pub fn to_dot(&self) -> String {
+        let mut dot = String::from("digraph dependencies {\n    rankdir=LR;\n    node [shape=ellipse];\n");
+
+        for node in &self.nodes {
+            let attributes = if node.external {
+                "shape=box, style=dashed".to_owned()
+            } else {
+                // non-standard attributes are ignored by Graphviz, but are retained for other tools
+                format!(
+                    "label=\"{id}\\n{language} {loc} LoC\", language=\"{language}\", loc={loc}, owners=\"{owners}\"",
+                    id = escape_dot(&node.id),
+                    language = escape_dot(&node.language),
+                    loc = node.loc,
+                    owners = escape_dot(&node.owners.join(", "))
+                )
+            };
+            dot.push_str(&format!("    \"{}\" [{}];\n", escape_dot(&node.id), attributes));
+        }
+
+        for edge in &self.edges {
+            dot.push_str(&format!("    \"{}\" -> \"{}\";\n", escape_dot(&edge.from), escape_dot(&edge.to)));
+        }
+
+        dot.push_str("}\n");
+        dot
+    }
+}
+
+/// Escapes a value for use inside a double-quoted DOT string.
+fn escape_dot(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+#[cfg(test)]
+mod test_dependency_graph {
+    use super::DependencyGraph;
+    use crate::report::{Report, Tech};
+
+    #[test]
+    fn test_dependency_graph() {
+        let mut report = Report::new();
+        let python = Tech::test("Python", "python").with_loc(10);
+        report.per_file_tech.insert(
+            python
+                .clone()
+                .with_file("app/main.py")
+                .with_local_deps(&["app/utils.py"])
+                .with_refs(&[("requests", 1)]),
+        );
+        report.per_file_tech.insert(python.clone().with_file("app/utils.py"));
+        report
+            .per_file_tech
+            .insert(python.with_file("setup.py").with_refs(&[("setuptools", 1)]));
+
+        let graph = DependencyGraph::from_report(&report, false);
+        assert_eq!(graph.nodes.len(), 2);
+        assert_eq!(graph.edges.len(), 1);
+        assert_eq!(graph.nodes[0].id, "app/main.py");
+        assert_eq!(graph.nodes[0].loc, 10);
+
+        let dot = graph.to_dot();
+        assert!(dot.starts_with("digraph dependencies {"));
+        assert!(dot.contains("    \"app/main.py\" -> \"app/utils.py\";\n"));
+
+        // external packages connect setup.py to the graph
+        let graph = DependencyGraph::from_report(&report, true);
+        assert_eq!(graph.nodes.len(), 5);
+        assert_eq!(graph.edges.len(), 3);
+        assert!(graph.nodes.iter().any(|v| v.id == "setuptools" && v.external));
+    }
+
+    #[test]
+    fn test_dependency_graph_multi_language() {
+        // a template has a record per language and imports itself
+        let mut report = Report::new();
+        for (language, loc) in &[("HTML", 40), ("eRuby", 80), ("Ruby", 80)] {
+            report.per_file_tech.insert(
+                Tech::test(language, &language.to_lowercase())
+                    .with_file("views/index.html.erb")
+                    .with_loc(*loc)
+                    .with_local_deps(&["views/index.html.erb", "app/helpers.rb"]),
+            );
+        }
+        report
+            .per_file_tech
+            .insert(Tech::test("Ruby", "ruby").with_file("app/helpers.rb").with_loc(10));
+
+        let graph = DependencyGraph::from_report(&report, false);
+        assert_eq!(
+            graph
+                .edges
+                .iter()
+                .map(|v| (v.from.as_str(), v.to.as_str()))
+                .collect::<Vec<_>>(),
+            vec![("views/index.html.erb", "app/helpers.rb")]
+        );
+        assert_eq!(graph.nodes.len(), 2);
+        assert_eq!(graph.nodes[1].id, "views/index.html.erb");
+        assert_eq!(graph.nodes[1].loc, 200);
+        assert_eq!(graph.nodes[1].language, "Ruby");
+        assert_eq!(graph.nodes[0].language, "Ruby");
+    }
+}
@@ -0,0 +1,162 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+
+/// Documentation generators detected by the name of their config file, lower case.
+const DOC_GENERATORS: [(&str, &str); 12] = [
+    ("mkdocs.yml", "mkdocs"),
+    ("mkdocs.yaml", "mkdocs"),
+    ("docusaurus.config.js", "docusaurus"),
+    ("docusaurus.config.ts", "docusaurus"),
+    ("conf.py", "sphinx"),
+    ("doxyfile", "doxygen"),
+    ("typedoc.json", "typedoc"),
+    ("jsdoc.json", "jsdoc"),
+    (".jsdoc.json", "jsdoc"),
+    ("book.toml", "mdbook"),
+    ("antora.yml", "antora"),
+    ("docfx.json", "docfx"),
+];
+
+/// Top level folders with project documentation, lower case.
+const DOC_FOLDERS: [&str; 3] = ["docs/", "doc/", "documentation/"];
+
+/// Structure-level facts about the project documentation taken from the tree at HEAD and the README file.
+/// No file contents are stored.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, Eq, PartialEq)]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
+pub struct Documentation {
+    /// The name of the README file in the root of the project, e.g. `README.md`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub readme_file: Option<String>,
+    /// Size of the README file in bytes
+    pub readme_bytes: u64,
+    /// Number of lines in the README file
+    pub readme_lines: u64,
+    /// Number of Markdown headings in the README file, excluding code blocks
+    pub readme_sections: u64,
+    /// Number of files in the top level docs/, doc/ or documentation/ folder
+    pub docs_files: u64,
+    /// There is a CHANGELOG or CHANGES file in the root of the project
+    pub changelog: bool,
+    /// There is a CONTRIBUTING file anywhere in the project, e.g. in .github/ folder
+    pub contributing: bool,
+    /// Names of documentation generators with config files in the project, e.g. `mkdocs`, `sphinx`. Sorted a-z.
+    #[serde(skip_serializing_if = "Vec::is_empty", default)]
+    pub doc_generators: Vec<String>,
+}
+
+impl Documentation {
+    /// Collects documentation facts from the list of files in the project tree.
+    /// README stats have to be added separately with `add_readme_stats`.
+    pub(crate) fn from_tree_files(tree_files: &HashSet<String>) -> Self {
+        let mut documentation = Self::default();
+        let mut doc_generators: HashSet<&str> = HashSet::new();
+
+        for file_name in tree_files {
+            let file_name_lc = file_name.to_lowercase();
+            let (dir, base_name) = match file_name_lc.rfind('/') {
+                Some(idx) => (&file_name_lc[..idx + 1], &file_name_lc[idx + 1..]),
+                None => ("", file_name_lc.as_str()),
+            };
+
+            if dir.is_empty() {
+                // there may be more than one README, e.g. README.md and README.rst, so pick the same one every time
+                if is_readme(base_name)
+                    && (documentation.readme_file.is_none() || documentation.readme_file.as_ref() > Some(file_name))
+                {
+                    documentation.readme_file = Some(file_name.clone());
+                }
+
+                if base_name.starts_with("changelog") || base_name.starts_with("changes") {
+                    documentation.changelog = true;
+                }
+            }
+
+            if DOC_FOLDERS.iter().any(|folder| dir.starts_with(folder)) {
+                documentation.docs_files += 1;
+            }
+
+            if base_name.starts_with("contributing") {
+                documentation.contributing = true;
+            }
+
+            // Sphinx's conf.py is too generic a name to count it outside of doc folders
+            if let Some((_, generator)) = DOC_GENERATORS.iter().find(|(config_file, _)| *config_file == base_name) {
+                if *generator != "sphinx" || DOC_FOLDERS.iter().any(|folder| dir.starts_with(folder)) {
+                    doc_generators.insert(generator);
+                }
+            }
+        }
+
+        documentation.doc_generators = doc_generators.into_iter().map(|v| v.to_owned()).collect();
+        documentation.doc_generators.sort_unstable();
+
+        documentation
+    }
+
+    /// Adds size and the number of sections from the contents of the README file.
+    pub(crate) fn add_readme_stats(&mut self, contents: &[u8]) {
+        let contents = String::from_utf8_lossy(contents);
+
+        self.readme_bytes = contents.len() as u64;
+        self.readme_lines = contents.lines().count() as u64;
+
+        // count ATX headings (# Heading) outside of fenced code blocks where # is a comment
+        let mut is_code_block = false;
+        self.readme_sections = 0;
+        for line in contents.lines() {
+            let line = line.trim_start();
+            if line.starts_with("```") || line.starts_with("~~~") {
+                is_code_block = !is_code_block;
+            } else if !is_code_block && line.starts_with('#') && line.trim_start_matches('#').starts_with(' ') {
+                self.readme_sections += 1;
+            }
+        }
+    }
+}
+
+/// Returns true if the lower case file name is a README in one of the text formats, e.g. `readme.md`.
+fn is_readme(file_name: &str) -> bool {
+    match file_name.strip_prefix("readme") {
+        Some(ext) => ["", ".md", ".markdown", ".rst", ".adoc", ".asciidoc", ".org"].contains(&ext),
+        None => false,
+    }
+}
+
+#[cfg(test)]
+mod test_documentation {
+    use super::Documentation;
+    use std::collections::HashSet;
+
+    #[test]
+    fn test_documentation() {
+        let tree_files = [
+            "README.md",
+            "CHANGELOG.md",
+            ".github/CONTRIBUTING.md",
+            "docs/index.md",
+            "docs/conf.py",
+            "docs/api/usage.md",
+            "mkdocs.yml",
+            "src/conf.py",
+            "src/readme.md",
+        ]
+        .iter()
+        .map(|v| v.to_string())
+        .collect::<HashSet<String>>();
+
+        let mut documentation = Documentation::from_tree_files(&tree_files);
+        assert_eq!(documentation.readme_file.as_deref(), Some("README.md"));
+        assert_eq!(documentation.docs_files, 3);
+        assert!(documentation.changelog);
+        assert!(documentation.contributing);
+        assert_eq!(documentation.doc_generators, vec!["mkdocs", "sphinx"]);
+
+        documentation.add_readme_stats(b"# Title\n\nIntro\n\n## Usage\n\n```sh\n# not a heading\n```\n#hashtag\n");
+        assert_eq!(documentation.readme_lines, 10);
+        assert_eq!(documentation.readme_sections, 2);
+
+        let documentation = Documentation::from_tree_files(&HashSet::new());
+        assert_eq!(documentation, Documentation::default());
+    }
+}
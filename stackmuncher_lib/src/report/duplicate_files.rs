@@ -0,0 +1,188 @@
+use super::tech::Tech;
+use crate::git::ListOfBlobs;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+/// The max number of blobs in `DuplicateFiles.top`.
+pub const TOP_DUPLICATE_BLOBS: usize = 10;
+
+/// Files with identical contents under several paths at HEAD, e.g. vendored or copy-pasted libraries.
+/// Only present in project reports because file names are sensitive.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
+pub struct DuplicateFiles {
+    /// Number of blobs found under more than one path.
+    pub blobs: u64,
+    /// Number of extra copies, i.e. all paths of the duplicated blobs less one per blob.
+    pub copies: u64,
+    /// Lines of code in the extra copies.
+    pub loc: u64,
+    /// The extra copies were left out of `tech` totals. See `Duplicates` in `.stackmuncher.toml`.
+    pub counted_once: bool,
+    /// The blobs with the most LoC in their extra copies, the largest first.
+    #[serde(skip_serializing_if = "Vec::is_empty", default)]
+    pub top: Vec<DuplicateBlob>,
+}
+
+/// A blob found under more than one path.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
+pub struct DuplicateBlob {
+    pub sha1: String,
+    /// Lines of code in a single copy.
+    pub loc: u64,
+    /// All paths of the blob, sorted. The first one is the copy counted in `tech` totals.
+    pub file_names: Vec<String>,
+}
+
+impl DuplicateFiles {
+    /// Groups the munched files from `per_file_tech` by their blob SHA1 in `blobs`. Empty files are not counted
+    /// because they all share the same blob. Returns None if there are no duplicates, otherwise the stats and
+    /// the sorted paths of every duplicated blob. See `extra_copies()`.
+    pub fn from_blobs(
+        blobs: &ListOfBlobs,
+        per_file_tech: &HashSet<Tech>,
+        counted_once: bool,
+    ) -> Option<(Self, Vec<Vec<String>>)> {
+        // a file may have several records, e.g. templates with the host language
+        let mut file_loc: HashMap<&str, u64> = HashMap::new();
+        for tech in per_file_tech.iter().filter(|tech| tech.total_lines > 0) {
+            if let Some(file_name) = tech.file_name.as_deref() {
+                *file_loc.entry(file_name).or_default() += tech.code_lines;
+            }
+        }
+
+        // blob SHA1 -> file names, sorted for a stable choice of the counted copy
+        let mut blob_files: BTreeMap<&str, Vec<&str>> = BTreeMap::new();
+        for (file_name, blob) in blobs {
+            if !blob.sha1.is_empty() && file_loc.contains_key(file_name.as_str()) {
+                blob_files.entry(&blob.sha1).or_default().push(file_name);
+            }
+        }
+
+        let mut duplicate_files = Self {
+            counted_once,
+            ..Default::default()
+        };
+        let mut groups = Vec::new();
+        for (sha1, mut file_names) in blob_files.into_iter().filter(|(_, v)| v.len() > 1) {
+            file_names.sort_unstable();
+            let loc = file_loc.get(file_names[0]).copied().unwrap_or_default();
+            let copies = file_names.len() as u64 - 1;
+
+            duplicate_files.blobs += 1;
+            duplicate_files.copies += copies;
+            duplicate_files.loc += loc * copies;
+            groups.push(file_names.iter().map(|v| v.to_string()).collect());
+            duplicate_files.top.push(DuplicateBlob {
+                sha1: sha1.to_owned(),
+                loc,
+                file_names: file_names.into_iter().map(|v| v.to_owned()).collect(),
+            });
+        }
+
+        if duplicate_files.blobs == 0 {
+            return None;
+        }
+
+        duplicate_files.top.sort_unstable_by(|x, y| {
+            (y.loc * (y.file_names.len() as u64 - 1))
+                .cmp(&(x.loc * (x.file_names.len() as u64 - 1)))
+                .then_with(|| y.file_names.len().cmp(&x.file_names.len()))
+                .then_with(|| x.sha1.cmp(&y.sha1))
+        });
+        duplicate_files.top.truncate(TOP_DUPLICATE_BLOBS);
+
+        Some((duplicate_files, groups))
+    }
+
+    /// Returns the copies of duplicate files in `groups` to leave out of `tech` totals, i.e. all but the first path
+    /// of every group found in `file_names`. Contributor reports have only some of the paths, so a contributor who
+    /// changed only one of the copies still gets it counted.
+    pub(crate) fn extra_copies<'a>(groups: &'a [Vec<String>], file_names: &HashSet<&str>) -> HashSet<&'a str> {
+        groups
+            .iter()
+            .flat_map(|group| {
+                group
+                    .iter()
+                    .map(|v| v.as_str())
+                    .filter(|file_name| file_names.contains(file_name))
+                    .skip(1)
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test_duplicate_files {
+    use super::DuplicateFiles;
+    use crate::git::{GitBlob, ListOfBlobs};
+    use crate::report::tech::Tech;
+    use std::collections::HashSet;
+
+    #[test]
+    fn test_duplicate_files() {
+        let files = [
+            ("vendor/a/jquery.js", "aaa", 1000),
+            ("vendor/b/jquery.js", "aaa", 1000),
+            ("static/jquery.js", "aaa", 1000),
+            ("src/util.js", "bbb", 20),
+            ("lib/util.js", "bbb", 20),
+            ("src/main.js", "ccc", 50),
+            ("src/empty.js", "e69", 0),
+            ("lib/empty.js", "e69", 0),
+        ];
+        let blobs = files
+            .iter()
+            .map(|(file_name, sha1, _)| {
+                let blob = GitBlob {
+                    sha1: sha1.to_string(),
+                    commit_sha1: String::new(),
+                    commit_date_epoch: 0,
+                    commit_date_iso: String::new(),
+                };
+                (file_name.to_string(), blob)
+            })
+            .collect::<ListOfBlobs>();
+        let per_file_tech = files
+            .iter()
+            .map(|(file_name, _, loc)| Tech::test("JavaScript", "js").with_file(file_name).with_loc(*loc))
+            .collect::<HashSet<Tech>>();
+
+        let (duplicate_files, groups) = DuplicateFiles::from_blobs(&blobs, &per_file_tech, true).unwrap();
+        let all_files = files
+            .iter()
+            .map(|(file_name, _, _)| *file_name)
+            .collect::<HashSet<&str>>();
+        let extra_copies = DuplicateFiles::extra_copies(&groups, &all_files);
+        assert_eq!(duplicate_files.blobs, 2);
+        assert_eq!(duplicate_files.copies, 3);
+        assert_eq!(duplicate_files.loc, 2020);
+        assert_eq!(duplicate_files.top[0].sha1, "aaa");
+        // the first path in alphabetical order is the counted copy
+        assert_eq!(duplicate_files.top[0].file_names[0], "static/jquery.js");
+        assert_eq!(duplicate_files.top[1].file_names, vec!["lib/util.js", "src/util.js"]);
+        assert_eq!(extra_copies.len(), 3);
+        assert!(extra_copies.contains("src/util.js"));
+        assert!(!extra_copies.contains("static/jquery.js"));
+
+        // a report with only some of the copies counts the first of those
+        let some_files = ["vendor/b/jquery.js", "vendor/a/jquery.js", "src/util.js"]
+            .iter()
+            .copied()
+            .collect();
+        let extra_copies = DuplicateFiles::extra_copies(&groups, &some_files);
+        assert_eq!(extra_copies, ["vendor/b/jquery.js"].iter().copied().collect());
+
+        // no duplicates among the files with contents
+        let js = Tech::test("JavaScript", "js");
+        let per_file_tech = vec![
+            js.clone().with_file("src/main.rs").with_loc(10),
+            js.clone().with_file("src/empty.js"),
+            js.with_file("lib/empty.js"),
+        ]
+        .into_iter()
+        .collect::<HashSet<Tech>>();
+        assert!(DuplicateFiles::from_blobs(&blobs, &per_file_tech, true).is_none());
+    }
+}
@@ -0,0 +1,175 @@
+use super::tech::Tech;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use tracing::error;
+
+/// Patterns of references and packages per framework. See the description inside the file for the matching rules.
+const FRAMEWORK_RULES: &str = include_str!("../../stm_rules/frameworks.json");
+
+/// How likely the project uses the framework, e.g. `low` for a package that also has non-framework uses.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "lowercase")]
+pub enum FrameworkConfidence {
+    Low,
+    Medium,
+    High,
+}
+
+/// A framework detected from the libraries and packages the code refers to, e.g. `Django` for `from django.db import models`.
+/// Employers search by framework names rather than library names.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
+pub struct Framework {
+    /// The name from `stm_rules/frameworks.json`, e.g. `Spring Boot`.
+    pub name: String,
+    /// The highest confidence of the matched references and packages.
+    pub confidence: FrameworkConfidence,
+    /// Languages of the files with the matches, e.g. `JavaScript` and `TypeScript` for React.
+    pub languages: BTreeSet<String>,
+    /// Number of matched references and package declarations.
+    pub refs: u64,
+}
+
+/// Patterns for a single framework as they are stored in `stm_rules/frameworks.json`.
+#[derive(Deserialize, Default)]
+struct FrameworkRule {
+    /// Package names from manifests, e.g. `actix-web` from Cargo.toml.
+    #[serde(default)]
+    pkgs: BTreeMap<String, FrameworkConfidence>,
+    /// References from the code, e.g. `django` from `import django`.
+    #[serde(default)]
+    refs: BTreeMap<String, FrameworkConfidence>,
+}
+
+impl Framework {
+    /// Matches `refs` and `pkgs` of all `tech` records against the framework rules. Returns the frameworks sorted
+    /// by confidence, highest first, and then by name. Returns an empty list if none were found.
+    pub fn from_tech<'a, I>(tech: I) -> Vec<Self>
+    where
+        I: Iterator<Item = &'a Tech>,
+    {
+        let rules = framework_rules();
+        let mut frameworks: BTreeMap<&str, Self> = BTreeMap::new();
+
+        for tech in tech {
+            for (name, rule) in &rules {
+                let matches = tech
+                    .pkgs
+                    .iter()
+                    .filter_map(|kw| match_pattern(&rule.pkgs, &kw.k).map(|confidence| (confidence, kw.c)))
+                    .chain(
+                        tech.refs
+                            .iter()
+                            .filter_map(|kw| match_pattern(&rule.refs, &kw.k).map(|confidence| (confidence, kw.c))),
+                    );
+
+                for (confidence, count) in matches {
+                    let framework = frameworks.entry(name.as_str()).or_insert_with(|| Self {
+                        name: name.clone(),
+                        confidence,
+                        languages: BTreeSet::new(),
+                        refs: 0,
+                    });
+                    framework.confidence = framework.confidence.max(confidence);
+                    framework.languages.insert(tech.language.clone());
+                    framework.refs += count;
+                }
+            }
+        }
+
+        let mut frameworks = frameworks.into_values().collect::<Vec<Self>>();
+        frameworks.sort_by(|a, b| b.confidence.cmp(&a.confidence).then_with(|| a.name.cmp(&b.name)));
+
+        frameworks
+    }
+}
+
+/// Returns the confidence of the longest pattern matching `name` or None if no patterns match.
+/// A pattern matches the whole name or its start followed by `.`, `::` or `/`, ignoring case,
+/// e.g. `org.springframework` matches `org.springframework.boot.SpringApplication`, but not `org.springframeworks`.
+fn match_pattern(patterns: &BTreeMap<String, FrameworkConfidence>, name: &str) -> Option<FrameworkConfidence> {
+    let name = name.to_lowercase();
+    patterns
+        .iter()
+        .filter(|(pattern, _)| {
+            let pattern = pattern.to_lowercase();
+            match name.strip_prefix(&pattern) {
+                Some(rest) => {
+                    rest.is_empty() || rest.starts_with('.') || rest.starts_with("::") || rest.starts_with('/')
+                }
+                None => false,
+            }
+        })
+        .max_by_key(|(pattern, _)| pattern.len())
+        .map(|(_, confidence)| *confidence)
+}
+
+/// Returns the framework rules by the framework name. Returns an empty list if the rules cannot be parsed.
+fn framework_rules() -> BTreeMap<String, FrameworkRule> {
+    #[derive(Deserialize)]
+    struct FrameworkRules {
+        frameworks: BTreeMap<String, FrameworkRule>,
+    }
+
+    match serde_json::from_str::<FrameworkRules>(FRAMEWORK_RULES) {
+        Ok(v) => v.frameworks,
+        Err(e) => {
+            error!("Cannot parse frameworks.json due to {}. It's a bug.", e);
+            BTreeMap::new()
+        }
+    }
+}
+
+#[cfg(test)]
+mod test_frameworks {
+    use super::{framework_rules, match_pattern, Framework, FrameworkConfidence};
+    use crate::report::Tech;
+
+    #[test]
+    fn test_match_pattern() {
+        let rules = framework_rules();
+        assert!(!rules.is_empty());
+
+        let spring = &rules["Spring"].refs;
+        assert_eq!(
+            match_pattern(spring, "org.springframework.boot.SpringApplication"),
+            Some(FrameworkConfidence::High)
+        );
+        assert_eq!(match_pattern(spring, "org.springframeworks.Other"), None);
+
+        // the longest pattern wins
+        let actix = &rules["Actix"].pkgs;
+        assert_eq!(match_pattern(actix, "actix-web"), Some(FrameworkConfidence::High));
+        assert_eq!(match_pattern(actix, "actix"), Some(FrameworkConfidence::Low));
+        assert_eq!(match_pattern(actix, "actix-rt"), None);
+
+        assert_eq!(match_pattern(&rules["Django"].pkgs, "Django"), Some(FrameworkConfidence::High));
+    }
+
+    #[test]
+    fn test_from_tech() {
+        let tech = [
+            Tech {
+                files: 2,
+                ..Tech::test("Python", "python.py").with_refs(&[("django.db", 3), ("django.http", 1), ("requests", 2)])
+            },
+            Tech::test("JavaScript", "js").with_refs(&[("react", 2), ("next/router", 1)]),
+            Tech::test("TypeScript", "ts").with_refs(&[("react", 1)]),
+            Tech::test("Rust", "rust.cargo.toml").with_pkgs(&[("actix", None, 1), ("serde", None, 1)]),
+        ];
+
+        let frameworks = Framework::from_tech(tech.iter());
+        let names = frameworks.iter().map(|v| v.name.as_str()).collect::<Vec<&str>>();
+        assert_eq!(names, vec!["Django", "React", "Next.js", "Actix"]);
+
+        assert_eq!(frameworks[0].refs, 4);
+        assert_eq!(frameworks[0].languages.iter().collect::<Vec<_>>(), vec!["Python"]);
+        assert_eq!(frameworks[1].refs, 3);
+        assert_eq!(frameworks[1].languages.len(), 2);
+        assert_eq!(frameworks[2].confidence, FrameworkConfidence::Medium);
+        assert_eq!(frameworks[3].confidence, FrameworkConfidence::Low);
+
+        assert!(Framework::from_tech(std::iter::empty::<&Tech>()).is_empty());
+    }
+}
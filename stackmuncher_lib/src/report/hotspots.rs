@@ -0,0 +1,168 @@
+use super::tech::Tech;
+use crate::git::GitLogEntry;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+
+/// The max number of files in `Hotspots.files`.
+pub const TOP_HOTSPOTS: usize = 20;
+
+/// Files that are both large and frequently changed, which makes them the first candidates for refactoring.
+/// Only present in project reports because file names are sensitive.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
+pub struct Hotspots {
+    /// Number of commits with at least one file included in the calculation.
+    pub commits_analyzed: u64,
+    /// The hottest files, the highest `score` first.
+    #[serde(skip_serializing_if = "Vec::is_empty", default)]
+    pub files: Vec<Hotspot>,
+}
+
+/// A file from the tree at HEAD with its churn and size.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
+pub struct Hotspot {
+    pub file_name: String,
+    /// Number of commits that changed the file.
+    pub commits: u64,
+    /// Lines of code at HEAD in all languages of the file, excluding comments, blank and bracket-only lines.
+    pub loc: u64,
+    /// `commits` × `loc`.
+    pub score: u64,
+}
+
+impl Hotspots {
+    /// Ranks the munched files at HEAD from `per_file_tech` by the number of commits in the log that changed them
+    /// times their LoC. A file with several records, e.g. eRuby and HTML in a template, has the LoC of all its records.
+    /// Returns None if there are no commits or no files with code.
+    pub fn from_log(git_log: &[GitLogEntry], per_file_tech: &HashSet<Tech>) -> Option<Self> {
+        // LoC of every munched file, e.g. `views/index.html.erb` -> 120 for 80 lines of eRuby and 40 lines of HTML
+        let mut file_loc: HashMap<&str, u64> = HashMap::new();
+        for tech in per_file_tech.iter().filter(|tech| tech.code_lines > 0) {
+            if let Some(file_name) = tech.file_name.as_ref() {
+                *file_loc.entry(file_name.as_str()).or_default() += tech.code_lines;
+            }
+        }
+
+        let mut commits_analyzed = 0u64;
+        let mut file_commits: HashMap<&str, u64> = HashMap::new();
+        for log_entry in git_log {
+            let mut counted = false;
+            for file_name in &log_entry.files {
+                if let Some((file_name, _)) = file_loc.get_key_value(file_name.as_str()) {
+                    *file_commits.entry(file_name).or_default() += 1;
+                    counted = true;
+                }
+            }
+            if counted {
+                commits_analyzed += 1;
+            }
+        }
+
+        if file_commits.is_empty() {
+            return None;
+        }
+
+        let mut files = file_commits
+            .into_iter()
+            .map(|(file_name, commits)| {
+                let loc = file_loc.get(file_name).cloned().unwrap_or_default();
+                Hotspot {
+                    file_name: file_name.to_owned(),
+                    commits,
+                    loc,
+                    score: commits * loc,
+                }
+            })
+            .collect::<Vec<Hotspot>>();
+
+        files.sort_unstable_by(|x, y| {
+            y.score
+                .cmp(&x.score)
+                .then_with(|| y.commits.cmp(&x.commits))
+                .then_with(|| x.file_name.cmp(&y.file_name))
+        });
+        files.truncate(TOP_HOTSPOTS);
+
+        Some(Self {
+            commits_analyzed,
+            files,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test_hotspots {
+    use super::Hotspots;
+    use crate::git::GitLogEntry;
+    use crate::report::tech::Tech;
+    use std::collections::HashSet;
+
+    /// Returns a log entry with the given files.
+    fn commit(files: &[&str]) -> GitLogEntry {
+        let mut entry = GitLogEntry::new();
+        entry.files = files.iter().map(|v| v.to_string()).collect();
+        entry
+    }
+
+    #[test]
+    fn test_hotspots() {
+        let rust = Tech::test("Rust", "rust");
+        let per_file_tech = vec![
+            rust.clone().with_file("src/main.rs").with_loc(50),
+            rust.clone().with_file("src/report.rs").with_loc(400),
+            rust.clone().with_file("src/utils.rs").with_loc(30),
+            rust.with_file("docs/empty.rs"),
+        ]
+        .into_iter()
+        .collect::<HashSet<Tech>>();
+        assert!(Hotspots::from_log(&[], &per_file_tech).is_none());
+
+        let git_log = vec![
+            commit(&["src/main.rs", "src/utils.rs"]),
+            commit(&["src/main.rs", "README.md"]),
+            commit(&["src/main.rs"]),
+            commit(&["src/report.rs"]),
+            commit(&["src/deleted.rs", "docs/empty.rs"]),
+        ];
+
+        let hotspots = Hotspots::from_log(&git_log, &per_file_tech).unwrap();
+        assert_eq!(hotspots.commits_analyzed, 4);
+
+        let files = hotspots
+            .files
+            .iter()
+            .map(|v| (v.file_name.as_str(), v.commits, v.score))
+            .collect::<Vec<(&str, u64, u64)>>();
+        assert_eq!(
+            files,
+            vec![
+                ("src/report.rs", 1, 400),
+                ("src/main.rs", 3, 150),
+                ("src/utils.rs", 1, 30)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_hotspots_template() {
+        // a template has a record per language
+        let per_file_tech = vec![
+            Tech::test("eRuby", "erb")
+                .with_file("views/index.html.erb")
+                .with_loc(80),
+            Tech::test("HTML", "html")
+                .with_file("views/index.html.erb")
+                .with_loc(40),
+        ]
+        .into_iter()
+        .collect::<HashSet<Tech>>();
+        assert_eq!(per_file_tech.len(), 2);
+
+        let git_log = vec![commit(&["views/index.html.erb"]), commit(&["views/index.html.erb"])];
+        let hotspots = Hotspots::from_log(&git_log, &per_file_tech).unwrap();
+        assert_eq!(hotspots.files.len(), 1);
+        assert_eq!(hotspots.files[0].loc, 120);
+        assert_eq!(hotspots.files[0].score, 240);
+    }
+}
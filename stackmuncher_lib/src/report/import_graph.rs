@@ -0,0 +1,220 @@
+use super::tech::Tech;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeSet, HashMap, HashSet};
+
+/// The max number of files in `ImportGraph.central_modules`.
+pub const TOP_CENTRAL_MODULES: usize = 20;
+
+/// Fan-in and fan-out of project files built from their local imports captured in `Tech.local_deps`.
+/// The most central files are what the rest of the project is built around.
+/// Only present in project reports because file names are sensitive.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
+pub struct ImportGraph {
+    /// Number of files that import or are imported by another project file.
+    pub modules: u64,
+    /// Number of distinct imports between project files.
+    pub imports: u64,
+    /// The most central files, the highest `fan_in + fan_out` first.
+    #[serde(skip_serializing_if = "Vec::is_empty", default)]
+    pub central_modules: Vec<ModuleCentrality>,
+}
+
+/// A project file with the number of its imports in both directions.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
+pub struct ModuleCentrality {
+    pub file_name: String,
+    /// Number of project files that import this file.
+    pub fan_in: u64,
+    /// Number of project files imported by this file.
+    pub fan_out: u64,
+    /// Number of distinct external packages and namespaces referenced by this file.
+    pub ext_refs: u64,
+    /// Lines of code at HEAD, excluding comments, blank and bracket-only lines. Zero if the file was not munched.
+    pub loc: u64,
+}
+
+/// Imports between project files and per-file totals from `per_file_tech`, shared by `ImportGraph` and
+/// `DependencyGraph` so that both count the same edges and LoC.
+pub(crate) struct LocalImports<'a> {
+    /// Importer -> imported, sorted and deduplicated for a stable output. A file importing itself is not included.
+    pub imports: BTreeSet<(&'a str, &'a str)>,
+    /// File name -> totals over all records of the file. A file may have records for several languages,
+    /// e.g. a template and its host language. Imported files that were not munched, e.g. JSON data, are not included.
+    pub files: HashMap<&'a str, FileTotals<'a>>,
+}
+
+/// Totals over all per-file records of a file.
+pub(crate) struct FileTotals<'a> {
+    /// The language with the most LoC, the first by name on a tie.
+    pub language: &'a str,
+    /// Lines of code in all languages of the file.
+    pub loc: u64,
+    /// Number of external packages and namespaces referenced by the file.
+    pub ext_refs: u64,
+    /// LoC of `language`.
+    language_loc: u64,
+}
+
+impl<'a> LocalImports<'a> {
+    /// Collects the local imports and file totals from the munched files in `per_file_tech`.
+    pub(crate) fn from_per_file_tech(per_file_tech: &'a HashSet<Tech>) -> Self {
+        let mut imports = BTreeSet::new();
+        let mut files: HashMap<&str, FileTotals> = HashMap::new();
+        for tech in per_file_tech {
+            let file_name = match &tech.file_name {
+                Some(v) => v.as_str(),
+                None => continue,
+            };
+
+            imports.extend(
+                tech.local_deps
+                    .iter()
+                    .filter(|local_dep| *local_dep != file_name)
+                    .map(|local_dep| (file_name, local_dep.as_str())),
+            );
+
+            let totals = files.entry(file_name).or_insert(FileTotals {
+                language: &tech.language,
+                loc: 0,
+                ext_refs: 0,
+                language_loc: tech.code_lines,
+            });
+            totals.loc += tech.code_lines;
+            totals.ext_refs += (tech.refs.len() + tech.pkgs.len()) as u64;
+            if tech.code_lines > totals.language_loc
+                || (tech.code_lines == totals.language_loc && tech.language.as_str() < totals.language)
+            {
+                totals.language = &tech.language;
+                totals.language_loc = tech.code_lines;
+            }
+        }
+
+        Self { imports, files }
+    }
+}
+
+impl ImportGraph {
+    /// Builds the graph from the local imports of the munched files in `per_file_tech` and ranks the files by the
+    /// number of imports in both directions. Returns None if no file imports another project file.
+    pub fn from_per_file_tech(per_file_tech: &HashSet<Tech>) -> Option<Self> {
+        let LocalImports { imports, files } = LocalImports::from_per_file_tech(per_file_tech);
+        if imports.is_empty() {
+            return None;
+        }
+
+        let mut modules: HashMap<&str, ModuleCentrality> = HashMap::new();
+        for (importer, imported) in &imports {
+            modules
+                .entry(importer)
+                .or_insert_with(|| ModuleCentrality::new(importer))
+                .fan_out += 1;
+            modules
+                .entry(imported)
+                .or_insert_with(|| ModuleCentrality::new(imported))
+                .fan_in += 1;
+        }
+
+        for (file_name, module) in modules.iter_mut() {
+            if let Some(totals) = files.get(file_name) {
+                module.loc = totals.loc;
+                module.ext_refs = totals.ext_refs;
+            }
+        }
+
+        let module_count = modules.len() as u64;
+        let mut central_modules = modules.into_values().collect::<Vec<ModuleCentrality>>();
+        central_modules.sort_unstable_by(|x, y| {
+            (y.fan_in + y.fan_out)
+                .cmp(&(x.fan_in + x.fan_out))
+                .then_with(|| y.fan_in.cmp(&x.fan_in))
+                .then_with(|| x.file_name.cmp(&y.file_name))
+        });
+        central_modules.truncate(TOP_CENTRAL_MODULES);
+
+        Some(Self {
+            modules: module_count,
+            imports: imports.len() as u64,
+            central_modules,
+        })
+    }
+}
+
+impl ModuleCentrality {
+    /// Returns a blank record for the file.
+    fn new(file_name: &str) -> Self {
+        Self {
+            file_name: file_name.to_owned(),
+            fan_in: 0,
+            fan_out: 0,
+            ext_refs: 0,
+            loc: 0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test_import_graph {
+    use super::ImportGraph;
+    use crate::report::tech::Tech;
+    use std::collections::HashSet;
+
+    #[test]
+    fn test_import_graph() {
+        let mut per_file_tech = HashSet::new();
+        let python = Tech::test("Python", "python");
+        per_file_tech.insert(
+            python
+                .clone()
+                .with_file("setup.py")
+                .with_loc(10)
+                .with_refs(&[("setuptools", 1)]),
+        );
+        per_file_tech.insert(python.clone().with_file("app/main.py").with_loc(50).with_local_deps(&[
+            "app/db.py",
+            "app/utils.py",
+            "app/main.py",
+        ]));
+        assert!(ImportGraph::from_per_file_tech(&HashSet::new()).is_none());
+
+        per_file_tech.insert(
+            python
+                .clone()
+                .with_file("app/db.py")
+                .with_loc(80)
+                .with_local_deps(&["app/utils.py", "data/schema.json"])
+                .with_refs(&[("sqlalchemy", 1)]),
+        );
+        per_file_tech.insert(
+            python
+                .clone()
+                .with_file("app/api.py")
+                .with_loc(40)
+                .with_local_deps(&["app/db.py", "app/utils.py"])
+                .with_refs(&[("flask", 1), ("json", 1)]),
+        );
+        per_file_tech.insert(python.with_file("app/utils.py").with_loc(20));
+
+        let graph = ImportGraph::from_per_file_tech(&per_file_tech).unwrap();
+        // setup.py imports nothing local and self-imports do not count
+        assert_eq!(graph.modules, 5);
+        assert_eq!(graph.imports, 6);
+
+        let modules = graph
+            .central_modules
+            .iter()
+            .map(|v| (v.file_name.as_str(), v.fan_in, v.fan_out, v.ext_refs, v.loc))
+            .collect::<Vec<(&str, u64, u64, u64, u64)>>();
+        assert_eq!(
+            modules,
+            vec![
+                ("app/db.py", 2, 2, 1, 80),
+                ("app/utils.py", 3, 0, 0, 20),
+                ("app/api.py", 0, 2, 2, 40),
+                ("app/main.py", 0, 2, 0, 50),
+                ("data/schema.json", 1, 0, 0, 0),
+            ]
+        );
+    }
+}
@@ -0,0 +1,89 @@
+use super::overview::ProjectReportOverview;
+use super::report::Report;
+use jsonschema::JSONSchema;
+use schemars::{schema::RootSchema, schema_for};
+use serde_json::Value;
+use std::sync::OnceLock;
+use tracing::error;
+
+/// The compiled schema of `Report` used by `validate_json()`. It is built on the first call.
+static REPORT_SCHEMA: OnceLock<Option<JSONSchema>> = OnceLock::new();
+
+impl Report {
+    /// Returns JSON Schema (draft 7) of the report as generated by this version of the lib. The version of the lib
+    /// and `REPORT_FORMAT_VERSION` are recorded in `$comment` so that consumers can tell which client the schema came from.
+    pub fn json_schema() -> Value {
+        schema_to_value(schema_for!(Report))
+    }
+
+    /// Validates report JSON, e.g. a report submitted by a client, against `json_schema()` of this version of the lib.
+    /// Returns a list of validation errors as `path: error`, e.g. `/tech/0/files: "10" is not of type "integer"`.
+    pub fn validate_json(json: &[u8]) -> Result<(), Vec<String>> {
+        let instance = serde_json::from_slice::<Value>(json).map_err(|e| vec![format!("invalid JSON: {}", e)])?;
+
+        let schema = REPORT_SCHEMA
+            .get_or_init(|| match JSONSchema::compile(&Report::json_schema()) {
+                Ok(v) => Some(v),
+                Err(e) => {
+                    error!("Cannot compile the report schema due to {}. It's a bug.", e);
+                    None
+                }
+            })
+            .as_ref()
+            .ok_or_else(|| vec!["the report schema is invalid".to_owned()])?;
+
+        schema.validate(&instance).map_err(|errors| {
+            errors
+                .map(|e| format!("{}: {}", e.instance_path, e))
+                .collect::<Vec<String>>()
+        })
+    }
+}
+
+impl ProjectReportOverview {
+    /// Returns JSON Schema (draft 7) of the overview as generated by this version of the lib.
+    pub fn json_schema() -> Value {
+        schema_to_value(schema_for!(ProjectReportOverview))
+    }
+}
+
+/// Converts the schema into JSON with the version of the lib and of the report format in `$comment`.
+fn schema_to_value(mut schema: RootSchema) -> Value {
+    schema.schema.extensions.insert(
+        "$comment".to_owned(),
+        Value::String(format!(
+            "stackmuncher_lib {}, report format {}",
+            env!("CARGO_PKG_VERSION"),
+            Report::REPORT_FORMAT_VERSION
+        )),
+    );
+
+    serde_json::to_value(schema).expect("Cannot serialize JSON Schema. It's a bug.")
+}
+
+#[cfg(test)]
+mod test_json_schema {
+    use crate::report::{ProjectReportOverview, Report};
+
+    #[test]
+    fn test_validate_json() {
+        let schema = Report::json_schema();
+        assert!(schema["properties"]["tech"].is_object());
+        assert!(schema["$comment"]
+            .as_str()
+            .unwrap()
+            .contains(Report::REPORT_FORMAT_VERSION));
+        assert!(ProjectReportOverview::json_schema()["properties"]["tech"].is_object());
+
+        let report = serde_json::to_vec(&Report::new()).unwrap();
+        assert_eq!(Report::validate_json(&report), Ok(()));
+
+        let mut report = serde_json::to_value(Report::new()).unwrap();
+        report["timestamp"] = serde_json::json!(42);
+        let errors = Report::validate_json(&serde_json::to_vec(&report).unwrap()).unwrap_err();
+        assert_eq!(errors.len(), 1, "{:?}", errors);
+        assert!(errors[0].starts_with("/timestamp: "), "{:?}", errors);
+
+        assert!(Report::validate_json(b"{").unwrap_err()[0].starts_with("invalid JSON"));
+    }
+}
@@ -3,6 +3,7 @@ use std::collections::HashSet;
 use tracing::{error, warn};
 
 #[derive(Debug, Serialize, Deserialize, Eq, Clone)]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
 pub struct KeywordCounter {
     /// keyword
     pub k: String,
@@ -15,6 +16,7 @@ pub struct KeywordCounter {
 
 pub(crate) trait KeywordCounterSet {
     fn increment_counters(&mut self, new_kw_counter: KeywordCounter);
+    fn retain_top(&mut self, max_len: usize) -> (u64, u64);
 }
 
 impl std::hash::Hash for KeywordCounter {
@@ -63,6 +65,23 @@ impl KeywordCounterSet for HashSet<KeywordCounter> {
             self.insert(new_kw_counter);
         }
     }
+
+    /// Keeps `max_len` records with the highest counts and removes the rest.
+    /// Ties are broken by the keyword to make the result the same between runs.
+    /// Returns the number of removed records and the sum of their counts.
+    fn retain_top(&mut self, max_len: usize) -> (u64, u64) {
+        if self.len() <= max_len {
+            return (0, 0);
+        }
+
+        let mut kw_counters = self.drain().collect::<Vec<KeywordCounter>>();
+        kw_counters.sort_unstable_by(|a, b| b.c.cmp(&a.c).then_with(|| a.k.cmp(&b.k)));
+
+        let overflow = kw_counters.split_off(max_len);
+        self.extend(kw_counters);
+
+        (overflow.len() as u64, overflow.iter().map(|kwc| kwc.c).sum::<u64>())
+    }
 }
 
 impl KeywordCounter {
@@ -80,7 +99,19 @@ impl KeywordCounter {
     }
 
     /// Splits `keyword` into `k` and `t`. Panics if `keyword` is empty.
+    /// A scoped name keeps its first `/`, e.g. `@angular/core`.
     pub(crate) fn new_ref(keyword: String, count: u64) -> Self {
+        let max_slashes = if keyword.starts_with('@') { 1 } else { 0 };
+        Self::split_ref(keyword, count, max_slashes)
+    }
+
+    /// Same as `new_ref`, but keeps all `/` in the name, e.g. `github.com/pkg/errors` or `consul/aws`.
+    pub(crate) fn new_pkg(keyword: String, count: u64) -> Self {
+        Self::split_ref(keyword, count, usize::MAX)
+    }
+
+    /// Splits `keyword` into `k` and `t` at the first separator, treating up to `max_slashes` `/` as part of `k`.
+    fn split_ref(keyword: String, count: u64, max_slashes: usize) -> Self {
         if keyword.is_empty() {
             error!("Empty keyword for KeywordCounter in new_ref");
         }
@@ -93,12 +124,19 @@ impl KeywordCounter {
         };
 
         // loop through the characters to find the first boundary
+        let mut slashes = 0;
         for (i, c) in kwc.k.as_bytes().iter().enumerate() {
             // keep iterating until the first separator (not -._"'@)
             if c.is_ascii_alphanumeric() || *c == 45u8 || *c == 46u8 || *c == 95u8 || *c == 64u8 {
                 continue;
             }
 
+            // `/` is a separator once there are more than `max_slashes` of them
+            if *c == 47u8 && i > 0 && slashes < max_slashes {
+                slashes += 1;
+                continue;
+            }
+
             // the very first character is a boundary - return as-is
             if i == 0 {
                 warn!("Invalid ref: {}", kwc.k);
@@ -165,3 +203,49 @@ impl KeywordCounter {
         kws
     }
 }
+
+#[cfg(test)]
+mod test_kwc {
+    use super::{KeywordCounter, KeywordCounterSet};
+    use std::collections::HashSet;
+
+    #[test]
+    fn test_retain_top() {
+        let mut kw_counters: HashSet<KeywordCounter> = HashSet::new();
+        for (k, c) in [("a", 5), ("b", 1), ("c", 3), ("d", 3), ("e", 2)] {
+            kw_counters.increment_counters(KeywordCounter::new_keyword(k.to_owned(), c));
+        }
+
+        // nothing to do if the set is within the limit
+        assert_eq!(kw_counters.retain_top(5), (0, 0));
+        assert_eq!(kw_counters.len(), 5);
+
+        // ties on the count are broken by the keyword
+        assert_eq!(kw_counters.retain_top(2), (3, 6));
+        let mut retained = kw_counters.into_iter().map(|kwc| kwc.k).collect::<Vec<String>>();
+        retained.sort();
+        assert_eq!(retained, vec!["a", "c"]);
+    }
+
+    #[test]
+    fn test_new_ref() {
+        let split = |kwc: KeywordCounter| (kwc.k, kwc.t.map(|t| t.into_iter().collect::<Vec<String>>()));
+
+        assert_eq!(
+            split(KeywordCounter::new_ref("std::io".to_owned(), 1)),
+            ("std".to_owned(), Some(vec!["::io".to_owned()]))
+        );
+        assert_eq!(
+            split(KeywordCounter::new_ref("lodash/fp".to_owned(), 1)),
+            ("lodash".to_owned(), Some(vec!["/fp".to_owned()]))
+        );
+        assert_eq!(
+            split(KeywordCounter::new_ref("@angular/core/testing".to_owned(), 1)),
+            ("@angular/core".to_owned(), Some(vec!["/testing".to_owned()]))
+        );
+        assert_eq!(
+            split(KeywordCounter::new_pkg("github.com/pkg/errors v0.9.1".to_owned(), 1)),
+            ("github.com/pkg/errors".to_owned(), Some(vec!["v0.9.1".to_owned()]))
+        );
+    }
+}
@@ -0,0 +1,329 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{BTreeMap, BTreeSet};
+
+/// Base names of license files in the root of the project, lower case, e.g. `LICENSE-MIT` or `COPYING.LESSER`.
+const LICENSE_FILE_PREFIXES: [&str; 4] = ["license", "licence", "copying", "unlicense"];
+
+/// Manifests with a license field, in the order of precedence.
+const MANIFEST_FILES: [&str; 3] = ["Cargo.toml", "package.json", "composer.json"];
+
+/// Lock files with the licenses of the resolved dependencies.
+const LOCK_FILES: [&str; 2] = ["package-lock.json", "composer.lock"];
+
+/// Dependencies with no license in the lock file are counted under this name.
+const UNKNOWN_LICENSE: &str = "unknown";
+
+/// Phrases from the title of license texts that differ only in the body, lower case. The more specific titles go first
+/// because, for example, the GPL text mentions the Lesser GPL.
+const LICENSE_TITLES: [(&str, &str); 9] = [
+    ("gnu affero general public license version 3", "AGPL-3.0"),
+    ("gnu lesser general public license version 3", "LGPL-3.0"),
+    ("gnu lesser general public license version 2.1", "LGPL-2.1"),
+    ("gnu general public license version 3", "GPL-3.0"),
+    ("gnu general public license version 2", "GPL-2.0"),
+    ("mozilla public license version 2.0", "MPL-2.0"),
+    ("mozilla public license, version 2.0", "MPL-2.0"),
+    ("apache license version 2.0", "Apache-2.0"),
+    ("boost software license - version 1.0", "BSL-1.0"),
+];
+
+/// Phrases from the body of permissive license texts with no distinctive title, lower case.
+const LICENSE_PHRASES: [(&str, &str); 5] = [
+    ("this is free and unencumbered software released into the public domain", "Unlicense"),
+    ("permission is hereby granted, free of charge", "MIT"),
+    ("distribute this software for any purpose with or without fee is hereby granted", "ISC"),
+    ("associated a work with this deed has dedicated the work to the public domain", "CC0-1.0"),
+    ("redistribution and use in source and binary forms", "BSD"),
+];
+
+/// Only this many characters from the start of a license file are checked for the title.
+const MAX_TITLE_CHARS: usize = 300;
+
+/// The license of the project and the licenses of its dependencies at HEAD. No file contents are stored.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, Eq, PartialEq)]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
+pub struct Licenses {
+    /// The license declared in Cargo.toml, package.json or composer.json or identified from the license files if there is
+    /// no declaration, e.g. `MIT OR Apache-2.0`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub project_license: Option<String>,
+    /// Names of license files in the root of the project, e.g. `LICENSE-MIT`. Sorted a-z.
+    #[serde(skip_serializing_if = "Vec::is_empty", default)]
+    pub license_files: Vec<String>,
+    /// Number of dependencies per license as declared in package-lock.json and composer.lock, e.g. `MIT: 120`.
+    /// Dependencies with no license are counted as `unknown`.
+    #[serde(skip_serializing_if = "BTreeMap::is_empty", default)]
+    pub dependencies: BTreeMap<String, u64>,
+}
+
+impl Licenses {
+    /// Returns true if the file is one of the license files, manifests or lock files in the root of the project
+    /// that are needed to build the section.
+    pub(crate) fn is_license_source(file_name: &str) -> bool {
+        if file_name.contains('/') {
+            return false;
+        }
+
+        MANIFEST_FILES.contains(&file_name) || LOCK_FILES.contains(&file_name) || is_license_file(file_name)
+    }
+
+    /// Builds the section from the names and contents of the files selected with `is_license_source`.
+    /// Returns None if no license information was found.
+    pub(crate) fn from_files<'a, I>(files: I) -> Option<Self>
+    where
+        I: Iterator<Item = (&'a str, &'a [u8])>,
+    {
+        let mut licenses = Self::default();
+        // manifest name -> declared license
+        let mut declared: BTreeMap<&str, String> = BTreeMap::new();
+        let mut identified: BTreeSet<&'static str> = BTreeSet::new();
+
+        for (file_name, contents) in files {
+            let contents = String::from_utf8_lossy(contents);
+            match file_name {
+                "Cargo.toml" => {
+                    if let Some(license) = cargo_toml_license(&contents) {
+                        declared.insert(file_name, license);
+                    }
+                }
+                "package.json" | "composer.json" => {
+                    if let Some(license) = serde_json::from_str::<Value>(&contents)
+                        .ok()
+                        .and_then(|v| json_license(&v))
+                    {
+                        declared.insert(file_name, license);
+                    }
+                }
+                "package-lock.json" => licenses.add_package_lock(&contents),
+                "composer.lock" => licenses.add_composer_lock(&contents),
+                _ if is_license_file(file_name) => {
+                    licenses.license_files.push(file_name.to_owned());
+                    if let Some(license) = identify_license(&contents) {
+                        identified.insert(license);
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        licenses.license_files.sort_unstable();
+        licenses.project_license = MANIFEST_FILES
+            .iter()
+            .find_map(|manifest| declared.remove(manifest))
+            .or_else(|| {
+                if identified.is_empty() {
+                    None
+                } else {
+                    // dual licensing is usually done with a file per license, e.g. LICENSE-MIT and LICENSE-APACHE
+                    Some(identified.into_iter().collect::<Vec<&str>>().join(" OR "))
+                }
+            });
+
+        if licenses.project_license.is_none() && licenses.license_files.is_empty() && licenses.dependencies.is_empty() {
+            None
+        } else {
+            Some(licenses)
+        }
+    }
+
+    /// Counts licenses of `packages` in package-lock.json v2 and later. Older lock files have no license info.
+    fn add_package_lock(&mut self, contents: &str) {
+        let lock = match serde_json::from_str::<Value>(contents) {
+            Ok(v) => v,
+            Err(_) => return,
+        };
+
+        if let Some(packages) = lock.get("packages").and_then(|v| v.as_object()) {
+            for (name, package) in packages {
+                // the empty name is the project itself and links point at local folders
+                if name.is_empty() || package.get("link").and_then(|v| v.as_bool()).unwrap_or_default() {
+                    continue;
+                }
+                self.add_dependency(json_license(package));
+            }
+        }
+    }
+
+    /// Counts licenses of `packages` and `packages-dev` in composer.lock.
+    fn add_composer_lock(&mut self, contents: &str) {
+        let lock = match serde_json::from_str::<Value>(contents) {
+            Ok(v) => v,
+            Err(_) => return,
+        };
+
+        for section in ["packages", "packages-dev"] {
+            if let Some(packages) = lock.get(section).and_then(|v| v.as_array()) {
+                for package in packages {
+                    self.add_dependency(json_license(package));
+                }
+            }
+        }
+    }
+
+    fn add_dependency(&mut self, license: Option<String>) {
+        let license = license.unwrap_or_else(|| UNKNOWN_LICENSE.to_owned());
+        *self.dependencies.entry(license).or_default() += 1;
+    }
+}
+
+/// Returns true if the name is a license file, e.g. `LICENSE`, `LICENSE.md`, `LICENSE-MIT` or `COPYING.LESSER`.
+fn is_license_file(file_name: &str) -> bool {
+    let file_name = file_name.to_lowercase();
+    LICENSE_FILE_PREFIXES
+        .iter()
+        .any(|prefix| match file_name.strip_prefix(prefix) {
+            Some(rest) => rest.is_empty() || rest.starts_with(['.', '-', '_']),
+            None => false,
+        })
+}
+
+/// Returns `license` from `[package]` section of Cargo.toml. Does not support multi-line strings.
+fn cargo_toml_license(contents: &str) -> Option<String> {
+    let mut is_package = false;
+    for line in contents.lines() {
+        let line = line.trim();
+        if line.starts_with('[') {
+            is_package = line == "[package]";
+        } else if is_package {
+            if let Some((key, value)) = line.split_once('=') {
+                if key.trim() == "license" {
+                    let license = value.trim().trim_matches(|c| c == '"' || c == '\'').trim();
+                    if !license.is_empty() {
+                        return Some(license.to_owned());
+                    }
+                }
+            }
+        }
+    }
+
+    None
+}
+
+/// Returns the license of a package.json, composer.json or a lock file entry. Supports an SPDX string, an array
+/// of alternatives as in composer, and deprecated `{"type": "MIT"}` objects.
+pub(super) fn json_license(package: &Value) -> Option<String> {
+    let license = package.get("license").or_else(|| package.get("licenses"))?;
+
+    let license = match license {
+        Value::String(v) => v.trim().to_owned(),
+        Value::Object(_) => license.get("type")?.as_str()?.trim().to_owned(),
+        Value::Array(v) => v
+            .iter()
+            .filter_map(|v| match v {
+                Value::String(v) => Some(v.trim()),
+                _ => v.get("type").and_then(|v| v.as_str()),
+            })
+            .collect::<Vec<&str>>()
+            .join(" OR "),
+        _ => return None,
+    };
+
+    if license.is_empty() {
+        None
+    } else {
+        Some(license)
+    }
+}
+
+/// Returns the SPDX ID of the license from the text of a license file, e.g. `MIT`. Only the most common
+/// licenses are recognized. The exact wording and the version are not verified.
+fn identify_license(contents: &str) -> Option<&'static str> {
+    // the text is reflowed differently from project to project
+    let text = contents
+        .split_whitespace()
+        .collect::<Vec<&str>>()
+        .join(" ")
+        .to_lowercase();
+
+    let title = match text.char_indices().nth(MAX_TITLE_CHARS) {
+        Some((idx, _)) => &text[..idx],
+        None => text.as_str(),
+    };
+    if let Some((_, license)) = LICENSE_TITLES.iter().find(|(phrase, _)| title.contains(phrase)) {
+        return Some(license);
+    }
+
+    match LICENSE_PHRASES.iter().find(|(phrase, _)| text.contains(phrase)) {
+        Some((_, "BSD")) => {
+            if text.contains("neither the name") || text.contains("endorse or promote") {
+                Some("BSD-3-Clause")
+            } else {
+                Some("BSD-2-Clause")
+            }
+        }
+        Some((_, license)) => Some(license),
+        None => None,
+    }
+}
+
+#[cfg(test)]
+mod test_licenses {
+    use super::{identify_license, Licenses};
+
+    #[test]
+    fn test_identify_license() {
+        assert_eq!(
+            identify_license(
+                "MIT License\n\nCopyright (c) 2021\n\nPermission is hereby granted, free\nof charge, to any person"
+            ),
+            Some("MIT")
+        );
+        assert_eq!(
+            identify_license("                    GNU AFFERO GENERAL PUBLIC LICENSE\n                       Version 3, 19 November 2007"),
+            Some("AGPL-3.0")
+        );
+        assert_eq!(
+            identify_license("GNU GENERAL PUBLIC LICENSE\nVersion 3, 29 June 2007\n...consider it more useful to permit linking proprietary applications with the library. If this is what you want to do, use the GNU Lesser General Public License instead of this License."),
+            Some("GPL-3.0")
+        );
+        assert_eq!(
+            identify_license("Redistribution and use in source and binary forms, with or without modification...\nNeither the name of the copyright holder nor"),
+            Some("BSD-3-Clause")
+        );
+        assert_eq!(identify_license("All rights reserved."), None);
+    }
+
+    #[test]
+    fn test_from_files() {
+        let files: Vec<(&str, &[u8])> = vec![
+            ("LICENSE-MIT", b"Permission is hereby granted, free of charge, to any person"),
+            ("LICENSE-APACHE", b"Apache License\nVersion 2.0, January 2004"),
+            ("README.md", b"# Readme"),
+        ];
+        let licenses = Licenses::from_files(files.into_iter()).unwrap();
+        assert_eq!(licenses.project_license.as_deref(), Some("Apache-2.0 OR MIT"));
+        assert_eq!(licenses.license_files, vec!["LICENSE-APACHE", "LICENSE-MIT"]);
+
+        let package_lock =
+            br#"{"lockfileVersion":3,"packages":{"":{"license":"ISC"},"node_modules/a":{"license":"MIT"},
+            "node_modules/b":{"license":"MIT"},"node_modules/c":{},"node_modules/d":{"link":true}}}"#;
+        let files: Vec<(&str, &[u8])> = vec![
+            ("LICENSE", b"Permission is hereby granted, free of charge, to any person"),
+            ("package.json", br#"{"name":"x","license":"ISC"}"#),
+            ("package-lock.json", package_lock),
+            (
+                "composer.lock",
+                br#"{"packages":[{"license":["MIT"]}],"packages-dev":[{"license":["GPL-2.0-only","MIT"]}]}"#,
+            ),
+        ];
+        let licenses = Licenses::from_files(files.into_iter()).unwrap();
+        assert_eq!(licenses.project_license.as_deref(), Some("ISC"));
+        assert_eq!(licenses.dependencies["MIT"], 3);
+        assert_eq!(licenses.dependencies["GPL-2.0-only OR MIT"], 1);
+        assert_eq!(licenses.dependencies["unknown"], 1);
+
+        let files: Vec<(&str, &[u8])> =
+            vec![("Cargo.toml", b"[package]\nname = \"x\"\n\n[dependencies]\nlicense = \"1\"")];
+        assert!(Licenses::from_files(files.into_iter()).is_none());
+        let files: Vec<(&str, &[u8])> =
+            vec![("Cargo.toml", b"[package]\nname = \"x\"\nlicense = \"MIT OR Apache-2.0\"")];
+        assert_eq!(
+            Licenses::from_files(files.into_iter())
+                .unwrap()
+                .project_license
+                .as_deref(),
+            Some("MIT OR Apache-2.0")
+        );
+    }
+}
@@ -0,0 +1,255 @@
+use super::report::Report;
+use super::sbom::Sbom;
+use chrono::{DateTime, Datelike};
+use std::collections::HashMap;
+
+/// The number of languages in the table of `to_markdown()`. The rest are left out as too small to mention.
+const MAX_MARKDOWN_LANGUAGES: usize = 10;
+
+/// The number of libraries listed by `to_markdown()`.
+const MAX_MARKDOWN_LIBRARIES: usize = 10;
+
+/// The first and the last years of commits per language, e.g. `2019` and `2023`, and the number of months between them.
+struct ActiveYears {
+    from: i32,
+    to: i32,
+    months: u64,
+}
+
+impl Report {
+    /// Returns a compact summary of the report in Markdown for pasting into a resume or a GitHub profile README:
+    /// a table of the top languages with LoC and the years they were used in, the most used libraries and
+    /// the commit cadence. Works best with combined contributor reports, which have the dates per language.
+    /// Sections with no data in the report are left out.
+    pub fn to_markdown(&self) -> String {
+        let mut md = String::from("## Tech stack\n\n");
+
+        let mut tech = self
+            .get_overview()
+            .tech
+            .into_iter()
+            .filter(|t| t.loc > 0)
+            .collect::<Vec<_>>();
+        tech.sort_unstable_by(|a, b| b.loc.cmp(&a.loc).then_with(|| a.language.cmp(&b.language)));
+
+        if tech.is_empty() {
+            md.push_str("No code was found in the report.\n");
+            return md;
+        }
+
+        let active_years = self.active_years();
+        md.push_str("| Language | LoC | Active |\n|---|--:|---|\n");
+        for t in tech.iter().take(MAX_MARKDOWN_LANGUAGES) {
+            let active = match active_years.get(t.language.as_str()) {
+                Some(v) if v.from == v.to => format!("{} ({})", v.from, format_months(v.months)),
+                Some(v) => format!("{} - {} ({})", v.from, v.to, format_months(v.months)),
+                None => String::new(),
+            };
+            md.push_str(&format!("| {} | {} | {} |\n", escape_markdown(&t.language), t.loc, active));
+        }
+
+        let libraries = self.top_libraries();
+        if !libraries.is_empty() {
+            let libraries = libraries
+                .iter()
+                .map(|v| ["`", v.replace('`', "").as_str(), "`"].concat())
+                .collect::<Vec<String>>();
+            md.push_str(&format!("\n**Top libraries:** {}\n", libraries.join(", ")));
+        }
+
+        if let Some(cadence) = &self.commit_cadence {
+            md.push_str(&format!(
+                "\n**Commit cadence:** active in {}, the longest streak of {}",
+                plural(cadence.active_weeks, "week"),
+                plural(cadence.longest_streak_days, "day")
+            ));
+            if cadence.avg_gap_hours > 0 {
+                md.push_str(&format!(", a commit every {} on average", format_hours(cadence.avg_gap_hours)));
+            }
+            md.push_str(".\n");
+        }
+
+        let commits = self.commit_count_contributor.or(self.commit_count_project);
+        let date = self.timestamp.get(..10).unwrap_or(&self.timestamp);
+        match commits {
+            Some(commits) => md.push_str(&format!(
+                "\n<sub>Generated by [StackMuncher](https://stackmuncher.com) from {} on {}.</sub>\n",
+                plural(commits, "commit"),
+                date
+            )),
+            None => md.push_str(&format!(
+                "\n<sub>Generated by [StackMuncher](https://stackmuncher.com) on {}.</sub>\n",
+                date
+            )),
+        }
+
+        md
+    }
+
+    /// Returns the years of the first and the last commits per language from `Tech.history`. Languages with no
+    /// history in any of their records get the dates of the first and the last commits of the contributor or of the project.
+    /// Records with no history, e.g. `Cargo.toml` next to Rust files with history, do not widen the range.
+    fn active_years(&self) -> HashMap<&str, ActiveYears> {
+        let report_dates = self
+            .first_contributor_commit_date_epoch
+            .zip(self.last_contributor_commit_date_epoch)
+            .or_else(|| {
+                let date_init = DateTime::parse_from_rfc3339(self.date_init.as_deref()?).ok()?;
+                let date_head = DateTime::parse_from_rfc3339(self.date_head.as_deref()?).ok()?;
+                Some((date_init.timestamp(), date_head.timestamp()))
+            });
+
+        let mut active_years: HashMap<&str, (i64, i64)> = HashMap::new();
+        for tech in &self.tech {
+            if let Some(history) = &tech.history {
+                let dates = (history.from_date_epoch, history.to_date_epoch);
+                let v = active_years.entry(tech.language.as_str()).or_insert(dates);
+                *v = (v.0.min(dates.0), v.1.max(dates.1));
+            }
+        }
+        if let Some(dates) = report_dates {
+            for tech in &self.tech {
+                active_years.entry(tech.language.as_str()).or_insert(dates);
+            }
+        }
+
+        active_years
+            .into_iter()
+            .filter_map(|(language, (from, to))| {
+                let from = DateTime::from_timestamp(from, 0)?;
+                let to = DateTime::from_timestamp(to, 0)?;
+                let months = ((to.year() - from.year()) * 12 + to.month() as i32 - from.month() as i32).max(0) as u64;
+                Some((
+                    language,
+                    ActiveYears {
+                        from: from.year(),
+                        to: to.year(),
+                        months,
+                    },
+                ))
+            })
+            .collect()
+    }
+
+    /// Returns the names of the most used libraries across all languages, most used first. Only libraries that would
+    /// be listed in the SBOM of the report are included, so that imports of std modules are left out.
+    fn top_libraries(&self) -> Vec<String> {
+        let sbom = Sbom::from_report(self, "");
+
+        // the same library may be declared in several manifests and imported in many files
+        let mut counts: HashMap<&str, u64> = HashMap::new();
+        for tech in &self.tech {
+            for kw in tech.pkgs.iter().chain(tech.refs.iter()) {
+                *counts.entry(kw.k.as_str()).or_default() += kw.c;
+            }
+        }
+
+        let mut libraries = sbom
+            .components
+            .iter()
+            .map(|v| (v.name.as_str(), counts.get(v.name.as_str()).copied().unwrap_or_default()))
+            .collect::<HashMap<&str, u64>>()
+            .into_iter()
+            .collect::<Vec<(&str, u64)>>();
+        libraries.sort_unstable_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(b.0)));
+
+        libraries
+            .into_iter()
+            .take(MAX_MARKDOWN_LIBRARIES)
+            .map(|(name, _)| name.to_owned())
+            .collect()
+    }
+}
+
+/// Returns the number of months as years if it is a year or longer, e.g. `3 years` or `5 months`.
+fn format_months(months: u64) -> String {
+    match months {
+        0..=11 => plural(months.max(1), "month"),
+        _ => plural(months / 12, "year"),
+    }
+}
+
+/// Returns the number of hours as days if it is 2 days or longer, e.g. `3 days` or `5 hours`.
+fn format_hours(hours: u64) -> String {
+    match hours {
+        0 | 1 => "hour".to_owned(),
+        2..=47 => plural(hours, "hour"),
+        _ => plural(hours / 24, "day"),
+    }
+}
+
+/// Returns the number with the noun in singular or plural, e.g. `1 week` or `3 weeks`.
+fn plural(count: u64, noun: &str) -> String {
+    if count == 1 {
+        format!("1 {}", noun)
+    } else {
+        format!("{} {}s", count, noun)
+    }
+}
+
+/// Escapes characters that would break a Markdown table cell, e.g. `|` in a language name.
+fn escape_markdown(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('|', "\\|")
+}
+
+#[cfg(test)]
+mod test_markdown {
+    use crate::report::commit_cadence::CommitCadence;
+    use crate::report::tech::TechHistory;
+    use crate::report::{Report, Tech};
+
+    #[test]
+    fn test_to_markdown() {
+        let tech = vec![
+            Tech {
+                files: 10,
+                code_lines: 1200,
+                history: Some(TechHistory {
+                    months: 40,
+                    from_date_epoch: 1577836800,
+                    from_date_iso: String::new(),
+                    to_date_epoch: 1682899200,
+                    to_date_iso: String::new(),
+                }),
+                ..Tech::test("Rust", "rust.rs")
+            },
+            Tech {
+                code_lines: 20,
+                ..Tech::test("Rust", "rust.cargo.toml").with_pkgs(&[
+                    ("tokio", Some("1.0"), 1),
+                    ("serde", Some("1.0"), 3),
+                    ("version", Some("0.1.0"), 1),
+                ])
+            },
+            Tech {
+                code_lines: 5,
+                ..Tech::test("Some|Lang", "x")
+            },
+        ];
+
+        let mut report = Report::new();
+        report.timestamp = "2023-06-01T10:00:00+00:00".to_owned();
+        report.commit_count_contributor = Some(120);
+        report.first_contributor_commit_date_epoch = Some(1546300800);
+        report.last_contributor_commit_date_epoch = Some(1685577600);
+        report.tech = tech.into_iter().collect();
+        report.commit_cadence = Some(CommitCadence {
+            weekly_commits: Vec::new(),
+            longest_streak_days: 6,
+            avg_gap_hours: 30,
+            active_weeks: 52,
+        });
+
+        let md = report.to_markdown();
+        assert!(md.starts_with("## Tech stack\n"), "{}", md);
+        // the Cargo.toml record has no history and does not widen the range of Rust
+        assert!(md.contains("| Rust | 1220 | 2020 - 2023 (3 years) |"), "{}", md);
+        assert!(md.contains("| Some\\|Lang | 5 | 2019 - 2023 (4 years) |"), "{}", md);
+        assert!(md.contains("**Top libraries:** `serde`, `tokio`\n"), "{}", md);
+        assert!(md.contains("active in 52 weeks, the longest streak of 6 days, a commit every 30 hours on average."));
+        assert!(md.contains("from 120 commits on 2023-06-01"));
+
+        report.tech.clear();
+        assert!(report.to_markdown().contains("No code was found"));
+    }
+}
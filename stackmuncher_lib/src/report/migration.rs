@@ -0,0 +1,242 @@
+use chrono::{DateTime, Utc};
+use serde_json::{Map, Value};
+use tracing::{debug, info, warn};
+
+/// Name of the JSON field with the format version of the report. Older reports don't have it and their version
+/// is taken from `timestamp`.
+const FORMAT_VERSION_FIELD: &str = "format_version";
+
+/// Upgrades a report in JSON form to the format of `Migration::version` from the format of the step before it.
+type UpgradeFn = fn(&mut Map<String, Value>);
+
+/// A single change of the report format.
+pub(crate) struct Migration {
+    /// The value of `Report::REPORT_FORMAT_VERSION` when the change was made, in RFC3339 format.
+    pub version: &'static str,
+    /// Upgrades a cached report from the previous version to `version` by renaming fields, filling in defaults, etc.
+    /// None if the change cannot be applied to a cached report and the project has to be reprocessed in full,
+    /// e.g. munchers started collecting new data.
+    pub upgrade: Option<UpgradeFn>,
+}
+
+/// The history of the report format changes, oldest first. The last entry must match `Report::REPORT_FORMAT_VERSION`.
+///
+/// Add a new entry with an `upgrade` fn for changes that can be derived from the cached report, e.g.
+/// ```ignore
+/// Migration {
+///     version: "2022-03-01T00:00:00+00:00",
+///     upgrade: Some(|report| rename_field(report, "loc_project", "project_loc")),
+/// },
+/// ```
+pub(crate) const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: "2021-11-02T00:23:00+00:00",
+        upgrade: None,
+    },
+    // per-file records started collecting `local_deps`
+    Migration {
+        version: "2021-11-15T00:00:00+00:00",
+        upgrade: None,
+    },
+    // `Contributor.commits` changed from indexes into `recent_project_commits` to the commits themselves
+    Migration {
+        version: "2021-12-01T00:00:00+00:00",
+        upgrade: Some(contributor_commit_indexes_to_refs),
+    },
+];
+
+/// Upgrades a cached report to the current format in place using `MIGRATIONS`. See `upgrade_with()` for details.
+pub(crate) fn upgrade(report: &mut Value) -> bool {
+    upgrade_with(report, MIGRATIONS)
+}
+
+/// Applies all `migrations` newer than the format of `report`, in order, and sets its format to the version of the last one.
+/// Returns `true` if the report is in the latest format, including when it was already current.
+/// Returns `false` and leaves the report as-is if any of the steps requires a full reprocessing or the report is not a JSON object.
+pub(crate) fn upgrade_with(report: &mut Value, migrations: &[Migration]) -> bool {
+    let report = match report.as_object_mut() {
+        Some(v) => v,
+        None => {
+            warn!("Cached report is not a JSON object");
+            return false;
+        }
+    };
+
+    let report_version = get_format_version(report);
+    let pending = migrations
+        .iter()
+        .filter(|m| parse_version(m.version) > report_version)
+        .collect::<Vec<&Migration>>();
+
+    if pending.is_empty() {
+        debug!("Cached report is in the latest format");
+        return true;
+    }
+
+    // all steps must be upgradable, otherwise there is no point in running any of them
+    if let Some(migration) = pending.iter().find(|m| m.upgrade.is_none()) {
+        info!("Cached report cannot be upgraded to format {}", migration.version);
+        return false;
+    }
+
+    for migration in &pending {
+        if let Some(upgrade) = migration.upgrade {
+            debug!("Upgrading cached report to format {}", migration.version);
+            upgrade(report);
+        }
+    }
+
+    let latest_version = pending
+        .last()
+        .expect("Cannot unwrap pending migrations. It's a bug.")
+        .version;
+    report.insert(FORMAT_VERSION_FIELD.to_owned(), Value::String(latest_version.to_owned()));
+    info!("Upgraded cached report to format {}", latest_version);
+
+    true
+}
+
+/// Returns the format version of a report in JSON form as an EPOCH. Reports without `format_version` field
+/// predate the migrations and are considered to be in the format of the time they were generated.
+/// Returns 0 if neither value is valid.
+fn get_format_version(report: &Map<String, Value>) -> i64 {
+    report
+        .get(FORMAT_VERSION_FIELD)
+        .or_else(|| report.get("timestamp"))
+        .and_then(|v| v.as_str())
+        .map(parse_version)
+        .unwrap_or_default()
+}
+
+/// Parses an RFC3339 version into an EPOCH. Returns 0 if the value is not valid.
+fn parse_version(version: &str) -> i64 {
+    match DateTime::parse_from_rfc3339(version) {
+        Ok(v) => v.with_timezone(&Utc).timestamp(),
+        Err(_) => {
+            warn!("Invalid report format version: {}", version);
+            0
+        }
+    }
+}
+
+/// Renames a field, if it exists. Does not overwrite the field with the new name if it's already present.
+#[allow(dead_code)]
+pub(crate) fn rename_field(report: &mut Map<String, Value>, from: &str, to: &str) {
+    if let Some(value) = report.remove(from) {
+        report.entry(to).or_insert(value);
+    }
+}
+
+/// Sets a field to `value` if it's missing or null.
+#[allow(dead_code)]
+pub(crate) fn default_field(report: &mut Map<String, Value>, field: &str, value: Value) {
+    match report.get(field) {
+        None | Some(Value::Null) => {
+            report.insert(field.to_owned(), value);
+        }
+        _ => {}
+    }
+}
+
+/// Replaces indexes into `recent_project_commits` in `contributors[].commits` with the commits they point at.
+/// Indexes outside of the list are dropped.
+fn contributor_commit_indexes_to_refs(report: &mut Map<String, Value>) {
+    let project_commits = match report.get("recent_project_commits") {
+        Some(Value::Array(v)) => v.clone(),
+        _ => Vec::new(),
+    };
+
+    let contributors = match report.get_mut("contributors") {
+        Some(Value::Array(v)) => v,
+        _ => return,
+    };
+
+    for contributor in contributors {
+        if let Some(Value::Array(commits)) = contributor.get_mut("commits") {
+            *commits = commits
+                .iter()
+                .filter_map(|idx| match idx {
+                    // refs are left as-is in case the report was saved by a newer version
+                    Value::String(_) => Some(idx.clone()),
+                    _ => idx.as_u64().and_then(|idx| project_commits.get(idx as usize).cloned()),
+                })
+                .collect::<Vec<Value>>();
+        }
+    }
+}
+
+#[cfg(test)]
+mod test_migration {
+    use super::{contributor_commit_indexes_to_refs, default_field, rename_field, upgrade_with, Migration, MIGRATIONS};
+    use crate::report::Report;
+    use serde_json::{json, Value};
+
+    #[test]
+    fn test_upgrade() {
+        let migrations = [
+            Migration {
+                version: "2021-01-01T00:00:00+00:00",
+                upgrade: None,
+            },
+            Migration {
+                version: "2021-06-01T00:00:00+00:00",
+                upgrade: Some(|r| rename_field(r, "loc", "loc_project")),
+            },
+            Migration {
+                version: "2021-09-01T00:00:00+00:00",
+                upgrade: Some(|r| default_field(r, "libs_project", json!(0))),
+            },
+        ];
+
+        // both upgradable steps are applied
+        let mut report = json!({"timestamp": "2021-03-01T00:00:00+00:00", "loc": 10});
+        assert!(upgrade_with(&mut report, &migrations));
+        assert_eq!(report["loc_project"], json!(10));
+        assert_eq!(report["libs_project"], json!(0));
+        assert!(report.get("loc").is_none());
+        assert_eq!(report["format_version"], json!("2021-09-01T00:00:00+00:00"));
+
+        // the format version takes precedence over the timestamp
+        let mut report =
+            json!({"timestamp": "2021-03-01T00:00:00+00:00", "format_version": "2021-07-01T00:00:00+00:00"});
+        assert!(upgrade_with(&mut report, &migrations));
+        assert_eq!(report["libs_project"], json!(0));
+        assert!(report.get("loc_project").is_none());
+
+        // too old to be upgraded
+        let mut report = json!({"timestamp": "2020-12-01T00:00:00+00:00", "loc": 10});
+        assert!(!upgrade_with(&mut report, &migrations));
+        assert_eq!(report["loc"], json!(10));
+
+        assert!(!upgrade_with(&mut Value::Null, &migrations));
+    }
+
+    #[test]
+    fn test_contributor_commit_indexes_to_refs() {
+        let mut report = json!({
+            "recent_project_commits": ["e29d17e6_1627380297", "a1b2c3d4_1627380000", "f0e1d2c3_1627370000"],
+            "contributors": [
+                {"git_id": "a@example.com", "commits": [0, 2]},
+                {"git_id": "b@example.com", "commits": [1, 5]},
+                {"git_id": "c@example.com"}
+            ]
+        });
+        contributor_commit_indexes_to_refs(report.as_object_mut().unwrap());
+        assert_eq!(
+            report["contributors"][0]["commits"],
+            json!(["e29d17e6_1627380297", "f0e1d2c3_1627370000"])
+        );
+        // the out of range index is dropped
+        assert_eq!(report["contributors"][1]["commits"], json!(["a1b2c3d4_1627380000"]));
+        assert!(report["contributors"][2].get("commits").is_none());
+
+        // already converted refs are left as-is
+        contributor_commit_indexes_to_refs(report.as_object_mut().unwrap());
+        assert_eq!(report["contributors"][1]["commits"], json!(["a1b2c3d4_1627380000"]));
+    }
+
+    #[test]
+    fn test_migrations_match_report_format_version() {
+        assert_eq!(MIGRATIONS.last().unwrap().version, Report::REPORT_FORMAT_VERSION);
+    }
+}
@@ -1,9 +1,56 @@
+pub mod assets;
+pub mod co_change;
+pub mod collaboration;
+pub mod coverage;
+pub mod duplicate_files;
+pub mod frameworks;
+#[cfg(feature = "json-schema")]
+pub mod json_schema;
 pub mod kwc;
+pub mod licenses;
+pub mod markdown;
+pub(crate) mod migration;
+pub mod ops_tech;
 pub mod overview;
 pub mod report;
+pub mod retention;
+pub mod sbom;
+pub mod schema_migrations;
+pub mod scrub;
 pub mod tech;
+pub mod tech_debt;
+pub mod tenure;
+pub mod trends;
+pub mod truncation;
+pub mod commit_cadence;
 pub mod commit_time_histo;
+pub mod dependency_graph;
+pub mod documentation;
+pub mod hotspots;
+pub mod import_graph;
+pub mod work_distribution;
 
-pub use overview::{ProjectReportOverview, TechOverview};
-pub use report::Report;
+pub use assets::{AssetCount, Assets};
+pub use co_change::{CoChange, CoChangeLimits};
+pub use collaboration::Collaboration;
+pub use coverage::Coverage;
+pub use commit_time_histo::{WorkPattern, WorkdayOverlap};
+pub use dependency_graph::DependencyGraph;
+pub use documentation::Documentation;
+pub use duplicate_files::{DuplicateBlob, DuplicateFiles};
+pub use frameworks::{Framework, FrameworkConfidence};
+pub use hotspots::{Hotspot, Hotspots};
+pub use import_graph::{ImportGraph, ModuleCentrality};
+pub use licenses::Licenses;
+pub use ops_tech::OpsTech;
+pub use overview::{LocWeighting, ProjectReportOverview, TechOverview};
+pub use report::{MergeLimits, Report, ReportError};
+pub use retention::{PrunedHistory, Retention};
+pub use sbom::{Sbom, SbomComponent, SbomSource};
+pub use schema_migrations::SchemaMigrations;
+pub use scrub::ScrubRules;
 pub use tech::Tech;
+pub use tech_debt::TechDebtMarkers;
+pub use tenure::Tenure;
+pub use trends::{OverviewHistory, OverviewSnapshot, Trends};
+pub use truncation::{TechTruncation, Truncation};
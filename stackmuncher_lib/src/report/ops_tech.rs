@@ -0,0 +1,118 @@
+use super::tech::Tech;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+
+/// CI/CD pipelines, containers and infra-as-code found by the munchers of their files, e.g. GitHub Actions workflows
+/// or Terraform modules. They are counted separately from the rest of the stack to surface DevOps work that is
+/// easily lost among the application code.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, Eq, PartialEq)]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
+pub struct OpsTech {
+    /// Number of ops files, including deleted files that are still in the report history.
+    pub files: u64,
+    /// Lines of code in the ops files, excluding comments, blank and bracket-only lines.
+    pub loc: u64,
+    /// Files and LoC per tool, e.g. `github_actions`, `docker`, `terraform`.
+    pub tools: BTreeMap<String, OpsToolCount>,
+}
+
+/// Number of files and their LoC for a single ops tool.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, Eq, PartialEq)]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
+pub struct OpsToolCount {
+    pub files: u64,
+    pub loc: u64,
+}
+
+impl OpsTech {
+    /// Counts files munched by ops munchers and their LoC. Returns None if there are no such files.
+    /// A file with several records, e.g. the current version and a deleted one in history, is counted once with its first
+    /// record, so the current records should come first.
+    pub fn from_per_file_tech<'a, I>(per_file_tech: I) -> Option<Self>
+    where
+        I: Iterator<Item = &'a Tech>,
+    {
+        let mut ops_tech = Self::default();
+        let mut counted_files: HashSet<&str> = HashSet::new();
+
+        for tech in per_file_tech {
+            let file_name = match tech.file_name.as_deref() {
+                Some(v) => v,
+                None => continue,
+            };
+            let tool = match ops_tool(&tech.muncher_name) {
+                Some(v) => v,
+                None => continue,
+            };
+
+            if !counted_files.insert(file_name) {
+                continue;
+            }
+
+            let count = ops_tech.tools.entry(tool.to_owned()).or_default();
+            ops_tech.files += 1;
+            count.files += 1;
+            ops_tech.loc += tech.code_lines;
+            count.loc += tech.code_lines;
+        }
+
+        if ops_tech.files == 0 {
+            None
+        } else {
+            Some(ops_tech)
+        }
+    }
+}
+
+/// Returns the name of the ops tool for the muncher or None if the muncher is not for an ops file.
+/// E.g. `github_actions` for `cicd.github.yml`.
+pub fn ops_tool(muncher_name: &str) -> Option<&'static str> {
+    match muncher_name {
+        "cicd.github.yml" => Some("github_actions"),
+        "cicd.gitlab.yml" => Some("gitlab_ci"),
+        "cicd.travis.yml" => Some("travis_ci"),
+        "cicd.circleci.yml" => Some("circleci"),
+        "cicd.appveyor.yml" => Some("appveyor"),
+        "docker" | "docker.yml" => Some("docker"),
+        "terraform" => Some("terraform"),
+        "helm.yml" => Some("helm"),
+        "ansible.yml" => Some("ansible"),
+        "k8s.yml" => Some("kubernetes"),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod test_ops_tech {
+    use super::OpsTech;
+    use crate::report::Tech;
+
+    #[test]
+    fn test_from_per_file_tech() {
+        let file_tech = |file_name: &str, muncher_name: &str, code_lines: u64| -> Tech {
+            Tech::test("DevOps", muncher_name)
+                .with_file(file_name)
+                .with_loc(code_lines)
+        };
+
+        let per_file_tech = [
+            file_tech(".github/workflows/ci.yml", "cicd.github.yml", 30),
+            file_tech(".github/workflows/release.yml", "cicd.github.yml", 20),
+            file_tech("Dockerfile", "docker", 10),
+            file_tech("docker-compose.yml", "docker.yml", 15),
+            file_tech("infra/main.tf", "terraform", 40),
+            file_tech("src/main.rs", "rust.rs", 100),
+            // an older version of the same file from history
+            file_tech("Dockerfile", "docker", 5),
+        ];
+        let ops_tech = OpsTech::from_per_file_tech(per_file_tech.iter()).unwrap();
+        assert_eq!(ops_tech.files, 5);
+        assert_eq!(ops_tech.loc, 115);
+        assert_eq!(ops_tech.tools["github_actions"].files, 2);
+        assert_eq!(ops_tech.tools["github_actions"].loc, 50);
+        assert_eq!(ops_tech.tools["docker"].files, 2);
+        assert_eq!(ops_tech.tools["terraform"].loc, 40);
+
+        assert!(OpsTech::from_per_file_tech(per_file_tech[5..6].iter()).is_none());
+    }
+}
@@ -1,21 +1,71 @@
+use super::assets::Assets;
+use super::commit_time_histo::{WorkPattern, WorkdayOverlap};
+use super::coverage::Coverage;
+use super::documentation::Documentation;
+use super::frameworks::Framework;
+use super::licenses::Licenses;
+use super::ops_tech::OpsTech;
+use super::schema_migrations::SchemaMigrations;
 use super::tech::Tech;
 use chrono::{DateTime, Datelike, Timelike, Utc};
 use serde::{Deserialize, Serialize};
-use std::collections::{HashMap, HashSet};
-use tracing::warn;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use tracing::{error, warn};
+
+/// Per-language multipliers for `TechOverview.effective_loc`. Languages that are not listed have a factor of 1.
+const VERBOSITY_FACTORS: &str = include_str!("../../stm_rules/verbosity.json");
+
+/// Weights of the signals in `TechOverview.score`. They add up to 100.
+const SCORE_WEIGHT_LOC_SHARE: f64 = 30.0;
+const SCORE_WEIGHT_KEYWORDS: f64 = 25.0;
+const SCORE_WEIGHT_LIBS: f64 = 20.0;
+const SCORE_WEIGHT_RECENCY: f64 = 25.0;
+
+/// Number of unique keywords at which the keyword diversity signal maxes out.
+const SCORE_KEYWORDS_SATURATION: f64 = 50.0;
+
+/// Number of unique libraries at which the library breadth signal maxes out.
+const SCORE_LIBS_SATURATION: f64 = 30.0;
+
+/// The recency signal halves with every N days since the last commit.
+const SCORE_RECENCY_HALF_LIFE_DAYS: f64 = 365.0;
 
 /// A very concise overview of a single Tech record
 /// to show the share of the technology in the project
 #[derive(Serialize, Deserialize, Clone, Debug, Eq)]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
 pub struct TechOverview {
     /// The same as Tech.language
     pub language: String,
     /// Lines Of Code including blank lines
     pub loc: u64,
+    /// Lines Of Code in test files. Included in `loc`.
+    #[serde(default)]
+    pub test_loc: u64,
+    /// Lines Of Code in generated or vendored files. Not included in `loc`.
+    #[serde(default)]
+    pub generated_loc: u64,
+    /// Number of functions, types and other entities declared in non-test code. See `Tech.entities`.
+    #[serde(default)]
+    pub entities: u64,
+    /// Number of `entities` with doc comments or docstrings.
+    #[serde(default)]
+    pub documented_entities: u64,
     /// Total number of unique library names
     pub libs: u64,
     /// Percentage of the LoC for this tech from the total LoC for the project
     pub loc_percentage: u64,
+    /// Effective statements: code lines multiplied by the verbosity factor of the language from `stm_rules/verbosity.json`
+    /// so that verbose languages do not dominate the stack. Bracket-only lines are already excluded from `loc`.
+    #[serde(default)]
+    pub effective_loc: u64,
+    /// Percentage of `effective_loc` for this tech from the total `effective_loc` for the project
+    #[serde(default)]
+    pub effective_loc_percentage: u64,
+    /// Experience confidence from 0 to 100 derived from LoC share, keyword diversity, library breadth and
+    /// commit recency. Comparable across languages, unlike raw keyword counts. See `tech_score()`.
+    #[serde(default)]
+    pub score: u64,
 }
 
 impl std::hash::Hash for TechOverview {
@@ -34,12 +84,49 @@ impl PartialEq for TechOverview {
     }
 }
 
+/// Which of the LoC figures in `TechOverview` is used for the share of a language in the stack.
+/// Both figures are always stored in the reports.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum LocWeighting {
+    /// Raw lines of code, `TechOverview.loc`
+    #[default]
+    Loc,
+    /// Code lines adjusted for language verbosity, `TechOverview.effective_loc`
+    Effective,
+}
+
+impl TechOverview {
+    /// Returns `loc` or `effective_loc` depending on the weighting.
+    pub fn weighted_loc(&self, weighting: LocWeighting) -> u64 {
+        match weighting {
+            LocWeighting::Loc => self.loc,
+            LocWeighting::Effective => self.effective_loc,
+        }
+    }
+
+    /// Returns the percentage of `entities` with docs, e.g. `38` for Rust if 38% of functions and types have doc comments.
+    /// Returns None if there are no entity rules for the language or no declarations.
+    pub fn docs_coverage(&self) -> Option<u64> {
+        (self.documented_entities * 100).checked_div(self.entities)
+    }
+
+    /// Returns `loc_percentage` or `effective_loc_percentage` depending on the weighting.
+    pub fn weighted_loc_percentage(&self, weighting: LocWeighting) -> u64 {
+        match weighting {
+            LocWeighting::Loc => self.loc_percentage,
+            LocWeighting::Effective => self.effective_loc_percentage,
+        }
+    }
+}
+
 /// An overview of an individual project report included in the combined report
 /// to avoid loading the full project report every time the combined report is looked at.
 #[derive(Serialize, Deserialize, Clone, Debug, Eq)]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
 pub struct ProjectReportOverview {
     /// A human-readable project name. It should not be used as an ID.
-    #[serde(default = "String::new")]
+    #[serde(default)]
     pub project_name: String,
     /// `owner_id` + `project_id` are used to identify which project the overview belongs to.
     /// There should be just one project included in a contributor or a combined contributor report.
@@ -78,6 +165,12 @@ pub struct ProjectReportOverview {
     /// The value is set to zero in full project reports.
     #[serde(default)]
     pub loc: u64,
+    /// Lines Of Code in test files. Included in `loc`.
+    #[serde(default)]
+    pub test_loc: u64,
+    /// Lines Of Code in generated or vendored files. Not included in `loc`.
+    #[serde(default)]
+    pub generated_loc: u64,
     /// Total number of unique library names to show the breadth of the contribution
     /// /// The value is set to zero in full project reports.
     #[serde(default)]
@@ -99,8 +192,32 @@ pub struct ProjectReportOverview {
     /// Total number of commits in the repo.
     #[serde(default)]
     pub commit_count_project: u64,
+    /// README, docs folder, changelog and other documentation facts for the project at HEAD.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub documentation: Option<Documentation>,
+    /// Counts and sizes of binary and media files for projects that are more than code.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub assets: Option<Assets>,
+    /// The project license and the licenses of its dependencies.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub licenses: Option<Licenses>,
+    /// Database schema migrations in the project or written by the contributor.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub migrations: Option<SchemaMigrations>,
+    /// CI/CD pipelines, containers and infra-as-code in the project or written by the contributor.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ops_tech: Option<OpsTech>,
+    /// Frameworks detected from the libraries and packages used in the project or by the contributor, e.g. `Django`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub frameworks: Vec<Framework>,
+    /// Present if the project was too big and only the most recently changed files were analyzed.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub coverage: Option<Coverage>,
     /// Stats per stack technology.
     pub tech: HashSet<TechOverview>,
+    /// The shares of all commits made on weekends and after hours in the local time of each commit.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub work_pattern: Option<WorkPattern>,
     /// The last N commits for matching reports to projects.
     /// Full project reports have the list of commits from all contributors. Contributor reports only have commits for that contributor.
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -148,8 +265,15 @@ impl Tech {
         TechOverview {
             language: self.language.clone(),
             loc: self.code_lines,
-            // the percentage is not known at this stage
+            test_loc: self.test_loc,
+            generated_loc: self.generated_loc,
+            entities: self.entities,
+            documented_entities: self.documented_entities,
+            // the percentages and the score are not known at this stage
             loc_percentage: 0,
+            effective_loc: 0,
+            effective_loc_percentage: 0,
+            score: 0,
             // this is not a good way of doing it
             // there will be some overlap between pkgs and refs,
             // but getting a unique list is not that straight forward and is language specific
@@ -165,12 +289,25 @@ impl super::report::Report {
         // collect all tech data in the overview form
         // there may be multiple records for the same tech, e.g. Rust/.rs and Rust/.toml, so they need to be added up
         let mut tech_overviews: HashMap<String, TechOverview> = HashMap::new();
+        // unique keywords and the last commit per tech for scoring - the same keyword may be counted more than once
+        // if there are multiple records for the tech, same as libs
+        let mut score_signals: HashMap<String, (u64, Option<i64>)> = HashMap::new();
         for tech in &self.tech {
+            let signals = score_signals.entry(tech.language.clone()).or_insert((0, None));
+            signals.0 += tech.keywords.len() as u64;
+            if let Some(history) = &tech.history {
+                signals.1 = signals.1.max(Some(history.to_date_epoch));
+            }
+
             let tech_to_update_from = tech.get_overview();
             // update the existing record or add a new one
             if let Some(tech_to_update) = tech_overviews.get_mut(&tech.language) {
                 tech_to_update.libs += tech_to_update_from.libs;
                 tech_to_update.loc += tech_to_update_from.loc;
+                tech_to_update.test_loc += tech_to_update_from.test_loc;
+                tech_to_update.generated_loc += tech_to_update_from.generated_loc;
+                tech_to_update.entities += tech_to_update_from.entities;
+                tech_to_update.documented_entities += tech_to_update_from.documented_entities;
             } else {
                 tech_overviews.insert(tech.language.clone(), tech_to_update_from);
             }
@@ -182,8 +319,22 @@ impl super::report::Report {
             .map(|(_, t)| t)
             .collect::<HashSet<TechOverview>>();
 
+        // apply verbosity factors to the totals per language
+        let verbosity_factors = verbosity_factors();
+        let tech_overviews = tech_overviews
+            .into_iter()
+            .map(|mut t| {
+                let factor = verbosity_factors.get(&t.language).cloned().unwrap_or(1.0);
+                t.effective_loc = (t.loc as f64 * factor).round() as u64;
+                t
+            })
+            .collect::<HashSet<TechOverview>>();
+
         // collect summary
         let loc = tech_overviews.iter().map(|t| t.loc).sum::<u64>();
+        let effective_loc = tech_overviews.iter().map(|t| t.effective_loc).sum::<u64>();
+        let test_loc = tech_overviews.iter().map(|t| t.test_loc).sum::<u64>();
+        let generated_loc = tech_overviews.iter().map(|t| t.generated_loc).sum::<u64>();
         let libs = tech_overviews.iter().map(|t| t.libs).sum::<u64>();
         // contributor reports do not have a list of contributors, but may have the number copied from the project
         let ppl = match self.contributor_count {
@@ -196,12 +347,27 @@ impl super::report::Report {
             },
         };
 
-        // update percentages
+        // techs without their own history are as recent as the latest commit in the report
+        let now = Utc::now();
+        let last_commit_epoch = self
+            .last_contributor_commit_date_iso
+            .as_ref()
+            .or(self.date_head.as_ref())
+            .and_then(|v| DateTime::parse_from_rfc3339(v).ok())
+            .map(|v| v.timestamp());
+
+        // update percentages and scores
         let tech = tech_overviews
             .into_iter()
             .map(|mut t| {
                 // avoid division by zero
                 t.loc_percentage = t.loc * 100 / loc.max(1);
+                t.effective_loc_percentage = t.effective_loc * 100 / effective_loc.max(1);
+                let (keywords, tech_last_commit_epoch) = score_signals.get(&t.language).cloned().unwrap_or_default();
+                let days_since_last_commit = tech_last_commit_epoch
+                    .or(last_commit_epoch)
+                    .map(|v| (now.timestamp() - v) / 86400);
+                t.score = tech_score(t.loc_percentage, keywords, t.libs, days_since_last_commit);
                 t
             })
             .collect::<HashSet<TechOverview>>();
@@ -222,6 +388,14 @@ impl super::report::Report {
             None => None,
         };
 
+        // reports from older versions only have the list of commits in UTC
+        let work_pattern = self.work_pattern.clone().or_else(|| {
+            WorkPattern::from_commits(
+                self.recent_project_commits.as_deref().unwrap_or_default(),
+                &WorkdayOverlap::default(),
+            )
+        });
+
         ProjectReportOverview {
             project_name,
             project_id: self.project_id.clone(),
@@ -234,13 +408,26 @@ impl super::report::Report {
             contributor_first_commit: commit_timestamp_to_date(&self.first_contributor_commit_date_iso),
             contributor_last_commit: commit_timestamp_to_date(&self.last_contributor_commit_date_iso),
             loc,
+            test_loc,
+            generated_loc,
             libs,
             ppl,
+            work_pattern,
             commits: recent_project_commits,
             loc_project: self.loc_project.clone().unwrap_or_default(),
             libs_project: self.libs_project.clone().unwrap_or_default(),
             commit_count: self.commit_count_contributor.as_ref().unwrap_or_else(|| &0).clone(),
             commit_count_project: self.commit_count_project.as_ref().unwrap_or_else(|| &0).clone(),
+            documentation: self.documentation.clone(),
+            assets: self.assets.clone(),
+            licenses: self.licenses.clone(),
+            migrations: self.migrations.as_ref().map(|migrations| SchemaMigrations {
+                contributors: BTreeMap::new(),
+                ..migrations.clone()
+            }),
+            ops_tech: self.ops_tech.clone(),
+            frameworks: Framework::from_tech(self.tech.iter()),
+            coverage: self.coverage.clone(),
         }
     }
 }
@@ -263,6 +450,14 @@ impl ProjectReportOverview {
             };
         }
 
+        self.work_pattern = match (self.work_pattern.take(), rhs.work_pattern) {
+            (Some(mut work_pattern), Some(rhs_work_pattern)) => {
+                work_pattern.merge(&rhs_work_pattern);
+                Some(work_pattern)
+            }
+            (work_pattern, rhs_work_pattern) => work_pattern.or(rhs_work_pattern),
+        };
+
         self.commit_count += rhs.commit_count;
 
         // contributor_first_commit if earlier
@@ -297,6 +492,19 @@ impl ProjectReportOverview {
             self.commit_count_project = rhs.commit_count_project;
             self.ppl = rhs.ppl;
             self.project_name = rhs.project_name;
+            if rhs.documentation.is_some() {
+                self.documentation = rhs.documentation;
+            }
+            if rhs.assets.is_some() {
+                self.assets = rhs.assets;
+            }
+            if rhs.licenses.is_some() {
+                self.licenses = rhs.licenses;
+            }
+            self.migrations = rhs.migrations;
+            self.ops_tech = rhs.ops_tech;
+            self.frameworks = rhs.frameworks;
+            self.coverage = rhs.coverage;
         }
 
         // merge individual tech records
@@ -309,7 +517,16 @@ impl ProjectReportOverview {
             if let Some(tech) = techs.get_mut(&rhs_tech.language) {
                 // update the existing tech record
                 tech.loc = tech.loc.max(rhs_tech.loc);
+                tech.effective_loc = tech.effective_loc.max(rhs_tech.effective_loc);
+                tech.test_loc = tech.test_loc.max(rhs_tech.test_loc);
+                tech.generated_loc = tech.generated_loc.max(rhs_tech.generated_loc);
+                // the counts are taken from the same report to keep the ratio meaningful
+                if rhs_tech.entities > tech.entities {
+                    tech.entities = rhs_tech.entities;
+                    tech.documented_entities = rhs_tech.documented_entities;
+                }
                 tech.libs = tech.libs.max(rhs_tech.libs);
+                tech.score = tech.score.max(rhs_tech.score);
             } else {
                 // new tech - insert as is
                 techs.insert(rhs_tech.language.clone(), rhs_tech);
@@ -318,9 +535,13 @@ impl ProjectReportOverview {
 
         // recalculate totals and LoC percentage
         self.loc = techs.iter().map(|(_, t)| t.loc).sum::<u64>();
+        self.test_loc = techs.values().map(|t| t.test_loc).sum::<u64>();
+        self.generated_loc = techs.values().map(|t| t.generated_loc).sum::<u64>();
         self.libs = techs.iter().map(|(_, t)| t.libs).sum::<u64>();
+        let effective_loc = techs.values().map(|t| t.effective_loc).sum::<u64>();
         for (_, tech) in techs.iter_mut() {
-            tech.loc_percentage = tech.loc * 100 / self.loc;
+            tech.loc_percentage = tech.loc * 100 / self.loc.max(1);
+            tech.effective_loc_percentage = tech.effective_loc * 100 / effective_loc.max(1);
         }
 
         // return the list of technologies back into self
@@ -328,6 +549,52 @@ impl ProjectReportOverview {
     }
 }
 
+/// Returns experience confidence from 0 to 100 for a single tech. Each signal is normalized into 0..1 range
+/// and weighted with `SCORE_WEIGHT_*` constants:
+/// * LoC share: square root of the percentage, so that a tech with 25% of the project gets half of the weight
+/// * keyword diversity and library breadth: logarithmic, maxing out at `SCORE_*_SATURATION` unique values,
+///   so that languages with large vocabularies do not dominate
+/// * recency: halves every `SCORE_RECENCY_HALF_LIFE_DAYS`, zero if the date of the last commit is unknown
+pub(crate) fn tech_score(loc_percentage: u64, keywords: u64, libs: u64, days_since_last_commit: Option<i64>) -> u64 {
+    let loc_share = (loc_percentage.min(100) as f64 / 100.0).sqrt();
+    let keywords = log_saturation(keywords, SCORE_KEYWORDS_SATURATION);
+    let libs = log_saturation(libs, SCORE_LIBS_SATURATION);
+    // commits from the future are as recent as it gets
+    let recency = match days_since_last_commit {
+        Some(days) => 0.5f64.powf(days.max(0) as f64 / SCORE_RECENCY_HALF_LIFE_DAYS),
+        None => 0.0,
+    };
+
+    let score = loc_share * SCORE_WEIGHT_LOC_SHARE
+        + keywords * SCORE_WEIGHT_KEYWORDS
+        + libs * SCORE_WEIGHT_LIBS
+        + recency * SCORE_WEIGHT_RECENCY;
+
+    (score.round() as u64).min(100)
+}
+
+/// Returns per-language verbosity factors from `stm_rules/verbosity.json`, e.g. `Java: 0.75`.
+/// An invalid file is a bug and is treated as having no factors.
+fn verbosity_factors() -> HashMap<String, f64> {
+    #[derive(Deserialize)]
+    struct VerbosityFactors {
+        factors: HashMap<String, f64>,
+    }
+
+    match serde_json::from_str::<VerbosityFactors>(VERBOSITY_FACTORS) {
+        Ok(v) => v.factors,
+        Err(e) => {
+            error!("Cannot parse verbosity.json due to {}. It's a bug.", e);
+            HashMap::new()
+        }
+    }
+}
+
+/// Converts a count into 0..1 range on a log scale with 1 at `saturation` and above.
+fn log_saturation(count: u64, saturation: f64) -> f64 {
+    ((count as f64).ln_1p() / saturation.ln_1p()).min(1.0)
+}
+
 /// Resets the time component by converting ISO dates like `2020-07-28T14:30:50-07:00` into `2020-07-28T00:00:00+00:00`
 fn commit_timestamp_to_date(timestamp: &Option<String>) -> Option<String> {
     // a russian doll of safe unwraps to get to the end of the formatting
@@ -391,3 +658,84 @@ const DAYS_AS_LETTERS: [&str; 31] = [
     "a", "b", "c", "d", "e", "f", "g", "h", "i", "j", "k", "l", "m", "n", "o", "p", "q", "r", "s", "t", "u", "v", "w",
     "x", "y", "z", "aa", "bb", "cc", "dd", "xx",
 ];
+
+#[cfg(test)]
+mod test_overview {
+    use super::{tech_score, verbosity_factors, LocWeighting};
+    use crate::report::{Report, Tech};
+
+    #[test]
+    fn test_tech_score() {
+        // nothing to go by
+        assert_eq!(tech_score(0, 0, 0, None), 0);
+        // all signals at the max
+        assert_eq!(tech_score(100, 50, 30, Some(0)), 100);
+        assert_eq!(tech_score(150, 5000, 3000, Some(-10)), 100);
+
+        // each signal on its own adds up to its weight
+        assert_eq!(tech_score(25, 0, 0, None), 15);
+        assert_eq!(tech_score(0, 50, 0, None), 25);
+        assert_eq!(tech_score(0, 0, 30, None), 20);
+        assert_eq!(tech_score(0, 0, 0, Some(365)), 13);
+        assert_eq!(tech_score(0, 0, 0, Some(3650)), 0);
+
+        // more is better, but with diminishing returns
+        assert!(tech_score(0, 10, 0, None) < tech_score(0, 20, 0, None));
+        assert!(tech_score(0, 20, 0, None) - tech_score(0, 10, 0, None) < tech_score(0, 10, 0, None));
+        assert!(tech_score(50, 20, 10, Some(30)) > tech_score(50, 20, 10, Some(300)));
+    }
+
+    #[test]
+    fn test_effective_loc() {
+        let factors = verbosity_factors();
+        assert_eq!(factors.get("Java"), Some(&0.75));
+        assert_eq!(factors.get("Python"), Some(&1.25));
+
+        let mut report = Report::new();
+        report.tech.insert(Tech {
+            total_lines: 120,
+            bracket_only_lines: 20,
+            ..Tech::test("Java", "java").with_loc(100)
+        });
+        report.tech.insert(Tech::test("Python", "python").with_loc(100));
+
+        let overview = report.get_overview();
+        let java = overview.tech.iter().find(|t| t.language == "Java").unwrap();
+        let python = overview.tech.iter().find(|t| t.language == "Python").unwrap();
+
+        // the same LoC, but Python does more per line
+        assert_eq!(java.loc_percentage, 50);
+        assert_eq!(python.loc_percentage, 50);
+        assert_eq!(java.effective_loc, 75);
+        assert_eq!(python.effective_loc, 125);
+        assert_eq!(java.weighted_loc_percentage(LocWeighting::Effective), 37);
+        assert_eq!(python.weighted_loc_percentage(LocWeighting::Effective), 62);
+        assert_eq!(python.weighted_loc_percentage(LocWeighting::Loc), 50);
+
+        // merging recalculates both percentages
+        let mut merged = overview.clone();
+        merged.merge(overview);
+        let java = merged.tech.iter().find(|t| t.language == "Java").unwrap();
+        assert_eq!(java.loc_percentage, 50);
+        assert_eq!(java.effective_loc_percentage, 37);
+    }
+
+    #[test]
+    fn test_merge_empty() {
+        // a report with files, but no LoC, e.g. only blank files
+        let mut report = Report::new();
+        report.tech.insert(Tech {
+            total_lines: 3,
+            blank_lines: 3,
+            ..Tech::test("Rust", "rust")
+        });
+
+        let overview = report.get_overview();
+        let mut merged = overview.clone();
+        merged.merge(overview);
+        assert_eq!(merged.loc, 0);
+        let rust = merged.tech.iter().find(|t| t.language == "Rust").unwrap();
+        assert_eq!(rust.loc_percentage, 0);
+        assert_eq!(rust.effective_loc_percentage, 0);
+    }
+}
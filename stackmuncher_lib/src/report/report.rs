@@ -1,9 +1,31 @@
-use super::commit_time_histo::CommitTimeHisto;
+use super::co_change::{CoChange, CoChangeLimits};
+use super::collaboration::Collaboration;
+use super::coverage::Coverage;
+use super::hotspots::Hotspots;
+use super::import_graph::ImportGraph;
+use super::commit_cadence::CommitCadence;
+use super::commit_time_histo::{CommitTimeHisto, WorkPattern, WorkdayOverlap};
+use super::assets::Assets;
+use super::documentation::Documentation;
+use super::duplicate_files::DuplicateFiles;
+use super::licenses::Licenses;
 use super::kwc::{KeywordCounter, KeywordCounterSet};
+use super::migration;
+use super::ops_tech::OpsTech;
+use super::schema_migrations::SchemaMigrations;
+use super::scrub::ScrubRules;
 use super::tech::{Tech, TechHistory};
+use super::tech_debt::TechDebtMarkers;
+use super::tenure::Tenure;
+use super::trends::Trends;
+use super::truncation::{TechTruncation, Truncation};
+use super::work_distribution::WorkDistributionQuarter;
 use super::ProjectReportOverview;
 use crate::utils::sha256::hash_str_to_sha256_as_base58;
-use crate::{contributor::Contributor, git::GitLogEntry, utils};
+use crate::code_rules::CodeRules;
+use crate::contributor::{Contributor, FailedContributor};
+use crate::skip_list::{ProblemFile, SkipList};
+use crate::{git::GitLogEntry, utils};
 use chrono::{DateTime, Utc};
 use flate2::write::GzEncoder;
 use flate2::Compression;
@@ -13,12 +35,14 @@ use serde_json;
 use std::collections::{HashMap, HashSet};
 use std::fs::File;
 use std::io::prelude::*;
+use std::io::BufReader;
 use std::path::{Path, PathBuf};
 use tracing::{debug, error, info, warn};
 
 /// Contains the number of elements per list to help with DB queries.
 /// The numbers are calculated once before saving the Report in the DB.
 #[derive(Serialize, Deserialize, Clone, Debug)]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
 pub struct ListCounts {
     tech: u64,
     contributor_git_ids: u64,
@@ -33,16 +57,57 @@ pub struct ListCounts {
     keywords: u64,
 }
 
+/// Limits on the size of tech records in merged reports. Can be changed in `merge_limits` section of config.json.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(default)]
+pub struct MergeLimits {
+    /// The max number of keywords, refs and pkgs per tech record retained by `Report::merge_with_limits`.
+    /// The rest are dropped with their counts added up in `Tech.overflow_distinct` and `Tech.overflow_total`.
+    pub max_keywords_per_tech: usize,
+}
+
+impl Default for MergeLimits {
+    fn default() -> Self {
+        Self {
+            max_keywords_per_tech: 1000,
+        }
+    }
+}
+
 #[derive(Serialize, Deserialize, Clone, Debug)]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
 #[serde(rename = "tech")]
 pub struct Report {
     /// The exact timestamp of the report generation in ISO3389 format.
     /// E.g. 2018-12-09T22:29:40+01:00
     pub timestamp: String,
+    /// The value of `REPORT_FORMAT_VERSION` the report conforms to. Missing in reports generated before cached reports
+    /// could be upgraded, in which case the format is that of `timestamp`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub format_version: Option<String>,
     /// Member email address for STM notifications. No update is needed if None. Clear if Some("").
     /// Only used for Inbox reports.
     #[serde(skip_serializing_if = "Option::is_none")]
     pub primary_email: Option<String>,
+    /// The name the member wants shown in their Directory Profile. No update is needed if None. Clear if Some("").
+    /// Only used for Inbox reports.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub public_name: Option<String>,
+    /// A one-line summary for the Directory Profile, e.g. `Backend engineer - Rust/Go`. No update is needed if None.
+    /// Clear if Some(""). Only used for Inbox reports.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub headline: Option<String>,
+    /// A public way to contact the member, e.g. a URL or an email shown in the Directory Profile. No update is needed
+    /// if None. Clear if Some(""). Only used for Inbox reports.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub public_contact: Option<String>,
+    /// Is the member open to job offers? No update is needed if None. Only used for Inbox reports.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub available: Option<bool>,
+    /// A free-form location for the Directory Profile, e.g. `Berlin, Germany` or `Remote, UTC+1`. No update is needed
+    /// if None. Clear if Some(""). Only used for Inbox reports.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub location: Option<String>,
     /// GitHub user name, if known
     #[serde(skip_serializing_if = "Option::is_none")]
     pub github_user_name: Option<String>,
@@ -50,6 +115,14 @@ pub struct Report {
     /// E.g. `https://github.com/awslabs/aws-lambda-rust-runtime.git` would be `aws-lambda-rust-runtime`.
     #[serde(skip_serializing_if = "Option::is_none")]
     pub github_repo_name: Option<String>,
+    /// The host and the owning org or user of the repo extracted from its remote URL, e.g. `github.com/stackmuncher`.
+    /// Used for grouping local rollups only. It is removed from the report before submission.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub remote_org: Option<String>,
+    /// SHA1 hash of the normalized `origin` remote URL of the repo for finding local clones of the same repo,
+    /// so that they are counted once in local rollups. It is removed from the report before submission.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub remote_url_hash: Option<String>,
     /// A unique identifier of the dev on STM server, if known.
     /// Populated by the server upon report submission and is None otherwise.
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -59,10 +132,10 @@ pub struct Report {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub project_id: Option<String>,
     /// A UUID of the report
-    #[serde(skip_serializing_if = "String::is_empty", default = "String::new")]
+    #[serde(skip_serializing_if = "String::is_empty", default)]
     pub report_id: String,
     /// A unique name containing user name and project name when stored in S3, e.g. `rimutaka/stackmuncher.report`
-    #[serde(skip_serializing_if = "String::is_empty", default = "String::new")]
+    #[serde(skip_serializing_if = "String::is_empty", default)]
     pub report_s3_name: String,
     /// A GH ownership validation Gist ID.
     /// E.g. fb8fc0f87ee78231f064131022c8154a from https://gist.github.com/rimutaka/fb8fc0f87ee78231f064131022c8154a
@@ -72,12 +145,35 @@ pub struct Report {
     /// The very last commit at the time of the report generation.
     #[serde(skip_serializing_if = "Option::is_none")]
     pub report_commit_sha1: Option<String>,
+    /// The branch, tag or commit the report was generated for, as given by the user, e.g. `release/1.2`.
+    /// None if it was HEAD. `report_commit_sha1` is the commit it pointed at.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub git_ref: Option<String>,
     /// A SHA1 hash of all commit SHA1s to determine changes by looking at the log
     #[serde(skip_serializing_if = "Option::is_none")]
     pub log_hash: Option<String>,
+    /// Same as `ProjectConfig::hash()` of `.stackmuncher.toml` the report was generated with. A mismatch means
+    /// the project has to be reprocessed even if there were no new commits. Only applies to project reports.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub project_config_hash: Option<String>,
     /// Is `true` if the report was generated by adding a single commit to a cached report
     #[serde(default = "default_as_false")]
     pub is_single_commit: bool,
+    /// Is `true` if some of the oldest commits were missing from the repo, e.g. a `git clone --depth 50` on CI.
+    /// `date_init` is then copied from an earlier report, if any, and `commit_count_project` may be too low.
+    #[serde(default = "default_as_false")]
+    pub history_truncated: bool,
+    /// Is `true` if the repo had more files than `MunchLimits.max_files` and only the most recently changed ones
+    /// were analyzed. See `coverage` for details.
+    #[serde(default = "default_as_false")]
+    pub partial: bool,
+    /// Is `true` if the repo had no commits yet, e.g. right after `git init`. The rest of the report is blank.
+    #[serde(default = "default_as_false", skip_serializing_if = "std::ops::Not::not")]
+    pub empty_repo: bool,
+    /// Numbers of files in the tree that were munched, skipped, ignored or not recognized, and the share of the files
+    /// analyzed if `partial` is `true`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub coverage: Option<Coverage>,
     /// Git identity of the author of the last (HEAD) commit. Should only be present in the project report.
     #[serde(skip_serializing_if = "Option::is_none")]
     pub last_commit_author: Option<String>,
@@ -149,27 +245,44 @@ pub struct Report {
     /// Per-file technology summary, e.g. Rust/main.rs.
     /// This member should not be shared publicly, unless it's a public project
     /// because file names are sensitive info that can be exploited.
-    #[serde(skip_serializing_if = "HashSet::is_empty", default = "HashSet::new")]
+    #[serde(skip_serializing_if = "HashSet::is_empty", default)]
     pub per_file_tech: HashSet<Tech>,
-    #[serde(skip_serializing_if = "HashSet::is_empty", default = "HashSet::new")]
+    /// Per-file records for files that are no longer in HEAD, moved out of `per_file_tech` by `compact_per_file_tech()`.
+    /// They are only kept for reuse of processed data and are stored in a separate `.history.json` file next to the report.
+    #[serde(skip)]
+    pub per_file_tech_history: HashSet<Tech>,
+    /// Files deleted from the tree whose records were pruned from `per_file_tech_history` by the retention policy.
+    /// They are left out of the contributor report unless they reappear in the tree. Used in contributor reports only.
+    #[serde(skip_serializing_if = "HashSet::is_empty", default)]
+    pub pruned_file_names: HashSet<String>,
+    /// Paths of files with identical contents, a sorted list per blob. Only the first path of every list found
+    /// in `per_file_tech` of the report is counted in `tech` totals by `recompute_tech_section()`.
+    /// Copied from the project report into the combined report. See `duplicate_files`.
+    #[serde(skip_serializing_if = "Vec::is_empty", default)]
+    pub duplicate_file_groups: Vec<Vec<String>>,
+    #[serde(skip_serializing_if = "HashSet::is_empty", default)]
     pub unprocessed_file_names: HashSet<String>,
     /// A list of all file extensions used in the project with the number of times they were encountered.
-    #[serde(skip_serializing_if = "HashSet::is_empty", default = "HashSet::new")]
+    #[serde(skip_serializing_if = "HashSet::is_empty", default)]
     pub file_types: HashSet<KeywordCounter>,
     /// S3 keys of the reports from `report_s3_name` merged into a combined user or org report
     /// This attribute was depricated in favour of projects_included, but has to be in use until
     /// https://github.com/stackmuncher/stm-html/issues/8 is resolved.
-    #[serde(skip_serializing_if = "HashSet::is_empty", default = "HashSet::new")]
+    #[serde(skip_serializing_if = "HashSet::is_empty", default)]
     pub reports_included: HashSet<String>,
     // Brief details about the projects included into a combined user or org report.
     /// Blank for individual project reports. It is only needed by STM server to display project details on the combined report page
     /// without going to the individual project reports.
-    #[serde(skip_serializing_if = "Vec::is_empty", default = "Vec::new")]
+    #[serde(skip_serializing_if = "Vec::is_empty", default)]
     pub projects_included: Vec<ProjectReportOverview>,
     /// A list of GIT identities for the contributors included in the report.
     /// Used only in combined contributor reports
-    #[serde(skip_serializing_if = "HashSet::is_empty", default = "HashSet::new")]
+    #[serde(skip_serializing_if = "HashSet::is_empty", default)]
     pub git_ids_included: HashSet<String>,
+    /// Local contributor identities whose reports could not be generated, e.g. due to missing git objects.
+    /// The tech section is incomplete if the list is not empty. Used in combined contributor reports only.
+    #[serde(skip_serializing_if = "Vec::is_empty", default)]
+    pub failed_contributors: Vec<FailedContributor>,
     /// List of names and emails of all committers for this repo. Only applies to per-project reports.
     #[serde(skip_serializing_if = "Option::is_none")]
     pub contributors: Option<Vec<Contributor>>,
@@ -177,6 +290,63 @@ pub struct Report {
     /// Used to determine approximate active timezone of the dev.
     #[serde(skip_serializing_if = "Option::is_none")]
     pub commit_time_histo: Option<CommitTimeHisto>,
+    /// The shares of all commits of the project or the contributor, depending on the report type, made on weekends
+    /// and after hours. Calculated from the full log before the list of commits is truncated.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub work_pattern: Option<WorkPattern>,
+    /// Weekly commit counts, streaks and gaps for the project or the contributor, depending on the report type.
+    /// Not present in reports merged from multiple projects.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub commit_cadence: Option<CommitCadence>,
+    /// How long the contributor was active in the project: the span of their commits, active months and the longest gap.
+    /// Only present in contributor and combined reports of a single project.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tenure: Option<Tenure>,
+    /// Number of commits per project per calendar quarter, oldest first. Populated during merge.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub work_distribution: Option<Vec<WorkDistributionQuarter>>,
+    /// Files and folders that are frequently changed together. Only present in project reports.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub co_change: Option<CoChange>,
+    /// Contributors who repeatedly change the same files close in time. Only present in project reports.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub collaboration: Option<Collaboration>,
+    /// Large files that change often, ranked by churn × LoC. Only present in project reports.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hotspots: Option<Hotspots>,
+    /// Files with identical contents under several paths, e.g. vendored libraries. Only present in project reports.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub duplicate_files: Option<DuplicateFiles>,
+    /// Files most imported by or importing other project files. Only present in project reports.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub import_graph: Option<ImportGraph>,
+    /// Files that failed or took too long to munch and are skipped on future runs. Only present in project reports.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub problem_files: Option<Vec<ProblemFile>>,
+    /// README, docs folder, changelog and other documentation facts for the project at HEAD.
+    /// Copied into the project overview.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub documentation: Option<Documentation>,
+    /// Counts and sizes of images, fonts, archives, ML models and other binary files at HEAD.
+    /// Copied into the project overview.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub assets: Option<Assets>,
+    /// The project license and the licenses of its dependencies at HEAD.
+    /// Copied into the project overview.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub licenses: Option<Licenses>,
+    /// Database schema migrations in the project or written by the contributor, depending on the report type.
+    /// Copied into the project overview.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub migrations: Option<SchemaMigrations>,
+    /// CI/CD pipelines, containers and infra-as-code in the project or written by the contributor, depending on the report type.
+    /// Copied into the project overview.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ops_tech: Option<OpsTech>,
+    /// TODO, FIXME and other markers in comments of the files at HEAD in the project or committed by the contributor,
+    /// depending on the report type. Only present if `MunchLimits.tech_debt_markers` is enabled.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tech_debt_markers: Option<TechDebtMarkers>,
     /// The current list of files in the GIT tree
     #[serde(skip_serializing_if = "Option::is_none")]
     pub tree_files: Option<HashSet<String>>,
@@ -188,6 +358,13 @@ pub struct Report {
     /// Populated during merge.
     #[serde(skip_serializing_if = "Option::is_none")]
     pub keywords: Option<HashSet<String>>,
+    /// Changes in LoC and language shares over the last few runs. Only in the combined report and never submitted.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub trends: Option<Trends>,
+    /// What was trimmed from the submission to fit `MAX_SUBMITTED_KEYWORDS_PER_TECH`, `MAX_RECENT_PROJECT_COMMITS`
+    /// and `MAX_SUBMITTED_FILES`. Only set by `sanitize` and only if anything was trimmed.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub truncation: Option<Truncation>,
 }
 
 /// A plug for Serde default
@@ -195,20 +372,72 @@ fn default_as_false() -> bool {
     false
 }
 
+/// Why a report could not be loaded with `Report::from_reader` or `Report::from_slice`.
+#[derive(Debug)]
+pub enum ReportError {
+    /// The contents could not be read, e.g. a closed connection.
+    Io(std::io::Error),
+    /// The contents are not valid JSON.
+    InvalidJson(serde_json::Error),
+    /// The JSON does not match the report format, even after an upgrade from an older format.
+    InvalidReport(serde_json::Error),
+}
+
+impl std::fmt::Display for ReportError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Io(e) => write!(f, "cannot read the report: {}", e),
+            Self::InvalidJson(e) => write!(f, "the report is not valid JSON: {}", e),
+            Self::InvalidReport(e) => write!(f, "invalid report format: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for ReportError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Io(e) => Some(e),
+            Self::InvalidJson(e) | Self::InvalidReport(e) => Some(e),
+        }
+    }
+}
+
 impl Report {
     /// .report
     pub const REPORT_FILE_NAME_SUFFIX: &'static str = ".report";
 
-    /// Repos with this more files than this are ignored
-    /// This is a temporary measure. The file count should be taken after some files were ignored,
-    /// but since ignoring files like nodejs modules is not implemented we'll just ignore such repos.
+    /// The default for `MunchLimits.max_files`. Repos with more files than this are ignored
+    /// unless partial analysis is enabled with `MunchLimits.partial`.
     pub const MAX_FILES_PER_REPO: u64 = 10000;
 
-    /// All project reports created prior to this date must be reprocessed
-    pub const REPORT_FORMAT_VERSION: &'static str = "2021-11-02T00:23:00+00:00";
+    /// All cached reports in an older format must be upgraded or reprocessed.
+    /// Add a matching entry to `migration::MIGRATIONS` when changing it.
+    pub const REPORT_FORMAT_VERSION: &'static str = "2021-12-01T00:00:00+00:00";
+
+    /// The max number of keywords, refs and pkgs per tech record in a submission. See `Truncation`.
+    pub const MAX_SUBMITTED_KEYWORDS_PER_TECH: usize = 250;
+
+    /// The max number of `recent_project_commits` kept in a report and submitted.
+    pub const MAX_RECENT_PROJECT_COMMITS: usize = 500;
+
+    /// The max number of `per_file_tech` records in a submission. The records with the most LoC are kept.
+    pub const MAX_SUBMITTED_FILES: usize = 5000;
 
     /// Adds up `tech` totals from `other_report` into `self`, clears unprocessed files and unknown extensions.
+    /// Keyword lists are capped with the default `MergeLimits`.
     pub fn merge(merge_into: Option<Self>, other_report: Self) -> Option<Self> {
+        Self::merge_with_limits(merge_into, other_report, &MergeLimits::default())
+    }
+
+    /// Merges all `reports` in order with `merge`. Returns None if there was nothing to merge, e.g. no reports
+    /// or only reports with no LoC.
+    pub fn merge_many(reports: impl IntoIterator<Item = Self>) -> Option<Self> {
+        reports.into_iter().fold(None, Self::merge)
+    }
+
+    /// Same as `merge`, but keeps only the top `limits.max_keywords_per_tech` keywords, refs and pkgs per tech record.
+    pub fn merge_with_limits(merge_into: Option<Self>, other_report: Self, limits: &MergeLimits) -> Option<Self> {
+        let max_keywords = limits.max_keywords_per_tech;
         let mut merge_into = merge_into;
         let mut other_report = other_report;
 
@@ -233,13 +462,27 @@ impl Report {
             tech.muncher_name = String::new();
             new_rep_tech.merge_tech_record(tech);
         }
-        other_report.tech = new_rep_tech.tech;
+        other_report.tech = new_rep_tech
+            .tech
+            .into_iter()
+            .map(|mut tech| {
+                tech.retain_top_keywords(max_keywords);
+                tech
+            })
+            .collect();
 
         // the very first report is added with minimal changes
         if merge_into.is_none() {
             info!("Adding 1st report (master)");
             other_report.unprocessed_file_names.clear();
             other_report.projects_included.clear();
+            // the cadence and the tenure are per project and would be misleading in a multi-project report
+            other_report.commit_cadence = None;
+            other_report.tenure = None;
+            // the histogram of the project is in percentages and is rebuilt from the commits of all projects below
+            other_report.commit_time_histo = None;
+            other_report.work_pattern = None;
+            other_report.trends = None;
             merge_into = Some(other_report);
         } else {
             // additional reports are merged
@@ -251,6 +494,16 @@ impl Report {
                 merge_into_inner.merge_tech_record(tech);
             }
 
+            // the merged lists may be over the limit again
+            merge_into_inner.tech = merge_into_inner
+                .tech
+                .drain()
+                .map(|mut tech| {
+                    tech.retain_top_keywords(max_keywords);
+                    tech
+                })
+                .collect();
+
             // merge unknown_file_types
             for uft in other_report.file_types {
                 merge_into_inner.file_types.increment_counters(uft);
@@ -304,6 +557,8 @@ impl Report {
                 warn!("Missing date_init");
             }
 
+            merge_into_inner.history_truncated |= other_report.history_truncated;
+
             // update contributor commit dates
             if merge_into_inner.last_contributor_commit_date_iso.is_none() {
                 merge_into_inner.last_contributor_commit_date_iso = other_report.last_contributor_commit_date_iso;
@@ -333,9 +588,22 @@ impl Report {
                 merge_into_inner.git_ids_included.insert(contributor_git_id);
             }
 
+            // a rollup of projects from different orgs has no single org
+            if merge_into_inner.remote_org != other_report.remote_org {
+                merge_into_inner.remote_org = None;
+            }
+            if merge_into_inner.remote_url_hash != other_report.remote_url_hash {
+                merge_into_inner.remote_url_hash = None;
+            }
+
             // copy the dev identity if the other report is newer by its timestamp
             if other_report.timestamp > merge_into_inner.timestamp {
                 merge_into_inner.primary_email = other_report.primary_email;
+                merge_into_inner.public_name = other_report.public_name;
+                merge_into_inner.headline = other_report.headline;
+                merge_into_inner.public_contact = other_report.public_contact;
+                merge_into_inner.available = other_report.available;
+                merge_into_inner.location = other_report.location;
             }
         }
 
@@ -343,6 +611,16 @@ impl Report {
         if let Some(mut report_inner) = merge_into.as_mut() {
             // update the commit time histogram
             CommitTimeHisto::add_commits(&mut report_inner, &other_report_overview.commits);
+            // the work pattern of all projects is weighted by the number of commits in each
+            if let Some(work_pattern) = &other_report_overview.work_pattern {
+                match report_inner.work_pattern.as_mut() {
+                    Some(v) => v.merge(work_pattern),
+                    None => report_inner.work_pattern = Some(work_pattern.clone()),
+                }
+            }
+
+            // the share of each project in the commits per quarter for the profile timeline
+            report_inner.add_to_work_distribution(&other_report_overview);
 
             // add the project overview
             if report_inner.projects_included.contains(&other_report_overview) {
@@ -397,6 +675,18 @@ impl Report {
             master.block_comments += tech.block_comments;
             master.bracket_only_lines += tech.bracket_only_lines;
             master.code_lines += tech.code_lines;
+            master.test_files += tech.test_files;
+            master.test_loc += tech.test_loc;
+            master.generated_loc += tech.generated_loc;
+            master.entities += tech.entities;
+            master.documented_entities += tech.documented_entities;
+            master.code_cells += tech.code_cells;
+            master.markdown_cells += tech.markdown_cells;
+            master.overflow_distinct += tech.overflow_distinct;
+            master.overflow_total += tech.overflow_total;
+            master.undecodable_files += tech.undecodable_files;
+            master.sampled |= tech.sampled;
+            master.history = TechHistory::merge(master.history.take(), tech.history);
 
             // add keyword counts
             for kw in tech.keywords {
@@ -410,6 +700,15 @@ impl Report {
             for kw in tech.pkgs {
                 master.pkgs.increment_counters(kw);
             }
+            for kw in tech.interop {
+                master.interop.increment_counters(kw);
+            }
+            for kw in tech.language_version {
+                master.language_version.increment_counters(kw);
+            }
+            for kw in tech.tech_debt_markers {
+                master.tech_debt_markers.increment_counters(kw);
+            }
 
             // add unique words from dependencies - references
             if tech.refs_kw.is_some() {
@@ -452,7 +751,105 @@ impl Report {
     /// Adds the name of the other report to `reports_included`.
     pub fn merge_same_project_contributor_reports(&mut self, other_report: Self, contributor_git_id: String) {
         debug!("Merging contributor report for {}", contributor_git_id);
-        'outer: for tech in other_report.per_file_tech {
+        // records for deleted files are still part of the contribution
+        self.restore_per_file_tech_history();
+        let mut other_report = other_report;
+        other_report.restore_per_file_tech_history();
+        self.merge_newer_per_file_tech(other_report.per_file_tech);
+
+        self.git_ids_included.insert(contributor_git_id.clone());
+
+        // merge project metadata - the latest of the two contributor reports gets its data copied over
+        // to the combined report
+        if let Some(commit_date_iso) = other_report.last_contributor_commit_date_iso {
+            if commit_date_iso > self.last_contributor_commit_date_iso.clone().unwrap_or_default() {
+                debug!(
+                    "Report for {} is newer: {} vs {:?}",
+                    contributor_git_id, commit_date_iso, self.last_contributor_commit_date_iso
+                );
+                self.last_contributor_commit_date_iso = Some(commit_date_iso);
+                self.last_contributor_commit_date_epoch = other_report.last_contributor_commit_date_epoch;
+                self.last_contributor_commit_sha1 = other_report.last_contributor_commit_sha1;
+                self.report_commit_sha1 = other_report.report_commit_sha1;
+                self.documentation = other_report.documentation;
+                self.assets = other_report.assets;
+                self.licenses = other_report.licenses;
+                self.migrations = other_report.migrations;
+            }
+        }
+    }
+
+    /// Merges the combined report of another local clone of the same repo into this one, e.g. a clone with commits
+    /// that were never pulled into this one. Unlike `merge`, the files present in both clones are counted once
+    /// with the most recent record and the commits of both clones are combined with no duplicates.
+    /// The `tech` section is recomputed from the combined per-file records.
+    pub fn merge_clone(&mut self, other_report: Self) {
+        let mut other_report = other_report;
+        self.restore_per_file_tech_history();
+        other_report.restore_per_file_tech_history();
+        self.merge_newer_per_file_tech(other_report.per_file_tech);
+        self.recompute_tech_section();
+
+        for contributor_git_id in other_report.git_ids_included {
+            self.git_ids_included.insert(contributor_git_id);
+        }
+
+        // the same commit is listed by both clones, e.g. `e29d17e6_1627380297`, newest first
+        let mut commits = self.recent_project_commits.take().unwrap_or_default();
+        for commit in other_report.recent_project_commits.unwrap_or_default() {
+            if !commits.contains(&commit) {
+                commits.push(commit);
+            }
+        }
+        commits.sort_by_key(|commit| {
+            std::cmp::Reverse(
+                commit
+                    .rsplit('_')
+                    .next()
+                    .and_then(|v| v.parse::<i64>().ok())
+                    .unwrap_or_default(),
+            )
+        });
+        commits.truncate(Self::MAX_RECENT_PROJECT_COMMITS);
+
+        // the lists of commits may be truncated, so the counts are the best known lower bound
+        self.commit_count_contributor = Some(
+            self.commit_count_contributor
+                .unwrap_or_default()
+                .max(other_report.commit_count_contributor.unwrap_or_default())
+                .max(commits.len() as u64),
+        );
+        self.commit_count_project = self.commit_count_project.max(other_report.commit_count_project);
+        self.recent_project_commits = Some(commits);
+
+        // None is less than any date, so only the first dates need a check for it
+        if other_report.last_contributor_commit_date_iso > self.last_contributor_commit_date_iso {
+            self.last_contributor_commit_sha1 = other_report.last_contributor_commit_sha1;
+            self.last_contributor_commit_date_iso = other_report.last_contributor_commit_date_iso;
+            self.last_contributor_commit_date_epoch = other_report.last_contributor_commit_date_epoch;
+        }
+        if other_report.first_contributor_commit_date_iso.is_some()
+            && (self.first_contributor_commit_date_iso.is_none()
+                || other_report.first_contributor_commit_date_iso < self.first_contributor_commit_date_iso)
+        {
+            self.first_contributor_commit_sha1 = other_report.first_contributor_commit_sha1;
+            self.first_contributor_commit_date_iso = other_report.first_contributor_commit_date_iso;
+            self.first_contributor_commit_date_epoch = other_report.first_contributor_commit_date_epoch;
+        }
+        if other_report.date_head > self.date_head {
+            self.date_head = other_report.date_head;
+        }
+        if other_report.date_init.is_some() && (self.date_init.is_none() || other_report.date_init < self.date_init) {
+            self.date_init = other_report.date_init;
+        }
+
+        self.history_truncated |= other_report.history_truncated;
+    }
+
+    /// Adds `per_file_tech` records to the report, keeping the record with the most recent commit date if the report
+    /// already has one for the same file. It does not affect `tech` records.
+    fn merge_newer_per_file_tech(&mut self, per_file_tech: HashSet<Tech>) {
+        'outer: for tech in per_file_tech {
             // check if tech should be added to the report at all or is it older than what we already have
             for existing_tech in &self.per_file_tech {
                 if *existing_tech == tech && existing_tech.commit_date_epoch > tech.commit_date_epoch {
@@ -468,31 +865,74 @@ impl Report {
             // insert the new one
             self.per_file_tech.insert(tech);
         }
+    }
 
-        self.git_ids_included.insert(contributor_git_id.clone());
-
-        // merge project metadata - the latest of the two contributor reports gets its data copied over
-        // to the combined report
-        if let Some(commit_date_iso) = other_report.last_contributor_commit_date_iso {
-            if commit_date_iso > self.last_contributor_commit_date_iso.clone().unwrap_or_default() {
-                debug!(
-                    "Report for {} is newer: {} vs {:?}",
-                    contributor_git_id, commit_date_iso, self.last_contributor_commit_date_iso
-                );
-                self.last_contributor_commit_date_iso = Some(commit_date_iso);
-                self.last_contributor_commit_date_epoch = other_report.last_contributor_commit_date_epoch;
-                self.last_contributor_commit_sha1 = other_report.last_contributor_commit_sha1;
-                self.report_commit_sha1 = other_report.report_commit_sha1;
+    /// Moves `per_file_tech` records for files that are not in `head_files` into `per_file_tech_history`
+    /// to keep the report file small. Does not affect `tech` records.
+    pub fn compact_per_file_tech(&mut self, head_files: &HashSet<String>) {
+        let per_file_tech = self.per_file_tech.drain().collect::<Vec<Tech>>();
+        for mut tech in per_file_tech {
+            if head_files.contains(tech.file_name.as_deref().unwrap_or_default()) {
+                tech.absent_runs = 0;
+                self.per_file_tech.insert(tech);
+            } else {
+                self.per_file_tech_history.replace(tech);
             }
         }
+        debug!(
+            "Compacted per_file_tech: {} in HEAD, {} in history",
+            self.per_file_tech.len(),
+            self.per_file_tech_history.len()
+        );
+    }
+
+    /// Moves all `per_file_tech_history` records back into `per_file_tech`. There is no overlap between the two
+    /// because the history only has files that were not in HEAD at the time of compaction.
+    pub fn restore_per_file_tech_history(&mut self) {
+        let history = self.per_file_tech_history.drain().collect::<Vec<Tech>>();
+        self.per_file_tech.extend(history);
     }
 
     /// Deletes existing `tech` records and re-creates them from scratch using `per_file_tech` records.
+    /// Records for deleted files in `per_file_tech_history` are included because they are still part of the contribution.
+    /// Extra copies of duplicate files from `duplicate_file_groups` are left out, counting only the first copy
+    /// present in this report.
     pub fn recompute_tech_section(&mut self) {
         debug!("Recomputing tech section");
         self.tech.clear();
 
-        for tech in self.per_file_tech.clone() {
+        // a file re-added after it was deleted may also have an older record in the history
+        let current_files = self
+            .per_file_tech
+            .iter()
+            .filter_map(|tech| tech.file_name.as_deref())
+            .collect::<HashSet<&str>>();
+        let per_file_tech = self
+            .per_file_tech
+            .iter()
+            .chain(
+                self.per_file_tech_history
+                    .iter()
+                    .filter(|tech| !current_files.contains(tech.file_name.as_deref().unwrap_or_default())),
+            )
+            .cloned()
+            .collect::<Vec<Tech>>();
+        let file_names = per_file_tech
+            .iter()
+            .filter_map(|tech| tech.file_name.as_deref())
+            .collect::<HashSet<&str>>();
+        let extra_copies = DuplicateFiles::extra_copies(&self.duplicate_file_groups, &file_names)
+            .into_iter()
+            .map(|v| v.to_owned())
+            .collect::<HashSet<String>>();
+        for tech in per_file_tech {
+            if tech
+                .file_name
+                .as_ref()
+                .is_some_and(|file_name| extra_copies.contains(file_name))
+            {
+                continue;
+            }
             self.merge_tech_record(tech);
         }
     }
@@ -509,13 +949,18 @@ impl Report {
         debug!("Resetting combined contributor report for {}", contributor_git_id);
         self.report_id = uuid::Uuid::new_v4().to_string();
         self.timestamp = Utc::now().to_rfc3339();
+        self.format_version = Some(Report::REPORT_FORMAT_VERSION.to_owned());
         self.report_s3_name = String::new();
         self.is_single_commit = false;
         self.log_hash = None;
         self.last_commit_author = None;
+        self.pruned_file_names.clear();
         self.git_ids_included.insert(contributor_git_id);
         self.date_head = project_report.date_head.clone();
         self.date_init = project_report.date_init.clone();
+        self.history_truncated = project_report.history_truncated;
+        self.git_ref = project_report.git_ref.clone();
+        self.duplicate_file_groups = project_report.duplicate_file_groups.clone();
 
         // the latest contributor commit is the first one in the list of commits
         if let Some(latest_log_entry) = list_of_commits.iter().next() {
@@ -545,10 +990,17 @@ impl Report {
         self.recent_project_commits = Some(
             list_of_commits
                 .iter()
-                .take(list_of_commits.len().min(500))
+                .take(Self::MAX_RECENT_PROJECT_COMMITS)
                 .filter_map(|log_entry| log_entry.join_commit_with_ts())
                 .collect(),
         );
+
+        self.commit_cadence =
+            CommitCadence::from_timestamps(list_of_commits.iter().map(|log_entry| log_entry.date_epoch), Utc::now());
+        self.tenure = Tenure::from_timestamps(list_of_commits.iter().map(|log_entry| log_entry.date_epoch));
+        // all commits of all identities, not just the recent ones in the list
+        self.commit_time_histo =
+            CommitTimeHisto::from_timestamps(list_of_commits.iter().map(|log_entry| log_entry.date_epoch), Utc::now());
     }
 
     /// Removes some sections that make no sense in the combined report.
@@ -561,7 +1013,7 @@ impl Report {
                 Some(v) => v.len() as u64,
                 None => 0,
             },
-            per_file_tech: self.per_file_tech.len() as u64,
+            per_file_tech: self.per_file_tech.len() as u64 + self.per_file_tech_history.len() as u64,
             file_types: self.file_types.len() as u64,
             reports_included: self.reports_included.len() as u64,
             projects_included: self.projects_included.len() as u64,
@@ -583,22 +1035,41 @@ impl Report {
 
         self.contributors = None;
         self.tree_files = None;
+        self.co_change = None;
+        self.collaboration = None;
+        self.hotspots = None;
+        self.duplicate_files = None;
+        self.duplicate_file_groups.clear();
+        self.import_graph = None;
+        self.problem_files = None;
         self.report_commit_sha1 = None;
+        self.git_ref = None;
         self.last_commit_author = None;
         self.log_hash = None;
+        self.project_config_hash = None;
         self.commit_count_project = None;
         self.commit_count_contributor = None;
         self.contributor_count = None;
         self.loc_project = None;
         self.libs_project = None;
+        self.documentation = None;
+        self.assets = None;
+        self.licenses = None;
+        self.migrations = None;
+        self.ops_tech = None;
+        self.tech_debt_markers = None;
         self.unprocessed_file_names.clear();
         self.per_file_tech.clear();
+        self.per_file_tech_history.clear();
 
         self.github_repo_name = None;
         self.github_user_name = None;
+        self.remote_org = None;
+        self.remote_url_hash = None;
         self.report_id = String::new();
         self.report_s3_name = String::new();
         self.timestamp = Utc::now().to_rfc3339();
+        self.format_version = Some(Report::REPORT_FORMAT_VERSION.to_owned());
 
         self.recent_project_commits = None;
 
@@ -631,7 +1102,7 @@ impl Report {
 
         // recalculate commit histograms from absolute number of commits to percentage
         if let Some(histo) = self.commit_time_histo.as_mut() {
-            histo.recalculate_counts_to_percentage();
+            histo.recalculate_counts_to_percentage(&WorkdayOverlap::default());
         }
 
         // extract all keyword into a single container
@@ -654,6 +1125,7 @@ impl Report {
 
         // this can be huge and is not really needed for search
         report.per_file_tech.clear();
+        report.per_file_tech_history.clear();
 
         // the list of contributors is useful, but indexing every file in the db isn't needed
         if let Some(contributors) = report.contributors.as_mut() {
@@ -682,30 +1154,48 @@ impl Report {
         Report {
             tech: HashSet::new(),
             per_file_tech: HashSet::new(),
+            per_file_tech_history: HashSet::new(),
+            pruned_file_names: HashSet::new(),
+            duplicate_file_groups: Vec::new(),
             timestamp: Utc::now().to_rfc3339(),
+            format_version: Some(Report::REPORT_FORMAT_VERSION.to_owned()),
             unprocessed_file_names: HashSet::new(),
             file_types: HashSet::new(),
             github_user_name: None,
             github_repo_name: None,
+            remote_org: None,
+            remote_url_hash: None,
             report_s3_name: String::new(),
             report_id: uuid::Uuid::new_v4().to_string(),
             reports_included: HashSet::new(),
             projects_included: Vec::new(),
             git_ids_included: HashSet::new(),
+            failed_contributors: Vec::new(),
             contributor_git_ids: None,
             contributors: None,
             date_head: None,
             date_init: None,
             tree_files: None,
             report_commit_sha1: None,
+            git_ref: None,
             is_single_commit: false,
+            history_truncated: false,
+            partial: false,
+            empty_repo: false,
+            coverage: None,
             log_hash: None,
+            project_config_hash: None,
             last_commit_author: None,
             recent_project_commits: None,
             last_contributor_commit_date_iso: None,
             last_contributor_commit_date_epoch: None,
             last_contributor_commit_sha1: None,
             primary_email: None,
+            public_name: None,
+            headline: None,
+            public_contact: None,
+            available: None,
+            location: None,
             first_contributor_commit_sha1: None,
             first_contributor_commit_date_iso: None,
             first_contributor_commit_date_epoch: None,
@@ -718,44 +1208,122 @@ impl Report {
             commit_count_project: None,
             commit_count_contributor: None,
             commit_time_histo: None,
+            work_pattern: None,
+            commit_cadence: None,
+            tenure: None,
+            work_distribution: None,
+            co_change: None,
+            collaboration: None,
+            hotspots: None,
+            duplicate_files: None,
+            import_graph: None,
+            problem_files: None,
+            documentation: None,
+            assets: None,
+            licenses: None,
+            migrations: None,
+            ops_tech: None,
+            tech_debt_markers: None,
             keywords: None,
             list_counts: None,
+            trends: None,
+            truncation: None,
         }
     }
 
     /// Load a report from the local storage, if one exists. Returns None and logs errors on failure.
-    pub fn from_disk(path: &PathBuf) -> Option<Self> {
+    pub fn from_disk(path: &Path) -> Option<Self> {
         // check if the file exists at all
-        let existing_report_file = Path::new(path);
-        if !existing_report_file.exists() {
+        if !path.exists() {
             info!("No report found at {}. The repo will be processed in full.", path.to_string_lossy());
 
             return None;
         }
 
         // try to load the file and read its contents
-        let mut existing_report_file = match File::open(path) {
+        let existing_report_file = match File::open(path) {
             Err(e) => {
                 error!("Cannot read report at {} due to {}.", path.to_string_lossy(), e);
                 return None;
             }
             Ok(v) => v,
         };
-        let mut report_contents = String::new();
-        if let Err(e) = existing_report_file.read_to_string(&mut report_contents) {
-            error!("Failed to read report contents from {} due to {}", path.to_string_lossy(), e);
-            return None;
-        };
 
-        // convert to a struct and return
-        match serde_json::from_str::<Report>(&report_contents) {
+        let mut report = match Self::from_reader(BufReader::new(existing_report_file)) {
             Err(e) => {
-                error!("Failed to deser report contents from {} due to {}", path.to_string_lossy(), e);
+                error!("Failed to load report from {} due to {}", path.to_string_lossy(), e);
                 return None;
             }
+            Ok(v) => v,
+        };
+        info!("Loaded a report from {}", path.to_string_lossy());
+        report.per_file_tech_history = Self::history_from_disk(path);
+        Some(report)
+    }
+
+    /// Reads a report in JSON from any source, e.g. a network stream or a decompressor, and upgrades it from an older
+    /// format, if needed. `per_file_tech_history` is left empty because it is stored separately.
+    pub fn from_reader<R: Read>(mut reader: R) -> Result<Self, ReportError> {
+        let mut report_contents = Vec::new();
+        reader.read_to_end(&mut report_contents).map_err(ReportError::Io)?;
+        Self::from_slice(&report_contents)
+    }
+
+    /// Same as `from_reader` for a report already in memory.
+    pub fn from_slice(report_contents: &[u8]) -> Result<Self, ReportError> {
+        let report_contents =
+            serde_json::from_slice::<serde_json::Value>(report_contents).map_err(ReportError::InvalidJson)?;
+        Self::from_upgraded_value(report_contents, "a byte slice").map_err(ReportError::InvalidReport)
+    }
+
+    /// Upgrades the report from an older format, if needed, and deserializes it. `source` is only used for logging.
+    /// `per_file_tech_history` is left empty because it is stored separately.
+    pub fn from_json_value(report_contents: serde_json::Value, source: &str) -> Option<Self> {
+        match Self::from_upgraded_value(report_contents, source) {
+            Err(e) => {
+                error!("Failed to deser report contents from {} due to {}", source, e);
+                None
+            }
+            Ok(v) => Some(v),
+        }
+    }
+
+    /// Upgrades the report in place and deserializes it. `source` is only used for logging.
+    fn from_upgraded_value(mut report_contents: serde_json::Value, source: &str) -> Result<Self, serde_json::Error> {
+        // older formats are upgraded as JSON because they may not deserialize into the current struct
+        if !migration::upgrade(&mut report_contents) {
+            info!("Report from {} is in an outdated format", source);
+        }
+
+        serde_json::from_value::<Report>(report_contents)
+    }
+
+    /// Returns the name of the file with `per_file_tech_history` for the report, e.g. `contributor_abc.history.json`.
+    fn history_file_name(report_file_name: &Path) -> PathBuf {
+        report_file_name.with_extension("history.json")
+    }
+
+    /// Loads `per_file_tech_history` saved next to the report, if any. A missing or invalid file means there is no history
+    /// and the records will have to be reprocessed.
+    fn history_from_disk(report_file_name: &Path) -> HashSet<Tech> {
+        let history_file_name = Self::history_file_name(report_file_name);
+        let history_contents = match std::fs::read(&history_file_name) {
+            Ok(v) => v,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return HashSet::new(),
+            Err(e) => {
+                error!("Cannot read report history at {} due to {}", history_file_name.to_string_lossy(), e);
+                return HashSet::new();
+            }
+        };
+
+        match serde_json::from_slice::<HashSet<Tech>>(&history_contents) {
             Ok(v) => {
-                info!("Loaded a report from {}", path.to_string_lossy());
-                return Some(v);
+                debug!("Loaded {} per_file_tech history records", v.len());
+                v
+            }
+            Err(e) => {
+                error!("Failed to deser report history from {} due to {}", history_file_name.to_string_lossy(), e);
+                HashSet::new()
             }
         }
     }
@@ -825,12 +1393,126 @@ impl Report {
         };
 
         // save into a file
-        if let Err(e) = std::fs::write(file_name, payload.clone()) {
+        if let Err(e) = utils::write_atomically(file_name, &payload) {
             error!("Cannot save a report in {} due to {}", absolute_file_name.to_string_lossy(), e);
             std::process::exit(1);
         };
 
         info!("Report saved into {}", absolute_file_name.to_string_lossy());
+
+        self.save_history(&absolute_file_name);
+    }
+
+    /// Removes a report saved with `save_as_local_file()` and its history file, e.g. a stale copy of a report kept elsewhere.
+    /// Missing files are ignored.
+    #[cfg(feature = "sqlite-store")]
+    pub(crate) fn remove_local_file(file_name: &Path) {
+        for file_name in [file_name.to_path_buf(), Self::history_file_name(file_name)] {
+            match std::fs::remove_file(&file_name) {
+                Ok(_) => debug!("Removed {}", file_name.to_string_lossy()),
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+                Err(e) => error!("Cannot remove {} due to {}", file_name.to_string_lossy(), e),
+            }
+        }
+    }
+
+    /// Saves `per_file_tech_history` next to the report or removes the stale history file if there are no records.
+    /// Failures are logged and only cost reprocessing of the files next time.
+    fn save_history(&self, report_file_name: &Path) {
+        let history_file_name = Self::history_file_name(report_file_name);
+
+        if self.per_file_tech_history.is_empty() {
+            if let Err(e) = std::fs::remove_file(&history_file_name) {
+                if e.kind() != std::io::ErrorKind::NotFound {
+                    error!("Cannot remove report history {} due to {}", history_file_name.to_string_lossy(), e);
+                }
+            }
+            return;
+        }
+
+        match serde_json::to_vec(&self.per_file_tech_history) {
+            Ok(v) => match utils::write_atomically(&history_file_name, &v) {
+                Ok(_) => info!("Report history saved into {}", history_file_name.to_string_lossy()),
+                Err(e) => error!("Cannot save report history in {} due to {}", history_file_name.to_string_lossy(), e),
+            },
+            Err(e) => error!("Cannot serialize report history due to {}. It's a bug.", e),
+        }
+    }
+
+    /// Adds co-change coupling between files and folders calculated from the full log within `limits`.
+    pub fn add_co_change(self, git_log: &[GitLogEntry], limits: &CoChangeLimits) -> Self {
+        let mut report = self;
+        report.co_change = CoChange::from_log(git_log, limits);
+        report
+    }
+
+    /// Adds the weekend and after-hours shares of all commits in the log for the `workday` hours.
+    pub fn add_work_pattern(self, git_log: &[GitLogEntry], workday: &WorkdayOverlap) -> Self {
+        let mut report = self;
+        report.work_pattern = WorkPattern::from_log(git_log, workday);
+        report
+    }
+
+    /// Adds pairs of contributors who changed the same files within a time window from the full log.
+    pub fn add_collaboration(self, git_log: &[GitLogEntry]) -> Self {
+        let mut report = self;
+        report.collaboration = Collaboration::from_log(git_log);
+        report
+    }
+
+    /// Adds hotspots ranked by the number of commits in the full log times LoC of the munched files at HEAD.
+    pub fn add_hotspots(self, git_log: &[GitLogEntry]) -> Self {
+        let mut report = self;
+        report.hotspots = Hotspots::from_log(git_log, &report.per_file_tech);
+        report
+    }
+
+    /// Adds the fan-in and fan-out of the most central files from the local imports of the munched files at HEAD.
+    pub fn add_import_graph(self) -> Self {
+        let mut report = self;
+        report.import_graph = ImportGraph::from_per_file_tech(&report.per_file_tech);
+        report
+    }
+
+    /// Adds schema migrations from the munched files, including files deleted since, and the number of migrations
+    /// changed by each contributor if the report has the list of contributors.
+    pub fn add_schema_migrations(self) -> Self {
+        let mut report = self;
+        // the current records go first to be counted instead of the history records of the same files
+        let all_per_file_tech = report.per_file_tech.iter().chain(report.per_file_tech_history.iter());
+        report.migrations = SchemaMigrations::from_per_file_tech(all_per_file_tech);
+        if let (Some(migrations), Some(contributors)) = (report.migrations.as_mut(), report.contributors.as_ref()) {
+            migrations.add_contributors(contributors);
+        }
+        report
+    }
+
+    /// Adds CI/CD, container and infra-as-code files and their LoC per tool from the munched files, including files
+    /// deleted since.
+    pub fn add_ops_tech(self) -> Self {
+        let mut report = self;
+        let all_per_file_tech = report.per_file_tech.iter().chain(report.per_file_tech_history.iter());
+        report.ops_tech = OpsTech::from_per_file_tech(all_per_file_tech);
+        report
+    }
+
+    /// Adds TODO, FIXME and other markers counted in the files at HEAD and the number of markers in the versions
+    /// of the files committed by each contributor if the report has the list of contributors.
+    pub fn add_tech_debt_markers(self) -> Self {
+        let mut report = self;
+        report.tech_debt_markers = TechDebtMarkers::from_per_file_tech(report.per_file_tech.iter());
+        let contributors = report.contributors.as_ref();
+        if let (Some(tech_debt), Some(contributors)) = (report.tech_debt_markers.as_mut(), contributors) {
+            tech_debt.add_contributors(report.per_file_tech.iter(), contributors);
+        }
+        report
+    }
+
+    /// Adds files from the skip list that are still in the tree at HEAD.
+    pub fn add_problem_files(self, skip_list: &SkipList) -> Self {
+        let mut report = self;
+        report.problem_files = skip_list.problem_files(report.tree_files.as_ref());
+        report
     }
 
     /// Adds details about the commit history to the report: head, init, contributors, collaborators, log hash, and remote URLs.
@@ -873,6 +1555,9 @@ impl Report {
                 .collect::<Vec<String>>(),
         );
 
+        report.commit_cadence =
+            CommitCadence::from_timestamps(git_log.iter().map(|log_entry| log_entry.date_epoch), Utc::now());
+
         // this part consumes git_log because there is a lot of data in it
         // so should appear at the end
         report.contributors = Some(Contributor::from_commit_history(git_log));
@@ -896,6 +1581,51 @@ impl Report {
         report
     }
 
+    /// Corrects the commit history details if the oldest commits are missing, e.g. in a shallow clone. Does nothing
+    /// if `history_truncated` is false.
+    /// * `date_init` is copied from the old report because the oldest commit in the log is not the first one
+    /// * `commit_count_project` is the old count plus the new commits if that is more than the commits in the log
+    /// * files last changed by the `shallow_commits` are removed from the contributors because git lists the entire
+    ///   tree as added by the oldest commit of a shallow clone
+    pub(crate) fn adjust_for_truncated_history(
+        self,
+        old_report: &Option<Report>,
+        shallow_commits: &HashSet<String>,
+    ) -> Self {
+        let mut report = self;
+        if !report.history_truncated {
+            return report;
+        }
+        info!("Truncated history with {} boundary commits", shallow_commits.len());
+
+        report.date_init = old_report.as_ref().and_then(|old_report| old_report.date_init.clone());
+
+        // the old report is only comparable if its HEAD is still in the log, e.g. no history rewrites
+        if let (Some(old_report), Some(commits)) = (old_report, &report.recent_project_commits) {
+            let old_head = old_report
+                .recent_project_commits
+                .as_ref()
+                .and_then(|old_commits| old_commits.first());
+            let new_commits = old_head.and_then(|old_head| commits.iter().position(|commit| commit == old_head));
+            if let Some(new_commits) = new_commits {
+                let commit_count = old_report.commit_count_project.unwrap_or_default() + new_commits as u64;
+                if commit_count > report.commit_count_project.unwrap_or_default() {
+                    report.commit_count_project = Some(commit_count);
+                }
+            }
+        }
+
+        if let Some(contributors) = report.contributors.as_mut() {
+            for contributor in contributors {
+                contributor
+                    .touched_files
+                    .retain(|file| !shallow_commits.contains(&file.commit));
+            }
+        }
+
+        report
+    }
+
     /// Copy the list of collaborators, init and head dates from the old report.
     pub async fn copy_commit_info(self, old_report: &Self) -> Self {
         let mut report = self;
@@ -909,11 +1639,41 @@ impl Report {
     }
 
     /// Adds the entire list of tree files or just the touched files to the report, extracts names of unprocessed files
-    /// and counts their extensions.
-    pub fn update_project_file_lists(self, all_tree_files: HashSet<String>) -> Self {
+    /// and counts their extensions. Adds `coverage` with the numbers of munched, skipped and unrecognized files
+    /// out of `selected_files` or all the files if the analysis was not partial.
+    pub fn update_project_file_lists(
+        self,
+        all_tree_files: HashSet<String>,
+        selected_files: Option<&HashSet<String>>,
+        coverage: Coverage,
+        code_rules: &mut CodeRules,
+    ) -> Self {
         // result collector
         let mut report = self;
 
+        // a file may have several records, e.g. templates with the host language
+        let mut munched_lines: HashMap<&str, u64> = HashMap::new();
+        for tech in &report.per_file_tech {
+            if let Some(file_name) = tech.file_name.as_deref() {
+                *munched_lines.entry(file_name).or_default() += tech.total_lines;
+            }
+        }
+        let mut coverage = coverage;
+        for file_name in &all_tree_files {
+            if selected_files.is_some_and(|v| !v.contains(file_name)) {
+                continue;
+            }
+            match munched_lines.get(file_name.as_str()) {
+                Some(lines) if *lines > 0 => coverage.files_munched += 1,
+                Some(_) => coverage.files_skipped += 1,
+                // files on the skip list have a muncher, but no records
+                None if code_rules.get_muncher(file_name).is_some() => coverage.files_skipped += 1,
+                None => coverage.files_unrecognized += 1,
+            }
+        }
+        debug!("Coverage: {:?}", coverage);
+        report.coverage = Some(coverage);
+
         // subtract processed files from all files to get the list of unprocessed files
         let processed_files = report
             .per_file_tech
@@ -944,7 +1704,8 @@ impl Report {
 
     /// Removes or replaces any sensitive info from the report for submission to stackmuncher.com.
     /// Requires a `salt` for name hashing. It has to be unique to the user, consistent across submissions, but is only known to the user
-    pub fn sanitize(&self, salt: String) -> Result<Self, ()> {
+    /// Per-file records and lib names matching `scrub_rules` are dropped or hashed on top of the standard clean up.
+    pub fn sanitize(&self, salt: String, scrub_rules: &ScrubRules) -> Result<Self, ()> {
         // this function should be replaced with a macro
         // see https://github.com/stackmuncher/stm_app/issues/12
 
@@ -952,9 +1713,15 @@ impl Report {
         // expensive, but probably unavoidable given that the original report will still be used at the point of call
         let mut report = self.clone();
 
-        // clean up per_file_tech section
+        // clean up per_file_tech section, including records for files no longer in HEAD
+        report.restore_per_file_tech_history();
         let per_file_tech = report.per_file_tech.drain().collect::<Vec<Tech>>();
         for mut x in per_file_tech {
+            // the user asked for some files to be excluded from submission altogether
+            if scrub_rules.is_path_scrubbed(x.file_name.as_deref().unwrap_or_default()) {
+                debug!("Scrubbed file: {:?}", x.file_name);
+                continue;
+            }
             // use a signed public key as the salt to make the file name hash consistent across submissions by the same user
             // making it very hard to match them across different users
             // it would be computationally prohibitive to try and find a match,
@@ -965,9 +1732,46 @@ impl Report {
             x.pkgs_kw = None;
             x.refs.clear();
             x.refs_kw = None;
+            x.local_deps.clear();
             report.per_file_tech.insert(x);
         }
 
+        // hash library names the user considers private, e.g. from an internal registry
+        if !scrub_rules.pkgs.is_empty() {
+            let tech = report.tech.drain().collect::<Vec<Tech>>();
+            for mut x in tech {
+                x.pkgs = scrub_rules.scrub_pkg_names(x.pkgs, &salt);
+                x.refs = scrub_rules.scrub_pkg_names(x.refs, &salt);
+                x.pkgs_kw = x.pkgs_kw.map(|v| scrub_rules.scrub_pkg_names(v, &salt));
+                x.refs_kw = x.refs_kw.map(|v| scrub_rules.scrub_pkg_names(v, &salt));
+                report.tech.insert(x);
+            }
+        }
+
+        // the org name and the remote hash are only needed for local rollups
+        report.remote_org = None;
+        report.remote_url_hash = None;
+
+        // the trends are for the user to see locally
+        report.trends = None;
+
+        // branch names may be telling of private work
+        report.git_ref = None;
+
+        // the totals already exclude the extra copies, the file names are not needed
+        report.duplicate_file_groups.clear();
+
+        // the failure reasons are for the user to act on and may include local paths
+        report.failed_contributors.clear();
+
+        // git IDs of other contributors
+        if let Some(migrations) = report.migrations.as_mut() {
+            migrations.contributors.clear();
+        }
+        if let Some(tech_debt) = report.tech_debt_markers.as_mut() {
+            tech_debt.contributors.clear();
+        }
+
         // this may be an email address of someone else
         report.last_commit_author = None;
         // someone's else commit hash can be used for matching across devs
@@ -998,19 +1802,84 @@ impl Report {
             }
         }
 
+        // huge profiles can exceed the inbox payload limit
+        report.truncate_for_submission(
+            Self::MAX_SUBMITTED_KEYWORDS_PER_TECH,
+            Self::MAX_RECENT_PROJECT_COMMITS,
+            Self::MAX_SUBMITTED_FILES,
+        );
+
         Ok(report)
     }
 
-    /// GZips itself
-    pub fn gzip(&self) -> Result<Vec<u8>, ()> {
-        // serialize the report into bytes
-        let report = match serde_json::to_vec(&self) {
+    /// Trims keywords, refs and pkgs per tech to `max_keywords` with the highest counts, `recent_project_commits`
+    /// to `max_commits` most recent ones and `per_file_tech` to `max_files` records with the most LoC.
+    /// What was trimmed is recorded in `truncation` so the server can request more if needed.
+    fn truncate_for_submission(&mut self, max_keywords: usize, max_commits: usize, max_files: usize) {
+        let mut truncation = Truncation::default();
+
+        let tech = self.tech.drain().collect::<Vec<Tech>>();
+        for mut x in tech {
+            if let Some(tech_truncation) = TechTruncation::trim(&mut x, max_keywords) {
+                truncation.tech.push(tech_truncation);
+            }
+            self.tech.insert(x);
+        }
+        truncation.tech.sort_unstable_by(|a, b| {
+            a.language
+                .cmp(&b.language)
+                .then_with(|| a.muncher_name.cmp(&b.muncher_name))
+        });
+
+        // the commits are listed latest first
+        if let Some(recent_project_commits) = self.recent_project_commits.as_mut() {
+            if recent_project_commits.len() > max_commits {
+                truncation.recent_project_commits = (recent_project_commits.len() - max_commits) as u64;
+                recent_project_commits.truncate(max_commits);
+            }
+        }
+
+        if self.per_file_tech.len() > max_files {
+            let mut per_file_tech = self.per_file_tech.drain().collect::<Vec<Tech>>();
+            // ties are broken by the file name to submit the same records every time
+            per_file_tech.sort_unstable_by(|a, b| {
+                b.code_lines
+                    .cmp(&a.code_lines)
+                    .then_with(|| a.file_name.cmp(&b.file_name))
+            });
+            truncation.per_file_tech = (per_file_tech.len() - max_files) as u64;
+            per_file_tech.truncate(max_files);
+            self.per_file_tech.extend(per_file_tech);
+        }
+
+        if truncation.is_empty() {
+            self.truncation = None;
+        } else {
+            info!(
+                "Trimmed for submission: {} tech records, {} commits, {} files",
+                truncation.tech.len(),
+                truncation.recent_project_commits,
+                truncation.per_file_tech
+            );
+            self.truncation = Some(truncation);
+        }
+    }
+
+    /// Serializes itself into the exact JSON bytes that get compressed and submitted by `gzip()`.
+    pub fn to_payload(&self) -> Result<Vec<u8>, ()> {
+        match serde_json::to_vec(&self) {
             Err(e) => {
                 error!("Cannot serialize a report after pre-sub cleanup due to {}", e);
-                return Err(());
+                Err(())
             }
-            Ok(v) => v,
-        };
+            Ok(v) => Ok(v),
+        }
+    }
+
+    /// GZips itself
+    pub fn gzip(&self) -> Result<Vec<u8>, ()> {
+        // serialize the report into bytes
+        let report = self.to_payload()?;
 
         // gzip it
         let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
@@ -1070,9 +1939,24 @@ impl Report {
             .timestamp()
     }
 
-    /// Returns TRUE if the report is in an older format than the current version.
+    /// Parses `self.format_version` from RFC3339 to an EPOCH. Falls back on `self.timestamp` for reports
+    /// that predate that field. Returns 0 if the value is not valid.
+    pub fn parsed_format_version(&self) -> i64 {
+        match &self.format_version {
+            Some(v) => match DateTime::parse_from_rfc3339(v) {
+                Ok(ts) => ts.with_timezone(&Utc).timestamp(),
+                Err(_) => {
+                    warn!("Invalid cached report format version: {}", v);
+                    0
+                }
+            },
+            None => self.parsed_timestamp(),
+        }
+    }
+
+    /// Returns TRUE if the report is in an older format than the current version and could not be upgraded on loading.
     pub fn is_outdated_format(&self) -> bool {
-        self.parsed_timestamp() < Report::report_format_version()
+        self.parsed_format_version() < Report::report_format_version()
     }
 
     /// Updated `keywords` member from all `refs` and `pkgs`. Splits words at separators like _-/@
@@ -1187,18 +2071,15 @@ impl Report {
 
         // update duration for every tech
         for (_, tech) in tech_history_map.iter_mut() {
-            // calculated as 31_536_000 / 12
-            // this i64 -> u64 conversion should be safe and is needed if somehow the dates are reversed and the result is negative
-            // it will simply produce a zero
-            tech.months = (tech.to_date_epoch - tech.from_date_epoch).max(0) as u64 / 2_628_000;
+            tech.update_months();
         }
 
-        // place the tech histories into the top level tech records of the report
+        // widen the ranges of the last commits of the files in the top level tech records of the report
         self.tech = self
             .tech
             .drain()
             .map(|mut v| {
-                v.history = tech_history_map.remove(&v.language);
+                v.history = TechHistory::merge(v.history.take(), tech_history_map.remove(&v.language));
                 v
             })
             .collect::<HashSet<Tech>>();
@@ -1221,7 +2102,9 @@ impl std::fmt::Display for Report {
 
 #[cfg(test)]
 mod test_report {
-    use super::Report;
+    use super::{MergeLimits, Report, ReportError, ScrubRules, Tech};
+    use crate::git::GitLogEntry;
+    use std::collections::HashSet;
     use std::fs::File;
     use std::io::prelude::*;
 
@@ -1340,4 +2223,255 @@ mod test_report {
         println!("Pkgs counts, merged: {}, expected {}", cs_pkgs_rm, cs_pkgs);
         assert_eq!(cs_pkgs_rm, cs_pkgs, "C# pkgs count");
     }
+
+    #[test]
+    fn test_merge_with_limits() {
+        let report = |keywords: &[(&str, u64)]| {
+            let mut report = Report::new();
+            report
+                .tech
+                .insert(Tech::test("Rust", "rust").with_loc(10).with_keywords(keywords));
+            report
+        };
+        let rust_keywords = |report: &Report| {
+            let rust = report.tech.iter().next().unwrap();
+            let mut keywords = rust
+                .keywords
+                .iter()
+                .map(|v| (v.k.clone(), v.c))
+                .collect::<Vec<(String, u64)>>();
+            keywords.sort();
+            (keywords, rust.overflow_distinct, rust.overflow_total)
+        };
+        let limits = MergeLimits {
+            max_keywords_per_tech: 2,
+        };
+
+        // the lowest counts are dropped from the first report
+        let merged =
+            Report::merge_with_limits(None, report(&[("fn", 5), ("impl", 3), ("unsafe", 1)]), &limits).unwrap();
+        assert_eq!(rust_keywords(&merged), (vec![("fn".to_owned(), 5), ("impl".to_owned(), 3)], 1, 1));
+
+        // and again after the merged lists went over the limit
+        let merged = Report::merge_with_limits(Some(merged), report(&[("match", 4), ("unsafe", 2)]), &limits).unwrap();
+        assert_eq!(rust_keywords(&merged), (vec![("fn".to_owned(), 5), ("match".to_owned(), 4)], 3, 6));
+
+        // the default limits keep all of them
+        let merged = Report::merge(None, report(&[("fn", 5), ("impl", 3), ("unsafe", 1)])).unwrap();
+        assert_eq!(rust_keywords(&merged).0.len(), 3);
+    }
+
+    #[test]
+    fn test_per_file_tech_history() {
+        let file_tech = |file_name: &str| -> Tech {
+            Tech {
+                commit_sha1: Some("abc".to_owned()),
+                ..Tech::test("Rust", "rust").with_file(file_name).with_loc(10)
+            }
+        };
+
+        let mut report = Report::new();
+        report.per_file_tech.insert(file_tech("src/main.rs"));
+        report.per_file_tech.insert(file_tech("src/deleted.rs"));
+
+        let head_files = vec!["src/main.rs".to_owned()].into_iter().collect();
+        report.compact_per_file_tech(&head_files);
+        assert_eq!(report.per_file_tech.len(), 1);
+        assert_eq!(report.per_file_tech_history.len(), 1);
+
+        // the history is saved next to the report and loaded back with it
+        let report_dir = std::env::temp_dir().join(format!("stm_report_history_test_{}", std::process::id()));
+        std::fs::create_dir_all(&report_dir).unwrap();
+        let report_file_name = report_dir.join("contributor_abc.json");
+        report.save_as_local_file(&report_file_name, false);
+        assert!(report_dir.join("contributor_abc.history.json").exists());
+        let report_contents = std::fs::read_to_string(&report_file_name).unwrap();
+        assert!(!report_contents.contains("deleted.rs"));
+
+        let mut loaded = Report::from_disk(&report_file_name).unwrap();
+        assert_eq!(loaded.per_file_tech_history.len(), 1);
+        loaded.restore_per_file_tech_history();
+        assert_eq!(loaded.per_file_tech.len(), 2);
+
+        // a report without history removes the stale history file
+        loaded.save_as_local_file(&report_file_name, false);
+        loaded.per_file_tech_history.clear();
+        loaded.per_file_tech.clear();
+        loaded.save_as_local_file(&report_file_name, false);
+        assert!(!report_dir.join("contributor_abc.history.json").exists());
+
+        std::fs::remove_dir_all(&report_dir).unwrap();
+    }
+
+    #[test]
+    fn test_from_slice() {
+        let mut report = Report::new();
+        report.tech.insert(Tech::test("Rust", "rust").with_loc(10));
+        let report_contents = serde_json::to_vec(&report).unwrap();
+
+        assert_eq!(Report::from_slice(&report_contents).unwrap().tech.len(), 1);
+        let loaded = Report::from_reader(report_contents.as_slice()).unwrap();
+        assert_eq!(loaded.tech.len(), 1);
+
+        assert!(matches!(Report::from_slice(br#"{"tech":"#), Err(ReportError::InvalidJson(_))));
+        assert!(matches!(Report::from_slice(br#"{"tech":42}"#), Err(ReportError::InvalidReport(_))));
+
+        let merged = Report::merge_many(vec![loaded.clone(), loaded]).unwrap();
+        assert_eq!(merged.tech.iter().next().unwrap().code_lines, 20);
+        assert!(Report::merge_many(Vec::new()).is_none());
+    }
+
+    #[test]
+    fn test_used_dates() {
+        let file_tech = |file_name: &str, commit_date_epoch: i64| -> Tech {
+            Tech {
+                commit_date_epoch: Some(commit_date_epoch),
+                commit_date_iso: Some(commit_date_epoch.to_string()),
+                ..Tech::test("Rust", "rust").with_file(file_name).with_loc(10)
+            }
+        };
+        let used_dates = |tech: &Tech| {
+            let history = tech.history.as_ref().unwrap();
+            (history.from_date_epoch, history.to_date_epoch, history.months)
+        };
+
+        let mut report = Report::new();
+        for (file_name, commit_date_epoch) in [("a.rs", 1600000000), ("b.rs", 1500000000), ("c.rs", 1700000000)] {
+            report.merge_tech_record(file_tech(file_name, commit_date_epoch));
+        }
+        let rust = report.tech.iter().next().unwrap();
+        assert_eq!(used_dates(rust), (1500000000, 1700000000, 76));
+        assert_eq!(rust.history.as_ref().unwrap().from_date_iso, "1500000000");
+        assert!(rust.commit_date_epoch.is_none());
+
+        // the range is widened by reports from other projects
+        let mut other_report = Report::new();
+        other_report.merge_tech_record(file_tech("d.rs", 1400000000));
+        let merged = Report::merge_many(vec![report, other_report]).unwrap();
+        let rust = merged.tech.iter().next().unwrap();
+        assert_eq!(used_dates(rust), (1400000000, 1700000000, 114));
+    }
+
+    #[test]
+    fn test_sanitize_per_file_tech() {
+        let mut report = Report::new();
+        let tech = Tech::test("Rust", "rust")
+            .with_file("src/secret_project.rs")
+            .with_loc(10);
+        report.per_file_tech.insert(tech);
+        let hashed_file_name = |report: &Report| report.per_file_tech.iter().next().unwrap().file_name.clone();
+
+        // per-file records are kept for file-level continuity, but the names are hashed with the user's salt
+        let sanitized = report.sanitize("salt1".to_owned(), &ScrubRules::default()).unwrap();
+        assert_eq!(sanitized.per_file_tech.len(), 1);
+        assert_eq!(sanitized.per_file_tech.iter().next().unwrap().code_lines, 10);
+        assert!(!serde_json::to_string(&sanitized).unwrap().contains("secret_project"));
+
+        let again = report.sanitize("salt1".to_owned(), &ScrubRules::default()).unwrap();
+        assert_eq!(hashed_file_name(&sanitized), hashed_file_name(&again));
+        let other_user = report.sanitize("salt2".to_owned(), &ScrubRules::default()).unwrap();
+        assert_ne!(hashed_file_name(&sanitized), hashed_file_name(&other_user));
+    }
+
+    #[test]
+    fn test_truncate_for_submission() {
+        let tech = |file_name: &str, code_lines: u64| -> Tech {
+            Tech::test("Rust", "rust").with_file(file_name).with_loc(code_lines)
+        };
+
+        let mut report = Report::new();
+        let mut rust = Tech::test("Rust", "rust").with_loc(30);
+        rust.keywords = serde_json::from_str(r#"[{"k":"fn","c":5},{"k":"impl","c":3},{"k":"unsafe","c":1}]"#).unwrap();
+        report.tech.insert(rust);
+        for (file_name, code_lines) in [("a.rs", 10), ("b.rs", 20), ("c.rs", 5)] {
+            report.per_file_tech.insert(tech(file_name, code_lines));
+        }
+        report.recent_project_commits = Some(vec!["c_3".to_owned(), "b_2".to_owned(), "a_1".to_owned()]);
+
+        // nothing to trim
+        let mut untrimmed = report.clone();
+        untrimmed.truncate_for_submission(3, 3, 3);
+        assert!(untrimmed.truncation.is_none());
+
+        report.truncate_for_submission(2, 1, 2);
+        let truncation = report.truncation.unwrap();
+        assert_eq!(truncation.tech.len(), 1);
+        assert_eq!((truncation.tech[0].keywords, truncation.tech[0].refs), (1, 0));
+        assert_eq!(truncation.recent_project_commits, 2);
+        assert_eq!(truncation.per_file_tech, 1);
+
+        let rust = report.tech.iter().next().unwrap();
+        let keywords = rust.keywords.iter().map(|v| v.k.as_str()).collect::<HashSet<_>>();
+        assert_eq!(keywords, ["fn", "impl"].iter().copied().collect());
+        assert_eq!(report.recent_project_commits.unwrap(), vec!["c_3".to_owned()]);
+        let files = report
+            .per_file_tech
+            .iter()
+            .filter_map(|v| v.file_name.as_deref())
+            .collect::<HashSet<_>>();
+        assert_eq!(files, ["a.rs", "b.rs"].iter().copied().collect());
+    }
+
+    #[tokio::test]
+    async fn test_adjust_for_truncated_history() {
+        let commit = |sha1: &str, date_epoch: i64, author: &str, files: &[&str]| -> GitLogEntry {
+            let mut entry = GitLogEntry::new();
+            entry.sha1 = sha1.repeat(40);
+            entry.date_epoch = date_epoch;
+            entry.date = "2021-01-07T06:10:00+00:00".to_owned();
+            entry.author_name_email = (author.to_owned(), [author, "@example.com"].concat());
+            entry.files = files.iter().map(|file| file.to_string()).collect();
+            entry
+        };
+        // a clone with `--depth 2` where the boundary commit `b` lists the entire tree
+        let git_log = vec![
+            commit("c", 1610000300, "dev1", &["src/main.rs"]),
+            commit("b", 1610000200, "dev2", &["src/main.rs", "src/lib.rs"]),
+        ];
+        let shallow_commits = vec!["b".repeat(40)].into_iter().collect::<HashSet<String>>();
+
+        let mut old_report = Report::new();
+        old_report.date_init = Some("2020-01-01T00:00:00+00:00".to_owned());
+        old_report.commit_count_project = Some(10);
+        old_report.recent_project_commits = Some(vec!["bbbbbbbb_1610000200".to_owned()]);
+        let old_report = Some(old_report);
+
+        let mut report = Report::new();
+        report.history_truncated = true;
+        let report = report
+            .add_commits_history(git_log.clone())
+            .await
+            .adjust_for_truncated_history(&old_report, &shallow_commits);
+        assert_eq!(report.date_init.as_deref(), Some("2020-01-01T00:00:00+00:00"));
+        assert_eq!(report.commit_count_project, Some(11));
+        let contributors = report.contributors.unwrap();
+        let touched_files = |git_id: &str| {
+            contributors
+                .iter()
+                .find(|contributor| contributor.git_id == git_id)
+                .unwrap()
+                .touched_files
+                .len()
+        };
+        assert_eq!(touched_files("dev1@example.com"), 1);
+        assert_eq!(touched_files("dev2@example.com"), 0);
+
+        // the first commit date is unknown without an earlier report
+        let mut report = Report::new();
+        report.history_truncated = true;
+        let report = report
+            .add_commits_history(git_log.clone())
+            .await
+            .adjust_for_truncated_history(&None, &shallow_commits);
+        assert!(report.date_init.is_none());
+        assert_eq!(report.commit_count_project, Some(2));
+
+        // full histories are left as-is
+        let report = Report::new()
+            .add_commits_history(git_log)
+            .await
+            .adjust_for_truncated_history(&old_report, &shallow_commits);
+        assert_eq!(report.date_init.as_deref(), Some("2021-01-07T06:10:00+00:00"));
+        assert_eq!(report.commit_count_project, Some(2));
+    }
 }
@@ -0,0 +1,107 @@
+use super::report::Report;
+use super::tech::Tech;
+use serde::{Deserialize, Serialize};
+use tracing::{debug, info};
+
+/// How long per-file records for files deleted from the tree are kept in the history of cached contributor reports.
+/// Can be changed in `retention` section of config.json.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Default)]
+#[serde(default)]
+pub struct Retention {
+    /// Records for files that were not in the tree at HEAD for more than this many consecutive runs are pruned.
+    /// 0 keeps them forever.
+    pub max_absent_runs: u64,
+}
+
+/// The number of history records removed by `Report::prune_per_file_tech_history` and their size as JSON.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct PrunedHistory {
+    pub records: u64,
+    pub bytes: u64,
+}
+
+impl PrunedHistory {
+    /// Adds up the counts from another pruning.
+    pub fn add(&mut self, other: Self) {
+        self.records += other.records;
+        self.bytes += other.bytes;
+    }
+}
+
+impl Report {
+    /// Counts one more run for every `per_file_tech_history` record and removes the records that were absent
+    /// for more than `retention.max_absent_runs` runs. The names of the removed files go into `pruned_file_names`
+    /// to stop them from being munched again while they are not in the tree. Does nothing if the retention is off.
+    pub fn prune_per_file_tech_history(&mut self, retention: &Retention) -> PrunedHistory {
+        let mut pruned = PrunedHistory::default();
+        if retention.max_absent_runs == 0 {
+            return pruned;
+        }
+
+        let history = self.per_file_tech_history.drain().collect::<Vec<Tech>>();
+        for mut tech in history {
+            tech.absent_runs += 1;
+            if tech.absent_runs <= retention.max_absent_runs {
+                self.per_file_tech_history.insert(tech);
+                continue;
+            }
+
+            pruned.records += 1;
+            pruned.bytes += serde_json::to_vec(&tech).map(|v| v.len() as u64).unwrap_or_default();
+            if let Some(file_name) = tech.file_name {
+                self.pruned_file_names.insert(file_name);
+            }
+        }
+
+        if pruned.records > 0 {
+            info!("Pruned {} history records, {} bytes", pruned.records, pruned.bytes);
+        }
+        debug!(
+            "History records kept: {}, pruned files: {}",
+            self.per_file_tech_history.len(),
+            self.pruned_file_names.len()
+        );
+
+        pruned
+    }
+}
+
+#[cfg(test)]
+mod test_retention {
+    use super::Retention;
+    use crate::report::{Report, Tech};
+
+    #[test]
+    fn test_prune_per_file_tech_history() {
+        let file_tech = |file_name: &str, absent_runs: u64| -> Tech {
+            Tech {
+                commit_sha1: Some("abc".to_owned()),
+                absent_runs,
+                ..Tech::test("Rust", "rust").with_file(file_name).with_loc(10)
+            }
+        };
+
+        let mut report = Report::new();
+        report.per_file_tech_history.insert(file_tech("src/old.rs", 2));
+        report.per_file_tech_history.insert(file_tech("src/recent.rs", 0));
+
+        // the retention is off by default
+        let pruned = report.prune_per_file_tech_history(&Retention::default());
+        assert_eq!(pruned.records, 0);
+        assert_eq!(report.per_file_tech_history.len(), 2);
+
+        let retention = Retention { max_absent_runs: 2 };
+        let pruned = report.prune_per_file_tech_history(&retention);
+        assert_eq!(pruned.records, 1);
+        assert!(pruned.bytes > 0);
+        assert_eq!(report.per_file_tech_history.len(), 1);
+        assert!(report.pruned_file_names.contains("src/old.rs"));
+
+        // the other record goes after 2 more runs
+        report.prune_per_file_tech_history(&retention);
+        assert_eq!(report.per_file_tech_history.len(), 1);
+        report.prune_per_file_tech_history(&retention);
+        assert!(report.per_file_tech_history.is_empty());
+        assert_eq!(report.pruned_file_names.len(), 2);
+    }
+}
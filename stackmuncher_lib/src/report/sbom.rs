@@ -0,0 +1,585 @@
+use super::licenses::json_license;
+use super::report::Report;
+use chrono::Utc;
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::collections::BTreeSet;
+
+/// Lock files in the root of the project with the exact versions of all direct and transitive dependencies.
+pub const LOCK_FILES: [&str; 3] = ["Cargo.lock", "package-lock.json", "composer.lock"];
+
+/// Package ecosystems of the languages with packages in `Tech.pkgs` or `Tech.refs`, as purl types.
+const LANGUAGE_ECOSYSTEMS: [(&str, &str); 7] = [
+    ("Rust", "cargo"),
+    ("C#", "nuget"),
+    ("Ruby", "gem"),
+    ("JavaScript", "npm"),
+    ("TypeScript", "npm"),
+    ("Python", "pypi"),
+    ("Go", "golang"),
+];
+
+/// Keys of the package section of manifests that the muncher picks up as packages, e.g. `version = "0.1.0"`
+/// in `[package]` of Cargo.toml.
+const MANIFEST_KEYS: [(&str, &str); 3] = [("cargo", "version"), ("cargo", "edition"), ("cargo", "rust-version")];
+
+/// Languages with npm package names in `Tech.refs`. Imports of other languages are namespaces or modules
+/// that cannot be mapped to packages.
+const IMPORT_LANGUAGES: [&str; 2] = ["JavaScript", "TypeScript"];
+
+/// Node.js modules that are imported the same way as npm packages.
+const NODE_BUILTINS: [&str; 18] = [
+    "assert",
+    "buffer",
+    "child_process",
+    "crypto",
+    "dns",
+    "events",
+    "fs",
+    "http",
+    "https",
+    "net",
+    "os",
+    "path",
+    "process",
+    "querystring",
+    "readline",
+    "stream",
+    "url",
+    "util",
+];
+
+/// The SPDX value for an unknown license, download location or copyright.
+const NO_ASSERTION: &str = "NOASSERTION";
+
+/// Where the component was found.
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "snake_case")]
+pub enum SbomSource {
+    /// A lock file, e.g. Cargo.lock, with the exact version.
+    LockFile,
+    /// A manifest munched into `Tech.pkgs`, e.g. Cargo.toml, with the version requirement.
+    Manifest,
+    /// Imports in the code from `Tech.refs` with no version.
+    Imports,
+}
+
+/// A dependency of the project.
+#[derive(Serialize, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct SbomComponent {
+    /// The purl type, e.g. `cargo` or `npm`. None for packages of other ecosystems, e.g. Terraform modules.
+    pub ecosystem: Option<String>,
+    pub name: String,
+    /// The exact version for lock files, the version requirement for manifests, e.g. `1.0`, and None for imports.
+    pub version: Option<String>,
+    /// As declared in the lock file, e.g. `MIT`. Only npm and composer lock files have licenses.
+    pub license: Option<String>,
+    pub source: SbomSource,
+}
+
+/// The list of libraries used by the project for exporting into CycloneDX or SPDX. Built from the project report,
+/// so it can be generated from any report with `tech` section without access to the repo.
+#[derive(Serialize, Clone, Debug, Default, PartialEq)]
+pub struct Sbom {
+    pub project_name: String,
+    pub project_license: Option<String>,
+    /// Sorted by ecosystem, name and version.
+    pub components: Vec<SbomComponent>,
+}
+
+impl Sbom {
+    /// Collects the packages from manifests in `pkgs` and, for JavaScript and TypeScript, the imported packages from `refs`
+    /// of the project report. The imports may include packages that are not in the manifests, e.g. workspace packages.
+    pub fn from_report(report: &Report, project_name: &str) -> Self {
+        let mut components = BTreeSet::new();
+
+        for tech in &report.tech {
+            let ecosystem = LANGUAGE_ECOSYSTEMS
+                .iter()
+                .find(|(language, _)| *language == tech.language)
+                .map(|(_, ecosystem)| ecosystem.to_string());
+
+            for pkg in &tech.pkgs {
+                if MANIFEST_KEYS.contains(&(ecosystem.as_deref().unwrap_or_default(), pkg.k.as_str())) {
+                    continue;
+                }
+                let versions = pkg
+                    .t
+                    .iter()
+                    .flatten()
+                    .map(|v| v.trim())
+                    .filter(|v| !v.is_empty())
+                    .collect::<BTreeSet<&str>>();
+                // .csproj files also list the SDK and the target framework, which have no version
+                if versions.is_empty() && ecosystem.as_deref() == Some("nuget") {
+                    continue;
+                }
+                let versions = if versions.is_empty() {
+                    vec![None]
+                } else {
+                    versions.into_iter().map(|v| Some(v.to_owned())).collect()
+                };
+                for version in versions {
+                    components.insert(SbomComponent {
+                        ecosystem: ecosystem.clone(),
+                        name: pkg.k.clone(),
+                        version,
+                        license: None,
+                        source: SbomSource::Manifest,
+                    });
+                }
+            }
+
+            if IMPORT_LANGUAGES.contains(&tech.language.as_str()) {
+                for import in &tech.refs {
+                    // scoped packages have the scope and the name, e.g. `@angular/core`, but older versions of
+                    // the muncher cut them off at `/`, e.g. `@angular`
+                    if import.k.starts_with('.')
+                        || (import.k.starts_with('@') && !import.k.contains('/'))
+                        || NODE_BUILTINS.contains(&import.k.as_str())
+                    {
+                        continue;
+                    }
+                    components.insert(SbomComponent {
+                        ecosystem: ecosystem.clone(),
+                        name: import.k.clone(),
+                        version: None,
+                        license: None,
+                        source: SbomSource::Imports,
+                    });
+                }
+            }
+        }
+
+        Self {
+            project_name: project_name.to_owned(),
+            project_license: report.licenses.as_ref().and_then(|v| v.project_license.clone()),
+            components: components.into_iter().collect(),
+        }
+    }
+
+    /// Adds the packages from one of `LOCK_FILES`. They replace the components of the same ecosystem found in manifests
+    /// under the same name and all components found in imports because the lock file is the complete list.
+    /// Invalid or unsupported files are ignored.
+    pub fn add_lock_file(&mut self, file_name: &str, contents: &[u8]) {
+        let contents = String::from_utf8_lossy(contents);
+        let (ecosystem, locked) = match file_name {
+            "Cargo.lock" => ("cargo", cargo_lock_packages(&contents)),
+            "package-lock.json" => ("npm", package_lock_packages(&contents)),
+            "composer.lock" => ("composer", composer_lock_packages(&contents)),
+            _ => return,
+        };
+        if locked.is_empty() {
+            return;
+        }
+
+        let locked_names = locked
+            .iter()
+            .map(|(name, _, _)| name.as_str())
+            .collect::<BTreeSet<&str>>();
+        let mut components = self
+            .components
+            .drain(..)
+            .filter(|v| {
+                v.ecosystem.as_deref() != Some(ecosystem)
+                    || v.source == SbomSource::LockFile
+                    || (v.source == SbomSource::Manifest && !locked_names.contains(v.name.as_str()))
+            })
+            .collect::<BTreeSet<SbomComponent>>();
+        for (name, version, license) in locked {
+            components.insert(SbomComponent {
+                ecosystem: Some(ecosystem.to_owned()),
+                name,
+                version: Some(version),
+                license,
+                source: SbomSource::LockFile,
+            });
+        }
+
+        self.components = components.into_iter().collect();
+    }
+
+    /// Returns a CycloneDX 1.5 JSON document with a component per library. `tool_version` is the version of the app
+    /// that generated the document.
+    pub fn to_cyclonedx(&self, tool_version: &str) -> Value {
+        let components = self
+            .components
+            .iter()
+            .enumerate()
+            .map(|(idx, component)| {
+                let mut v = json!({
+                    "type": "library",
+                    "bom-ref": format!("component-{}", idx + 1),
+                    "name": component.name,
+                    "properties": [{"name": "stackmuncher:source", "value": component.source}],
+                });
+                if let Some(version) = &component.version {
+                    v["version"] = json!(version);
+                }
+                if let Some(purl) = component.purl() {
+                    v["purl"] = json!(purl);
+                }
+                if let Some(license) = &component.license {
+                    v["licenses"] = json!([cyclonedx_license(license)]);
+                }
+                v
+            })
+            .collect::<Vec<Value>>();
+
+        let mut project = json!({
+            "type": "application",
+            "bom-ref": "project",
+            "name": self.project_name,
+        });
+        if let Some(license) = &self.project_license {
+            project["licenses"] = json!([cyclonedx_license(license)]);
+        }
+
+        json!({
+            "bomFormat": "CycloneDX",
+            "specVersion": "1.5",
+            "serialNumber": format!("urn:uuid:{}", uuid::Uuid::new_v4()),
+            "version": 1,
+            "metadata": {
+                "timestamp": Utc::now().format("%Y-%m-%dT%H:%M:%SZ").to_string(),
+                "tools": {"components": [{"type": "application", "name": "stackmuncher", "version": tool_version}]},
+                "component": project,
+            },
+            "components": components,
+        })
+    }
+
+    /// Returns an SPDX 2.3 JSON document with a package per library, all of them dependencies of the project package.
+    /// `tool_version` is the version of the app that generated the document.
+    pub fn to_spdx(&self, tool_version: &str) -> Value {
+        let mut packages = vec![json!({
+            "SPDXID": "SPDXRef-Project",
+            "name": self.project_name,
+            "downloadLocation": NO_ASSERTION,
+            "filesAnalyzed": false,
+            "licenseConcluded": NO_ASSERTION,
+            "licenseDeclared": spdx_license(self.project_license.as_deref()),
+            "copyrightText": NO_ASSERTION,
+        })];
+        let mut relationships = vec![json!({
+            "spdxElementId": "SPDXRef-DOCUMENT",
+            "relationshipType": "DESCRIBES",
+            "relatedSpdxElement": "SPDXRef-Project",
+        })];
+
+        for (idx, component) in self.components.iter().enumerate() {
+            let spdx_id = format!("SPDXRef-Package-{}", idx + 1);
+            let mut package = json!({
+                "SPDXID": spdx_id,
+                "name": component.name,
+                "downloadLocation": NO_ASSERTION,
+                "filesAnalyzed": false,
+                "licenseConcluded": NO_ASSERTION,
+                "licenseDeclared": spdx_license(component.license.as_deref()),
+                "copyrightText": NO_ASSERTION,
+                "comment": format!("Found in {}", component.source.as_str()),
+            });
+            if let Some(version) = &component.version {
+                package["versionInfo"] = json!(version);
+            }
+            if let Some(purl) = component.purl() {
+                package["externalRefs"] = json!([{
+                    "referenceCategory": "PACKAGE-MANAGER",
+                    "referenceType": "purl",
+                    "referenceLocator": purl,
+                }]);
+            }
+            packages.push(package);
+            relationships.push(json!({
+                "spdxElementId": "SPDXRef-Project",
+                "relationshipType": "DEPENDS_ON",
+                "relatedSpdxElement": spdx_id,
+            }));
+        }
+
+        let namespace_name = self
+            .project_name
+            .chars()
+            .map(|c| {
+                if c.is_ascii_alphanumeric() || c == '-' || c == '.' {
+                    c
+                } else {
+                    '_'
+                }
+            })
+            .collect::<String>();
+
+        json!({
+            "spdxVersion": "SPDX-2.3",
+            "dataLicense": "CC0-1.0",
+            "SPDXID": "SPDXRef-DOCUMENT",
+            "name": self.project_name,
+            "documentNamespace": format!("https://stackmuncher.com/spdx/{}-{}", namespace_name, uuid::Uuid::new_v4()),
+            "creationInfo": {
+                "created": Utc::now().format("%Y-%m-%dT%H:%M:%SZ").to_string(),
+                "creators": [format!("Tool: stackmuncher-{}", tool_version)],
+            },
+            "packages": packages,
+            "relationships": relationships,
+        })
+    }
+}
+
+impl SbomSource {
+    /// Returns the name as in the JSON output, e.g. `lock_file`.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::LockFile => "lock_file",
+            Self::Manifest => "manifest",
+            Self::Imports => "imports",
+        }
+    }
+}
+
+impl SbomComponent {
+    /// Returns the package URL, e.g. `pkg:cargo/serde@1.0.130`, or None if the ecosystem is unknown.
+    /// Only the exact versions from lock files are included. Version requirements from manifests are not versions.
+    pub fn purl(&self) -> Option<String> {
+        let ecosystem = self.ecosystem.as_ref()?;
+        // the namespace of scoped npm packages starts with `@`, which has to be encoded
+        let name = match self.name.strip_prefix('@') {
+            Some(name) => ["%40", name].concat(),
+            None => self.name.clone(),
+        };
+
+        match (&self.version, self.source) {
+            (Some(version), SbomSource::LockFile) => Some(format!("pkg:{}/{}@{}", ecosystem, name, version)),
+            _ => Some(format!("pkg:{}/{}", ecosystem, name)),
+        }
+    }
+}
+
+/// Returns `(name, version, None)` of every `[[package]]` from a registry or a git repo in Cargo.lock.
+/// Workspace members have no `source`.
+fn cargo_lock_packages(contents: &str) -> Vec<(String, String, Option<String>)> {
+    let mut packages = Vec::new();
+    let mut package: Option<(String, String, bool)> = None;
+    for line in contents.lines().map(|v| v.trim()).chain(std::iter::once("[end]")) {
+        if line.starts_with('[') {
+            if let Some((name, version, true)) = package.take() {
+                if !name.is_empty() && !version.is_empty() {
+                    packages.push((name, version, None));
+                }
+            }
+            if line == "[[package]]" {
+                package = Some((String::new(), String::new(), false));
+            }
+        } else if let (Some(package), Some((key, value))) = (package.as_mut(), line.split_once('=')) {
+            let value = value.trim().trim_matches('"');
+            match key.trim() {
+                "name" => package.0 = value.to_owned(),
+                "version" => package.1 = value.to_owned(),
+                "source" => package.2 = true,
+                _ => {}
+            }
+        }
+    }
+
+    packages
+}
+
+/// Returns `(name, version, license)` of every installed package in package-lock.json v2 and later.
+fn package_lock_packages(contents: &str) -> Vec<(String, String, Option<String>)> {
+    let lock = match serde_json::from_str::<Value>(contents) {
+        Ok(v) => v,
+        Err(_) => return Vec::new(),
+    };
+
+    let mut packages = Vec::new();
+    if let Some(lock_packages) = lock.get("packages").and_then(|v| v.as_object()) {
+        for (path, package) in lock_packages {
+            // the empty path is the project itself and links point at local folders
+            if path.is_empty() || package.get("link").and_then(|v| v.as_bool()).unwrap_or_default() {
+                continue;
+            }
+            // nested packages are installed under the path of the parent, e.g. `node_modules/a/node_modules/b`
+            let name = match package.get("name").and_then(|v| v.as_str()) {
+                Some(v) => v,
+                None => path.rsplit("node_modules/").next().unwrap_or(path),
+            };
+            if let Some(version) = package.get("version").and_then(|v| v.as_str()) {
+                packages.push((name.to_owned(), version.to_owned(), json_license(package)));
+            }
+        }
+    }
+
+    packages
+}
+
+/// Returns `(name, version, license)` of every package in `packages` and `packages-dev` of composer.lock.
+fn composer_lock_packages(contents: &str) -> Vec<(String, String, Option<String>)> {
+    let lock = match serde_json::from_str::<Value>(contents) {
+        Ok(v) => v,
+        Err(_) => return Vec::new(),
+    };
+
+    let mut packages = Vec::new();
+    for section in ["packages", "packages-dev"] {
+        for package in lock.get(section).and_then(|v| v.as_array()).into_iter().flatten() {
+            if let (Some(name), Some(version)) = (
+                package.get("name").and_then(|v| v.as_str()),
+                package.get("version").and_then(|v| v.as_str()),
+            ) {
+                packages.push((name.to_owned(), version.to_owned(), json_license(package)));
+            }
+        }
+    }
+
+    packages
+}
+
+/// Returns true if the license looks like an SPDX expression, e.g. `MIT OR Apache-2.0`, rather than free text,
+/// e.g. `SEE LICENSE IN LICENSE.txt`. The license IDs are not validated.
+fn is_spdx_expression(license: &str) -> bool {
+    let tokens = license
+        .split_whitespace()
+        .map(|v| v.trim_matches(['(', ')']))
+        .collect::<Vec<&str>>();
+
+    // license IDs alternate with operators
+    !tokens.is_empty()
+        && tokens.iter().enumerate().all(|(idx, token)| {
+            if idx % 2 == 1 {
+                ["AND", "OR", "WITH"].contains(token)
+            } else {
+                !token.is_empty() && token.chars().all(|c| c.is_ascii_alphanumeric() || ".-+:".contains(c))
+            }
+        })
+}
+
+/// Returns the license for `licenseDeclared` of an SPDX package.
+fn spdx_license(license: Option<&str>) -> String {
+    match license {
+        Some(v) if is_spdx_expression(v) => v.to_owned(),
+        _ => NO_ASSERTION.to_owned(),
+    }
+}
+
+/// Returns the license as an SPDX expression or as a name if it is free text.
+fn cyclonedx_license(license: &str) -> Value {
+    if is_spdx_expression(license) {
+        json!({ "expression": license })
+    } else {
+        json!({"license": {"name": license}})
+    }
+}
+
+#[cfg(test)]
+mod test_sbom {
+    use super::{Sbom, SbomSource};
+    use crate::report::{Report, Tech};
+
+    #[test]
+    fn test_sbom() {
+        let rust = Tech::test("Rust", "rust.cargo.toml").with_loc(5).with_pkgs(&[
+            ("serde", Some("1.0"), 1),
+            ("regex", Some("1.5"), 1),
+            ("edition", Some("2018"), 1),
+        ]);
+        let js = Tech::test("JavaScript", "js")
+            .with_loc(5)
+            .with_refs(&[("react", 3), ("fs", 1), ("@angular", 1)]);
+        let mut report = Report::new();
+        report.tech.insert(rust);
+        report.tech.insert(js);
+
+        let mut sbom = Sbom::from_report(&report, "my project");
+        let names = sbom.components.iter().map(|v| v.name.as_str()).collect::<Vec<&str>>();
+        assert_eq!(names, vec!["regex", "serde", "react"]);
+        assert_eq!(sbom.components[2].purl().unwrap(), "pkg:npm/react");
+
+        // the lock file replaces the manifest entries with the exact versions and adds the transitive dependencies
+        sbom.add_lock_file(
+            "Cargo.lock",
+            b"version = 3\n\n[[package]]\nname = \"my_project\"\nversion = \"0.1.0\"\n\n[[package]]\nname = \"serde\"\nversion = \"1.0.130\"\nsource = \"registry+https://github.com/rust-lang/crates.io-index\"\n\n[[package]]\nname = \"itoa\"\nversion = \"1.0.1\"\nsource = \"registry+https://github.com/rust-lang/crates.io-index\"\n",
+        );
+        let components = sbom
+            .components
+            .iter()
+            .map(|v| (v.name.as_str(), v.version.as_deref(), v.source))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            components,
+            vec![
+                ("itoa", Some("1.0.1"), SbomSource::LockFile),
+                ("regex", Some("1.5"), SbomSource::Manifest),
+                ("serde", Some("1.0.130"), SbomSource::LockFile),
+                ("react", None, SbomSource::Imports),
+            ]
+        );
+        assert_eq!(sbom.components[2].purl().unwrap(), "pkg:cargo/serde@1.0.130");
+        assert_eq!(sbom.components[1].purl().unwrap(), "pkg:cargo/regex");
+
+        // imports are replaced by the lock file altogether
+        sbom.add_lock_file(
+            "package-lock.json",
+            br#"{"lockfileVersion":3,"packages":{"":{"name":"x"},"node_modules/@types/node":{"version":"20.1.0","license":"MIT"},
+            "node_modules/a/node_modules/react":{"version":"18.2.0","license":"SEE LICENSE IN LICENSE"}}}"#,
+        );
+        let npm = sbom
+            .components
+            .iter()
+            .filter(|v| v.ecosystem.as_deref() == Some("npm"))
+            .collect::<Vec<_>>();
+        assert_eq!(npm.len(), 2);
+        assert_eq!(npm[0].purl().unwrap(), "pkg:npm/%40types/node@20.1.0");
+        assert_eq!(npm[1].name, "react");
+
+        let cyclonedx = sbom.to_cyclonedx("0.1.7");
+        assert_eq!(cyclonedx["components"].as_array().unwrap().len(), 5);
+        assert_eq!(cyclonedx["metadata"]["component"]["name"], "my project");
+        assert_eq!(cyclonedx["components"][3]["licenses"][0]["expression"], "MIT");
+        assert_eq!(cyclonedx["components"][4]["licenses"][0]["license"]["name"], "SEE LICENSE IN LICENSE");
+
+        let spdx = sbom.to_spdx("0.1.7");
+        assert_eq!(spdx["packages"].as_array().unwrap().len(), 6);
+        assert_eq!(spdx["relationships"].as_array().unwrap().len(), 6);
+        assert_eq!(spdx["packages"][4]["licenseDeclared"], "MIT");
+        assert_eq!(spdx["packages"][5]["licenseDeclared"], "NOASSERTION");
+        assert!(spdx["documentNamespace"]
+            .as_str()
+            .unwrap()
+            .starts_with("https://stackmuncher.com/spdx/my_project-"));
+    }
+
+    #[test]
+    fn test_sbom_ecosystems() {
+        let python =
+            Tech::test("Python", "python.pyproject.toml")
+                .with_loc(5)
+                .with_pkgs(&[("requests", Some("2.28"), 1)]);
+        let go = Tech::test("Go", "go.mod")
+            .with_loc(5)
+            .with_pkgs(&[("github.com/pkg/errors", Some("v0.9.1"), 1)]);
+        let ts = Tech::test("TypeScript", "ts").with_loc(5).with_refs(&[
+            ("@angular/core", 2),
+            ("@angular", 1),
+            ("./app", 1),
+        ]);
+        let mut report = Report::new();
+        report.tech.insert(python);
+        report.tech.insert(go);
+        report.tech.insert(ts);
+
+        // scoped npm packages are kept with the scope, but a scope on its own is not a package
+        let mut purls = Sbom::from_report(&report, "my project")
+            .components
+            .iter()
+            .map(|v| v.purl().unwrap())
+            .collect::<Vec<String>>();
+        purls.sort();
+        assert_eq!(
+            purls,
+            vec![
+                "pkg:golang/github.com/pkg/errors",
+                "pkg:npm/%40angular/core",
+                "pkg:pypi/requests"
+            ]
+        );
+    }
+}
@@ -0,0 +1,179 @@
+use super::tech::Tech;
+use crate::contributor::Contributor;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+
+/// Database schema migrations found by the naming conventions of migration frameworks, e.g. Flyway or Alembic.
+/// Migration files are counted separately from the rest of the code in their language to surface data-layer work.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, Eq, PartialEq)]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
+pub struct SchemaMigrations {
+    /// Number of migration files, including deleted files that are still in the report history.
+    pub files: u64,
+    /// Lines of code in the migration files, excluding comments, blank and bracket-only lines.
+    pub loc: u64,
+    /// Number of migration files per framework, e.g. `alembic: 12`.
+    pub frameworks: BTreeMap<String, u64>,
+    /// Number of migration files changed by each contributor by their git ID, deleted files included.
+    /// Only present in project reports.
+    #[serde(skip_serializing_if = "BTreeMap::is_empty", default)]
+    pub contributors: BTreeMap<String, u64>,
+}
+
+impl SchemaMigrations {
+    /// Counts migration files and their LoC in the munched records. Returns None if there are no migrations.
+    pub fn from_per_file_tech<'a, I>(per_file_tech: I) -> Option<Self>
+    where
+        I: Iterator<Item = &'a Tech>,
+    {
+        let mut migrations = Self::default();
+        let mut counted_files: HashSet<&str> = HashSet::new();
+
+        for tech in per_file_tech {
+            let file_name = match tech.file_name.as_deref() {
+                Some(v) => v,
+                None => continue,
+            };
+            let framework = match migration_framework(file_name) {
+                Some(v) => v,
+                None => continue,
+            };
+
+            // a file may have several records, e.g. templates with the host language
+            if counted_files.insert(file_name) {
+                migrations.files += 1;
+                *migrations.frameworks.entry(framework.to_owned()).or_default() += 1;
+            }
+            migrations.loc += tech.code_lines;
+        }
+
+        if migrations.files == 0 {
+            None
+        } else {
+            Some(migrations)
+        }
+    }
+
+    /// Adds the number of migration files changed by each contributor.
+    pub(crate) fn add_contributors(&mut self, contributors: &[Contributor]) {
+        for contributor in contributors {
+            let files = contributor
+                .touched_files
+                .iter()
+                .filter(|file| migration_framework(&file.name).is_some())
+                .count() as u64;
+            if files > 0 {
+                self.contributors.insert(contributor.git_id.clone(), files);
+            }
+        }
+    }
+}
+
+/// Returns the name of the framework the file is a migration for or None if it's not a migration.
+/// E.g. `alembic` for `alembic/versions/1975ea83b712_create_account_table.py`.
+pub fn migration_framework(file_name: &str) -> Option<&'static str> {
+    let (dir, base_name) = match file_name.rfind('/') {
+        Some(idx) => (&file_name[..idx + 1], &file_name[idx + 1..]),
+        None => ("", file_name),
+    };
+    let parent_dir = dir.trim_end_matches('/').rsplit('/').next().unwrap_or_default();
+    let (stem, ext) = base_name.rsplit_once('.').unwrap_or((base_name, ""));
+
+    match ext {
+        // Rails: db/migrate/20210101120000_create_users.rb
+        "rb" if dir.ends_with("db/migrate/") && starts_with_number(stem, '_') => Some("rails"),
+        // Alembic and Flask-Migrate: alembic/versions/1975ea83b712_create_account_table.py
+        "py" if dir.ends_with("alembic/versions/") || dir.ends_with("migrations/versions/") => Some("alembic"),
+        // Django: app/migrations/0001_initial.py
+        "py" if parent_dir == "migrations" && starts_with_number(stem, '_') => Some("django"),
+        // Flyway: V1_1__add_users.sql, U1_1__add_users.sql for undo or R__views.sql for repeatable migrations
+        "sql" if is_flyway_migration(stem) => Some("flyway"),
+        // Diesel: migrations/2021-01-01-120000_create_users/up.sql
+        "sql"
+            if (stem == "up" || stem == "down")
+                && dir.contains("migrations/")
+                && starts_with_number(parent_dir, '-') =>
+        {
+            Some("diesel")
+        }
+        // sqlx and other plain SQL migrations: migrations/20210101120000_create_users.sql or .up.sql
+        "sql" if parent_dir == "migrations" && starts_with_number(stem, '_') => Some("sqlx"),
+        _ => None,
+    }
+}
+
+/// Returns true if `name` starts with digits followed by `separator`, e.g. `0001_initial` with `_`.
+fn starts_with_number(name: &str, separator: char) -> bool {
+    let digits = name.chars().take_while(|c| c.is_ascii_digit()).count();
+    digits > 0 && name[digits..].starts_with(separator)
+}
+
+/// Returns true for Flyway versioned, undo or repeatable migration names without the extension,
+/// e.g. `V1_1__add_users`, `U2__drop_users` or `R__views`.
+fn is_flyway_migration(stem: &str) -> bool {
+    let (prefix, description) = match stem.split_once("__") {
+        Some(v) => v,
+        None => return false,
+    };
+    if description.is_empty() {
+        return false;
+    }
+
+    match prefix.strip_prefix('V').or_else(|| prefix.strip_prefix('U')) {
+        Some(version) => {
+            !version.is_empty()
+                && version.starts_with(|c: char| c.is_ascii_digit())
+                && version.chars().all(|c| c.is_ascii_digit() || c == '.' || c == '_')
+        }
+        None => prefix == "R",
+    }
+}
+
+#[cfg(test)]
+mod test_schema_migrations {
+    use super::{migration_framework, SchemaMigrations};
+    use crate::report::Tech;
+
+    #[test]
+    fn test_migration_framework() {
+        assert_eq!(migration_framework("db/migrate/20210101120000_create_users.rb"), Some("rails"));
+        assert_eq!(
+            migration_framework("alembic/versions/1975ea83b712_create_account_table.py"),
+            Some("alembic")
+        );
+        assert_eq!(migration_framework("app/migrations/versions/ae1027a6acf_.py"), Some("alembic"));
+        assert_eq!(migration_framework("blog/migrations/0001_initial.py"), Some("django"));
+        assert_eq!(migration_framework("src/main/resources/db/migration/V1_1__add_users.sql"), Some("flyway"));
+        assert_eq!(migration_framework("sql/R__views.sql"), Some("flyway"));
+        assert_eq!(migration_framework("migrations/2021-01-01-120000_create_users/up.sql"), Some("diesel"));
+        assert_eq!(migration_framework("migrations/20210101120000_create_users.sql"), Some("sqlx"));
+        assert_eq!(migration_framework("migrations/0002_users.up.sql"), Some("sqlx"));
+
+        assert_eq!(migration_framework("blog/migrations/__init__.py"), None);
+        assert_eq!(migration_framework("db/migrate/helpers.rb"), None);
+        assert_eq!(migration_framework("sql/Version__notes.sql"), None);
+        assert_eq!(migration_framework("sql/schema.sql"), None);
+        assert_eq!(migration_framework("src/up.sql"), None);
+    }
+
+    #[test]
+    fn test_from_per_file_tech() {
+        let file_tech = |file_name: &str, code_lines: u64| -> Tech {
+            Tech::test("Python", "python").with_file(file_name).with_loc(code_lines)
+        };
+
+        let per_file_tech = [
+            file_tech("blog/migrations/0001_initial.py", 30),
+            file_tech("blog/migrations/0002_post_title.py", 10),
+            file_tech("db/migration/V1__init.sql", 5),
+            file_tech("blog/models.py", 100),
+        ];
+        let migrations = SchemaMigrations::from_per_file_tech(per_file_tech.iter()).unwrap();
+        assert_eq!(migrations.files, 3);
+        assert_eq!(migrations.loc, 45);
+        assert_eq!(migrations.frameworks["django"], 2);
+        assert_eq!(migrations.frameworks["flyway"], 1);
+
+        assert!(SchemaMigrations::from_per_file_tech(per_file_tech[3..].iter()).is_none());
+    }
+}
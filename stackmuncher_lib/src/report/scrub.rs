@@ -0,0 +1,101 @@
+use super::kwc::KeywordCounter;
+use crate::utils::sha256::hash_str_to_sha256_as_base58;
+use regex::Regex;
+use std::collections::HashSet;
+use tracing::debug;
+
+/// User-defined rules for removing sensitive info from a report before submission.
+/// Applied by `Report::sanitize()` on top of the built-in clean up.
+#[derive(Debug, Clone, Default)]
+pub struct ScrubRules {
+    /// Per-file records with file paths matching any of these regexes are dropped from the report.
+    pub file_paths: Vec<Regex>,
+    /// Library names and refs matching any of these regexes are replaced with a salted hash,
+    /// e.g. packages from a private registry.
+    pub pkgs: Vec<Regex>,
+}
+
+impl ScrubRules {
+    /// Compiles the rules from lists of regex strings.
+    /// Returns an error with the invalid regex and the reason if any of them fails to compile.
+    pub fn new(file_paths: &[String], pkgs: &[String]) -> Result<Self, String> {
+        Ok(Self {
+            file_paths: compile_regex_list(file_paths)?,
+            pkgs: compile_regex_list(pkgs)?,
+        })
+    }
+
+    /// Returns true if there are no user-defined rules.
+    pub fn is_empty(&self) -> bool {
+        self.file_paths.is_empty() && self.pkgs.is_empty()
+    }
+
+    /// Returns true if the file path matches any of the `file_paths` rules.
+    pub fn is_path_scrubbed(&self, file_path: &str) -> bool {
+        self.file_paths.iter().any(|r| r.is_match(file_path))
+    }
+
+    /// Replaces the keywords matching any of `pkgs` rules with a hash of `salt`+keyword.
+    /// The free text attached to the matching keywords is removed because it may contain the original name.
+    pub(crate) fn scrub_pkg_names(&self, kw_counters: HashSet<KeywordCounter>, salt: &str) -> HashSet<KeywordCounter> {
+        if self.pkgs.is_empty() {
+            return kw_counters;
+        }
+
+        kw_counters
+            .into_iter()
+            .map(|kwc| {
+                if self.pkgs.iter().any(|r| r.is_match(&kwc.k)) {
+                    debug!("Scrubbed pkg: {}", kwc.k);
+                    KeywordCounter {
+                        k: hash_str_to_sha256_as_base58(&[salt, kwc.k.as_str()].concat()),
+                        t: None,
+                        c: kwc.c,
+                    }
+                } else {
+                    kwc
+                }
+            })
+            .collect()
+    }
+}
+
+/// Compiles a list of regex strings or returns an error with the first invalid one.
+fn compile_regex_list(list: &[String]) -> Result<Vec<Regex>, String> {
+    list.iter()
+        .map(|s| Regex::new(s).map_err(|e| format!("invalid regex `{}`: {}", s, e)))
+        .collect()
+}
+
+#[cfg(test)]
+mod test_scrub {
+    use super::ScrubRules;
+    use crate::report::kwc::KeywordCounter;
+    use std::collections::HashSet;
+
+    #[test]
+    fn test_scrub_rules() {
+        assert!(ScrubRules::new(&["(".to_owned()], &[]).is_err());
+
+        let rules = ScrubRules::new(&["^internal/".to_owned()], &["^acme[-_]".to_owned()]).unwrap();
+        assert!(rules.is_path_scrubbed("internal/secret.rs"));
+        assert!(!rules.is_path_scrubbed("src/internal/main.rs"));
+
+        let mut pkgs = HashSet::new();
+        pkgs.insert(KeywordCounter {
+            k: "acme_billing".to_owned(),
+            t: None,
+            c: 2,
+        });
+        pkgs.insert(KeywordCounter {
+            k: "serde".to_owned(),
+            t: None,
+            c: 1,
+        });
+        let pkgs = rules.scrub_pkg_names(pkgs, "salt");
+
+        assert_eq!(pkgs.len(), 2);
+        assert!(pkgs.iter().any(|v| v.k == "serde"));
+        assert!(!pkgs.iter().any(|v| v.k.contains("acme")));
+    }
+}
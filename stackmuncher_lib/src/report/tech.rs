@@ -6,6 +6,7 @@ use tracing::{debug, trace, warn};
 
 /// Contains time-range data for its parent Tech.
 #[derive(Serialize, Deserialize, Debug, Eq, PartialEq, Clone)]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
 #[serde(rename = "tech")]
 pub struct TechHistory {
     /// Number of months between the first and the last commit.
@@ -20,11 +21,54 @@ pub struct TechHistory {
     pub to_date_iso: String,
 }
 
+impl TechHistory {
+    /// Returns the history of a single commit, e.g. the last commit of a file.
+    pub(crate) fn from_commit(date_epoch: i64, date_iso: String) -> Self {
+        Self {
+            months: 0,
+            from_date_epoch: date_epoch,
+            from_date_iso: date_iso.clone(),
+            to_date_epoch: date_epoch,
+            to_date_iso: date_iso,
+        }
+    }
+
+    /// Widens the date range of `history` to include `other_history` and recalculates `months`.
+    /// Returns whichever is present if the other one is None.
+    pub(crate) fn merge(history: Option<Self>, other_history: Option<Self>) -> Option<Self> {
+        let (mut history, other_history) = match (history, other_history) {
+            (Some(history), Some(other_history)) => (history, other_history),
+            (history, other_history) => return history.or(other_history),
+        };
+
+        if other_history.from_date_epoch < history.from_date_epoch {
+            history.from_date_epoch = other_history.from_date_epoch;
+            history.from_date_iso = other_history.from_date_iso;
+        }
+        if other_history.to_date_epoch > history.to_date_epoch {
+            history.to_date_epoch = other_history.to_date_epoch;
+            history.to_date_iso = other_history.to_date_iso;
+        }
+        history.update_months();
+
+        Some(history)
+    }
+
+    /// Sets `months` to the number of months between the first and the last commit.
+    pub(crate) fn update_months(&mut self) {
+        // calculated as 31_536_000 / 12
+        // this i64 -> u64 conversion should be safe and is needed if somehow the dates are reversed and the result is negative
+        // it will simply produce a zero
+        self.months = (self.to_date_epoch - self.from_date_epoch).max(0) as u64 / 2_628_000;
+    }
+}
+
 /// # PRIVACY REMINDER
 /// Any additions to this struct should be considered for clean up before submission to stackmuncher.com
 /// to avoid sending out any info that doesn't need to be sent.
 /// See https://github.com/stackmuncher/stm_app/issues/12
 #[derive(Serialize, Deserialize, Debug, Eq, Clone)]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
 #[serde(rename = "tech")]
 pub struct Tech {
     /// The name of the file for individual file reports. Not present in combined tech reports.
@@ -34,7 +78,8 @@ pub struct Tech {
     pub language: String,
     /// The name of the muncher used to process the file
     pub muncher_name: String,
-    /// A short hash of the muncher rules to detect a muncher change for reprocessing
+    /// A short hash of the muncher rules and the relevant `MunchLimits` to detect a change for reprocessing,
+    /// see `Muncher::cache_hash`
     #[serde(default)]
     pub muncher_hash: u64,
     /// SHA1 of the commit this file was taken from. E.g. 105eaf871c7248c93ae2f13337e9881caf89d489
@@ -58,17 +103,47 @@ pub struct Tech {
     pub line_comments: u64,
     pub block_comments: u64,
     pub docs_comments: u64,
+    /// Number of files classified as test code by the muncher. Included in `files`.
+    #[serde(default)]
+    pub test_files: u64,
+    /// Lines of code in files classified as test code by the muncher. Included in `code_lines`.
+    #[serde(default)]
+    pub test_loc: u64,
+    /// Lines of code in generated or vendored files, e.g. protobuf output or minified JS. Not included in `code_lines`.
+    #[serde(default)]
+    pub generated_loc: u64,
+    /// Number of functions, types and other entities declared in non-test code, as defined by the muncher.
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub entities: u64,
+    /// Number of `entities` with doc comments or docstrings.
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub documented_entities: u64,
+    /// Number of code cells in notebooks, e.g. Jupyter. The code is counted in the record of the kernel language.
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub code_cells: u64,
+    /// Number of markdown cells in notebooks. Their lines are counted as `docs_comments`.
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub markdown_cells: u64,
+    /// Number of distinct keywords, refs and pkgs dropped from this record to keep the lists within
+    /// `MergeLimits.max_keywords_per_tech`. The same keyword dropped from several merged reports is counted more than once.
+    #[serde(default)]
+    pub overflow_distinct: u64,
+    /// The sum of counts of the keywords, refs and pkgs dropped from this record. See `overflow_distinct`.
+    #[serde(default)]
+    pub overflow_total: u64,
     /// Historical stats for this tech record: first/last commits, LoC changes.
-    /// Populated on STM server.
-    /// See https://github.com/stackmuncher/stm_app/issues/46 for more info.
+    /// In project and contributor reports it is the range of the last commits of the files of this record, so it starts
+    /// when the oldest file still in the report was last changed rather than when the tech was first used.
+    /// Combined reports widen it to the first and the last commits of the contributor in the projects with this tech.
+    /// Not present in per-file records. See https://github.com/stackmuncher/stm_app/issues/46 for more info.
     #[serde(skip_serializing_if = "Option::is_none")]
     pub history: Option<TechHistory>,
     /// Language-specific keywords, e.g. static, class, try-catch
-    #[serde(skip_serializing_if = "HashSet::is_empty", default = "HashSet::new")]
+    #[serde(skip_serializing_if = "HashSet::is_empty", default)]
     pub keywords: HashSet<KeywordCounter>, // has to be Option<>
     /// References to other libs, packages and namespaces
     /// E.g. `use` keyword
-    #[serde(skip_serializing_if = "HashSet::is_empty", default = "HashSet::new")]
+    #[serde(skip_serializing_if = "HashSet::is_empty", default)]
     pub refs: HashSet<KeywordCounter>, // has to be Option<>
     /// Unique words from refs. Only populated during the final merge of
     /// all user reports.
@@ -76,18 +151,56 @@ pub struct Tech {
     pub refs_kw: Option<HashSet<KeywordCounter>>,
     /// References to other libs and packages in pkg managers
     /// E.g. refs from NuGet or Cargo.toml
-    #[serde(skip_serializing_if = "HashSet::is_empty", default = "HashSet::new")]
+    #[serde(skip_serializing_if = "HashSet::is_empty", default)]
     pub pkgs: HashSet<KeywordCounter>, // has to be Option<>
     /// Unique words from pkgs. Only populated during the final merge of
     /// all user reports.
     #[serde(skip_serializing_if = "Option::is_none")]
     pub pkgs_kw: Option<HashSet<KeywordCounter>>,
+    /// Number of code lines at a boundary with another language by the name of that language, e.g. `C` for FFI
+    /// declarations, P/Invoke, JNI or cgo.
+    #[serde(skip_serializing_if = "HashSet::is_empty", default)]
+    pub interop: HashSet<KeywordCounter>,
+    /// Number of lines declaring the version or edition of the language by that version, e.g. `2021` for Rust
+    /// edition 2021 in Cargo.toml or `3.11` for `python_requires=">=3.11"`. Mostly comes from manifest files.
+    #[serde(skip_serializing_if = "HashSet::is_empty", default)]
+    pub language_version: HashSet<KeywordCounter>,
+    /// Number of comment lines with tech-debt markers by the marker, e.g. `TODO` or `FIXME`. Generated code is
+    /// not counted. Only counted if enabled in `MunchLimits.tech_debt_markers`.
+    #[serde(skip_serializing_if = "HashSet::is_empty", default)]
+    pub tech_debt_markers: HashSet<KeywordCounter>,
+    /// Project files referenced from this file, e.g. `src/utils.py` for `from src import utils`.
+    /// Only present in per-file records. Such references are removed from `refs` as local imports.
+    #[serde(skip_serializing_if = "HashSet::is_empty", default)]
+    pub local_deps: HashSet<String>,
+    /// Number of consecutive runs the file was not in the tree at HEAD. Only counted for records in
+    /// `Report.per_file_tech_history` if the retention policy is on. See `Retention`.
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub absent_runs: u64,
+    /// The encoding the file was decoded from if it was not UTF-8, e.g. `Shift_JIS` or `UTF-16LE`.
+    /// Only present in per-file records.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub encoding: Option<String>,
+    /// Number of binary files or files that could not be decoded as text. They are included in `files`
+    /// with no lines counted.
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub undecodable_files: u64,
+    /// True if the counts are extrapolated from samples of a very large file or a file with very long lines,
+    /// see `MunchLimits.sample_file_size`. Keywords and libraries only come from the samples.
+    /// Summary records are marked if any of their files were sampled.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub sampled: bool,
     // PRIVACY REMINDER
     // Any additions to this struct should be considered for clean up before submission to stackmuncher.com
     // to avoid sending out any info that doesn't need to be sent.
     // See https://github.com/stackmuncher/stm_app/issues/12
 }
 
+/// Used to omit counters that only apply to some file types or records, e.g. notebook cells.
+fn is_zero(value: &u64) -> bool {
+    *value == 0
+}
+
 impl std::hash::Hash for Tech {
     fn hash<H>(&self, state: &mut H)
     where
@@ -115,17 +228,52 @@ impl Tech {
     /// Sets `file_name` and commit info to None to match tech records on `muncher_name` and `language` only.
     /// `per_file_tech` records are matched with all that info present because it is specific to the file.
     /// `tech` records in the report are aggregates across multiple files and should have that info removed.
+    /// The commit date of a per-file record becomes the `history` of the summary.
     pub(crate) fn reset_file_and_commit_info(self) -> Self {
         let mut tech = self;
 
+        if tech.history.is_none() {
+            tech.history = tech
+                .commit_date_epoch
+                .zip(tech.commit_date_iso.clone())
+                .map(|(date_epoch, date_iso)| TechHistory::from_commit(date_epoch, date_iso));
+        }
         tech.file_name = None;
         tech.commit_sha1 = None;
         tech.commit_date_epoch = None;
         tech.commit_date_iso = None;
+        tech.local_deps.clear();
+        tech.absent_runs = 0;
+        tech.encoding = None;
 
         tech
     }
 
+    /// Keeps no more than `max_len` top records in each of keywords, refs and pkgs and adds up the counts of the
+    /// dropped records in `overflow_distinct` and `overflow_total`. The keyword summaries are trimmed without counting
+    /// because they are derived from refs and pkgs.
+    pub(crate) fn retain_top_keywords(&mut self, max_len: usize) {
+        for kw_counters in [&mut self.keywords, &mut self.refs, &mut self.pkgs] {
+            let (distinct, total) = kw_counters.retain_top(max_len);
+            self.overflow_distinct += distinct;
+            self.overflow_total += total;
+        }
+
+        if let Some(refs_kw) = self.refs_kw.as_mut() {
+            refs_kw.retain_top(max_len);
+        }
+        if let Some(pkgs_kw) = self.pkgs_kw.as_mut() {
+            pkgs_kw.retain_top(max_len);
+        }
+
+        if self.overflow_distinct > 0 {
+            debug!(
+                "{} keywords over the limit for {}, total count: {}",
+                self.overflow_distinct, self.language, self.overflow_total
+            );
+        }
+    }
+
     /// Extract and count matches for `self.refs`
     #[inline]
     pub(crate) fn count_refs(&mut self, regex: &Option<Vec<Regex>>, line: &String) {
@@ -141,7 +289,51 @@ impl Tech {
     /// Extract and count matches for `self.pkgs`
     #[inline]
     pub(crate) fn count_pkgs(&mut self, regex: &Option<Vec<Regex>>, line: &String) {
-        Self::count_matches(regex, line, &mut self.pkgs, &KeywordCounter::new_ref);
+        Self::count_matches(regex, line, &mut self.pkgs, &KeywordCounter::new_pkg);
+    }
+
+    /// Counts the line once for every language it has an interop boundary with, e.g. `C` for `extern "C" {`.
+    #[inline]
+    pub(crate) fn count_interop(&mut self, regex: &Option<Vec<(String, Regex)>>, line: &str) {
+        if let Some(v) = regex {
+            // the regex for the same language are next to each other
+            let mut languages = v
+                .iter()
+                .filter(|(_, r)| r.is_match(line))
+                .map(|(language, _)| language)
+                .collect::<Vec<&String>>();
+            languages.dedup();
+
+            for language in languages {
+                trace!("interop with {}", language);
+                self.interop
+                    .increment_counters(KeywordCounter::new_keyword(language.clone(), 1));
+            }
+        }
+    }
+
+    /// Extract and count matches for `self.language_version`
+    #[inline]
+    pub(crate) fn count_language_version(&mut self, regex: &Option<Vec<Regex>>, line: &String) {
+        Self::count_matches(regex, line, &mut self.language_version, &KeywordCounter::new_keyword);
+    }
+
+    /// Extract and count matches for `self.tech_debt_markers`. Unlike keywords, every marker in the line is counted,
+    /// e.g. `XXX and a TODO` counts as one `XXX` and one `TODO`.
+    #[inline]
+    pub(crate) fn count_tech_debt_markers(&mut self, regex: &Option<Vec<Regex>>, line: &str) {
+        for r in regex.iter().flatten() {
+            for groups in r.captures_iter(line) {
+                // the marker is the 1st capture group or the whole match if there are no groups
+                let marker = match groups.get(1).or_else(|| groups.get(0)) {
+                    Some(v) if !v.as_str().trim().is_empty() => v.as_str().trim().to_owned(),
+                    _ => continue,
+                };
+                trace!("{} for {}", marker, r);
+                self.tech_debt_markers
+                    .increment_counters(KeywordCounter::new_keyword(marker, 1));
+            }
+        }
     }
 
     /// Count `regex` matches in the given `line` using `kw_counter_factory` Fn
@@ -265,6 +457,8 @@ impl Tech {
 
         // a collector of pointers at kwc.k for local imports
         let mut local_imports: Vec<KeywordCounter> = Vec::new();
+        // a collector of the project files the local imports point to
+        let mut local_deps: Vec<String> = Vec::new();
 
         // check every import name against the file names for possible matches
         for (kwc, normalized_import) in all_imports_normalized {
@@ -302,6 +496,10 @@ impl Tech {
                 // .clone() is necessary to remove the local kwc from the list later
                 // there should only be a small number of imports per project
                 local_imports.push(kwc.clone());
+                // keep the file it points to for the dependency graph, unless it's a self-reference
+                if tech.file_name.as_ref() != Some(*full_file_name) {
+                    local_deps.push((*full_file_name).clone());
+                }
                 debug!("Removing local import: {} / {}", kwc.k, full_file_name);
                 break;
             }
@@ -311,6 +509,7 @@ impl Tech {
         for local_import in local_imports {
             tech.refs.remove(&local_import);
         }
+        tech.local_deps.extend(local_deps);
 
         // some TypeScript refs start with @, e.g. @angular/core
         // it's a valid name, but @ will get in the way of users searching for "angular"
@@ -332,3 +531,98 @@ impl Tech {
         tech
     }
 }
+
+#[cfg(test)]
+impl Tech {
+    /// Returns a blank summary record with a single file for building test reports.
+    /// Other fields can be set with the `with_` functions or the struct update syntax.
+    pub(crate) fn test(language: &str, muncher_name: &str) -> Self {
+        Self {
+            language: language.to_owned(),
+            muncher_name: muncher_name.to_owned(),
+            file_name: None,
+            commit_sha1: None,
+            commit_date_epoch: None,
+            commit_date_iso: None,
+            files: 1,
+            total_lines: 0,
+            code_lines: 0,
+            line_comments: 0,
+            block_comments: 0,
+            docs_comments: 0,
+            test_files: 0,
+            test_loc: 0,
+            generated_loc: 0,
+            entities: 0,
+            documented_entities: 0,
+            code_cells: 0,
+            markdown_cells: 0,
+            overflow_distinct: 0,
+            overflow_total: 0,
+            absent_runs: 0,
+            encoding: None,
+            undecodable_files: 0,
+            sampled: false,
+            inline_comments: 0,
+            blank_lines: 0,
+            bracket_only_lines: 0,
+            keywords: HashSet::new(),
+            refs: HashSet::new(),
+            refs_kw: None,
+            pkgs: HashSet::new(),
+            pkgs_kw: None,
+            interop: HashSet::new(),
+            language_version: HashSet::new(),
+            tech_debt_markers: HashSet::new(),
+            local_deps: HashSet::new(),
+            muncher_hash: 0,
+            history: None,
+        }
+    }
+
+    /// Turns the record into a per-file record for `file_name`.
+    pub(crate) fn with_file(mut self, file_name: &str) -> Self {
+        self.file_name = Some(file_name.to_owned());
+        self
+    }
+
+    /// Sets both `total_lines` and `code_lines` to `loc`.
+    pub(crate) fn with_loc(mut self, loc: u64) -> Self {
+        self.total_lines = loc;
+        self.code_lines = loc;
+        self
+    }
+
+    /// Adds `keywords` as `(keyword, count)` pairs.
+    pub(crate) fn with_keywords(mut self, keywords: &[(&str, u64)]) -> Self {
+        self.keywords.extend(
+            keywords
+                .iter()
+                .map(|(k, c)| KeywordCounter::new_keyword(k.to_string(), *c)),
+        );
+        self
+    }
+
+    /// Adds `refs` as `(keyword, count)` pairs.
+    pub(crate) fn with_refs(mut self, refs: &[(&str, u64)]) -> Self {
+        self.refs
+            .extend(refs.iter().map(|(k, c)| KeywordCounter::new_keyword(k.to_string(), *c)));
+        self
+    }
+
+    /// Adds `local_deps` as paths of files within the project.
+    pub(crate) fn with_local_deps(mut self, local_deps: &[&str]) -> Self {
+        self.local_deps.extend(local_deps.iter().map(|v| v.to_string()));
+        self
+    }
+
+    /// Adds `pkgs` as `(name, version, count)` tuples.
+    pub(crate) fn with_pkgs(mut self, pkgs: &[(&str, Option<&str>, u64)]) -> Self {
+        self.pkgs.extend(pkgs.iter().map(|(k, t, c)| KeywordCounter {
+            k: k.to_string(),
+            t: t.map(|t| [t.to_owned()].iter().cloned().collect()),
+            c: *c,
+        }));
+        self
+    }
+}
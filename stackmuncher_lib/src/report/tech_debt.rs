@@ -0,0 +1,137 @@
+use super::tech::Tech;
+use crate::contributor::Contributor;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+
+/// TODO, FIXME, HACK and other tech-debt markers in comments of the files at HEAD. A rough signal of how much
+/// known unfinished work there is and where. Only present if `MunchLimits.tech_debt_markers` is enabled.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, Eq, PartialEq)]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
+pub struct TechDebtMarkers {
+    /// Total number of markers.
+    pub total: u64,
+    /// Number of markers by the marker, e.g. `TODO: 12`.
+    pub markers: BTreeMap<String, u64>,
+    /// Number of markers per language, e.g. `Rust: 10`.
+    pub languages: BTreeMap<String, u64>,
+    /// Number of markers by the git ID of the contributor who committed the version of the file at HEAD.
+    /// Only present in project reports.
+    #[serde(skip_serializing_if = "BTreeMap::is_empty", default)]
+    pub contributors: BTreeMap<String, u64>,
+}
+
+impl TechDebtMarkers {
+    /// Adds up the markers in the munched records. Returns None if there are no markers.
+    pub fn from_per_file_tech<'a, I>(per_file_tech: I) -> Option<Self>
+    where
+        I: Iterator<Item = &'a Tech>,
+    {
+        let mut tech_debt = Self::default();
+
+        for tech in per_file_tech {
+            for marker in &tech.tech_debt_markers {
+                tech_debt.total += marker.c;
+                *tech_debt.markers.entry(marker.k.clone()).or_default() += marker.c;
+                *tech_debt.languages.entry(tech.language.clone()).or_default() += marker.c;
+            }
+        }
+
+        if tech_debt.total == 0 {
+            None
+        } else {
+            Some(tech_debt)
+        }
+    }
+
+    /// Attributes the markers in every file to the contributor who committed that version of the file.
+    /// Files committed by identities not in `contributors` are not attributed to anyone.
+    pub(crate) fn add_contributors<'a, I>(&mut self, per_file_tech: I, contributors: &[Contributor])
+    where
+        I: Iterator<Item = &'a Tech>,
+    {
+        let authors = contributors
+            .iter()
+            .flat_map(|contributor| {
+                contributor
+                    .touched_files
+                    .iter()
+                    .map(move |file| ((file.name.as_str(), file.commit.as_str()), contributor.git_id.as_str()))
+            })
+            .collect::<HashMap<(&str, &str), &str>>();
+
+        for tech in per_file_tech {
+            let markers = tech.tech_debt_markers.iter().map(|v| v.c).sum::<u64>();
+            if markers == 0 {
+                continue;
+            }
+            let author = match (tech.file_name.as_deref(), tech.commit_sha1.as_deref()) {
+                (Some(file_name), Some(commit_sha1)) => authors.get(&(file_name, commit_sha1)),
+                _ => None,
+            };
+            if let Some(author) = author {
+                *self.contributors.entry((*author).to_owned()).or_default() += markers;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test_tech_debt {
+    use super::TechDebtMarkers;
+    use crate::contributor::{Contributor, ContributorFile};
+    use crate::report::kwc::KeywordCounter;
+    use crate::report::Tech;
+    use std::collections::HashSet;
+
+    #[test]
+    fn test_tech_debt_markers() {
+        let per_file_tech = [
+            ("src/main.rs", "Rust", "abc", vec![("TODO", 2), ("FIXME", 1)]),
+            ("src/lib.rs", "Rust", "def", vec![("TODO", 1)]),
+            ("app.py", "Python", "abc", vec![("HACK", 1)]),
+            ("README.md", "Markdown", "abc", Vec::new()),
+        ]
+        .iter()
+        .map(|(file_name, language, commit_sha1, markers)| Tech {
+            commit_sha1: Some(commit_sha1.to_string()),
+            tech_debt_markers: markers
+                .iter()
+                .map(|(k, c)| KeywordCounter::new_keyword(k.to_string(), *c))
+                .collect(),
+            ..Tech::test(language, "x").with_file(file_name).with_loc(10)
+        })
+        .collect::<Vec<Tech>>();
+
+        assert!(TechDebtMarkers::from_per_file_tech(per_file_tech[3..].iter()).is_none());
+
+        let mut tech_debt = TechDebtMarkers::from_per_file_tech(per_file_tech.iter()).unwrap();
+        assert_eq!(tech_debt.total, 5);
+        assert_eq!(tech_debt.markers.get("TODO"), Some(&3));
+        assert_eq!(tech_debt.markers.get("HACK"), Some(&1));
+        assert_eq!(tech_debt.languages.get("Rust"), Some(&4));
+        assert_eq!(tech_debt.languages.get("Python"), Some(&1));
+
+        // src/lib.rs was last changed in a commit by someone else
+        let touched_file = |name: &str, commit: &str| ContributorFile {
+            name: name.to_owned(),
+            commit: commit.to_owned(),
+            date_epoch: 0,
+            date_iso: String::new(),
+        };
+        let contributor = Contributor {
+            git_id: "max@onebro.me".to_owned(),
+            name_email_pairs: HashSet::new(),
+            last_commit_sha1: String::new(),
+            last_commit_epoch: 0,
+            last_commit_date: String::new(),
+            commit_count: 2,
+            touched_files: vec![touched_file("src/main.rs", "abc"), touched_file("src/lib.rs", "123")]
+                .into_iter()
+                .collect(),
+            commits: Vec::new(),
+        };
+        tech_debt.add_contributors(per_file_tech.iter(), &[contributor]);
+        assert_eq!(tech_debt.contributors.len(), 1);
+        assert_eq!(tech_debt.contributors.get("max@onebro.me"), Some(&3));
+    }
+}
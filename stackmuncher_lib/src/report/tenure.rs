@@ -0,0 +1,119 @@
+use chrono::{DateTime, Datelike, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+
+/// Number of seconds in a day for converting EPOCH timestamps into days.
+const SECONDS_IN_DAY: i64 = 86400;
+
+/// How long the contributor has been active in the project. A longer tenure with regular commits says more about
+/// the depth of the experience than the number of commits. Complements `CommitCadence`, which is about the last year.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
+pub struct Tenure {
+    /// Days between the first and the last commits of the contributor.
+    pub span_days: u64,
+    /// Number of calendar months (UTC) with at least one commit.
+    pub active_months: u64,
+    /// The longest time between two consecutive commits in days, rounded down. Zero if there is only one commit.
+    pub longest_gap_days: u64,
+    /// The date of the commit the longest gap started with, e.g. `2021-03-04`. Not present if there is no gap.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub longest_gap_since: Option<String>,
+}
+
+impl Tenure {
+    /// Calculates the tenure from commit timestamps in EPOCH format. Returns None if there are no valid timestamps.
+    pub(crate) fn from_timestamps<I>(timestamps: I) -> Option<Self>
+    where
+        I: Iterator<Item = i64>,
+    {
+        let mut timestamps = timestamps.filter(|ts| *ts > 0).collect::<Vec<i64>>();
+        timestamps.sort_unstable();
+        let (first, last) = (*timestamps.first()?, *timestamps.last()?);
+
+        let active_months = timestamps
+            .iter()
+            .filter_map(|ts| DateTime::<Utc>::from_timestamp(*ts, 0))
+            .map(|v| (v.year(), v.month()))
+            .collect::<BTreeSet<(i32, u32)>>();
+
+        // the first of equal gaps is reported
+        let longest_gap = timestamps
+            .windows(2)
+            .map(|v| (v[1] - v[0], v[0]))
+            .rev()
+            .max_by_key(|(gap, _)| *gap)
+            .filter(|(gap, _)| *gap > 0);
+
+        Some(Self {
+            span_days: ((last - first) / SECONDS_IN_DAY) as u64,
+            active_months: active_months.len() as u64,
+            longest_gap_days: longest_gap
+                .map(|(gap, _)| (gap / SECONDS_IN_DAY) as u64)
+                .unwrap_or_default(),
+            longest_gap_since: longest_gap
+                .and_then(|(_, since)| DateTime::<Utc>::from_timestamp(since, 0))
+                .map(|v| v.date_naive().to_string()),
+        })
+    }
+
+    /// Same as `from_timestamps`, but takes commits in `e29d17e6_1627380297` format. Invalid entries are skipped.
+    pub(crate) fn from_commits(commits: &[String]) -> Option<Self> {
+        Self::from_timestamps(
+            commits
+                .iter()
+                .filter_map(|commit| commit.split_once('_').and_then(|(_, ts)| ts.parse::<i64>().ok())),
+        )
+    }
+
+    /// Returns a one-line summary for the user, e.g. `2.5 years, 18 active months, longest gap 94 days since 2022-03-01`.
+    /// Spans under 2 months are in days.
+    pub fn summary(&self) -> String {
+        let span = if self.span_days < 60 {
+            format!("{} days", self.span_days)
+        } else {
+            format!("{:.1} years", self.span_days as f64 / 365.25)
+        };
+
+        match &self.longest_gap_since {
+            Some(since) => format!(
+                "{}, {} active months, longest gap {} days since {}",
+                span, self.active_months, self.longest_gap_days, since
+            ),
+            None => format!("{}, {} active months", span, self.active_months),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test_tenure {
+    use super::Tenure;
+
+    #[test]
+    fn test_tenure() {
+        assert!(Tenure::from_commits(&[]).is_none());
+
+        let commits = [
+            "00000005_1640995200", // 2022-01-01 00:00
+            "00000004_1627380297", // 2021-07-27 10:04
+            "00000003_1609502400", // 2021-01-01 12:00
+            "00000002_1609495200", // 2021-01-01 10:00
+            "00000001_1577880000", // 2020-01-01 12:00
+            "invalid",
+        ]
+        .iter()
+        .map(|v| v.to_string())
+        .collect::<Vec<String>>();
+
+        let tenure = Tenure::from_commits(&commits).unwrap();
+        assert_eq!(tenure.span_days, 730);
+        assert_eq!(tenure.active_months, 4);
+        assert_eq!(tenure.longest_gap_days, 365);
+        assert_eq!(tenure.longest_gap_since.as_deref(), Some("2020-01-01"));
+        assert_eq!(tenure.summary(), "2.0 years, 4 active months, longest gap 365 days since 2020-01-01");
+
+        let tenure = Tenure::from_commits(&commits[..1]).unwrap();
+        assert_eq!(tenure.longest_gap_days, 0);
+        assert_eq!(tenure.summary(), "0 days, 1 active months");
+    }
+}
@@ -0,0 +1,239 @@
+use super::Report;
+use crate::utils;
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::Path;
+use tracing::{debug, warn};
+
+/// The name of the file with the rolling history of overview snapshots in the project report folder.
+pub const OVERVIEW_HISTORY_FILE_NAME: &str = "overview_history.json";
+
+/// The max number of snapshots kept in the history. The oldest are dropped first.
+pub const MAX_OVERVIEW_SNAPSHOTS: usize = 50;
+
+/// The number of the most recent snapshots `Trends` are calculated over.
+pub const TREND_RUNS: usize = 10;
+
+/// The stack of the contributor in the project as it was after a single run.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct OverviewSnapshot {
+    /// When the snapshot was taken, RFC3339, e.g. `2023-06-01T10:00:00+00:00`.
+    pub timestamp: String,
+    /// HEAD commit of the project at the time. Repeated runs at the same commit replace the snapshot.
+    pub commit_sha1: String,
+    /// LoC per language, e.g. `Rust: 12656`.
+    pub languages: BTreeMap<String, u64>,
+}
+
+/// Snapshots of the stack from previous runs, oldest first, capped at `MAX_OVERVIEW_SNAPSHOTS`.
+/// Kept locally next to the project report and never submitted.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct OverviewHistory {
+    #[serde(default)]
+    pub snapshots: Vec<OverviewSnapshot>,
+}
+
+/// Changes in the stack between the oldest of the last `TREND_RUNS` snapshots and the latest one.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
+pub struct Trends {
+    /// Number of snapshots the trends were calculated over, including the latest one.
+    pub runs: u64,
+    /// The timestamp of the oldest snapshot compared.
+    pub since: String,
+    /// The change in the total LoC.
+    pub loc_delta: i64,
+    /// Languages with any change in LoC or share, the largest change in share first.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub languages: Vec<LanguageTrend>,
+}
+
+/// The change in LoC and the share of the total LoC of a single language.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
+pub struct LanguageTrend {
+    pub language: String,
+    pub loc_delta: i64,
+    /// The current percentage of the total LoC.
+    pub share: u64,
+    /// The change in the percentage of the total LoC in percentage points, e.g. `12` for 20% -> 32%.
+    pub share_delta: i64,
+}
+
+impl OverviewSnapshot {
+    /// Takes a snapshot of the stack in `report`, usually the combined report of the contributor.
+    pub fn from_report(report: &Report, commit_sha1: &str) -> Self {
+        Self {
+            timestamp: Utc::now().to_rfc3339(),
+            commit_sha1: commit_sha1.to_owned(),
+            languages: report
+                .get_overview()
+                .tech
+                .into_iter()
+                .filter(|tech| tech.loc > 0)
+                .map(|tech| (tech.language, tech.loc))
+                .collect(),
+        }
+    }
+
+    /// Returns the total LoC of all languages.
+    fn loc(&self) -> u64 {
+        self.languages.values().sum()
+    }
+
+    /// Returns the percentage of the total LoC for the language, 0 if there is no such language.
+    fn share(&self, language: &str) -> u64 {
+        match (self.languages.get(language), self.loc()) {
+            (Some(loc), total) if total > 0 => (*loc as f64 * 100.0 / total as f64).round() as u64,
+            _ => 0,
+        }
+    }
+}
+
+impl OverviewHistory {
+    /// Loads the history from the project report folder. Returns a blank history if there is none or it is invalid.
+    pub fn from_disk(report_dir: &Path) -> Self {
+        let history_file = report_dir.join(OVERVIEW_HISTORY_FILE_NAME);
+        let contents = match std::fs::read(&history_file) {
+            Ok(v) => v,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Self::default(),
+            Err(e) => {
+                warn!("Cannot read {} due to {}", history_file.to_string_lossy(), e);
+                return Self::default();
+            }
+        };
+
+        match serde_json::from_slice::<Self>(&contents) {
+            Ok(v) => v,
+            Err(e) => {
+                warn!("Invalid {}. Starting a new one. {}", history_file.to_string_lossy(), e);
+                Self::default()
+            }
+        }
+    }
+
+    /// Saves the history in the project report folder. Failures are logged and ignored.
+    pub fn save(&self, report_dir: &Path) {
+        let history_file = report_dir.join(OVERVIEW_HISTORY_FILE_NAME);
+        let json = serde_json::to_vec_pretty(self).expect("Cannot serialize the overview history. It's a bug.");
+        match utils::write_atomically(&history_file, &json) {
+            Ok(_) => debug!("Overview history saved into {}", history_file.to_string_lossy()),
+            Err(e) => warn!("Cannot save {} due to {}", history_file.to_string_lossy(), e),
+        }
+    }
+
+    /// Adds the snapshot to the end of the history. A snapshot at the same commit as the latest one replaces it
+    /// to keep repeated runs from flattening the trends. The oldest snapshots over `MAX_OVERVIEW_SNAPSHOTS` are dropped.
+    pub fn add(&mut self, snapshot: OverviewSnapshot) {
+        if self
+            .snapshots
+            .last()
+            .is_some_and(|v| v.commit_sha1 == snapshot.commit_sha1)
+        {
+            self.snapshots.pop();
+        }
+        self.snapshots.push(snapshot);
+
+        if self.snapshots.len() > MAX_OVERVIEW_SNAPSHOTS {
+            let excess = self.snapshots.len() - MAX_OVERVIEW_SNAPSHOTS;
+            self.snapshots.drain(..excess);
+        }
+    }
+
+    /// Compares the latest snapshot with the oldest of the last `runs` snapshots.
+    /// Returns None if there are fewer than 2 snapshots to compare.
+    pub fn trends(&self, runs: usize) -> Option<Trends> {
+        let compared = &self.snapshots[self.snapshots.len().saturating_sub(runs)..];
+        let (first, last) = match compared {
+            [first, .., last] => (first, last),
+            _ => return None,
+        };
+
+        let languages = first
+            .languages
+            .keys()
+            .chain(last.languages.keys())
+            .collect::<BTreeSet<&String>>();
+        let mut languages = languages
+            .into_iter()
+            .map(|language| LanguageTrend {
+                language: language.clone(),
+                loc_delta: loc_delta(
+                    first.languages.get(language).copied().unwrap_or_default(),
+                    last.languages.get(language).copied().unwrap_or_default(),
+                ),
+                share: last.share(language),
+                share_delta: last.share(language) as i64 - first.share(language) as i64,
+            })
+            .filter(|v| v.loc_delta != 0 || v.share_delta != 0)
+            .collect::<Vec<LanguageTrend>>();
+        languages.sort_by(|a, b| {
+            b.share_delta
+                .abs()
+                .cmp(&a.share_delta.abs())
+                .then_with(|| b.loc_delta.abs().cmp(&a.loc_delta.abs()))
+        });
+
+        Some(Trends {
+            runs: compared.len() as u64,
+            since: first.timestamp.clone(),
+            loc_delta: loc_delta(first.loc(), last.loc()),
+            languages,
+        })
+    }
+}
+
+/// Returns `after - before` as a signed number.
+fn loc_delta(before: u64, after: u64) -> i64 {
+    after as i64 - before as i64
+}
+
+#[cfg(test)]
+mod test_trends {
+    use super::{OverviewHistory, OverviewSnapshot, MAX_OVERVIEW_SNAPSHOTS};
+
+    fn snapshot(commit_sha1: &str, languages: &[(&str, u64)]) -> OverviewSnapshot {
+        OverviewSnapshot {
+            timestamp: ["2023-06-01T00:00:00+00:00_", commit_sha1].concat(),
+            commit_sha1: commit_sha1.to_owned(),
+            languages: languages.iter().map(|(k, v)| (k.to_string(), *v)).collect(),
+        }
+    }
+
+    #[test]
+    fn test_trends() {
+        let mut history = OverviewHistory::default();
+        history.add(snapshot("a", &[("Rust", 800), ("TypeScript", 200)]));
+        assert!(history.trends(10).is_none());
+
+        history.add(snapshot("b", &[("Rust", 900), ("TypeScript", 300)]));
+        // the same commit replaces the latest snapshot
+        history.add(snapshot("c", &[("Rust", 800), ("TypeScript", 700), ("Go", 0)]));
+        history.add(snapshot("c", &[("Rust", 800), ("TypeScript", 1200)]));
+        assert_eq!(history.snapshots.len(), 3);
+
+        let trends = history.trends(10).unwrap();
+        assert_eq!(trends.runs, 3);
+        assert_eq!(trends.since, "2023-06-01T00:00:00+00:00_a");
+        assert_eq!(trends.loc_delta, 1000);
+        assert_eq!(trends.languages.len(), 2);
+        // the same change in share is sorted by the change in LoC
+        assert_eq!(trends.languages[0].language, "TypeScript");
+        assert_eq!((trends.languages[0].share, trends.languages[0].share_delta), (60, 40));
+        assert_eq!(trends.languages[0].loc_delta, 1000);
+        assert_eq!((trends.languages[1].share, trends.languages[1].share_delta), (40, -40));
+        assert_eq!(trends.languages[1].loc_delta, 0);
+
+        // only the last 2 runs
+        let trends = history.trends(2).unwrap();
+        assert_eq!(trends.since, "2023-06-01T00:00:00+00:00_b");
+        assert_eq!(trends.loc_delta, 800);
+
+        for i in 0..MAX_OVERVIEW_SNAPSHOTS {
+            history.add(snapshot(&i.to_string(), &[("Rust", i as u64)]));
+        }
+        assert_eq!(history.snapshots.len(), MAX_OVERVIEW_SNAPSHOTS);
+        assert_eq!(history.snapshots[0].commit_sha1, "0");
+    }
+}
@@ -0,0 +1,67 @@
+use super::kwc::KeywordCounterSet;
+use super::tech::Tech;
+use serde::{Deserialize, Serialize};
+
+/// What was trimmed from a submission to keep it within the inbox payload limit. The numbers are of the dropped items,
+/// so the server can tell a complete list from a trimmed one and request more if needed.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, Eq, PartialEq)]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
+pub struct Truncation {
+    /// Tech records with trimmed keywords, refs or pkgs, sorted by language and muncher.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tech: Vec<TechTruncation>,
+    /// Number of the oldest `recent_project_commits` dropped.
+    #[serde(default)]
+    pub recent_project_commits: u64,
+    /// Number of the smallest `per_file_tech` records dropped.
+    #[serde(default)]
+    pub per_file_tech: u64,
+}
+
+/// Number of keywords, refs and pkgs with the lowest counts dropped from a single tech record.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, Eq, PartialEq)]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
+pub struct TechTruncation {
+    pub language: String,
+    pub muncher_name: String,
+    pub keywords: u64,
+    pub refs: u64,
+    pub pkgs: u64,
+}
+
+impl Truncation {
+    /// Returns true if nothing was trimmed.
+    pub fn is_empty(&self) -> bool {
+        self.tech.is_empty() && self.recent_project_commits == 0 && self.per_file_tech == 0
+    }
+}
+
+impl TechTruncation {
+    /// Keeps no more than `max_len` top records in each of keywords, refs and pkgs of `tech`.
+    /// The keyword summaries are trimmed without counting because they are derived from refs and pkgs.
+    /// Returns None if nothing was dropped.
+    pub(crate) fn trim(tech: &mut Tech, max_len: usize) -> Option<Self> {
+        let (keywords, _) = tech.keywords.retain_top(max_len);
+        let (refs, _) = tech.refs.retain_top(max_len);
+        let (pkgs, _) = tech.pkgs.retain_top(max_len);
+
+        if let Some(refs_kw) = tech.refs_kw.as_mut() {
+            refs_kw.retain_top(max_len);
+        }
+        if let Some(pkgs_kw) = tech.pkgs_kw.as_mut() {
+            pkgs_kw.retain_top(max_len);
+        }
+
+        if keywords + refs + pkgs == 0 {
+            return None;
+        }
+
+        Some(Self {
+            language: tech.language.clone(),
+            muncher_name: tech.muncher_name.clone(),
+            keywords,
+            refs,
+            pkgs,
+        })
+    }
+}
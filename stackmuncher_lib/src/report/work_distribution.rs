@@ -0,0 +1,150 @@
+use super::{ProjectReportOverview, Report};
+use chrono::{Datelike, TimeZone, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use tracing::warn;
+
+/// The number of commits a project received in a calendar quarter.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
+pub struct ProjectShare {
+    /// The same as `ProjectReportOverview.project_name`
+    pub project_name: String,
+    /// Number of commits to this project in the quarter
+    pub commits: u64,
+    /// Percentage of all commits made in the quarter, rounded down
+    pub percentage: u64,
+}
+
+/// A breakdown of commits per project for a single calendar quarter, UTC.
+/// Used to show how the work was distributed between projects over time, e.g. `2022: mostly project A, started B`.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
+pub struct WorkDistributionQuarter {
+    /// The year and the quarter, e.g. `2022Q1`
+    pub quarter: String,
+    /// Total number of commits to all projects in the quarter
+    pub commits: u64,
+    /// Projects that received commits in the quarter, the most commits first
+    pub projects: Vec<ProjectShare>,
+}
+
+impl Report {
+    /// Adds the commits from the list of commits in `project_overview` to `work_distribution` and recalculates the percentages.
+    /// Must be called before the overview is merged with another overview of the same project because all overviews
+    /// without owner or GitHub IDs look the same, e.g. in local rollups.
+    /// Only the commits retained in project overviews are counted, which are the last 500 or so per project.
+    pub(crate) fn add_to_work_distribution(&mut self, project_overview: &ProjectReportOverview) {
+        let commits = match &project_overview.commits {
+            Some(v) => v,
+            None => return,
+        };
+
+        // quarter -> project name -> number of commits
+        // BTreeMap keeps quarters in chronological order because of their YYYYQN format
+        let mut quarters: BTreeMap<String, HashMap<String, u64>> = BTreeMap::new();
+        for quarter in self.work_distribution.take().unwrap_or_default() {
+            let projects = quarter
+                .projects
+                .into_iter()
+                .map(|p| (p.project_name, p.commits))
+                .collect::<HashMap<String, u64>>();
+            quarters.insert(quarter.quarter, projects);
+        }
+
+        for commit in commits {
+            let quarter = match commit_quarter(commit) {
+                Some(v) => v,
+                None => {
+                    warn!("Invalid commit timestamp: {}", commit);
+                    continue;
+                }
+            };
+
+            *quarters
+                .entry(quarter)
+                .or_default()
+                .entry(project_overview.project_name.clone())
+                .or_insert(0) += 1;
+        }
+
+        if quarters.is_empty() {
+            return;
+        }
+
+        let work_distribution = quarters
+            .into_iter()
+            .map(|(quarter, projects)| {
+                let commits = projects.values().sum::<u64>();
+                let mut projects = projects
+                    .into_iter()
+                    .map(|(project_name, project_commits)| ProjectShare {
+                        project_name,
+                        commits: project_commits,
+                        percentage: project_commits * 100 / commits.max(1),
+                    })
+                    .collect::<Vec<ProjectShare>>();
+                // the name is a tie-breaker to keep the order stable between runs
+                projects.sort_unstable_by(|a, b| b.commits.cmp(&a.commits).then(a.project_name.cmp(&b.project_name)));
+
+                WorkDistributionQuarter {
+                    quarter,
+                    commits,
+                    projects,
+                }
+            })
+            .collect::<Vec<WorkDistributionQuarter>>();
+
+        self.work_distribution = Some(work_distribution);
+    }
+}
+
+/// Returns the quarter of a commit in `e29d17e6_1627380297` format as `2021Q3`, UTC.
+/// Returns None if there is no valid timestamp.
+fn commit_quarter(commit: &str) -> Option<String> {
+    let (_, ts) = commit.split_once('_')?;
+    let ts = Utc.timestamp_opt(ts.parse::<i64>().ok()?, 0).single()?;
+    Some(format!("{}Q{}", ts.year(), (ts.month() - 1) / 3 + 1))
+}
+
+#[cfg(test)]
+mod test_work_distribution {
+    use super::commit_quarter;
+    use crate::report::{ProjectReportOverview, Report};
+    use serde_json::json;
+
+    /// Returns a minimal project overview with the given commits.
+    fn overview(project_name: &str, commits: &[&str]) -> ProjectReportOverview {
+        serde_json::from_value(json!({"project_name": project_name, "tech": [], "commits": commits})).unwrap()
+    }
+
+    #[test]
+    fn test_add_to_work_distribution() {
+        assert_eq!(commit_quarter("e29d17e6_1627380297").unwrap(), "2021Q3");
+        assert_eq!(commit_quarter("e29d17e6_1609459200").unwrap(), "2021Q1");
+        assert!(commit_quarter("e29d17e6").is_none());
+
+        let mut report = Report::new();
+        // 2 commits in 2021Q1, 1 in 2021Q3
+        report.add_to_work_distribution(&overview(
+            "A",
+            &["00000001_1609459200", "00000002_1612137600", "00000003_1627380297"],
+        ));
+        // 1 commit in 2021Q3 and an invalid one
+        report.add_to_work_distribution(&overview("B", &["00000004_1627380297", "00000005"]));
+
+        let work_distribution = report.work_distribution.unwrap();
+        assert_eq!(work_distribution.len(), 2);
+
+        assert_eq!(work_distribution[0].quarter, "2021Q1");
+        assert_eq!(work_distribution[0].commits, 2);
+        assert_eq!(work_distribution[0].projects.len(), 1);
+        assert_eq!(work_distribution[0].projects[0].percentage, 100);
+
+        assert_eq!(work_distribution[1].quarter, "2021Q3");
+        assert_eq!(work_distribution[1].commits, 2);
+        assert_eq!(work_distribution[1].projects[0].project_name, "A");
+        assert_eq!(work_distribution[1].projects[0].percentage, 50);
+        assert_eq!(work_distribution[1].projects[1].project_name, "B");
+    }
+}
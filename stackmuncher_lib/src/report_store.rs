@@ -0,0 +1,70 @@
+use crate::report::Report;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+/// Where `Analyzer` keeps the cached project and contributor reports between runs. Set with `report_store` in config.json.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum ReportStore {
+    /// A JSON file per report in the project reports folder.
+    #[default]
+    Json,
+    /// A single SQLite database in the project reports folder with a row per file, commit and contributor.
+    /// Requires `sqlite-store` feature. The project and combined reports are still exported as JSON because other
+    /// commands and submissions read them from the files.
+    Sqlite,
+}
+
+impl ReportStore {
+    /// Returns an error with a user-friendly message if this build of the app does not support the store.
+    pub fn validate(&self) -> Result<(), String> {
+        if *self == Self::Sqlite && cfg!(not(feature = "sqlite-store")) {
+            return Err(
+                "this build of stackmuncher has no SQLite support, rebuild it with `--features sqlite-store`"
+                    .to_owned(),
+            );
+        }
+        Ok(())
+    }
+
+    /// Loads a report saved with `save()` under the same file name. Returns None if there is no such report or it
+    /// could not be loaded, in which case the project is processed in full. The SQLite store falls back on the JSON file,
+    /// so switching from `json` to `sqlite` does not reprocess the project.
+    pub fn load(&self, report_file: &Path) -> Option<Report> {
+        match self {
+            Self::Json => Report::from_disk(report_file),
+            Self::Sqlite => {
+                #[cfg(feature = "sqlite-store")]
+                if let Some(v) = crate::report_store_sqlite::load(report_file) {
+                    return Some(v);
+                }
+                Report::from_disk(report_file)
+            }
+        }
+    }
+
+    /// Saves the report for reuse by the next run. The SQLite store also saves it as a JSON file if `export_json` is set.
+    /// Otherwise, the JSON copy left from the `json` store or an earlier failure is removed, so that `load()` and
+    /// switching back to the `json` store never pick up an outdated report. The report is saved as JSON if it
+    /// could not be saved in SQLite. JSON files are prettified if `make_pretty` is set.
+    pub fn save(&self, report: &Report, report_file: &PathBuf, make_pretty: bool, export_json: bool) {
+        match self {
+            Self::Json => report.save_as_local_file(report_file, make_pretty),
+            #[cfg(feature = "sqlite-store")]
+            Self::Sqlite => {
+                let saved = crate::report_store_sqlite::save(report, report_file).is_ok();
+                if export_json || !saved {
+                    report.save_as_local_file(report_file, make_pretty);
+                } else {
+                    Report::remove_local_file(report_file);
+                }
+            }
+            // rejected by validate(), but the report should not be lost if the check was skipped
+            #[cfg(not(feature = "sqlite-store"))]
+            Self::Sqlite => {
+                let _ = export_json;
+                report.save_as_local_file(report_file, make_pretty);
+            }
+        }
+    }
+}
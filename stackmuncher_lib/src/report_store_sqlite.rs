@@ -0,0 +1,331 @@
+//! The SQLite backend of `ReportStore`. All reports of a project are kept in a single database in the project reports
+//! folder. Per-file tech records, recent commits and contributors have a row each, so they can be queried with any
+//! SQLite client. The rest of the report is kept as a JSON document.
+use crate::config::Config;
+use crate::report::{Report, Tech};
+use rusqlite::{params, Connection, OpenFlags, OptionalExtension};
+use serde_json::Value;
+use std::collections::HashSet;
+use std::path::Path;
+use tracing::{debug, error, info};
+
+/// The name of the database in the project reports folder.
+pub(crate) const DATABASE_FILE_NAME: &str = "reports.sqlite";
+
+/// Reports are identified by the name of their JSON file without the extension, e.g. `project_report`.
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS reports (
+    name TEXT PRIMARY KEY,
+    body TEXT NOT NULL
+);
+CREATE TABLE IF NOT EXISTS per_file_tech (
+    report TEXT NOT NULL,
+    history INTEGER NOT NULL,
+    file_name TEXT,
+    language TEXT NOT NULL,
+    code_lines INTEGER NOT NULL,
+    body TEXT NOT NULL
+);
+CREATE INDEX IF NOT EXISTS per_file_tech_report ON per_file_tech (report);
+CREATE TABLE IF NOT EXISTS commits (
+    report TEXT NOT NULL,
+    position INTEGER NOT NULL,
+    commit_id TEXT NOT NULL
+);
+CREATE INDEX IF NOT EXISTS commits_report ON commits (report);
+CREATE TABLE IF NOT EXISTS contributors (
+    report TEXT NOT NULL,
+    git_id TEXT NOT NULL,
+    commit_count INTEGER NOT NULL,
+    last_commit_date TEXT NOT NULL,
+    body TEXT NOT NULL
+);
+CREATE INDEX IF NOT EXISTS contributors_report ON contributors (report);
+";
+
+/// Saves the report in the database next to `report_file`, replacing the previous version. Failures are logged and only
+/// cost reprocessing of the project next time.
+pub(crate) fn save(report: &Report, report_file: &Path) -> Result<(), ()> {
+    match try_save(report, report_file) {
+        Ok(_) => {
+            info!("Report saved into {} in SQLite", report_name(report_file));
+            Ok(())
+        }
+        Err(e) => {
+            error!("Cannot save report {} in SQLite due to {}", report_name(report_file), e);
+            Err(())
+        }
+    }
+}
+
+/// Loads the report saved with `save()`. Returns None if it is not in the database or cannot be loaded.
+pub(crate) fn load(report_file: &Path) -> Option<Report> {
+    match try_load(report_file) {
+        Ok(v) => v,
+        Err(e) => {
+            error!("Cannot load report {} from SQLite due to {}", report_name(report_file), e);
+            None
+        }
+    }
+}
+
+/// Checks the integrity of the database in `report_dir` and loads every report in it the same way as `load()`.
+/// Returns the project report if it is in the database and None if there is no database.
+pub(crate) fn verify(report_dir: &Path) -> Result<Option<Report>, String> {
+    let database_file = report_dir.join(DATABASE_FILE_NAME);
+    if !database_file.exists() {
+        return Ok(None);
+    }
+
+    let conn =
+        Connection::open_with_flags(&database_file, OpenFlags::SQLITE_OPEN_READ_ONLY).map_err(|e| e.to_string())?;
+    let integrity = conn
+        .query_row("PRAGMA integrity_check", params![], |row| row.get::<_, String>(0))
+        .map_err(|e| e.to_string())?;
+    if integrity != "ok" {
+        return Err(integrity);
+    }
+
+    let mut stmt = conn.prepare("SELECT name FROM reports").map_err(|e| e.to_string())?;
+    let names = stmt
+        .query_map(params![], |row| row.get::<_, String>(0))
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<String>, _>>()
+        .map_err(|e| e.to_string())?;
+
+    let mut project_report = None;
+    for name in names {
+        // reports in a format that cannot be upgraded are not loaded and are replaced on the next run
+        let report = try_load(&report_dir.join([&name, Config::REPORT_FILE_EXTENSION].concat()))
+            .map_err(|e| format!("report {}: {}", name, e))?;
+        if name == Config::PROJECT_REPORT_FILE_NAME {
+            project_report = report;
+        }
+    }
+
+    Ok(project_report)
+}
+
+fn try_save(report: &Report, report_file: &Path) -> Result<(), String> {
+    let name = report_name(report_file);
+    let mut conn = open(report_file)?;
+
+    // the rows are stored separately, unless the list is empty to tell apart `None` and `Some(vec![])`
+    let mut body = serde_json::to_value(report).map_err(|e| e.to_string())?;
+    if let Value::Object(fields) = &mut body {
+        fields.remove("per_file_tech");
+        if report.contributors.as_ref().is_some_and(|v| !v.is_empty()) {
+            fields.remove("contributors");
+        }
+        if report.recent_project_commits.as_ref().is_some_and(|v| !v.is_empty()) {
+            fields.remove("recent_project_commits");
+        }
+    }
+    let body = serde_json::to_string(&body).map_err(|e| e.to_string())?;
+
+    let tx = conn.transaction().map_err(|e| e.to_string())?;
+    for table in ["per_file_tech", "commits", "contributors"] {
+        tx.execute(&format!("DELETE FROM {} WHERE report = ?1", table), params![name])
+            .map_err(|e| e.to_string())?;
+    }
+    tx.execute("INSERT OR REPLACE INTO reports (name, body) VALUES (?1, ?2)", params![name, body])
+        .map_err(|e| e.to_string())?;
+
+    {
+        let mut stmt = tx
+            .prepare(
+                "INSERT INTO per_file_tech (report, history, file_name, language, code_lines, body) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            )
+            .map_err(|e| e.to_string())?;
+        let techs = report
+            .per_file_tech
+            .iter()
+            .map(|tech| (false, tech))
+            .chain(report.per_file_tech_history.iter().map(|tech| (true, tech)));
+        for (history, tech) in techs {
+            let tech_body = serde_json::to_string(tech).map_err(|e| e.to_string())?;
+            stmt.execute(params![
+                name,
+                history,
+                tech.file_name,
+                tech.language,
+                tech.code_lines as i64,
+                tech_body
+            ])
+            .map_err(|e| e.to_string())?;
+        }
+
+        let mut stmt = tx
+            .prepare("INSERT INTO commits (report, position, commit_id) VALUES (?1, ?2, ?3)")
+            .map_err(|e| e.to_string())?;
+        for (position, commit_id) in report.recent_project_commits.iter().flatten().enumerate() {
+            stmt.execute(params![name, position as i64, commit_id])
+                .map_err(|e| e.to_string())?;
+        }
+
+        let mut stmt = tx
+            .prepare(
+                "INSERT INTO contributors (report, git_id, commit_count, last_commit_date, body) VALUES (?1, ?2, ?3, ?4, ?5)",
+            )
+            .map_err(|e| e.to_string())?;
+        for contributor in report.contributors.iter().flatten() {
+            let contributor_body = serde_json::to_string(contributor).map_err(|e| e.to_string())?;
+            stmt.execute(params![
+                name,
+                contributor.git_id,
+                contributor.commit_count as i64,
+                contributor.last_commit_date,
+                contributor_body
+            ])
+            .map_err(|e| e.to_string())?;
+        }
+    }
+
+    tx.commit().map_err(|e| e.to_string())
+}
+
+fn try_load(report_file: &Path) -> Result<Option<Report>, String> {
+    // the database is not created until there is something to save
+    if !database_file(report_file).exists() {
+        return Ok(None);
+    }
+
+    let name = report_name(report_file);
+    let conn = open(report_file)?;
+
+    let body = conn
+        .query_row("SELECT body FROM reports WHERE name = ?1", params![name], |row| row.get::<_, String>(0))
+        .optional()
+        .map_err(|e| e.to_string())?;
+    let mut body = match body {
+        Some(v) => serde_json::from_str::<Value>(&v).map_err(|e| e.to_string())?,
+        None => {
+            debug!("No report {} in SQLite", name);
+            return Ok(None);
+        }
+    };
+
+    // all rows are added back as JSON so that older formats are upgraded along with the rest of the report
+    let per_file_tech = select_json(&conn, "SELECT body FROM per_file_tech WHERE report = ?1 AND history = 0", &name)?;
+    let commits = select_strings(&conn, "SELECT commit_id FROM commits WHERE report = ?1 ORDER BY position", &name)?;
+    let contributors = select_json(&conn, "SELECT body FROM contributors WHERE report = ?1 ORDER BY rowid", &name)?;
+    if let Value::Object(fields) = &mut body {
+        fields.insert("per_file_tech".to_owned(), Value::Array(per_file_tech));
+        if !commits.is_empty() {
+            fields.insert(
+                "recent_project_commits".to_owned(),
+                Value::Array(commits.into_iter().map(Value::String).collect()),
+            );
+        }
+        if !contributors.is_empty() {
+            fields.insert("contributors".to_owned(), Value::Array(contributors));
+        }
+    }
+
+    let mut report = match Report::from_json_value(body, &name) {
+        Some(v) => v,
+        None => return Ok(None),
+    };
+
+    // history records are not upgraded, same as in `.history.json` files
+    report.per_file_tech_history =
+        select_json(&conn, "SELECT body FROM per_file_tech WHERE report = ?1 AND history = 1", &name)?
+            .into_iter()
+            .filter_map(|v| serde_json::from_value::<Tech>(v).ok())
+            .collect::<HashSet<Tech>>();
+
+    info!("Loaded report {} from SQLite", name);
+    Ok(Some(report))
+}
+
+/// Opens or creates the database next to `report_file` and makes sure it has all the tables.
+fn open(report_file: &Path) -> Result<Connection, String> {
+    let conn = Connection::open(database_file(report_file)).map_err(|e| e.to_string())?;
+    conn.execute_batch(SCHEMA).map_err(|e| e.to_string())?;
+    Ok(conn)
+}
+
+fn database_file(report_file: &Path) -> std::path::PathBuf {
+    report_file.with_file_name(DATABASE_FILE_NAME)
+}
+
+/// Returns the name of the report file without the extension, e.g. `project_report` for `.../project_report.json`.
+fn report_name(report_file: &Path) -> String {
+    report_file
+        .file_stem()
+        .map(|v| v.to_string_lossy().to_string())
+        .unwrap_or_default()
+}
+
+/// Returns the first column of all rows of the query for the report as a list of strings.
+fn select_strings(conn: &Connection, query: &str, name: &str) -> Result<Vec<String>, String> {
+    let mut stmt = conn.prepare(query).map_err(|e| e.to_string())?;
+    let rows = stmt
+        .query_map(params![name], |row| row.get::<_, String>(0))
+        .map_err(|e| e.to_string())?;
+    rows.collect::<Result<Vec<String>, _>>().map_err(|e| e.to_string())
+}
+
+/// Same as `select_strings`, but parses every value as JSON.
+fn select_json(conn: &Connection, query: &str, name: &str) -> Result<Vec<Value>, String> {
+    select_strings(conn, query, name)?
+        .iter()
+        .map(|v| serde_json::from_str::<Value>(v).map_err(|e| e.to_string()))
+        .collect()
+}
+
+#[cfg(test)]
+mod test_report_store_sqlite {
+    use super::{load, save};
+    use crate::contributor::Contributor;
+    use crate::report::{Report, Tech};
+    use crate::report_store::ReportStore;
+
+    #[test]
+    fn test_save_and_load() {
+        let report_dir = std::env::temp_dir().join(["stm_sqlite_", &uuid::Uuid::new_v4().to_string()].concat());
+        std::fs::create_dir_all(&report_dir).unwrap();
+        let report_file = report_dir.join("project_report.json");
+        assert!(load(&report_file).is_none());
+
+        let tech = Tech::test("Rust", "rust").with_file("src/main.rs").with_loc(10);
+        let contributor: Contributor = serde_json::from_str(
+            r#"{"git_id": "a@example.com", "name_email_pairs": [], "last_commit_sha1": "", "last_commit_epoch": 0,
+            "last_commit_date": "", "touched_files": []}"#,
+        )
+        .unwrap();
+        let mut report = Report::new();
+        report.per_file_tech.insert(tech.clone());
+        report.per_file_tech_history.insert(tech);
+        report.recent_project_commits = Some(vec!["e29d17e6_1627380297".to_owned(), "a1b2c3d4_1627380200".to_owned()]);
+        report.contributors = Some(vec![contributor]);
+
+        save(&report, &report_file).unwrap();
+        // saving again replaces the rows
+        save(&report, &report_file).unwrap();
+
+        let loaded = load(&report_file).unwrap();
+        assert_eq!(loaded.report_id, report.report_id);
+        assert_eq!(loaded.per_file_tech.len(), 1);
+        assert_eq!(loaded.per_file_tech_history.len(), 1);
+        assert_eq!(loaded.recent_project_commits, report.recent_project_commits);
+        assert_eq!(loaded.contributors.unwrap()[0].git_id, "a@example.com");
+
+        // other reports in the same database are separate
+        assert!(load(&report_dir.join("combined_report.json")).is_none());
+
+        // a JSON copy left from the `json` store is outdated once the report is saved in SQLite without export
+        let contributor_file = report_dir.join("contributor_abc.json");
+        let mut outdated = report.clone();
+        outdated.report_id = "outdated".to_owned();
+        ReportStore::Json.save(&outdated, &contributor_file, false, false);
+        assert!(report_dir.join("contributor_abc.history.json").exists());
+        ReportStore::Sqlite.save(&report, &contributor_file, false, false);
+        assert!(!contributor_file.exists());
+        assert!(!report_dir.join("contributor_abc.history.json").exists());
+        assert_eq!(ReportStore::Json.load(&contributor_file).map(|v| v.report_id), None);
+        assert_eq!(ReportStore::Sqlite.load(&contributor_file).unwrap().report_id, report.report_id);
+
+        std::fs::remove_dir_all(&report_dir).unwrap();
+    }
+}
@@ -0,0 +1,227 @@
+//! Keeps track of files that fail to munch or take too long, so that they are skipped on future runs instead of
+//! failing or slowing down every run. The list is stored in the project report folder and is retried after
+//! the muncher for the file changes.
+use crate::utils;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::path::Path;
+use std::time::Duration;
+use tracing::{debug, info, warn};
+
+/// The name of the file in the project report folder with the list of problem files.
+pub const SKIP_LIST_FILE_NAME: &str = "skip_list.json";
+
+/// A file that took longer than this to munch is skipped on future runs.
+pub const TIME_BUDGET: Duration = Duration::from_secs(30);
+
+/// A file that failed to munch this many times in a row is skipped on future runs.
+pub const MAX_FAILURES: u32 = 3;
+
+/// Files that failed or took too long to munch in the current or previous runs.
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq)]
+pub struct SkipList {
+    #[serde(default)]
+    pub files: Vec<ProblemFile>,
+    /// Set to true if the list has to be saved.
+    #[serde(skip)]
+    changed: bool,
+}
+
+/// A file that failed or took too long to munch. Listed in the skip list and in `problem_files` section of the project report.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
+pub struct ProblemFile {
+    pub file_name: String,
+    /// The number of failures in a row. Reset after a successful run.
+    #[serde(default)]
+    pub failures: u32,
+    /// The reason for the last failure.
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub error: Option<String>,
+    /// How long it took to munch the file if it was over `TIME_BUDGET` or timed out.
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub duration_ms: Option<u64>,
+    /// The muncher the file failed with. The file is retried if the muncher changes.
+    pub muncher_hash: u64,
+    /// The file is skipped if true.
+    pub skipped: bool,
+}
+
+impl SkipList {
+    /// Loads the list from the project report folder. Returns an empty list if there is none or it cannot be read.
+    pub fn from_disk(report_dir: &Path) -> Self {
+        let skip_list_file = report_dir.join(SKIP_LIST_FILE_NAME);
+        let contents = match std::fs::read(&skip_list_file) {
+            Ok(v) => v,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Self::default(),
+            Err(e) => {
+                warn!("Cannot read {} due to {}", skip_list_file.to_string_lossy(), e);
+                return Self::default();
+            }
+        };
+
+        match serde_json::from_slice::<Self>(&contents) {
+            Ok(v) => {
+                debug!("Loaded {} problem files from {}", v.files.len(), skip_list_file.to_string_lossy());
+                v
+            }
+            Err(e) => {
+                warn!("Cannot parse {} due to {}. It will be reset.", skip_list_file.to_string_lossy(), e);
+                Self::default()
+            }
+        }
+    }
+
+    /// Saves the list in the project report folder if it changed since it was loaded. The file is removed if the list is empty.
+    pub fn save(&self, report_dir: &Path) {
+        if !self.changed {
+            return;
+        }
+
+        let skip_list_file = report_dir.join(SKIP_LIST_FILE_NAME);
+        if self.files.is_empty() {
+            let _ = std::fs::remove_file(&skip_list_file);
+            return;
+        }
+
+        let json = serde_json::to_vec_pretty(self).expect("Cannot serialize the skip list. It's a bug.");
+        match utils::write_atomically(&skip_list_file, &json) {
+            Ok(_) => debug!("Skip list saved into {}", skip_list_file.to_string_lossy()),
+            Err(e) => warn!("Cannot save {} due to {}", skip_list_file.to_string_lossy(), e),
+        }
+    }
+
+    /// Returns true if the file should not be munched with the muncher.
+    pub fn is_skipped(&self, file_name: &str, muncher_hash: u64) -> bool {
+        self.files
+            .iter()
+            .any(|v| v.skipped && v.file_name == file_name && v.muncher_hash == muncher_hash)
+    }
+
+    /// Records a failure to munch the file. The file is skipped after `MAX_FAILURES` in a row.
+    pub fn record_failure(&mut self, file_name: &str, muncher_hash: u64, error: String) {
+        let problem_file = self.get_or_insert(file_name, muncher_hash);
+        problem_file.failures += 1;
+        problem_file.error = Some(error);
+        if problem_file.failures >= MAX_FAILURES {
+            info!("{} failed {} times and will be skipped", file_name, problem_file.failures);
+            problem_file.skipped = true;
+        }
+        self.changed = true;
+    }
+
+    /// Records how long it took to munch the file. The file is skipped if it was over `TIME_BUDGET`.
+    /// A file munched within the budget is removed from the list because its failures are no longer in a row.
+    pub fn record_success(&mut self, file_name: &str, muncher_hash: u64, duration: Duration) {
+        if duration > TIME_BUDGET {
+            info!("{} took {}s and will be skipped", file_name, duration.as_secs());
+            let problem_file = self.get_or_insert(file_name, muncher_hash);
+            problem_file.duration_ms = Some(duration.as_millis() as u64);
+            problem_file.skipped = true;
+            self.changed = true;
+        } else if let Some(idx) = self.files.iter().position(|v| v.file_name == file_name) {
+            self.files.remove(idx);
+            self.changed = true;
+        }
+    }
+
+    /// Records a file that was stopped after `MunchLimits.timeout_ms`. It is skipped straight away because
+    /// it would time out again with the same muncher.
+    pub fn record_timeout(&mut self, file_name: &str, muncher_hash: u64, error: String, duration: Duration) {
+        let problem_file = self.get_or_insert(file_name, muncher_hash);
+        problem_file.failures += 1;
+        problem_file.error = Some(error);
+        problem_file.duration_ms = Some(duration.as_millis() as u64);
+        problem_file.skipped = true;
+        self.changed = true;
+    }
+
+    /// Returns the entry for the file, resetting it if the muncher changed since it was added.
+    fn get_or_insert(&mut self, file_name: &str, muncher_hash: u64) -> &mut ProblemFile {
+        let idx = match self.files.iter().position(|v| v.file_name == file_name) {
+            Some(idx) => idx,
+            None => {
+                self.files.push(ProblemFile {
+                    file_name: file_name.to_owned(),
+                    failures: 0,
+                    error: None,
+                    duration_ms: None,
+                    muncher_hash,
+                    skipped: false,
+                });
+                self.files.len() - 1
+            }
+        };
+
+        let problem_file = &mut self.files[idx];
+        if problem_file.muncher_hash != muncher_hash {
+            *problem_file = ProblemFile {
+                file_name: file_name.to_owned(),
+                failures: 0,
+                error: None,
+                duration_ms: None,
+                muncher_hash,
+                skipped: false,
+            };
+        }
+
+        problem_file
+    }
+
+    /// Returns the problem files that are still in the tree, sorted by name, or None if there are none.
+    pub fn problem_files(&self, tree_files: Option<&HashSet<String>>) -> Option<Vec<ProblemFile>> {
+        let mut problem_files = self
+            .files
+            .iter()
+            .filter(|v| {
+                tree_files
+                    .map(|tree_files| tree_files.contains(&v.file_name))
+                    .unwrap_or(true)
+            })
+            .cloned()
+            .collect::<Vec<ProblemFile>>();
+        if problem_files.is_empty() {
+            return None;
+        }
+        problem_files.sort_by(|x, y| x.file_name.cmp(&y.file_name));
+
+        Some(problem_files)
+    }
+}
+
+#[cfg(test)]
+mod test_skip_list {
+    use super::{SkipList, MAX_FAILURES, TIME_BUDGET};
+    use std::time::Duration;
+
+    #[test]
+    fn test_skip_list() {
+        let mut skip_list = SkipList::default();
+        for _ in 1..MAX_FAILURES {
+            skip_list.record_failure("src/bad.rs", 1, "cannot read the blob".to_owned());
+        }
+        assert!(!skip_list.is_skipped("src/bad.rs", 1));
+        skip_list.record_failure("src/bad.rs", 1, "cannot read the blob".to_owned());
+        assert!(skip_list.is_skipped("src/bad.rs", 1));
+        // a different muncher gets another chance
+        assert!(!skip_list.is_skipped("src/bad.rs", 2));
+        skip_list.record_failure("src/bad.rs", 2, "cannot read the blob".to_owned());
+        assert_eq!(skip_list.files[0].failures, 1);
+
+        // a success resets the failures and a slow success skips the file
+        skip_list.record_success("src/bad.rs", 2, Duration::from_millis(10));
+        assert!(skip_list.files.is_empty());
+        skip_list.record_success("src/slow.rs", 1, TIME_BUDGET + Duration::from_secs(1));
+        assert!(skip_list.is_skipped("src/slow.rs", 1));
+        skip_list.record_timeout("src/min.js", 1, "timed out".to_owned(), Duration::from_secs(5));
+        assert!(skip_list.is_skipped("src/min.js", 1));
+
+        let report_dir = std::env::temp_dir().join(format!("stm_skip_list_test_{}", std::process::id()));
+        std::fs::create_dir_all(&report_dir).unwrap();
+        skip_list.save(&report_dir);
+        let loaded = SkipList::from_disk(&report_dir);
+        assert_eq!(loaded.files, skip_list.files);
+        assert_eq!(loaded.problem_files(None).unwrap()[0].file_name, "src/min.js");
+        std::fs::remove_dir_all(&report_dir).unwrap();
+    }
+}
@@ -1,4 +1,57 @@
 use sha1::{Digest, Sha1};
+use std::path::Path;
+
+/// The extension of temp files written by `write_atomically`. Files with this extension are work in progress
+/// and should be ignored by anything that lists report folders.
+pub const TEMP_FILE_EXTENSION: &str = ".tmp";
+
+/// Returns the name of this machine with characters that are not safe in file names replaced with `_`.
+/// E.g. `build-server-01`
+pub fn host_name() -> String {
+    let host_name = gethostname::gethostname()
+        .to_string_lossy()
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' || c == '.' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect::<String>();
+
+    if host_name.is_empty() {
+        "unknown".to_owned()
+    } else {
+        host_name
+    }
+}
+
+/// Writes the contents into a temp file next to `file_name` and then renames it into `file_name`.
+/// Other processes never see a partially written file, which matters for report folders shared over NFS or SMB.
+/// The temp file name is unique per host and process, e.g. `.project_report.json.laptop.1234.tmp`.
+pub fn write_atomically(file_name: &Path, contents: &[u8]) -> std::io::Result<()> {
+    let temp_file_name = file_name.with_file_name(
+        [
+            ".",
+            &file_name.file_name().unwrap_or_default().to_string_lossy(),
+            ".",
+            &host_name(),
+            ".",
+            &std::process::id().to_string(),
+            TEMP_FILE_EXTENSION,
+        ]
+        .concat(),
+    );
+
+    std::fs::write(&temp_file_name, contents)?;
+    if let Err(e) = std::fs::rename(&temp_file_name, file_name) {
+        let _ = std::fs::remove_file(&temp_file_name);
+        return Err(e);
+    }
+
+    Ok(())
+}
 
 /// Returns a string representation of a hash hex using SHA1.
 /// E.g. `6bdf08b30f8cc1173729d8559933bea5c024c25`
@@ -10,11 +63,13 @@ pub fn hash_str_sha1(string: &str) -> String {
 
 /// Returns a string representation of a hash hex using SHA1.
 /// E.g. `6bdf08b30f8cc1173729d8559933bea5c024c25`
+/// Trailing CR and LF are removed from every string, so lists taken from git output with Windows or Unix line endings
+/// hash the same. Strings without them hash the same as before.
 pub fn hash_vec_sha1(vec_of_strings: Vec<String>) -> String {
     let mut hasher = Sha1::new();
 
     for string in vec_of_strings {
-        hasher.update(string);
+        hasher.update(string.trim_end_matches(['\r', '\n']));
     }
 
     format!("{:x}", hasher.finalize())
@@ -0,0 +1,191 @@
+use crate::config::Config;
+use crate::git::{self, GitLogEntry, ListOfBlobs};
+use crate::hg::Mercurial;
+use async_trait::async_trait;
+use regex::Regex;
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+use tracing::debug;
+
+/// The type of the version control system the project is kept in.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum VcsKind {
+    Git,
+    Mercurial,
+}
+
+/// The operations on the repository needed to build reports. Implemented by shelling out to the VCS command line tool.
+/// The output is normalized to Git terms, e.g. a Mercurial changeset is a `GitLogEntry` and a file revision is a `GitBlob`.
+#[async_trait]
+pub trait Vcs: Send + Sync {
+    /// Returns the type of the VCS behind this implementation.
+    fn kind(&self) -> VcsKind;
+
+    /// Returns the list of commits, latest first, with the files they touched. Merge commits and ignored files are excluded.
+    /// Only the history reachable from `rev` is listed, HEAD if None.
+    async fn get_log(
+        &self,
+        repo_dir: &Path,
+        contributor_git_identity: Option<&String>,
+        rev: Option<&str>,
+        ignore_paths: &[Regex],
+    ) -> Result<Vec<GitLogEntry>, ()>;
+
+    /// Returns the list of files in the tree at the given commit or at the current HEAD if None. Ignored files are excluded.
+    async fn get_all_tree_files(
+        &self,
+        repo_dir: &Path,
+        commit_sha1: Option<String>,
+        ignore_paths: &[Regex],
+    ) -> Result<HashSet<String>, ()>;
+
+    /// Sets the blob ID of the requested `blobs` at the point of the given commit or HEAD if None.
+    /// Blobs that are not in the tree are dropped.
+    async fn populate_blob_sha1(
+        &self,
+        repo_dir: &Path,
+        blobs: ListOfBlobs,
+        commit_sha1: Option<String>,
+        ignore_case: bool,
+    ) -> Result<ListOfBlobs, ()>;
+
+    /// Returns the contents of a file revision identified either by its blob ID or the file name at the commit, depending on the VCS.
+    async fn get_blob_contents(
+        &self,
+        repo_dir: &Path,
+        file_name: &str,
+        blob_sha1: &str,
+        commit_sha1: &str,
+    ) -> Result<Vec<u8>, ()>;
+
+    /// Returns the contents of many file revisions at once by their blob IDs. Blobs that cannot be read in a batch
+    /// are not in the list and have to be read with `get_blob_contents`.
+    async fn get_blobs_contents(&self, repo_dir: &Path, blob_sha1s: &[&str]) -> HashMap<String, Vec<u8>>;
+
+    /// Returns a list of commit identities of the local user, lower case, email preferred.
+    async fn get_local_identities(&self, repo_dir: &Path) -> Result<Vec<String>, ()>;
+
+    /// Returns true if the repo is on a case-insensitive file system and the same file may appear under different cases.
+    async fn is_ignore_case(&self, repo_dir: &Path) -> bool;
+
+    /// Returns the ID of the commit `rev` points at, e.g. a branch or a tag, or the commit the working directory is at
+    /// if None, e.g. HEAD in Git. Fails if there are no commits yet or no such commit.
+    async fn get_head_commit(&self, repo_dir: &Path, rev: Option<&str>) -> Result<String, ()>;
+
+    /// Returns true if the repo has no commits yet, e.g. right after `git init`. False for a damaged repo whose
+    /// HEAD cannot be resolved or on any other error.
+    async fn is_empty_repo(&self, repo_dir: &Path) -> bool;
+
+    /// Returns IDs of the oldest commits of a partially cloned history, e.g. a Git clone with `--depth`.
+    /// Empty if the full history is present.
+    async fn get_shallow_commits(&self, repo_dir: &Path) -> HashSet<String>;
+
+    /// Returns the filter of a partial clone that has some of the file contents missing locally, e.g. `blob:none`.
+    /// None if all the contents are present.
+    async fn get_partial_clone_filter(&self, repo_dir: &Path) -> Option<String>;
+
+    /// Fetches the blobs of the tree at `rev` or HEAD that are missing from a partial clone in one request, so that
+    /// they are not fetched one by one when their contents are read. Does nothing if all the contents are present.
+    async fn prefetch_missing_blobs(&self, repo_dir: &Path, rev: Option<&str>, blob_sha1s: &[&str]);
+
+    /// Returns the files at HEAD that are left out of the working directory by a sparse checkout.
+    /// None if it is not a sparse checkout.
+    async fn get_sparse_checkout_exclusions(&self, repo_dir: &Path) -> Option<HashSet<String>>;
+}
+
+/// Git implementation of `Vcs`. See `git` module for details.
+pub struct Git;
+
+#[async_trait]
+impl Vcs for Git {
+    fn kind(&self) -> VcsKind {
+        VcsKind::Git
+    }
+
+    async fn get_log(
+        &self,
+        repo_dir: &Path,
+        contributor_git_identity: Option<&String>,
+        rev: Option<&str>,
+        ignore_paths: &[Regex],
+    ) -> Result<Vec<GitLogEntry>, ()> {
+        git::get_log(repo_dir, contributor_git_identity, rev, ignore_paths).await
+    }
+
+    async fn get_all_tree_files(
+        &self,
+        repo_dir: &Path,
+        commit_sha1: Option<String>,
+        ignore_paths: &[Regex],
+    ) -> Result<HashSet<String>, ()> {
+        git::get_all_tree_files(repo_dir, commit_sha1, ignore_paths).await
+    }
+
+    async fn populate_blob_sha1(
+        &self,
+        repo_dir: &Path,
+        blobs: ListOfBlobs,
+        commit_sha1: Option<String>,
+        ignore_case: bool,
+    ) -> Result<ListOfBlobs, ()> {
+        git::populate_blob_sha1(repo_dir, blobs, commit_sha1, ignore_case).await
+    }
+
+    async fn get_blob_contents(
+        &self,
+        repo_dir: &Path,
+        _file_name: &str,
+        blob_sha1: &str,
+        _commit_sha1: &str,
+    ) -> Result<Vec<u8>, ()> {
+        git::get_blob_contents(repo_dir, &blob_sha1.to_owned()).await
+    }
+
+    async fn get_blobs_contents(&self, repo_dir: &Path, blob_sha1s: &[&str]) -> HashMap<String, Vec<u8>> {
+        git::get_blobs_contents(repo_dir, blob_sha1s).await.unwrap_or_default()
+    }
+
+    async fn get_local_identities(&self, repo_dir: &Path) -> Result<Vec<String>, ()> {
+        git::get_local_identities(repo_dir).await
+    }
+
+    async fn is_ignore_case(&self, repo_dir: &Path) -> bool {
+        git::is_ignore_case(repo_dir).await
+    }
+
+    async fn get_head_commit(&self, repo_dir: &Path, rev: Option<&str>) -> Result<String, ()> {
+        git::get_head_commit(repo_dir, rev).await
+    }
+
+    async fn is_empty_repo(&self, repo_dir: &Path) -> bool {
+        git::is_unborn_head(repo_dir).await
+    }
+
+    async fn get_shallow_commits(&self, repo_dir: &Path) -> HashSet<String> {
+        git::get_shallow_commits(repo_dir).await
+    }
+
+    async fn get_partial_clone_filter(&self, repo_dir: &Path) -> Option<String> {
+        git::get_partial_clone_filter(repo_dir).await
+    }
+
+    async fn prefetch_missing_blobs(&self, repo_dir: &Path, rev: Option<&str>, blob_sha1s: &[&str]) {
+        git::prefetch_missing_blobs(repo_dir, rev, blob_sha1s).await
+    }
+
+    async fn get_sparse_checkout_exclusions(&self, repo_dir: &Path) -> Option<HashSet<String>> {
+        git::get_sparse_checkout_exclusions(repo_dir).await
+    }
+}
+
+/// Returns the VCS implementation for the repo in `repo_dir`. Mercurial is only chosen if there is `.hg` and no `.git`,
+/// which is a folder in a regular checkout and a file in worktrees and submodules.
+/// Defaults to Git otherwise, including folders with no repo at all.
+pub fn detect(repo_dir: &Path) -> Box<dyn Vcs> {
+    if !repo_dir.join(Config::GIT_FOLDER_NAME).exists() && repo_dir.join(Config::HG_FOLDER_NAME).exists() {
+        debug!("Mercurial repo detected in {}", repo_dir.to_string_lossy());
+        Box::new(Mercurial)
+    } else {
+        Box::new(Git)
+    }
+}